@@ -0,0 +1,18 @@
+//! 构建脚本 / Build script
+//!
+//! 启用 `grpc` 特性时从 `proto/workflow.proto` 生成 gRPC 服务代码，使用内置的 protoc。
+//! With the `grpc` feature, generates the gRPC service code from `proto/workflow.proto` using a vendored protoc.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/workflow.proto");
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc unavailable");
+        let mut config = tonic_prost_build::Config::new();
+        config.protoc_executable(protoc);
+        tonic_prost_build::configure()
+            .compile_with_config(config, &["proto/workflow.proto"], &["proto"])
+            .expect("failed to compile proto/workflow.proto");
+    }
+}
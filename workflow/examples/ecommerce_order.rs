@@ -11,140 +11,10 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-// TODO: 当temporal模块完全实现后，使用以下导入
-// use workflow::temporal::*;
-
-// 临时使用声明（示例代码）
-use std::future::Future;
-use std::pin::Pin;
-
-// 临时类型定义（实际应该从workflow::temporal导入）
-#[allow(dead_code)]
-type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
-
-// 这些类型应该从workflow::temporal模块导入
-// 为了示例完整性，这里提供占位符定义
-#[allow(dead_code)]
-struct WorkflowContext;
-#[allow(dead_code)]
-struct ActivityContext;
-#[allow(dead_code)]
-struct ActivityOptions {
-    start_to_close_timeout: Option<Duration>,
-    retry_policy: Option<RetryPolicy>,
-}
-#[allow(dead_code)]
-impl Default for ActivityOptions {
-    fn default() -> Self {
-        Self {
-            start_to_close_timeout: None,
-            retry_policy: None,
-        }
-    }
-}
-#[allow(dead_code)]
-struct RetryPolicy {
-    max_attempts: Option<u32>,
-}
-#[allow(dead_code)]
-impl Default for RetryPolicy {
-    fn default() -> Self {
-        Self {
-            max_attempts: Some(3),
-        }
-    }
-}
-#[allow(dead_code)]
-#[derive(Debug)]
-struct WorkflowError;
-#[allow(dead_code)]
-#[derive(Debug)]
-struct ActivityError;
-#[allow(dead_code)]
-trait Workflow {
-    type Input;
-    type Output;
-    fn name() -> &'static str;
-    fn execute(ctx: WorkflowContext, input: Self::Input) -> impl Future<Output = Result<Self::Output, WorkflowError>> + Send;
-}
-#[allow(dead_code)]
-trait Activity {
-    type Input;
-    type Output;
-    fn name() -> &'static str;
-    fn execute(ctx: ActivityContext, input: Self::Input) -> impl Future<Output = Result<Self::Output, ActivityError>> + Send;
-}
-#[allow(dead_code)]
-impl WorkflowContext {
-    async fn execute_activity<A: Activity>(
-        &self,
-        _input: A::Input,
-        _options: ActivityOptions,
-    ) -> Result<A::Output, WorkflowError> {
-        unimplemented!("This is a demonstration example")
-    }
-}
-#[allow(dead_code)]
-impl ActivityContext {
-    async fn record_heartbeat(&self, _details: serde_json::Value) {
-        // Heartbeat implementation
-    }
-}
-#[allow(dead_code)]
-struct WorkflowWorker;
-#[allow(dead_code)]
-struct WorkerConfig;
-#[allow(dead_code)]
-struct WorkerConfigBuilder {
-    _task_queue: String,
-    _max_concurrent_workflow_tasks: usize,
-    _max_concurrent_activity_tasks: usize,
-    _tags: Vec<(String, String)>,
-}
-#[allow(dead_code)]
-impl WorkerConfig {
-    fn builder() -> WorkerConfigBuilder {
-        WorkerConfigBuilder {
-            _task_queue: String::new(),
-            _max_concurrent_workflow_tasks: 10,
-            _max_concurrent_activity_tasks: 50,
-            _tags: Vec::new(),
-        }
-    }
-}
-#[allow(dead_code)]
-impl WorkerConfigBuilder {
-    fn task_queue(mut self, queue: &str) -> Self {
-        self._task_queue = queue.to_string();
-        self
-    }
-    fn max_concurrent_workflow_tasks(mut self, max: usize) -> Self {
-        self._max_concurrent_workflow_tasks = max;
-        self
-    }
-    fn max_concurrent_activity_tasks(mut self, max: usize) -> Self {
-        self._max_concurrent_activity_tasks = max;
-        self
-    }
-    fn tag(mut self, key: &str, value: &str) -> Self {
-        self._tags.push((key.to_string(), value.to_string()));
-        self
-    }
-    fn build(self) -> WorkerConfig {
-        WorkerConfig
-    }
-}
-#[allow(dead_code)]
-impl WorkflowWorker {
-    fn new(_config: WorkerConfig) -> Self {
-        Self
-    }
-    async fn register_workflow<W: Workflow>(&self) {}
-    async fn register_activity<A: Activity>(&self) {}
-    async fn run(self) -> Result<(), Box<dyn std::error::Error>> {
-        Ok(())
-    }
-}
+use workflow::temporal::{
+    Activity, ActivityContext, ActivityError, ActivityOptions, RetryPolicy, StartWorkflowOptions, WorkerConfig,
+    Workflow, WorkflowContext, WorkflowError, WorkflowId, WorkflowWorker,
+};
 
 // ============================================================================
 // 数据模型
@@ -299,9 +169,9 @@ impl Activity for ReserveInventoryActivity {
         tokio::time::sleep(Duration::from_millis(500)).await;
         
         // 发送心跳
-        ctx.record_heartbeat(serde_json::json!({
+        ctx.heartbeat_with_details(serde_json::json!({
             "progress": "checking_inventory"
-        })).await;
+        })).await?;
         
         // 检查每个商品的库存
         for item in &input.items {
@@ -365,9 +235,9 @@ impl Activity for ProcessPaymentActivity {
         tokio::time::sleep(Duration::from_secs(2)).await;
         
         // 发送心跳
-        ctx.record_heartbeat(serde_json::json!({
+        ctx.heartbeat_with_details(serde_json::json!({
             "progress": "contacting_payment_gateway"
-        })).await;
+        })).await?;
         
         // 实际应该调用支付网关API
         let payment_id = format!("PAY-{}", uuid::Uuid::new_v4());
@@ -456,7 +326,8 @@ impl Activity for SendNotificationActivity {
             input.user_id
         );
         
-        // 实际应该调用通知服务
+        // 实际应该调用通知服务；启用 `notifications` 特性后可改用
+        // `temporal::activities::notifications` 中的 SendEmailActivity / SendSmsActivity
         println!("📧 Notification: {}", input.message);
         
         Ok(())
@@ -560,9 +431,9 @@ impl Workflow for OrderProcessingWorkflow {
             completed_at: None,
         };
         
-        // 1. 验证订单
+        // 1. 验证订单（本地Activity：在Worker进程内直接执行，不经过任务队列）
         tracing::info!("Step 1: Validating order");
-        let validation = ctx.execute_activity::<ValidateOrderActivity>(
+        let validation = ctx.execute_local_activity::<ValidateOrderActivity>(
             ValidateOrderInput {
                 order: order.clone(),
             },
@@ -580,36 +451,38 @@ impl Workflow for OrderProcessingWorkflow {
             return Ok(result);
         }
         
-        // 2. 预留库存
+        // 2-4. 预留库存、支付、发货：每一步成功后登记补偿，失败时按相反顺序自动补偿（Saga模式）
+        let mut saga = ctx.saga();
+        let retry_three_times = Some(RetryPolicy {
+            max_attempts: 3,
+            ..Default::default()
+        });
+
         tracing::info!("Step 2: Reserving inventory");
-        let reservation = match ctx.execute_activity::<ReserveInventoryActivity>(
+        let reservation = saga.step::<ReserveInventoryActivity, ReleaseInventoryActivity>(
             ReserveInventoryInput {
                 order_id: order_id.clone(),
                 items: order.items.clone(),
             },
             ActivityOptions {
                 start_to_close_timeout: Some(Duration::from_secs(60)),
-                retry_policy: Some(RetryPolicy {
-                    max_attempts: Some(3),
-                    ..Default::default()
-                }),
+                retry_policy: retry_three_times.clone(),
                 ..Default::default()
             },
-        ).await {
-            Ok(res) => res,
-            Err(e) => {
-                result.status = OrderStatus::Failed {
-                    reason: format!("Inventory reservation failed: {:?}", e),
-                };
-                return Ok(result);
-            }
-        };
-        
+            |reservation| ReleaseInventoryInput {
+                reservation_id: reservation.reservation_id.clone(),
+            },
+        ).await;
+        if let Err(e) = reservation {
+            result.status = OrderStatus::Failed {
+                reason: format!("Inventory reservation failed: {}", e),
+            };
+            return Ok(result);
+        }
         result.status = OrderStatus::InventoryReserved;
         
-        // 3. 处理支付
         tracing::info!("Step 3: Processing payment");
-        let payment = match ctx.execute_activity::<ProcessPaymentActivity>(
+        let payment = match saga.step::<ProcessPaymentActivity, RefundPaymentActivity>(
             ProcessPaymentInput {
                 order_id: order_id.clone(),
                 amount: order.total_amount,
@@ -617,36 +490,25 @@ impl Workflow for OrderProcessingWorkflow {
             },
             ActivityOptions {
                 start_to_close_timeout: Some(Duration::from_secs(120)),
-                retry_policy: Some(RetryPolicy {
-                    max_attempts: Some(3),
-                    ..Default::default()
-                }),
+                retry_policy: retry_three_times,
                 ..Default::default()
             },
+            |payment| RefundPaymentInput {
+                payment_id: payment.payment_id.clone(),
+                amount: order.total_amount,
+            },
         ).await {
-            Ok(pay) => pay,
+            Ok(payment) => payment,
             Err(e) => {
-                // 支付失败，需要补偿：释放库存
-                tracing::warn!("Payment failed, initiating compensation");
-                
-                let _ = ctx.execute_activity::<ReleaseInventoryActivity>(
-                    ReleaseInventoryInput {
-                        reservation_id: reservation.reservation_id,
-                    },
-                    ActivityOptions::default(),
-                ).await;
-                
                 result.status = OrderStatus::Failed {
-                    reason: format!("Payment failed: {:?}", e),
+                    reason: format!("Payment failed: {}", e),
                 };
                 return Ok(result);
             }
         };
-        
         result.payment_id = Some(payment.payment_id.clone());
         result.status = OrderStatus::PaymentCompleted;
         
-        // 4. 创建发货单
         tracing::info!("Step 4: Creating shipment");
         let shipment = match ctx.execute_activity::<CreateShipmentActivity>(
             CreateShipmentInput {
@@ -660,28 +522,13 @@ impl Workflow for OrderProcessingWorkflow {
                 ..Default::default()
             },
         ).await {
-            Ok(ship) => ship,
+            Ok(shipment) => shipment,
             Err(e) => {
-                // 发货失败，需要补偿：退款 + 释放库存
+                // 发货失败：退款并释放库存
                 tracing::warn!("Shipment creation failed, initiating compensation");
-                
-                let _ = ctx.execute_activity::<RefundPaymentActivity>(
-                    RefundPaymentInput {
-                        payment_id: payment.payment_id,
-                        amount: order.total_amount,
-                    },
-                    ActivityOptions::default(),
-                ).await;
-                
-                let _ = ctx.execute_activity::<ReleaseInventoryActivity>(
-                    ReleaseInventoryInput {
-                        reservation_id: reservation.reservation_id,
-                    },
-                    ActivityOptions::default(),
-                ).await;
-                
+                saga.compensate().await?;
                 result.status = OrderStatus::Failed {
-                    reason: format!("Shipment creation failed: {:?}", e),
+                    reason: format!("Shipment creation failed: {}", e),
                 };
                 return Ok(result);
             }
@@ -740,25 +587,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let worker = WorkflowWorker::new(worker_config);
     
     // 注册Workflow
-    worker.register_workflow::<OrderProcessingWorkflow>().await;
+    worker.register_workflow::<OrderProcessingWorkflow>();
     
-    // 注册Activities
-    worker.register_activity::<ValidateOrderActivity>().await;
-    worker.register_activity::<ReserveInventoryActivity>().await;
-    worker.register_activity::<ProcessPaymentActivity>().await;
-    worker.register_activity::<CreateShipmentActivity>().await;
-    worker.register_activity::<SendNotificationActivity>().await;
+    // 注册Activities（ValidateOrder 作为本地Activity执行，无需注册）
+    worker.register_activity::<ReserveInventoryActivity>();
+    worker.register_activity::<ProcessPaymentActivity>();
+    worker.register_activity::<CreateShipmentActivity>();
+    worker.register_activity::<SendNotificationActivity>();
     
     // 注册补偿Activities
-    worker.register_activity::<ReleaseInventoryActivity>().await;
-    worker.register_activity::<RefundPaymentActivity>().await;
+    worker.register_activity::<ReleaseInventoryActivity>();
+    worker.register_activity::<RefundPaymentActivity>();
     
     tracing::info!("✅ Worker registered all workflows and activities");
     
-    // 在另一个任务中启动一个测试订单（模拟客户端）
-    tokio::spawn(async {
-        tokio::time::sleep(Duration::from_secs(2)).await;
-        
+    // 在另一个任务中启动一个测试订单（模拟客户端），完成后关闭Worker
+    let client = worker.client();
+    let shutdown = worker.shutdown_handle();
+    tokio::spawn(async move {
         tracing::info!("📦 Creating test order...");
         
         // 创建测试订单
@@ -796,9 +642,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         
         tracing::info!("Order created: {}", test_order.order_id);
         
-        // 实际应该通过WorkflowClient启动工作流
-        // let client = WorkflowClient::new(...);
-        // let result = client.start_workflow::<OrderProcessingWorkflow>(test_order).await;
+        let options = StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new(test_order.order_id.clone())),
+            task_queue: "order-processing".to_string(),
+            ..Default::default()
+        };
+        match client.start_workflow::<OrderProcessingWorkflow>(test_order, options).await {
+            Ok(handle) => match handle.result().await {
+                Ok(result) => tracing::info!("✅ Order finished: {:?}", result),
+                Err(e) => tracing::error!("❌ Order workflow failed: {}", e),
+            },
+            Err(e) => tracing::error!("❌ Failed to start order workflow: {}", e),
+        }
+        shutdown.shutdown(Duration::from_secs(10));
     });
     
     // 运行Worker
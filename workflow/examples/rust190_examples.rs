@@ -3,6 +3,9 @@
 //! 本示例展示了如何使用 Rust 1.90 的新特性和改进
 //! This example demonstrates how to use new features and improvements in Rust 1.90
 
+// 演示旧引擎，新代码使用 workflow::model / Demonstrates the legacy engines, new code uses workflow::model
+#![allow(deprecated)]
+
 use std::time::Duration;
 use workflow::rust190::*;
 
@@ -0,0 +1,406 @@
+//! 审计日志模块 / Audit Log Module
+//! 记录每个由外部发起的操作（启动、信号、取消、终止、查询）：调用方、时间、工作流 ID 与负载摘要
+//! Records every externally initiated action (start, signal, cancel, terminate, query) with the principal, time,
+//! workflow ID and a digest of the payload
+//!
+//! 条目写入可替换的 [`AuditSink`]：内存、JSON Lines 文件、标准输出，或启用 `sqlite` 特性时的数据库。
+//! 负载本身不落盘，只保存其 SHA-256 摘要。
+//! Entries go to a pluggable [`AuditSink`]: memory, a JSON Lines file, stdout, or a database with the `sqlite`
+//! feature. Payloads are never stored, only their SHA-256 digest.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteAuditSink;
+
+/// 未认证调用方的名称 / Principal recorded for unauthenticated callers
+pub const ANONYMOUS: &str = "anonymous";
+
+/// 内存审计日志的默认容量 / Default capacity of the in-memory audit log
+pub const DEFAULT_MEMORY_CAPACITY: usize = 10_000;
+
+/// 单页最多条目数 / Most entries returned in one page
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// 被审计的操作 / Audited action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Start,
+    Signal,
+    Cancel,
+    Terminate,
+    Query,
+    Update,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::Start => "start",
+            AuditAction::Signal => "signal",
+            AuditAction::Cancel => "cancel",
+            AuditAction::Terminate => "terminate",
+            AuditAction::Query => "query",
+            AuditAction::Update => "update",
+        }
+    }
+}
+
+impl std::str::FromStr for AuditAction {
+    type Err = AuditError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Self::Start, Self::Signal, Self::Cancel, Self::Terminate, Self::Query, Self::Update]
+            .into_iter()
+            .find(|action| action.as_str() == s)
+            .ok_or_else(|| AuditError::InvalidConfig(format!("unknown audit action: {}", s)))
+    }
+}
+
+/// 审计条目 / Audit entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct AuditEntry {
+    /// 由接收端分配的递增序号 / Increasing sequence number assigned by the sink
+    pub id: u64,
+    pub timestamp: DateTime<Utc>,
+    /// 调用方 ID / Principal ID
+    pub principal: String,
+    pub action: AuditAction,
+    pub workflow_id: String,
+    /// 工作流类型、信号名或查询名 / Workflow type, signal name or query name
+    pub target: Option<String>,
+    /// 负载的 SHA-256 十六进制摘要 / Hex SHA-256 digest of the payload
+    pub payload_digest: Option<String>,
+    /// 入口，如 `http` 或 `grpc` / Entry point, e.g. `http` or `grpc`
+    pub source: String,
+    pub succeeded: bool,
+}
+
+impl AuditEntry {
+    /// 匿名、成功的新条目 / New anonymous, successful entry
+    pub fn new(action: AuditAction, workflow_id: impl Into<String>) -> Self {
+        Self {
+            id: 0,
+            timestamp: Utc::now(),
+            principal: ANONYMOUS.to_string(),
+            action,
+            workflow_id: workflow_id.into(),
+            target: None,
+            payload_digest: None,
+            source: String::new(),
+            succeeded: true,
+        }
+    }
+
+    pub fn principal(mut self, principal: impl Into<String>) -> Self {
+        self.principal = principal.into();
+        self
+    }
+
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// 记录负载摘要 / Record the digest of a payload
+    pub fn payload(mut self, payload: &serde_json::Value) -> Self {
+        self.payload_digest = Some(digest(payload));
+        self
+    }
+
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = source.into();
+        self
+    }
+
+    pub fn succeeded(mut self, succeeded: bool) -> Self {
+        self.succeeded = succeeded;
+        self
+    }
+}
+
+/// JSON 负载的 SHA-256 十六进制摘要 / Hex SHA-256 digest of a JSON payload
+pub fn digest(payload: &serde_json::Value) -> String {
+    let bytes = serde_json::to_vec(payload).unwrap_or_default();
+    Sha256::digest(&bytes).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// 审计错误 / Audit error
+#[derive(Debug, thiserror::Error)]
+pub enum AuditError {
+    #[error("审计 IO 错误 / Audit IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("审计数据库错误 / Audit database error: {0}")]
+    Database(String),
+    #[error("审计序列化错误 / Audit serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("该审计接收端不支持读取 / This audit sink cannot be read back: {0}")]
+    Unsupported(&'static str),
+    #[error("无效的审计配置 / Invalid audit configuration: {0}")]
+    InvalidConfig(String),
+}
+
+/// 审计接收端 / Audit sink
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    /// 追加条目并返回分配的序号 / Append an entry and return the sequence number assigned to it
+    async fn append(&self, entry: AuditEntry) -> Result<u64, AuditError>;
+
+    /// 按序号升序返回 `after` 之后最多 `limit` 条 / Up to `limit` entries after `after`, in increasing order
+    async fn page(&self, after: Option<u64>, limit: usize) -> Result<Vec<AuditEntry>, AuditError>;
+}
+
+/// 内存接收端，超出容量时丢弃最旧条目 / In-memory sink dropping the oldest entries beyond its capacity
+pub struct MemoryAuditSink {
+    capacity: usize,
+    entries: Mutex<(VecDeque<AuditEntry>, u64)>,
+}
+
+impl MemoryAuditSink {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Mutex::new((VecDeque::new(), 0)),
+        }
+    }
+}
+
+impl Default for MemoryAuditSink {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_CAPACITY)
+    }
+}
+
+#[async_trait]
+impl AuditSink for MemoryAuditSink {
+    async fn append(&self, mut entry: AuditEntry) -> Result<u64, AuditError> {
+        let mut guard = self.entries.lock();
+        let (entries, last_id) = &mut *guard;
+        *last_id += 1;
+        entry.id = *last_id;
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+        Ok(*last_id)
+    }
+
+    async fn page(&self, after: Option<u64>, limit: usize) -> Result<Vec<AuditEntry>, AuditError> {
+        let guard = self.entries.lock();
+        Ok(guard
+            .0
+            .iter()
+            .filter(|entry| after.is_none_or(|after| entry.id > after))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+}
+
+/// JSON Lines 文件接收端，每行一个条目 / JSON Lines file sink, one entry per line
+pub struct JsonFileAuditSink {
+    path: PathBuf,
+    file: Mutex<(File, u64)>,
+}
+
+impl JsonFileAuditSink {
+    /// 以追加方式打开 `path`，序号接续文件中已有的条目 / Open `path` for appending, continuing the numbering of its entries
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AuditError> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).read(true).open(&path)?;
+        let last_id = read_entries(&path)?.last().map_or(0, |entry| entry.id);
+        Ok(Self {
+            path,
+            file: Mutex::new((file, last_id)),
+        })
+    }
+}
+
+fn read_entries(path: &Path) -> Result<Vec<AuditEntry>, AuditError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            entries.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(entries)
+}
+
+#[async_trait]
+impl AuditSink for JsonFileAuditSink {
+    async fn append(&self, mut entry: AuditEntry) -> Result<u64, AuditError> {
+        let mut guard = self.file.lock();
+        let (file, last_id) = &mut *guard;
+        entry.id = *last_id + 1;
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+        file.flush()?;
+        *last_id = entry.id;
+        Ok(entry.id)
+    }
+
+    async fn page(&self, after: Option<u64>, limit: usize) -> Result<Vec<AuditEntry>, AuditError> {
+        // 读取期间持锁，避免读到写了一半的行 / hold the lock so a half-written line is never read
+        let _guard = self.file.lock();
+        Ok(read_entries(&self.path)?
+            .into_iter()
+            .filter(|entry| after.is_none_or(|after| entry.id > after))
+            .take(limit)
+            .collect())
+    }
+}
+
+/// 以 JSON 写到标准输出，供日志采集器收集 / Writes JSON to stdout for a log collector to pick up
+#[derive(Default)]
+pub struct StdoutAuditSink {
+    last_id: Mutex<u64>,
+}
+
+#[async_trait]
+impl AuditSink for StdoutAuditSink {
+    async fn append(&self, mut entry: AuditEntry) -> Result<u64, AuditError> {
+        let mut last_id = self.last_id.lock();
+        *last_id += 1;
+        entry.id = *last_id;
+        println!("{}", serde_json::to_string(&entry)?);
+        Ok(entry.id)
+    }
+
+    async fn page(&self, _after: Option<u64>, _limit: usize) -> Result<Vec<AuditEntry>, AuditError> {
+        Err(AuditError::Unsupported("stdout"))
+    }
+}
+
+/// 一页审计条目 / One page of audit entries
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    /// 下一页的 `after` 参数；没有更多条目时为空 / `after` for the next page, absent when there are no more entries
+    pub next_after: Option<u64>,
+}
+
+/// 审计日志 / Audit log
+#[derive(Clone)]
+pub struct AuditLog {
+    sink: Arc<dyn AuditSink>,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::new(MemoryAuditSink::default())
+    }
+}
+
+impl AuditLog {
+    pub fn new(sink: impl AuditSink + 'static) -> Self {
+        Self { sink: Arc::new(sink) }
+    }
+
+    /// 按 `WORKFLOW_AUDIT_LOG` 选择接收端 / Choose the sink from `WORKFLOW_AUDIT_LOG`
+    ///
+    /// 取值为 `memory`（缺省）、`stdout`、`file:<路径>`，或启用 `sqlite` 特性时的 `sqlite:<URL>`。
+    /// One of `memory` (the default), `stdout`, `file:<path>`, or `sqlite:<url>` with the `sqlite` feature.
+    pub async fn from_env() -> Result<Self, AuditError> {
+        let spec = std::env::var("WORKFLOW_AUDIT_LOG").unwrap_or_else(|_| "memory".to_string());
+        Self::from_spec(&spec).await
+    }
+
+    /// 按 [`from_env`](Self::from_env) 的取值格式选择接收端 / Choose the sink from a value in the format of
+    /// [`from_env`](Self::from_env)
+    pub async fn from_spec(spec: &str) -> Result<Self, AuditError> {
+        match spec.split_once(':') {
+            None if spec == "memory" => Ok(Self::default()),
+            None if spec == "stdout" => Ok(Self::new(StdoutAuditSink::default())),
+            Some(("file", path)) => Ok(Self::new(JsonFileAuditSink::open(path)?)),
+            #[cfg(feature = "sqlite")]
+            Some(("sqlite", _)) => Ok(Self::new(SqliteAuditSink::connect(spec).await?)),
+            _ => Err(AuditError::InvalidConfig(format!("unsupported WORKFLOW_AUDIT_LOG: {}", spec))),
+        }
+    }
+
+    /// 写入条目；失败只记录日志，不影响被审计的操作 / Write an entry; failures are logged and never fail the audited action
+    pub async fn record(&self, entry: AuditEntry) {
+        let action = entry.action.as_str();
+        match self.sink.append(entry).await {
+            Ok(_) => metrics::counter!("audit_entries_total", "action" => action).increment(1),
+            Err(e) => {
+                metrics::counter!("audit_write_failures_total").increment(1);
+                tracing::error!(action, error = %e, "failed to write audit entry");
+            }
+        }
+    }
+
+    /// 读取 `after` 之后的一页，`limit` 不超过 [`MAX_PAGE_SIZE`] / Read the page after `after`, `limit` capped at [`MAX_PAGE_SIZE`]
+    pub async fn page(&self, after: Option<u64>, limit: usize) -> Result<AuditPage, AuditError> {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let entries = self.sink.page(after, limit).await?;
+        let next_after = (entries.len() == limit).then(|| entries.last().map(|entry| entry.id)).flatten();
+        Ok(AuditPage { entries, next_after })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(action: AuditAction) -> AuditEntry {
+        AuditEntry::new(action, "order-1")
+            .principal("ops")
+            .payload(&serde_json::json!({ "amount": 3 }))
+            .source("http")
+    }
+
+    #[tokio::test]
+    async fn test_memory_log_pages_in_order() {
+        let log = AuditLog::new(MemoryAuditSink::new(3));
+        for action in [AuditAction::Start, AuditAction::Signal, AuditAction::Query, AuditAction::Cancel] {
+            log.record(entry(action)).await;
+        }
+        // 最旧的条目已被丢弃 / the oldest entry was dropped
+        let first = log.page(None, 2).await.unwrap();
+        assert_eq!(first.entries.iter().map(|e| e.id).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(first.next_after, Some(3));
+        let second = log.page(first.next_after, 2).await.unwrap();
+        assert_eq!(second.entries[0].action, AuditAction::Cancel);
+        assert_eq!(second.next_after, None);
+    }
+
+    #[tokio::test]
+    async fn test_file_sink_continues_numbering_after_reopen() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let sink = JsonFileAuditSink::open(&path).unwrap();
+        assert_eq!(sink.append(entry(AuditAction::Start)).await.unwrap(), 1);
+        drop(sink);
+
+        let log = AuditLog::new(JsonFileAuditSink::open(&path).unwrap());
+        log.record(entry(AuditAction::Signal)).await;
+        let page = log.page(Some(1), 10).await.unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].id, 2);
+        assert_eq!(page.entries[0].principal, "ops");
+        assert_eq!(page.entries[0].payload_digest.as_deref(), Some(digest(&serde_json::json!({ "amount": 3 })).as_str()));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stdout_sink_cannot_be_paged() {
+        let log = AuditLog::new(StdoutAuditSink::default());
+        assert!(matches!(log.page(None, 10).await, Err(AuditError::Unsupported(_))));
+    }
+}
@@ -0,0 +1,123 @@
+//! SQLite 审计接收端 / SQLite audit sink
+
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+
+use super::{AuditAction, AuditEntry, AuditError, AuditSink};
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    principal TEXT NOT NULL,
+    action TEXT NOT NULL,
+    workflow_id TEXT NOT NULL,
+    target TEXT,
+    payload_digest TEXT,
+    source TEXT NOT NULL,
+    succeeded INTEGER NOT NULL
+)";
+
+/// 写入 `audit_log` 表的接收端 / Sink writing to the `audit_log` table
+#[derive(Clone)]
+pub struct SqliteAuditSink {
+    pool: SqlitePool,
+}
+
+fn database_error(e: sqlx::Error) -> AuditError {
+    AuditError::Database(e.to_string())
+}
+
+impl SqliteAuditSink {
+    /// 打开（必要时创建）`url` 处的数据库 / Open (creating if needed) the database at `url`, e.g. `sqlite://audit.db`
+    pub async fn connect(url: &str) -> Result<Self, AuditError> {
+        let options = SqliteConnectOptions::from_str(url)
+            .map_err(database_error)?
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new().connect_with(options).await.map_err(database_error)?;
+        Self::with_pool(pool).await
+    }
+
+    /// 使用已有的连接池，例如 [`SqliteStorage::pool`](crate::temporal::SqliteStorage::pool) /
+    /// Use an existing pool, e.g. [`SqliteStorage::pool`](crate::temporal::SqliteStorage::pool)
+    pub async fn with_pool(pool: SqlitePool) -> Result<Self, AuditError> {
+        sqlx::query(SCHEMA).execute(&pool).await.map_err(database_error)?;
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait]
+impl AuditSink for SqliteAuditSink {
+    async fn append(&self, entry: AuditEntry) -> Result<u64, AuditError> {
+        let result = sqlx::query(
+            "INSERT INTO audit_log (timestamp, principal, action, workflow_id, target, payload_digest, source, succeeded)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )
+        .bind(entry.timestamp.to_rfc3339())
+        .bind(&entry.principal)
+        .bind(entry.action.as_str())
+        .bind(&entry.workflow_id)
+        .bind(&entry.target)
+        .bind(&entry.payload_digest)
+        .bind(&entry.source)
+        .bind(entry.succeeded)
+        .execute(&self.pool)
+        .await
+        .map_err(database_error)?;
+        Ok(result.last_insert_rowid() as u64)
+    }
+
+    async fn page(&self, after: Option<u64>, limit: usize) -> Result<Vec<AuditEntry>, AuditError> {
+        let rows = sqlx::query("SELECT * FROM audit_log WHERE id > ?1 ORDER BY id LIMIT ?2")
+            .bind(after.unwrap_or(0) as i64)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(database_error)?;
+        rows.iter()
+            .map(|row| {
+                let timestamp: String = row.try_get("timestamp").map_err(database_error)?;
+                let action: String = row.try_get("action").map_err(database_error)?;
+                Ok(AuditEntry {
+                    id: row.try_get::<i64, _>("id").map_err(database_error)? as u64,
+                    timestamp: DateTime::parse_from_rfc3339(&timestamp)
+                        .map_err(|e| AuditError::Database(e.to_string()))?
+                        .with_timezone(&Utc),
+                    principal: row.try_get("principal").map_err(database_error)?,
+                    action: AuditAction::from_str(&action)?,
+                    workflow_id: row.try_get("workflow_id").map_err(database_error)?,
+                    target: row.try_get("target").map_err(database_error)?,
+                    payload_digest: row.try_get("payload_digest").map_err(database_error)?,
+                    source: row.try_get("source").map_err(database_error)?,
+                    succeeded: row.try_get("succeeded").map_err(database_error)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::AuditLog;
+
+    #[tokio::test]
+    async fn test_sqlite_sink_round_trips_entries() {
+        // 每个连接各有一个内存库，故只用一个连接 / every connection gets its own in-memory database, so use just one
+        let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        let sink = SqliteAuditSink::with_pool(pool).await.unwrap();
+        let log = AuditLog::new(sink);
+        log.record(AuditEntry::new(AuditAction::Start, "order-1").target("order").source("grpc")).await;
+        log.record(AuditEntry::new(AuditAction::Cancel, "order-1").succeeded(false)).await;
+
+        let page = log.page(None, 10).await.unwrap();
+        assert_eq!(page.entries.len(), 2);
+        assert_eq!(page.entries[0].target.as_deref(), Some("order"));
+        assert_eq!(page.entries[1].action, AuditAction::Cancel);
+        assert!(!page.entries[1].succeeded);
+        assert!(log.page(Some(2), 10).await.unwrap().entries.is_empty());
+    }
+}
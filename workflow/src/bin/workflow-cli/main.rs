@@ -0,0 +1,518 @@
+//! 工作流命令行客户端 / Workflow command-line client
+//!
+//! 通过 HTTP API 启动、查看、发送信号、查询与取消工作流，读取并回放事件历史，校验声明式工作流规范，
+//! 使运维人员无需为简单操作编写 Rust 程序；`new` 生成新的工作者项目。结果以表格（缺省）或 JSON 输出。
+//! Starts, describes, signals, queries and cancels workflows, reads and replays their event history and validates
+//! declarative workflow specs through the HTTP API, so operators need not write Rust programs for simple operations;
+//! `new` generates a new worker project. Results are printed as a table (default) or as JSON.
+//!
+//! ```text
+//! workflow-cli --endpoint http://localhost:8080 start order --id order-7 --input '{"amount": 100}'
+//! workflow-cli signal order-7 approve --input true
+//! workflow-cli --output json describe order-7
+//! workflow-cli replay --spec pipelines/order.yaml --workflow-id order-7
+//! workflow-cli validate-dsl pipelines/*.yaml
+//! workflow-cli new order-service --features sqlite
+//! ```
+
+mod scaffold;
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde_json::{Value, json};
+
+use workflow::dsl::{DynamicWorkflow, Validator};
+use workflow::temporal::{HistoryExport, HistoryFormat};
+use workflow::testing::WorkflowReplayer;
+
+use scaffold::Scaffold;
+
+/// 单页读取的历史事件数 / History events read per page
+const HISTORY_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Parser)]
+#[command(name = "workflow-cli", version, about = "Operate workflows through the HTTP API")]
+struct Cli {
+    /// 服务地址 / Server address
+    #[arg(long, env = "WORKFLOW_ENDPOINT", default_value = "http://localhost:8080", global = true)]
+    endpoint: String,
+    /// 以 `X-API-Key` 发送的 API Key / API key sent as `X-API-Key`
+    #[arg(long, env = "WORKFLOW_API_KEY", hide_env_values = true, global = true)]
+    api_key: Option<String>,
+    /// 以 `Authorization: Bearer` 发送的令牌 / Token sent as `Authorization: Bearer`
+    #[arg(long, env = "WORKFLOW_TOKEN", hide_env_values = true, global = true)]
+    token: Option<String>,
+    #[arg(long, short, value_enum, default_value_t = Output::Table, global = true)]
+    output: Output,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 启动工作流 / Start a workflow
+    Start {
+        workflow_type: String,
+        /// 缺省时由服务生成 / Generated by the server when absent
+        #[arg(long)]
+        id: Option<String>,
+        /// JSON 输入 / JSON input
+        #[arg(long, default_value = "null", value_parser = parse_json)]
+        input: Value,
+        #[arg(long)]
+        task_queue: Option<String>,
+        /// 1（最先）到 5（最后），缺省为 3 / 1 (first) to 5 (last), defaults to 3
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=5))]
+        priority: Option<u8>,
+    },
+    /// 向工作流发送信号 / Signal a workflow
+    Signal {
+        workflow_id: String,
+        name: String,
+        /// JSON 负载 / JSON payload
+        #[arg(long, default_value = "null", value_parser = parse_json)]
+        input: Value,
+    },
+    /// 查询运行中的工作流 / Query a running workflow
+    Query { workflow_id: String, name: String },
+    /// 请求取消工作流 / Request cancellation of a workflow
+    Cancel { workflow_id: String },
+    /// 最新运行的状态与结果 / Status and result of the latest run
+    Describe { workflow_id: String },
+    /// 最新运行的事件历史 / Event history of the latest run
+    History { workflow_id: String },
+    /// 列出工作流 / List workflows
+    List {
+        #[arg(long = "type")]
+        workflow_type: Option<String>,
+        /// 如 `Running`、`Completed` / E.g. `Running`, `Completed`
+        #[arg(long)]
+        status: Option<String>,
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// 以记录的历史回放声明式工作流，检查其改动是否破坏确定性 /
+    /// Replay a declarative workflow against a recorded history to check that changes keep it deterministic
+    Replay {
+        /// YAML 或 JSON 规范 / YAML or JSON spec
+        #[arg(long)]
+        spec: PathBuf,
+        /// 导出的历史文件，`.pb` 为 protobuf，其余为 JSON / Exported history file, protobuf for `.pb`, JSON otherwise
+        #[arg(long, conflicts_with = "workflow_id", required_unless_present = "workflow_id")]
+        history: Option<PathBuf>,
+        /// 从服务导出此工作流的历史 / Export the history of this workflow from the server
+        #[arg(long)]
+        workflow_id: Option<String>,
+    },
+    /// 校验声明式工作流规范 / Validate declarative workflow specs
+    ValidateDsl {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// 已注册的活动，给出时检查未知活动名 / Registered activities; unknown activity names are reported when given
+        #[arg(long, value_delimiter = ',')]
+        activities: Option<Vec<String>>,
+    },
+    /// 生成新的工作者项目 / Generate a new worker project
+    New {
+        /// 包名 / Package name
+        name: String,
+        /// 项目目录，缺省为 `./<name>` / Project directory, `./<name>` by default
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// 启用的 `workflow` 特性，如 `sqlite,grpc` / `workflow` features to enable, such as `sqlite,grpc`
+        #[arg(long, value_delimiter = ',')]
+        features: Vec<String>,
+        /// 以路径依赖本地的 `workflow` / Depend on a local `workflow` by path
+        #[arg(long)]
+        workflow_path: Option<PathBuf>,
+    },
+}
+
+fn parse_json(text: &str) -> Result<Value, String> {
+    serde_json::from_str(text).map_err(|e| format!("invalid JSON: {}", e))
+}
+
+/// HTTP API 客户端 / Client of the HTTP API
+struct Api {
+    http: reqwest::Client,
+    endpoint: String,
+    api_key: Option<String>,
+    token: Option<String>,
+}
+
+impl Api {
+    fn new(cli: &Cli) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            endpoint: cli.endpoint.trim_end_matches('/').to_string(),
+            api_key: cli.api_key.clone(),
+            token: cli.token.clone(),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let mut request = self.http.request(method, format!("{}/api/v1{}", self.endpoint, path));
+        if let Some(key) = &self.api_key {
+            request = request.header("X-API-Key", key);
+        }
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        request
+    }
+
+    /// 发送请求，非 2xx 时以服务返回的错误信息失败 / Send a request, failing with the server's message on a non-2xx status
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response, String> {
+        let response = request.send().await.map_err(|e| format!("request failed: {}", e))?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        match body["message"].as_str() {
+            Some(message) => Err(format!("{}: {}", status, message)),
+            None => Err(status.to_string()),
+        }
+    }
+
+    async fn json(&self, request: RequestBuilder) -> Result<Value, String> {
+        let response = self.send(request).await?;
+        if response.status() == StatusCode::ACCEPTED || response.status() == StatusCode::NO_CONTENT {
+            return Ok(Value::Null);
+        }
+        response.json().await.map_err(|e| format!("invalid response: {}", e))
+    }
+
+    /// 逐页读取全部事件 / Read every event, page by page
+    async fn history(&self, workflow_id: &str) -> Result<Value, String> {
+        let path = format!("/workflows/{}/history", encode(workflow_id));
+        let mut events = Vec::new();
+        let mut page_token = None;
+        loop {
+            let mut request = self.request(Method::GET, &path).query(&[("limit", HISTORY_PAGE_SIZE)]);
+            if let Some(token) = &page_token {
+                request = request.query(&[("page_token", token)]);
+            }
+            let mut page = self.json(request).await?;
+            if let Some(page_events) = page["events"].as_array_mut() {
+                events.append(page_events);
+            }
+            match page["next_page_token"].as_str() {
+                Some(next) => page_token = Some(next.to_string()),
+                None => return Ok(json!({ "execution": page["execution"].take(), "events": events })),
+            }
+        }
+    }
+}
+
+/// 路径段的百分号编码 / Percent-encode a path segment
+fn encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// 表格单元格中的值 / A value as shown in a table cell
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// 事件类型的变体名；带字段的变体序列化为单键对象 / Variant name of an event type; variants with fields serialize
+/// as single-key objects
+fn event_name(event_type: &Value) -> String {
+    match event_type {
+        Value::Object(fields) if fields.len() == 1 => fields.keys().next().cloned().unwrap_or_default(),
+        other => cell(other),
+    }
+}
+
+/// 列宽对齐的表格 / Table with aligned columns
+fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.chars().count()).collect();
+    for row in rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+    let line = |values: Vec<&str>| {
+        let padded: Vec<String> = values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:<width$}", value, width = *width))
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+    let mut lines = vec![line(headers.to_vec())];
+    lines.extend(rows.iter().map(|row| line(row.iter().map(String::as_str).collect())));
+    lines.join("\n")
+}
+
+/// 键值两列的表格 / Two-column key-value table
+fn fields(value: &Value, keys: &[(&str, &str)]) -> String {
+    let rows: Vec<Vec<String>> = keys
+        .iter()
+        .map(|(label, pointer)| vec![label.to_string(), cell(value.pointer(pointer).unwrap_or(&Value::Null))])
+        .collect();
+    table(&["FIELD", "VALUE"], &rows)
+}
+
+fn render(command: &Command, value: &Value, output: Output) -> String {
+    if output == Output::Json {
+        return serde_json::to_string_pretty(value).unwrap_or_default();
+    }
+    match command {
+        Command::Start { .. } => fields(value, &[("workflow_id", "/workflow_id"), ("run_id", "/run_id")]),
+        Command::Describe { .. } => fields(
+            value,
+            &[
+                ("workflow_id", "/execution/workflow_id"),
+                ("run_id", "/execution/run_id"),
+                ("type", "/workflow_type"),
+                ("task_queue", "/task_queue"),
+                ("status", "/status"),
+                ("started", "/start_time"),
+                ("closed", "/close_time"),
+                ("result", "/result"),
+                ("failure", "/failure"),
+            ],
+        ),
+        Command::History { .. } => {
+            let rows: Vec<Vec<String>> = value["events"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|event| vec![cell(&event["event_id"]), cell(&event["timestamp"]), event_name(&event["event_type"])])
+                .collect();
+            table(&["ID", "TIME", "EVENT"], &rows)
+        }
+        Command::List { .. } => {
+            let rows: Vec<Vec<String>> = value["workflows"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|run| {
+                    vec![
+                        cell(&run["execution"]["workflow_id"]),
+                        cell(&run["workflow_type"]),
+                        cell(&run["status"]),
+                        cell(&run["start_time"]),
+                        cell(&run["close_time"]),
+                    ]
+                })
+                .collect();
+            table(&["WORKFLOW ID", "TYPE", "STATUS", "STARTED", "CLOSED"], &rows)
+        }
+        Command::ValidateDsl { .. } => {
+            let rows: Vec<Vec<String>> = value["files"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .flat_map(|file| {
+                    let diagnostics = file["diagnostics"].as_array().cloned().unwrap_or_default();
+                    if diagnostics.is_empty() {
+                        return vec![vec![cell(&file["file"]), String::new(), String::new(), "ok".to_string()]];
+                    }
+                    diagnostics
+                        .iter()
+                        .map(|diagnostic| {
+                            let position = match (diagnostic["line"].as_u64(), diagnostic["column"].as_u64()) {
+                                (Some(line), Some(column)) => format!("{}:{}", line, column),
+                                _ => String::new(),
+                            };
+                            vec![cell(&file["file"]), position, cell(&diagnostic["severity"]), cell(&diagnostic["message"])]
+                        })
+                        .collect()
+                })
+                .collect();
+            table(&["FILE", "AT", "SEVERITY", "MESSAGE"], &rows)
+        }
+        Command::Replay { .. } => cell(&value["outcome"]),
+        Command::New { .. } => {
+            let rows: Vec<Vec<String>> =
+                value["files"].as_array().into_iter().flatten().map(|file| vec![cell(file)]).collect();
+            format!("{}\n\nrun `cargo test` in {} to try it", table(&["CREATED"], &rows), cell(&value["path"]))
+        }
+        Command::Signal { .. } | Command::Cancel { .. } => "accepted".to_string(),
+        Command::Query { .. } => serde_json::to_string_pretty(value).unwrap_or_default(),
+    }
+}
+
+fn load_export(path: &Path) -> Result<HistoryExport, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let format = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("pb") => HistoryFormat::Protobuf,
+        _ => HistoryFormat::Json,
+    };
+    HistoryExport::decode(format, &bytes).map_err(|e| format!("invalid history {}: {}", path.display(), e))
+}
+
+fn validate(files: &[PathBuf], activities: Option<&[String]>) -> Result<(Value, bool), String> {
+    let mut validator = Validator::new();
+    if let Some(activities) = activities {
+        validator = validator.with_activities(activities.iter().cloned());
+    }
+    let mut valid = true;
+    let mut reports = Vec::new();
+    for file in files {
+        let text = std::fs::read_to_string(file).map_err(|e| format!("failed to read {}: {}", file.display(), e))?;
+        let diagnostics = match file.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => validator.validate_json(&text),
+            _ => validator.validate_yaml(&text),
+        };
+        valid &= !diagnostics.iter().any(|diagnostic| diagnostic.is_error());
+        reports.push(json!({ "file": file.display().to_string(), "diagnostics": diagnostics }));
+    }
+    Ok((json!({ "valid": valid, "files": reports }), valid))
+}
+
+/// 执行子命令；第二项为假时进程以失败退出 / Run the command; the process exits with failure when the flag is false
+async fn run(cli: &Cli) -> Result<(Value, bool), String> {
+    let api = Api::new(cli);
+    let value = match &cli.command {
+        Command::Start { workflow_type, id, input, task_queue, priority } => {
+            let body = json!({
+                "workflow_type": workflow_type,
+                "workflow_id": id,
+                "input": input,
+                "task_queue": task_queue,
+                "priority": priority,
+            });
+            api.json(api.request(Method::POST, "/workflows").json(&body)).await?
+        }
+        Command::Signal { workflow_id, name, input } => {
+            let path = format!("/workflows/{}/signal/{}", encode(workflow_id), encode(name));
+            api.json(api.request(Method::POST, &path).json(input)).await?
+        }
+        Command::Query { workflow_id, name } => {
+            let path = format!("/workflows/{}/query/{}", encode(workflow_id), encode(name));
+            api.json(api.request(Method::POST, &path)).await?
+        }
+        Command::Cancel { workflow_id } => {
+            api.json(api.request(Method::POST, &format!("/workflows/{}/cancel", encode(workflow_id)))).await?
+        }
+        Command::Describe { workflow_id } => {
+            api.json(api.request(Method::GET, &format!("/workflows/{}", encode(workflow_id)))).await?
+        }
+        Command::History { workflow_id } => api.history(workflow_id).await?,
+        Command::List { workflow_type, status, limit } => {
+            let mut query = Vec::new();
+            if let Some(workflow_type) = workflow_type {
+                query.push(("workflow_type", workflow_type.clone()));
+            }
+            if let Some(status) = status {
+                query.push(("status", status.clone()));
+            }
+            if let Some(limit) = limit {
+                query.push(("limit", limit.to_string()));
+            }
+            api.json(api.request(Method::GET, "/workflows").query(&query)).await?
+        }
+        Command::Replay { spec, history, workflow_id } => {
+            let workflow = DynamicWorkflow::from_file(spec).map_err(|e| e.to_string())?;
+            let export = match (history, workflow_id) {
+                (Some(path), _) => load_export(path)?,
+                (None, Some(workflow_id)) => {
+                    let path = format!("/workflows/{}/history/export", encode(workflow_id));
+                    let response = api.send(api.request(Method::GET, &path).query(&[("format", "json")])).await?;
+                    let bytes = response.bytes().await.map_err(|e| format!("request failed: {}", e))?;
+                    HistoryExport::decode(HistoryFormat::Json, &bytes).map_err(|e| e.to_string())?
+                }
+                (None, None) => unreachable!("clap requires --history or --workflow-id"),
+            };
+            let outcome = WorkflowReplayer::replay_dynamic(&workflow, &export);
+            let replayed = outcome.is_ok();
+            let value = json!({
+                "workflow_id": export.execution.workflow_id.to_string(),
+                "run_id": export.execution.run_id.to_string(),
+                "outcome": match outcome {
+                    Ok(()) => "replayed without divergence".to_string(),
+                    Err(e) => e.to_string(),
+                },
+            });
+            return Ok((value, replayed));
+        }
+        Command::ValidateDsl { files, activities } => return validate(files, activities.as_deref()),
+        Command::New { name, path, features, workflow_path } => {
+            let scaffold = Scaffold {
+                name: name.clone(),
+                features: features.clone(),
+                workflow_path: workflow_path.clone(),
+            };
+            let dir = path.clone().unwrap_or_else(|| PathBuf::from(name));
+            let files = scaffold.create(&dir)?;
+            let files: Vec<String> = files.iter().map(|file| file.display().to_string()).collect();
+            json!({ "name": name, "path": dir.display().to_string(), "files": files })
+        }
+    };
+    Ok((value, true))
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli).await {
+        Ok((value, succeeded)) => {
+            println!("{}", render(&cli.command, &value, cli.output));
+            if succeeded { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+        }
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition_is_consistent() {
+        Cli::command().debug_assert();
+        let cli = Cli::try_parse_from(["workflow-cli", "start", "order", "--input", "{\"amount\": 5}", "-o", "json"]).unwrap();
+        assert_eq!(cli.output, Output::Json);
+        assert!(matches!(cli.command, Command::Start { ref input, .. } if input["amount"] == 5));
+        assert!(Cli::try_parse_from(["workflow-cli", "signal", "o-1", "go", "--input", "{"]).is_err());
+        assert!(Cli::try_parse_from(["workflow-cli", "replay", "--spec", "order.yaml"]).is_err());
+        let cli = Cli::try_parse_from(["workflow-cli", "new", "orders", "--features", "sqlite,grpc"]).unwrap();
+        assert!(matches!(cli.command, Command::New { ref features, .. } if features == &["sqlite", "grpc"]));
+    }
+
+    #[test]
+    fn test_tables_align_columns() {
+        let runs = json!({"workflows": [
+            {"execution": {"workflow_id": "order-7"}, "workflow_type": "order", "status": "Running", "start_time": "t0"},
+            {"execution": {"workflow_id": "o-8"}, "workflow_type": "refund", "status": "Completed", "start_time": "t1", "close_time": "t2"},
+        ]});
+        let list = Command::List { workflow_type: None, status: None, limit: None };
+        assert_eq!(
+            render(&list, &runs, Output::Table),
+            "WORKFLOW ID  TYPE    STATUS     STARTED  CLOSED\n\
+             order-7      order   Running    t0\n\
+             o-8          refund  Completed  t1       t2"
+        );
+        let events = json!({"events": [{"event_id": 1, "timestamp": "t0", "event_type": {"WorkflowExecutionStarted": {"input": 1}}}]});
+        let history = Command::History { workflow_id: "order-7".to_string() };
+        assert!(render(&history, &events, Output::Table).ends_with("1   t0    WorkflowExecutionStarted"));
+        assert_eq!(encode("orders/7 a"), "orders%2F7%20a");
+    }
+}
@@ -0,0 +1,140 @@
+//! `workflow-cli new` 的工作者项目模板 / Worker project templates of `workflow-cli new`
+//!
+//! 生成的项目包含一个示例工作流与两个活动、运行工作者并提供 HTTP API 的 `main.rs`，以及在测试环境中运行工作流的测试。
+//! The generated project holds a sample workflow with two activities, a `main.rs` running a worker behind the HTTP
+//! API and a test running the workflow on the test environment.
+
+use std::path::{Path, PathBuf};
+
+/// 生成的文件及其模板 / Generated files and their templates
+const TEMPLATES: &[(&str, &str)] = &[
+    ("Cargo.toml", include_str!("templates/Cargo.toml.tmpl")),
+    (".gitignore", include_str!("templates/gitignore.tmpl")),
+    ("src/lib.rs", include_str!("templates/lib.rs.tmpl")),
+    ("src/main.rs", include_str!("templates/main.rs.tmpl")),
+    ("tests/order.rs", include_str!("templates/order_test.rs.tmpl")),
+];
+
+/// 可为生成的项目启用的 `workflow` 特性 / `workflow` features the generated project may enable
+pub(crate) const FEATURES: &[&str] = &[
+    "middleware", "sqlite", "database", "grpc", "nats", "kafka", "otel", "dashboard", "notifications", "bpmn",
+    "diagnostics",
+];
+
+/// 待生成的项目 / Project to generate
+#[derive(Debug)]
+pub(crate) struct Scaffold {
+    pub name: String,
+    /// 启用的 `workflow` 特性 / Enabled `workflow` features
+    pub features: Vec<String>,
+    /// 以路径依赖本地的 `workflow`，缺省依赖当前版本 / Depend on a local `workflow` by path instead of this version
+    pub workflow_path: Option<PathBuf>,
+}
+
+impl Scaffold {
+    /// 检查包名与特性 / Check the package name and the features
+    pub fn validate(&self) -> Result<(), String> {
+        let mut chars = self.name.chars();
+        let valid = chars.next().is_some_and(|first| first.is_ascii_alphabetic())
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(format!(
+                "invalid package name {:?}: use ASCII letters, digits, `-` and `_`, starting with a letter",
+                self.name
+            ));
+        }
+        if self.crate_name() == "workflow" {
+            return Err("the package cannot be named `workflow`, which it depends on".to_string());
+        }
+        match self.features.iter().find(|feature| !FEATURES.contains(&feature.as_str())) {
+            Some(feature) => Err(format!("unknown feature {:?}, expected one of: {}", feature, FEATURES.join(", "))),
+            None => Ok(()),
+        }
+    }
+
+    /// 代码中引用的 crate 名 / Crate name as referred to in code
+    pub fn crate_name(&self) -> String {
+        self.name.replace('-', "_")
+    }
+
+    fn workflow_dependency(&self) -> String {
+        let source = match &self.workflow_path {
+            Some(path) => format!("path = {:?}", path.display().to_string()),
+            None => format!("version = \"{}\"", env!("CARGO_PKG_VERSION")),
+        };
+        let features: Vec<String> = self.features.iter().map(|feature| format!("{:?}", feature)).collect();
+        format!("workflow = {{ {}, default-features = false, features = [{}] }}", source, features.join(", "))
+    }
+
+    fn render(&self, template: &str) -> String {
+        template
+            .replace("{{name}}", &self.name)
+            .replace("{{crate_name}}", &self.crate_name())
+            .replace("{{workflow_dependency}}", &self.workflow_dependency())
+    }
+
+    /// 在 `dir` 下生成项目，返回写入的文件；`dir` 已存在且非空时失败 /
+    /// Generate the project in `dir`, returning the files written; fails when `dir` exists and is not empty
+    pub fn create(&self, dir: &Path) -> Result<Vec<PathBuf>, String> {
+        self.validate()?;
+        if dir.read_dir().is_ok_and(|mut entries| entries.next().is_some()) {
+            return Err(format!("destination {} already exists and is not empty", dir.display()));
+        }
+        let mut written = Vec::new();
+        for (file, template) in TEMPLATES {
+            let path = dir.join(file);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+            }
+            std::fs::write(&path, self.render(template)).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scaffold(name: &str, features: &[&str]) -> Scaffold {
+        Scaffold {
+            name: name.to_string(),
+            features: features.iter().map(|feature| feature.to_string()).collect(),
+            workflow_path: None,
+        }
+    }
+
+    #[test]
+    fn test_project_is_generated_from_templates() {
+        let dir = std::env::temp_dir().join(format!("workflow-cli-new-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let written = scaffold("order-service", &["sqlite"]).create(&dir).unwrap();
+        assert_eq!(written.len(), TEMPLATES.len());
+
+        let manifest = std::fs::read_to_string(dir.join("Cargo.toml")).unwrap();
+        assert!(manifest.contains("name = \"order-service\""));
+        assert!(manifest.contains(&format!(
+            "workflow = {{ version = \"{}\", default-features = false, features = [\"sqlite\"] }}",
+            env!("CARGO_PKG_VERSION")
+        )));
+        let main = std::fs::read_to_string(dir.join("src/main.rs")).unwrap();
+        assert!(main.contains("use order_service::{ChargePayment, OrderWorkflow, ReserveInventory};"));
+        for path in &written {
+            assert!(!std::fs::read_to_string(path).unwrap().contains("{{"), "{}", path.display());
+        }
+
+        // 不覆盖已有项目 / an existing project is not overwritten
+        assert!(scaffold("order-service", &[]).create(&dir).unwrap_err().contains("not empty"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_names_and_features_are_checked() {
+        assert!(scaffold("orders_2", &["grpc", "otel"]).validate().is_ok());
+        for name in ["", "2orders", "order service", "workflow"] {
+            assert!(scaffold(name, &[]).validate().is_err(), "{name:?}");
+        }
+        assert!(scaffold("orders", &["temporal"]).validate().unwrap_err().contains("unknown feature"));
+    }
+}
@@ -0,0 +1,465 @@
+//! # 服务配置 / Service Configuration
+//!
+//! [`Config`] 汇总服务进程的设置：先取缺省值，再叠加 TOML、YAML 或 JSON 文件（按扩展名识别），最后叠加
+//! [`ENV_OVERRIDES`] 中的环境变量，并在使用前校验。[`reload::ConfigReloader`] 在收到 SIGHUP 或文件变化时
+//! 重新加载，只把可在运行时调整的值（并发上限、只读模式）交给订阅者。
+//! [`Config`] gathers the settings of the service process: defaults first, then a TOML, YAML or JSON file (told apart
+//! by extension), then the environment variables in [`ENV_OVERRIDES`], validated before use.
+//! [`reload::ConfigReloader`] reloads it on SIGHUP or when the file changes and hands subscribers only the values
+//! tunable at runtime (concurrency limits, read-only mode).
+//!
+//! ```
+//! use workflow::config::{Config, FileFormat};
+//!
+//! let config = Config::from_str(
+//!     r#"
+//!     [server]
+//!     port = 9000
+//!
+//!     [worker]
+//!     max_concurrent_activity_tasks = 16
+//!     "#,
+//!     FileFormat::Toml,
+//! )
+//! .unwrap();
+//! assert_eq!(config.server.port, 9000);
+//! assert_eq!(config.worker.max_concurrent_workflow_tasks, 100);
+//! ```
+
+pub mod reload;
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::temporal::retention::{RetentionPolicies, RetentionPolicy};
+use crate::temporal::WorkerConfig;
+
+pub use ::config::FileFormat;
+pub use reload::ConfigReloader;
+
+/// 配置文件路径的环境变量 / Environment variable naming the config file
+pub const CONFIG_PATH_ENV: &str = "WORKFLOW_CONFIG";
+
+/// 分页令牌密钥的最短长度 / Shortest accepted page token key
+pub const MIN_PAGE_TOKEN_KEY_LEN: usize = 32;
+
+/// 覆盖配置项的环境变量及其键 / Environment variables overriding config keys, with the keys they set
+pub const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("WORKFLOW_HOST", "server.host"),
+    ("WORKFLOW_PORT", "server.port"),
+    ("WORKFLOW_GRPC_PORT", "server.grpc_port"),
+    ("WORKFLOW_READ_ONLY", "server.read_only"),
+    ("WORKFLOW_SHUTDOWN_GRACE_SECS", "server.shutdown_grace_secs"),
+    ("WORKFLOW_PAGE_TOKEN_KEY", "server.page_token_key"),
+    ("WORKFLOW_DEBUG_ENDPOINTS", "server.debug_endpoints"),
+    ("WORKFLOW_METRICS_ENABLED", "metrics.enabled"),
+    ("WORKFLOW_METRICS_LISTEN", "metrics.listen"),
+    ("WORKFLOW_STORAGE_BACKEND", "storage.backend"),
+    ("WORKFLOW_STORAGE_URL", "storage.url"),
+    ("WORKFLOW_TASK_QUEUE", "worker.task_queue"),
+    ("WORKFLOW_MAX_CONCURRENT_WORKFLOW_TASKS", "worker.max_concurrent_workflow_tasks"),
+    ("WORKFLOW_MAX_CONCURRENT_ACTIVITY_TASKS", "worker.max_concurrent_activity_tasks"),
+    ("WORKFLOW_RETENTION_ENABLED", "retention.enabled"),
+    ("WORKFLOW_RATE_LIMIT_PER_MINUTE", "middleware.rate_limit_per_minute"),
+    ("WORKFLOW_RATE_LIMIT_BURST", "middleware.rate_limit_burst"),
+    ("WORKFLOW_AUDIT_LOG", "middleware.audit_log"),
+];
+
+/// 配置错误 / Configuration error
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("配置加载失败 / Failed to load configuration: {0}")]
+    Load(#[from] ::config::ConfigError),
+
+    #[error("配置无效 / Invalid configuration: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+}
+
+/// 服务配置 / Service configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub metrics: MetricsConfig,
+    pub storage: StorageConfig,
+    pub worker: WorkerSettings,
+    pub retention: RetentionSettings,
+    pub middleware: MiddlewareSettings,
+}
+
+/// HTTP 与 gRPC 服务 / HTTP and gRPC servers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub grpc_port: u16,
+    /// 可热加载 / Reloadable
+    pub read_only: bool,
+    /// 关闭时留给进行中活动的时间 / Time in-flight activities get to finish on shutdown
+    pub shutdown_grace_secs: u64,
+    /// 签发分页令牌的密钥，至少 32 字节；共享存储的各实例须使用同一密钥，缺省为进程级随机密钥 / Key signing page
+    /// tokens, at least 32 bytes; instances sharing storage need the same key, a random per-process key by default
+    pub page_token_key: Option<String>,
+    /// 未配置认证时也提供 `/debug` 剖析路由；配置认证时始终提供 / Serve the `/debug` profiling routes even without
+    /// authentication; they are always served behind it
+    pub debug_endpoints: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            grpc_port: 50051,
+            read_only: false,
+            shutdown_grace_secs: 30,
+            page_token_key: None,
+            debug_endpoints: false,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn http_addr(&self) -> Result<SocketAddr, std::net::AddrParseError> {
+        format!("{}:{}", self.host, self.port).parse()
+    }
+
+    pub fn grpc_addr(&self) -> Result<SocketAddr, std::net::AddrParseError> {
+        format!("{}:{}", self.host, self.grpc_port).parse()
+    }
+
+    pub fn shutdown_grace_period(&self) -> Duration {
+        Duration::from_secs(self.shutdown_grace_secs)
+    }
+}
+
+/// Prometheus 导出 / Prometheus exporter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    pub listen: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            listen: "0.0.0.0:9090".to_string(),
+        }
+    }
+}
+
+/// 存储后端 / Storage backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackend {
+    #[default]
+    Memory,
+    /// 需启用 `sqlite` 特性 / Requires the `sqlite` feature
+    Sqlite,
+}
+
+/// 工作流存储 / Workflow storage
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    /// 数据库 URL，如 `sqlite://workflow.db` / Database URL, e.g. `sqlite://workflow.db`
+    pub url: Option<String>,
+}
+
+impl StorageConfig {
+    /// 存储可被多个实例共享，即可能多实例部署 / Whether several instances can share the storage, so the service may
+    /// run as more than one instance
+    pub fn is_shared(&self) -> bool {
+        self.backend != StorageBackend::Memory
+    }
+}
+
+/// 进程内工作者，见 [`WorkerConfig`] / In-process worker, see [`WorkerConfig`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkerSettings {
+    pub task_queue: String,
+    /// 可热加载 / Reloadable
+    pub max_concurrent_workflow_tasks: usize,
+    /// 可热加载 / Reloadable
+    pub max_concurrent_activity_tasks: usize,
+    pub poll_timeout_ms: u64,
+    pub sticky_cache_size: usize,
+    pub max_task_failures: u32,
+    pub max_concurrent_sessions: usize,
+}
+
+impl Default for WorkerSettings {
+    fn default() -> Self {
+        let defaults = WorkerConfig::default();
+        Self {
+            task_queue: defaults.task_queue,
+            max_concurrent_workflow_tasks: defaults.max_concurrent_workflow_tasks,
+            max_concurrent_activity_tasks: defaults.max_concurrent_activity_tasks,
+            poll_timeout_ms: defaults.poll_timeout.as_millis() as u64,
+            sticky_cache_size: defaults.sticky_cache_size,
+            max_task_failures: defaults.max_task_failures,
+            max_concurrent_sessions: defaults.max_concurrent_sessions,
+        }
+    }
+}
+
+impl WorkerSettings {
+    pub fn worker_config(&self) -> WorkerConfig {
+        WorkerConfig::builder()
+            .task_queue(self.task_queue.clone())
+            .max_concurrent_workflow_tasks(self.max_concurrent_workflow_tasks)
+            .max_concurrent_activity_tasks(self.max_concurrent_activity_tasks)
+            .poll_timeout(Duration::from_millis(self.poll_timeout_ms))
+            .sticky_cache_size(self.sticky_cache_size)
+            .max_task_failures(self.max_task_failures)
+            .max_concurrent_sessions(self.max_concurrent_sessions)
+            .build()
+    }
+}
+
+/// 已关闭执行的保留期，见 [`RetentionPolicies`] / Retention of closed executions, see [`RetentionPolicies`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionSettings {
+    pub enabled: bool,
+    /// 未设置时永久保留 / Kept forever when unset
+    pub keep_completed_secs: Option<u64>,
+    /// 未设置时永久保留 / Kept forever when unset
+    pub keep_failed_secs: Option<u64>,
+    pub interval_secs: u64,
+    pub dry_run: bool,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keep_completed_secs: None,
+            keep_failed_secs: None,
+            interval_secs: crate::temporal::retention::DEFAULT_CLEANUP_INTERVAL.as_secs(),
+            dry_run: false,
+        }
+    }
+}
+
+impl RetentionSettings {
+    /// 启用时的保留策略 / Retention policies, when enabled
+    pub fn policies(&self) -> Option<RetentionPolicies> {
+        if !self.enabled {
+            return None;
+        }
+        let mut policy = RetentionPolicy::new();
+        if let Some(secs) = self.keep_completed_secs {
+            policy = policy.keep_completed(Duration::from_secs(secs));
+        }
+        if let Some(secs) = self.keep_failed_secs {
+            policy = policy.keep_failed(Duration::from_secs(secs));
+        }
+        Some(
+            RetentionPolicies::new()
+                .default_policy(policy)
+                .every(Duration::from_secs(self.interval_secs))
+                .dry_run(self.dry_run),
+        )
+    }
+}
+
+/// 请求中间件 / Request middleware
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MiddlewareSettings {
+    /// 每个客户端每分钟的请求数，未设置时不限流 / Requests per minute per client; unlimited when unset
+    pub rate_limit_per_minute: Option<u32>,
+    pub rate_limit_burst: Option<u32>,
+    /// 审计日志接收端，见 [`AuditLog::from_spec`](crate::audit::AuditLog::from_spec) / Audit sink, see
+    /// [`AuditLog::from_spec`](crate::audit::AuditLog::from_spec)
+    pub audit_log: String,
+}
+
+impl Default for MiddlewareSettings {
+    fn default() -> Self {
+        Self {
+            rate_limit_per_minute: None,
+            rate_limit_burst: None,
+            audit_log: "memory".to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// 从 `WORKFLOW_CONFIG` 指定的文件（若有）与进程环境加载 / Load from the file named by `WORKFLOW_CONFIG`, if any,
+    /// and the process environment
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let path = std::env::var_os(CONFIG_PATH_ENV).map(PathBuf::from);
+        Self::load(path.as_deref(), |name| std::env::var(name).ok())
+    }
+
+    /// 从可选的文件与 `lookup` 给出的环境变量加载并校验 / Load from an optional file and the environment variables
+    /// `lookup` returns, then validate
+    pub fn load(path: Option<&Path>, lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut builder = ::config::Config::builder();
+        if let Some(path) = path {
+            builder = builder.add_source(::config::File::from(path).required(true));
+        }
+        for (name, key) in ENV_OVERRIDES {
+            if let Some(value) = lookup(name) {
+                builder = builder.set_override(*key, value)?;
+            }
+        }
+        let config: Self = builder.build()?.try_deserialize()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 从给定格式的文本加载并校验，不读取环境 / Load from text in the given format and validate, without the
+    /// environment
+    pub fn from_str(text: &str, format: FileFormat) -> Result<Self, ConfigError> {
+        let config: Self = ::config::Config::builder()
+            .add_source(::config::File::from_str(text, format))
+            .build()?
+            .try_deserialize()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 检查全部设置，返回每个问题 / Check every setting, reporting each problem
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        if let Err(e) = self.server.http_addr() {
+            problems.push(format!("server.host/server.port: {}", e));
+        }
+        if self.server.port == 0 {
+            problems.push("server.port must not be 0".to_string());
+        }
+        if self.server.grpc_port == self.server.port {
+            problems.push("server.grpc_port must differ from server.port".to_string());
+        }
+        if self.server.page_token_key.as_ref().is_some_and(|key| key.len() < MIN_PAGE_TOKEN_KEY_LEN) {
+            problems.push(format!("server.page_token_key must be at least {} bytes", MIN_PAGE_TOKEN_KEY_LEN));
+        }
+        if self.metrics.enabled && self.metrics.listen.parse::<SocketAddr>().is_err() {
+            problems.push(format!("metrics.listen is not a socket address: {}", self.metrics.listen));
+        }
+        match (self.storage.backend, &self.storage.url) {
+            (StorageBackend::Sqlite, None) => problems.push("storage.url is required for the sqlite backend".to_string()),
+            #[cfg(not(feature = "sqlite"))]
+            (StorageBackend::Sqlite, Some(_)) => {
+                problems.push("storage.backend sqlite requires the sqlite feature".to_string())
+            }
+            _ => {}
+        }
+        if self.worker.task_queue.is_empty() {
+            problems.push("worker.task_queue must not be empty".to_string());
+        }
+        if self.worker.max_concurrent_workflow_tasks == 0 {
+            problems.push("worker.max_concurrent_workflow_tasks must be at least 1".to_string());
+        }
+        if self.worker.max_concurrent_activity_tasks == 0 {
+            problems.push("worker.max_concurrent_activity_tasks must be at least 1".to_string());
+        }
+        if self.retention.enabled && self.retention.interval_secs == 0 {
+            problems.push("retention.interval_secs must be at least 1".to_string());
+        }
+        if self.middleware.rate_limit_per_minute == Some(0) {
+            problems.push("middleware.rate_limit_per_minute must be at least 1".to_string());
+        }
+        if problems.is_empty() { Ok(()) } else { Err(ConfigError::Invalid(problems)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_file_then_env_layering() {
+        let dir = std::env::temp_dir().join(format!("workflow-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("workflow.yaml");
+        std::fs::write(
+            &path,
+            "server:\n  port: 9000\n  host: 127.0.0.1\nworker:\n  task_queue: orders\n  max_concurrent_activity_tasks: 8\n",
+        )
+        .unwrap();
+
+        let key = "k".repeat(MIN_PAGE_TOKEN_KEY_LEN);
+        let vars = [
+            ("WORKFLOW_PORT", "9100"),
+            ("WORKFLOW_READ_ONLY", "true"),
+            ("WORKFLOW_PAGE_TOKEN_KEY", key.as_str()),
+            ("WORKFLOW_DEBUG_ENDPOINTS", "true"),
+        ];
+        let config = Config::load(Some(&path), env(&vars)).unwrap();
+        assert_eq!(config.server.http_addr().unwrap(), "127.0.0.1:9100".parse().unwrap());
+        assert!(config.server.read_only);
+        assert_eq!(config.server.page_token_key.as_deref(), Some(key.as_str()));
+        assert!(config.server.debug_endpoints);
+        let worker = config.worker.worker_config();
+        assert_eq!(worker.task_queue, "orders");
+        assert_eq!(worker.max_concurrent_activity_tasks, 8);
+        assert_eq!(worker.max_concurrent_workflow_tasks, 100);
+        assert_eq!(config.middleware.audit_log, "memory");
+
+        assert_eq!(Config::load(None, env(&[])).unwrap(), Config::default());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_validation_reports_every_problem() {
+        let toml = r#"
+            [server]
+            port = 50051
+            page_token_key = "short"
+
+            [worker]
+            max_concurrent_workflow_tasks = 0
+
+            [storage]
+            backend = "sqlite"
+        "#;
+        let ConfigError::Invalid(problems) = Config::from_str(toml, FileFormat::Toml).unwrap_err() else {
+            panic!("expected a validation error");
+        };
+        assert_eq!(
+            problems,
+            [
+                "server.grpc_port must differ from server.port",
+                "server.page_token_key must be at least 32 bytes",
+                "storage.url is required for the sqlite backend",
+                "worker.max_concurrent_workflow_tasks must be at least 1",
+            ]
+        );
+
+        let unknown = Config::from_str("[server]\nprot = 1\n", FileFormat::Toml).unwrap_err();
+        assert!(matches!(unknown, ConfigError::Load(_)), "{}", unknown);
+        let env_error = Config::load(None, env(&[("WORKFLOW_PORT", "http")])).unwrap_err();
+        assert!(matches!(env_error, ConfigError::Load(_)), "{}", env_error);
+    }
+
+    #[test]
+    fn test_retention_policies_only_when_enabled() {
+        let mut retention = RetentionSettings::default();
+        assert!(retention.policies().is_none());
+        retention.enabled = true;
+        retention.keep_completed_secs = Some(3600);
+        retention.interval_secs = 60;
+        let policies = retention.policies().unwrap();
+        assert_eq!(policies.interval(), Duration::from_secs(60));
+        assert_eq!(policies.policy_for("any").completed, Some(Duration::from_secs(3600)));
+        assert_eq!(policies.policy_for("any").failed, None);
+    }
+}
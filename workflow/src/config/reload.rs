@@ -0,0 +1,227 @@
+//! # 配置热加载 / Configuration Hot Reload
+//!
+//! [`ConfigReloader`] 持有运行中的配置；收到 SIGHUP 或发现配置文件修改时间变化后重新加载。新配置无效时保留
+//! 原配置；只有可在运行时调整的值会生效，其余改动记录警告，待重启后生效。
+//! [`ConfigReloader`] holds the running configuration and reloads it on SIGHUP or when the config file's modification
+//! time changes. An invalid new configuration leaves the running one in place; only the values tunable at runtime take
+//! effect, other changes are logged as needing a restart.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use parking_lot::Mutex;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use super::{Config, ConfigError};
+use crate::http::ReadOnlyMode;
+use crate::temporal::WorkflowWorker;
+
+type Lookup = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// 运行中配置的持有者 / Holder of the running configuration
+pub struct ConfigReloader {
+    path: Option<PathBuf>,
+    lookup: Lookup,
+    current: watch::Sender<Arc<Config>>,
+    /// 上次加载时文件的修改时间 / Modification time of the file when last loaded
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl ConfigReloader {
+    /// 以已加载的 `config` 开始，从 `path` 与进程环境重新加载 / Start from the loaded `config`, reloading from `path`
+    /// and the process environment
+    pub fn new(config: Config, path: Option<PathBuf>) -> Self {
+        let modified = path.as_deref().and_then(modified);
+        Self {
+            path,
+            lookup: Box::new(|name| std::env::var(name).ok()),
+            current: watch::channel(Arc::new(config)).0,
+            modified: Mutex::new(modified),
+        }
+    }
+
+    /// 以 `lookup` 代替进程环境 / Read environment variables through `lookup` instead of the process environment
+    pub fn with_lookup(mut self, lookup: impl Fn(&str) -> Option<String> + Send + Sync + 'static) -> Self {
+        self.lookup = Box::new(lookup);
+        self
+    }
+
+    pub fn current(&self) -> Arc<Config> {
+        self.current.borrow().clone()
+    }
+
+    /// 每次生效的重新加载都会通知接收端 / The receiver is notified of every reload that takes effect
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Config>> {
+        self.current.subscribe()
+    }
+
+    /// 重新加载，返回可调整的值是否有变化 / Reload, returning whether any tunable value changed
+    pub fn reload(&self) -> Result<bool, ConfigError> {
+        *self.modified.lock() = self.path.as_deref().and_then(modified);
+        let loaded = Config::load(self.path.as_deref(), &self.lookup)?;
+        let running = self.current();
+        let next = with_tunables(&running, &loaded);
+        let pending = restart_required(&next, &loaded);
+        if !pending.is_empty() {
+            tracing::warn!(sections = ?pending, "配置改动需重启后生效 / configuration changes need a restart to take effect");
+        }
+        if next == *running {
+            return Ok(false);
+        }
+        tracing::info!(
+            max_concurrent_workflow_tasks = next.worker.max_concurrent_workflow_tasks,
+            max_concurrent_activity_tasks = next.worker.max_concurrent_activity_tasks,
+            read_only = next.server.read_only,
+            "配置已重新加载 / configuration reloaded"
+        );
+        self.current.send_replace(Arc::new(next));
+        Ok(true)
+    }
+
+    /// 文件修改时间自上次加载后是否变化 / Whether the file's modification time changed since it was last loaded
+    fn file_changed(&self) -> bool {
+        let Some(path) = &self.path else {
+            return false;
+        };
+        modified(path) != *self.modified.lock()
+    }
+
+    /// 在收到 SIGHUP 或每 `poll_interval` 发现文件变化时重新加载 / Reload on SIGHUP, and whenever a check every
+    /// `poll_interval` finds the file changed
+    pub fn spawn(self: Arc<Self>, poll_interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            #[cfg(unix)]
+            let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+            let mut poll = tokio::time::interval(poll_interval);
+            poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                #[cfg(unix)]
+                let signalled = async {
+                    match &mut hangup {
+                        Some(hangup) => hangup.recv().await,
+                        None => std::future::pending().await,
+                    }
+                };
+                #[cfg(not(unix))]
+                let signalled = std::future::pending::<Option<()>>();
+                tokio::select! {
+                    _ = signalled => tracing::info!("收到 SIGHUP / received SIGHUP"),
+                    _ = poll.tick() => {
+                        if !self.file_changed() {
+                            continue;
+                        }
+                    }
+                }
+                if let Err(e) = self.reload() {
+                    tracing::error!(error = %e, "配置重新加载失败，保留原配置 / configuration reload failed, keeping the running one");
+                }
+            }
+        })
+    }
+}
+
+fn modified(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// `running` 换上 `loaded` 中可调整的值 / `running` with the tunable values of `loaded`
+fn with_tunables(running: &Config, loaded: &Config) -> Config {
+    let mut next = running.clone();
+    next.server.read_only = loaded.server.read_only;
+    next.worker.max_concurrent_workflow_tasks = loaded.worker.max_concurrent_workflow_tasks;
+    next.worker.max_concurrent_activity_tasks = loaded.worker.max_concurrent_activity_tasks;
+    next
+}
+
+/// 仍与 `loaded` 不同的部分 / Sections still differing from `loaded`
+fn restart_required(next: &Config, loaded: &Config) -> Vec<&'static str> {
+    let mut sections = Vec::new();
+    if next.server != loaded.server {
+        sections.push("server");
+    }
+    if next.metrics != loaded.metrics {
+        sections.push("metrics");
+    }
+    if next.storage != loaded.storage {
+        sections.push("storage");
+    }
+    if next.worker != loaded.worker {
+        sections.push("worker");
+    }
+    if next.retention != loaded.retention {
+        sections.push("retention");
+    }
+    if next.middleware != loaded.middleware {
+        sections.push("middleware");
+    }
+    sections
+}
+
+/// 把可调整的值用于运行中的 `worker` 与服务的只读开关 / Apply the tunable values to a running `worker` and the
+/// service's read-only switch
+///
+/// 只读开关仅在配置中的值相对 `previous` 改变时才设置，以免覆盖运行时经管理端点设置的开关。
+/// The read-only switch is only set when its configured value changed from `previous`, so that reloads leave a switch
+/// set at runtime through the admin endpoint alone.
+pub fn apply_tunables(previous: &Config, config: &Config, worker: &WorkflowWorker, read_only: &ReadOnlyMode) {
+    worker.load().set_max_concurrent_workflow_tasks(config.worker.max_concurrent_workflow_tasks);
+    worker.load().set_max_concurrent_activity_tasks(config.worker.max_concurrent_activity_tasks);
+    if config.server.read_only != previous.server.read_only {
+        read_only.set(config.server.read_only);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reload_applies_only_tunable_values() {
+        let dir = std::env::temp_dir().join(format!("workflow-reload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("workflow.toml");
+        std::fs::write(&path, "[worker]\nmax_concurrent_activity_tasks = 4\n").unwrap();
+        let config = Config::load(Some(&path), |_| None).unwrap();
+        let reloader = ConfigReloader::new(config, Some(path.clone())).with_lookup(|_| None);
+        let mut updates = reloader.subscribe();
+        assert!(!reloader.reload().unwrap());
+
+        std::fs::write(&path, "[server]\nport = 9000\n\n[worker]\nmax_concurrent_activity_tasks = 12\n").unwrap();
+        assert!(reloader.reload().unwrap());
+        assert!(updates.has_changed().unwrap());
+        let current = updates.borrow_and_update().clone();
+        assert_eq!(current.worker.max_concurrent_activity_tasks, 12);
+        // 端口需重启才生效 / the port needs a restart
+        assert_eq!(current.server.port, 8080);
+
+        std::fs::write(&path, "[worker]\nmax_concurrent_activity_tasks = 0\n").unwrap();
+        assert!(matches!(reloader.reload(), Err(ConfigError::Invalid(_))));
+        assert_eq!(reloader.current().worker.max_concurrent_activity_tasks, 12);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_reloads_leave_the_runtime_read_only_switch_alone() {
+        let worker = WorkflowWorker::default();
+        let read_only = ReadOnlyMode::new();
+        let running = Config::load(None, |_| None).unwrap();
+        // 运维经管理端点打开 / an operator switches it on through the admin endpoint
+        read_only.set(true);
+
+        let mut loaded = running.clone();
+        loaded.worker.max_concurrent_activity_tasks = 12;
+        apply_tunables(&running, &loaded, &worker, &read_only);
+        assert!(read_only.is_enabled());
+
+        let mut switched = loaded.clone();
+        switched.server.read_only = true;
+        apply_tunables(&loaded, &switched, &worker, &read_only);
+        let mut released = switched.clone();
+        released.server.read_only = false;
+        apply_tunables(&switched, &released, &worker, &read_only);
+        assert!(!read_only.is_enabled());
+    }
+}
@@ -0,0 +1,284 @@
+//! 采样堆剖析 / Sampling heap profiler
+//!
+//! [`SamplingAllocator`] 包装全局分配器，每分配约 [`SAMPLE_INTERVAL`] 字节记录一次调用栈，释放时移除；
+//! `/debug/pprof/heap` 将仍存活的采样导出为 pprof 堆剖析（`inuse_objects` / `inuse_space`），数值按采样间隔估算。
+//! [`SamplingAllocator`] wraps the global allocator and records a call stack about every [`SAMPLE_INTERVAL`]
+//! allocated bytes, dropping it again on free; `/debug/pprof/heap` exports the live samples as a pprof heap profile
+//! (`inuse_objects` / `inuse_space`) whose values are estimated from the sampling interval.
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOCATOR: workflow::diagnostics::heap::SamplingAllocator = workflow::diagnostics::heap::SamplingAllocator::system();
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+// 标准库互斥锁加锁不分配内存 / std's mutex never allocates while locking
+use std::sync::Mutex;
+
+/// 平均每分配多少字节采样一次 / Bytes allocated, on average, between two samples
+pub const SAMPLE_INTERVAL: usize = 512 * 1024;
+/// 每个采样保留的栈帧数 / Stack frames kept per sample
+pub const MAX_FRAMES: usize = 64;
+
+/// 可能被采样的地址计数，释放时先查此处，避免每次释放都加锁 / Counts of addresses that may be sampled, checked on
+/// free so that most frees never take the lock
+const FILTER_SLOTS: usize = 16 * 1024;
+
+struct Sample {
+    size: usize,
+    frames: Vec<usize>,
+}
+
+static LIVE: Mutex<BTreeMap<usize, Sample>> = Mutex::new(BTreeMap::new());
+static FILTER: [AtomicU32; FILTER_SLOTS] = [const { AtomicU32::new(0) }; FILTER_SLOTS];
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// 距下次采样还剩的字节数 / Bytes left until the next sample
+    static UNTIL_SAMPLE: Cell<usize> = const { Cell::new(SAMPLE_INTERVAL) };
+    /// 剖析器自身的分配不采样 / The profiler's own allocations are not sampled
+    static IN_PROFILER: Cell<bool> = const { Cell::new(false) };
+}
+
+fn filter_slot(ptr: usize) -> &'static AtomicU32 {
+    // 低位受对齐影响，先打散 / the low bits follow the alignment, so mix them first
+    &FILTER[((ptr as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32) as usize % FILTER_SLOTS]
+}
+
+/// 在本线程标记剖析器工作期间 / Marks the profiler at work on this thread
+struct Reentry;
+
+impl Reentry {
+    fn enter() -> Option<Self> {
+        IN_PROFILER
+            .try_with(|busy| !busy.replace(true))
+            .unwrap_or(false)
+            .then_some(Reentry)
+    }
+
+    fn is_active() -> bool {
+        IN_PROFILER.try_with(Cell::get).unwrap_or(true)
+    }
+}
+
+impl Drop for Reentry {
+    fn drop(&mut self) {
+        let _ = IN_PROFILER.try_with(|busy| busy.set(false));
+    }
+}
+
+/// 采样的全局分配器包装 / Sampling wrapper around a global allocator
+pub struct SamplingAllocator<A = System> {
+    inner: A,
+}
+
+impl SamplingAllocator<System> {
+    pub const fn system() -> Self {
+        Self::new(System)
+    }
+}
+
+impl<A> SamplingAllocator<A> {
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+/// 是否安装了 [`SamplingAllocator`] / Whether a [`SamplingAllocator`] is installed
+pub fn is_installed() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
+
+fn record(ptr: *mut u8, size: usize) {
+    if ptr.is_null() {
+        return;
+    }
+    if !INSTALLED.load(Ordering::Relaxed) {
+        INSTALLED.store(true, Ordering::Relaxed);
+    }
+    let due = UNTIL_SAMPLE
+        .try_with(|left| match left.get().checked_sub(size) {
+            Some(rest) if rest > 0 => {
+                left.set(rest);
+                false
+            }
+            _ => {
+                left.set(SAMPLE_INTERVAL);
+                true
+            }
+        })
+        .unwrap_or(false);
+    if !due {
+        return;
+    }
+    let Some(_reentry) = Reentry::enter() else {
+        return;
+    };
+    let mut frames = [0usize; MAX_FRAMES];
+    let mut depth = 0;
+    // 不分配内存；本线程的其他分配此时不采样 / does not allocate, and this thread's allocations are not sampled meanwhile
+    unsafe {
+        backtrace::trace_unsynchronized(|frame| {
+            frames[depth] = frame.ip() as usize;
+            depth += 1;
+            depth < MAX_FRAMES
+        });
+    }
+    let sample = Sample { size, frames: frames[..depth].to_vec() };
+    if let Ok(mut live) = LIVE.lock() {
+        filter_slot(ptr as usize).fetch_add(1, Ordering::Relaxed);
+        if live.insert(ptr as usize, sample).is_some() {
+            filter_slot(ptr as usize).fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+fn forget(ptr: *mut u8) {
+    if filter_slot(ptr as usize).load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    // 剖析器自身只释放未采样的内存 / the profiler only frees memory that was never sampled
+    if Reentry::is_active() {
+        return;
+    }
+    if let Ok(mut live) = LIVE.lock()
+        && live.remove(&(ptr as usize)).is_some()
+    {
+        filter_slot(ptr as usize).fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for SamplingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { self.inner.alloc(layout) };
+        record(ptr, layout.size());
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { self.inner.alloc_zeroed(layout) };
+        record(ptr, layout.size());
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        forget(ptr);
+        unsafe { self.inner.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // 先移除，旧地址释放后可能立即被其他线程复用 / forget first, the old address may be reused as soon as it is freed
+        forget(ptr);
+        let moved = unsafe { self.inner.realloc(ptr, layout, new_size) };
+        record(moved, new_size);
+        moved
+    }
+}
+
+/// 将存活的采样导出为 pprof 堆剖析 / Export the live samples as a pprof heap profile
+///
+/// 符号解析在阻塞线程池中进行，期间分配照常进行。
+/// Symbols are resolved on the blocking pool; allocations carry on meanwhile.
+pub async fn capture_heap_profile() -> anyhow::Result<Vec<u8>> {
+    anyhow::ensure!(is_installed(), "the sampling allocator is not installed as the global allocator");
+    tokio::task::spawn_blocking(|| -> anyhow::Result<Vec<u8>> {
+        use pprof::protos::Message;
+
+        let _reentry = Reentry::enter();
+        let samples: Vec<(usize, Vec<usize>)> = match LIVE.lock() {
+            Ok(live) => live.values().map(|sample| (sample.size, sample.frames.clone())).collect(),
+            Err(_) => anyhow::bail!("heap samples are unavailable"),
+        };
+        let mut body = Vec::new();
+        encode(&samples).write_to_vec(&mut body)?;
+        Ok(body)
+    })
+    .await?
+}
+
+/// 字符串表与符号化后的位置 / String table and symbolized locations of a profile being built
+#[derive(Default)]
+struct Encoder {
+    profile: pprof::protos::Profile,
+    strings: HashMap<String, i64>,
+    locations: HashMap<usize, u64>,
+    functions: HashMap<(String, String), u64>,
+}
+
+impl Encoder {
+    fn string(&mut self, value: &str) -> i64 {
+        if let Some(index) = self.strings.get(value) {
+            return *index;
+        }
+        let index = self.profile.string_table.len() as i64;
+        self.profile.string_table.push(value.to_string());
+        self.strings.insert(value.to_string(), index);
+        index
+    }
+
+    fn function(&mut self, name: String, filename: String) -> u64 {
+        if let Some(id) = self.functions.get(&(name.clone(), filename.clone())) {
+            return *id;
+        }
+        let id = self.profile.function.len() as u64 + 1;
+        let function = pprof::protos::Function {
+            id,
+            name: self.string(&name),
+            system_name: self.string(&name),
+            filename: self.string(&filename),
+            ..Default::default()
+        };
+        self.profile.function.push(function);
+        self.functions.insert((name, filename), id);
+        id
+    }
+
+    fn location(&mut self, ip: usize) -> u64 {
+        if let Some(id) = self.locations.get(&ip) {
+            return *id;
+        }
+        let mut symbols = Vec::new();
+        // 返回地址指向调用的下一条指令 / return addresses point past the call
+        backtrace::resolve(ip.saturating_sub(1) as *mut std::ffi::c_void, |symbol| {
+            let name = symbol.name().map(|name| name.to_string()).unwrap_or_else(|| format!("{:#x}", ip));
+            let filename = symbol.filename().map(|path| path.display().to_string()).unwrap_or_default();
+            symbols.push((name, filename, symbol.lineno().unwrap_or(0) as i64));
+        });
+        if symbols.is_empty() {
+            symbols.push((format!("{:#x}", ip), String::new(), 0));
+        }
+        let id = self.profile.location.len() as u64 + 1;
+        // 内联的函数在前，调用者在后 / inlined functions come first, their caller last
+        let line = symbols
+            .into_iter()
+            .map(|(name, filename, line)| pprof::protos::Line { function_id: self.function(name, filename), line, ..Default::default() })
+            .collect();
+        self.profile.location.push(pprof::protos::Location { id, address: ip as u64, line, ..Default::default() });
+        self.locations.insert(ip, id);
+        id
+    }
+}
+
+fn encode(samples: &[(usize, Vec<usize>)]) -> pprof::protos::Profile {
+    let mut encoder = Encoder::default();
+    encoder.string("");
+    for (kind, unit) in [("inuse_objects", "count"), ("inuse_space", "bytes")] {
+        let value_type = pprof::protos::ValueType { type_: encoder.string(kind), unit: encoder.string(unit), ..Default::default() };
+        encoder.profile.sample_type.push(value_type);
+    }
+    for (size, frames) in samples {
+        // 每个采样代表约一个采样间隔的分配 / each sample stands for about one sampling interval of allocations
+        let size = (*size).max(1);
+        let bytes = size.max(SAMPLE_INTERVAL);
+        let location_id = frames.iter().map(|ip| encoder.location(*ip)).collect();
+        encoder.profile.sample.push(pprof::protos::Sample {
+            location_id,
+            value: vec![(bytes / size) as i64, bytes as i64],
+            ..Default::default()
+        });
+    }
+    encoder.profile
+}
@@ -0,0 +1,289 @@
+//! 诊断模块 / Diagnostics Module
+//! 提供在线 CPU 与堆剖析端点，以及按执行剖析单个任务，无需附加外部工具即可分析运行中的服务
+//! Provides on-demand CPU and heap profiling endpoints, plus profiles of single tasks of chosen executions, so a live
+//! service can be analysed without external tools
+//!
+//! `/debug` 仅在配置认证时挂载，未配置认证时须显式开启，见
+//! [`build_router_with_debug_endpoints`](crate::http::build_router_with_debug_endpoints)。
+//! `/debug` is only mounted when authentication is configured; without it the routes need an explicit opt-in, see
+//! [`build_router_with_debug_endpoints`](crate::http::build_router_with_debug_endpoints).
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+pub mod heap;
+
+/// 默认采样时长（秒）/ Default sampling duration in seconds
+pub const DEFAULT_PROFILE_SECONDS: u64 = 10;
+/// 最长采样时长（秒）/ Maximum sampling duration in seconds
+pub const MAX_PROFILE_SECONDS: u64 = 60;
+/// 采样频率（Hz）/ Sampling frequency in Hz
+pub const PROFILE_FREQUENCY: i32 = 99;
+/// 保留的任务剖析数量，超出时丢弃最早的 / Task profiles kept; the oldest are dropped beyond it
+pub const MAX_TASK_PROFILES: usize = 16;
+
+const PROFILE_BLOCKLIST: &[&str] = &["libc", "libgcc", "pthread", "vdso"];
+
+// 同一时间只允许一个剖析任务 / Only one profile may run at a time
+static PROFILE_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+struct ProfileSlot;
+
+impl ProfileSlot {
+    fn acquire() -> Option<Self> {
+        PROFILE_IN_PROGRESS
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+            .map(|_| ProfileSlot)
+    }
+}
+
+impl Drop for ProfileSlot {
+    fn drop(&mut self) {
+        PROFILE_IN_PROGRESS.store(false, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ProfileParams {
+    pub seconds: Option<u64>,
+}
+
+/// 采集一段 CPU 剖析并编码为 pprof protobuf / Capture a CPU profile and encode it as pprof protobuf
+///
+/// 采样在阻塞线程池中进行，不占用异步工作线程。
+/// Sampling runs on the blocking pool so async worker threads are never parked.
+pub async fn capture_cpu_profile(duration: Duration) -> anyhow::Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<u8>> {
+        use pprof::protos::Message;
+
+        let guard = pprof::ProfilerGuardBuilder::default()
+            .frequency(PROFILE_FREQUENCY)
+            .blocklist(PROFILE_BLOCKLIST)
+            .build()?;
+        std::thread::sleep(duration);
+        let profile = guard.report().build()?.pprof()?;
+        let mut body = Vec::new();
+        profile.write_to_vec(&mut body)?;
+        Ok(body)
+    })
+    .await?
+}
+
+fn in_progress() -> Response {
+    (
+        StatusCode::CONFLICT,
+        Json(serde_json::json!({
+            "code": "PROFILE_IN_PROGRESS",
+            "message": "another profile is already being captured"
+        })),
+    ).into_response()
+}
+
+fn profile_response(profile: anyhow::Result<Vec<u8>>, filename: &'static str) -> Response {
+    match profile {
+        Ok(body) => (
+            [
+                (header::CONTENT_TYPE, "application/octet-stream"),
+                (header::CONTENT_DISPOSITION, filename),
+            ],
+            body,
+        ).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "code": "PROFILE_FAILED", "message": e.to_string() })),
+        ).into_response(),
+    }
+}
+
+async fn cpu_profile(Query(params): Query<ProfileParams>) -> Response {
+    let Some(_slot) = ProfileSlot::acquire() else {
+        return in_progress();
+    };
+    let seconds = params.seconds.unwrap_or(DEFAULT_PROFILE_SECONDS).clamp(1, MAX_PROFILE_SECONDS);
+    metrics::counter!("diagnostics_profiles_total", "kind" => "cpu").increment(1);
+
+    let profile = capture_cpu_profile(Duration::from_secs(seconds)).await;
+    profile_response(profile, "attachment; filename=\"profile.pb\"")
+}
+
+async fn heap_profile() -> Response {
+    let Some(_slot) = ProfileSlot::acquire() else {
+        return in_progress();
+    };
+    metrics::counter!("diagnostics_profiles_total", "kind" => "heap").increment(1);
+
+    profile_response(heap::capture_heap_profile().await, "attachment; filename=\"heap.pb\"")
+}
+
+/// 单个任务的剖析结果 / Outcome of a task profile
+#[derive(Debug, Clone)]
+pub enum TaskProfile {
+    /// 等待该执行的下一个任务 / Waiting for the next task of the execution
+    Pending,
+    /// pprof protobuf，可用 `go tool pprof -http` 等查看火焰图 / pprof protobuf, viewable as a flamegraph with
+    /// `go tool pprof -http` and the like
+    Ready(Arc<Vec<u8>>),
+    Failed(String),
+}
+
+#[derive(Default)]
+struct TaskProfiles {
+    /// 工作流 ID 到等待中的剖析 ID / Workflow ID to the ID of its pending profile
+    requested: HashMap<String, String>,
+    profiles: HashMap<String, TaskProfile>,
+    /// 完成顺序，用于淘汰 / Completion order, for eviction
+    finished: VecDeque<String>,
+}
+
+/// 剖析指定执行的下一个任务 / Profiles the next task of chosen executions
+///
+/// 剖析与 `/debug/pprof/*` 共用同一名额；名额被占用时请求保留到之后的任务。剖析在阻塞线程池中编码，任务只承担
+/// 采样开销。
+/// Task profiles share their slot with `/debug/pprof/*`; while it is taken the request waits for a later task.
+/// Profiles are encoded on the blocking pool, so tasks only bear the sampling overhead.
+#[derive(Default)]
+pub struct TaskProfiler {
+    profiles: Mutex<TaskProfiles>,
+}
+
+/// 进行中的任务剖析，释放时结束 / A task profile in progress, finished when dropped
+pub struct TaskProfileGuard {
+    _done: oneshot::Sender<()>,
+}
+
+impl TaskProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 剖析该工作流的下一个任务，返回剖析 ID / Profile the workflow's next task, returning the profile ID
+    pub fn request(&self, workflow_id: &str) -> String {
+        let mut profiles = self.profiles.lock();
+        if let Some(id) = profiles.requested.get(workflow_id) {
+            return id.clone();
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        profiles.requested.insert(workflow_id.to_string(), id.clone());
+        profiles.profiles.insert(id.clone(), TaskProfile::Pending);
+        id
+    }
+
+    pub fn get(&self, profile_id: &str) -> Option<TaskProfile> {
+        self.profiles.lock().profiles.get(profile_id).cloned()
+    }
+
+    /// 若该工作流请求了剖析则开始采样，直到返回值被释放（最长 [`MAX_PROFILE_SECONDS`]）/ Start sampling if the
+    /// workflow has a profile requested, until the returned guard is dropped or [`MAX_PROFILE_SECONDS`] pass
+    pub fn start(self: &Arc<Self>, workflow_id: &str) -> Option<TaskProfileGuard> {
+        if !self.profiles.lock().requested.contains_key(workflow_id) {
+            return None;
+        }
+        let Some(slot) = ProfileSlot::acquire() else {
+            tracing::debug!(workflow_id, "profiler busy, profiling a later task");
+            return None;
+        };
+        let id = self.profiles.lock().requested.remove(workflow_id)?;
+        let guard = match pprof::ProfilerGuardBuilder::default()
+            .frequency(PROFILE_FREQUENCY)
+            .blocklist(PROFILE_BLOCKLIST)
+            .build()
+        {
+            Ok(guard) => guard,
+            Err(e) => {
+                self.finish(id, Err(e.into()));
+                return None;
+            }
+        };
+        metrics::counter!("diagnostics_profiles_total", "kind" => "task").increment(1);
+
+        let (done, finished) = oneshot::channel();
+        let profiler = self.clone();
+        tokio::spawn(async move {
+            let _ = tokio::time::timeout(Duration::from_secs(MAX_PROFILE_SECONDS), finished).await;
+            let profile = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<u8>> {
+                use pprof::protos::Message;
+
+                let report = guard.report().build();
+                // 先停止采样再释放名额 / stop sampling before giving the slot back
+                drop(guard);
+                drop(slot);
+                let mut body = Vec::new();
+                report?.pprof()?.write_to_vec(&mut body)?;
+                Ok(body)
+            })
+            .await;
+            profiler.finish(id, profile.unwrap_or_else(|e| Err(e.into())));
+        });
+        Some(TaskProfileGuard { _done: done })
+    }
+
+    fn finish(&self, id: String, profile: anyhow::Result<Vec<u8>>) {
+        let profile = match profile {
+            Ok(body) => TaskProfile::Ready(Arc::new(body)),
+            Err(e) => {
+                tracing::warn!(profile_id = %id, error = %e, "task profile failed");
+                TaskProfile::Failed(e.to_string())
+            }
+        };
+        let mut profiles = self.profiles.lock();
+        profiles.profiles.insert(id.clone(), profile);
+        profiles.finished.push_back(id);
+        while profiles.finished.len() > MAX_TASK_PROFILES {
+            if let Some(oldest) = profiles.finished.pop_front() {
+                profiles.profiles.remove(&oldest);
+            }
+        }
+    }
+}
+
+async fn request_task_profile(State(profiler): State<Arc<TaskProfiler>>, Path(workflow_id): Path<String>) -> Response {
+    let profile_id = profiler.request(&workflow_id);
+    (StatusCode::ACCEPTED, Json(serde_json::json!({ "profile_id": profile_id }))).into_response()
+}
+
+async fn task_profile(State(profiler): State<Arc<TaskProfiler>>, Path(profile_id): Path<String>) -> Response {
+    match profiler.get(&profile_id) {
+        Some(TaskProfile::Ready(body)) => profile_response(Ok(body.to_vec()), "attachment; filename=\"task.pb\""),
+        Some(TaskProfile::Pending) => (
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({ "code": "PROFILE_PENDING", "message": "the task has not run yet" })),
+        ).into_response(),
+        Some(TaskProfile::Failed(message)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "code": "PROFILE_FAILED", "message": message })),
+        ).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "code": "PROFILE_NOT_FOUND", "message": "unknown or expired profile" })),
+        ).into_response(),
+    }
+}
+
+/// 诊断路由；给出工作者的任务剖析器时提供按执行剖析 / Diagnostics routes, with per-execution task profiles when
+/// given the worker's task profiler
+pub fn router(tasks: Option<Arc<TaskProfiler>>) -> Router {
+    let router = Router::new()
+        .route("/debug/pprof/profile", get(cpu_profile))
+        .route("/debug/pprof/heap", get(heap_profile));
+    match tasks {
+        Some(profiler) => router.merge(
+            Router::new()
+                .route("/debug/pprof/executions/{workflow_id}", post(request_task_profile))
+                .route("/debug/pprof/tasks/{profile_id}", get(task_profile))
+                .with_state(profiler),
+        ),
+        None => router,
+    }
+}
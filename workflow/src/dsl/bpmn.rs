@@ -0,0 +1,896 @@
+//! BPMN 2.0 导入 / BPMN 2.0 import
+//!
+//! 把 BPMN XML（如 Camunda Modeler 保存的图）中的流程转换为 [`WorkflowSpec`]。支持的子集：
+//! Converts a process of a BPMN XML document, such as a diagram saved by Camunda Modeler, into a
+//! [`WorkflowSpec`]. The supported subset:
+//!
+//! - 无事件定义的开始与结束事件 / start and end events without event definitions
+//! - `task`、`serviceTask`、`sendTask` 与 `businessRuleTask` 成为活动步骤。活动名依次取 `camunda:topic`、
+//!   `zeebe:taskDefinition` 的 `type`、`name` 与 `id`；输入为工作流输入，结果保存在以任务 `id` 命名的变量中。
+//!   Tasks become activity steps named by `camunda:topic`, the `type` of `zeebe:taskDefinition`, the `name` or the
+//!   `id`, in that order; they take the workflow input and store their result in a variable named by the task `id`.
+//! - 排他网关成为分支。条件是单个比较（`${amount > 100}`，运算符 `==`、`!=`、`>`、`<` 或 `eq`、`ne`、`gt`、`lt`）
+//!   或单个变量；不是任务结果的变量指工作流输入的字段。
+//!   Exclusive gateways become branches. Conditions are a single comparison (`${amount > 100}` with `==`, `!=`,
+//!   `>`, `<` or `eq`, `ne`, `gt`, `lt`) or a single variable; variables other than task results refer to fields of
+//!   the workflow input.
+//! - 并行网关成为并行块 / Parallel gateways become parallel blocks
+//! - 带 `timeDuration` 的定时中间捕获事件成为等待 / Intermediate timer catch events with a `timeDuration` become sleeps
+//! - 任务上的错误边界事件捕获该任务的任何失败，失败保存在以边界事件 `id` 命名的变量中，错误码不加区分。
+//!   An error boundary event on a task catches any failure of the task, whatever its error code, and stores it in a
+//!   variable named by the boundary event `id`.
+//!
+//! 分叉的网关须在同一个网关汇合，循环不受支持。其他元素与结构以带行列位置的 [`Unsupported`] 报告。
+//! Branches split by a gateway must join at a single gateway, and loops are not supported. Other elements and
+//! structures are reported as [`Unsupported`] with their line and column.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::time::Duration;
+
+use roxmltree::{Document, Node};
+use serde_json::Value;
+
+use super::{ActivityStep, BranchStep, Condition, INPUT, Step, WorkflowSpec};
+
+const MODEL: &str = "http://www.omg.org/spec/BPMN/20100524/MODEL";
+const CAMUNDA: &str = "http://camunda.org/schema/1.0/bpmn";
+const ZEEBE: &str = "http://camunda.org/schema/zeebe/1.0";
+
+/// 流程中与执行无关、导入时忽略的元素 / Process elements without execution semantics, ignored on import
+const IGNORED: &[&str] = &[
+    "documentation",
+    "extensionElements",
+    "laneSet",
+    "textAnnotation",
+    "association",
+    "group",
+    "dataObject",
+    "dataObjectReference",
+    "dataStoreReference",
+];
+
+/// 导入 BPMN 时的错误 / Error importing BPMN
+#[derive(Debug)]
+pub enum BpmnError {
+    /// XML 格式错误 / The XML is not well formed
+    Xml(String),
+    /// 找不到要导入的流程 / There is no process to import
+    NoProcess(String),
+    /// 流程使用了不支持的元素或结构 / The process uses unsupported elements or structures
+    Unsupported(Vec<Unsupported>),
+}
+
+impl fmt::Display for BpmnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BpmnError::Xml(msg) => write!(f, "invalid BPMN XML: {}", msg),
+            BpmnError::NoProcess(msg) => write!(f, "no BPMN process to import: {}", msg),
+            BpmnError::Unsupported(elements) => {
+                write!(f, "unsupported BPMN")?;
+                for (index, element) in elements.iter().enumerate() {
+                    write!(f, "{} {}", if index == 0 { ":" } else { ";" }, element)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BpmnError {}
+
+/// 不支持的元素 / Unsupported element
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsupported {
+    /// 元素名，如 `inclusiveGateway` / Element name such as `inclusiveGateway`
+    pub element: String,
+    pub id: Option<String>,
+    /// 从 1 开始的行号 / 1-based line
+    pub line: u32,
+    /// 从 1 开始的列号 / 1-based column
+    pub column: u32,
+    pub reason: String,
+}
+
+impl Unsupported {
+    fn new(doc: &Document, node: Node, reason: impl Into<String>) -> Self {
+        let position = doc.text_pos_at(node.range().start);
+        Self {
+            element: node.tag_name().name().to_string(),
+            id: node.attribute("id").map(str::to_string),
+            line: position.row,
+            column: position.col,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.element)?;
+        if let Some(id) = &self.id {
+            write!(f, " `{}`", id)?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// 导入文档中唯一的（或唯一可执行的）流程 / Import the only process, or the only executable one, of a document
+pub fn import(xml: &str) -> Result<WorkflowSpec, BpmnError> {
+    let doc = Document::parse(xml).map_err(|e| BpmnError::Xml(e.to_string()))?;
+    let processes: Vec<_> = processes(&doc).collect();
+    let process = match processes.as_slice() {
+        [] => return Err(BpmnError::NoProcess("the document has no process".to_string())),
+        [process] => *process,
+        _ => {
+            let executable: Vec<_> = processes
+                .iter()
+                .filter(|process| process.attribute("isExecutable") == Some("true"))
+                .collect();
+            match executable.as_slice() {
+                [process] => **process,
+                _ => {
+                    return Err(BpmnError::NoProcess(format!(
+                        "the document has {} processes; choose one with `import_process`",
+                        processes.len()
+                    )));
+                }
+            }
+        }
+    };
+    convert(&doc, process)
+}
+
+/// 按 `id` 导入文档中的流程 / Import a process of a document by its `id`
+pub fn import_process(xml: &str, process_id: &str) -> Result<WorkflowSpec, BpmnError> {
+    let doc = Document::parse(xml).map_err(|e| BpmnError::Xml(e.to_string()))?;
+    let process = processes(&doc)
+        .find(|process| process.attribute("id") == Some(process_id))
+        .ok_or_else(|| BpmnError::NoProcess(format!("process not found: {}", process_id)))?;
+    convert(&doc, process)
+}
+
+fn processes<'a, 'input>(doc: &'a Document<'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    doc.root_element().children().filter(|node| is(node, "process"))
+}
+
+fn is(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().namespace() == Some(MODEL) && node.tag_name().name() == name
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| is(child, name))
+}
+
+fn event_definitions<'a, 'input>(node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(|child| child.is_element() && child.tag_name().name().ends_with("EventDefinition"))
+}
+
+fn convert(doc: &Document, process: Node) -> Result<WorkflowSpec, BpmnError> {
+    let graph = Graph::read(doc, process);
+    if !graph.problems.is_empty() {
+        return Err(BpmnError::Unsupported(graph.problems));
+    }
+    let mut builder = Builder {
+        graph: &graph,
+        visited: BTreeSet::new(),
+    };
+    let steps = builder.process().map_err(|problem| BpmnError::Unsupported(vec![problem]))?;
+    let unreachable: Vec<_> = graph
+        .order
+        .iter()
+        .filter(|id| !builder.visited.contains(*id))
+        .map(|id| graph.unsupported(id, "not reachable from the start event"))
+        .collect();
+    if !unreachable.is_empty() {
+        return Err(BpmnError::Unsupported(unreachable));
+    }
+    Ok(WorkflowSpec {
+        name: process.attribute("id").unwrap_or("process").to_string(),
+        description: process.attribute("name").map(str::to_string),
+        steps,
+        output: None,
+    })
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Start,
+    End,
+    Task(String),
+    Timer(Duration),
+    Exclusive { default: Option<String> },
+    Parallel,
+    /// 错误边界事件 / Error boundary event
+    Boundary,
+}
+
+struct FlowNode<'a, 'input> {
+    node: Node<'a, 'input>,
+    kind: Kind,
+    /// 出边在 `Graph::flows` 中的下标 / Indices of the outgoing flows in `Graph::flows`
+    outgoing: Vec<usize>,
+    incoming: usize,
+}
+
+struct SequenceFlow {
+    id: Option<String>,
+    target: String,
+    condition: Option<Condition>,
+}
+
+struct Graph<'a, 'input> {
+    doc: &'a Document<'input>,
+    nodes: HashMap<String, FlowNode<'a, 'input>>,
+    /// 流程节点的文档顺序 / Flow nodes in document order
+    order: Vec<String>,
+    flows: Vec<SequenceFlow>,
+    /// 任务的错误边界事件 / Error boundary event of each task
+    boundaries: HashMap<String, String>,
+    start: Option<String>,
+    problems: Vec<Unsupported>,
+}
+
+impl<'a, 'input> Graph<'a, 'input> {
+    fn read(doc: &'a Document<'input>, process: Node<'a, 'input>) -> Self {
+        let mut graph = Graph {
+            doc,
+            nodes: HashMap::new(),
+            order: Vec::new(),
+            flows: Vec::new(),
+            boundaries: HashMap::new(),
+            start: None,
+            problems: Vec::new(),
+        };
+        let elements: Vec<_> = process
+            .children()
+            .filter(|node| node.is_element() && node.tag_name().namespace() == Some(MODEL))
+            .collect();
+        for node in elements.iter().copied() {
+            let name = node.tag_name().name();
+            if name == "sequenceFlow" || IGNORED.contains(&name) {
+                continue;
+            }
+            match graph.kind(node) {
+                Ok(kind) => graph.add(node, kind),
+                Err(reason) => graph.report(node, reason),
+            }
+        }
+        let results: BTreeSet<String> = graph
+            .nodes
+            .iter()
+            .filter(|(_, node)| matches!(node.kind, Kind::Task(_) | Kind::Boundary))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for node in elements.iter().copied().filter(|node| is(node, "sequenceFlow")) {
+            graph.flow(node, &results);
+        }
+        // Counting flows is only meaningful once every element and flow was understood
+        if graph.problems.is_empty() {
+            graph.check();
+        }
+        graph
+    }
+
+    fn report(&mut self, node: Node, reason: impl Into<String>) {
+        self.problems.push(Unsupported::new(self.doc, node, reason));
+    }
+
+    fn unsupported(&self, id: &str, reason: impl Into<String>) -> Unsupported {
+        Unsupported::new(self.doc, self.nodes[id].node, reason)
+    }
+
+    fn kind(&self, node: Node) -> Result<Kind, String> {
+        let name = node.tag_name().name();
+        let mut definitions = event_definitions(node);
+        match name {
+            "startEvent" => match definitions.next() {
+                Some(definition) => Err(format!("start events with a {} are not supported", definition.tag_name().name())),
+                None => Ok(Kind::Start),
+            },
+            "endEvent" => match definitions.next() {
+                Some(definition) => Err(format!("end events with a {} are not supported", definition.tag_name().name())),
+                None => Ok(Kind::End),
+            },
+            "task" | "serviceTask" | "sendTask" | "businessRuleTask" => {
+                if node
+                    .children()
+                    .any(|child| is(&child, "multiInstanceLoopCharacteristics") || is(&child, "standardLoopCharacteristics"))
+                {
+                    return Err("loop and multi-instance tasks are not supported".to_string());
+                }
+                Ok(Kind::Task(activity_name(node)))
+            }
+            "exclusiveGateway" => Ok(Kind::Exclusive {
+                default: node.attribute("default").map(str::to_string),
+            }),
+            "parallelGateway" => Ok(Kind::Parallel),
+            "intermediateCatchEvent" => match (definitions.next(), definitions.next()) {
+                (Some(definition), None) if is(&definition, "timerEventDefinition") => timer(definition).map(Kind::Timer),
+                (Some(definition), None) => Err(format!(
+                    "intermediate events with a {} are not supported; only timers are",
+                    definition.tag_name().name()
+                )),
+                _ => Err("intermediate catch events need exactly one event definition".to_string()),
+            },
+            "boundaryEvent" => match (definitions.next(), definitions.next()) {
+                (Some(definition), None) if is(&definition, "errorEventDefinition") => Ok(Kind::Boundary),
+                _ => Err("only error boundary events are supported".to_string()),
+            },
+            "userTask" | "manualTask" => Err("human tasks are not supported".to_string()),
+            "scriptTask" => Err("script tasks are not supported; implement the script as an activity".to_string()),
+            _ => Err(format!("{} elements are not supported", name)),
+        }
+    }
+
+    fn add(&mut self, node: Node<'a, 'input>, kind: Kind) {
+        let Some(id) = node.attribute("id") else {
+            self.report(node, "the element has no id");
+            return;
+        };
+        match &kind {
+            Kind::Start if self.start.is_some() => {
+                self.report(node, "only one start event is supported");
+                return;
+            }
+            Kind::Start => self.start = Some(id.to_string()),
+            Kind::Boundary => {
+                let attached = node.attribute("attachedToRef").unwrap_or_default();
+                if self.boundaries.insert(attached.to_string(), id.to_string()).is_some() {
+                    self.report(node, format!("`{}` already has an error boundary event", attached));
+                    return;
+                }
+            }
+            _ => {}
+        }
+        self.order.push(id.to_string());
+        self.nodes.insert(
+            id.to_string(),
+            FlowNode {
+                node,
+                kind,
+                outgoing: Vec::new(),
+                incoming: 0,
+            },
+        );
+    }
+
+    fn flow(&mut self, node: Node, results: &BTreeSet<String>) {
+        let (source, target) = (node.attribute("sourceRef").unwrap_or_default(), node.attribute("targetRef").unwrap_or_default());
+        if !self.nodes.contains_key(source) || !self.nodes.contains_key(target) {
+            // The endpoint was either reported already or does not exist
+            if !self.problems.iter().any(|problem| [source, target].contains(&problem.id.as_deref().unwrap_or_default())) {
+                self.report(node, "the flow connects elements that do not exist");
+            }
+            return;
+        }
+        let condition = match child(node, "conditionExpression") {
+            None => None,
+            Some(_) if !matches!(self.nodes[source].kind, Kind::Exclusive { .. }) => {
+                self.report(node, "conditions are only supported on flows out of exclusive gateways");
+                return;
+            }
+            Some(expression) => match condition(expression.text().unwrap_or_default(), results) {
+                Ok(condition) => Some(condition),
+                Err(reason) => {
+                    self.report(expression, reason);
+                    return;
+                }
+            },
+        };
+        self.flows.push(SequenceFlow {
+            id: node.attribute("id").map(str::to_string),
+            target: target.to_string(),
+            condition,
+        });
+        let index = self.flows.len() - 1;
+        self.nodes.get_mut(source).expect("checked above").outgoing.push(index);
+        self.nodes.get_mut(target).expect("checked above").incoming += 1;
+    }
+
+    /// 检查各节点的出入边数 / Check the number of flows in and out of each node
+    fn check(&mut self) {
+        if self.start.is_none() {
+            self.problems.push(Unsupported {
+                element: "process".to_string(),
+                id: None,
+                line: 1,
+                column: 1,
+                reason: "the process has no start event".to_string(),
+            });
+        }
+        let mut problems = Vec::new();
+        for id in &self.order {
+            let node = &self.nodes[id];
+            let outgoing = node.outgoing.len();
+            let problem = match &node.kind {
+                Kind::End if outgoing > 0 => Some("end events cannot have outgoing flows"),
+                Kind::End => None,
+                Kind::Exclusive { .. } | Kind::Parallel if outgoing == 0 => Some("gateways need an outgoing flow"),
+                Kind::Exclusive { .. } | Kind::Parallel => None,
+                Kind::Boundary if !matches!(
+                    self.nodes.get(node.node.attribute("attachedToRef").unwrap_or_default()).map(|task| &task.kind),
+                    Some(Kind::Task(_))
+                ) =>
+                {
+                    Some("error boundary events must be attached to a task")
+                }
+                _ if outgoing != 1 => Some("needs exactly one outgoing flow; split the flow with a gateway"),
+                _ => None,
+            };
+            if let Some(reason) = problem {
+                problems.push(Unsupported::new(self.doc, node.node, reason));
+            }
+        }
+        self.problems.extend(problems);
+    }
+
+    fn incoming(&self, id: &str) -> usize {
+        self.nodes[id].incoming
+    }
+
+    fn next(&self, id: &str) -> &str {
+        &self.flows[self.nodes[id].outgoing[0]].target
+    }
+}
+
+fn activity_name(node: Node) -> String {
+    let zeebe = node
+        .children()
+        .filter(|child| is(child, "extensionElements"))
+        .flat_map(|extensions| extensions.children())
+        .find(|child| child.tag_name().namespace() == Some(ZEEBE) && child.tag_name().name() == "taskDefinition")
+        .and_then(|definition| definition.attribute("type"));
+    node.attribute((CAMUNDA, "topic"))
+        .or(zeebe)
+        .or(node.attribute("name"))
+        .or(node.attribute("id"))
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn timer(definition: Node) -> Result<Duration, String> {
+    if child(definition, "timeDate").is_some() || child(definition, "timeCycle").is_some() {
+        return Err("only timers with a timeDuration are supported".to_string());
+    }
+    let duration = child(definition, "timeDuration").ok_or("the timer has no timeDuration")?;
+    parse_iso_duration(duration.text().unwrap_or_default())
+}
+
+/// 解析 `PT30S`、`P1DT12H`、`P2W` 形式的 ISO 8601 时长 / Parse ISO 8601 durations such as `PT30S`, `P1DT12H` or `P2W`
+pub fn parse_iso_duration(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid ISO 8601 duration: {:?}", text);
+    let mut rest = text.trim().strip_prefix('P').filter(|rest| !rest.is_empty()).ok_or_else(invalid)?;
+    let mut time = false;
+    let mut seconds = 0.0;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('T').filter(|after| !time && !after.is_empty()) {
+            time = true;
+            rest = after;
+        }
+        let split = rest.find(|c: char| !c.is_ascii_digit() && c != '.').ok_or_else(invalid)?;
+        let amount: f64 = rest[..split].parse().map_err(|_| invalid())?;
+        let unit = match (time, rest[split..].chars().next()) {
+            (false, Some('W')) => 604_800.0,
+            (false, Some('D')) => 86_400.0,
+            (false, Some('Y' | 'M')) => return Err(format!("{:?}: years and months have no fixed length", text)),
+            (true, Some('H')) => 3_600.0,
+            (true, Some('M')) => 60.0,
+            (true, Some('S')) => 1.0,
+            _ => return Err(invalid()),
+        };
+        seconds += amount * unit;
+        rest = &rest[split + 1..];
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+/// 把 JUEL 条件转换为 [`Condition`] / Convert a JUEL condition into a [`Condition`]
+fn condition(expression: &str, results: &BTreeSet<String>) -> Result<Condition, String> {
+    let body = expression
+        .trim()
+        .strip_prefix("${")
+        .and_then(|body| body.strip_suffix('}'))
+        .ok_or_else(|| format!("unsupported condition {:?}; write it as ${{...}}", expression.trim()))?
+        .trim();
+    let compound = ["&&", "||", " and ", " or ", "?", "empty ", "not "];
+    if compound.iter().any(|token| body.contains(token)) || (body.starts_with('!') && !body.starts_with("!=")) {
+        return Err(format!("unsupported condition {:?}; only a single comparison or variable is supported", body));
+    }
+    let operators = [
+        (">=", None),
+        ("<=", None),
+        (" ge ", None),
+        (" le ", None),
+        ("==", Some("==")),
+        ("!=", Some("!=")),
+        (">", Some(">")),
+        ("<", Some("<")),
+        (" eq ", Some("==")),
+        (" ne ", Some("!=")),
+        (" gt ", Some(">")),
+        (" lt ", Some("<")),
+    ];
+    let Some((position, token, operator)) = operators
+        .iter()
+        .filter_map(|(token, operator)| body.find(token).map(|position| (position, *token, *operator)))
+        .min_by_key(|(position, token, _)| (*position, usize::MAX - token.len()))
+    else {
+        return Ok(Condition {
+            var: variable(body, results)?,
+            ..Condition::default()
+        });
+    };
+    let operator = operator.ok_or_else(|| format!("unsupported operator `{}` in {:?}; use `>` or `<`", token.trim(), body))?;
+    let var = variable(body[..position].trim(), results)?;
+    let literal = literal(body[position + token.len()..].trim())
+        .ok_or_else(|| format!("unsupported condition {:?}; compare a variable with a literal", body))?;
+    let bound = || literal.as_f64().ok_or_else(|| format!("`{}` needs a number in {:?}", operator, body));
+    let mut condition = Condition {
+        var,
+        ..Condition::default()
+    };
+    match operator {
+        "==" => condition.equals = Some(literal.clone()),
+        "!=" => condition.not_equals = Some(literal.clone()),
+        ">" => condition.greater_than = Some(bound()?),
+        _ => condition.less_than = Some(bound()?),
+    }
+    Ok(condition)
+}
+
+/// 任务结果按名引用，其余变量指工作流输入的字段 / Task results are referenced by name, other variables are input fields
+fn variable(path: &str, results: &BTreeSet<String>) -> Result<String, String> {
+    let valid = !path.is_empty()
+        && !path.starts_with(|c: char| c.is_ascii_digit())
+        && path.split('.').all(|segment| !segment.is_empty() && segment.chars().all(|c| c.is_alphanumeric() || c == '_'));
+    if !valid {
+        return Err(format!("unsupported condition operand {:?}; the left side must be a variable", path));
+    }
+    let root = path.split('.').next().unwrap_or_default();
+    Ok(if results.contains(root) || root == INPUT {
+        path.to_string()
+    } else {
+        format!("{}.{}", INPUT, path)
+    })
+}
+
+fn literal(text: &str) -> Option<Value> {
+    let quoted = |quote: char| text.strip_prefix(quote).and_then(|text| text.strip_suffix(quote));
+    if let Some(text) = quoted('\'').or_else(|| quoted('"')) {
+        return Some(Value::String(text.to_string()));
+    }
+    match text {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        "null" => Some(Value::Null),
+        _ => serde_json::from_str::<serde_json::Number>(text).ok().map(Value::Number),
+    }
+}
+
+/// 把图转换为嵌套的步骤 / Turns the graph into nested steps
+struct Builder<'g, 'a, 'input> {
+    graph: &'g Graph<'a, 'input>,
+    visited: BTreeSet<String>,
+}
+
+impl Builder<'_, '_, '_> {
+    fn process(&mut self) -> Result<Vec<Step>, Unsupported> {
+        let start = self.graph.start.clone().unwrap_or_default();
+        let mut steps = Vec::new();
+        match self.path(&start, &mut steps)? {
+            Some(merge) => Err(self.graph.unsupported(
+                &merge,
+                "joins flows that no gateway split; loops and unstructured flows are not supported",
+            )),
+            None => Ok(steps),
+        }
+    }
+
+    /// 从 `id` 起的路径，返回停下时遇到的汇合节点，到达结束事件时为 `None`
+    /// Path from `id`; returns the merging node it stopped at, or `None` at an end event
+    fn path(&mut self, id: &str, steps: &mut Vec<Step>) -> Result<Option<String>, Unsupported> {
+        let mut id = id.to_string();
+        loop {
+            if !self.visited.insert(id.clone()) {
+                return Err(self.graph.unsupported(&id, "the flow returns to this element; loops are not supported"));
+            }
+            let node = &self.graph.nodes[&id];
+            match &node.kind {
+                Kind::End => return Ok(None),
+                Kind::Task(activity) => {
+                    let error = self.graph.boundaries.get(&id).cloned();
+                    steps.push(Step::Activity(ActivityStep {
+                        name: activity.clone(),
+                        input: Value::String(format!("${{{}}}", INPUT)),
+                        result: Some(id.clone()),
+                        error: error.clone(),
+                        task_queue: None,
+                        timeout: None,
+                        schedule_to_close_timeout: None,
+                        retry: None,
+                    }));
+                    if let Some(boundary) = error {
+                        self.visited.insert(boundary.clone());
+                        let caught = Condition {
+                            var: boundary.clone(),
+                            exists: Some(true),
+                            ..Condition::default()
+                        };
+                        let options = vec![
+                            (Some(caught), self.graph.next(&boundary).to_string()),
+                            (None, self.graph.next(&id).to_string()),
+                        ];
+                        return self.choice(&id, options, steps);
+                    }
+                }
+                Kind::Timer(duration) => steps.push(Step::Sleep(*duration)),
+                Kind::Exclusive { default } if node.outgoing.len() > 1 => {
+                    let mut options = Vec::new();
+                    let mut otherwise = None;
+                    for flow in node.outgoing.iter().map(|index| &self.graph.flows[*index]) {
+                        match &flow.condition {
+                            _ if flow.id.is_some() && flow.id == *default => otherwise = Some(flow.target.clone()),
+                            Some(condition) => options.push((Some(condition.clone()), flow.target.clone())),
+                            None if otherwise.is_none() && default.is_none() => otherwise = Some(flow.target.clone()),
+                            None => {
+                                return Err(self.graph.unsupported(&id, "only one outgoing flow may lack a condition"));
+                            }
+                        }
+                    }
+                    options.extend(otherwise.map(|target| (None, target)));
+                    return self.choice(&id, options, steps);
+                }
+                Kind::Parallel if node.outgoing.len() > 1 => return self.fork(&id, steps),
+                Kind::Start | Kind::Exclusive { .. } | Kind::Parallel | Kind::Boundary => {}
+            }
+            let next = self.graph.next(&id);
+            if self.graph.incoming(next) > 1 {
+                return Ok(Some(next.to_string()));
+            }
+            id = next.to_string();
+        }
+    }
+
+    /// 从 `target` 起直到汇合节点的分支 / Branch from `target` up to a merging node
+    fn branch(&mut self, target: &str) -> Result<(Vec<Step>, Option<String>), Unsupported> {
+        let mut steps = Vec::new();
+        if self.graph.incoming(target) > 1 {
+            return Ok((steps, Some(target.to_string())));
+        }
+        let end = self.path(target, &mut steps)?;
+        Ok((steps, end))
+    }
+
+    /// 检查分支汇合于同一节点且只汇合这些分支 / Check that the branches join at one node, and only these branches
+    fn join(&self, split: &str, ends: &[Option<String>]) -> Result<Option<String>, Unsupported> {
+        let joins: BTreeSet<_> = ends.iter().flatten().collect();
+        if joins.len() > 1 {
+            return Err(self.graph.unsupported(split, "the branches split here must join at a single gateway"));
+        }
+        let Some(join) = joins.into_iter().next() else {
+            return Ok(None);
+        };
+        let joined = ends.iter().flatten().count();
+        if self.graph.incoming(join) != joined {
+            return Err(self.graph.unsupported(
+                join,
+                format!("joins branches split at `{}` together with other flows; unstructured flows are not supported", split),
+            ));
+        }
+        Ok(Some(join.clone()))
+    }
+
+    fn fork(&mut self, id: &str, steps: &mut Vec<Step>) -> Result<Option<String>, Unsupported> {
+        let mut lanes = Vec::new();
+        let mut ends = Vec::new();
+        for index in &self.graph.nodes[id].outgoing {
+            let (lane, end) = self.branch(&self.graph.flows[*index].target)?;
+            lanes.push(lane);
+            ends.push(end);
+        }
+        let join = self.join(id, &ends)?;
+        steps.push(Step::Parallel(lanes));
+        match join {
+            None => Ok(None),
+            Some(_) if ends.iter().any(Option::is_none) => Err(self.graph.unsupported(
+                id,
+                "branches of a parallel gateway must either all end or all join at a parallel gateway",
+            )),
+            Some(join) if !matches!(self.graph.nodes[&join].kind, Kind::Parallel) => {
+                Err(self.graph.unsupported(&join, format!("parallel branches split at `{}` must join at a parallel gateway", id)))
+            }
+            Some(join) => self.path(&join, steps),
+        }
+    }
+
+    /// 按顺序检查条件、选择一条分支；无条件的选项须在最后
+    /// Choose the first option whose condition holds; an option without a condition must come last
+    fn choice(
+        &mut self,
+        id: &str,
+        options: Vec<(Option<Condition>, String)>,
+        steps: &mut Vec<Step>,
+    ) -> Result<Option<String>, Unsupported> {
+        let mut arms = Vec::new();
+        let mut ends = Vec::new();
+        for (condition, target) in options {
+            let (arm, end) = self.branch(&target)?;
+            arms.push((condition, arm));
+            ends.push(end);
+        }
+        let join = self.join(id, &ends)?;
+        let mut tail = Vec::new();
+        let mut stopped = None;
+        if let Some(join) = &join {
+            if matches!(self.graph.nodes[join].kind, Kind::Parallel) {
+                return Err(self.graph.unsupported(join, format!("exclusive branches split at `{}` cannot join at a parallel gateway", id)));
+            }
+            stopped = self.path(join, &mut tail)?;
+        }
+        // Branches ending the process must not run the steps after the join, so those go into the other branches
+        let inline = join.is_some() && ends.iter().any(Option::is_none);
+        if inline && stopped.is_some() {
+            return Err(self.graph.unsupported(
+                id,
+                "branches ending the process cannot be mixed with branches continuing past an enclosing join",
+            ));
+        }
+        let with_tail = |mut arm: Vec<Step>, end: &Option<String>| {
+            if inline && end.is_some() {
+                arm.extend(tail.iter().cloned());
+            }
+            arm
+        };
+        let mut arms: Vec<_> = arms.into_iter().zip(&ends).collect();
+        let mut otherwise = match arms.last() {
+            Some(((None, _), _)) => {
+                let ((_, arm), end) = arms.pop().expect("checked above");
+                with_tail(arm, end)
+            }
+            _ if inline => tail.clone(),
+            _ => Vec::new(),
+        };
+        for ((condition, arm), end) in arms.into_iter().rev() {
+            otherwise = vec![Step::Branch(BranchStep {
+                condition: condition.expect("only the last option lacks a condition"),
+                then: with_tail(arm, end),
+                otherwise,
+            })];
+        }
+        steps.extend(otherwise);
+        if !inline {
+            steps.extend(tail);
+        }
+        Ok(stopped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::DynamicWorkflow;
+
+    fn definitions(process: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:camunda="http://camunda.org/schema/1.0/bpmn" id="Definitions_1">
+  <bpmn:process id="order" name="Order handling" isExecutable="true">
+{}
+  </bpmn:process>
+</bpmn:definitions>
+"#,
+            process
+        )
+    }
+
+    const ORDER: &str = r#"
+    <bpmn:startEvent id="start" />
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="charge" />
+    <bpmn:serviceTask id="charge" name="Charge card" camunda:type="external" camunda:topic="charge" />
+    <bpmn:boundaryEvent id="declined" attachedToRef="charge"><bpmn:errorEventDefinition /></bpmn:boundaryEvent>
+    <bpmn:sequenceFlow id="f2" sourceRef="declined" targetRef="refund" />
+    <bpmn:serviceTask id="refund" name="refund" />
+    <bpmn:sequenceFlow id="f3" sourceRef="refund" targetRef="failed" />
+    <bpmn:endEvent id="failed" />
+    <bpmn:sequenceFlow id="f4" sourceRef="charge" targetRef="size" />
+    <bpmn:exclusiveGateway id="size" default="small" />
+    <bpmn:sequenceFlow id="large" sourceRef="size" targetRef="fork">
+      <bpmn:conditionExpression xsi:type="bpmn:tFormalExpression" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">${amount &gt; 100}</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+    <bpmn:sequenceFlow id="small" sourceRef="size" targetRef="merge" />
+    <bpmn:parallelGateway id="fork" />
+    <bpmn:sequenceFlow id="f5" sourceRef="fork" targetRef="ship" />
+    <bpmn:sequenceFlow id="f6" sourceRef="fork" targetRef="notify" />
+    <bpmn:sendTask id="ship" camunda:topic="ship" />
+    <bpmn:task id="notify" name="notify" />
+    <bpmn:sequenceFlow id="f7" sourceRef="ship" targetRef="joined" />
+    <bpmn:sequenceFlow id="f8" sourceRef="notify" targetRef="joined" />
+    <bpmn:parallelGateway id="joined" />
+    <bpmn:sequenceFlow id="f9" sourceRef="joined" targetRef="merge" />
+    <bpmn:exclusiveGateway id="merge" />
+    <bpmn:sequenceFlow id="f10" sourceRef="merge" targetRef="wait" />
+    <bpmn:intermediateCatchEvent id="wait">
+      <bpmn:timerEventDefinition><bpmn:timeDuration>PT1H30M</bpmn:timeDuration></bpmn:timerEventDefinition>
+    </bpmn:intermediateCatchEvent>
+    <bpmn:sequenceFlow id="f11" sourceRef="wait" targetRef="done" />
+    <bpmn:endEvent id="done" />
+"#;
+
+    #[test]
+    fn test_converts_gateways_timers_and_error_boundaries() {
+        let expected = DynamicWorkflow::from_yaml(
+            r#"
+name: order
+description: Order handling
+steps:
+  - activity: { name: charge, input: "${input}", result: charge, error: declined }
+  - branch:
+      if: { var: declined, exists: true }
+      then:
+        - activity: { name: refund, input: "${input}", result: refund }
+      else:
+        - branch:
+            if: { var: input.amount, greater_than: 100 }
+            then:
+              - parallel:
+                  - - activity: { name: ship, input: "${input}", result: ship }
+                  - - activity: { name: notify, input: "${input}", result: notify }
+        - sleep: 90m
+"#,
+        )
+        .unwrap();
+        assert_eq!(import(&definitions(ORDER)).unwrap(), *expected.spec());
+        assert_eq!(parse_iso_duration("P1DT0.5S"), Ok(Duration::from_millis(86_400_500)));
+        assert!(parse_iso_duration("P1M").unwrap_err().contains("no fixed length"));
+    }
+
+    #[test]
+    fn test_reports_unsupported_elements_with_positions() {
+        let process = r#"    <bpmn:startEvent id="start" />
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="choose" />
+    <bpmn:inclusiveGateway id="choose" />
+    <bpmn:userTask id="approve" />
+    <bpmn:exclusiveGateway id="check" />
+    <bpmn:sequenceFlow id="f2" sourceRef="check" targetRef="end">
+      <bpmn:conditionExpression>${amount &gt;= 10}</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+    <bpmn:endEvent id="end" />"#;
+        let Err(BpmnError::Unsupported(problems)) = import(&definitions(process)) else {
+            panic!("expected unsupported elements");
+        };
+        let found: Vec<_> = problems
+            .iter()
+            .map(|problem| (problem.element.as_str(), problem.line, problem.reason.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("inclusiveGateway", 6, "inclusiveGateway elements are not supported"),
+                ("userTask", 7, "human tasks are not supported"),
+                ("conditionExpression", 10, "unsupported operator `>=` in \"amount >= 10\"; use `>` or `<`"),
+            ]
+        );
+        assert_eq!(problems[0].column, 5);
+    }
+
+    #[test]
+    fn test_rejects_loops() {
+        let process = r#"    <bpmn:startEvent id="start" />
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="retry" />
+    <bpmn:exclusiveGateway id="retry" />
+    <bpmn:sequenceFlow id="f2" sourceRef="retry" targetRef="poll" />
+    <bpmn:serviceTask id="poll" />
+    <bpmn:sequenceFlow id="f3" sourceRef="poll" targetRef="ready" />
+    <bpmn:exclusiveGateway id="ready" />
+    <bpmn:sequenceFlow id="f4" sourceRef="ready" targetRef="end">
+      <bpmn:conditionExpression>${poll.done}</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+    <bpmn:sequenceFlow id="f5" sourceRef="ready" targetRef="retry" />
+    <bpmn:endEvent id="end" />"#;
+        let error = import(&definitions(process)).unwrap_err();
+        assert!(matches!(&error, BpmnError::Unsupported(problems) if problems[0].id.as_deref() == Some("retry")), "{}", error);
+        assert!(error.to_string().contains("loops"), "{}", error);
+    }
+}
@@ -0,0 +1,249 @@
+//! 工作流结构图 / Workflow structure diagrams
+//!
+//! 把 [`WorkflowSpec`] 的步骤画成 Graphviz DOT 或 Mermaid 流程图：活动与等待为节点，分支为菱形判断，
+//! 并行块为分叉与汇合。
+//! Draws the steps of a [`WorkflowSpec`] as a Graphviz DOT or Mermaid flowchart: activities and sleeps are
+//! nodes, branches are decision diamonds and parallel blocks fork and join.
+
+use std::fmt::Write;
+
+use serde::Deserialize;
+
+use super::spec::format_duration;
+use super::{Condition, Step, WorkflowSpec};
+
+/// 图的文本格式 / Text format of a diagram
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagramFormat {
+    /// Graphviz DOT
+    Dot,
+    #[default]
+    Mermaid,
+}
+
+impl DiagramFormat {
+    pub fn render(self, spec: &WorkflowSpec) -> String {
+        match self {
+            DiagramFormat::Dot => to_dot(spec),
+            DiagramFormat::Mermaid => to_mermaid(spec),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            DiagramFormat::Dot => "text/vnd.graphviz; charset=utf-8",
+            DiagramFormat::Mermaid => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+/// 渲染为 Graphviz DOT / Render as Graphviz DOT
+pub fn to_dot(spec: &WorkflowSpec) -> String {
+    let diagram = Diagram::of(spec);
+    let mut out = String::new();
+    let _ = writeln!(out, "digraph {} {{", dot_quote(&spec.name));
+    out.push_str("  rankdir=TB;\n  node [fontname=\"Helvetica\"];\n");
+    for (id, (shape, label)) in diagram.nodes.iter().enumerate() {
+        let attributes = match shape {
+            Shape::Start => "shape=circle".to_string(),
+            Shape::End => "shape=doublecircle".to_string(),
+            Shape::Activity => "shape=box, style=rounded".to_string(),
+            Shape::Sleep => "shape=ellipse, style=dashed".to_string(),
+            Shape::Decision => "shape=diamond".to_string(),
+            Shape::Fork | Shape::Join => "shape=box, style=filled, fillcolor=black, height=0.1, width=1.5".to_string(),
+        };
+        let label = match shape {
+            Shape::Fork | Shape::Join => String::new(),
+            _ => label.clone(),
+        };
+        let _ = writeln!(out, "  n{} [label={}, {}];", id, dot_quote(&label), attributes);
+    }
+    for (from, to, label) in &diagram.edges {
+        match label {
+            Some(label) => {
+                let _ = writeln!(out, "  n{} -> n{} [label={}];", from, to, dot_quote(label));
+            }
+            None => {
+                let _ = writeln!(out, "  n{} -> n{};", from, to);
+            }
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// 渲染为 Mermaid 流程图 / Render as a Mermaid flowchart
+pub fn to_mermaid(spec: &WorkflowSpec) -> String {
+    let diagram = Diagram::of(spec);
+    let mut out = String::from("flowchart TD\n");
+    for (id, (shape, label)) in diagram.nodes.iter().enumerate() {
+        let label = mermaid_quote(label);
+        let _ = match shape {
+            Shape::Start | Shape::End => writeln!(out, "  n{}(({}))", id, label),
+            Shape::Activity => writeln!(out, "  n{}[{}]", id, label),
+            Shape::Sleep => writeln!(out, "  n{}([{}])", id, label),
+            Shape::Decision => writeln!(out, "  n{}{{{}}}", id, label),
+            Shape::Fork | Shape::Join => writeln!(out, "  n{}{{{{{}}}}}", id, label),
+        };
+    }
+    for (from, to, label) in &diagram.edges {
+        let _ = match label {
+            Some(label) => writeln!(out, "  n{} -->|{}| n{}", from, mermaid_quote(label), to),
+            None => writeln!(out, "  n{} --> n{}", from, to),
+        };
+    }
+    out
+}
+
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn mermaid_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "#quot;"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Start,
+    End,
+    Activity,
+    Sleep,
+    Decision,
+    Fork,
+    Join,
+}
+
+/// 与格式无关的节点与边 / Nodes and edges independent of the format
+#[derive(Debug, Default)]
+struct Diagram {
+    nodes: Vec<(Shape, String)>,
+    edges: Vec<(usize, usize, Option<String>)>,
+}
+
+/// 尚未连到下一节点的出边 / Edges still waiting for the next node
+type Exits = Vec<(usize, Option<String>)>;
+
+impl Diagram {
+    fn of(spec: &WorkflowSpec) -> Self {
+        let mut diagram = Diagram::default();
+        let start = diagram.node(Shape::Start, "start", Vec::new());
+        let exits = diagram.steps(&spec.steps, vec![(start, None)]);
+        diagram.node(Shape::End, "end", exits);
+        diagram
+    }
+
+    fn node(&mut self, shape: Shape, label: impl Into<String>, exits: Exits) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push((shape, label.into()));
+        self.edges.extend(exits.into_iter().map(|(from, label)| (from, id, label)));
+        id
+    }
+
+    fn steps(&mut self, steps: &[Step], mut exits: Exits) -> Exits {
+        for step in steps {
+            exits = match step {
+                Step::Activity(activity) => {
+                    let mut label = activity.name.clone();
+                    if let Some(result) = &activity.result {
+                        label = format!("{} → {}", label, result);
+                    }
+                    let id = self.node(Shape::Activity, label, exits);
+                    vec![(id, None)]
+                }
+                Step::Sleep(duration) => vec![(self.node(Shape::Sleep, format!("sleep {}", format_duration(*duration)), exits), None)],
+                Step::Branch(branch) => {
+                    let decision = self.node(Shape::Decision, describe(&branch.condition), exits);
+                    let mut joined = self.steps(&branch.then, vec![(decision, Some("yes".to_string()))]);
+                    joined.extend(self.steps(&branch.otherwise, vec![(decision, Some("no".to_string()))]));
+                    joined
+                }
+                Step::Parallel(lanes) => {
+                    let fork = self.node(Shape::Fork, "parallel", exits);
+                    let joined = lanes.iter().flat_map(|lane| self.steps(lane, vec![(fork, None)])).collect();
+                    vec![(self.node(Shape::Join, "join", joined), None)]
+                }
+            };
+        }
+        exits
+    }
+}
+
+fn describe(condition: &Condition) -> String {
+    let mut checks = Vec::new();
+    if let Some(value) = &condition.equals {
+        checks.push(format!("{} == {}", condition.var, value));
+    }
+    if let Some(value) = &condition.not_equals {
+        checks.push(format!("{} != {}", condition.var, value));
+    }
+    if let Some(bound) = condition.greater_than {
+        checks.push(format!("{} > {}", condition.var, bound));
+    }
+    if let Some(bound) = condition.less_than {
+        checks.push(format!("{} < {}", condition.var, bound));
+    }
+    match condition.exists {
+        Some(true) => checks.push(format!("{} exists", condition.var)),
+        Some(false) => checks.push(format!("{} is missing", condition.var)),
+        None => {}
+    }
+    if checks.is_empty() {
+        condition.var.clone()
+    } else {
+        checks.join(" and ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::DynamicWorkflow;
+
+    const SPEC: &str = r#"
+name: order
+steps:
+  - activity: { name: charge, result: payment }
+  - branch:
+      if: { var: payment.status, equals: approved }
+      then:
+        - parallel:
+            - - activity: { name: ship }
+            - []
+      else:
+        - sleep: 1d
+"#;
+
+    #[test]
+    fn test_renders_dot_and_mermaid() {
+        let workflow = DynamicWorkflow::from_yaml(SPEC).unwrap();
+        assert_eq!(
+            to_mermaid(workflow.spec()),
+            r#"flowchart TD
+  n0(("start"))
+  n1["charge → payment"]
+  n2{"payment.status == #quot;approved#quot;"}
+  n3{{"parallel"}}
+  n4["ship"]
+  n5{{"join"}}
+  n6(["sleep 1d"])
+  n7(("end"))
+  n0 --> n1
+  n1 --> n2
+  n2 -->|"yes"| n3
+  n3 --> n4
+  n4 --> n5
+  n3 --> n5
+  n2 -->|"no"| n6
+  n5 --> n7
+  n6 --> n7
+"#
+        );
+        let dot = DiagramFormat::Dot.render(workflow.spec());
+        assert!(dot.starts_with("digraph \"order\" {\n"), "{}", dot);
+        assert!(dot.contains("  n2 [label=\"payment.status == \\\"approved\\\"\", shape=diamond];\n"), "{}", dot);
+        assert!(dot.contains("  n2 -> n6 [label=\"no\"];\n"), "{}", dot);
+        assert!(dot.ends_with("  n6 -> n7;\n}\n"), "{}", dot);
+    }
+}
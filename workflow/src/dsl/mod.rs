@@ -0,0 +1,481 @@
+//! 声明式工作流模块 / Declarative Workflow Module
+//! 从 YAML 或 JSON 规范加载工作流：活动、重试、超时、条件分支与并行块，无需重新编译即可定义简单流水线
+//! Loads workflows from YAML or JSON specs with activities, retries, timeouts, branches and parallel blocks, so
+//! simple pipelines can be defined without recompiling
+//!
+//! [`DynamicWorkflow`] 解释一个 [`WorkflowSpec`]，像 Rust 工作流一样注册到工作者并记录事件历史；
+//! 它调用的活动仍需在工作者上注册。
+//! A [`DynamicWorkflow`] interprets a [`WorkflowSpec`], is registered with a worker like a Rust workflow and records
+//! the same event history; the activities it calls still have to be registered on a worker.
+//!
+//! [`Validator`] 在加载前检查规范，并以带行列位置的 [`Diagnostic`] 报告问题。
+//! A [`Validator`] checks a spec before loading it and reports problems as [`Diagnostic`]s with line and column.
+//!
+//! ```no_run
+//! # async fn example(worker: workflow::temporal::WorkflowWorker) -> Result<(), Box<dyn std::error::Error>> {
+//! use workflow::dsl::Validator;
+//! use workflow::temporal::StartWorkflowOptions;
+//!
+//! let pipeline = Validator::new()
+//!     .with_activities(worker.registered_activities())
+//!     .load_file("pipelines/order.yaml")?;
+//! let name = pipeline.name().to_string();
+//! worker.register_dynamic_workflow(pipeline);
+//! let handle = worker
+//!     .client()
+//!     .start_dynamic_workflow(&name, serde_json::json!({"amount": 100}), StartWorkflowOptions::default())
+//!     .await?;
+//! println!("{}", handle.result().await?);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use futures::future::{self, BoxFuture, FutureExt};
+use serde_json::{Map, Value};
+
+use crate::temporal::{WorkflowContext, WorkflowError};
+
+#[cfg(feature = "bpmn")]
+pub mod bpmn;
+pub mod diagram;
+pub mod spec;
+pub mod validate;
+
+pub use diagram::DiagramFormat;
+pub use spec::{ActivityStep, BranchStep, Condition, RetrySpec, Step, WorkflowSpec};
+pub use validate::{Diagnostic, DiagnosticKind, Severity, Validator};
+
+/// 变量名：工作流输入 / Variable holding the workflow input
+pub const INPUT: &str = "input";
+
+/// 加载规范时的错误 / Error loading a spec
+#[derive(Debug)]
+pub enum DslError {
+    /// 读取文件失败 / Reading the file failed
+    Io(std::io::Error),
+    /// 规范无法解析 / The spec does not parse
+    Parse(String),
+    /// 文件扩展名不是 yaml、yml 或 json / The file extension is not yaml, yml or json
+    UnsupportedFormat(String),
+    /// 校验发现错误 / Validation found errors
+    Invalid(Vec<Diagnostic>),
+}
+
+impl fmt::Display for DslError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DslError::Io(e) => write!(f, "failed to read workflow spec: {}", e),
+            DslError::Parse(msg) => write!(f, "invalid workflow spec: {}", msg),
+            DslError::UnsupportedFormat(path) => write!(f, "unsupported workflow spec format: {}", path),
+            DslError::Invalid(diagnostics) => {
+                write!(f, "invalid workflow spec")?;
+                for (index, diagnostic) in diagnostics.iter().enumerate() {
+                    write!(f, "{} {}", if index == 0 { ":" } else { ";" }, diagnostic)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for DslError {}
+
+impl From<std::io::Error> for DslError {
+    fn from(e: std::io::Error) -> Self {
+        DslError::Io(e)
+    }
+}
+
+/// 由规范解释执行的工作流 / Workflow interpreted from a spec
+#[derive(Debug, Clone)]
+pub struct DynamicWorkflow {
+    spec: Arc<WorkflowSpec>,
+}
+
+impl DynamicWorkflow {
+    pub fn new(spec: WorkflowSpec) -> Self {
+        Self { spec: Arc::new(spec) }
+    }
+
+    /// 从 YAML 解析 / Parse from YAML
+    pub fn from_yaml(text: &str) -> Result<Self, DslError> {
+        // Steps are single-key maps (`- sleep: 1d`) rather than YAML tags (`- !sleep 1d`)
+        serde_yaml::with::singleton_map_recursive::deserialize(serde_yaml::Deserializer::from_str(text))
+            .map(Self::new)
+            .map_err(|e| DslError::Parse(e.to_string()))
+    }
+
+    /// 从 JSON 解析 / Parse from JSON
+    pub fn from_json(text: &str) -> Result<Self, DslError> {
+        serde_json::from_str(text).map(Self::new).map_err(|e| DslError::Parse(e.to_string()))
+    }
+
+    /// 按扩展名（`.yaml`、`.yml`、`.json`）加载文件 / Load a file by its extension (`.yaml`, `.yml` or `.json`)
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, DslError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => Self::from_yaml(&text),
+            Some("json") => Self::from_json(&text),
+            _ => Err(DslError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+
+    /// 导入 BPMN 2.0 XML 中的流程，见 [`bpmn`] / Import the process of a BPMN 2.0 XML document; see [`bpmn`]
+    #[cfg(feature = "bpmn")]
+    pub fn from_bpmn(xml: &str) -> Result<Self, bpmn::BpmnError> {
+        bpmn::import(xml).map(Self::new)
+    }
+
+    /// 工作流类型名 / Workflow type name
+    pub fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    pub fn spec(&self) -> &WorkflowSpec {
+        &self.spec
+    }
+
+    /// 以 `input` 运行规范 / Run the spec with `input`
+    pub async fn execute(&self, ctx: WorkflowContext, input: Value) -> Result<Value, WorkflowError> {
+        let mut vars = Map::new();
+        vars.insert(INPUT.to_string(), input);
+        run_steps(&ctx, &self.spec.steps, &mut vars).await?;
+        match &self.spec.output {
+            Some(output) => resolve(output, &vars),
+            None => {
+                vars.remove(INPUT);
+                Ok(Value::Object(vars))
+            }
+        }
+    }
+}
+
+fn run_steps<'a>(
+    ctx: &'a WorkflowContext,
+    steps: &'a [Step],
+    vars: &'a mut Map<String, Value>,
+) -> BoxFuture<'a, Result<(), WorkflowError>> {
+    async move {
+        for step in steps {
+            match step {
+                Step::Activity(activity) => {
+                    let input = resolve(&activity.input, vars)?;
+                    let outcome = ctx.execute_activity_value(&activity.name, input, &activity.options()).await;
+                    match (outcome, &activity.error) {
+                        (Ok(result), _) => {
+                            if let Some(name) = &activity.result {
+                                vars.insert(name.clone(), result);
+                            }
+                        }
+                        (Err(e @ (WorkflowError::ActivityFailed(_) | WorkflowError::Timeout(_))), Some(error)) => {
+                            vars.insert(error.clone(), serde_json::json!({ "message": e.to_string() }));
+                        }
+                        (Err(e), _) => return Err(e),
+                    }
+                }
+                Step::Sleep(duration) => ctx.sleep(*duration).await,
+                Step::Branch(branch) => {
+                    let steps = if branch.condition.holds(lookup(&branch.condition.var, vars)) {
+                        &branch.then
+                    } else {
+                        &branch.otherwise
+                    };
+                    run_steps(ctx, steps, vars).await?;
+                }
+                Step::Parallel(branches) => {
+                    // Each branch sees the variables as they were; their results are merged in branch order
+                    let before = vars.clone();
+                    let runs = branches.iter().map(|steps| {
+                        let mut scope = before.clone();
+                        async move {
+                            run_steps(ctx, steps, &mut scope).await?;
+                            Ok::<_, WorkflowError>(scope)
+                        }
+                    });
+                    for scope in future::try_join_all(runs).await? {
+                        for (name, value) in scope {
+                            if before.get(&name) != Some(&value) {
+                                vars.insert(name, value);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    .boxed()
+}
+
+/// 值在 `path`（如 `payment.items.0.sku`）处的部分 / The part of the variables at `path` such as `payment.items.0.sku`
+fn lookup<'a>(path: &str, vars: &'a Map<String, Value>) -> Option<&'a Value> {
+    let mut segments = path.split('.');
+    let mut value = vars.get(segments.next()?)?;
+    for segment in segments {
+        value = match value {
+            Value::Object(fields) => fields.get(segment)?,
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// 替换模板中的 `${path}` 引用 / Substitute the `${path}` references in a template
+fn resolve(template: &Value, vars: &Map<String, Value>) -> Result<Value, WorkflowError> {
+    let find = |path: &str| {
+        lookup(path.trim(), vars).ok_or_else(|| WorkflowError::InvalidInput(format!("unknown variable: {}", path)))
+    };
+    match template {
+        Value::String(text) => {
+            if let Some(path) = text.strip_prefix("${").and_then(|rest| rest.strip_suffix('}'))
+                && !path.contains("${")
+            {
+                return find(path).cloned();
+            }
+            let mut resolved = String::new();
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("${") {
+                let end = rest[start..]
+                    .find('}')
+                    .ok_or_else(|| WorkflowError::InvalidInput(format!("unterminated reference in {:?}", text)))?;
+                resolved.push_str(&rest[..start]);
+                match find(&rest[start + 2..start + end])? {
+                    Value::String(s) => resolved.push_str(s),
+                    other => resolved.push_str(&other.to_string()),
+                }
+                rest = &rest[start + end + 1..];
+            }
+            resolved.push_str(rest);
+            Ok(Value::String(resolved))
+        }
+        Value::Array(items) => items.iter().map(|item| resolve(item, vars)).collect::<Result<_, _>>().map(Value::Array),
+        Value::Object(fields) => fields
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), resolve(value, vars)?)))
+            .collect::<Result<_, WorkflowError>>()
+            .map(Value::Object),
+        other => Ok(other.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::temporal::{
+        Activity, ActivityContext, ActivityError, HistoryExport, StartWorkflowOptions, WorkerConfig, WorkflowWorker,
+    };
+    use crate::testing::{ReplayError, TestWorkflowEnvironment, WorkflowReplayer};
+
+    struct Charge;
+
+    impl Activity for Charge {
+        type Input = Value;
+        type Output = Value;
+
+        fn name() -> &'static str {
+            "charge"
+        }
+
+        async fn execute(ctx: ActivityContext, input: Value) -> Result<Value, ActivityError> {
+            if ctx.attempt() < 2 {
+                return Err(ActivityError::TemporaryFailure("gateway busy".to_string()));
+            }
+            let amount = input["amount"].as_u64().unwrap_or_default();
+            let status = if amount <= 500 { "approved" } else { "declined" };
+            Ok(serde_json::json!({"status": status, "amount": amount}))
+        }
+    }
+
+    struct Echo;
+
+    impl Activity for Echo {
+        type Input = Value;
+        type Output = Value;
+
+        fn name() -> &'static str {
+            "echo"
+        }
+
+        async fn execute(_ctx: ActivityContext, input: Value) -> Result<Value, ActivityError> {
+            Ok(input)
+        }
+    }
+
+    const PIPELINE: &str = r#"
+name: order-pipeline
+steps:
+  - activity:
+      name: charge
+      input: { amount: "${input.amount}" }
+      result: payment
+      timeout: 5s
+      retry: { max_attempts: 3, initial_interval: 1ms }
+  - branch:
+      if: { var: payment.status, equals: approved }
+      then:
+        - parallel:
+            - - activity: { name: echo, input: "shipped ${input.order}", result: shipping }
+            - - activity: { name: echo, input: "${payment.amount}", result: receipt }
+      else:
+        - activity: { name: echo, input: refunded, result: shipping }
+output: { shipping: "${shipping}", receipt: "${receipt}" }
+"#;
+
+    async fn run(spec: &str, input: Value) -> Result<Value, WorkflowError> {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        }));
+        let workflow = DynamicWorkflow::from_yaml(spec).unwrap();
+        let name = workflow.name().to_string();
+        worker.register_dynamic_workflow(workflow);
+        worker.register_activity::<Charge>();
+        worker.register_activity::<Echo>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let handle = worker
+            .client()
+            .start_dynamic_workflow(&name, input, StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        let result = handle.result().await;
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+        result
+    }
+
+    #[tokio::test]
+    async fn test_yaml_pipeline_retries_branches_and_runs_in_parallel() {
+        let result = run(PIPELINE, serde_json::json!({"amount": 100, "order": 7})).await.unwrap();
+        assert_eq!(result, serde_json::json!({"shipping": "shipped 7", "receipt": 100}));
+
+        // 被拒绝的付款走 else 分支，未赋值的 receipt 使输出失败
+        // a declined payment takes the else branch, and the unassigned receipt fails the output
+        let declined = run(PIPELINE, serde_json::json!({"amount": 900, "order": 8})).await;
+        assert!(matches!(declined, Err(WorkflowError::Custom(failure)) if failure.contains("unknown variable: receipt")));
+    }
+
+    #[tokio::test]
+    async fn test_failures_caught_into_a_variable() {
+        let spec = r#"
+name: caught
+steps:
+  - activity: { name: charge, input: { amount: 1 }, result: payment, error: failure, retry: { max_attempts: 1 } }
+  - branch:
+      if: { var: failure, exists: true }
+      then:
+        - activity: { name: echo, input: "${failure.message}", result: note }
+output: "${note}"
+"#;
+        let note = run(spec, Value::Null).await.unwrap();
+        assert!(note.as_str().is_some_and(|note| note.contains("gateway busy")), "{}", note);
+    }
+
+    #[tokio::test]
+    async fn test_specs_call_dynamic_activities() {
+        let registry = Arc::new(crate::temporal::DynamicActivityRegistry::new());
+        registry.register(crate::temporal::DynamicActivity::new("shout", |_ctx, input: Value| async move {
+            Ok(Value::String(input.as_str().unwrap_or_default().to_uppercase()))
+        }));
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                ..WorkerConfig::default()
+            })
+            .with_dynamic_activities(registry),
+        );
+        let spec = "name: greet\nsteps:\n  - activity: { name: shout, input: \"hi ${input}\", result: greeting }\n";
+        let workflow = Validator::new().with_activities(worker.registered_activities()).load_yaml(spec).unwrap();
+        worker.register_dynamic_workflow(workflow);
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let handle = worker
+            .client()
+            .start_dynamic_workflow("greet", serde_json::json!("ada"), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(handle.result().await.unwrap(), serde_json::json!({"greeting": "HI ADA"}));
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_json_spec_sleeps_on_virtual_clock() {
+        let workflow = DynamicWorkflow::from_json(
+            r#"{"name": "reminder", "steps": [{"sleep": "30d"}, {"activity": {"name": "echo", "input": "${input}", "result": "sent"}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(workflow.spec().steps[0], Step::Sleep(Duration::from_secs(30 * 86_400)));
+
+        let env = TestWorkflowEnvironment::new();
+        env.worker().register_dynamic_workflow(workflow);
+        env.register_activity::<Echo>();
+        let handle = env
+            .client()
+            .start_dynamic_workflow("reminder", serde_json::json!("renew"), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(env.result(&handle).await.unwrap(), serde_json::json!({"sent": "renew"}));
+        assert!(env.elapsed() >= Duration::from_secs(30 * 86_400));
+        env.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_recorded_spec_runs_replay() {
+        let spec = "name: greet\nsteps:\n  - activity: { name: echo, input: \"hi ${input}\", result: greeting }\noutput: \"${greeting}\"\n";
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        }));
+        worker.register_dynamic_workflow(DynamicWorkflow::from_yaml(spec).unwrap());
+        worker.register_activity::<Echo>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+        let handle = worker
+            .client()
+            .start_dynamic_workflow("greet", serde_json::json!("ann"), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(handle.result().await.unwrap(), serde_json::json!("hi ann"));
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+        let export = HistoryExport {
+            execution: handle.execution().clone(),
+            history: handle.history().await.unwrap(),
+        };
+
+        WorkflowReplayer::replay_dynamic(&DynamicWorkflow::from_yaml(spec).unwrap(), &export).unwrap();
+        let changed = DynamicWorkflow::from_yaml(&spec.replace("name: echo", "name: charge")).unwrap();
+        assert!(matches!(WorkflowReplayer::replay_dynamic(&changed, &export), Err(ReplayError::Mismatch { .. })));
+    }
+
+    #[test]
+    fn test_rejects_malformed_specs() {
+        assert!(matches!(DynamicWorkflow::from_yaml("name: x\nsteps:\n  - wait: 1s\n"), Err(DslError::Parse(_))));
+        assert!(matches!(DynamicWorkflow::from_yaml("name: x\nsteps:\n  - sleep: soon\n"), Err(DslError::Parse(_))));
+        assert!(matches!(DynamicWorkflow::from_file("pipeline.toml"), Err(DslError::Io(_))));
+    }
+
+    #[test]
+    fn test_resolve_substitutes_references() {
+        let mut vars = Map::new();
+        vars.insert(INPUT.to_string(), serde_json::json!({"items": [{"sku": "a-1"}], "qty": 2}));
+        let resolved = resolve(
+            &serde_json::json!({"sku": "${input.items.0.sku}", "label": "${input.qty} x ${input.items.0.sku}"}),
+            &vars,
+        )
+        .unwrap();
+        assert_eq!(resolved, serde_json::json!({"sku": "a-1", "label": "2 x a-1"}));
+        assert!(resolve(&serde_json::json!("${missing}"), &vars).is_err());
+    }
+}
@@ -0,0 +1,311 @@
+//! 声明式工作流规范 / Declarative workflow specification
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::temporal::{ActivityOptions, RetryPolicy};
+
+/// 工作流规范 / Workflow specification
+///
+/// ```yaml
+/// name: order-pipeline
+/// steps:
+///   - activity:
+///       name: charge
+///       input: { amount: "${input.amount}" }
+///       result: payment
+///       timeout: 30s
+///       retry: { max_attempts: 5, initial_interval: 1s }
+///       error: declined
+///   - branch:
+///       if: { var: payment.status, equals: approved }
+///       then:
+///         - parallel:
+///             - [ { activity: { name: ship, input: "${input.order_id}" } } ]
+///             - [ { activity: { name: notify, input: "${input.email}" } } ]
+///       else:
+///         - activity: { name: refund, input: "${payment}" }
+///   - sleep: 1d
+/// output: "${payment}"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkflowSpec {
+    /// 注册到工作者的工作流类型名 / Workflow type name the workflow is registered under
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub steps: Vec<Step>,
+    /// 结果模板，缺省为所有具名结果组成的对象 / Result template; defaults to an object of all named results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+}
+
+/// 工作流步骤 / Workflow step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// 执行活动 / Execute an activity
+    Activity(ActivityStep),
+    /// 等待一段时间 / Wait for a duration
+    Sleep(#[serde(with = "duration")] Duration),
+    /// 按条件选择分支 / Choose a branch by a condition
+    Branch(BranchStep),
+    /// 并发运行多个分支，全部成功后继续 / Run branches concurrently and continue once all succeeded
+    Parallel(Vec<Vec<Step>>),
+}
+
+/// 活动步骤 / Activity step
+///
+/// 输入中的字符串可以引用变量：整个字符串为 `${path}` 时替换为该值本身，否则插入其文本。
+/// `input` 是工作流输入，其余变量是之前步骤的具名结果。
+/// Strings in the input may reference variables: a string that is exactly `${path}` is replaced by the value
+/// itself, otherwise its text is interpolated. `input` is the workflow input, other variables are the named
+/// results of earlier steps.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActivityStep {
+    /// 活动类型名 / Activity type name
+    pub name: String,
+    #[serde(default)]
+    pub input: serde_json::Value,
+    /// 保存结果的变量名 / Variable the result is stored in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    /// 保存失败的变量名；给出时活动在重试后仍失败或超时不会使工作流失败，失败以 `{message}` 对象保存
+    /// Variable a failure is stored in; when given, an activity still failing or timing out after its retries
+    /// does not fail the workflow, and the failure is stored as a `{message}` object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_queue: Option<String>,
+    /// 单次尝试的超时 / Timeout of a single attempt
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Duration>,
+    /// 包括重试在内的总超时 / Timeout across all attempts
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    pub schedule_to_close_timeout: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetrySpec>,
+}
+
+impl ActivityStep {
+    pub fn options(&self) -> ActivityOptions {
+        let defaults = ActivityOptions::default();
+        ActivityOptions {
+            task_queue: self.task_queue.clone(),
+            start_to_close_timeout: self.timeout.or(defaults.start_to_close_timeout),
+            schedule_to_close_timeout: self.schedule_to_close_timeout,
+            retry_policy: self.retry.as_ref().map(RetrySpec::policy),
+            ..defaults
+        }
+    }
+}
+
+/// 重试策略，未给出的字段取 [`RetryPolicy`] 默认值 / Retry policy; omitted fields take the [`RetryPolicy`] defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetrySpec {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    pub initial_interval: Option<Duration>,
+    #[serde(default, with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    pub max_interval: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backoff_coefficient: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub non_retryable_error_types: Vec<String>,
+}
+
+impl RetrySpec {
+    pub fn policy(&self) -> RetryPolicy {
+        let defaults = RetryPolicy::default();
+        RetryPolicy {
+            max_attempts: self.max_attempts.unwrap_or(defaults.max_attempts),
+            initial_interval: self.initial_interval.unwrap_or(defaults.initial_interval),
+            max_interval: self.max_interval.unwrap_or(defaults.max_interval),
+            backoff_coefficient: self.backoff_coefficient.unwrap_or(defaults.backoff_coefficient),
+            non_retryable_error_types: self.non_retryable_error_types.clone(),
+        }
+    }
+}
+
+/// 分支步骤 / Branch step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BranchStep {
+    #[serde(rename = "if")]
+    pub condition: Condition,
+    pub then: Vec<Step>,
+    #[serde(default, rename = "else", skip_serializing_if = "Vec::is_empty")]
+    pub otherwise: Vec<Step>,
+}
+
+/// 对一个变量的条件，给出的检查须全部成立；未给出检查时判断其真值
+/// Condition on a variable; all given checks must hold, and without checks the variable must be truthy
+///
+/// 不存在、`null`、`false`、`0`、空字符串与空集合为假。
+/// Missing, `null`, `false`, `0`, empty strings and empty collections are falsy.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    /// 变量路径，如 `payment.status` / Variable path such as `payment.status`
+    pub var: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub equals: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_equals: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub greater_than: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub less_than: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exists: Option<bool>,
+}
+
+impl Condition {
+    /// 对变量值求值 / Evaluate against the variable's value
+    pub fn holds(&self, value: Option<&serde_json::Value>) -> bool {
+        let number = value.and_then(serde_json::Value::as_f64);
+        let checked = self.equals.is_some()
+            || self.not_equals.is_some()
+            || self.greater_than.is_some()
+            || self.less_than.is_some()
+            || self.exists.is_some();
+        if !checked {
+            return value.is_some_and(truthy);
+        }
+        self.equals.as_ref().is_none_or(|expected| value == Some(expected))
+            && self.not_equals.as_ref().is_none_or(|unexpected| value != Some(unexpected))
+            && self.greater_than.is_none_or(|bound| number.is_some_and(|n| n > bound))
+            && self.less_than.is_none_or(|bound| number.is_some_and(|n| n < bound))
+            && self.exists.is_none_or(|exists| value.is_some() == exists)
+    }
+}
+
+fn truthy(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => false,
+        serde_json::Value::Bool(b) => *b,
+        serde_json::Value::Number(n) => n.as_f64() != Some(0.0),
+        serde_json::Value::String(s) => !s.is_empty(),
+        serde_json::Value::Array(items) => !items.is_empty(),
+        serde_json::Value::Object(fields) => !fields.is_empty(),
+    }
+}
+
+/// 解析 `500ms`、`30s`、`5m`、`2h`、`7d` 形式的时长 / Parse durations written as `500ms`, `30s`, `5m`, `2h` or `7d`
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (amount, unit) = text.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| format!("invalid duration: {:?}", text))?;
+    let millis = match unit.trim() {
+        "ms" => 1,
+        "s" | "" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        _ => return Err(format!("invalid duration unit in {:?}; use ms, s, m, h or d", text)),
+    };
+    amount
+        .checked_mul(millis)
+        .map(Duration::from_millis)
+        .ok_or_else(|| format!("duration out of range: {:?}", text))
+}
+
+/// 以最大的整除单位书写时长 / Write a duration in the largest unit that divides it
+pub fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    [(86_400_000, "d"), (3_600_000, "h"), (60_000, "m"), (1_000, "s")]
+        .into_iter()
+        .find(|(unit, _)| millis > 0 && millis.is_multiple_of(*unit))
+        .map(|(unit, suffix)| format!("{}{}", millis / unit, suffix))
+        .unwrap_or_else(|| format!("{}ms", millis))
+}
+
+mod duration {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Text {
+        Seconds(u64),
+        Text(String),
+    }
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::format_duration(*duration))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        match Text::deserialize(deserializer)? {
+            Text::Seconds(seconds) => Ok(Duration::from_secs(seconds)),
+            Text::Text(text) => super::parse_duration(&text).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+mod optional_duration {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => super::duration::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        #[derive(Deserialize)]
+        struct Wrapped(#[serde(with = "super::duration")] Duration);
+        Ok(Option::<Wrapped>::deserialize(deserializer)?.map(|Wrapped(duration)| duration))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_durations_round_trip() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(7 * 86_400)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert!(parse_duration("3 weeks").is_err());
+        assert_eq!(format_duration(Duration::from_secs(7200)), "2h");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1500ms");
+    }
+
+    #[test]
+    fn test_condition_checks() {
+        let status = serde_json::json!("approved");
+        let approved = Condition {
+            var: "payment.status".to_string(),
+            equals: Some(status.clone()),
+            ..Condition::default()
+        };
+        assert!(approved.holds(Some(&status)));
+        assert!(!approved.holds(None));
+
+        let truthy = Condition {
+            var: "flag".to_string(),
+            ..Condition::default()
+        };
+        assert!(!truthy.holds(Some(&serde_json::json!(0))));
+        assert!(truthy.holds(Some(&serde_json::json!([1]))));
+
+        let missing = Condition {
+            var: "flag".to_string(),
+            exists: Some(false),
+            ..Condition::default()
+        };
+        assert!(missing.holds(None));
+    }
+}
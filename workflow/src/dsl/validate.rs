@@ -0,0 +1,815 @@
+//! 规范校验 / Spec validation
+//!
+//! [`Validator`] 在运行之前检查文档：结构是否符合 [`WorkflowSpec`] 模式与其约束，活动名是否已注册，
+//! 变量引用是否会在使用前赋值（引用自身或之后步骤的结果即循环依赖），以及条件恒真或恒假导致的不可达分支。
+//! 每条 [`Diagnostic`] 都带有步骤路径以及 YAML 或 JSON 源文本中的行列位置。
+//! [`Validator`] checks a document before it runs: whether its structure matches the [`WorkflowSpec`] schema and
+//! its constraints, whether the activity names are registered, whether variable references are assigned before
+//! use (referencing a step's own result or a later step's is a cyclic dependency), and which branches are
+//! unreachable because their condition always or never holds. Every [`Diagnostic`] carries the step path and the
+//! line and column in the YAML or JSON source.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::Value;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+use super::{DslError, DynamicWorkflow, INPUT, Step, WorkflowSpec};
+use crate::temporal::DynamicActivityRegistry;
+use super::spec::Condition;
+
+/// 诊断严重程度 / Diagnostic severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// 规范不能运行 / The spec cannot run
+    Error,
+    /// 规范可以运行，但可能并非本意 / The spec runs but likely not as intended
+    Warning,
+}
+
+/// 诊断类别 / Kind of diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// 文本不是合法的 YAML 或 JSON / The text is not valid YAML or JSON
+    Syntax,
+    /// 文档不符合规范模式或其约束 / The document does not match the spec schema or its constraints
+    Schema,
+    /// 活动未在工作者上注册 / The activity is not registered on the worker
+    UnknownActivity,
+    /// 变量在使用前没有赋值 / The variable is not assigned before use
+    UnknownVariable,
+    /// 步骤依赖自身或之后步骤的结果 / The step depends on its own result or a later step's
+    CyclicDependency,
+    /// 条件使步骤永远不会运行 / A condition keeps the steps from ever running
+    UnreachableSteps,
+}
+
+/// 校验发现的问题 / Problem found by validation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    /// 文档中的路径，如 `steps[1].branch.then[0].activity.name` / Path in the document
+    pub path: String,
+    pub message: String,
+    /// 从 1 开始的行号 / 1-based line
+    pub line: Option<usize>,
+    /// 从 1 开始的列号 / 1-based column
+    pub column: Option<usize>,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, kind: DiagnosticKind, path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            kind,
+            path: path.into(),
+            message: message.into(),
+            line: None,
+            column: None,
+        }
+    }
+
+    fn error(kind: DiagnosticKind, path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, kind, path, message)
+    }
+
+    fn warning(kind: DiagnosticKind, path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, kind, path, message)
+    }
+
+    fn at(mut self, line: usize, column: usize) -> Self {
+        self.line = Some(line);
+        self.column = Some(column);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)?;
+        if !self.path.is_empty() {
+            write!(f, " (at {})", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// 规范校验器 / Spec validator
+#[derive(Debug, Clone, Default)]
+pub struct Validator {
+    activities: Option<BTreeSet<String>>,
+    dynamic: Option<Arc<DynamicActivityRegistry>>,
+}
+
+impl Validator {
+    /// 不检查活动名的校验器 / Validator that does not check activity names
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 只接受这些活动名，例如 [`WorkflowWorker::registered_activities`](crate::temporal::WorkflowWorker::registered_activities)
+    /// Accept only these activity names, e.g. those of [`WorkflowWorker::registered_activities`](crate::temporal::WorkflowWorker::registered_activities)
+    pub fn with_activities<S: Into<String>>(mut self, activities: impl IntoIterator<Item = S>) -> Self {
+        self.activities = Some(activities.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// 用动态活动的输入校验钩子检查不含变量引用的输入 /
+    /// Check inputs without variable references with the input validation hooks of dynamic activities
+    pub fn with_dynamic_activities(mut self, registry: Arc<DynamicActivityRegistry>) -> Self {
+        self.dynamic = Some(registry);
+        self
+    }
+
+    /// 校验 YAML 文档 / Validate a YAML document
+    pub fn validate_yaml(&self, text: &str) -> Vec<Diagnostic> {
+        self.parse_yaml(text).1
+    }
+
+    /// 校验 JSON 文档 / Validate a JSON document
+    pub fn validate_json(&self, text: &str) -> Vec<Diagnostic> {
+        self.parse_json(text).1
+    }
+
+    /// 校验已解析的规范，诊断不带位置 / Validate a parsed spec; the diagnostics carry no positions
+    pub fn validate(&self, spec: &WorkflowSpec) -> Vec<Diagnostic> {
+        self.check(spec, &Positions::default())
+    }
+
+    /// 校验并加载 YAML，有错误时失败 / Validate and load YAML, failing on errors
+    pub fn load_yaml(&self, text: &str) -> Result<DynamicWorkflow, DslError> {
+        Self::loaded(self.parse_yaml(text))
+    }
+
+    /// 校验并加载 JSON，有错误时失败 / Validate and load JSON, failing on errors
+    pub fn load_json(&self, text: &str) -> Result<DynamicWorkflow, DslError> {
+        Self::loaded(self.parse_json(text))
+    }
+
+    /// 按扩展名校验并加载文件 / Validate and load a file by its extension
+    pub fn load_file(&self, path: impl AsRef<Path>) -> Result<DynamicWorkflow, DslError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => self.load_yaml(&text),
+            Some("json") => self.load_json(&text),
+            _ => Err(DslError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+
+    fn loaded((spec, diagnostics): (Option<WorkflowSpec>, Vec<Diagnostic>)) -> Result<DynamicWorkflow, DslError> {
+        match spec {
+            Some(spec) if !diagnostics.iter().any(Diagnostic::is_error) => Ok(DynamicWorkflow::new(spec)),
+            _ => Err(DslError::Invalid(diagnostics.into_iter().filter(Diagnostic::is_error).collect())),
+        }
+    }
+
+    /// 解析出的规范（若能解析）及其诊断 / The parsed spec, if it parses, and its diagnostics
+    fn parse_yaml(&self, text: &str) -> (Option<WorkflowSpec>, Vec<Diagnostic>) {
+        let positions = match Positions::scan(text) {
+            Ok(positions) => positions,
+            Err(diagnostic) => return (None, vec![diagnostic]),
+        };
+        match serde_yaml::with::singleton_map_recursive::deserialize(serde_yaml::Deserializer::from_str(text)) {
+            Ok(spec) => {
+                let diagnostics = self.check(&spec, &positions);
+                (Some(spec), diagnostics)
+            }
+            Err(e) => {
+                let location = e.location().map(|location| (location.line(), location.column()));
+                (None, vec![schema_error(&e.to_string(), location, &positions)])
+            }
+        }
+    }
+
+    fn parse_json(&self, text: &str) -> (Option<WorkflowSpec>, Vec<Diagnostic>) {
+        match serde_json::from_str(text) {
+            Ok(spec) => {
+                // JSON is YAML, so the YAML scanner finds the positions of its nodes as well
+                let positions = Positions::scan(text).unwrap_or_default();
+                let diagnostics = self.check(&spec, &positions);
+                (Some(spec), diagnostics)
+            }
+            Err(e) => {
+                let kind = if e.is_syntax() || e.is_eof() { DiagnosticKind::Syntax } else { DiagnosticKind::Schema };
+                let message = e.to_string();
+                let message = message.rsplit_once(" at line ").map_or(message.as_str(), |(message, _)| message);
+                (None, vec![Diagnostic::error(kind, "", message).at(e.line(), e.column())])
+            }
+        }
+    }
+
+    fn check(&self, spec: &WorkflowSpec, positions: &Positions) -> Vec<Diagnostic> {
+        let mut producers = Vec::new();
+        collect_producers(&spec.steps, "steps", &mut 0, &mut producers);
+        let mut walk = Walk {
+            activities: self.activities.as_ref(),
+            dynamic: self.dynamic.as_deref(),
+            producers,
+            order: 0,
+            diagnostics: Vec::new(),
+        };
+
+        if spec.name.trim().is_empty() {
+            walk.report(Diagnostic::error(DiagnosticKind::Schema, "name", "workflow name must not be empty"));
+        }
+        if spec.steps.is_empty() {
+            walk.report(Diagnostic::error(DiagnosticKind::Schema, "steps", "workflow has no steps"));
+        }
+        let mut flow = Flow::default();
+        flow.definite.insert(INPUT.to_string());
+        walk.steps(&spec.steps, "steps", &mut flow);
+        if let Some(output) = &spec.output {
+            walk.references(output, "output", "output", usize::MAX, &flow);
+        }
+
+        walk.diagnostics
+            .into_iter()
+            .map(|diagnostic| match positions.find(&diagnostic.path) {
+                Some((line, column)) if diagnostic.line.is_none() => diagnostic.at(line, column),
+                _ => diagnostic,
+            })
+            .collect()
+    }
+}
+
+/// 把 serde 的错误拆成路径与信息 / Split a serde error into its path and message
+fn schema_error(error: &str, location: Option<(usize, usize)>, positions: &Positions) -> Diagnostic {
+    let message = match location {
+        Some((line, column)) => error.strip_suffix(&format!(" at line {} column {}", line, column)).unwrap_or(error),
+        None => error,
+    };
+    let (path, message) = match message.split_once(": ") {
+        Some((path, rest)) if !path.is_empty() && path.chars().all(|c| c.is_alphanumeric() || "_.[]".contains(c)) => {
+            (path, rest)
+        }
+        _ => ("", message),
+    };
+    let diagnostic = Diagnostic::error(DiagnosticKind::Schema, path, message);
+    match location.or_else(|| positions.find(path)) {
+        Some((line, column)) => diagnostic.at(line, column),
+        None => diagnostic,
+    }
+}
+
+/// 步骤路径到源文本位置 / Source positions by path
+#[derive(Debug, Default)]
+struct Positions(HashMap<String, (usize, usize)>);
+
+impl Positions {
+    fn scan(text: &str) -> Result<Self, Diagnostic> {
+        let mut scanner = Scanner::default();
+        Parser::new_from_str(text).load(&mut scanner, false).map_err(|e| {
+            let marker = e.marker();
+            Diagnostic::error(DiagnosticKind::Syntax, "", e.info().to_string()).at(marker.line(), marker.col() + 1)
+        })?;
+        Ok(Self(scanner.positions))
+    }
+
+    /// 路径的位置；没有记录时取最近的上级 / Position of a path, or of its nearest recorded ancestor
+    fn find(&self, path: &str) -> Option<(usize, usize)> {
+        let mut path = path;
+        loop {
+            if let Some(position) = self.0.get(path) {
+                return Some(*position);
+            }
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+}
+
+enum Container {
+    Mapping { key: Option<String> },
+    Sequence { index: usize },
+}
+
+#[derive(Default)]
+struct Scanner {
+    stack: Vec<(String, Container)>,
+    positions: HashMap<String, (usize, usize)>,
+}
+
+impl Scanner {
+    /// 当前节点的路径；映射的键返回 `None` / Path of the node being opened; `None` for a mapping key
+    fn enter(&mut self, scalar: Option<&str>, marker: Marker) -> Option<String> {
+        let position = (marker.line(), marker.col() + 1);
+        let path = match self.stack.last_mut() {
+            None => String::new(),
+            Some((parent, Container::Mapping { key })) => match key.take() {
+                Some(key) => join(parent, &key),
+                None => {
+                    // A key; its position stands for the entry
+                    let key = scalar.unwrap_or_default().to_string();
+                    self.positions.entry(join(parent, &key)).or_insert(position);
+                    *key_slot(&mut self.stack) = Some(key);
+                    return None;
+                }
+            },
+            Some((parent, Container::Sequence { index })) => {
+                *index += 1;
+                format!("{}[{}]", parent, *index - 1)
+            }
+        };
+        self.positions.entry(path.clone()).or_insert(position);
+        Some(path)
+    }
+}
+
+fn key_slot(stack: &mut [(String, Container)]) -> &mut Option<String> {
+    match stack.last_mut() {
+        Some((_, Container::Mapping { key })) => key,
+        _ => unreachable!("keys only occur in mappings"),
+    }
+}
+
+fn join(parent: &str, key: &str) -> String {
+    if parent.is_empty() { key.to_string() } else { format!("{}.{}", parent, key) }
+}
+
+impl MarkedEventReceiver for Scanner {
+    fn on_event(&mut self, event: Event, marker: Marker) {
+        match event {
+            Event::Scalar(value, ..) => {
+                self.enter(Some(&value), marker);
+            }
+            Event::Alias(_) => {
+                self.enter(None, marker);
+            }
+            Event::MappingStart(..) => {
+                let path = self.enter(None, marker).unwrap_or_default();
+                self.stack.push((path, Container::Mapping { key: None }));
+            }
+            Event::SequenceStart(..) => {
+                let path = self.enter(None, marker).unwrap_or_default();
+                self.stack.push((path, Container::Sequence { index: 0 }));
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn has_references(template: &Value) -> bool {
+    match template {
+        Value::String(text) => text.contains("${"),
+        Value::Array(items) => items.iter().any(has_references),
+        Value::Object(fields) => fields.values().any(has_references),
+        _ => false,
+    }
+}
+
+/// 产生变量的步骤 / Step producing a variable
+struct Producer {
+    var: String,
+    path: String,
+    order: usize,
+}
+
+/// 按文档顺序编号步骤并收集其结果变量 / Number the steps in document order and collect their result variables
+fn collect_producers(steps: &[Step], prefix: &str, order: &mut usize, producers: &mut Vec<Producer>) {
+    for (index, step) in steps.iter().enumerate() {
+        let path = format!("{}[{}]", prefix, index);
+        *order += 1;
+        match step {
+            Step::Activity(activity) => {
+                for var in activity.result.iter().chain(&activity.error) {
+                    producers.push(Producer {
+                        var: var.clone(),
+                        path: path.clone(),
+                        order: *order,
+                    });
+                }
+            }
+            Step::Sleep(_) => {}
+            Step::Branch(branch) => {
+                collect_producers(&branch.then, &format!("{}.branch.then", path), order, producers);
+                collect_producers(&branch.otherwise, &format!("{}.branch.else", path), order, producers);
+            }
+            Step::Parallel(branches) => {
+                for (lane, steps) in branches.iter().enumerate() {
+                    collect_producers(steps, &format!("{}.parallel[{}]", path, lane), order, producers);
+                }
+            }
+        }
+    }
+}
+
+/// 到某一点为止已赋值的变量 / Variables assigned up to a point
+#[derive(Debug, Clone, Default)]
+struct Flow {
+    /// 在所有路径上都已赋值 / Assigned on every path
+    definite: BTreeSet<String>,
+    /// 只在部分分支上赋值 / Assigned on some branches only
+    maybe: BTreeSet<String>,
+}
+
+impl Flow {
+    fn merge(flows: Vec<Flow>) -> Flow {
+        let mut definite: Option<BTreeSet<String>> = None;
+        let mut assigned = BTreeSet::new();
+        for flow in flows {
+            assigned.extend(flow.definite.iter().cloned());
+            assigned.extend(flow.maybe);
+            definite = Some(match definite {
+                Some(definite) => definite.intersection(&flow.definite).cloned().collect(),
+                None => flow.definite,
+            });
+        }
+        let definite = definite.unwrap_or_default();
+        let maybe = assigned.difference(&definite).cloned().collect();
+        Flow { definite, maybe }
+    }
+}
+
+struct Walk<'a> {
+    activities: Option<&'a BTreeSet<String>>,
+    dynamic: Option<&'a DynamicActivityRegistry>,
+    producers: Vec<Producer>,
+    order: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Walk<'_> {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn steps(&mut self, steps: &[Step], prefix: &str, flow: &mut Flow) {
+        for (index, step) in steps.iter().enumerate() {
+            let path = format!("{}[{}]", prefix, index);
+            self.order += 1;
+            let order = self.order;
+            match step {
+                Step::Activity(activity) => {
+                    let at = format!("{}.activity", path);
+                    if activity.name.trim().is_empty() {
+                        self.report(Diagnostic::error(DiagnosticKind::Schema, format!("{}.name", at), "activity name must not be empty"));
+                    } else if let Some(known) = self.activities
+                        && !known.contains(&activity.name)
+                    {
+                        self.report(Diagnostic::error(
+                            DiagnosticKind::UnknownActivity,
+                            format!("{}.name", at),
+                            format!("unknown activity `{}`", activity.name),
+                        ));
+                    }
+                    self.activity_constraints(activity, &at);
+                    if let Some(dynamic) = self.dynamic.and_then(|registry| registry.get(&activity.name))
+                        && !has_references(&activity.input)
+                        && let Err(e) = dynamic.check_input(&activity.input)
+                    {
+                        self.report(Diagnostic::error(
+                            DiagnosticKind::Schema,
+                            format!("{}.input", at),
+                            format!("input rejected by `{}`: {}", activity.name, e),
+                        ));
+                    }
+                    self.references(&activity.input, &format!("{}.input", at), &path, order, flow);
+                    // A caught failure leaves the result unassigned
+                    let (definite, maybe) = match &activity.error {
+                        Some(error) => (None, vec![activity.result.as_ref(), Some(error)]),
+                        None => (activity.result.as_ref(), Vec::new()),
+                    };
+                    if let Some(var) = definite {
+                        flow.maybe.remove(var);
+                        flow.definite.insert(var.clone());
+                    }
+                    for var in maybe.into_iter().flatten() {
+                        if !flow.definite.contains(var) {
+                            flow.maybe.insert(var.clone());
+                        }
+                    }
+                }
+                Step::Sleep(_) => {}
+                Step::Branch(branch) => {
+                    let at = format!("{}.branch", path);
+                    let decided = self.condition(&branch.condition, &format!("{}.if", at), order, flow);
+                    let mut flows = Vec::new();
+                    for (taken, steps, arm) in [(true, &branch.then, "then"), (false, &branch.otherwise, "else")] {
+                        let arm = format!("{}.{}", at, arm);
+                        let mut arm_flow = flow.clone();
+                        self.steps(steps, &arm, &mut arm_flow);
+                        match decided {
+                            Some(holds) if holds != taken => {
+                                if !steps.is_empty() {
+                                    let never = if taken { "never holds" } else { "always holds" };
+                                    self.report(Diagnostic::warning(
+                                        DiagnosticKind::UnreachableSteps,
+                                        arm,
+                                        format!("steps are unreachable: the condition on `{}` {}", branch.condition.var, never),
+                                    ));
+                                }
+                            }
+                            _ => flows.push(arm_flow),
+                        }
+                    }
+                    *flow = Flow::merge(flows);
+                }
+                Step::Parallel(branches) => {
+                    let at = format!("{}.parallel", path);
+                    if branches.is_empty() {
+                        self.report(Diagnostic::error(DiagnosticKind::Schema, at.as_str(), "parallel block has no branches"));
+                    }
+                    // Branches start from the same variables and cannot see each other's results
+                    let mut joined = flow.clone();
+                    for (lane, steps) in branches.iter().enumerate() {
+                        let mut lane_flow = flow.clone();
+                        self.steps(steps, &format!("{}[{}]", at, lane), &mut lane_flow);
+                        joined.definite.extend(lane_flow.definite);
+                        joined.maybe.extend(lane_flow.maybe);
+                    }
+                    joined.maybe = joined.maybe.difference(&joined.definite).cloned().collect();
+                    *flow = joined;
+                }
+            }
+        }
+    }
+
+    fn activity_constraints(&mut self, activity: &super::ActivityStep, at: &str) {
+        for (var, field) in [(&activity.result, "result"), (&activity.error, "error")] {
+            if var.as_deref() == Some(INPUT) {
+                self.report(Diagnostic::error(
+                    DiagnosticKind::Schema,
+                    format!("{}.{}", at, field),
+                    format!("`{}` is reserved for the workflow input", INPUT),
+                ));
+            }
+        }
+        if activity.error.is_some() && activity.error == activity.result {
+            self.report(Diagnostic::error(
+                DiagnosticKind::Schema,
+                format!("{}.error", at),
+                "error and result must be different variables",
+            ));
+        }
+        for (timeout, field) in [(activity.timeout, "timeout"), (activity.schedule_to_close_timeout, "schedule_to_close_timeout")] {
+            if timeout.is_some_and(|timeout| timeout.is_zero()) {
+                self.report(Diagnostic::error(DiagnosticKind::Schema, format!("{}.{}", at, field), "timeout must be positive"));
+            }
+        }
+        if let Some(retry) = &activity.retry {
+            if retry.max_attempts == Some(0) {
+                self.report(Diagnostic::error(
+                    DiagnosticKind::Schema,
+                    format!("{}.retry.max_attempts", at),
+                    "max_attempts must be at least 1",
+                ));
+            }
+            if retry.backoff_coefficient.is_some_and(|coefficient| coefficient < 1.0) {
+                self.report(Diagnostic::error(
+                    DiagnosticKind::Schema,
+                    format!("{}.retry.backoff_coefficient", at),
+                    "backoff_coefficient must be at least 1.0",
+                ));
+            }
+        }
+    }
+
+    /// 检查模板中的引用 / Check the references in a template
+    fn references(&mut self, template: &Value, at: &str, step: &str, order: usize, flow: &Flow) {
+        match template {
+            Value::String(text) => {
+                let mut rest = text.as_str();
+                while let Some(start) = rest.find("${") {
+                    let Some(end) = rest[start..].find('}') else {
+                        self.report(Diagnostic::error(DiagnosticKind::Schema, at, format!("unterminated reference in {:?}", text)));
+                        return;
+                    };
+                    let path = rest[start + 2..start + end].trim();
+                    self.variable(path, at, step, order, flow);
+                    rest = &rest[start + end + 1..];
+                }
+            }
+            Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    self.references(item, &format!("{}[{}]", at, index), step, order, flow);
+                }
+            }
+            Value::Object(fields) => {
+                for (key, value) in fields {
+                    self.references(value, &join(at, key), step, order, flow);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 检查对变量的一次使用，返回它在此处是否肯定不存在
+    /// Check one use of a variable; returns whether it is certainly missing here
+    fn variable(&mut self, path: &str, at: &str, step: &str, order: usize, flow: &Flow) -> bool {
+        let var = path.split('.').next().unwrap_or_default();
+        if flow.definite.contains(var) {
+            return false;
+        }
+        if flow.maybe.contains(var) {
+            self.report(Diagnostic::warning(
+                DiagnosticKind::UnknownVariable,
+                at,
+                format!("`{}` is only assigned on some branches", var),
+            ));
+            return false;
+        }
+        let producers: Vec<_> = self.producers.iter().filter(|producer| producer.var == var).collect();
+        let diagnostic = if producers.iter().any(|producer| producer.path == step) {
+            Diagnostic::error(DiagnosticKind::CyclicDependency, at, format!("step depends on its own result `{}`", var))
+        } else if let Some(later) = producers.iter().find(|producer| producer.order > order) {
+            Diagnostic::error(
+                DiagnosticKind::CyclicDependency,
+                at,
+                format!("step depends on `{}`, which is produced by {} after it", var, later.path),
+            )
+        } else if let Some(elsewhere) = producers.first() {
+            Diagnostic::error(
+                DiagnosticKind::UnknownVariable,
+                at,
+                format!("`{}` is produced by {}, which does not run before this step", var, elsewhere.path),
+            )
+        } else {
+            Diagnostic::error(DiagnosticKind::UnknownVariable, at, format!("`{}` is never assigned", var))
+        };
+        let missing = diagnostic.kind == DiagnosticKind::UnknownVariable;
+        self.report(diagnostic);
+        missing
+    }
+
+    /// 检查条件；若其结果在校验时已确定则返回之 / Check a condition; returns its outcome if already decided
+    fn condition(&mut self, condition: &Condition, at: &str, order: usize, flow: &Flow) -> Option<bool> {
+        let contradictory = condition.equals.is_some() && condition.equals == condition.not_equals
+            || condition.greater_than.zip(condition.less_than).is_some_and(|(low, high)| low >= high)
+            || (condition.greater_than.is_some() || condition.less_than.is_some())
+                && condition.equals.as_ref().is_some_and(|equals| !equals.is_number())
+            || condition.exists == Some(false)
+                && (condition.equals.is_some() || condition.greater_than.is_some() || condition.less_than.is_some());
+        if contradictory {
+            return Some(false);
+        }
+
+        let var = condition.var.split('.').next().unwrap_or_default();
+        let missing = !flow.definite.contains(var) && !flow.maybe.contains(var);
+        if !missing {
+            return None;
+        }
+        // A variable never assigned before the branch is missing whenever it is evaluated
+        let before = self.diagnostics.len();
+        if self.variable(&condition.var, &format!("{}.var", at), at, order, flow) {
+            self.diagnostics.truncate(before);
+            return Some(condition.holds(None));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIPELINE: &str = "name: orders
+steps:
+  - activity:
+      name: charge
+      input: \"${input.amount}\"
+      result: payment
+  - branch:
+      if: { var: payment.status, equals: approved }
+      then:
+        - activity: { name: ship, input: \"${payment}\", result: shipment }
+  - activity: { name: notify, input: \"${shipment}\" }
+";
+
+    fn kinds(diagnostics: &[Diagnostic]) -> Vec<(DiagnosticKind, Severity)> {
+        diagnostics.iter().map(|diagnostic| (diagnostic.kind, diagnostic.severity)).collect()
+    }
+
+    #[test]
+    fn test_unknown_activity_points_at_its_line() {
+        let validator = Validator::new().with_activities(["charge", "ship"]);
+        let diagnostics = validator.validate_yaml(PIPELINE);
+        assert_eq!(
+            kinds(&diagnostics),
+            [
+                (DiagnosticKind::UnknownActivity, Severity::Error),
+                (DiagnosticKind::UnknownVariable, Severity::Warning),
+            ]
+        );
+        assert_eq!(diagnostics[0].path, "steps[2].activity.name");
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (Some(11), Some(17)));
+        assert_eq!(diagnostics[1].message, "`shipment` is only assigned on some branches");
+
+        match validator.load_yaml(PIPELINE) {
+            Err(DslError::Invalid(errors)) => assert_eq!(errors.len(), 1),
+            other => panic!("expected invalid spec, got {:?}", other.map(|_| ())),
+        }
+        assert!(Validator::new().with_activities(["charge", "ship", "notify"]).load_yaml(PIPELINE).is_ok());
+    }
+
+    #[test]
+    fn test_constant_inputs_meet_dynamic_activity_hooks() {
+        let registry = Arc::new(DynamicActivityRegistry::new());
+        registry.register(
+            crate::temporal::DynamicActivity::new("notify", |_ctx, input| async move { Ok(input) })
+                .validate_input(|input| if input.is_string() { Ok(()) } else { Err("expected an address".to_string()) }),
+        );
+        let validator = Validator::new().with_dynamic_activities(registry);
+        let spec = "name: notify\nsteps:\n  - activity: { name: notify, input: 42 }\n  - activity: { name: notify, input: \"${input}\" }\n";
+        let diagnostics = validator.validate_yaml(spec);
+        assert_eq!(kinds(&diagnostics), [(DiagnosticKind::Schema, Severity::Error)]);
+        assert_eq!(diagnostics[0].path, "steps[0].activity.input");
+        assert_eq!(diagnostics[0].line, Some(3));
+        assert!(diagnostics[0].message.ends_with("expected an address"));
+    }
+
+    #[test]
+    fn test_schema_errors_carry_position() {
+        let diagnostics = Validator::new().validate_yaml("name: orders\nsteps:\n  - activity:\n      nmae: charge\n");
+        assert_eq!(kinds(&diagnostics), [(DiagnosticKind::Schema, Severity::Error)]);
+        assert_eq!(diagnostics[0].path, "steps[0].activity");
+        assert!(diagnostics[0].message.starts_with("unknown field `nmae`"));
+        assert_eq!(diagnostics[0].line, Some(4));
+
+        let diagnostics = Validator::new().validate_yaml("name: [orders\n");
+        assert_eq!(kinds(&diagnostics), [(DiagnosticKind::Syntax, Severity::Error)]);
+
+        let diagnostics = Validator::new().validate_json("{\"name\": \"orders\", \"steps\": [{\"sleep\": \"soon\"}]}");
+        assert_eq!(kinds(&diagnostics), [(DiagnosticKind::Schema, Severity::Error)]);
+        assert_eq!(diagnostics[0].line, Some(1));
+    }
+
+    #[test]
+    fn test_cyclic_dependencies() {
+        let spec = "name: loop
+steps:
+  - activity: { name: a, input: \"${first}\", result: first }
+  - activity: { name: b, input: \"${second}\" }
+  - activity: { name: c, result: second }
+  - parallel:
+      - - activity: { name: d, input: \"${right}\", result: left }
+      - - activity: { name: e, input: \"${left}\", result: right }
+";
+        let diagnostics = Validator::new().validate_yaml(spec);
+        let messages: Vec<_> = diagnostics.iter().map(|diagnostic| (diagnostic.kind, diagnostic.message.as_str())).collect();
+        assert_eq!(
+            messages,
+            [
+                (DiagnosticKind::CyclicDependency, "step depends on its own result `first`"),
+                (DiagnosticKind::CyclicDependency, "step depends on `second`, which is produced by steps[2] after it"),
+                (DiagnosticKind::CyclicDependency, "step depends on `right`, which is produced by steps[3].parallel[1][0] after it"),
+                (
+                    DiagnosticKind::UnknownVariable,
+                    "`left` is produced by steps[3].parallel[0][0], which does not run before this step"
+                ),
+            ]
+        );
+        assert_eq!(diagnostics[1].line, Some(4));
+    }
+
+    #[test]
+    fn test_unreachable_branches() {
+        let spec = "name: flags
+steps:
+  - branch:
+      if: { var: input.retries, greater_than: 5, less_than: 3 }
+      then:
+        - activity: { name: alert }
+  - branch:
+      if: { var: approval, exists: false }
+      then:
+        - activity: { name: escalate }
+      else:
+        - activity: { name: archive }
+  - branch:
+      if: { var: input.vip }
+      then:
+        - sleep: 1h
+";
+        let diagnostics = Validator::new().validate_yaml(spec);
+        let unreachable: Vec<_> = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.kind == DiagnosticKind::UnreachableSteps)
+            .map(|diagnostic| diagnostic.path.as_str())
+            .collect();
+        assert_eq!(unreachable, ["steps[0].branch.then", "steps[1].branch.else"]);
+        assert!(diagnostics.iter().all(|diagnostic| !diagnostic.is_error()));
+        assert_eq!(diagnostics[0].line, Some(5));
+    }
+}
@@ -396,12 +396,17 @@ impl WorkflowEngine {
         // 中间件（前置）/ Middleware (before)
         #[cfg(feature = "middleware")]
         if let Some(manager) = &self.middleware_manager {
+            let mut context = MiddlewareContext::new(
+                crate::types::utils::generate_instance_id(),
+                instance_id.clone(),
+                data.clone().unwrap_or(serde_json::json!({})),
+            );
+            let workflow_name = self.instances.read().unwrap().get(&instance_id).map(|i| i.workflow_name.clone());
+            if let Some(workflow_name) = workflow_name {
+                context.set_metadata("workflow_name".to_string(), workflow_name);
+            }
             let mut chain = manager
-                .create_chain(MiddlewareContext::new(
-                    crate::types::utils::generate_instance_id(),
-                    instance_id.clone(),
-                    data.clone().unwrap_or(serde_json::json!({})),
-                ))
+                .create_chain(context)
                 .await
                 .map_err(|e| WorkflowError::InternalError(e.to_string()))?;
             let _ = chain.execute().await; // 忽略中间件错误，避免中断核心流
@@ -1,18 +1,104 @@
 //! # 高级示例 / Advanced Examples
+//!
+//! 在限制并发实例数的引擎上启动多个实例，展示容量限制与实例清理。
+//! Starts several instances on an engine with a concurrency cap, showing the capacity limit and instance cleanup.
+
+use crate::engine::{EngineConfig, WorkflowEngine};
+use crate::error::WorkflowError;
+use crate::examples::basic_workflow::{order_workflow_definition, ORDER_WORKFLOW};
+use crate::types::WorkflowData;
+use serde_json::json;
+
+/// 高级示例结果 / Advanced example result
+#[derive(Debug, Clone)]
+pub struct AdvancedExampleReport {
+    /// 引擎允许的最大实例数 / Maximum instances the engine allows
+    pub capacity: usize,
+    /// 成功启动的实例 / Instances started successfully
+    pub started: Vec<String>,
+    /// 超出容量时的错误 / Error returned once capacity is exceeded
+    pub rejected: Vec<String>,
+    /// 引擎中的实例数 / Instances held by the engine
+    pub instance_count: usize,
+    /// 清理运行中实例时移除的数量 / Instances removed by cleanup while all are running
+    pub cleaned_up: usize,
+}
+
+/// 运行高级示例并返回结构化结果 / Run the advanced example and return a structured result
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() {
+/// use workflow::examples::advanced_example;
+///
+/// let report = advanced_example(2, 3).await.unwrap();
+/// assert_eq!(report.started.len(), 2);
+/// assert_eq!(report.rejected.len(), 1);
+/// # }
+/// ```
+pub async fn advanced_example(capacity: usize, requests: usize) -> Result<AdvancedExampleReport, WorkflowError> {
+    let engine = WorkflowEngine::with_config(EngineConfig {
+        max_concurrent_instances: capacity,
+        ..EngineConfig::default()
+    });
+    engine.register_workflow(ORDER_WORKFLOW.to_string(), order_workflow_definition()).await?;
+
+    let mut started = Vec::new();
+    let mut rejected = Vec::new();
+    for i in 0..requests {
+        let data = WorkflowData::new(json!({ "order_id": format!("order-{i}"), "amount": 10.0 }));
+        match engine.start_workflow(ORDER_WORKFLOW, data).await {
+            Ok(id) => started.push(id),
+            Err(e @ WorkflowError::ResourceLimitExceeded(_)) => rejected.push(e.to_string()),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let instance_count = engine.get_all_instances().len();
+    // 仍在运行的实例不会被清理 / Running instances survive cleanup
+    let cleaned_up = engine.cleanup_completed_instances().await?;
+
+    Ok(AdvancedExampleReport {
+        capacity,
+        started,
+        rejected,
+        instance_count,
+        cleaned_up,
+    })
+}
 
 /// 运行高级示例 / Run advanced examples
 pub async fn run_advanced_examples() -> Result<(), Box<dyn std::error::Error>> {
+    let report = advanced_example(3, 5).await?;
+
     println!("🚀 高级示例 / Advanced Examples");
     println!("==============================");
+    println!("   容量 / Capacity: {}", report.capacity);
+    println!("   已启动 / Started: {}", report.started.len());
+    println!("   被拒绝 / Rejected: {}", report.rejected.len());
+    if let Some(reason) = report.rejected.first() {
+        println!("   拒绝原因 / Reason: {}", reason);
+    }
+    println!("   引擎实例数 / Engine instances: {}, cleaned up: {}", report.instance_count, report.cleaned_up);
+    Ok(())
+}
 
-    println!("\n1. 分布式工作流 / Distributed Workflow");
-    println!("   - 跨节点协调 / Cross-node coordination");
-    println!("   - 故障恢复 / Fault recovery");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("\n2. 实时工作流 / Real-time Workflow");
-    println!("   - 流式处理 / Stream processing");
-    println!("   - 低延迟优化 / Low latency optimization");
+    #[tokio::test]
+    async fn test_advanced_example() {
+        let report = advanced_example(3, 5).await.unwrap();
+        assert_eq!(report.started.len(), 3);
+        assert_eq!(report.rejected.len(), 2);
+        assert!(report.rejected[0].contains("Maximum concurrent instances reached"));
+        assert_eq!(report.instance_count, 3);
+        assert_eq!(report.cleaned_up, 0);
+    }
 
-    println!("\n✅ 高级示例运行完成 / Advanced examples completed successfully");
-    Ok(())
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn test_run_advanced_examples() {
+        run_advanced_examples().await.unwrap();
+    }
+}
@@ -1,18 +1,166 @@
 //! # 基础工作流示例 / Basic Workflow Examples
+//!
+//! 定义一个两步骤的订单工作流，注册到引擎并逐步执行两个活动。
+//! Defines a two-step order workflow, registers it with the engine and runs both activities step by step.
+
+use crate::engine::WorkflowEngine;
+use crate::error::WorkflowError;
+use crate::types::{WorkflowData, WorkflowDefinition, WorkflowInstance, WorkflowStatus};
+use serde_json::{json, Value};
+
+/// 示例工作流名称 / Example workflow name
+pub const ORDER_WORKFLOW: &str = "order_fulfillment";
+
+/// 基础工作流示例结果 / Basic workflow example result
+#[derive(Debug, Clone)]
+pub struct BasicWorkflowReport {
+    /// 引擎分配的实例 ID / Instance ID assigned by the engine
+    pub instance_id: String,
+    /// 引擎中实例的当前状态 / Current state of the instance in the engine
+    pub engine_state: String,
+    /// 依次经过的状态 / States visited in order
+    pub visited_states: Vec<String>,
+    /// 最终状态 / Final status
+    pub status: WorkflowStatus,
+    /// 最终输出 / Final output
+    pub output: Value,
+    /// 带环定义被拒绝时的错误 / Error returned for the cyclic definition
+    pub cyclic_rejection: String,
+}
+
+/// 订单工作流定义 / Order workflow definition
+///
+/// `initial -> charge_payment -> ship_order -> completed`
+pub fn order_workflow_definition() -> WorkflowDefinition {
+    let mut definition = WorkflowDefinition::new(ORDER_WORKFLOW.to_string());
+    for state in ["initial", "charge_payment", "ship_order", "completed"] {
+        definition.add_state(state.to_string());
+    }
+    definition.add_transition("initial".to_string(), "charge_payment".to_string(), None);
+    definition.add_transition("charge_payment".to_string(), "ship_order".to_string(), None);
+    definition.add_transition("ship_order".to_string(), "completed".to_string(), None);
+    definition.final_states.push("completed".to_string());
+    definition
+}
+
+async fn charge_payment(input: &Value) -> Result<Value, WorkflowError> {
+    let amount = input["amount"]
+        .as_f64()
+        .ok_or_else(|| WorkflowError::ValidationError("amount is required".to_string()))?;
+    Ok(json!({ "payment_id": format!("pay_{}", input["order_id"].as_str().unwrap_or_default()), "charged": amount }))
+}
+
+async fn ship_order(input: &Value) -> Result<Value, WorkflowError> {
+    Ok(json!({ "tracking_number": format!("trk_{}", input["order_id"].as_str().unwrap_or_default()) }))
+}
+
+/// 运行基础工作流示例并返回结构化结果 / Run the basic workflow example and return a structured result
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() {
+/// use workflow::examples::basic_workflow_example;
+///
+/// let report = basic_workflow_example().await.unwrap();
+/// assert_eq!(report.visited_states, ["charge_payment", "ship_order", "completed"]);
+/// assert_eq!(report.output["tracking_number"], "trk_order-42");
+/// # }
+/// ```
+pub async fn basic_workflow_example() -> Result<BasicWorkflowReport, WorkflowError> {
+    let engine = WorkflowEngine::new();
+    let definition = order_workflow_definition();
+    engine.register_workflow(ORDER_WORKFLOW.to_string(), definition.clone()).await?;
+
+    // 带环定义无法注册 / A cyclic definition is refused at registration
+    let mut cyclic = order_workflow_definition();
+    cyclic.name = "cyclic".to_string();
+    cyclic.add_transition("ship_order".to_string(), "charge_payment".to_string(), None);
+    let cyclic_rejection = match engine.register_workflow("cyclic".to_string(), cyclic).await {
+        Err(e) => e.to_string(),
+        Ok(()) => return Err(WorkflowError::ValidationError("cyclic definition was accepted".to_string())),
+    };
+
+    let input = json!({ "order_id": "order-42", "amount": 99.5 });
+    let instance_id = engine.start_workflow(ORDER_WORKFLOW, WorkflowData::new(input.clone())).await?;
+    let engine_state = engine.get_workflow_state(&instance_id).await?;
+
+    // 沿定义的转换依次执行两个活动 / Follow the definition's transitions, running one activity per step
+    let mut instance = engine
+        .get_instance(&instance_id)
+        .ok_or_else(|| WorkflowError::InstanceNotFound(instance_id.clone()))?;
+    let mut output = input;
+    while !definition.final_states.contains(&instance.current_state) {
+        let next = definition
+            .transitions
+            .iter()
+            .find(|t| t.from_state == instance.current_state)
+            .map(|t| t.to_state.clone())
+            .ok_or_else(|| WorkflowError::InvalidStateTransition {
+                from: instance.current_state.clone(),
+                to: "?".to_string(),
+            })?;
+        let step_output = match next.as_str() {
+            "charge_payment" => charge_payment(&output).await?,
+            "ship_order" => ship_order(&output).await?,
+            _ => json!({}),
+        };
+        merge(&mut output, step_output.clone());
+        instance.transition(next, Some(step_output));
+    }
+    instance.complete(output.clone());
+
+    Ok(BasicWorkflowReport {
+        instance_id,
+        engine_state,
+        visited_states: visited_states(&instance),
+        status: instance.status,
+        output,
+        cyclic_rejection,
+    })
+}
+
+fn merge(target: &mut Value, update: Value) {
+    if let (Some(target), Value::Object(update)) = (target.as_object_mut(), update) {
+        target.extend(update);
+    }
+}
+
+fn visited_states(instance: &WorkflowInstance) -> Vec<String> {
+    instance.history.iter().map(|r| r.to_state.clone()).collect()
+}
 
 /// 运行基础工作流示例 / Run basic workflow examples
 pub async fn run_basic_workflow_examples() -> Result<(), Box<dyn std::error::Error>> {
+    let report = basic_workflow_example().await?;
+
     println!("📋 基础工作流示例 / Basic Workflow Examples");
     println!("==========================================");
+    println!("   实例 / Instance: {} (engine state: {})", report.instance_id, report.engine_state);
+    println!("   状态序列 / States: {}", report.visited_states.join(" -> "));
+    println!("   结果 / Status: {:?}, output: {}", report.status, report.output);
+    println!("   带环定义被拒绝 / Cyclic definition rejected: {}", report.cyclic_rejection);
+    Ok(())
+}
 
-    println!("\n1. 简单工作流 / Simple Workflow");
-    println!("   - 创建基本步骤 / Creating basic steps");
-    println!("   - 执行工作流 / Executing workflow");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("\n2. 条件工作流 / Conditional Workflow");
-    println!("   - 条件分支 / Conditional branches");
-    println!("   - 错误处理 / Error handling");
+    #[tokio::test]
+    async fn test_basic_workflow_example() {
+        let report = basic_workflow_example().await.unwrap();
+        assert!(report.instance_id.starts_with("wf_"));
+        assert_eq!(report.engine_state, "initial");
+        assert_eq!(report.visited_states, ["charge_payment", "ship_order", "completed"]);
+        assert!(matches!(report.status, WorkflowStatus::Completed));
+        assert_eq!(report.output["payment_id"], "pay_order-42");
+        assert_eq!(report.output["charged"], 99.5);
+        assert_eq!(report.output["tracking_number"], "trk_order-42");
+        assert!(report.cyclic_rejection.contains("Circular dependency"));
+    }
 
-    println!("\n✅ 基础工作流示例运行完成 / Basic workflow examples completed successfully");
-    Ok(())
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn test_run_basic_workflow_examples() {
+        run_basic_workflow_examples().await.unwrap();
+    }
+}
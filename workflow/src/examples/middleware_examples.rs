@@ -3,30 +3,148 @@
 //! 本模块展示了工作流中间件的使用方法
 //! This module demonstrates how to use workflow middleware
 
-//use std::time::Duration;
+use crate::middleware::{
+    AuthenticationMiddleware, LoggingMiddleware, MiddlewareContext, MiddlewareError,
+    MiddlewarePriority, RateLimitingMiddleware, WorkflowMiddleware, WorkflowMiddlewareManager,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// 中间件示例结果 / Middleware example result
+#[derive(Debug, Clone)]
+pub struct MiddlewareExampleReport {
+    /// 携带有效令牌的请求处理后的上下文 / Context after a request with a valid token
+    pub authorized: MiddlewareContext,
+    /// 缺少令牌时链返回的错误 / Error returned by the chain when the token is missing
+    pub missing_token_error: String,
+    /// 令牌无效时链返回的错误 / Error returned by the chain for an invalid token
+    pub invalid_token_error: String,
+    /// 链尾探针被调用的次数 / How many requests reached the probe at the end of the chain
+    pub probe_calls: usize,
+}
+
+/// 统计到达链尾的请求数 / Counts requests that reach the end of the chain
+struct ProbeMiddleware {
+    calls: Arc<AtomicUsize>,
+}
+
+#[async_trait::async_trait]
+impl WorkflowMiddleware for ProbeMiddleware {
+    fn name(&self) -> &str {
+        "ProbeMiddleware"
+    }
+
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+
+    fn description(&self) -> &str {
+        "示例探针 / Example probe"
+    }
+
+    fn priority(&self) -> MiddlewarePriority {
+        MiddlewarePriority::Low
+    }
+
+    async fn before_request(&self, _context: &mut MiddlewareContext) -> Result<(), String> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn after_request(&self, _context: &mut MiddlewareContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn handle_error(&self, _context: &mut MiddlewareContext, _error: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+fn request(id: &str, token: Option<&str>) -> MiddlewareContext {
+    let mut context = MiddlewareContext::new(id.to_string(), "order_workflow".to_string(), serde_json::json!({}));
+    if let Some(token) = token {
+        context.set_header("Authorization".to_string(), token.to_string());
+    }
+    context
+}
+
+/// 运行认证 + 限流 + 日志中间件链 / Run an auth + rate-limit + logging chain
+///
+/// 中间件按优先级排序，认证失败时链被短路，后续中间件不会执行。
+/// Middleware runs in priority order; a failed authentication short-circuits the chain before later middleware runs.
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() {
+/// use workflow::examples::middleware_example;
+///
+/// let report = middleware_example().await.unwrap();
+/// assert_eq!(report.authorized.get_metadata("user_role").unwrap(), "admin");
+/// assert_eq!(report.probe_calls, 1);
+/// # }
+/// ```
+pub async fn middleware_example() -> Result<MiddlewareExampleReport, MiddlewareError> {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut manager = WorkflowMiddlewareManager::new();
+    // 注册顺序与执行顺序无关 / Registration order does not matter, priority does
+    manager.register_middleware(Box::new(ProbeMiddleware { calls: calls.clone() }));
+    manager.register_middleware(Box::new(LoggingMiddleware::new()));
+    manager.register_middleware(Box::new(RateLimitingMiddleware::new()));
+    manager.register_middleware(Box::new(AuthenticationMiddleware::new()));
+
+    let authorized = manager.create_chain(request("req-1", Some("admin_token_123"))).await?.execute().await?;
+
+    let missing_token_error = match manager.create_chain(request("req-2", None)).await?.execute().await {
+        Err(e) => e.to_string(),
+        Ok(_) => return Err(MiddlewareError::AuthenticationFailed("request without token was accepted".to_string())),
+    };
+    let invalid_token_error = match manager.create_chain(request("req-3", Some("forged"))).await?.execute().await {
+        Err(e) => e.to_string(),
+        Ok(_) => return Err(MiddlewareError::AuthenticationFailed("forged token was accepted".to_string())),
+    };
+
+    Ok(MiddlewareExampleReport {
+        authorized,
+        missing_token_error,
+        invalid_token_error,
+        probe_calls: calls.load(Ordering::SeqCst),
+    })
+}
 
 /// 运行中间件示例 / Run middleware examples
 pub async fn run_middleware_examples() -> Result<(), Box<dyn std::error::Error>> {
+    let report = middleware_example().await?;
+
     println!("🔧 中间件示例 / Middleware Examples");
     println!("==================================");
+    for key in ["authenticated", "user_role", "rate_limit_checked"] {
+        println!("   {}: {}", key, report.authorized.get_metadata(key).map(String::as_str).unwrap_or("-"));
+    }
+    println!("   缺少令牌 / Missing token: {}", report.missing_token_error);
+    println!("   无效令牌 / Invalid token: {}", report.invalid_token_error);
+    println!("   到达链尾的请求 / Requests reaching the end of the chain: {}", report.probe_calls);
+    Ok(())
+}
 
-    // 模拟中间件功能 / Simulate middleware functionality
-    println!("\n1. 认证中间件示例 / Authentication Middleware Example");
-    println!("   - 模拟 API 密钥验证 / Simulating API key validation");
-    println!("   - 模拟用户权限检查 / Simulating user permission check");
-
-    println!("\n2. 日志中间件示例 / Logging Middleware Example");
-    println!("   - 记录请求信息 / Logging request information");
-    println!("   - 记录响应状态 / Logging response status");
-
-    println!("\n3. 监控中间件示例 / Monitoring Middleware Example");
-    println!("   - 收集性能指标 / Collecting performance metrics");
-    println!("   - 生成监控报告 / Generating monitoring reports");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("\n4. 限流中间件示例 / Rate Limiting Middleware Example");
-    println!("   - 限制请求频率 / Limiting request frequency");
-    println!("   - 处理限流异常 / Handling rate limit exceptions");
+    #[tokio::test]
+    async fn test_middleware_example() {
+        let report = middleware_example().await.unwrap();
+        assert_eq!(report.authorized.get_metadata("authenticated").unwrap(), "true");
+        assert_eq!(report.authorized.get_metadata("user_role").unwrap(), "admin");
+        assert_eq!(report.authorized.get_metadata("rate_limit_checked").unwrap(), "true");
+        assert!(report.authorized.get_metadata("request_end_time").is_some());
+        assert!(report.missing_token_error.contains("Missing authorization token"));
+        assert!(report.invalid_token_error.contains("Invalid authorization token"));
+        // 被拒绝的请求没有到达链尾 / rejected requests never reached the probe
+        assert_eq!(report.probe_calls, 1);
+    }
 
-    println!("\n✅ 中间件示例运行完成 / Middleware examples completed successfully");
-    Ok(())
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn test_run_middleware_examples() {
+        run_middleware_examples().await.unwrap();
+    }
+}
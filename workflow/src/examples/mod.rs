@@ -5,7 +5,9 @@
 
 pub mod advanced_examples;
 pub mod basic_workflow;
+#[cfg(feature = "middleware")]
 pub mod middleware_examples;
+#[cfg(feature = "patterns")]
 pub mod pattern_examples;
 pub mod rust190_examples;
 pub mod simple_example;
@@ -13,7 +15,9 @@ pub mod simple_example;
 // 重新导出示例 / Re-export examples
 pub use advanced_examples::*;
 pub use basic_workflow::*;
+#[cfg(feature = "middleware")]
 pub use middleware_examples::*;
+#[cfg(feature = "patterns")]
 pub use pattern_examples::*;
 pub use rust190_examples::*;
 pub use simple_example::*;
@@ -1,33 +1,162 @@
 //! # 设计模式示例 / Design Pattern Examples
+//!
+//! 用建造者构建工作流定义，通过模式工厂按分类查找并应用模式，并用生产者-消费者通道传递消息。
+//! Builds a workflow definition with the builder, looks patterns up by category through the pattern factory,
+//! and moves messages over a producer-consumer channel.
+
+use crate::patterns::creational::builder::WorkflowBuilder;
+use crate::patterns::{
+    PatternCategory, PatternError, WorkflowChainOfResponsibility, WorkflowConcurrencyManager,
+    WorkflowContext, WorkflowMessage, WorkflowPatternFactory, WorkflowPipeline, WorkflowStrategy,
+};
+use crate::types::{StateTransition, WorkflowDefinition};
+use serde_json::json;
+
+/// 设计模式示例结果 / Design pattern example result
+#[derive(Debug, Clone)]
+pub struct PatternExampleReport {
+    /// 建造者构建的定义 / Definition produced by the builder
+    pub definition: WorkflowDefinition,
+    /// 缺少初始状态时建造者返回的错误 / Builder error when no initial state is set
+    pub builder_error: String,
+    /// 已应用的模式名称（按应用顺序）/ Names of the applied patterns, in order
+    pub applied_patterns: Vec<String>,
+    /// 分类不匹配时查找是否被拒绝 / Whether a lookup with the wrong category was refused
+    pub category_mismatch_refused: bool,
+    /// 消费者收到的消息 ID / Message IDs received by the consumer
+    pub consumed_messages: Vec<String>,
+}
+
+fn transition(from: &str, to: &str) -> StateTransition {
+    StateTransition {
+        from_state: from.to_string(),
+        to_state: to.to_string(),
+        condition: None,
+        actions: Vec::new(),
+        timeout: None,
+    }
+}
+
+/// 运行设计模式示例并返回结构化结果 / Run the design pattern example and return a structured result
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() {
+/// use workflow::examples::pattern_example;
+///
+/// let report = pattern_example().await.unwrap();
+/// assert_eq!(report.definition.initial_state, "received");
+/// assert_eq!(report.consumed_messages, ["msg-1", "msg-2", "msg-3"]);
+/// # }
+/// ```
+pub async fn pattern_example() -> Result<PatternExampleReport, PatternError> {
+    // 建造者模式 / Builder pattern
+    let definition = WorkflowBuilder::new("order_review".to_string())
+        .add_states(vec!["received".to_string(), "reviewed".to_string(), "approved".to_string()])
+        .add_transition(transition("received", "reviewed"))
+        .add_transition(transition("reviewed", "approved"))
+        .initial_state("received".to_string())
+        .add_final_state("approved".to_string())
+        .build()?;
+    let builder_error = match WorkflowBuilder::new("broken".to_string()).add_state("only".to_string()).build() {
+        Err(e) => e,
+        Ok(_) => return Err(PatternError::InvalidContext("builder accepted a definition without initial state".to_string())),
+    };
+
+    // 模式工厂 / Pattern factory
+    let mut factory = WorkflowPatternFactory::new();
+    factory.register_pattern("chain".to_string(), Box::new(WorkflowChainOfResponsibility::new()));
+    factory.register_pattern("strategy".to_string(), Box::new(WorkflowStrategy::new()));
+    factory.register_pattern("pipeline".to_string(), Box::new(WorkflowPipeline::new()));
+
+    let context = WorkflowContext {
+        workflow_id: definition.name.clone(),
+        data: json!({ "order_id": "order-42" }),
+        metadata: Default::default(),
+    };
+    let mut applied_patterns = Vec::new();
+    for (name, category) in [
+        ("chain", PatternCategory::Behavioral),
+        ("strategy", PatternCategory::Behavioral),
+        ("pipeline", PatternCategory::Concurrent),
+    ] {
+        let pattern = factory
+            .create_pattern(name, category)
+            .ok_or_else(|| PatternError::PatternNotSupported(name.to_string()))?;
+        pattern.validate(&context)?;
+        let result = pattern.apply(&context)?;
+        if !result.success || result.data["workflow_id"] != context.workflow_id.as_str() {
+            return Err(PatternError::ApplicationFailed(result.message));
+        }
+        applied_patterns.push(pattern.name().to_string());
+    }
+    let category_mismatch_refused = factory.create_pattern("pipeline", PatternCategory::Structural).is_none();
+
+    // 生产者-消费者 / Producer-consumer
+    let manager = WorkflowConcurrencyManager::new();
+    let (sender, mut receiver) = manager.create_producer_consumer_channel("orders".to_string(), 8).await?;
+    let consumer = tokio::spawn(async move {
+        let mut received = Vec::new();
+        while let Some(message) = receiver.recv().await {
+            received.push(message.id);
+        }
+        received
+    });
+    for i in 1..=3 {
+        let message = WorkflowMessage {
+            id: format!("msg-{i}"),
+            message_type: "order".to_string(),
+            payload: json!({ "seq": i }),
+            timestamp: chrono::Utc::now(),
+        };
+        sender.send(message).await.map_err(|e| PatternError::ApplicationFailed(e.to_string()))?;
+    }
+    // 管理器也持有一个发送端，释放后消费者才会结束 / The manager keeps a sender too; drop it so the consumer finishes
+    drop(sender);
+    drop(manager);
+    let consumed_messages = consumer.await.map_err(|e| PatternError::ApplicationFailed(e.to_string()))?;
+
+    Ok(PatternExampleReport {
+        definition,
+        builder_error,
+        applied_patterns,
+        category_mismatch_refused,
+        consumed_messages,
+    })
+}
 
 /// 运行设计模式示例 / Run design pattern examples
 pub async fn run_pattern_examples() -> Result<(), Box<dyn std::error::Error>> {
+    let report = pattern_example().await?;
+
     println!("🎨 设计模式示例 / Design Pattern Examples");
     println!("========================================");
-
-    println!("\n1. 创建型模式示例 / Creational Patterns Example");
-    println!("   - 建造者模式 / Builder Pattern");
-    println!("   - 工厂模式 / Factory Pattern");
-    println!("   - 单例模式 / Singleton Pattern");
-
-    println!("\n2. 结构型模式示例 / Structural Patterns Example");
-    println!("   - 适配器模式 / Adapter Pattern");
-    println!("   - 桥接模式 / Bridge Pattern");
-    println!("   - 组合模式 / Composite Pattern");
-    println!("   - 装饰器模式 / Decorator Pattern");
-
-    println!("\n3. 行为型模式示例 / Behavioral Patterns Example");
-    println!("   - 观察者模式 / Observer Pattern");
-    println!("   - 策略模式 / Strategy Pattern");
-    println!("   - 状态模式 / State Pattern");
-    println!("   - 命令模式 / Command Pattern");
-
-    println!("\n4. 并发模式示例 / Concurrent Patterns Example");
-    println!("   - Actor 模式 / Actor Pattern");
-    println!("   - 生产者-消费者模式 / Producer-Consumer Pattern");
-    println!("   - 管道模式 / Pipeline Pattern");
-    println!("   - 反应器模式 / Reactor Pattern");
-
-    println!("\n✅ 设计模式示例运行完成 / Design pattern examples completed successfully");
+    println!("   建造者 / Builder: {} states, initial '{}'", report.definition.states.len(), report.definition.initial_state);
+    println!("   建造者校验 / Builder validation: {}", report.builder_error);
+    println!("   已应用模式 / Applied patterns: {}", report.applied_patterns.join(", "));
+    println!("   分类不匹配被拒绝 / Category mismatch refused: {}", report.category_mismatch_refused);
+    println!("   消费的消息 / Consumed messages: {}", report.consumed_messages.join(", "));
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pattern_example() {
+        let report = pattern_example().await.unwrap();
+        assert_eq!(report.definition.states, ["received", "reviewed", "approved"]);
+        assert_eq!(report.definition.final_states, ["approved"]);
+        assert!(report.definition.validate().is_ok());
+        assert!(report.builder_error.contains("Initial state must be set"));
+        assert_eq!(report.applied_patterns, ["WorkflowChainOfResponsibility", "WorkflowStrategy", "WorkflowPipeline"]);
+        assert!(report.category_mismatch_refused);
+        assert_eq!(report.consumed_messages, ["msg-1", "msg-2", "msg-3"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_pattern_examples() {
+        run_pattern_examples().await.unwrap();
+    }
+}
@@ -3,6 +3,9 @@
 //! 本模块展示了如何使用 Rust 1.90 的新特性来构建工作流系统。
 //! This module demonstrates how to use new features from Rust 1.90 to build workflow systems.
 
+// 演示旧引擎，新代码使用 crate::model / Demonstrates the legacy engines, new code uses crate::model
+#![allow(deprecated)]
+
 use crate::rust190::{
     JITOptimizedProcessor, PerformanceBenchmark, AsyncStreamProcessor, AsyncData,
     ConstContextProcessor, StableWorkflowDefinition, StableWorkflowStep, StableWorkflowConfig,
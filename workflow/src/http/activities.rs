@@ -0,0 +1,142 @@
+//! 动态活动 REST API / Dynamic activity REST API
+//!
+//! 当 [`WorkflowApi`](super::workflows::WorkflowApi) 配置了 [`DynamicActivityRegistry`] 时挂载于 `/api/v1/activities`：
+//! 列出已注册的名称，并在工作流之外直接调用活动一次，负载先经活动的校验钩子检查。
+//! Mounted at `/api/v1/activities` when the [`WorkflowApi`](super::workflows::WorkflowApi) has a
+//! [`DynamicActivityRegistry`]: lists the registered names and invokes an activity once outside any workflow, its
+//! payloads checked by the activity's validation hooks.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::{Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::versioning::RouteRegistry;
+use super::workflows::error_response;
+use crate::temporal::{ActivityError, DynamicActivityRegistry};
+
+/// 已注册的动态活动 / Registered dynamic activities
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActivityList {
+    pub activities: Vec<String>,
+}
+
+/// 调用结果 / Invocation result
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActivityInvocation {
+    #[schema(value_type = Object)]
+    pub result: serde_json::Value,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/activities",
+    tag = "activities",
+    responses((status = 200, description = "Registered dynamic activities", body = ActivityList))
+)]
+pub(super) async fn list_activities(State(registry): State<Arc<DynamicActivityRegistry>>) -> Json<ActivityList> {
+    Json(ActivityList {
+        activities: registry.names(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/activities/{name}/invoke",
+    tag = "activities",
+    params(("name" = String, Path, description = "Activity name")),
+    request_body(content = Object, description = "Activity input"),
+    responses(
+        (status = 200, description = "Activity completed", body = ActivityInvocation),
+        (status = 404, description = "Activity not registered", body = super::workflows::ErrorBody),
+        (status = 422, description = "Input or output rejected by the activity's validation", body = super::workflows::ErrorBody),
+        (status = 502, description = "Activity failed", body = super::workflows::ErrorBody)
+    )
+)]
+pub(super) async fn invoke_activity(
+    State(registry): State<Arc<DynamicActivityRegistry>>,
+    Path(name): Path<String>,
+    Json(input): Json<serde_json::Value>,
+) -> Response {
+    if !registry.contains(&name) {
+        return error_response(StatusCode::NOT_FOUND, "ACTIVITY_NOT_FOUND", format!("activity not registered: {}", name));
+    }
+    match registry.invoke(&name, input).await {
+        Ok(result) => Json(ActivityInvocation { result }).into_response(),
+        Err(e @ (ActivityError::ValidationFailed(_) | ActivityError::InvalidInput(_))) => {
+            error_response(StatusCode::UNPROCESSABLE_ENTITY, "INVALID_PAYLOAD", e.to_string())
+        }
+        Err(e) => error_response(StatusCode::BAD_GATEWAY, "ACTIVITY_FAILED", e.to_string()),
+    }
+}
+
+/// 动态活动路由，挂载于 `/api/v1` 下 / Dynamic activity routes, nested under `/api/v1`
+pub(crate) fn routes(registry: Arc<DynamicActivityRegistry>) -> Router {
+    Router::new()
+        .route("/activities", get(list_activities))
+        .route("/activities/{name}/invoke", post(invoke_activity))
+        .with_state(registry)
+}
+
+/// 在注册表中登记动态活动路由 / Add the dynamic activity routes to a registry
+pub fn register_routes(registry: RouteRegistry) -> RouteRegistry {
+    registry
+        .route(Method::GET, "/api/v1/activities")
+        .route(Method::POST, "/api/v1/activities/{name}/invoke")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::workflows::WorkflowApi;
+    use crate::temporal::{DynamicActivity, WorkflowWorker};
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn call(app: &Router, method: Method, uri: &str, body: Option<serde_json::Value>) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder().method(method).uri(uri).header("content-type", "application/json");
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let response = app.clone().oneshot(request.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_invoke_dynamic_activity_over_http() {
+        let registry = Arc::new(DynamicActivityRegistry::new());
+        registry.register(
+            DynamicActivity::new("double", |_ctx, input: serde_json::Value| async move {
+                match input.as_i64() {
+                    Some(n) if n < 1000 => Ok(serde_json::json!(n * 2)),
+                    _ => Err(ActivityError::ExecutionFailed("overflow".to_string())),
+                }
+            })
+            .validate_input(|input| if input.is_number() { Ok(()) } else { Err("expected a number".to_string()) }),
+        );
+        let worker = WorkflowWorker::default().with_dynamic_activities(registry);
+        let app = crate::http::build_router_with_workflows(WorkflowApi::from_worker(&worker));
+
+        let (status, body) = call(&app, Method::GET, "/api/v1/activities", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["activities"], serde_json::json!(["double"]));
+
+        let (status, body) = call(&app, Method::POST, "/api/v1/activities/double/invoke", Some(serde_json::json!(21))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["result"], 42);
+        let (status, body) = call(&app, Method::POST, "/api/v1/activities/double/invoke", Some(serde_json::json!("x"))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "INVALID_PAYLOAD");
+        let (status, _) = call(&app, Method::POST, "/api/v1/activities/double/invoke", Some(serde_json::json!(5000))).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        let (status, body) = call(&app, Method::POST, "/api/v1/activities/triple/invoke", Some(serde_json::json!(1))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "ACTIVITY_NOT_FOUND");
+    }
+}
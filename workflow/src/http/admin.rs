@@ -0,0 +1,262 @@
+//! 运行时内省 REST API / Runtime introspection REST API
+//!
+//! 当 [`WorkflowApi`](super::workflows::WorkflowApi) 带有工作者状态时挂载于 `/api/v1/admin` 下，列出已注册的类型、
+//! 工作者当前配置、粘性缓存统计、任务队列积压、进行中的执行数与分页的死信，供运维排查线上部署。启用 RBAC 时需要
+//! admin 角色。
+//! Mounted under `/api/v1/admin` when the [`WorkflowApi`](super::workflows::WorkflowApi) has a worker status; lists
+//! the registered types, the worker's current settings, sticky cache statistics, task queue depths, in-flight
+//! execution counts and, page by page, dead letters, for operators debugging a live deployment. Needs the admin role
+//! when RBAC is enabled.
+
+use std::collections::HashMap;
+
+use axum::extract::{Query, State};
+use axum::http::{Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::versioning::RouteRegistry;
+use super::workflows::{error_response, invalid_page_token, ErrorBody, WorkflowApi};
+use crate::temporal::event::HistoryLimits;
+use crate::temporal::{DeadLetter, InFlightCounts, QueueDepths, StickyCacheStats, WorkerStatus};
+use crate::util::pagination::{filter_hash, PageRequest, PageToken};
+
+/// 已注册的类型 / Registered types
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RegisteredTypes {
+    pub workflows: Vec<String>,
+    pub activities: Vec<String>,
+}
+
+/// 工作者当前配置与生命周期 / Current settings and lifecycle of the worker
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WorkerSettings {
+    pub task_queue: String,
+    pub identity: String,
+    /// 当前调整后的值 / As currently tuned
+    pub max_concurrent_workflow_tasks: usize,
+    /// 当前调整后的值 / As currently tuned
+    pub max_concurrent_activity_tasks: usize,
+    pub max_concurrent_sessions: usize,
+    pub poll_timeout_ms: u64,
+    pub sticky_cache_size: usize,
+    pub max_task_failures: u32,
+    pub history_limits: HistoryLimits,
+    pub tags: HashMap<String, String>,
+    pub running: bool,
+    pub shutting_down: bool,
+}
+
+impl From<&WorkerStatus> for WorkerSettings {
+    fn from(status: &WorkerStatus) -> Self {
+        let config = status.config();
+        Self {
+            task_queue: config.task_queue,
+            identity: config.identity,
+            max_concurrent_workflow_tasks: config.max_concurrent_workflow_tasks,
+            max_concurrent_activity_tasks: config.max_concurrent_activity_tasks,
+            max_concurrent_sessions: config.max_concurrent_sessions,
+            poll_timeout_ms: config.poll_timeout.as_millis() as u64,
+            sticky_cache_size: config.sticky_cache_size,
+            max_task_failures: config.max_task_failures,
+            history_limits: config.history_limits,
+            tags: config.tags,
+            running: status.is_running(),
+            shutting_down: status.is_shutting_down(),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/types",
+    tag = "admin",
+    responses((status = 200, description = "Registered workflow and activity types", body = RegisteredTypes))
+)]
+pub(super) async fn registered_types(State(status): State<WorkerStatus>) -> Json<RegisteredTypes> {
+    Json(RegisteredTypes {
+        workflows: status.workflow_types(),
+        activities: status.activity_types(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/worker",
+    tag = "admin",
+    responses((status = 200, description = "Current worker settings", body = WorkerSettings))
+)]
+pub(super) async fn worker_settings(State(status): State<WorkerStatus>) -> Json<WorkerSettings> {
+    Json(WorkerSettings::from(&status))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/sticky-cache",
+    tag = "admin",
+    responses((status = 200, description = "Sticky cache statistics", body = StickyCacheStats))
+)]
+pub(super) async fn sticky_cache(State(status): State<WorkerStatus>) -> Json<StickyCacheStats> {
+    Json(status.sticky_cache())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/queues",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Tasks waiting on each lane of the worker's task queue", body = QueueDepths),
+        (status = 503, description = "The task queue backend is unreachable", body = ErrorBody)
+    )
+)]
+pub(super) async fn queue_depths(State(status): State<WorkerStatus>) -> Response {
+    match status.queue_depths().await {
+        Ok(depths) => Json(depths).into_response(),
+        Err(e) => error_response(StatusCode::SERVICE_UNAVAILABLE, "TASK_QUEUE_UNAVAILABLE", e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/executions",
+    tag = "admin",
+    responses((status = 200, description = "Executions and tasks in flight on the worker", body = InFlightCounts))
+)]
+pub(super) async fn in_flight(State(status): State<WorkerStatus>) -> Json<InFlightCounts> {
+    Json(status.in_flight())
+}
+
+/// 死信分页 / Dead-letter paging
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DeadLetterQuery {
+    /// 每页死信数，缺省 100，最多 1000 / Dead letters per page, defaults to 100, at most 1000
+    pub limit: Option<usize>,
+    /// 上一页返回的 `next_page_token` / `next_page_token` of the previous page
+    #[param(value_type = Option<String>)]
+    pub page_token: Option<PageToken>,
+}
+
+/// 一页死信 / One page of dead letters
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeadLetterList {
+    /// 最早进入死信队列的在前 / Oldest first
+    #[schema(value_type = Vec<Object>)]
+    pub dead_letters: Vec<DeadLetter>,
+    /// 下一页的 `page_token`；最后一页为空 / `page_token` of the next page, absent on the last page
+    #[schema(value_type = Option<String>)]
+    pub next_page_token: Option<PageToken>,
+}
+
+/// 死信的排序键：最早的在前，同时进入的按 ID / Dead-letter order: oldest first, letters dead-lettered together by ID
+fn dead_letter_key(letter: &DeadLetter) -> (String, String) {
+    (format!("{:020}", letter.dead_lettered_at.timestamp_micros()), letter.id.to_string())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/dead-letters",
+    tag = "admin",
+    params(DeadLetterQuery),
+    responses(
+        (status = 200, description = "One page of the tasks moved to the dead-letter queue, oldest first", body = DeadLetterList),
+        (status = 400, description = "Invalid page token", body = ErrorBody)
+    )
+)]
+pub(super) async fn dead_letters(State(api): State<WorkflowApi>, Query(query): Query<DeadLetterQuery>) -> Response {
+    let filters = filter_hash(&serde_json::json!({ "dead_letters": {} }));
+    let mut letters = api.client().dead_letters();
+    letters.sort_by_cached_key(dead_letter_key);
+    let request = PageRequest {
+        page_size: query.limit,
+        page_token: query.page_token,
+    };
+    match api.page_tokens().paginate(&letters, dead_letter_key, &filters, &request) {
+        Ok(page) => Json(DeadLetterList {
+            dead_letters: page.items,
+            next_page_token: page.next_token,
+        })
+        .into_response(),
+        Err(e) => invalid_page_token(e),
+    }
+}
+
+/// 内省路由，挂载于 `/api/v1` 下 / Introspection routes, nested under `/api/v1`
+pub(crate) fn routes(status: WorkerStatus) -> Router {
+    Router::new()
+        .route("/admin/types", get(registered_types))
+        .route("/admin/worker", get(worker_settings))
+        .route("/admin/sticky-cache", get(sticky_cache))
+        .route("/admin/queues", get(queue_depths))
+        .route("/admin/executions", get(in_flight))
+        .with_state(status)
+}
+
+/// 死信路由，与内省路由一同挂载 / Dead-letter routes, mounted with the introspection routes
+pub(crate) fn dead_letter_routes(api: WorkflowApi) -> Router {
+    Router::new().route("/admin/dead-letters", get(dead_letters)).with_state(api)
+}
+
+/// 在注册表中登记内省路由 / Add the introspection routes to a registry
+pub fn register_routes(registry: RouteRegistry) -> RouteRegistry {
+    registry
+        .route(Method::GET, "/api/v1/admin/types")
+        .route(Method::GET, "/api/v1/admin/worker")
+        .route(Method::GET, "/api/v1/admin/sticky-cache")
+        .route(Method::GET, "/api/v1/admin/queues")
+        .route(Method::GET, "/api/v1/admin/executions")
+        .route(Method::GET, "/api/v1/admin/dead-letters")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::http::build_router_with_workflows;
+    use crate::http::workflows::WorkflowApi;
+    use crate::temporal::task_queue::{Task, WorkflowTask};
+    use crate::temporal::{WorkerConfig, WorkflowExecution, WorkflowId, WorkflowWorker};
+
+    async fn get(app: &axum::Router, uri: &str) -> serde_json::Value {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{uri}");
+        serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_admin_introspection_over_http() {
+        let worker = WorkflowWorker::new(WorkerConfig::builder().task_queue("orders").max_concurrent_activity_tasks(5).build());
+        worker.load().set_max_concurrent_workflow_tasks(3);
+        let task = Task::Workflow(WorkflowTask {
+            execution: WorkflowExecution::new(WorkflowId::new("o-1")),
+            workflow_type: "order".to_string(),
+            task_queue: "orders".to_string(),
+            input: serde_json::json!(null),
+            priority: Default::default(),
+            trace_context: Default::default(),
+        });
+        worker.client().task_queue().push("orders", task).await.unwrap();
+        let app = build_router_with_workflows(WorkflowApi::from_worker(&worker));
+
+        let settings = get(&app, "/api/v1/admin/worker").await;
+        assert_eq!(settings["task_queue"], "orders");
+        assert_eq!(settings["max_concurrent_workflow_tasks"], 3);
+        assert_eq!(settings["max_concurrent_activity_tasks"], 5);
+        assert_eq!(settings["running"], false);
+
+        let queues = get(&app, "/api/v1/admin/queues").await;
+        assert_eq!((queues["workflow"].as_u64(), queues["activity"].as_u64()), (Some(1), Some(0)));
+
+        assert_eq!(get(&app, "/api/v1/admin/types").await["workflows"], serde_json::json!([]));
+        assert_eq!(get(&app, "/api/v1/admin/sticky-cache").await["hits"], 0);
+        assert_eq!(get(&app, "/api/v1/admin/executions").await["executions"], 0);
+        let letters = get(&app, "/api/v1/admin/dead-letters").await;
+        assert_eq!(letters["dead_letters"], serde_json::json!([]));
+        assert!(letters["next_page_token"].is_null());
+    }
+}
@@ -0,0 +1,81 @@
+//! 审计日志 REST API / Audit log REST API
+//!
+//! 当 [`WorkflowApi`](super::workflows::WorkflowApi) 配置了审计日志时挂载于 `/api/v1/audit`，以签名的分页令牌按序号读取条目。
+//! Mounted at `/api/v1/audit` when the [`WorkflowApi`](super::workflows::WorkflowApi) has an audit log; pages
+//! through its entries by sequence number with signed page tokens.
+
+use axum::extract::{Query, State};
+use axum::http::{Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::versioning::RouteRegistry;
+use super::workflows::{error_response, invalid_page_token, sequence_after, sequence_token};
+use crate::audit::{AuditEntry, AuditError, AuditLog};
+use crate::util::pagination::{filter_hash, PageToken, PageTokenCodec};
+
+/// 缺省页大小 / Default page size
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// 分页参数 / Paging parameters
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AuditPageQuery {
+    /// 上一页返回的 `next_page_token`，缺省从头读取 / `next_page_token` of the previous page, from the start when absent
+    #[param(value_type = Option<String>)]
+    pub page_token: Option<PageToken>,
+    /// 缺省 100，最多 500 / Defaults to 100, at most 500
+    pub limit: Option<usize>,
+}
+
+/// 一页审计条目 / One page of audit entries
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditList {
+    pub entries: Vec<AuditEntry>,
+    /// 下一页的 `page_token`；没有更多条目时为空 / `page_token` of the next page, absent when there are no more entries
+    #[schema(value_type = Option<String>)]
+    pub next_page_token: Option<PageToken>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/audit",
+    tag = "audit",
+    params(AuditPageQuery),
+    responses(
+        (status = 200, description = "Page of audit entries", body = AuditList),
+        (status = 400, description = "Invalid page token", body = super::workflows::ErrorBody),
+        (status = 501, description = "The audit sink cannot be read back", body = super::workflows::ErrorBody)
+    )
+)]
+pub(super) async fn list_audit_entries(
+    State((audit, tokens)): State<(AuditLog, PageTokenCodec)>,
+    Query(query): Query<AuditPageQuery>,
+) -> Response {
+    let filters = filter_hash("audit");
+    let after = match sequence_after(&tokens, query.page_token.as_ref(), &filters) {
+        Ok(after) => after,
+        Err(e) => return invalid_page_token(e),
+    };
+    match audit.page(after, query.limit.unwrap_or(DEFAULT_PAGE_SIZE)).await {
+        Ok(page) => Json(AuditList {
+            entries: page.entries,
+            next_page_token: page.next_after.map(|after| sequence_token(&tokens, &filters, after)),
+        })
+        .into_response(),
+        Err(e @ AuditError::Unsupported(_)) => error_response(StatusCode::NOT_IMPLEMENTED, "AUDIT_NOT_READABLE", e.to_string()),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", e.to_string()),
+    }
+}
+
+/// 审计路由，挂载于 `/api/v1` 下 / Audit routes, nested under `/api/v1`
+pub(crate) fn routes(audit: AuditLog, tokens: PageTokenCodec) -> Router {
+    Router::new().route("/audit", get(list_audit_entries)).with_state((audit, tokens))
+}
+
+/// 在注册表中登记审计路由 / Add the audit routes to a registry
+pub fn register_routes(registry: RouteRegistry) -> RouteRegistry {
+    registry.route(Method::GET, "/api/v1/audit")
+}
@@ -0,0 +1,452 @@
+//! 认证 / Authentication
+//!
+//! 为 `/api` 下的路由（以及旧的 `/admin` 别名）校验静态 API Key（`X-API-Key` 头）或 JWT
+//! （`Authorization: Bearer`，HMAC 密钥或 JWKS 校验签名），`/health`、`/version`、`/stats` 与文档保持开放。
+//! 认证通过后将 [`Principal`] 放入请求扩展，处理函数可用 `Extension<Principal>` 取得。
+//! Routes under `/api` (and the legacy `/admin` alias) require a static API key (`X-API-Key` header) or a JWT
+//! (`Authorization: Bearer`, signature checked with an HMAC secret or a JWKS); `/health`, `/version`, `/stats` and
+//! the docs stay open. The authenticated [`Principal`] is put in the request extensions, where handlers read it
+//! with `Extension<Principal>`.
+//!
+//! 带签名头的 Webhook 请求不需要凭据，由 Webhook 自己校验签名（见 [`SignedHookRequest`]）。
+//! Webhook requests carrying a signature header need no credentials; the hook checks the signature itself (see
+//! [`SignedHookRequest`]).
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{header, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use sha2::{Digest, Sha256};
+
+use crate::temporal::activities::WEBHOOK_SIGNATURE_HEADER;
+
+/// API Key 请求头 / Header carrying an API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// JWKS 缓存时长 / How long a fetched JWKS is used before it is fetched again
+pub const JWKS_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// 遇到未知 `kid` 时重新拉取 JWKS 的最小间隔 / Minimum interval between refetches caused by an unknown `kid`
+const JWKS_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 认证方式 / How a principal authenticated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    ApiKey,
+    Jwt,
+}
+
+/// 已认证的调用方 / Authenticated caller
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Principal {
+    /// API Key 的名称或 JWT 的 `sub` / Name of the API key, or the `sub` of the JWT
+    pub id: String,
+    pub method: AuthMethod,
+    /// JWT 声明；API Key 为空对象 / JWT claims; an empty object for API keys
+    pub claims: serde_json::Value,
+}
+
+/// JWT 签名密钥来源 / Where JWT signing keys come from
+#[derive(Clone)]
+pub enum JwtKeys {
+    /// HMAC 共享密钥（HS256/HS384/HS512）/ Shared HMAC secret (HS256/HS384/HS512)
+    Secret(Vec<u8>),
+    /// 固定的 JWKS / Fixed JWKS
+    Jwks(JwkSet),
+    /// 从 URL 拉取并缓存的 JWKS / JWKS fetched from a URL and cached
+    JwksUrl(String),
+}
+
+/// JWT 校验配置 / JWT validation settings
+#[derive(Clone)]
+pub struct JwtConfig {
+    pub keys: JwtKeys,
+    /// 要求的 `iss` / Required `iss`
+    pub issuer: Option<String>,
+    /// 要求的 `aud` / Required `aud`
+    pub audience: Option<String>,
+    /// 允许的时钟偏差（秒）/ Allowed clock skew in seconds
+    pub leeway_secs: u64,
+}
+
+impl JwtConfig {
+    pub fn new(keys: JwtKeys) -> Self {
+        Self {
+            keys,
+            issuer: None,
+            audience: None,
+            leeway_secs: 60,
+        }
+    }
+
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+}
+
+#[derive(Default)]
+struct CachedJwks {
+    keys: Option<JwkSet>,
+    fetched_at: Option<Instant>,
+}
+
+/// 认证失败原因 / Why authentication failed
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("缺少凭据 / Missing credentials")]
+    MissingCredentials,
+    #[error("无效的 API Key / Invalid API key")]
+    InvalidApiKey,
+    #[error("未配置 JWT 校验 / JWT authentication is not configured")]
+    JwtNotConfigured,
+    #[error("无效的令牌 / Invalid token: {0}")]
+    InvalidToken(String),
+    #[error("无法获取 JWKS / Cannot fetch JWKS: {0}")]
+    Jwks(String),
+}
+
+/// 校验 API Key 与 JWT / Checks API keys and JWTs
+pub struct Authenticator {
+    /// SHA-256(key) -> 名称 / SHA-256(key) -> name; keys themselves are not kept
+    api_keys: HashMap<[u8; 32], String>,
+    jwt: Option<JwtConfig>,
+    jwks: tokio::sync::Mutex<CachedJwks>,
+    http: reqwest::Client,
+}
+
+impl Default for Authenticator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn digest(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+impl Authenticator {
+    /// 不接受任何凭据 / Accepts no credentials until keys or JWT validation are added
+    pub fn new() -> Self {
+        Self {
+            api_keys: HashMap::new(),
+            jwt: None,
+            jwks: tokio::sync::Mutex::new(CachedJwks::default()),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// 接受 `key`，认证为 `name` / Accept `key`, authenticating as `name`
+    pub fn api_key(mut self, key: impl AsRef<str>, name: impl Into<String>) -> Self {
+        self.api_keys.insert(digest(key.as_ref()), name.into());
+        self
+    }
+
+    /// 接受按 `config` 校验的 JWT / Accept JWTs validated with `config`
+    pub fn jwt(mut self, config: JwtConfig) -> Self {
+        self.jwt = Some(config);
+        self
+    }
+
+    /// 从环境变量读取配置；均未设置时返回 `None` / Read the configuration from the environment; `None` if nothing is set
+    ///
+    /// - `WORKFLOW_API_KEYS`: `name:key` 逗号分隔 / comma-separated `name:key` pairs
+    /// - `WORKFLOW_JWT_SECRET` 或 / or `WORKFLOW_JWT_JWKS_URL`
+    /// - `WORKFLOW_JWT_ISSUER`, `WORKFLOW_JWT_AUDIENCE`
+    pub fn from_env() -> Option<Self> {
+        let mut auth = Self::new();
+        let mut configured = false;
+        if let Ok(keys) = std::env::var("WORKFLOW_API_KEYS") {
+            for entry in keys.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                match entry.split_once(':') {
+                    Some((name, key)) => auth = auth.api_key(key, name),
+                    None => tracing::warn!("ignoring WORKFLOW_API_KEYS entry without a name"),
+                }
+            }
+            configured = true;
+        }
+        let keys = std::env::var("WORKFLOW_JWT_SECRET")
+            .map(|secret| JwtKeys::Secret(secret.into_bytes()))
+            .or_else(|_| std::env::var("WORKFLOW_JWT_JWKS_URL").map(JwtKeys::JwksUrl));
+        if let Ok(keys) = keys {
+            let mut config = JwtConfig::new(keys);
+            config.issuer = std::env::var("WORKFLOW_JWT_ISSUER").ok();
+            config.audience = std::env::var("WORKFLOW_JWT_AUDIENCE").ok();
+            auth = auth.jwt(config);
+            configured = true;
+        }
+        configured.then_some(auth)
+    }
+
+    /// 认证请求头 / Authenticate the credentials in request headers
+    pub async fn authenticate(&self, headers: &axum::http::HeaderMap) -> Result<Principal, AuthError> {
+        if let Some(key) = headers.get(API_KEY_HEADER) {
+            let key = key.to_str().map_err(|_| AuthError::InvalidApiKey)?;
+            let name = self.api_keys.get(&digest(key)).ok_or(AuthError::InvalidApiKey)?;
+            return Ok(Principal {
+                id: name.clone(),
+                method: AuthMethod::ApiKey,
+                claims: serde_json::json!({}),
+            });
+        }
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or(AuthError::MissingCredentials)?;
+        self.validate_jwt(token.trim()).await
+    }
+
+    async fn validate_jwt(&self, token: &str) -> Result<Principal, AuthError> {
+        let config = self.jwt.as_ref().ok_or(AuthError::JwtNotConfigured)?;
+        let jwt_header = jsonwebtoken::decode_header(token).map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+        let key = match &config.keys {
+            JwtKeys::Secret(secret) => {
+                if !matches!(jwt_header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+                    return Err(AuthError::InvalidToken(format!("unexpected algorithm {:?}", jwt_header.alg)));
+                }
+                DecodingKey::from_secret(secret)
+            }
+            JwtKeys::Jwks(jwks) => jwk_key(jwks, &jwt_header)?,
+            JwtKeys::JwksUrl(url) => {
+                let jwks = self.fetch_jwks(url, jwt_header.kid.as_deref()).await?;
+                jwk_key(&jwks, &jwt_header)?
+            }
+        };
+
+        let mut validation = Validation::new(jwt_header.alg);
+        validation.leeway = config.leeway_secs;
+        if let Some(issuer) = &config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        let claims = jsonwebtoken::decode::<serde_json::Value>(token, &key, &validation)
+            .map_err(|e| AuthError::InvalidToken(e.to_string()))?
+            .claims;
+        let id = claims
+            .get("sub")
+            .and_then(|s| s.as_str())
+            .ok_or_else(|| AuthError::InvalidToken("missing sub claim".to_string()))?
+            .to_string();
+        Ok(Principal {
+            id,
+            method: AuthMethod::Jwt,
+            claims,
+        })
+    }
+
+    /// 缓存的 JWKS；过期或缺少 `kid` 时重新拉取 / Cached JWKS, refetched when stale or missing `kid`
+    async fn fetch_jwks(&self, url: &str, kid: Option<&str>) -> Result<JwkSet, AuthError> {
+        let mut cache = self.jwks.lock().await;
+        let age = cache.fetched_at.map(|t| t.elapsed());
+        let stale = age.is_none_or(|age| age >= JWKS_CACHE_TTL);
+        let unknown_kid = match (&cache.keys, kid) {
+            (Some(keys), Some(kid)) => keys.find(kid).is_none(),
+            _ => false,
+        };
+        if stale || (unknown_kid && age.is_none_or(|age| age >= JWKS_MIN_REFRESH_INTERVAL)) {
+            let fetched = async {
+                self.http
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json::<JwkSet>()
+                    .await
+            }
+            .await;
+            match fetched {
+                Ok(keys) => {
+                    cache.keys = Some(keys);
+                    cache.fetched_at = Some(Instant::now());
+                }
+                // 拉取失败时继续使用旧的 JWKS / keep using the previous JWKS if the fetch failed
+                Err(e) if cache.keys.is_some() => tracing::warn!(error = %e, "JWKS refresh failed"),
+                Err(e) => return Err(AuthError::Jwks(e.to_string())),
+            }
+        }
+        cache.keys.clone().ok_or_else(|| AuthError::Jwks("no keys".to_string()))
+    }
+}
+
+fn jwk_key(jwks: &JwkSet, jwt_header: &jsonwebtoken::Header) -> Result<DecodingKey, AuthError> {
+    let jwk = match &jwt_header.kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .ok_or_else(|| AuthError::InvalidToken("no matching key".to_string()))?;
+    if let Some(alg) = jwk.common.key_algorithm
+        && alg.to_string() != format!("{:?}", jwt_header.alg)
+    {
+        return Err(AuthError::InvalidToken(format!("key {} does not allow {:?}", alg, jwt_header.alg)));
+    }
+    DecodingKey::from_jwk(jwk).map_err(|e| AuthError::InvalidToken(e.to_string()))
+}
+
+/// 需要认证的路径 / Paths that require authentication
+pub fn requires_auth(path: &str) -> bool {
+    let under = |prefix: &str| path == prefix || path.starts_with(&format!("{prefix}/"));
+    under("/api") || under("/admin") || under("/debug")
+}
+
+/// 未带凭据、改由 Webhook 签名证明来源的请求 / Request without credentials whose webhook signature proves where it
+/// came from
+///
+/// 认证中间件将其放入请求扩展；没有密钥的 Webhook 拒绝此类请求。
+/// The authentication middleware puts it in the request extensions; hooks without a secret reject such requests.
+#[derive(Debug, Clone, Copy)]
+pub struct SignedHookRequest;
+
+/// Webhook 路由，包括命名空间内的 / Hook routes, including those inside a namespace
+pub fn is_hook_route(path: &str) -> bool {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    matches!(segments.as_slice(), ["api", "v1", "hooks", _] | ["api", "v1", "namespaces", _, "hooks", _])
+}
+
+/// 认证中间件；未配置认证器时放行 / Authentication middleware; passes everything when no authenticator is configured
+pub(crate) async fn authenticate(
+    State(auth): State<Option<Arc<Authenticator>>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(auth) = auth else {
+        return next.run(req).await;
+    };
+    if !requires_auth(req.uri().path()) {
+        return next.run(req).await;
+    }
+    if is_hook_route(req.uri().path()) && req.headers().contains_key(WEBHOOK_SIGNATURE_HEADER) {
+        req.extensions_mut().insert(SignedHookRequest);
+        return next.run(req).await;
+    }
+    match auth.authenticate(req.headers()).await {
+        Ok(principal) => {
+            metrics::counter!("http_auth_total", "result" => "ok").increment(1);
+            req.extensions_mut().insert(principal);
+            next.run(req).await
+        }
+        Err(e) => {
+            metrics::counter!("http_auth_total", "result" => "rejected").increment(1);
+            tracing::debug!(error = %e, path = %req.uri().path(), "rejected unauthenticated request");
+            let status = match e {
+                AuthError::Jwks(_) => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::UNAUTHORIZED,
+            };
+            let mut response = (
+                status,
+                Json(serde_json::json!({ "code": "UNAUTHENTICATED", "message": e.to_string() })),
+            )
+                .into_response();
+            response.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap;
+    use jsonwebtoken::{EncodingKey, Header};
+
+    fn token(secret: &[u8], claims: serde_json::Value) -> String {
+        jsonwebtoken::encode(&Header::default(), &claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, format!("Bearer {token}").parse().unwrap());
+        headers
+    }
+
+    fn exp() -> u64 {
+        jsonwebtoken::get_current_timestamp() + 600
+    }
+
+    #[tokio::test]
+    async fn test_api_keys() {
+        let auth = Authenticator::new().api_key("s3cret", "ci");
+        let mut headers = HeaderMap::new();
+        assert!(matches!(auth.authenticate(&headers).await, Err(AuthError::MissingCredentials)));
+        headers.insert(API_KEY_HEADER, "wrong".parse().unwrap());
+        assert!(matches!(auth.authenticate(&headers).await, Err(AuthError::InvalidApiKey)));
+        headers.insert(API_KEY_HEADER, "s3cret".parse().unwrap());
+        let principal = auth.authenticate(&headers).await.unwrap();
+        assert_eq!((principal.id.as_str(), principal.method), ("ci", AuthMethod::ApiKey));
+    }
+
+    #[tokio::test]
+    async fn test_jwt_with_secret_checks_issuer_and_expiry() {
+        let auth = Authenticator::new().jwt(JwtConfig::new(JwtKeys::Secret(b"k".to_vec())).issuer("https://idp"));
+        let valid = token(b"k", serde_json::json!({ "sub": "alice", "iss": "https://idp", "exp": exp(), "roles": ["operator"] }));
+        let principal = auth.authenticate(&bearer(&valid)).await.unwrap();
+        assert_eq!(principal.id, "alice");
+        assert_eq!(principal.claims["roles"][0], "operator");
+
+        let other_issuer = token(b"k", serde_json::json!({ "sub": "alice", "iss": "https://evil", "exp": exp() }));
+        let expired = token(b"k", serde_json::json!({ "sub": "alice", "iss": "https://idp", "exp": 1 }));
+        let other_key = token(b"x", serde_json::json!({ "sub": "alice", "iss": "https://idp", "exp": exp() }));
+        for token in [other_issuer, expired, other_key, "garbage".to_string()] {
+            assert!(matches!(auth.authenticate(&bearer(&token)).await, Err(AuthError::InvalidToken(_))));
+        }
+        assert!(matches!(
+            Authenticator::new().authenticate(&bearer("x")).await,
+            Err(AuthError::JwtNotConfigured)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_jwt_with_jwks_selects_key_by_kid() {
+        use base64::Engine;
+        let secret = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(b"jwks-secret");
+        let jwks: JwkSet = serde_json::from_value(serde_json::json!({
+            "keys": [{ "kty": "oct", "kid": "k1", "alg": "HS256", "k": secret }]
+        }))
+        .unwrap();
+        let auth = Authenticator::new().jwt(JwtConfig::new(JwtKeys::Jwks(jwks)));
+        let claims = serde_json::json!({ "sub": "svc", "exp": exp() });
+        let sign = |kid: &str| {
+            let header = Header {
+                kid: Some(kid.to_string()),
+                ..Header::default()
+            };
+            jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(b"jwks-secret")).unwrap()
+        };
+        assert_eq!(auth.authenticate(&bearer(&sign("k1"))).await.unwrap().id, "svc");
+
+        let unknown = sign("k2");
+        assert!(matches!(auth.authenticate(&bearer(&unknown)).await, Err(AuthError::InvalidToken(_))));
+    }
+
+    #[test]
+    fn test_protected_paths() {
+        assert!(requires_auth("/api/v1/workflows"));
+        assert!(requires_auth("/api/versions"));
+        assert!(requires_auth("/admin/read-only"));
+        assert!(requires_auth("/debug/pprof/profile"));
+        assert!(!requires_auth("/health"));
+        assert!(!requires_auth("/apix"));
+        assert!(!requires_auth("/docs/"));
+        assert!(is_hook_route("/api/v1/hooks/gateway"));
+        assert!(is_hook_route("/api/v1/namespaces/acme/hooks/gateway"));
+        assert!(!is_hook_route("/api/v1/hooks"));
+    }
+}
@@ -0,0 +1,301 @@
+//! 批量操作 REST API / Batch operation REST API
+//!
+//! 与工作流路由一同挂载于 `/api/v1/batches`：对一组工作流 ID 或按搜索属性筛选出的运行中工作流批量取消、终止或发送信号，
+//! 操作在后台以系统工作流执行，可分页列出并查询其进度。
+//! Mounted with the workflow routes at `/api/v1/batches`: cancels, terminates or signals a list of workflow IDs or
+//! the running workflows matching a search-attribute filter, as a system workflow in the background that can be
+//! listed page by page and whose progress can be read.
+
+use axum::extract::{Path, Query, State};
+use axum::http::{Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::auth::Principal;
+use super::versioning::RouteRegistry;
+use super::workflows::{
+    error_response, invalid_page_token, listing_key, workflow_error_response, ErrorBody, WorkflowApi,
+};
+use crate::audit::{AuditAction, AuditEntry};
+use crate::temporal::batch::BATCH_WORKFLOW_TYPE;
+use crate::temporal::{
+    BatchOperation, BatchProgress, BatchRequest, StartWorkflowOptions, WorkflowExecutionStatus, WorkflowFilter,
+    WorkflowId,
+};
+use crate::util::pagination::{filter_hash, PageRequest, PageToken};
+
+/// 批量操作请求 / Batch operation request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct StartBatchRequest {
+    /// 批量操作 ID，缺省时生成 / Batch ID, generated when absent
+    pub batch_id: Option<String>,
+    /// 操作与目标 / Operation and target
+    #[serde(flatten)]
+    #[schema(value_type = Object)]
+    pub request: BatchRequest,
+}
+
+/// 已开始的批量操作 / Started batch operation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StartedBatch {
+    pub batch_id: String,
+    pub run_id: String,
+}
+
+/// 批量操作筛选与分页 / Batch operation filter and paging
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ListBatchesQuery {
+    /// 如 `Running`、`Completed` / E.g. `Running`, `Completed`
+    #[param(value_type = Option<String>)]
+    pub status: Option<WorkflowExecutionStatus>,
+    /// 每页批量操作数，缺省 100，最多 1000 / Batches per page, defaults to 100, at most 1000
+    pub limit: Option<usize>,
+    /// 上一页返回的 `next_page_token`，须与相同的过滤条件一起使用 / `next_page_token` of the previous page, valid
+    /// only with the same filter
+    #[param(value_type = Option<String>)]
+    pub page_token: Option<PageToken>,
+}
+
+/// 一页批量操作 / One page of batch operations
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchList {
+    /// 最近开始的在前 / Most recently started first
+    #[schema(value_type = Vec<Object>)]
+    pub batches: Vec<BatchProgress>,
+    /// 下一页的 `page_token`；最后一页为空 / `page_token` of the next page, absent on the last page
+    #[schema(value_type = Option<String>)]
+    pub next_page_token: Option<PageToken>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/batches",
+    tag = "batches",
+    params(ListBatchesQuery),
+    responses(
+        (status = 200, description = "One page of the progress of batch operations, most recently started first", body = BatchList),
+        (status = 400, description = "Invalid page token", body = ErrorBody)
+    )
+)]
+pub(super) async fn list_batches(State(api): State<WorkflowApi>, Query(query): Query<ListBatchesQuery>) -> Response {
+    let filters = filter_hash(&serde_json::json!({ "batches": { "status": query.status } }));
+    let mut filter = WorkflowFilter::new().workflow_type(BATCH_WORKFLOW_TYPE);
+    if let Some(status) = query.status {
+        filter = filter.status(status);
+    }
+    let mut batches = match api.client().list_workflows(&filter).await {
+        Ok(batches) => batches,
+        Err(e) => return workflow_error_response(e),
+    };
+    batches.sort_by_cached_key(listing_key);
+    let request = PageRequest {
+        page_size: query.limit,
+        page_token: query.page_token,
+    };
+    match api.page_tokens().paginate(&batches, listing_key, &filters, &request) {
+        Ok(page) => Json(BatchList {
+            batches: page.items.iter().map(BatchProgress::from_info).collect(),
+            next_page_token: page.next_token,
+        })
+        .into_response(),
+        Err(e) => invalid_page_token(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/batches",
+    tag = "batches",
+    request_body(content = Object, description = "`operation` (cancel, terminate or signal), `target` (workflow_ids or filter) and an optional `batch_id`"),
+    responses(
+        (status = 202, description = "Batch started", body = StartedBatch),
+        (status = 409, description = "A batch with this ID is already running", body = ErrorBody)
+    )
+)]
+pub(super) async fn start_batch(
+    State(api): State<WorkflowApi>,
+    principal: Option<Extension<Principal>>,
+    Json(body): Json<StartBatchRequest>,
+) -> Response {
+    let requested = body.batch_id.clone().unwrap_or_default();
+    let options = StartWorkflowOptions {
+        workflow_id: body.batch_id.map(WorkflowId::new),
+        ..StartWorkflowOptions::default()
+    };
+    let action = match &body.request.operation {
+        BatchOperation::Cancel => AuditAction::Cancel,
+        BatchOperation::Terminate { .. } => AuditAction::Terminate,
+        BatchOperation::Signal { .. } => AuditAction::Signal,
+    };
+    let payload = serde_json::to_value(&body.request).unwrap_or_default();
+    let result = api.client().batch(body.request, options).await;
+    let batch_id = match &result {
+        Ok(handle) => handle.execution().workflow_id.to_string(),
+        Err(_) => requested,
+    };
+    api.audit(principal, AuditEntry::new(action, &batch_id).target("batch").payload(&payload), result.is_ok())
+        .await;
+    match result {
+        Ok(handle) => (
+            StatusCode::ACCEPTED,
+            Json(StartedBatch {
+                batch_id,
+                run_id: handle.execution().run_id.to_string(),
+            }),
+        )
+            .into_response(),
+        Err(e) => workflow_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/batches/{batch_id}",
+    tag = "batches",
+    params(("batch_id" = String, Path, description = "Batch operation")),
+    responses(
+        (status = 200, description = "Progress of the batch operation", body = Object),
+        (status = 404, description = "No batch with this ID", body = ErrorBody)
+    )
+)]
+pub(super) async fn batch_progress(State(api): State<WorkflowApi>, Path(batch_id): Path<String>) -> Response {
+    match api.client().batch_progress(&WorkflowId::new(batch_id)).await {
+        Ok(Some(progress)) => Json::<BatchProgress>(progress).into_response(),
+        Ok(None) => error_response(StatusCode::NOT_FOUND, "BATCH_NOT_FOUND", "batch not found"),
+        Err(e) => workflow_error_response(e),
+    }
+}
+
+/// 批量操作路由，挂载于 `/api/v1` 下 / Batch operation routes, nested under `/api/v1`
+pub(crate) fn routes(api: WorkflowApi) -> Router {
+    Router::new()
+        .route("/batches", get(list_batches).post(start_batch))
+        .route("/batches/{batch_id}", get(batch_progress))
+        .with_state(api)
+}
+
+/// 在注册表中登记批量操作路由 / Add the batch operation routes to a registry
+pub fn register_routes(registry: RouteRegistry) -> RouteRegistry {
+    registry
+        .route(Method::GET, "/api/v1/batches")
+        .route(Method::POST, "/api/v1/batches")
+        .route(Method::GET, "/api/v1/batches/{batch_id}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::{Signal, WorkerConfig, Workflow, WorkflowContext, WorkflowError, WorkflowWorker};
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    /// 放行信号 / Release signal
+    #[derive(Serialize, Deserialize)]
+    struct Release;
+
+    impl Signal for Release {
+        fn name() -> &'static str {
+            "release"
+        }
+    }
+
+    /// 等待放行 / Waits to be released
+    struct Held;
+
+    impl Workflow for Held {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "held"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<(), WorkflowError> {
+            ctx.wait_for_signal::<Release>(None).await.map(drop)
+        }
+    }
+
+    async fn call(app: &Router, method: Method, uri: &str, body: Value) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_batch_started_and_progress_read() {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        }));
+        worker.register_workflow::<Held>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+        let mut held = Vec::new();
+        for i in 0..2 {
+            let options = StartWorkflowOptions {
+                workflow_id: Some(WorkflowId::new(format!("held-{}", i))),
+                ..StartWorkflowOptions::default()
+            };
+            held.push(worker.client().start_workflow::<Held>((), options).await.unwrap());
+        }
+        let app = crate::http::build_router_with_workflows(WorkflowApi::from_worker(&worker));
+
+        let body = json!({
+            "batch_id": "release-all",
+            "operation": {"type": "signal", "signal_name": "release", "input": null},
+            "target": {"filter": {"workflow_type": "held"}}
+        });
+        let (status, started) = call(&app, Method::POST, "/api/v1/batches", body).await;
+        assert_eq!((status, started["batch_id"].as_str()), (StatusCode::ACCEPTED, Some("release-all")));
+        for handle in held {
+            handle.result().await.unwrap();
+        }
+
+        let mut progress = Value::Null;
+        for _ in 0..100 {
+            progress = call(&app, Method::GET, "/api/v1/batches/release-all", Value::Null).await.1;
+            if progress["status"] == "Completed" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!((progress["total"].as_u64(), progress["succeeded"].as_u64()), (Some(2), Some(2)));
+        let (status, _) = call(&app, Method::GET, "/api/v1/batches/unknown", Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let body = json!({
+            "batch_id": "cancel-done",
+            "operation": {"type": "cancel"},
+            "target": {"workflow_ids": ["held-0"]}
+        });
+        assert_eq!(call(&app, Method::POST, "/api/v1/batches", body).await.0, StatusCode::ACCEPTED);
+        let (status, first) = call(&app, Method::GET, "/api/v1/batches?limit=1", Value::Null).await;
+        assert_eq!((status, first["batches"][0]["batch_id"].as_str()), (StatusCode::OK, Some("cancel-done")));
+        let token = first["next_page_token"].as_str().unwrap().to_string();
+        let uri = format!("/api/v1/batches?limit=1&page_token={}", token);
+        let (_, second) = call(&app, Method::GET, &uri, Value::Null).await;
+        assert_eq!(second["batches"][0]["batch_id"], "release-all");
+        assert!(second["next_page_token"].is_null());
+        // 令牌绑定过滤条件 / The token is bound to the filter
+        let uri = format!("/api/v1/batches?status=Running&limit=1&page_token={}", token);
+        let (status, body) = call(&app, Method::GET, &uri, Value::Null).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("INVALID_PAGE_TOKEN")));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
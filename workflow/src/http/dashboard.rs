@@ -0,0 +1,44 @@
+//! 内置监控面板 / Embedded monitoring dashboard
+//!
+//! 启用 `dashboard` 特性时在 `/dashboard` 提供单页面板：列出最近的运行及其状态，查看历史时间线，发送信号与取消运行。
+//! 页面只调用 `/api/v1/workflows` 下的 REST 与 SSE 端点，API Key 保存在浏览器本地并以 `X-API-Key` 头发送。
+//! With the `dashboard` feature a single-page dashboard is served at `/dashboard`: it lists recent runs with their
+//! status, shows history timelines, and sends signals and cancels runs. The page only calls the REST and SSE endpoints
+//! under `/api/v1/workflows`; the API key is kept in the browser and sent in the `X-API-Key` header.
+
+use axum::http::header;
+use axum::response::{Html, IntoResponse};
+use axum::routing::get;
+use axum::Router;
+
+/// 页面随二进制一起编译 / The page is compiled into the binary
+const INDEX: &str = include_str!("dashboard.html");
+
+async fn index() -> impl IntoResponse {
+    ([(header::CACHE_CONTROL, "no-cache")], Html(INDEX))
+}
+
+/// 面板路由 / Dashboard routes
+pub(crate) fn routes() -> Router {
+    Router::new().route("/dashboard", get(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::http::build_router;
+
+    #[tokio::test]
+    async fn test_dashboard_is_served_without_credentials() {
+        let request = Request::builder().uri("/dashboard").body(Body::empty()).unwrap();
+        let response = build_router().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/html"));
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let page = std::str::from_utf8(&body).unwrap();
+        assert!(page.contains("/api/v1/workflows") && page.contains("/history/stream"));
+    }
+}
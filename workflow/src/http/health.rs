@@ -0,0 +1,340 @@
+//! 存活与就绪探针 / Liveness and readiness probes
+//!
+//! `/livez` 只说明进程仍能响应请求；`/readyz` 主动检查存储、任务队列与工作者，逐项返回状态与耗时，
+//! 任一关键依赖不可用时返回 503，使负载均衡器暂不转发流量；只读模式列为警告。`/health` 保留为 `/livez` 的旧名。
+//! `/livez` only tells that the process still answers requests; `/readyz` actively checks the storage, the task queue
+//! and the worker, returning the status and latency of each, with a 503 while any critical dependency is down so that
+//! load balancers hold traffic back; read-only mode is listed as a warning. `/health` stays as the old name of
+//! `/livez`.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::read_only::ReadOnlyMode;
+use crate::temporal::storage::WorkflowStorage;
+use crate::temporal::task_queue::TaskQueue;
+use crate::temporal::{WorkerStatus, WorkflowClient, WorkflowWorker};
+
+/// 单项检查的默认超时 / Default timeout of a single check
+const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 就绪检查的一项依赖 / A dependency checked for readiness
+#[async_trait]
+pub trait DependencyCheck: Send + Sync {
+    /// 在响应中的名称 / Name in the response
+    fn name(&self) -> &str;
+
+    /// 不可用时服务是否未就绪，缺省为是 / Whether the service is not ready while it is down, yes by default
+    fn critical(&self) -> bool {
+        true
+    }
+
+    /// 可用时返回附加信息（可为 `Null`），不可用时返回原因 / Extra details (possibly `Null`) when available, the
+    /// reason when down
+    async fn check(&self) -> Result<serde_json::Value, String>;
+}
+
+/// 存储可达 / The storage is reachable
+pub struct StorageCheck(Arc<dyn WorkflowStorage>);
+
+impl StorageCheck {
+    /// 检查客户端的存储 / Check the client's storage
+    pub fn for_client(client: &WorkflowClient) -> Self {
+        Self(client.storage().clone())
+    }
+}
+
+#[async_trait]
+impl DependencyCheck for StorageCheck {
+    fn name(&self) -> &str {
+        "storage"
+    }
+
+    async fn check(&self) -> Result<serde_json::Value, String> {
+        self.0.ping().await.map_err(|e| e.to_string())?;
+        Ok(serde_json::Value::Null)
+    }
+}
+
+struct TaskQueueCheck(Arc<dyn TaskQueue>);
+
+#[async_trait]
+impl DependencyCheck for TaskQueueCheck {
+    fn name(&self) -> &str {
+        "task_queue"
+    }
+
+    async fn check(&self) -> Result<serde_json::Value, String> {
+        self.0.ping().await.map_err(|e| e.to_string())?;
+        Ok(serde_json::Value::Null)
+    }
+}
+
+/// 工作者在轮询、未在排空且注册了类型时可用 / The worker is up while polling, not draining and with types registered
+struct WorkerCheck(WorkerStatus);
+
+#[async_trait]
+impl DependencyCheck for WorkerCheck {
+    fn name(&self) -> &str {
+        "worker"
+    }
+
+    async fn check(&self) -> Result<serde_json::Value, String> {
+        let status = &self.0;
+        let (workflows, activities) = (status.registered_workflows(), status.registered_activities());
+        if status.is_shutting_down() {
+            return Err("worker is shutting down".to_string());
+        }
+        if !status.is_running() {
+            return Err("worker is not polling".to_string());
+        }
+        if workflows == 0 && activities == 0 {
+            return Err("no workflow or activity types registered".to_string());
+        }
+        Ok(serde_json::json!({ "workflows": workflows, "activities": activities }))
+    }
+}
+
+/// 依赖状态 / Dependency status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Up,
+    Down,
+}
+
+/// 单项检查结果 / Outcome of one check
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CheckReport {
+    pub status: CheckStatus,
+    pub critical: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    #[schema(value_type = Object)]
+    pub details: serde_json::Value,
+}
+
+/// `/readyz` 的响应 / Response of `/readyz`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReadinessReport {
+    /// 所有关键依赖都可用 / Every critical dependency is up
+    pub ready: bool,
+    pub checks: BTreeMap<String, CheckReport>,
+    /// 不影响就绪的提醒，如只读模式 / Notices that leave readiness alone, such as read-only mode
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// 就绪检查集合 / Set of readiness checks
+///
+/// 各项检查并发执行，超时视为不可用。没有检查时服务总是就绪。
+/// Checks run concurrently and count as down when they time out. A service without checks is always ready.
+#[derive(Clone)]
+pub struct Readiness {
+    checks: Vec<Arc<dyn DependencyCheck>>,
+    timeout: Duration,
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self {
+            checks: Vec::new(),
+            timeout: DEFAULT_CHECK_TIMEOUT,
+        }
+    }
+}
+
+impl Readiness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 检查客户端的存储与任务队列 / Check the client's storage and task queue
+    pub fn for_client(client: &WorkflowClient) -> Self {
+        Self::new()
+            .with_check(StorageCheck::for_client(client))
+            .with_check(TaskQueueCheck(client.task_queue().clone()))
+    }
+
+    /// 另外检查工作者是否在轮询且注册了类型 / Also check that the worker polls and has types registered
+    pub fn for_worker(worker: &WorkflowWorker) -> Self {
+        Self::for_client(&worker.client()).with_check(WorkerCheck(worker.status()))
+    }
+
+    /// 增加一项检查；同名检查替换先前的 / Add a check, replacing an earlier one of the same name
+    pub fn with_check(mut self, check: impl DependencyCheck + 'static) -> Self {
+        self.checks.retain(|existing| existing.name() != check.name());
+        self.checks.push(Arc::new(check));
+        self
+    }
+
+    /// 单项检查的超时，缺省为两秒 / Timeout of a single check, two seconds by default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 执行全部检查 / Run every check
+    pub async fn report(&self) -> ReadinessReport {
+        let outcomes = futures::future::join_all(self.checks.iter().map(|check| async move {
+            let started = Instant::now();
+            let outcome = match tokio::time::timeout(self.timeout, check.check()).await {
+                Ok(outcome) => outcome,
+                Err(_) => Err(format!("check timed out after {:?}", self.timeout)),
+            };
+            let latency_ms = started.elapsed().as_millis() as u64;
+            let report = match outcome {
+                Ok(details) => CheckReport {
+                    status: CheckStatus::Up,
+                    critical: check.critical(),
+                    latency_ms,
+                    error: None,
+                    details,
+                },
+                Err(error) => {
+                    tracing::warn!(dependency = check.name(), %error, "readiness check failed");
+                    CheckReport {
+                        status: CheckStatus::Down,
+                        critical: check.critical(),
+                        latency_ms,
+                        error: Some(error),
+                        details: serde_json::Value::Null,
+                    }
+                }
+            };
+            (check.name().to_string(), report)
+        }))
+        .await;
+        let checks: BTreeMap<_, _> = outcomes.into_iter().collect();
+        let ready = checks.values().all(|check| check.status == CheckStatus::Up || !check.critical);
+        ReadinessReport {
+            ready,
+            checks,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+#[utoipa::path(get, path = "/livez", tag = "service", responses((status = 200, description = "The process answers requests", body = Object)))]
+pub(super) async fn livez() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "alive" }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "service",
+    responses(
+        (status = 200, description = "Every critical dependency is up", body = ReadinessReport),
+        (status = 503, description = "A critical dependency is down", body = ReadinessReport)
+    )
+)]
+pub(super) async fn readyz(State((readiness, read_only)): State<(Arc<Readiness>, ReadOnlyMode)>) -> Response {
+    let mut report = readiness.report().await;
+    if read_only.is_storage_degraded() {
+        report.warnings.push("read-only mode: storage is degraded, writes are rejected".to_string());
+    } else if read_only.is_enabled() {
+        report.warnings.push("read-only mode: writes are rejected".to_string());
+    }
+    let status = if report.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(report)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use crate::http::build_router_with_workflows;
+    use crate::http::workflows::WorkflowApi;
+    use crate::temporal::error::StorageError;
+    use crate::temporal::storage::InMemoryStorage;
+    use crate::temporal::task_queue::InMemoryTaskQueue;
+
+    struct Unreachable {
+        critical: bool,
+    }
+
+    #[async_trait]
+    impl DependencyCheck for Unreachable {
+        fn name(&self) -> &str {
+            "search_index"
+        }
+
+        fn critical(&self) -> bool {
+            self.critical
+        }
+
+        async fn check(&self) -> Result<serde_json::Value, String> {
+            Err(StorageError::ConnectionError("connection refused".to_string()).to_string())
+        }
+    }
+
+    async fn readyz_of(api: WorkflowApi) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder().uri("/readyz").body(Body::empty()).unwrap();
+        let response = build_router_with_workflows(api).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_readyz_reports_each_dependency() {
+        let worker = WorkflowWorker::default();
+        let (status, body) = readyz_of(WorkflowApi::from_worker(&worker)).await;
+        // 工作者尚未轮询 / the worker is not polling yet
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["checks"]["storage"]["status"], "up");
+        assert_eq!(body["checks"]["task_queue"]["status"], "up");
+        assert_eq!(body["checks"]["worker"]["status"], "down");
+        assert_eq!(body["checks"]["worker"]["error"], "worker is not polling");
+
+        let client = WorkflowClient::new(Arc::new(InMemoryTaskQueue::new()), Arc::new(InMemoryStorage::new()));
+        let api = WorkflowApi::new(client, ["order"]).with_readiness_check(Unreachable { critical: false });
+        let (status, body) = readyz_of(api.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["checks"]["search_index"]["status"], "down");
+        assert_eq!(body["checks"]["search_index"]["critical"], false);
+
+        let (status, body) = readyz_of(api.with_readiness_check(Unreachable { critical: true })).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(body["checks"]["search_index"]["error"].as_str().unwrap().contains("connection refused"));
+    }
+
+    #[tokio::test]
+    async fn test_readyz_warns_while_read_only() {
+        let client = WorkflowClient::new(Arc::new(InMemoryTaskQueue::new()), Arc::new(InMemoryStorage::new()));
+        let api = WorkflowApi::new(client, ["order"]);
+        let (_, body) = readyz_of(api.clone()).await;
+        assert!(body.get("warnings").is_none());
+
+        api.read_only().set(true);
+        let (status, body) = readyz_of(api).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["warnings"][0], "read-only mode: writes are rejected");
+    }
+
+    #[tokio::test]
+    async fn test_livez_does_not_check_dependencies() {
+        let worker = WorkflowWorker::default();
+        worker.shutdown(Duration::ZERO);
+        let app = build_router_with_workflows(WorkflowApi::from_worker(&worker));
+        let request = Request::builder().uri("/livez").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::OK);
+        assert!(!Readiness::for_worker(&worker).report().await.ready);
+    }
+}
@@ -0,0 +1,439 @@
+//! 入站 Webhook / Inbound webhooks
+//!
+//! 当 [`WorkflowApi`](super::workflows::WorkflowApi) 配置了 Webhook 时挂载于 `/api/v1/hooks/{hook_id}`：外部系统
+//! （支付网关回调、物流状态更新）以其原生负载调用该端点，每个 Webhook 从负载中选出目标工作流，并以 JSON 指针
+//! 选取的部分作为信号发送给它，无需专门的客户端。
+//! Mounted at `/api/v1/hooks/{hook_id}` when the [`WorkflowApi`](super::workflows::WorkflowApi) has hooks: external
+//! systems (payment gateway callbacks, shipping updates) call it with their native payloads, and each hook selects
+//! the target workflows from the payload and signals them with the part a JSON pointer selects, without a custom
+//! client.
+//!
+//! ```
+//! use workflow::http::hooks::{Hook, HookTarget};
+//!
+//! // {"type": "payment.succeeded", "data": {"order": "1042", "amount": 990}}
+//! let hook = Hook::new("payment_received", HookTarget::workflow_id_at("/data/order").with_prefix("order-"))
+//!     .payload_at("/data")
+//!     .with_secret("gateway-secret");
+//! ```
+//!
+//! 配置了密钥的 Webhook 要求 [`WebhookNotifyActivity`](crate::temporal::WebhookNotifyActivity) 的签名头，
+//! 时间戳须在五分钟之内。
+//! Hooks with a secret require the signature headers of
+//! [`WebhookNotifyActivity`](crate::temporal::WebhookNotifyActivity), with a timestamp within five minutes.
+//!
+//! 启用认证时，带签名头的请求无需 API Key 或 JWT，但只能调用配置了密钥的 Webhook。
+//! With authentication enabled, signed requests need no API key or JWT, but may only call hooks with a secret.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use super::auth::{Principal, SignedHookRequest};
+use super::versioning::RouteRegistry;
+use super::workflows::{error_response, signal_error_response, WorkflowApi};
+use crate::audit::{AuditAction, AuditEntry};
+use crate::temporal::activities::{WEBHOOK_SIGNATURE_HEADER, WEBHOOK_TIMESTAMP_HEADER};
+use crate::temporal::search::Comparison;
+use crate::temporal::{SearchAttributeValue, WebhookNotifyActivity, WorkflowExecutionStatus, WorkflowFilter, WorkflowId};
+
+/// 签名时间戳允许的偏差（秒）/ Allowed skew of signature timestamps, in seconds
+pub const SIGNATURE_TOLERANCE_SECS: u64 = 300;
+
+/// Webhook 的目标工作流 / Workflows a hook signals
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HookTarget {
+    /// 固定的工作流 ID / A fixed workflow ID
+    WorkflowId { workflow_id: String },
+    /// 负载中 `pointer` 处的值，加上 `prefix` / The value at `pointer` in the payload, after `prefix`
+    WorkflowIdAt {
+        pointer: String,
+        #[serde(default)]
+        prefix: String,
+    },
+    /// 属性 `name` 等于负载中 `pointer` 处的值的所有运行中工作流 / All running workflows whose attribute `name`
+    /// equals the value at `pointer` in the payload
+    SearchAttribute { name: String, pointer: String },
+}
+
+impl HookTarget {
+    pub fn workflow_id(workflow_id: impl Into<String>) -> Self {
+        Self::WorkflowId {
+            workflow_id: workflow_id.into(),
+        }
+    }
+
+    pub fn workflow_id_at(pointer: impl Into<String>) -> Self {
+        Self::WorkflowIdAt {
+            pointer: pointer.into(),
+            prefix: String::new(),
+        }
+    }
+
+    pub fn search_attribute(name: impl Into<String>, pointer: impl Into<String>) -> Self {
+        Self::SearchAttribute {
+            name: name.into(),
+            pointer: pointer.into(),
+        }
+    }
+
+    /// 为从负载读出的工作流 ID 加前缀 / Prefix workflow IDs read from the payload
+    pub fn with_prefix(self, prefix: impl Into<String>) -> Self {
+        match self {
+            Self::WorkflowIdAt { pointer, .. } => Self::WorkflowIdAt {
+                pointer,
+                prefix: prefix.into(),
+            },
+            other => other,
+        }
+    }
+}
+
+/// 入站 Webhook 配置 / Configuration of an inbound webhook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hook {
+    pub target: HookTarget,
+    pub signal_name: String,
+    /// 选取信号输入的 JSON 指针，缺省为整个负载 / JSON pointer selecting the signal input, the whole payload by
+    /// default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_pointer: Option<String>,
+    /// 校验请求签名的密钥 / Secret verifying request signatures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+impl Hook {
+    pub fn new(signal_name: impl Into<String>, target: HookTarget) -> Self {
+        Self {
+            target,
+            signal_name: signal_name.into(),
+            payload_pointer: None,
+            secret: None,
+        }
+    }
+
+    /// 以 `pointer` 处的值作为信号输入 / Signal the value at `pointer`
+    pub fn payload_at(mut self, pointer: impl Into<String>) -> Self {
+        self.payload_pointer = Some(pointer.into());
+        self
+    }
+
+    /// 拒绝未以 `secret` 签名的请求 / Reject requests not signed with `secret`
+    pub fn with_secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    fn verify(&self, headers: &HeaderMap, body: &[u8]) -> bool {
+        let Some(secret) = &self.secret else { return true };
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let (Some(timestamp), Some(signature)) = (header(WEBHOOK_TIMESTAMP_HEADER), header(WEBHOOK_SIGNATURE_HEADER)) else {
+            return false;
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        timestamp.parse::<u64>().is_ok_and(|signed| signed.abs_diff(now) <= SIGNATURE_TOLERANCE_SECS)
+            && WebhookNotifyActivity::verify(secret, timestamp, body, signature)
+    }
+}
+
+/// 被通知的工作流 / Signaled workflows
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HookDelivery {
+    pub signaled: Vec<String>,
+}
+
+/// 负载中缺少所选值 / The payload lacks a selected value
+struct InvalidPayload(String);
+
+impl IntoResponse for InvalidPayload {
+    fn into_response(self) -> Response {
+        error_response(StatusCode::UNPROCESSABLE_ENTITY, "INVALID_PAYLOAD", self.0)
+    }
+}
+
+fn selected<'a>(payload: &'a Value, pointer: &str) -> Result<&'a Value, InvalidPayload> {
+    payload
+        .pointer(pointer)
+        .ok_or_else(|| InvalidPayload(format!("payload has no value at {}", pointer)))
+}
+
+/// 指针处的标量值作为文本 / The scalar at `pointer` as text
+fn selected_text(payload: &Value, pointer: &str) -> Result<String, InvalidPayload> {
+    match selected(payload, pointer)? {
+        Value::String(text) => Ok(text.clone()),
+        Value::Number(number) => Ok(number.to_string()),
+        other => Err(InvalidPayload(format!("expected a string or number at {}, found {}", pointer, other))),
+    }
+}
+
+async fn targets(api: &WorkflowApi, target: &HookTarget, payload: &Value) -> Result<Vec<WorkflowId>, Response> {
+    match target {
+        HookTarget::WorkflowId { workflow_id } => Ok(vec![WorkflowId::new(workflow_id.clone())]),
+        HookTarget::WorkflowIdAt { pointer, prefix } => {
+            let workflow_id = selected_text(payload, pointer).map_err(IntoResponse::into_response)?;
+            Ok(vec![WorkflowId::new(format!("{}{}", prefix, workflow_id))])
+        }
+        HookTarget::SearchAttribute { name, pointer } => {
+            let value = match selected(payload, pointer).map_err(IntoResponse::into_response)? {
+                Value::Number(number) if number.is_i64() => SearchAttributeValue::Int(number.as_i64().unwrap_or_default()),
+                _ => SearchAttributeValue::String(selected_text(payload, pointer).map_err(IntoResponse::into_response)?),
+            };
+            let filter = WorkflowFilter::new()
+                .status(WorkflowExecutionStatus::Running)
+                .attribute(name.clone(), Comparison::Eq, value);
+            let running = api.client().list_workflows(&filter).await.map_err(|e| {
+                error_response(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", e.to_string())
+            })?;
+            Ok(running.into_iter().map(|info| info.execution.workflow_id).collect())
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/hooks/{hook_id}",
+    tag = "hooks",
+    params(
+        ("hook_id" = String, Path, description = "Configured hook"),
+        ("X-Webhook-Timestamp" = Option<String>, Header, description = "Signing time in Unix seconds, required by hooks with a secret"),
+        ("X-Webhook-Signature" = Option<String>, Header, description = "`sha256=` and the hex HMAC of `{timestamp}.{body}`, required by hooks with a secret")
+    ),
+    request_body(content = Object, description = "Payload of the calling system"),
+    responses(
+        (status = 202, description = "Signals queued", body = HookDelivery),
+        (status = 401, description = "Signature missing, stale or invalid, or credentials missing for a hook without a secret", body = super::workflows::ErrorBody),
+        (status = 404, description = "Hook not configured, or no workflow matched", body = super::workflows::ErrorBody),
+        (status = 409, description = "Workflow already closed", body = super::workflows::ErrorBody),
+        (status = 422, description = "Payload lacks a value the hook selects", body = super::workflows::ErrorBody)
+    )
+)]
+pub(super) async fn receive_hook(
+    State(api): State<WorkflowApi>,
+    principal: Option<Extension<Principal>>,
+    signed_only: Option<Extension<SignedHookRequest>>,
+    Path(hook_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(hook) = api.hooks().get(&hook_id).cloned() else {
+        return error_response(StatusCode::NOT_FOUND, "HOOK_NOT_FOUND", format!("hook not configured: {}", hook_id));
+    };
+    // 未经认证的请求只能由签名证明来源 / Only a signature can vouch for an unauthenticated request
+    if signed_only.is_some() && hook.secret.is_none() {
+        return error_response(StatusCode::UNAUTHORIZED, "UNAUTHENTICATED", "hook has no secret, credentials are required");
+    }
+    if !hook.verify(&headers, &body) {
+        return error_response(StatusCode::UNAUTHORIZED, "INVALID_SIGNATURE", "webhook signature missing, stale or invalid");
+    }
+    let payload = if body.is_empty() {
+        Value::Null
+    } else {
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(payload) => payload,
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, "INVALID_INPUT", e.to_string()),
+        }
+    };
+    let input = match &hook.payload_pointer {
+        Some(pointer) => match selected(&payload, pointer) {
+            Ok(input) => input.clone(),
+            Err(invalid) => return invalid.into_response(),
+        },
+        None => payload.clone(),
+    };
+    let workflow_ids = match targets(&api, &hook.target, &payload).await {
+        Ok(ids) if ids.is_empty() => {
+            return error_response(StatusCode::NOT_FOUND, "WORKFLOW_NOT_FOUND", "no running workflow matches the payload");
+        }
+        Ok(ids) => ids,
+        Err(response) => return response,
+    };
+
+    let mut signaled = Vec::with_capacity(workflow_ids.len());
+    for workflow_id in workflow_ids {
+        let entry = AuditEntry::new(AuditAction::Signal, workflow_id.as_str())
+            .target(&hook.signal_name)
+            .payload(&input);
+        let result = api.client().signal_workflow_value(&workflow_id, &hook.signal_name, input.clone()).await;
+        api.audit(principal.clone(), entry, result.is_ok()).await;
+        match result {
+            Ok(()) => signaled.push(workflow_id.to_string()),
+            // 已通知的工作流会随重试再次收到信号 / Workflows signaled so far get the signal again on retry
+            Err(e) if signaled.is_empty() => return signal_error_response(e),
+            Err(e) => tracing::warn!(hook = %hook_id, workflow_id = %workflow_id, error = %e, "hook signal not delivered"),
+        }
+    }
+    (StatusCode::ACCEPTED, Json(HookDelivery { signaled })).into_response()
+}
+
+/// Webhook 路由，挂载于 `/api/v1` 下 / Hook routes, nested under `/api/v1`
+pub(crate) fn routes(api: WorkflowApi) -> Router {
+    Router::new().route("/hooks/{hook_id}", post(receive_hook)).with_state(api)
+}
+
+/// 在注册表中登记 Webhook 路由 / Add the hook routes to a registry
+pub fn register_routes(registry: RouteRegistry) -> RouteRegistry {
+    registry.route(Method::POST, "/api/v1/hooks/{hook_id}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::{Signal, StartWorkflowOptions, WorkerConfig, Workflow, WorkflowContext, WorkflowError, WorkflowWorker};
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[derive(Serialize, Deserialize)]
+    struct Paid(i64);
+
+    impl Signal for Paid {
+        fn name() -> &'static str {
+            "paid"
+        }
+    }
+
+    /// 等待付款信号并返回金额 / Waits for the payment signal and returns its amount
+    struct Checkout;
+
+    impl Workflow for Checkout {
+        type Input = ();
+        type Output = i64;
+
+        fn name() -> &'static str {
+            "checkout"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<i64, WorkflowError> {
+            let Paid(amount) = ctx.wait_for_signal::<Paid>(None).await?;
+            Ok(amount)
+        }
+    }
+
+    async fn call(app: &Router, uri: &str, body: &[u8], headers: &[(&str, String)]) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(Method::POST).uri(uri).header("content-type", "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, value);
+        }
+        let response = app.clone().oneshot(request.body(Body::from(body.to_vec())).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_hooks_signal_selected_workflows() {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        }));
+        worker.register_workflow::<Checkout>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+        let client = worker.client();
+        let mut handles = Vec::new();
+        for (id, customer) in [("order-1", "c-7"), ("order-2", "c-7"), ("order-3", "c-8")] {
+            let options = StartWorkflowOptions {
+                workflow_id: Some(WorkflowId::new(id)),
+                search_attributes: [("Customer".to_string(), SearchAttributeValue::from(customer))].into(),
+                ..StartWorkflowOptions::default()
+            };
+            handles.push(client.start_workflow::<Checkout>((), options).await.unwrap());
+        }
+
+        let api = WorkflowApi::from_worker(&worker)
+            .with_hook("gateway", Hook::new("paid", HookTarget::workflow_id_at("/data/order").with_prefix("order-")).payload_at("/data/amount").with_secret("s3cret"))
+            .with_hook("bulk", Hook::new("paid", HookTarget::search_attribute("Customer", "/customer")).payload_at("/amount"));
+        let app = crate::http::build_router_with_workflows(api);
+
+        let body = json!({"type": "payment.succeeded", "data": {"order": 1, "amount": 990}}).to_string();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs().to_string();
+        let signature = WebhookNotifyActivity::sign("s3cret", &timestamp, body.as_bytes());
+        let (status, _) = call(&app, "/api/v1/hooks/gateway", body.as_bytes(), &[]).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let stale = (SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() - 3600).to_string();
+        let stale_headers = [(WEBHOOK_TIMESTAMP_HEADER, stale.clone()), (WEBHOOK_SIGNATURE_HEADER, WebhookNotifyActivity::sign("s3cret", &stale, body.as_bytes()))];
+        assert_eq!(call(&app, "/api/v1/hooks/gateway", body.as_bytes(), &stale_headers).await.0, StatusCode::UNAUTHORIZED);
+
+        let signed = [(WEBHOOK_TIMESTAMP_HEADER, timestamp), (WEBHOOK_SIGNATURE_HEADER, signature)];
+        let (status, delivery) = call(&app, "/api/v1/hooks/gateway", body.as_bytes(), &signed).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(delivery["signaled"], json!(["order-1"]));
+        assert_eq!(handles[0].result().await.unwrap(), 990);
+
+        let (status, delivery) = call(&app, "/api/v1/hooks/bulk", json!({"customer": "c-7", "amount": 5}).to_string().as_bytes(), &[]).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(delivery["signaled"], json!(["order-2"]));
+        let (status, body) = call(&app, "/api/v1/hooks/bulk", json!({"customer": "c-9", "amount": 5}).to_string().as_bytes(), &[]).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("WORKFLOW_NOT_FOUND")));
+        let (status, body) = call(&app, "/api/v1/hooks/bulk", json!({"amount": 5}).to_string().as_bytes(), &[]).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some("INVALID_PAYLOAD")));
+        let (status, body) = call(&app, "/api/v1/hooks/unknown", b"{}", &[]).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("HOOK_NOT_FOUND")));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[cfg(feature = "middleware")]
+    #[tokio::test]
+    async fn test_signed_hooks_pass_authentication() {
+        use crate::http::auth::Authenticator;
+        use crate::middleware::rbac::{RbacPolicy, Role, RoleMapping};
+
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        }));
+        worker.register_workflow::<Checkout>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+        let client = worker.client();
+        let mut handles = Vec::new();
+        for id in ["order-1", "order-2"] {
+            let options = StartWorkflowOptions {
+                workflow_id: Some(WorkflowId::new(id)),
+                ..StartWorkflowOptions::default()
+            };
+            handles.push(client.start_workflow::<Checkout>((), options).await.unwrap());
+        }
+
+        let api = WorkflowApi::from_worker(&worker)
+            .with_hook("gateway", Hook::new("paid", HookTarget::workflow_id("order-1")).with_secret("s3cret"))
+            .with_hook("open", Hook::new("paid", HookTarget::workflow_id("order-2")));
+        let auth = Authenticator::new().api_key("ops-key", "ops");
+        let policy = RbacPolicy::new(RoleMapping::new().api_key("ops", Role::Operator));
+        let app = crate::http::build_router_with_rbac(Some(api), auth, policy);
+
+        let body = b"990";
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs().to_string();
+        let signature = WebhookNotifyActivity::sign("s3cret", &timestamp, body);
+        let signed = [(WEBHOOK_TIMESTAMP_HEADER, timestamp), (WEBHOOK_SIGNATURE_HEADER, signature)];
+        assert_eq!(call(&app, "/api/v1/hooks/gateway", body, &[]).await.0, StatusCode::UNAUTHORIZED);
+        let (status, delivery) = call(&app, "/api/v1/hooks/gateway", body, &signed).await;
+        assert_eq!((status, &delivery["signaled"]), (StatusCode::ACCEPTED, &json!(["order-1"])));
+        assert_eq!(handles[0].result().await.unwrap(), 990);
+
+        // 没有密钥的 Webhook 仍需凭据 / hooks without a secret still need credentials
+        let (status, _) = call(&app, "/api/v1/hooks/open", body, &signed).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = call(&app, "/api/v1/hooks/open", body, &[(crate::http::auth::API_KEY_HEADER, "ops-key".to_string())]).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(handles[1].result().await.unwrap(), 990);
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,313 @@
+use axum::{middleware, routing::get, Json, Router};
+use tower_http::trace::TraceLayer;
+use tracing::Level;
+use std::sync::OnceLock;
+use axum::body::Body;
+use axum::http::{Method, Request};
+use axum::response::IntoResponse;
+use axum::middleware::Next;
+use metrics::{counter, histogram};
+use std::time::Instant;
+
+pub mod activities;
+pub mod admin;
+pub mod audit;
+pub mod auth;
+pub mod batches;
+#[cfg(feature = "dashboard")]
+pub mod dashboard;
+pub mod health;
+pub mod hooks;
+pub mod namespaces;
+pub mod tasks;
+pub mod openapi;
+pub mod read_only;
+pub mod versioning;
+pub mod worker;
+pub mod workflows;
+
+use auth::Authenticator;
+pub use read_only::ReadOnlyMode;
+use versioning::{ApiVersionLayer, RouteRegistry, CURRENT_API_VERSION, SUPPORTED_API_VERSIONS};
+use workflows::WorkflowApi;
+
+/// `/livez` 的旧名 / Old name of `/livez`
+#[utoipa::path(get, path = "/health", tag = "service", responses((status = 200, description = "Service is up", body = String)))]
+async fn health() -> &'static str { "OK" }
+
+#[utoipa::path(get, path = "/version", tag = "service", responses((status = 200, description = "Crate version", body = String)))]
+async fn version() -> String { format!("{}", crate::VERSION) }
+
+static START_TIME: OnceLock<std::time::Instant> = OnceLock::new();
+pub fn set_start_time() { let _ = START_TIME.set(std::time::Instant::now()); }
+
+#[utoipa::path(get, path = "/stats", tag = "service", responses((status = 200, description = "Version and uptime", body = Object)))]
+async fn stats() -> String {
+    let uptime = START_TIME.get().map(|t| t.elapsed().as_secs()).unwrap_or(0);
+    serde_json::json!({
+        "version": crate::VERSION,
+        "uptime_seconds": uptime
+    }).to_string()
+}
+
+async fn track_metrics(req: Request<Body>, next: Next) -> impl IntoResponse {
+    let method = req.method().as_str().to_string();
+    let path = req.uri().path().to_string();
+    counter!("http_requests_total", "method" => method.clone(), "path" => path.clone()).increment(1);
+
+    let start = Instant::now();
+    let response = next.run(req).await;
+    let latency = start.elapsed().as_secs_f64();
+
+    let status = response.status().as_u16().to_string();
+    histogram!("http_request_duration_seconds", "method" => method, "path" => path.clone(), "status" => status.clone()).record(latency);
+    counter!(
+        "http_responses_total",
+        "path" => path,
+        "status" => status
+    ).increment(1);
+
+    response
+}
+
+// 旧的无前缀管理端点下线日期 / Sunset of the legacy unprefixed admin endpoint
+const LEGACY_ADMIN_SUNSET: &str = "Thu, 01 Jul 2027 00:00:00 GMT";
+
+/// 默认路由注册表 / Default route registry
+pub fn default_route_registry() -> RouteRegistry {
+    RouteRegistry::new()
+        .route(Method::GET, "/health")
+        .route(Method::GET, "/livez")
+        .route(Method::GET, "/readyz")
+        .route(Method::GET, "/version")
+        .route(Method::GET, "/stats")
+        .route(Method::GET, "/api/versions")
+        .route(Method::GET, "/openapi.json")
+        .route(Method::GET, "/docs")
+        .route(Method::GET, "/api/v1/admin/read-only")
+        .route(Method::POST, "/api/v1/admin/read-only")
+        .deprecated_route(Method::GET, "/admin/read-only", LEGACY_ADMIN_SUNSET, Some("/api/v1/admin/read-only"))
+        .deprecated_route(Method::POST, "/admin/read-only", LEGACY_ADMIN_SUNSET, Some("/api/v1/admin/read-only"))
+}
+
+#[utoipa::path(get, path = "/api/versions", tag = "service", responses((status = 200, description = "Supported API versions and routes", body = Object)))]
+async fn api_versions(axum::extract::State(registry): axum::extract::State<std::sync::Arc<RouteRegistry>>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "current": format!("v{}", CURRENT_API_VERSION),
+        "supported": SUPPORTED_API_VERSIONS.iter().map(|v| format!("v{v}")).collect::<Vec<_>>(),
+        "routes": registry.routes(),
+    }))
+}
+
+/// 只读开关的管理端点 / Admin endpoint of the read-only switch
+fn read_only_routes(mode: ReadOnlyMode) -> Router {
+    Router::new()
+        .route("/admin/read-only", get(read_only::get_read_only).post(read_only::put_read_only))
+        .with_state(mode)
+}
+
+/// v1 路由树 / The v1 route tree, mounted under `/api/v1`
+fn api_v1_routes(workflows: Option<WorkflowApi>, read_only: ReadOnlyMode) -> Router {
+    let router = read_only_routes(read_only);
+    let Some(api) = workflows else {
+        return router;
+    };
+    let router = api.namespaces().iter().fold(router, |router, (namespace, namespaced)| {
+        router.nest(&format!("/namespaces/{}", namespace), workflow_routes(namespaced.clone()))
+    });
+    router.merge(workflow_routes(api))
+}
+
+/// 一个命名空间的工作流相关路由 / Workflow routes of one namespace
+fn workflow_routes(api: WorkflowApi) -> Router {
+    let router = Router::new();
+    let router = match api.audit_log() {
+        Some(log) => router.merge(audit::routes(log.clone(), api.page_tokens().clone())),
+        None => router,
+    };
+    let router = match api.activity_registry() {
+        Some(registry) => router.merge(activities::routes(registry.clone())),
+        None => router,
+    };
+    let router = if api.hooks().is_empty() { router } else { router.merge(hooks::routes(api.clone())) };
+    let router = match api.worker_load() {
+        Some(load) => router.merge(worker::routes(load.clone())),
+        None => router,
+    };
+    let router = match api.worker_status() {
+        Some(status) => router.merge(admin::routes(status.clone())).merge(admin::dead_letter_routes(api.clone())),
+        None => router,
+    };
+    router
+        .merge(tasks::routes(api.clone()))
+        .merge(batches::routes(api.clone()))
+        .merge(workflows::routes(api))
+}
+
+pub fn build_router() -> Router {
+    build_router_with_registry(default_route_registry())
+}
+
+/// 使用自定义注册表构建路由 / Build the router with a custom route registry
+pub fn build_router_with_registry(registry: RouteRegistry) -> Router {
+    assemble_router(registry, None, Security::default())
+}
+
+/// 挂载工作流生命周期 API 的路由 / Router that also serves the workflow lifecycle API under `/api/v1/workflows`
+pub fn build_router_with_workflows(api: WorkflowApi) -> Router {
+    let api = Some(api);
+    assemble_router(registry_for(&api), api, Security::default())
+}
+
+/// 未配置认证却仍提供 `/debug` 剖析路由，任何人都能访问，仅用于隔离环境 / Router that serves the `/debug` profiling
+/// routes without authentication, open to anyone, for isolated environments only
+///
+/// 其余未认证的路由不挂载 `/debug`。
+/// The other unauthenticated routers leave `/debug` out.
+#[cfg(feature = "diagnostics")]
+pub fn build_router_with_debug_endpoints(api: Option<WorkflowApi>) -> Router {
+    let security = Security {
+        debug_endpoints: true,
+        ..Security::default()
+    };
+    assemble_router(registry_for(&api), api, security)
+}
+
+/// 要求认证的路由；`/api` 下的路由需要 API Key 或 JWT / Router requiring an API key or JWT for routes under `/api`
+pub fn build_router_with_auth(api: Option<WorkflowApi>, auth: Authenticator) -> Router {
+    let security = Security {
+        auth: Some(std::sync::Arc::new(auth)),
+        ..Security::default()
+    };
+    assemble_router(registry_for(&api), api, security)
+}
+
+/// 认证之外再按角色授权的路由 / Router that also authorizes requests by role after authenticating them
+#[cfg(feature = "middleware")]
+pub fn build_router_with_rbac(
+    api: Option<WorkflowApi>,
+    auth: Authenticator,
+    policy: crate::middleware::rbac::RbacPolicy,
+) -> Router {
+    let security = Security {
+        auth: Some(std::sync::Arc::new(auth)),
+        rbac: Some(std::sync::Arc::new(policy)),
+        #[cfg(feature = "diagnostics")]
+        debug_endpoints: false,
+    };
+    assemble_router(registry_for(&api), api, security)
+}
+
+/// 在最外层按客户端限流，先于认证执行 / Rate-limit clients in the outermost layer, ahead of authentication
+///
+/// 以 `into_make_service_with_connect_info::<SocketAddr>()` 提供服务时按客户端 IP 计数。
+/// Clients are counted by IP when served with `into_make_service_with_connect_info::<SocketAddr>()`.
+#[cfg(feature = "middleware")]
+pub fn with_rate_limit(router: Router, limiter: std::sync::Arc<crate::middleware::rate_limit::RateLimiter>) -> Router {
+    router.layer(middleware::from_fn_with_state(limiter, crate::middleware::rate_limit::limit))
+}
+
+fn registry_for(api: &Option<WorkflowApi>) -> RouteRegistry {
+    let Some(api) = api else {
+        return default_route_registry();
+    };
+    let registry = api.namespaces().iter().fold(default_route_registry(), |registry, (namespace, namespaced)| {
+        registry.nest(&format!("/namespaces/{}", namespace), &register_workflow_routes(namespaced, RouteRegistry::new()))
+    });
+    register_workflow_routes(api, registry)
+}
+
+fn register_workflow_routes(api: &WorkflowApi, registry: RouteRegistry) -> RouteRegistry {
+    let mut registry = batches::register_routes(tasks::register_routes(workflows::register_routes(registry)));
+    if api.audit_log().is_some() {
+        registry = audit::register_routes(registry);
+    }
+    if api.activity_registry().is_some() {
+        registry = activities::register_routes(registry);
+    }
+    if !api.hooks().is_empty() {
+        registry = hooks::register_routes(registry);
+    }
+    if api.worker_load().is_some() {
+        registry = worker::register_routes(registry);
+    }
+    if api.worker_status().is_some() {
+        registry = admin::register_routes(registry);
+    }
+    registry
+}
+
+/// 认证与授权设置 / Authentication and authorization settings
+#[derive(Default)]
+struct Security {
+    auth: Option<std::sync::Arc<Authenticator>>,
+    #[cfg(feature = "middleware")]
+    rbac: Option<std::sync::Arc<crate::middleware::rbac::RbacPolicy>>,
+    /// 未配置认证时也挂载 `/debug` / Mount `/debug` even without authentication
+    #[cfg(feature = "diagnostics")]
+    debug_endpoints: bool,
+}
+
+fn assemble_router(registry: RouteRegistry, workflows: Option<WorkflowApi>, security: Security) -> Router {
+    let registry = std::sync::Arc::new(registry);
+    let routing = namespaces::NamespaceRouting {
+        root: workflows.as_ref().map(|api| api.client().namespace().to_string()).unwrap_or_default(),
+        nested: workflows
+            .iter()
+            .flat_map(|api| api.namespaces().keys().map(|namespace| namespace.to_string()))
+            .collect(),
+    };
+    let readiness = workflows.as_ref().map(|api| api.readiness().clone()).unwrap_or_default();
+    let read_only = workflows.as_ref().map(|api| api.read_only().clone()).unwrap_or_default();
+    let router = Router::new().merge(openapi::routes(openapi::document(workflows.as_ref())));
+    // 剖析会暴露内部细节，须认证或显式开启 / profiles expose internals, so they need authentication or an opt-in
+    #[cfg(feature = "diagnostics")]
+    let router = match security.auth.is_some() || security.debug_endpoints {
+        true => router.merge(crate::diagnostics::router(workflows.as_ref().and_then(|api| api.task_profiler().cloned()))),
+        false => router,
+    };
+    #[cfg(feature = "dashboard")]
+    let router = router.merge(dashboard::routes());
+    let router = router
+        .route("/health", get(health))
+        .route("/livez", get(health::livez))
+        .route("/readyz", get(health::readyz).with_state((std::sync::Arc::new(readiness), read_only.clone())))
+        .route("/version", get(version))
+        .route("/stats", get(stats))
+        .route("/api/versions", get(api_versions).with_state(registry.clone()))
+        .nest("/api/v1", api_v1_routes(workflows, read_only.clone()))
+        // 旧路径保留至下线日期 / legacy path kept until its sunset date
+        .merge(read_only_routes(read_only.clone()))
+        .layer(ApiVersionLayer::new(registry))
+        .layer(middleware::from_fn_with_state(read_only, read_only::reject_writes));
+    // 授权位于认证之内 / authorization runs inside authentication
+    #[cfg(feature = "middleware")]
+    let router = match security.rbac {
+        Some(policy) => router.layer(middleware::from_fn_with_state(policy, crate::middleware::rbac::authorize)),
+        None => router,
+    };
+    let router = router
+        .layer(middleware::from_fn_with_state(security.auth, auth::authenticate))
+        .layer(middleware::from_fn(track_metrics))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|req: &Request<Body>| {
+                    let method = req.method().as_str().to_string();
+                    let path = req.uri().path().to_string();
+                    let ua = req.headers().get("user-agent").and_then(|v| v.to_str().ok()).unwrap_or("").to_string();
+                    tracing::span!(
+                        Level::INFO,
+                        "http_request",
+                        http.method = %method,
+                        http.path = %path,
+                        http.user_agent = %ua
+                    )
+                })
+        );
+    if routing.nested.is_empty() {
+        return router;
+    }
+    // 命名空间改写须先于路由匹配 / the namespace rewrite must happen before routing
+    let select = middleware::from_fn_with_state(std::sync::Arc::new(routing), namespaces::select_namespace);
+    Router::new().fallback_service(tower::Layer::layer(&select, router))
+}
@@ -0,0 +1,174 @@
+//! 命名空间选择 / Namespace selection
+//!
+//! 以 [`WorkflowApi::with_namespace`](super::workflows::WorkflowApi::with_namespace) 加入的命名空间可通过路径段
+//! `/api/v1/namespaces/{namespace}/...` 或 `X-Namespace` 头访问；两者都未给出时使用根 API 所在的命名空间。
+//! 管理端点（`/api/v1/admin/...`）不属于任何命名空间。
+//! Namespaces added with [`WorkflowApi::with_namespace`](super::workflows::WorkflowApi::with_namespace) are reached
+//! through the `/api/v1/namespaces/{namespace}/...` path segment or the `X-Namespace` header; without either, the
+//! namespace of the root API is used. Admin endpoints (`/api/v1/admin/...`) belong to no namespace.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{Request, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use super::workflows::error_response;
+
+pub const NAMESPACE_HEADER: &str = "x-namespace";
+
+const API_PREFIX: &str = "/api/v1/";
+const NAMESPACES_PREFIX: &str = "/api/v1/namespaces/";
+
+/// 可选择的命名空间 / Namespaces requests may select
+#[derive(Debug, Clone)]
+pub(super) struct NamespaceRouting {
+    /// 根 API 的命名空间 / Namespace of the root API
+    pub(super) root: String,
+    pub(super) nested: BTreeSet<String>,
+}
+
+/// 将 `X-Namespace` 头改写为路径段，并拒绝未知命名空间 / Rewrite the `X-Namespace` header into the path segment and reject unknown namespaces
+///
+/// 须位于路由之外，改写后的路径才会参与路由匹配。
+/// Must wrap the router from outside so the rewritten path is the one routed.
+pub(super) async fn select_namespace(
+    State(routing): State<Arc<NamespaceRouting>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let path = req.uri().path();
+    let (namespace, rest) = if let Some(scoped) = path.strip_prefix(NAMESPACES_PREFIX) {
+        let (namespace, rest) = scoped.split_once('/').unwrap_or((scoped, ""));
+        (namespace.to_string(), rest.to_string())
+    } else if let Some(rest) = path.strip_prefix(API_PREFIX).filter(|rest| !rest.starts_with("admin/"))
+        && let Some(namespace) = req.headers().get(NAMESPACE_HEADER)
+    {
+        let Ok(namespace) = namespace.to_str() else {
+            return unknown_namespace("<invalid>");
+        };
+        (namespace.to_string(), rest.to_string())
+    } else {
+        return next.run(req).await;
+    };
+
+    let path = if namespace == routing.root {
+        format!("{API_PREFIX}{rest}")
+    } else if routing.nested.contains(&namespace) {
+        format!("{NAMESPACES_PREFIX}{namespace}/{rest}")
+    } else {
+        return unknown_namespace(&namespace);
+    };
+    let path_and_query = match req.uri().query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path,
+    };
+    let mut parts = req.uri().clone().into_parts();
+    parts.path_and_query = path_and_query.parse().ok();
+    if let Ok(uri) = Uri::from_parts(parts) {
+        *req.uri_mut() = uri;
+    }
+    next.run(req).await
+}
+
+fn unknown_namespace(namespace: &str) -> Response {
+    error_response(
+        StatusCode::NOT_FOUND,
+        "NAMESPACE_NOT_FOUND",
+        format!("namespace '{}' not found", namespace),
+    )
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::Method;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::http::build_router_with_workflows;
+    use crate::http::workflows::WorkflowApi;
+    use crate::temporal::namespace::{Namespace, Namespaces};
+    use crate::temporal::storage::InMemoryStorage;
+    use crate::temporal::task_queue::InMemoryTaskQueue;
+    use crate::temporal::{WorkerConfig, Workflow, WorkflowContext, WorkflowError};
+
+    /// 保持运行 / Stays running
+    struct Wait;
+
+    impl Workflow for Wait {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "wait"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<(), WorkflowError> {
+            ctx.sleep(Duration::from_secs(60)).await;
+            Ok(())
+        }
+    }
+
+    async fn call(router: &axum::Router, method: Method, uri: &str, namespace: Option<&str>, body: Value) -> (StatusCode, Value) {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        if let Some(namespace) = namespace {
+            req = req.header(NAMESPACE_HEADER, namespace);
+        }
+        let response = router
+            .clone()
+            .oneshot(req.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_by_path_and_header() {
+        let acme = Namespace::new("acme").unwrap();
+        let namespaces = Namespaces::new(Arc::new(InMemoryStorage::new()), Arc::new(InMemoryTaskQueue::new()))
+            .with_quota(acme.clone(), 1);
+        let config = || WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        };
+        let root = Arc::new(namespaces.worker(&Namespace::default(), config()));
+        let tenant = Arc::new(namespaces.worker(&acme, config()));
+        root.register_workflow::<Wait>();
+        tenant.register_workflow::<Wait>();
+        let api = WorkflowApi::from_worker(&root).with_namespace(acme, WorkflowApi::from_worker(&tenant));
+        let router = build_router_with_workflows(api);
+
+        let start = json!({"workflow_type": "wait", "workflow_id": "order-1", "input": null});
+        let (status, _) = call(&router, Method::POST, "/api/v1/namespaces/acme/workflows", None, start.clone()).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, body) = call(&router, Method::POST, "/api/v1/workflows", Some("acme"), json!({
+            "workflow_type": "wait", "workflow_id": "order-2", "input": null
+        }))
+        .await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::TOO_MANY_REQUESTS, Some("QUOTA_EXCEEDED")));
+
+        let (status, _) = call(&router, Method::GET, "/api/v1/workflows/order-1", Some("acme"), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(&router, Method::GET, "/api/v1/workflows/order-1", None, Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call(&router, Method::POST, "/api/v1/namespaces/default/workflows", None, start).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, body) = call(&router, Method::GET, "/api/v1/workflows", Some("other"), Value::Null).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("NAMESPACE_NOT_FOUND")));
+        let (status, _) = call(&router, Method::GET, "/api/v1/namespaces/other/workflows", None, Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,198 @@
+//! OpenAPI 文档 / OpenAPI document
+//!
+//! 由处理函数上的 `#[utoipa::path]` 注解生成 OpenAPI 3 文档，在 `/openapi.json` 提供，
+//! 并在 `/docs` 提供内置的 Swagger UI。工作流路由仅在挂载时出现在文档中。
+//! The OpenAPI 3 document is generated from the `#[utoipa::path]` annotations on the handlers and served at
+//! `/openapi.json`, with a bundled Swagger UI at `/docs`. Workflow routes appear only when they are mounted.
+
+use axum::Router;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+use super::workflows::{self, WorkflowApi};
+
+/// 服务与管理端点 / Service and admin endpoints
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "workflow", description = "Workflow engine HTTP API"),
+    paths(
+        super::health,
+        super::health::livez,
+        super::health::readyz,
+        super::version,
+        super::stats,
+        super::api_versions,
+        super::read_only::get_read_only,
+        super::read_only::put_read_only
+    ),
+    components(schemas(
+        super::read_only::ReadOnlyRequest,
+        super::health::ReadinessReport,
+        super::health::CheckReport,
+        super::health::CheckStatus
+    )),
+    tags(
+        (name = "service", description = "Probes and service information"),
+        (name = "admin", description = "Operational switches")
+    )
+)]
+struct ServiceApi;
+
+/// 工作流生命周期端点 / Workflow lifecycle endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        workflows::start_workflow,
+        workflows::list_workflows,
+        workflows::describe_workflow,
+        workflows::workflow_history,
+        workflows::stream_workflow_history,
+        workflows::export_history,
+        workflows::import_history,
+        workflows::signal_workflow,
+        workflows::query_workflow,
+        workflows::update_workflow,
+        workflows::cancel_workflow,
+        workflows::workflow_diagram
+    ),
+    components(schemas(
+        workflows::StartWorkflowRequest,
+        workflows::StartedWorkflow,
+        workflows::WorkflowStatusResponse,
+        workflows::WorkflowList,
+        workflows::ErrorBody
+    )),
+    tags((name = "workflows", description = "Start, inspect, signal, update and cancel workflows, export and import their histories, draw their structure"))
+)]
+struct WorkflowsApi;
+
+/// 审计端点 / Audit endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(super::audit::list_audit_entries),
+    components(schemas(super::audit::AuditList, crate::audit::AuditEntry, crate::audit::AuditAction)),
+    tags((name = "audit", description = "Page through audited workflow operations"))
+)]
+struct AuditApi;
+
+/// 动态活动端点 / Dynamic activity endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(super::activities::list_activities, super::activities::invoke_activity),
+    components(schemas(super::activities::ActivityList, super::activities::ActivityInvocation)),
+    tags((name = "activities", description = "List and directly invoke dynamic activities"))
+)]
+struct ActivitiesApi;
+
+/// 入站 Webhook 端点 / Inbound webhook endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(super::hooks::receive_hook),
+    components(schemas(super::hooks::HookDelivery)),
+    tags((name = "hooks", description = "Signal workflows from callbacks of external systems"))
+)]
+struct HooksApi;
+
+/// 人工任务端点 / Human task endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(super::tasks::list_tasks, super::tasks::complete_task),
+    components(schemas(super::tasks::TaskList, super::tasks::CompletedTask)),
+    tags((name = "tasks", description = "List and complete the human tasks of running workflows"))
+)]
+struct TasksApi;
+
+/// 批量操作端点 / Batch operation endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(super::batches::list_batches, super::batches::start_batch, super::batches::batch_progress),
+    components(schemas(super::batches::BatchList, super::batches::StartBatchRequest, super::batches::StartedBatch)),
+    tags((name = "batches", description = "Cancel, terminate or signal many workflows in the background"))
+)]
+struct BatchesApi;
+
+/// 工作者负载端点 / Worker load endpoint
+#[derive(OpenApi)]
+#[openapi(
+    paths(super::worker::worker_load),
+    components(schemas(crate::temporal::tuning::WorkerSignals, crate::temporal::tuning::SlotSignals)),
+    tags((name = "worker", description = "Saturation signals of the worker, for autoscalers"))
+)]
+struct WorkerApi;
+
+/// 运行时内省端点 / Runtime introspection endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        super::admin::registered_types,
+        super::admin::worker_settings,
+        super::admin::sticky_cache,
+        super::admin::queue_depths,
+        super::admin::in_flight,
+        super::admin::dead_letters
+    ),
+    components(schemas(
+        super::admin::RegisteredTypes,
+        super::admin::WorkerSettings,
+        super::admin::DeadLetterList,
+        crate::temporal::event::HistoryLimits,
+        crate::temporal::StickyCacheStats,
+        crate::temporal::QueueDepths,
+        crate::temporal::InFlightCounts
+    ))
+)]
+struct AdminApi;
+
+/// 生成 OpenAPI 文档 / Build the OpenAPI document
+pub fn document(workflows: Option<&WorkflowApi>) -> utoipa::openapi::OpenApi {
+    let mut document = ServiceApi::openapi();
+    if let Some(api) = workflows {
+        document.merge(WorkflowsApi::openapi());
+        document.merge(TasksApi::openapi());
+        document.merge(BatchesApi::openapi());
+        if api.audit_log().is_some() {
+            document.merge(AuditApi::openapi());
+        }
+        if api.activity_registry().is_some() {
+            document.merge(ActivitiesApi::openapi());
+        }
+        if !api.hooks().is_empty() {
+            document.merge(HooksApi::openapi());
+        }
+        if api.worker_load().is_some() {
+            document.merge(WorkerApi::openapi());
+        }
+        if api.worker_status().is_some() {
+            document.merge(AdminApi::openapi());
+        }
+    }
+    document
+}
+
+/// `/openapi.json` 与 `/docs` 路由 / Routes serving `/openapi.json` and `/docs`
+pub(crate) fn routes(document: utoipa::openapi::OpenApi) -> Router {
+    SwaggerUi::new("/docs").url("/openapi.json", document).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_lists_workflow_routes_only_when_mounted() {
+        let service = document(None);
+        assert!(service.paths.paths.contains_key("/health"));
+        assert!(!service.paths.paths.contains_key("/api/v1/workflows"));
+
+        let api = WorkflowApi::from_worker(&crate::temporal::WorkflowWorker::default());
+        assert!(!document(Some(&api)).paths.paths.contains_key("/api/v1/audit"));
+        let full = document(Some(&api.with_audit(crate::audit::AuditLog::default())));
+        assert!(full.paths.paths.contains_key("/api/v1/audit"));
+        for path in ["/api/v1/workflows", "/api/v1/workflows/{id}", "/api/v1/workflows/{id}/signal/{name}"] {
+            assert!(full.paths.paths.contains_key(path), "{path} missing");
+        }
+        let schemas = &full.components.as_ref().unwrap().schemas;
+        assert!(schemas.contains_key("StartWorkflowRequest"));
+        assert!(serde_json::to_value(&full).unwrap()["openapi"].as_str().unwrap().starts_with("3."));
+    }
+}
@@ -0,0 +1,285 @@
+//! 只读模式 / Read-only mode
+//!
+//! 维护窗口内（如存储迁移）读请求照常应答，写请求返回 503 `READ_ONLY_MODE` 与 Retry-After，工作者暂停派发新的
+//! 工作流任务，`/readyz` 给出警告。开关来自配置或 `POST /api/v1/admin/read-only`（经持久化适配器保存，重启后恢复）；
+//! 存储检查失败期间也会自动进入只读，恢复后自动退出。
+//! During maintenance windows such as storage migrations reads keep being answered while writes get a 503
+//! `READ_ONLY_MODE` with Retry-After, workers stop dispatching new workflow tasks and `/readyz` reports a warning. The
+//! switch comes from the configuration or `POST /api/v1/admin/read-only` (saved through the persistence adapter so
+//! that it survives restarts); read-only mode is also engaged while storage checks fail, and left once they pass.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "persistence")]
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use parking_lot::Mutex;
+use tokio::task::JoinHandle;
+
+use super::health::DependencyCheck;
+use crate::temporal::PauseHandle;
+
+const READ_ONLY_RETRY_AFTER_SECS: u64 = 30;
+
+/// 记录只读开关的快照键 / Snapshot key used to persist the read-only switch
+#[cfg(feature = "persistence")]
+pub const READ_ONLY_SNAPSHOT_KEY: &str = "__service__/read_only";
+
+#[derive(Default)]
+struct Inner {
+    /// 由配置或管理端点打开 / Switched on by the configuration or the admin endpoint
+    switched: AtomicBool,
+    /// 存储检查失败 / Storage checks fail
+    storage_degraded: AtomicBool,
+    /// 随模式暂停派发的工作者 / Workers whose dispatch pauses with the mode
+    workers: Mutex<Vec<PauseHandle>>,
+    #[cfg(feature = "persistence")]
+    store: OnceLock<Arc<dyn crate::persistence::PersistenceAdapter>>,
+}
+
+/// 服务的只读开关，克隆共享同一状态 / The service's read-only switch; clones share the same state
+#[derive(Clone, Default)]
+pub struct ReadOnlyMode(Arc<Inner>);
+
+impl ReadOnlyMode {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 手动开关或存储降级时为只读 / Read-only while switched on or while storage is degraded
+    pub fn is_enabled(&self) -> bool {
+        self.is_switched() || self.is_storage_degraded()
+    }
+
+    /// 配置或管理端点是否打开了开关 / Whether the configuration or the admin endpoint switched it on
+    pub fn is_switched(&self) -> bool {
+        self.0.switched.load(Ordering::SeqCst)
+    }
+
+    pub fn is_storage_degraded(&self) -> bool {
+        self.0.storage_degraded.load(Ordering::SeqCst)
+    }
+
+    /// 打开或关闭开关，不保存 / Switch read-only mode on or off, without saving it
+    pub fn set(&self, enabled: bool) {
+        if self.0.switched.swap(enabled, Ordering::SeqCst) != enabled {
+            tracing::warn!(read_only = enabled, "service read-only mode changed");
+            self.apply();
+        }
+    }
+
+    /// 记录存储检查结果；降级期间为只读 / Record the outcome of a storage check; read-only while degraded
+    pub fn set_storage_degraded(&self, degraded: bool) {
+        if self.0.storage_degraded.swap(degraded, Ordering::SeqCst) != degraded {
+            tracing::warn!(storage_degraded = degraded, "storage health changed read-only mode");
+            self.apply();
+        }
+    }
+
+    /// 只读期间暂停该工作者派发新的工作流任务 / Pause the worker's dispatch of new workflow tasks while read-only
+    pub fn pause_worker(&self, worker: PauseHandle) {
+        worker.pause(self.is_enabled());
+        self.0.workers.lock().push(worker);
+    }
+
+    fn apply(&self) {
+        let enabled = self.is_enabled();
+        for worker in self.0.workers.lock().iter() {
+            worker.pause(enabled);
+        }
+    }
+
+    /// 每 `interval` 执行一次存储检查（如 [`StorageCheck`](super::health::StorageCheck)），失败期间为只读 / Run
+    /// a storage check such as [`StorageCheck`](super::health::StorageCheck) every `interval`, read-only while it fails
+    pub fn follow_storage(&self, check: Arc<dyn DependencyCheck>, interval: Duration) -> JoinHandle<()> {
+        let mode = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let checked = tokio::time::timeout(interval, check.check()).await;
+                mode.set_storage_degraded(!matches!(checked, Ok(Ok(_))));
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    /// 绑定持久化适配器并恢复上次保存的开关 / Bind a persistence adapter and restore the switch saved last
+    #[cfg(feature = "persistence")]
+    pub async fn restore(&self, adapter: Arc<dyn crate::persistence::PersistenceAdapter>) -> anyhow::Result<bool> {
+        let enabled = adapter
+            .load_state(READ_ONLY_SNAPSHOT_KEY)
+            .await?
+            .and_then(|snap| snap.state.get("enabled").and_then(|v| v.as_bool()))
+            .unwrap_or(false);
+        let _ = self.0.store.set(adapter);
+        self.set(enabled);
+        Ok(enabled)
+    }
+
+    #[cfg(feature = "persistence")]
+    async fn persist(&self, enabled: bool) -> anyhow::Result<()> {
+        if let Some(adapter) = self.0.store.get() {
+            adapter
+                .save_state(crate::persistence::StateSnapshot {
+                    workflow_id: READ_ONLY_SNAPSHOT_KEY.to_string(),
+                    state: serde_json::json!({ "enabled": enabled }),
+                    updated_at: chrono::Utc::now().timestamp(),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> serde_json::Value {
+        serde_json::json!({ "read_only": self.is_enabled(), "storage_degraded": self.is_storage_degraded() })
+    }
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::ToSchema)]
+pub(super) struct ReadOnlyRequest {
+    enabled: bool,
+}
+
+#[utoipa::path(get, path = "/api/v1/admin/read-only", tag = "admin", responses((status = 200, description = "Current read-only state", body = Object)))]
+pub(super) async fn get_read_only(State(mode): State<ReadOnlyMode>) -> Json<serde_json::Value> {
+    Json(mode.state())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/read-only",
+    tag = "admin",
+    request_body = ReadOnlyRequest,
+    responses(
+        (status = 200, description = "New read-only state; still read-only while storage is degraded", body = Object),
+        (status = 500, description = "The switch could not be persisted", body = Object)
+    )
+)]
+pub(super) async fn put_read_only(State(mode): State<ReadOnlyMode>, Json(req): Json<ReadOnlyRequest>) -> Response {
+    #[cfg(feature = "persistence")]
+    if let Err(e) = mode.persist(req.enabled).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "code": "PERSISTENCE_ERROR", "message": e.to_string() })),
+        )
+            .into_response();
+    }
+    mode.set(req.enabled);
+    Json(mode.state()).into_response()
+}
+
+/// 查询以 POST 携带参数，但不改变状态 / Queries are POSTed to carry their arguments, but change nothing
+fn is_write_request(req: &Request<Body>) -> bool {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return false;
+    }
+    let segments: Vec<&str> = req.uri().path().trim_matches('/').split('/').collect();
+    !matches!(
+        segments.as_slice(),
+        ["api", "v1", "workflows", _, "query", _] | ["api", "v1", "namespaces", _, "workflows", _, "query", _]
+    )
+}
+
+pub(super) async fn reject_writes(State(mode): State<ReadOnlyMode>, req: Request<Body>, next: Next) -> Response {
+    let toggling = matches!(req.uri().path(), "/admin/read-only" | "/api/v1/admin/read-only");
+    if mode.is_enabled() && is_write_request(&req) && !toggling {
+        metrics::counter!("http_read_only_rejections_total").increment(1);
+        let mut response = (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "code": "READ_ONLY_MODE",
+                "message": "service is in read-only mode, writes are temporarily rejected"
+            })),
+        )
+            .into_response();
+        response.headers_mut().insert(header::RETRY_AFTER, READ_ONLY_RETRY_AFTER_SECS.into());
+        return response;
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    use crate::temporal::WorkflowWorker;
+
+    #[test]
+    fn test_queries_are_reads() {
+        let request = |method: Method, path: &str| Request::builder().method(method).uri(path).body(Body::empty()).unwrap();
+        assert!(!is_write_request(&request(Method::GET, "/api/v1/workflows/o-1")));
+        assert!(!is_write_request(&request(Method::POST, "/api/v1/workflows/o-1/query/total")));
+        assert!(!is_write_request(&request(Method::POST, "/api/v1/namespaces/acme/workflows/o-1/query/total")));
+        assert!(is_write_request(&request(Method::POST, "/api/v1/workflows/o-1/signal/proceed")));
+        assert!(is_write_request(&request(Method::POST, "/api/v1/workflows")));
+    }
+
+    #[test]
+    fn test_mode_pauses_workers() {
+        let mode = ReadOnlyMode::new();
+        let worker = WorkflowWorker::default();
+        mode.pause_worker(worker.pause_handle());
+        assert!(!worker.is_dispatch_paused());
+
+        mode.set(true);
+        assert!(worker.is_dispatch_paused());
+        mode.set_storage_degraded(true);
+        mode.set(false);
+        // 存储仍降级 / storage is still degraded
+        assert!(mode.is_enabled());
+        assert!(worker.is_dispatch_paused());
+        mode.set_storage_degraded(false);
+        assert!(!mode.is_enabled());
+        assert!(!worker.is_dispatch_paused());
+    }
+
+    /// 写入失败时报告降级的存储检查 / Storage check reporting degraded writes while they fail
+    struct Degraded(AtomicBool);
+
+    #[async_trait]
+    impl DependencyCheck for Degraded {
+        fn name(&self) -> &str {
+            "storage"
+        }
+
+        async fn check(&self) -> Result<serde_json::Value, String> {
+            match self.0.load(Ordering::SeqCst) {
+                true => Err("writes failing".to_string()),
+                false => Ok(serde_json::Value::Null),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_degraded_storage_engages_read_only() {
+        let storage = Arc::new(Degraded(AtomicBool::new(true)));
+        let mode = ReadOnlyMode::new();
+        let checks = mode.follow_storage(storage.clone(), Duration::from_millis(10));
+        let settled = |expected: bool| {
+            let mode = mode.clone();
+            async move {
+                tokio::time::timeout(Duration::from_secs(5), async {
+                    while mode.is_enabled() != expected {
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                    }
+                })
+                .await
+                .unwrap()
+            }
+        };
+
+        settled(true).await;
+        assert!(mode.is_storage_degraded());
+        assert!(!mode.is_switched());
+        storage.0.store(false, Ordering::SeqCst);
+        settled(false).await;
+        checks.abort();
+    }
+}
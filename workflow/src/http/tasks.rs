@@ -0,0 +1,275 @@
+//! 人工任务 REST API / Human task REST API
+//!
+//! 与工作流路由一同挂载于 `/api/v1/tasks`：按处理人列出运行中工作流的待办人工任务，并以表单数据完成任务，
+//! 恢复等待它的工作流。
+//! Mounted with the workflow routes at `/api/v1/tasks`: lists the open human tasks of running workflows per
+//! assignee, and completes them with form data, resuming the workflows waiting for them.
+
+use axum::extract::{Path, Query, State};
+use axum::http::{Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+
+use super::auth::Principal;
+use super::versioning::RouteRegistry;
+use super::workflows::{error_response, invalid_page_token, ErrorBody, WorkflowApi};
+use crate::audit::{AuditAction, AuditEntry};
+use crate::temporal::error::HumanTaskError;
+use crate::temporal::human_task::HUMAN_TASK_SIGNAL_PREFIX;
+use crate::temporal::HumanTask;
+use crate::util::pagination::{filter_hash, PageRequest, PageToken};
+
+/// 任务筛选 / Task filter
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TaskQuery {
+    /// 仅列出此处理人的任务 / Only list the tasks of this assignee
+    pub assignee: Option<String>,
+    /// 每页任务数，缺省 100，最多 1000 / Tasks per page, defaults to 100, at most 1000
+    pub limit: Option<usize>,
+    /// 上一页返回的 `next_page_token`，须与相同的处理人一起使用 / `next_page_token` of the previous page, valid
+    /// only with the same assignee
+    #[param(value_type = Option<String>)]
+    pub page_token: Option<PageToken>,
+}
+
+/// 一页待办任务 / One page of open tasks
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TaskList {
+    /// 最早创建的在前 / Oldest first
+    #[schema(value_type = Vec<Object>)]
+    pub tasks: Vec<HumanTask>,
+    /// 下一页的 `page_token`；最后一页为空 / `page_token` of the next page, absent on the last page
+    #[schema(value_type = Option<String>)]
+    pub next_page_token: Option<PageToken>,
+}
+
+/// 列表的排序键：最早创建的在前，同时创建的按 ID / Listing order: oldest first, tasks created together by ID
+fn listing_key(task: &HumanTask) -> (String, String) {
+    (format!("{:020}", task.created_at.timestamp_micros()), task.task_id.clone())
+}
+
+/// 已完成的任务 / Completed task
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CompletedTask {
+    pub task_id: String,
+    /// 被恢复的工作流 / The resumed workflow
+    pub workflow_id: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/tasks",
+    tag = "tasks",
+    params(TaskQuery),
+    responses(
+        (status = 200, description = "One page of the open human tasks of running workflows, oldest first", body = TaskList),
+        (status = 400, description = "Invalid page token", body = ErrorBody)
+    )
+)]
+pub(super) async fn list_tasks(State(api): State<WorkflowApi>, Query(query): Query<TaskQuery>) -> Response {
+    let filters = filter_hash(&serde_json::json!({ "tasks": { "assignee": query.assignee } }));
+    let mut tasks = match api.client().human_tasks(query.assignee.as_deref()).await {
+        Ok(tasks) => tasks,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", e.to_string()),
+    };
+    tasks.sort_by_cached_key(listing_key);
+    let request = PageRequest {
+        page_size: query.limit,
+        page_token: query.page_token,
+    };
+    match api.page_tokens().paginate(&tasks, listing_key, &filters, &request) {
+        Ok(page) => Json(TaskList {
+            tasks: page.items,
+            next_page_token: page.next_token,
+        })
+        .into_response(),
+        Err(e) => invalid_page_token(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/tasks/{task_id}/complete",
+    tag = "tasks",
+    params(("task_id" = String, Path, description = "Open human task")),
+    request_body(content = Object, description = "Form data matching the form schema of the task"),
+    responses(
+        (status = 202, description = "Task completed, workflow resuming", body = CompletedTask),
+        (status = 404, description = "No open task with this ID", body = ErrorBody),
+        (status = 422, description = "Form data does not match the form schema", body = ErrorBody)
+    )
+)]
+pub(super) async fn complete_task(
+    State(api): State<WorkflowApi>,
+    principal: Option<Extension<Principal>>,
+    Path(task_id): Path<String>,
+    Json(data): Json<Value>,
+) -> Response {
+    let result = api.client().complete_human_task(&task_id, data.clone()).await;
+    if let Ok(task) = &result {
+        let entry = AuditEntry::new(AuditAction::Signal, task.workflow_id.as_str())
+            .target(format!("{}{}", HUMAN_TASK_SIGNAL_PREFIX, task_id))
+            .payload(&data);
+        api.audit(principal, entry, true).await;
+    }
+    match result {
+        Ok(task) => (
+            StatusCode::ACCEPTED,
+            Json(CompletedTask {
+                task_id: task.task_id,
+                workflow_id: task.workflow_id.to_string(),
+            }),
+        )
+            .into_response(),
+        Err(e @ HumanTaskError::TaskNotFound(_)) => error_response(StatusCode::NOT_FOUND, "TASK_NOT_FOUND", e.to_string()),
+        Err(e @ HumanTaskError::InvalidForm(_)) => error_response(StatusCode::UNPROCESSABLE_ENTITY, "INVALID_FORM", e.to_string()),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", e.to_string()),
+    }
+}
+
+/// 人工任务路由，挂载于 `/api/v1` 下 / Human task routes, nested under `/api/v1`
+pub(crate) fn routes(api: WorkflowApi) -> Router {
+    Router::new()
+        .route("/tasks", get(list_tasks))
+        .route("/tasks/{task_id}/complete", post(complete_task))
+        .with_state(api)
+}
+
+/// 在注册表中登记人工任务路由 / Add the human task routes to a registry
+pub fn register_routes(registry: RouteRegistry) -> RouteRegistry {
+    registry
+        .route(Method::GET, "/api/v1/tasks")
+        .route(Method::POST, "/api/v1/tasks/{task_id}/complete")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::{StartWorkflowOptions, WorkerConfig, Workflow, WorkflowContext, WorkflowError, WorkflowId, WorkflowWorker};
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    /// 等待审批人填写金额 / Waits for the approver to fill in an amount
+    struct Approve;
+
+    impl Workflow for Approve {
+        type Input = ();
+        type Output = i64;
+
+        fn name() -> &'static str {
+            "approve"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<i64, WorkflowError> {
+            let form = json!({"type": "object", "required": ["amount"], "properties": {"amount": {"type": "integer"}}});
+            let data: Value = ctx.create_human_task("alice", form, None).await?;
+            Ok(data["amount"].as_i64().unwrap_or_default())
+        }
+    }
+
+    async fn call(app: &Router, method: Method, uri: &str, body: Value) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_tasks_listed_and_completed() {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        }));
+        worker.register_workflow::<Approve>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+        let options = StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new("approval-1")),
+            ..StartWorkflowOptions::default()
+        };
+        let handle = worker.client().start_workflow::<Approve>((), options).await.unwrap();
+        let app = crate::http::build_router_with_workflows(WorkflowApi::from_worker(&worker));
+
+        let mut tasks = Value::Null;
+        for _ in 0..100 {
+            tasks = call(&app, Method::GET, "/api/v1/tasks?assignee=alice", Value::Null).await.1;
+            if tasks["tasks"].as_array().is_some_and(|tasks| !tasks.is_empty()) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(tasks["tasks"][0]["workflow_id"], "approval-1");
+        let task_id = tasks["tasks"][0]["task_id"].as_str().unwrap().to_string();
+        let (_, others) = call(&app, Method::GET, "/api/v1/tasks?assignee=bob", Value::Null).await;
+        assert_eq!(others["tasks"], json!([]));
+
+        let uri = format!("/api/v1/tasks/{}/complete", task_id);
+        let (status, body) = call(&app, Method::POST, &uri, json!({"amount": "many"})).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some("INVALID_FORM")));
+        let (status, body) = call(&app, Method::POST, &uri, json!({"amount": 120})).await;
+        assert_eq!((status, body["workflow_id"].as_str()), (StatusCode::ACCEPTED, Some("approval-1")));
+        assert_eq!(handle.result().await.unwrap(), 120);
+        let (status, body) = call(&app, Method::POST, &uri, json!({"amount": 120})).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("TASK_NOT_FOUND")));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tasks_are_paged() {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        }));
+        worker.register_workflow::<Approve>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+        for id in ["approval-1", "approval-2"] {
+            let options = StartWorkflowOptions {
+                workflow_id: Some(WorkflowId::new(id)),
+                ..StartWorkflowOptions::default()
+            };
+            worker.client().start_workflow::<Approve>((), options).await.unwrap();
+        }
+        let app = crate::http::build_router_with_workflows(WorkflowApi::from_worker(&worker));
+        for _ in 0..100 {
+            if worker.client().human_tasks(Some("alice")).await.unwrap().len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let (status, first) = call(&app, Method::GET, "/api/v1/tasks?assignee=alice&limit=1", Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(first["tasks"].as_array().unwrap().len(), 1);
+        let token = first["next_page_token"].as_str().unwrap().to_string();
+        let uri = format!("/api/v1/tasks?assignee=alice&limit=1&page_token={}", token);
+        let (_, second) = call(&app, Method::GET, &uri, Value::Null).await;
+        assert_eq!(second["tasks"].as_array().unwrap().len(), 1);
+        assert_ne!(second["tasks"][0]["task_id"], first["tasks"][0]["task_id"]);
+        assert!(second["next_page_token"].is_null());
+
+        // 令牌绑定处理人 / The token is bound to the assignee
+        let uri = format!("/api/v1/tasks?assignee=bob&limit=1&page_token={}", token);
+        let (status, body) = call(&app, Method::GET, &uri, Value::Null).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("INVALID_PAGE_TOKEN")));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,262 @@
+//! API 版本协商 / API version negotiation
+//!
+//! 工作流端点挂载在 `/api/v{n}` 下；探活类端点（/health、/version、/stats）保持无前缀。
+//! `ApiVersionLayer` 为每个响应设置 `X-API-Version`，为注册表中标记为弃用的路由附加
+//! `Deprecation`/`Sunset` 头，并对未知的 `/api/v{n}` 前缀返回 406。
+//! Workflow endpoints live under `/api/v{n}` while probes (/health, /version, /stats) stay unprefixed.
+//! `ApiVersionLayer` stamps `X-API-Version` on every response, adds `Deprecation`/`Sunset` headers for routes
+//! marked deprecated in the registry, and answers unknown `/api/v{n}` prefixes with 406.
+
+use axum::body::Body;
+use axum::http::{HeaderValue, Method, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// 当前 API 版本 / Current API version
+pub const CURRENT_API_VERSION: u32 = 1;
+/// 支持的 API 版本 / Supported API versions
+pub const SUPPORTED_API_VERSIONS: &[u32] = &[1];
+
+pub const API_VERSION_HEADER: &str = "x-api-version";
+
+/// 路由弃用信息 / Route deprecation details
+#[derive(Debug, Clone, Serialize)]
+pub struct Deprecation {
+    /// 下线日期（HTTP-date）/ Sunset date (HTTP-date)
+    pub sunset: String,
+    /// 替代路由 / Successor route
+    pub successor: Option<String>,
+}
+
+/// 路由描述 / Route descriptor
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteInfo {
+    pub method: String,
+    pub path: String,
+    pub version: Option<u32>,
+    pub deprecated: Option<Deprecation>,
+}
+
+/// 路由注册表 / Route registry
+///
+/// 记录对外公开的路由及其版本与弃用状态，`GET /api/versions` 直接序列化它。
+/// Records the public routes with their version and deprecation status; `GET /api/versions` serializes it.
+#[derive(Debug, Clone, Default)]
+pub struct RouteRegistry {
+    routes: Vec<RouteInfo>,
+}
+
+impl RouteRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册路由，路径中的 `{param}` 段匹配任意值 / Register a route; `{param}` segments match any value
+    pub fn route(mut self, method: Method, path: &str) -> Self {
+        self.routes.push(RouteInfo {
+            method: method.as_str().to_string(),
+            path: path.to_string(),
+            version: path_version(path),
+            deprecated: None,
+        });
+        self
+    }
+
+    /// 注册已弃用路由 / Register a deprecated route
+    pub fn deprecated_route(mut self, method: Method, path: &str, sunset: &str, successor: Option<&str>) -> Self {
+        self.routes.push(RouteInfo {
+            method: method.as_str().to_string(),
+            path: path.to_string(),
+            version: path_version(path),
+            deprecated: Some(Deprecation {
+                sunset: sunset.to_string(),
+                successor: successor.map(str::to_string),
+            }),
+        });
+        self
+    }
+
+    /// 合入另一注册表的路由，并在其 `/api/v{n}` 之后插入 `prefix` /
+    /// Add the routes of another registry with `prefix` inserted after their `/api/v{n}`
+    pub fn nest(mut self, prefix: &str, other: &RouteRegistry) -> Self {
+        self.routes.extend(other.routes.iter().map(|route| {
+            let path = match route.version {
+                Some(version) => {
+                    let base = format!("/api/v{version}");
+                    format!("{base}{prefix}{}", &route.path[base.len()..])
+                }
+                None => format!("{prefix}{}", route.path),
+            };
+            RouteInfo { path, ..route.clone() }
+        }));
+        self
+    }
+
+    pub fn routes(&self) -> &[RouteInfo] {
+        &self.routes
+    }
+
+    /// 查找匹配的路由 / Find the route matching a request
+    pub fn find(&self, method: &Method, path: &str) -> Option<&RouteInfo> {
+        self.routes
+            .iter()
+            .find(|r| r.method == method.as_str() && path_matches(&r.path, path))
+    }
+}
+
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let mut pattern_segments = pattern.trim_end_matches('/').split('/');
+    let mut path_segments = path.trim_end_matches('/').split('/');
+    loop {
+        match (pattern_segments.next(), path_segments.next()) {
+            (None, None) => return true,
+            (Some(p), Some(s)) if p.starts_with('{') && p.ends_with('}') && !s.is_empty() => continue,
+            (Some(p), Some(s)) if p == s => continue,
+            _ => return false,
+        }
+    }
+}
+
+/// 解析 `/api/v{n}/...` 中的版本号 / Parse the version out of `/api/v{n}/...`
+///
+/// 只有 `v` 后紧跟数字的段才算版本前缀，因此 `/api/versions` 不受影响。
+/// Only a segment of `v` followed by digits counts as a version prefix, so `/api/versions` is unaffected.
+pub fn path_version(path: &str) -> Option<u32> {
+    let segment = path.strip_prefix("/api/")?.split('/').next()?;
+    let digits = segment.strip_prefix('v')?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(digits.parse().unwrap_or(u32::MAX))
+}
+
+#[derive(Debug, Serialize)]
+struct UnsupportedVersion<'a> {
+    code: &'static str,
+    message: String,
+    supported_versions: Vec<String>,
+    requested: &'a str,
+}
+
+fn version_label(version: u32) -> String {
+    format!("v{version}")
+}
+
+/// API 版本中间层 / API version layer
+#[derive(Clone)]
+pub struct ApiVersionLayer {
+    registry: Arc<RouteRegistry>,
+}
+
+impl ApiVersionLayer {
+    pub fn new(registry: Arc<RouteRegistry>) -> Self {
+        Self { registry }
+    }
+}
+
+impl<S> Layer<S> for ApiVersionLayer {
+    type Service = ApiVersionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ApiVersionService { inner, registry: self.registry.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct ApiVersionService<S> {
+    inner: S,
+    registry: Arc<RouteRegistry>,
+}
+
+impl<S> Service<Request<Body>> for ApiVersionService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let path = req.uri().path().to_string();
+        let requested = path_version(&path);
+
+        if let Some(version) = requested.filter(|v| !SUPPORTED_API_VERSIONS.contains(v)) {
+            let requested = path.trim_start_matches("/api/").split('/').next().unwrap_or_default().to_string();
+            let body = UnsupportedVersion {
+                code: "UNSUPPORTED_API_VERSION",
+                message: format!("API version {} is not supported", version_label(version)),
+                supported_versions: SUPPORTED_API_VERSIONS.iter().copied().map(version_label).collect(),
+                requested: &requested,
+            };
+            let response = (StatusCode::NOT_ACCEPTABLE, Json(body)).into_response();
+            return Box::pin(async move { Ok(response) });
+        }
+
+        let deprecation = self.registry.find(req.method(), &path).and_then(|r| r.deprecated.clone());
+        let version = requested.unwrap_or(CURRENT_API_VERSION);
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let mut response = fut.await?;
+            let headers = response.headers_mut();
+            if let Ok(v) = HeaderValue::from_str(&version_label(version)) {
+                headers.insert(API_VERSION_HEADER, v);
+            }
+            if let Some(deprecation) = deprecation {
+                headers.insert("deprecation", HeaderValue::from_static("true"));
+                if let Ok(v) = HeaderValue::from_str(&deprecation.sunset) {
+                    headers.insert("sunset", v);
+                }
+                if let Some(successor) = deprecation.successor
+                    && let Ok(v) = HeaderValue::from_str(&format!("<{successor}>; rel=\"successor-version\""))
+                {
+                    headers.insert("link", v);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_version() {
+        assert_eq!(path_version("/api/v1/workflows"), Some(1));
+        assert_eq!(path_version("/api/v9"), Some(9));
+        assert_eq!(path_version("/api/vnext/x"), None);
+        assert_eq!(path_version("/api/versions"), None);
+        assert_eq!(path_version("/health"), None);
+    }
+
+    #[test]
+    fn test_registry_matches_path_params() {
+        let registry = RouteRegistry::new()
+            .route(Method::GET, "/api/v1/workflows/{id}")
+            .deprecated_route(Method::POST, "/admin/read-only", "Wed, 01 Jul 2026 00:00:00 GMT", None);
+        assert!(registry.find(&Method::GET, "/api/v1/workflows/wf-1").is_some());
+        assert!(registry.find(&Method::GET, "/api/v1/workflows/").is_none());
+        assert!(registry.find(&Method::POST, "/api/v1/workflows/wf-1").is_none());
+        assert!(registry.find(&Method::POST, "/admin/read-only").unwrap().deprecated.is_some());
+    }
+
+    #[test]
+    fn test_nested_registry_keeps_version_prefix() {
+        let inner = RouteRegistry::new().route(Method::GET, "/api/v1/workflows/{id}");
+        let registry = RouteRegistry::new().nest("/namespaces/acme", &inner);
+        let route = registry.find(&Method::GET, "/api/v1/namespaces/acme/workflows/wf-1").unwrap();
+        assert_eq!(route.version, Some(1));
+        assert!(registry.find(&Method::GET, "/api/v1/workflows/wf-1").is_none());
+    }
+}
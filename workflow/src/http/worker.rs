@@ -0,0 +1,63 @@
+//! 工作者负载 REST API / Worker load REST API
+//!
+//! 当 [`WorkflowApi`](super::workflows::WorkflowApi) 带有工作者负载时挂载于 `/api/v1/worker/load`，返回任务槽使用率与
+//! 调度到开始的延迟，供外部自动扩缩容（如 KEDA 或 HPA 的外部指标）决定副本数。
+//! Mounted at `/api/v1/worker/load` when the [`WorkflowApi`](super::workflows::WorkflowApi) has a worker load;
+//! returns task slot utilization and schedule-to-start latency for external autoscalers (such as KEDA or HPA
+//! external metrics) to size the deployment.
+
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::Method;
+use axum::routing::get;
+use axum::{Json, Router};
+
+use super::versioning::RouteRegistry;
+use crate::temporal::tuning::{WorkerLoad, WorkerSignals};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/worker/load",
+    tag = "worker",
+    responses((status = 200, description = "Task slot usage and schedule-to-start latency", body = WorkerSignals))
+)]
+pub(super) async fn worker_load(State(load): State<Arc<WorkerLoad>>) -> Json<WorkerSignals> {
+    Json(load.signals())
+}
+
+/// 工作者负载路由，挂载于 `/api/v1` 下 / Worker load routes, nested under `/api/v1`
+pub(crate) fn routes(load: Arc<WorkerLoad>) -> Router {
+    Router::new().route("/worker/load", get(worker_load)).with_state(load)
+}
+
+/// 在注册表中登记工作者负载路由 / Add the worker load routes to a registry
+pub fn register_routes(registry: RouteRegistry) -> RouteRegistry {
+    registry.route(Method::GET, "/api/v1/worker/load")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::http::build_router_with_workflows;
+    use crate::http::workflows::WorkflowApi;
+    use crate::temporal::{WorkerConfig, WorkflowWorker};
+
+    #[tokio::test]
+    async fn test_worker_load_over_http() {
+        let worker = WorkflowWorker::new(WorkerConfig::builder().max_concurrent_activity_tasks(5).build());
+        worker.load().set_max_concurrent_workflow_tasks(3);
+        let app = build_router_with_workflows(WorkflowApi::from_worker(&worker));
+
+        let request = Request::builder().uri("/api/v1/worker/load").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["workflow"]["max_slots"], 3);
+        assert_eq!(body["activity"]["max_slots"], 5);
+        assert_eq!(body["activity"]["used_slots"], 0);
+    }
+}
@@ -0,0 +1,1273 @@
+//! 工作流生命周期 REST API / Workflow lifecycle REST API
+//!
+//! 由 [`build_router_with_workflows`](super::build_router_with_workflows) 挂载在 `/api/v1/workflows` 下，
+//! 通过 [`WorkflowClient`] 启动、查看、发送信号、查询、取消工作流，读取、导出与导入事件历史。
+//! Mounted under `/api/v1/workflows` by [`build_router_with_workflows`](super::build_router_with_workflows);
+//! starts, describes, signals, queries and cancels workflows and reads, exports and imports their event history through a
+//! [`WorkflowClient`].
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::Extension;
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::auth::Principal;
+use super::health::{DependencyCheck, Readiness};
+use super::hooks::Hook;
+use super::read_only::ReadOnlyMode;
+use super::versioning::RouteRegistry;
+use crate::audit::{AuditAction, AuditEntry, AuditLog};
+use crate::dsl::{DiagramFormat, WorkflowSpec};
+use crate::temporal::error::{QueryError, SignalError, UpdateError};
+use crate::temporal::event::WorkflowEvent;
+use crate::temporal::namespace::Namespace;
+use crate::temporal::tuning::WorkerLoad;
+use crate::temporal::{
+    DynamicActivityRegistry, EventId, HistoryExport, HistoryFormat, Memo, Priority, SearchAttributes, StartWorkflowOptions, WorkflowClient, WorkflowError, WorkflowExecution, WorkflowExecutionInfo,
+    WorkflowExecutionStatus, WorkflowFilter, WorkflowId, WorkflowIdReusePolicy, PauseHandle, WorkerStatus, WorkflowWorker,
+};
+use crate::util::pagination::{filter_hash, Cursor, PageRequest, PageToken, PageTokenCodec, PaginationError};
+
+/// 启动请求的幂等键头 / Header carrying the idempotency key of a start request
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// 单页历史事件数上限 / Most history events returned per page
+const HISTORY_PAGE_SIZE: usize = 1000;
+
+/// 历史事件流检查存储的间隔 / How often the history event stream checks storage for new events
+const HISTORY_STREAM_POLL: Duration = Duration::from_secs(1);
+
+/// 工作流 API 状态 / State of the workflow API
+#[derive(Clone)]
+pub struct WorkflowApi {
+    client: WorkflowClient,
+    workflow_types: Arc<BTreeSet<String>>,
+    audit: Option<AuditLog>,
+    activities: Option<Arc<DynamicActivityRegistry>>,
+    outlines: Arc<BTreeMap<String, WorkflowSpec>>,
+    hooks: Arc<BTreeMap<String, Hook>>,
+    namespaces: Arc<BTreeMap<Namespace, WorkflowApi>>,
+    load: Option<Arc<WorkerLoad>>,
+    status: Option<WorkerStatus>,
+    readiness: Readiness,
+    page_tokens: PageTokenCodec,
+    read_only: ReadOnlyMode,
+    /// 随只读模式暂停派发的工作者 / Worker whose dispatch pauses with read-only mode
+    dispatch: Option<PauseHandle>,
+    /// 工作者的任务剖析器，供 `/debug` 使用 / The worker's task profiler, for `/debug`
+    #[cfg(feature = "diagnostics")]
+    task_profiler: Option<Arc<crate::diagnostics::TaskProfiler>>,
+}
+
+impl WorkflowApi {
+    /// 仅允许启动 `workflow_types` 中的类型 / Only the types in `workflow_types` can be started
+    pub fn new(client: WorkflowClient, workflow_types: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            readiness: Readiness::for_client(&client),
+            client,
+            workflow_types: Arc::new(workflow_types.into_iter().map(Into::into).collect()),
+            audit: None,
+            activities: None,
+            outlines: Arc::default(),
+            hooks: Arc::default(),
+            namespaces: Arc::default(),
+            load: None,
+            status: None,
+            page_tokens: PageTokenCodec::default(),
+            read_only: ReadOnlyMode::default(),
+            dispatch: None,
+            #[cfg(feature = "diagnostics")]
+            task_profiler: None,
+        }
+    }
+
+    /// 将启动、信号与取消写入审计日志，并在 `/api/v1/audit` 提供 / Audit starts, signals and cancels, served at `/api/v1/audit`
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    pub(super) fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit.as_ref()
+    }
+
+    /// 在 `/api/v1/activities` 列出并直接调用动态活动 / List and directly invoke dynamic activities at `/api/v1/activities`
+    pub fn with_activities(mut self, activities: Arc<DynamicActivityRegistry>) -> Self {
+        self.activities = Some(activities);
+        self
+    }
+
+    pub(super) fn activity_registry(&self) -> Option<&Arc<DynamicActivityRegistry>> {
+        self.activities.as_ref()
+    }
+
+    /// 在 `/api/v1/workflows/{type}/diagram` 提供该类型的结构图 / Serve a diagram of the type at `/api/v1/workflows/{type}/diagram`
+    pub fn with_outline(mut self, outline: WorkflowSpec) -> Self {
+        Arc::make_mut(&mut self.outlines).insert(outline.name.clone(), outline);
+        self
+    }
+
+    /// 在 `/api/v1/hooks/{hook_id}` 接收外部系统的回调并转为信号 / Turn callbacks of external systems to
+    /// `/api/v1/hooks/{hook_id}` into signals
+    pub fn with_hook(mut self, hook_id: impl Into<String>, hook: Hook) -> Self {
+        Arc::make_mut(&mut self.hooks).insert(hook_id.into(), hook);
+        self
+    }
+
+    pub(super) fn hooks(&self) -> &BTreeMap<String, Hook> {
+        &self.hooks
+    }
+
+    /// 在 `/api/v1/namespaces/{namespace}` 下（或带 `X-Namespace` 头时）提供另一命名空间的 API /
+    /// Serve the API of another namespace under `/api/v1/namespaces/{namespace}`, or with an `X-Namespace` header
+    ///
+    /// 通常来自 [`Namespaces::worker`](crate::temporal::namespace::Namespaces::worker) 创建的工作者。
+    /// Usually built from a worker created by [`Namespaces::worker`](crate::temporal::namespace::Namespaces::worker).
+    pub fn with_namespace(mut self, namespace: Namespace, api: WorkflowApi) -> Self {
+        if let Some(dispatch) = &api.dispatch {
+            self.read_only.pause_worker(dispatch.clone());
+        }
+        Arc::make_mut(&mut self.namespaces).insert(namespace, api);
+        self
+    }
+
+    pub(super) fn namespaces(&self) -> &BTreeMap<Namespace, WorkflowApi> {
+        &self.namespaces
+    }
+
+    /// 在 `/api/v1/worker/load` 提供工作者的饱和度信号，供外部自动扩缩容读取 /
+    /// Serve the saturation signals of a worker at `/api/v1/worker/load`, for external autoscalers
+    pub fn with_worker_load(mut self, load: Arc<WorkerLoad>) -> Self {
+        self.load = Some(load);
+        self
+    }
+
+    pub(super) fn worker_load(&self) -> Option<&Arc<WorkerLoad>> {
+        self.load.as_ref()
+    }
+
+    /// 在 `/api/v1/admin` 下提供工作者的内省信息 / Serve introspection of a worker under `/api/v1/admin`
+    pub fn with_worker_status(mut self, status: WorkerStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub(super) fn worker_status(&self) -> Option<&WorkerStatus> {
+        self.status.as_ref()
+    }
+
+    /// 在 `/readyz` 中另外检查一项依赖，如审计日志所在的数据库 / Also check a dependency at `/readyz`, such as the
+    /// database behind the audit log
+    pub fn with_readiness_check(mut self, check: impl DependencyCheck + 'static) -> Self {
+        self.readiness = self.readiness.with_check(check);
+        self
+    }
+
+    pub(super) fn readiness(&self) -> &Readiness {
+        &self.readiness
+    }
+
+    /// 用共享密钥签发分页令牌，使各实例接受彼此签发的令牌；缺省为进程级随机密钥 / Sign page tokens with a shared
+    /// key, so every instance accepts the tokens of the others; a random per-process key by default
+    pub fn with_page_token_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.page_tokens = PageTokenCodec::new(key);
+        self
+    }
+
+    pub(super) fn page_tokens(&self) -> &PageTokenCodec {
+        &self.page_tokens
+    }
+
+    /// 使用共享的只读开关，如由配置与持久化适配器恢复的开关；其工作者随之暂停派发 / Use a shared read-only switch,
+    /// such as one restored from the configuration and the persistence adapter; its workers pause dispatch with it
+    pub fn with_read_only(mut self, mode: ReadOnlyMode) -> Self {
+        for dispatch in self.dispatch.iter().chain(self.namespaces.values().filter_map(|api| api.dispatch.as_ref())) {
+            mode.pause_worker(dispatch.clone());
+        }
+        self.read_only = mode;
+        self
+    }
+
+    /// 服务的只读开关 / The service's read-only switch
+    pub fn read_only(&self) -> &ReadOnlyMode {
+        &self.read_only
+    }
+
+    /// 工作者的任务剖析器 / The worker's task profiler
+    #[cfg(feature = "diagnostics")]
+    pub fn task_profiler(&self) -> Option<&Arc<crate::diagnostics::TaskProfiler>> {
+        self.task_profiler.as_ref()
+    }
+
+    pub(super) fn client(&self) -> &WorkflowClient {
+        &self.client
+    }
+
+    pub(super) async fn audit(&self, principal: Option<Extension<Principal>>, entry: AuditEntry, succeeded: bool) {
+        if let Some(audit) = &self.audit {
+            let principal = principal.map_or_else(|| crate::audit::ANONYMOUS.to_string(), |Extension(p)| p.id);
+            audit.record(entry.principal(principal).source("http").succeeded(succeeded)).await;
+        }
+    }
+
+    /// 使用工作者的客户端、其当前已注册的工作流类型及其步骤概要、动态活动、负载与内省信息，在 `/readyz`
+    /// 检查该工作者，并在只读期间暂停其派发 / The worker's client, the workflow types registered so far with their step
+    /// outlines, and the worker's dynamic activities, load and introspection, with the worker checked at `/readyz` and
+    /// its dispatch paused while read-only
+    pub fn from_worker(worker: &WorkflowWorker) -> Self {
+        let workflow_types = worker.registered_workflows();
+        let mut api = Self::new(worker.client(), workflow_types.iter().cloned())
+            .with_worker_load(worker.load().clone())
+            .with_worker_status(worker.status());
+        api.readiness = Readiness::for_worker(worker);
+        api.dispatch = Some(worker.pause_handle());
+        #[cfg(feature = "diagnostics")]
+        {
+            api.task_profiler = Some(worker.task_profiler().clone());
+        }
+        api.read_only.pause_worker(worker.pause_handle());
+        for outline in workflow_types.iter().filter_map(|workflow_type| worker.workflow_outline(workflow_type)) {
+            api = api.with_outline(outline);
+        }
+        match worker.dynamic_activities() {
+            Some(activities) => api.with_activities(activities.clone()),
+            None => api,
+        }
+    }
+}
+
+/// 启动请求 / Start request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct StartWorkflowRequest {
+    /// 已注册的工作流类型 / Registered workflow type
+    pub workflow_type: String,
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub input: serde_json::Value,
+    /// 缺省时生成 / Generated when absent
+    pub workflow_id: Option<String>,
+    /// 缺省为 `default` / Defaults to `default`
+    pub task_queue: Option<String>,
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub search_attributes: SearchAttributes,
+    /// 随运行返回但不建索引的元数据，如关联 ID 或客户名 / Metadata returned with the run but not indexed, such as a
+    /// correlation ID or a customer name
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub memo: Memo,
+    /// 已关闭的同 ID 运行能否被再次启动，缺省为 `AllowDuplicate` / Whether a closed run with the same ID may be
+    /// started again, defaults to `AllowDuplicate`
+    #[serde(default)]
+    #[schema(value_type = String, example = "RejectDuplicate")]
+    pub id_reuse_policy: WorkflowIdReusePolicy,
+    /// 任务出队优先级，1（最先）到 5（最后），缺省为 3 / Dequeue priority of the run's tasks, 1 (first) to 5 (last),
+    /// defaults to 3
+    #[schema(value_type = Option<u8>, minimum = 1, maximum = 5)]
+    pub priority: Option<Priority>,
+    /// 业务去重键（如订单号）：同类型、同键的运行未关闭或关闭不久时返回该运行 / Business dedup key such as an order
+    /// ID: while a run of the same type and key is open or recently closed, that run is returned
+    #[schema(example = "order-1042")]
+    pub dedup_key: Option<String>,
+}
+
+/// 已启动的运行 / Started run
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StartedWorkflow {
+    pub workflow_id: String,
+    pub run_id: String,
+}
+
+impl From<WorkflowExecution> for StartedWorkflow {
+    fn from(execution: WorkflowExecution) -> Self {
+        Self {
+            workflow_id: execution.workflow_id.to_string(),
+            run_id: execution.run_id.to_string(),
+        }
+    }
+}
+
+/// 状态响应 / Status response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WorkflowStatusResponse {
+    #[schema(value_type = StartedWorkflow)]
+    pub execution: WorkflowExecution,
+    pub workflow_type: String,
+    pub task_queue: String,
+    #[schema(value_type = String, example = "Running")]
+    pub status: WorkflowExecutionStatus,
+    pub start_time: DateTime<Utc>,
+    pub close_time: Option<DateTime<Utc>>,
+    #[schema(value_type = Object)]
+    pub search_attributes: SearchAttributes,
+    #[schema(value_type = Object)]
+    pub memo: Memo,
+    /// 完成时的结果 / Result once completed
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<serde_json::Value>,
+    /// 失败原因 / Failure once failed
+    pub failure: Option<String>,
+}
+
+/// 错误响应 / Error response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+}
+
+pub(super) fn error_response(status: StatusCode, code: &str, message: impl Into<String>) -> Response {
+    let body = ErrorBody {
+        code: code.to_string(),
+        message: message.into(),
+    };
+    (status, Json(body)).into_response()
+}
+
+pub(super) fn workflow_error_response(e: WorkflowError) -> Response {
+    match e {
+        WorkflowError::AlreadyStarted(_) => error_response(StatusCode::CONFLICT, "WORKFLOW_ALREADY_STARTED", e.to_string()),
+        WorkflowError::QuotaExceeded(_) => error_response(StatusCode::TOO_MANY_REQUESTS, "QUOTA_EXCEEDED", e.to_string()),
+        WorkflowError::InvalidInput(_) | WorkflowError::SerializationError(_) => {
+            error_response(StatusCode::BAD_REQUEST, "INVALID_INPUT", e.to_string())
+        }
+        _ => error_response(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", e.to_string()),
+    }
+}
+
+pub(super) fn signal_error_response(e: SignalError) -> Response {
+    match e {
+        SignalError::WorkflowNotFound => not_found(),
+        SignalError::WorkflowClosed(_) => error_response(StatusCode::CONFLICT, "WORKFLOW_CLOSED", e.to_string()),
+        SignalError::SerializationError(_) => error_response(StatusCode::BAD_REQUEST, "INVALID_INPUT", e.to_string()),
+        _ => error_response(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", e.to_string()),
+    }
+}
+
+pub(super) fn query_error_response(e: QueryError) -> Response {
+    match e {
+        QueryError::WorkflowNotFound => not_found(),
+        QueryError::QueryNotRegistered(_) => error_response(StatusCode::BAD_REQUEST, "QUERY_NOT_REGISTERED", e.to_string()),
+        QueryError::WorkflowNotRunning => error_response(StatusCode::CONFLICT, "WORKFLOW_CLOSED", e.to_string()),
+        _ => error_response(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", e.to_string()),
+    }
+}
+
+pub(super) fn update_error_response(e: UpdateError) -> Response {
+    match e {
+        UpdateError::WorkflowNotFound => not_found(),
+        UpdateError::UpdateNotRegistered(_) => {
+            error_response(StatusCode::BAD_REQUEST, "UPDATE_NOT_REGISTERED", e.to_string())
+        }
+        UpdateError::Rejected(_) => error_response(StatusCode::UNPROCESSABLE_ENTITY, "UPDATE_REJECTED", e.to_string()),
+        UpdateError::WorkflowNotRunning => error_response(StatusCode::CONFLICT, "WORKFLOW_CLOSED", e.to_string()),
+        _ => error_response(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", e.to_string()),
+    }
+}
+
+fn not_found() -> Response {
+    error_response(StatusCode::NOT_FOUND, "WORKFLOW_NOT_FOUND", "workflow not found")
+}
+
+/// 伪造、损坏或为其他过滤条件签发的分页令牌 / Page token that is forged, corrupt or issued for another filter
+pub(super) fn invalid_page_token(e: PaginationError) -> Response {
+    error_response(StatusCode::BAD_REQUEST, "INVALID_PAGE_TOKEN", e.to_string())
+}
+
+/// 按序号翻页的列表中 `after` 之后一页的令牌 / Token of the page after `after` in a listing paged by sequence number
+pub(super) fn sequence_token(tokens: &PageTokenCodec, filter_hash: &str, after: u64) -> PageToken {
+    tokens.encode(&Cursor {
+        sort_key: format!("{:020}", after),
+        last_id: after.to_string(),
+        filter_hash: filter_hash.to_string(),
+    })
+}
+
+/// 令牌所接续的序号；没有令牌时为空 / Sequence number a token continues after; `None` without a token
+pub(super) fn sequence_after(
+    tokens: &PageTokenCodec,
+    token: Option<&PageToken>,
+    filter_hash: &str,
+) -> Result<Option<u64>, PaginationError> {
+    let Some(token) = token else {
+        return Ok(None);
+    };
+    let cursor = tokens.decode(token, filter_hash)?;
+    cursor.last_id.parse().map(Some).map_err(|_| PaginationError::MalformedToken)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/workflows",
+    tag = "workflows",
+    request_body = StartWorkflowRequest,
+    params(("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key return the run the first request started")),
+    responses(
+        (status = 201, description = "Workflow started", body = StartedWorkflow),
+        (status = 404, description = "Workflow type not registered", body = ErrorBody),
+        (status = 409, description = "Workflow ID already running", body = ErrorBody),
+        (status = 429, description = "Namespace at its limit of concurrent runs", body = ErrorBody)
+    )
+)]
+pub(super) async fn start_workflow(
+    State(api): State<WorkflowApi>,
+    principal: Option<Extension<Principal>>,
+    headers: HeaderMap,
+    Json(req): Json<StartWorkflowRequest>,
+) -> Response {
+    let mut entry = AuditEntry::new(AuditAction::Start, req.workflow_id.clone().unwrap_or_default())
+        .target(&req.workflow_type)
+        .payload(&req.input);
+    if !api.workflow_types.contains(&req.workflow_type) {
+        api.audit(principal, entry, false).await;
+        return error_response(
+            StatusCode::NOT_FOUND,
+            "WORKFLOW_TYPE_NOT_FOUND",
+            format!("workflow type not registered: {}", req.workflow_type),
+        );
+    }
+    let defaults = StartWorkflowOptions::default();
+    let options = StartWorkflowOptions {
+        workflow_id: req.workflow_id.map(WorkflowId::new),
+        task_queue: req.task_queue.unwrap_or(defaults.task_queue.clone()),
+        search_attributes: req.search_attributes,
+        memo: req.memo,
+        id_reuse_policy: req.id_reuse_policy,
+        priority: req.priority.unwrap_or(defaults.priority),
+        dedup_key: req.dedup_key,
+        idempotency_key: headers
+            .get(IDEMPOTENCY_KEY)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        ..defaults
+    };
+    match api.client.start_workflow_value(&req.workflow_type, req.input, &options).await {
+        Ok(execution) => {
+            entry.workflow_id = execution.workflow_id.to_string();
+            api.audit(principal, entry, true).await;
+            (StatusCode::CREATED, Json(StartedWorkflow::from(execution))).into_response()
+        }
+        Err(e) => {
+            api.audit(principal, entry, false).await;
+            workflow_error_response(e)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/workflows/{id}",
+    tag = "workflows",
+    params(("id" = String, Path, description = "Workflow ID")),
+    responses(
+        (status = 200, description = "Status and result of the latest run", body = WorkflowStatusResponse),
+        (status = 404, description = "Workflow not found", body = ErrorBody)
+    )
+)]
+pub(super) async fn describe_workflow(State(api): State<WorkflowApi>, Path(id): Path<String>) -> Response {
+    let (execution, history) = match api.client.load_workflow(&WorkflowId::new(id)).await {
+        Ok(Some(found)) => found,
+        Ok(None) => return not_found(),
+        Err(e) => return workflow_error_response(e),
+    };
+    let Some(info) = WorkflowExecutionInfo::from_history(execution, &history) else {
+        return not_found();
+    };
+    let (result, failure) = match history.outcome() {
+        Some(Ok(result)) => (Some(result), None),
+        Some(Err(failure)) => (None, Some(failure)),
+        None => (None, None),
+    };
+    Json(WorkflowStatusResponse {
+        execution: info.execution,
+        workflow_type: info.workflow_type,
+        task_queue: info.task_queue,
+        status: info.status,
+        start_time: info.start_time,
+        close_time: info.close_time,
+        search_attributes: info.search_attributes,
+        memo: info.memo,
+        result,
+        failure,
+    })
+    .into_response()
+}
+
+/// 列表过滤条件 / Listing filter
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ListWorkflowsQuery {
+    pub workflow_type: Option<String>,
+    /// 如 `Running`、`Completed` / E.g. `Running`, `Completed`
+    #[param(value_type = Option<String>)]
+    pub status: Option<WorkflowExecutionStatus>,
+    /// 每页运行数，缺省 100，最多 1000 / Runs per page, defaults to 100, at most 1000
+    pub limit: Option<usize>,
+    /// 上一页返回的 `next_page_token`，须与相同的过滤条件一起使用 / `next_page_token` of the previous page, valid
+    /// only with the same filter
+    #[param(value_type = Option<String>)]
+    pub page_token: Option<PageToken>,
+}
+
+/// 运行列表 / List of runs
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WorkflowList {
+    /// 最近启动的在前 / Most recently started first
+    #[schema(value_type = Vec<Object>)]
+    pub workflows: Vec<WorkflowExecutionInfo>,
+    /// 下一页的 `page_token`；最后一页为空 / `page_token` of the next page, absent on the last page
+    #[schema(value_type = Option<String>)]
+    pub next_page_token: Option<PageToken>,
+}
+
+/// 列表的排序键：最近启动的在前，同时启动的按 ID / Listing order: most recently started first, runs started
+/// together by ID
+pub(super) fn listing_key(info: &WorkflowExecutionInfo) -> (String, String) {
+    let newest_first = i64::MAX.saturating_sub(info.start_time.timestamp_micros());
+    (format!("{:020}", newest_first), info.execution.workflow_id.to_string())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/workflows",
+    tag = "workflows",
+    params(ListWorkflowsQuery),
+    responses(
+        (status = 200, description = "One page of the latest run of each matching workflow, most recently started first", body = WorkflowList),
+        (status = 400, description = "Invalid page token", body = ErrorBody)
+    )
+)]
+pub(super) async fn list_workflows(State(api): State<WorkflowApi>, Query(query): Query<ListWorkflowsQuery>) -> Response {
+    let filters = filter_hash(&serde_json::json!({
+        "workflows": { "workflow_type": query.workflow_type, "status": query.status }
+    }));
+    let mut filter = WorkflowFilter::new();
+    if let Some(workflow_type) = query.workflow_type {
+        filter = filter.workflow_type(workflow_type);
+    }
+    if let Some(status) = query.status {
+        filter = filter.status(status);
+    }
+    let mut workflows = match api.client.list_workflows(&filter).await {
+        Ok(workflows) => workflows,
+        Err(e) => return workflow_error_response(e),
+    };
+    workflows.sort_by_cached_key(listing_key);
+    let request = PageRequest {
+        page_size: query.limit,
+        page_token: query.page_token,
+    };
+    match api.page_tokens.paginate(&workflows, listing_key, &filters, &request) {
+        Ok(page) => Json(WorkflowList {
+            workflows: page.items,
+            next_page_token: page.next_token,
+        })
+        .into_response(),
+        Err(e) => invalid_page_token(e),
+    }
+}
+
+/// 历史分页 / History paging
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct HistoryQuery {
+    /// 上一页返回的 `next_page_token`，缺省从头读取 / `next_page_token` of the previous page, from the start when absent
+    #[param(value_type = Option<String>)]
+    pub page_token: Option<PageToken>,
+    /// 每页事件数，缺省且最多为 1000 / Events per page, 1000 by default and at most
+    pub limit: Option<usize>,
+}
+
+/// 一页历史事件 / One page of history events
+#[derive(Debug, Serialize)]
+pub struct HistoryPageResponse {
+    pub execution: WorkflowExecution,
+    pub events: Vec<WorkflowEvent>,
+    /// 下一页的 `page_token`；最后一页为空 / `page_token` of the next page, absent on the last page
+    pub next_page_token: Option<PageToken>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/workflows/{id}/history",
+    tag = "workflows",
+    params(("id" = String, Path, description = "Workflow ID"), HistoryQuery),
+    responses(
+        (status = 200, description = "Execution and one page of its events; `next_page_token` is the `page_token` of the next page", body = Object),
+        (status = 400, description = "Invalid page token", body = ErrorBody),
+        (status = 404, description = "Workflow not found", body = ErrorBody)
+    )
+)]
+pub(super) async fn workflow_history(
+    State(api): State<WorkflowApi>,
+    Path(id): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let filters = filter_hash(&serde_json::json!({ "history": id }));
+    let after = match sequence_after(&api.page_tokens, query.page_token.as_ref(), &filters) {
+        Ok(after) => EventId(after.unwrap_or_default()),
+        Err(e) => return invalid_page_token(e),
+    };
+    let limit = query.limit.unwrap_or(HISTORY_PAGE_SIZE).clamp(1, HISTORY_PAGE_SIZE);
+    match api.client.load_events(&WorkflowId::new(id), after, limit).await {
+        Ok(Some(page)) => Json(HistoryPageResponse {
+            execution: page.execution,
+            events: page.events,
+            next_page_token: page.next_after.map(|after| sequence_token(&api.page_tokens, &filters, after.0)),
+        })
+        .into_response(),
+        Ok(None) => not_found(),
+        Err(e) => workflow_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/workflows/{id}/history/stream",
+    tag = "workflows",
+    params(
+        ("id" = String, Path, description = "Workflow ID"),
+        ("Last-Event-ID" = Option<u64>, Header, description = "Resume after this event ID")
+    ),
+    responses(
+        (status = 200, description = "Server-sent `history` events, one per history event, then `closed` once the run closes", content_type = "text/event-stream", body = String),
+        (status = 404, description = "Workflow not found", body = ErrorBody)
+    )
+)]
+pub(super) async fn stream_workflow_history(
+    State(api): State<WorkflowApi>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let workflow_id = WorkflowId::new(id);
+    let after = EventId(
+        headers
+            .get("last-event-id")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default(),
+    );
+    // 先确认工作流存在，才能回答 404 / Make sure the workflow exists first, so that a 404 can be answered
+    match api.client.load_events(&workflow_id, after, 1).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return not_found(),
+        Err(e) => return workflow_error_response(e),
+    }
+    let client = api.client.clone();
+    let batches = futures::stream::unfold(Some(after), move |after| {
+        let (client, workflow_id) = (client.clone(), workflow_id.clone());
+        async move {
+            let mut after = after?;
+            loop {
+                let page = match client.load_events(&workflow_id, after, HISTORY_PAGE_SIZE).await {
+                    Ok(Some(page)) => page,
+                    Ok(None) => return None,
+                    Err(e) => return Some((vec![Event::default().event("error").data(e.to_string())], None)),
+                };
+                if page.events.is_empty() {
+                    tokio::time::sleep(HISTORY_STREAM_POLL).await;
+                    continue;
+                }
+                let closed = page.next_after.is_none() && page.events.iter().any(|e| e.closes_run());
+                let mut batch: Vec<Event> = page
+                    .events
+                    .iter()
+                    .map(|e| {
+                        Event::default()
+                            .event("history")
+                            .id(e.event_id.0.to_string())
+                            .data(serde_json::to_string(e).unwrap_or_default())
+                    })
+                    .collect();
+                if let Some(last) = page.events.last() {
+                    after = last.event_id;
+                }
+                if closed {
+                    batch.push(Event::default().event("closed").data(page.execution.run_id.to_string()));
+                    return Some((batch, None));
+                }
+                return Some((batch, Some(after)));
+            }
+        }
+    });
+    let events = batches.flat_map(futures::stream::iter).map(Ok::<_, Infallible>);
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+/// 导出格式 / Export format
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct HistoryExportQuery {
+    /// `json`（缺省）或 `protobuf` / `json` (default) or `protobuf`
+    #[param(value_type = Option<String>)]
+    pub format: Option<HistoryFormat>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/workflows/{id}/history/export",
+    tag = "workflows",
+    params(("id" = String, Path, description = "Workflow ID"), HistoryExportQuery),
+    responses(
+        (status = 200, description = "History of the latest run as a download", content(
+            (Object = "application/json"),
+            (Vec<u8> = "application/x-protobuf")
+        )),
+        (status = 404, description = "Workflow not found", body = ErrorBody)
+    )
+)]
+pub(super) async fn export_history(
+    State(api): State<WorkflowApi>,
+    Path(id): Path<String>,
+    Query(query): Query<HistoryExportQuery>,
+) -> Response {
+    let format = query.format.unwrap_or_default();
+    match api.client.export_history(&WorkflowId::new(&id), format).await {
+        Ok(Some(bytes)) => {
+            let extension = match format {
+                HistoryFormat::Json => "json",
+                HistoryFormat::Protobuf => "pb",
+            };
+            let disposition = format!("attachment; filename=\"{}-history.{}\"", id, extension);
+            (
+                [(header::CONTENT_TYPE, format.content_type().to_string()), (header::CONTENT_DISPOSITION, disposition)],
+                bytes,
+            )
+                .into_response()
+        }
+        Ok(None) => not_found(),
+        Err(e) => workflow_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/workflows/{id}/history/import",
+    tag = "workflows",
+    params(("id" = String, Path, description = "Workflow ID")),
+    request_body(content(
+        (Object = "application/json"),
+        (Vec<u8> = "application/x-protobuf")
+    ), description = "Exported history"),
+    responses(
+        (status = 201, description = "History imported; an open run is resumed", body = StartedWorkflow),
+        (status = 400, description = "Malformed export or workflow ID mismatch", body = ErrorBody),
+        (status = 409, description = "Workflow ID already running", body = ErrorBody),
+        (status = 415, description = "Unsupported content type", body = ErrorBody)
+    )
+)]
+pub(super) async fn import_history(
+    State(api): State<WorkflowApi>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/json");
+    let Some(format) = HistoryFormat::from_content_type(content_type) else {
+        return error_response(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "UNSUPPORTED_MEDIA_TYPE",
+            format!("unsupported content type: {}", content_type),
+        );
+    };
+    let export = match HistoryExport::decode(format, &body) {
+        Ok(export) => export,
+        Err(e) => return workflow_error_response(e),
+    };
+    if export.execution.workflow_id.0 != id {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "INVALID_INPUT",
+            format!("export is of workflow {}, not {}", export.execution.workflow_id, id),
+        );
+    }
+    match api.client.import_history(export).await {
+        Ok(execution) => (StatusCode::CREATED, Json(StartedWorkflow::from(execution))).into_response(),
+        Err(e) => workflow_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/workflows/{id}/signal/{name}",
+    tag = "workflows",
+    params(
+        ("id" = String, Path, description = "Workflow ID"),
+        ("name" = String, Path, description = "Signal name")
+    ),
+    request_body(content = Object, description = "Signal payload", content_type = "application/json"),
+    responses(
+        (status = 202, description = "Signal queued"),
+        (status = 404, description = "Workflow not found", body = ErrorBody),
+        (status = 409, description = "Workflow already closed", body = ErrorBody)
+    )
+)]
+pub(super) async fn signal_workflow(
+    State(api): State<WorkflowApi>,
+    principal: Option<Extension<Principal>>,
+    Path((id, name)): Path<(String, String)>,
+    input: Option<Json<serde_json::Value>>,
+) -> Response {
+    let input = input.map(|Json(v)| v).unwrap_or_default();
+    let entry = AuditEntry::new(AuditAction::Signal, &id).target(&name).payload(&input);
+    let result = api.client.signal_workflow_value(&WorkflowId::new(id), &name, input).await;
+    api.audit(principal, entry, result.is_ok()).await;
+    match result {
+        Ok(()) => StatusCode::ACCEPTED.into_response(),
+        Err(e) => signal_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/workflows/{id}/query/{name}",
+    tag = "workflows",
+    params(
+        ("id" = String, Path, description = "Workflow ID"),
+        ("name" = String, Path, description = "Query name")
+    ),
+    responses(
+        (status = 200, description = "Query result", body = Object),
+        (status = 400, description = "Query not registered", body = ErrorBody),
+        (status = 404, description = "Workflow not found", body = ErrorBody),
+        (status = 409, description = "Workflow already closed", body = ErrorBody)
+    )
+)]
+pub(super) async fn query_workflow(
+    State(api): State<WorkflowApi>,
+    principal: Option<Extension<Principal>>,
+    Path((id, name)): Path<(String, String)>,
+) -> Response {
+    let entry = AuditEntry::new(AuditAction::Query, &id).target(&name);
+    let result = api.client.query_workflow_value(&WorkflowId::new(id), &name).await;
+    api.audit(principal, entry, result.is_ok()).await;
+    match result {
+        Ok(value) => Json(value).into_response(),
+        Err(e) => query_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/workflows/{id}/update/{name}",
+    tag = "workflows",
+    params(
+        ("id" = String, Path, description = "Workflow ID"),
+        ("name" = String, Path, description = "Update name")
+    ),
+    request_body = Object,
+    responses(
+        (status = 200, description = "Update handler result", body = Object),
+        (status = 400, description = "Update not registered", body = ErrorBody),
+        (status = 404, description = "Workflow not found", body = ErrorBody),
+        (status = 409, description = "Workflow already closed", body = ErrorBody),
+        (status = 422, description = "Update rejected by the workflow's validator", body = ErrorBody)
+    )
+)]
+pub(super) async fn update_workflow(
+    State(api): State<WorkflowApi>,
+    principal: Option<Extension<Principal>>,
+    Path((id, name)): Path<(String, String)>,
+    input: Option<Json<serde_json::Value>>,
+) -> Response {
+    let input = input.map(|Json(v)| v).unwrap_or_default();
+    let entry = AuditEntry::new(AuditAction::Update, &id).target(&name).payload(&input);
+    let result = api.client.update_workflow_value(&WorkflowId::new(id), &name, input).await;
+    api.audit(principal, entry, result.is_ok()).await;
+    match result {
+        Ok(value) => Json(value).into_response(),
+        Err(e) => update_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/workflows/{id}/cancel",
+    tag = "workflows",
+    params(("id" = String, Path, description = "Workflow ID")),
+    responses(
+        (status = 202, description = "Cancellation requested"),
+        (status = 404, description = "Workflow not found", body = ErrorBody),
+        (status = 409, description = "Workflow already closed", body = ErrorBody)
+    )
+)]
+pub(super) async fn cancel_workflow(
+    State(api): State<WorkflowApi>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<String>,
+) -> Response {
+    let entry = AuditEntry::new(AuditAction::Cancel, &id);
+    let result = api.client.cancel_workflow(&WorkflowId::new(id)).await;
+    api.audit(principal, entry, result.is_ok()).await;
+    match result {
+        Ok(()) => StatusCode::ACCEPTED.into_response(),
+        Err(e) => signal_error_response(e),
+    }
+}
+
+/// 结构图格式 / Diagram format
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DiagramQuery {
+    /// `mermaid`（缺省）或 `dot` / `mermaid` (default) or `dot`
+    #[param(value_type = Option<String>)]
+    pub format: Option<DiagramFormat>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/workflows/{id}/diagram",
+    tag = "workflows",
+    params(("id" = String, Path, description = "Workflow type"), DiagramQuery),
+    responses(
+        (status = 200, description = "Diagram of the workflow type's steps", content(
+            (String = "text/plain"),
+            (String = "text/vnd.graphviz")
+        )),
+        (status = 404, description = "Workflow type not registered, or registered without an outline", body = ErrorBody)
+    )
+)]
+pub(super) async fn workflow_diagram(
+    State(api): State<WorkflowApi>,
+    Path(workflow_type): Path<String>,
+    Query(query): Query<DiagramQuery>,
+) -> Response {
+    let format = query.format.unwrap_or_default();
+    match api.outlines.get(&workflow_type) {
+        Some(outline) => ([(header::CONTENT_TYPE, format.content_type())], format.render(outline)).into_response(),
+        None if api.workflow_types.contains(&workflow_type) => error_response(
+            StatusCode::NOT_FOUND,
+            "OUTLINE_NOT_FOUND",
+            format!("workflow type {} does not describe its steps", workflow_type),
+        ),
+        None => error_response(
+            StatusCode::NOT_FOUND,
+            "WORKFLOW_TYPE_NOT_FOUND",
+            format!("workflow type not registered: {}", workflow_type),
+        ),
+    }
+}
+
+/// 工作流路由，挂载于 `/api/v1` 下 / Workflow routes, nested under `/api/v1`
+pub(crate) fn routes(api: WorkflowApi) -> Router {
+    Router::new()
+        .route("/workflows", post(start_workflow).get(list_workflows))
+        .route("/workflows/{id}", get(describe_workflow))
+        .route("/workflows/{id}/history", get(workflow_history))
+        .route("/workflows/{id}/history/stream", get(stream_workflow_history))
+        .route("/workflows/{id}/history/export", get(export_history))
+        .route("/workflows/{id}/history/import", post(import_history))
+        .route("/workflows/{id}/signal/{name}", post(signal_workflow))
+        .route("/workflows/{id}/query/{name}", post(query_workflow))
+        .route("/workflows/{id}/update/{name}", post(update_workflow))
+        .route("/workflows/{id}/cancel", post(cancel_workflow))
+        .route("/workflows/{id}/diagram", get(workflow_diagram))
+        .with_state(api)
+}
+
+/// 在注册表中登记工作流路由 / Add the workflow routes to a registry
+pub fn register_routes(registry: RouteRegistry) -> RouteRegistry {
+    registry
+        .route(Method::POST, "/api/v1/workflows")
+        .route(Method::GET, "/api/v1/workflows")
+        .route(Method::GET, "/api/v1/workflows/{id}")
+        .route(Method::GET, "/api/v1/workflows/{id}/history")
+        .route(Method::GET, "/api/v1/workflows/{id}/history/stream")
+        .route(Method::GET, "/api/v1/workflows/{id}/history/export")
+        .route(Method::POST, "/api/v1/workflows/{id}/history/import")
+        .route(Method::POST, "/api/v1/workflows/{id}/signal/{name}")
+        .route(Method::POST, "/api/v1/workflows/{id}/query/{name}")
+        .route(Method::POST, "/api/v1/workflows/{id}/update/{name}")
+        .route(Method::POST, "/api/v1/workflows/{id}/cancel")
+        .route(Method::GET, "/api/v1/workflows/{id}/diagram")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::{Signal, Workflow, WorkflowContext, WorkerConfig};
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[derive(Serialize, Deserialize)]
+    struct Proceed(i64);
+
+    impl Signal for Proceed {
+        fn name() -> &'static str {
+            "proceed"
+        }
+    }
+
+    /// 等待信号并将其值加到输入上 / Waits for a signal and adds its value to the input
+    struct AddOnSignal;
+
+    impl Workflow for AddOnSignal {
+        type Input = i64;
+        type Output = i64;
+
+        fn name() -> &'static str {
+            "add_on_signal"
+        }
+
+        async fn execute(ctx: WorkflowContext, input: i64) -> Result<i64, WorkflowError> {
+            let Proceed(value) = ctx.wait_for_signal::<Proceed>(None).await?;
+            Ok(input + value)
+        }
+    }
+
+    async fn call(app: &Router, method: Method, uri: &str, body: Option<serde_json::Value>) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder().method(method).uri(uri);
+        let request = match body {
+            Some(body) => request
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        };
+        let response = app.clone().oneshot(request.unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    async fn wait_for_status(app: &Router, uri: &str, status: &str) -> serde_json::Value {
+        for _ in 0..250 {
+            let (_, body) = call(app, Method::GET, uri, None).await;
+            if body["status"] == status {
+                return body;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("{uri} never reached status {status}");
+    }
+
+    #[tokio::test]
+    async fn test_workflow_lifecycle_over_http() {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..Default::default()
+        }));
+        worker.register_workflow::<AddOnSignal>();
+        let app = super::super::build_router_with_workflows(WorkflowApi::from_worker(&worker));
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let (status, body) = call(&app, Method::POST, "/api/v1/workflows", Some(serde_json::json!({"workflow_type": "missing"}))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "WORKFLOW_TYPE_NOT_FOUND");
+
+        let start = serde_json::json!({
+            "workflow_type": "add_on_signal",
+            "workflow_id": "sum",
+            "input": 40,
+            "memo": {"customer": "Acme", "attempt": 1}
+        });
+        let (status, body) = call(&app, Method::POST, "/api/v1/workflows", Some(start.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["workflow_id"], "sum");
+        let (status, _) = call(&app, Method::POST, "/api/v1/workflows", Some(start)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        wait_for_status(&app, "/api/v1/workflows/sum", "Running").await;
+        let (status, _) = call(&app, Method::POST, "/api/v1/workflows/sum/signal/proceed", Some(serde_json::json!(2))).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let body = wait_for_status(&app, "/api/v1/workflows/sum", "Completed").await;
+        assert_eq!(body["result"], 42);
+        assert_eq!(body["workflow_type"], "add_on_signal");
+        assert_eq!(body["memo"], serde_json::json!({"customer": "Acme", "attempt": 1}));
+        let restart = serde_json::json!({"workflow_type": "add_on_signal", "workflow_id": "sum", "id_reuse_policy": "AllowDuplicateFailedOnly"});
+        let (status, _) = call(&app, Method::POST, "/api/v1/workflows", Some(restart)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, body) = call(&app, Method::GET, "/api/v1/workflows/sum/history", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let events = body["events"].as_array().unwrap().len();
+        assert!(events >= 3);
+        assert!(body["next_page_token"].is_null());
+        let (_, page) = call(&app, Method::GET, "/api/v1/workflows/sum/history?limit=1", None).await;
+        assert_eq!(page["events"][0]["event_id"], 1);
+        let token = page["next_page_token"].as_str().unwrap().to_string();
+        let (_, page) = call(&app, Method::GET, &format!("/api/v1/workflows/sum/history?page_token={}&limit=1", token), None).await;
+        assert_eq!(page["events"][0]["event_id"], 2);
+        let (_, page) = call(&app, Method::GET, &format!("/api/v1/workflows/sum/history?page_token={}", token), None).await;
+        assert_eq!((page["events"].as_array().unwrap().len(), &page["next_page_token"]), (events - 1, &serde_json::Value::Null));
+        // 令牌只对签发它的工作流有效，且不能被改写 / tokens only work for the workflow they were issued for, unaltered
+        let (status, rejected) = call(&app, Method::GET, &format!("/api/v1/workflows/other/history?page_token={}", token), None).await;
+        assert_eq!((status, rejected["code"].as_str()), (StatusCode::BAD_REQUEST, Some("INVALID_PAGE_TOKEN")));
+        let (payload, signature) = token.split_once('.').unwrap();
+        let tampered = format!("{}.{}", payload, signature.chars().rev().collect::<String>());
+        let (status, _) = call(&app, Method::GET, &format!("/api/v1/workflows/sum/history?page_token={}", tampered), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = call(&app, Method::POST, "/api/v1/workflows/sum/signal/proceed", Some(serde_json::json!(1))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, _) = call(&app, Method::POST, "/api/v1/workflows/sum/query/total", None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, _) = call(&app, Method::POST, "/api/v1/workflows/missing/query/total", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_, listed) = call(&app, Method::GET, "/api/v1/workflows?status=Completed", None).await;
+        assert_eq!(listed["workflows"][0]["execution"]["workflow_id"], "sum");
+        assert_eq!(listed["workflows"][0]["memo"]["customer"], "Acme");
+        let (_, listed) = call(&app, Method::GET, "/api/v1/workflows?status=Running", None).await;
+        assert_eq!(listed["workflows"], serde_json::json!([]));
+        let later = serde_json::json!({"workflow_type": "add_on_signal", "workflow_id": "later", "input": 1});
+        assert_eq!(call(&app, Method::POST, "/api/v1/workflows", Some(later)).await.0, StatusCode::CREATED);
+        let (_, listed) = call(&app, Method::GET, "/api/v1/workflows?limit=1", None).await;
+        assert_eq!(listed["workflows"][0]["execution"]["workflow_id"], "later");
+        let token = listed["next_page_token"].as_str().unwrap().to_string();
+        let (_, listed) = call(&app, Method::GET, &format!("/api/v1/workflows?limit=1&page_token={}", token), None).await;
+        assert_eq!(listed["workflows"][0]["execution"]["workflow_id"], "sum");
+        assert!(listed["next_page_token"].is_null());
+        // 令牌绑定签发时的过滤条件 / tokens are bound to the filter they were issued for
+        let (status, rejected) = call(&app, Method::GET, &format!("/api/v1/workflows?status=Completed&page_token={}", token), None).await;
+        assert_eq!((status, rejected["message"].as_str().unwrap().contains("different filter")), (StatusCode::BAD_REQUEST, true));
+        // 已关闭运行的事件流在 `closed` 后结束 / the stream of a closed run ends after `closed`
+        let request = Request::get("/api/v1/workflows/sum/history/stream").header("last-event-id", "1").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+        let stream = String::from_utf8(to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+        assert_eq!(stream.matches("event: history").count(), events - 1);
+        assert!(stream.contains("id: 2\n") && stream.trim_end().ends_with(&format!("data: {}", body["execution"]["run_id"].as_str().unwrap())));
+
+        let start = serde_json::json!({"workflow_type": "add_on_signal", "workflow_id": "abandoned", "input": 1});
+        call(&app, Method::POST, "/api/v1/workflows", Some(start)).await;
+        let (status, _) = call(&app, Method::POST, "/api/v1/workflows/abandoned/cancel", None).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let body = wait_for_status(&app, "/api/v1/workflows/abandoned", "Failed").await;
+        assert_eq!(body["failure"], "Workflow cancelled");
+
+        let (status, _) = call(&app, Method::GET, "/api/v1/workflows/unknown", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call(&app, Method::POST, "/api/v1/workflows/unknown/cancel", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+    #[tokio::test]
+    async fn test_workflow_diagrams() {
+        let worker = WorkflowWorker::default();
+        worker.register_workflow::<AddOnSignal>();
+        let pipeline = "name: pipeline\nsteps:\n  - activity: { name: charge }\n  - sleep: 1h\n";
+        worker.register_dynamic_workflow(crate::dsl::DynamicWorkflow::from_yaml(pipeline).unwrap());
+        let app = super::super::build_router_with_workflows(WorkflowApi::from_worker(&worker));
+
+        let fetch = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+                let content_type = response.headers()[header::CONTENT_TYPE].to_str().unwrap().to_string();
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (content_type, String::from_utf8(body.to_vec()).unwrap())
+            }
+        };
+        let (content_type, mermaid) = fetch("/api/v1/workflows/pipeline/diagram").await;
+        assert!(content_type.starts_with("text/plain"));
+        assert!(mermaid.starts_with("flowchart TD\n") && mermaid.contains("[\"charge\"]"), "{}", mermaid);
+        let (content_type, dot) = fetch("/api/v1/workflows/pipeline/diagram?format=dot").await;
+        assert!(content_type.starts_with("text/vnd.graphviz"));
+        assert!(dot.contains("[label=\"sleep 1h\""), "{}", dot);
+
+        let (status, body) = call(&app, Method::GET, "/api/v1/workflows/add_on_signal/diagram", None).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("OUTLINE_NOT_FOUND")));
+        let (status, body) = call(&app, Method::GET, "/api/v1/workflows/missing/diagram", None).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("WORKFLOW_TYPE_NOT_FOUND")));
+        let (status, _) = call(&app, Method::GET, "/api/v1/workflows/pipeline/diagram?format=svg", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_history_export_and_import_over_http() {
+        let source = WorkflowWorker::default();
+        source.register_workflow::<AddOnSignal>();
+        let source_app = super::super::build_router_with_workflows(WorkflowApi::from_worker(&source));
+        let start = serde_json::json!({"workflow_type": "add_on_signal", "workflow_id": "moved", "input": 1});
+        let (status, _) = call(&source_app, Method::POST, "/api/v1/workflows", Some(start)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let export = Request::get("/api/v1/workflows/moved/history/export?format=protobuf").body(Body::empty());
+        let response = source_app.clone().oneshot(export.unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-protobuf");
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        let target = WorkflowWorker::default();
+        let target_app = super::super::build_router_with_workflows(WorkflowApi::from_worker(&target));
+        let import = |uri: &str, content_type: &str| {
+            let request = Request::post(uri).header("content-type", content_type).body(Body::from(bytes.clone()));
+            target_app.clone().oneshot(request.unwrap())
+        };
+        let response = import("/api/v1/workflows/moved/history/import", "text/plain").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let response = import("/api/v1/workflows/other/history/import", "application/x-protobuf").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = import("/api/v1/workflows/moved/history/import", "application/x-protobuf").await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let (status, body) = call(&target_app, Method::GET, "/api/v1/workflows/moved/history/export", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["execution"]["workflow_id"], "moved");
+        assert_eq!(body["events"].as_array().unwrap().len(), 1);
+        let (status, _) = call(&target_app, Method::GET, "/api/v1/workflows/missing/history/export", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_operations_are_audited_with_principal() {
+        let worker = WorkflowWorker::default();
+        worker.register_workflow::<AddOnSignal>();
+        let audit = AuditLog::default();
+        let api = WorkflowApi::from_worker(&worker).with_audit(audit.clone());
+        let auth = super::super::auth::Authenticator::new().api_key("k", "ops");
+        let app = super::super::build_router_with_auth(Some(api), auth);
+        let call = |method: Method, uri: &str, body: Option<serde_json::Value>| {
+            let request = Request::builder().method(method).uri(uri).header("x-api-key", "k");
+            let request = match body {
+                Some(body) => request.header("content-type", "application/json").body(Body::from(body.to_string())),
+                None => request.body(Body::empty()),
+            };
+            app.clone().oneshot(request.unwrap())
+        };
+
+        let start = serde_json::json!({"workflow_type": "add_on_signal", "workflow_id": "audited", "input": 1});
+        assert_eq!(call(Method::POST, "/api/v1/workflows", Some(start)).await.unwrap().status(), StatusCode::CREATED);
+        let signal = call(Method::POST, "/api/v1/workflows/audited/signal/proceed", Some(serde_json::json!(2)));
+        assert_eq!(signal.await.unwrap().status(), StatusCode::ACCEPTED);
+        let cancel = call(Method::POST, "/api/v1/workflows/unknown/cancel", None);
+        assert_eq!(cancel.await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        let response = call(Method::GET, "/api/v1/audit?limit=1", None).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(page["entries"][0]["action"], "start");
+        let token = page["next_page_token"].as_str().unwrap().to_string();
+        let response = call(Method::GET, &format!("/api/v1/audit?limit=1&page_token={}", token), None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let page: serde_json::Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        let entry = &page["entries"][0];
+        assert_eq!(entry["action"], "signal");
+        assert_eq!(entry["principal"], "ops");
+        assert_eq!(entry["target"], "proceed");
+        assert_eq!(entry["payload_digest"], crate::audit::digest(&serde_json::json!(2)));
+        assert!(page["next_page_token"].is_string());
+        let response = call(Method::GET, "/api/v1/audit?page_token=forged.token", None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let entries = audit.page(None, 10).await.unwrap().entries;
+        assert_eq!(entries.len(), 3);
+        assert_eq!((entries[0].action, entries[0].workflow_id.as_str()), (AuditAction::Start, "audited"));
+        assert_eq!((entries[2].action, entries[2].succeeded), (AuditAction::Cancel, false));
+    }
+}
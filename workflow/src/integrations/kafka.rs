@@ -0,0 +1,475 @@
+//! # Kafka 集成 / Kafka Integration
+//!
+//! [`KafkaTrigger`] 消费配置的主题，把每条消息映射为工作流启动或信号：消息键即工作流 ID，消息体由
+//! [`DataConverter`] 解码为输入。[`KafkaEventPublisher`] 把 [`EventBus`](crate::patterns::EventBus) 上的生命周期事件发布到主题，以工作流 ID
+//! 为分区键，因此同一工作流的事件保持有序。[`KafkaOutboxPublisher`] 供 [`OutboxRelay`](crate::temporal::OutboxRelay)
+//! 发布发件箱消息。
+//! [`KafkaTrigger`] consumes the configured topics and maps every message to a workflow start or signal: the
+//! message key is the workflow ID and the payload is decoded into the input by a [`DataConverter`].
+//! [`KafkaEventPublisher`] publishes the lifecycle events of an [`EventBus`](crate::patterns::EventBus) to a topic, keyed by workflow ID, so
+//! the events of one workflow stay in order within their partition. [`KafkaOutboxPublisher`] publishes outbox messages
+//! for an [`OutboxRelay`](crate::temporal::OutboxRelay).
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use workflow::integrations::kafka::{KafkaEventPublisher, KafkaTrigger};
+//! # use workflow::patterns::EventBus;
+//! # use workflow::temporal::{WorkerConfig, WorkflowWorker};
+//! # async fn run() -> Result<(), workflow::temporal::WorkflowError> {
+//! let publisher = Arc::new(KafkaEventPublisher::new("localhost:9092", "workflow-events")?);
+//! let bus = publisher.subscribe(EventBus::builder()).build();
+//! let worker = Arc::new(WorkflowWorker::new(WorkerConfig::default()).with_event_bus(Arc::new(bus)));
+//!
+//! let trigger = KafkaTrigger::builder("localhost:9092", "order-triggers")
+//!     .start_on("orders.created", "fulfil_order")
+//!     .signal_on("payments.settled", "payment_settled")
+//!     .build(worker.client())?;
+//! trigger.run().await
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rdkafka::ClientConfig;
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::message::{Header, Message, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::patterns::{EngineEvent, EventBusBuilder};
+use crate::temporal::{
+    DataConverter, JsonConverter, OutboxDestination, OutboxMessage, OutboxPublisher, StartWorkflowOptions,
+    WorkflowClient, WorkflowError, WorkflowId,
+};
+
+/// 出错后重新接收前的等待 / Wait before receiving again after an error
+const RECEIVE_BACKOFF: Duration = Duration::from_secs(1);
+
+/// 发送的默认超时 / Default timeout of a send
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 记录编码的消息头 / Message header naming the encoding
+pub const ENCODING_HEADER: &str = "encoding";
+
+/// 记录事件种类的消息头 / Message header naming the event
+pub const EVENT_HEADER: &str = "event";
+
+/// 记录发件箱消息 ID 的消息头 / Message header carrying the outbox message ID
+pub const OUTBOX_ID_HEADER: &str = "outbox-id";
+
+fn kafka_error(e: rdkafka::error::KafkaError) -> WorkflowError {
+    WorkflowError::Custom(format!("kafka: {}", e))
+}
+
+/// 消息触发的操作 / Action a message triggers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TriggerAction {
+    /// 启动工作流，消息键（若有）为工作流 ID / Start a workflow, with the message key, if any, as workflow ID
+    Start { workflow_type: String, task_queue: String },
+    /// 向消息键指定的工作流发送信号 / Signal the workflow named by the message key
+    Signal { signal_name: String },
+}
+
+/// 把消息分发为启动或信号 / Dispatches messages as starts or signals
+struct Dispatcher {
+    client: WorkflowClient,
+    routes: HashMap<String, TriggerAction>,
+    converter: Arc<dyn DataConverter>,
+}
+
+impl Dispatcher {
+    async fn dispatch(&self, topic: &str, key: Option<&[u8]>, payload: Option<&[u8]>) -> Result<(), WorkflowError> {
+        let action = self
+            .routes
+            .get(topic)
+            .ok_or_else(|| WorkflowError::InvalidInput(format!("no route for topic {}", topic)))?;
+        let key = key
+            .map(|key| String::from_utf8(key.to_vec()))
+            .transpose()
+            .map_err(|_| WorkflowError::InvalidInput("message key is not UTF-8".to_string()))?;
+        let input = match payload {
+            Some(payload) if !payload.is_empty() => self.converter.decode_value(payload)?,
+            _ => Value::Null,
+        };
+        match action {
+            TriggerAction::Start { workflow_type, task_queue } => {
+                let options = StartWorkflowOptions {
+                    workflow_id: key.map(WorkflowId::new),
+                    task_queue: task_queue.clone(),
+                    ..StartWorkflowOptions::default()
+                };
+                match self.client.start_workflow_value(workflow_type, input, &options).await {
+                    // 重投的消息 / A redelivered message
+                    Err(WorkflowError::AlreadyStarted(id)) => {
+                        tracing::debug!(topic, workflow_id = %id, "workflow already started");
+                        Ok(())
+                    }
+                    result => result.map(|_| ()),
+                }
+            }
+            TriggerAction::Signal { signal_name } => {
+                let key = key.ok_or_else(|| WorkflowError::InvalidInput("signal messages need a key".to_string()))?;
+                self.client
+                    .signal_workflow_value(&WorkflowId::new(key), signal_name, input)
+                    .await
+                    .map_err(|e| WorkflowError::Custom(e.to_string()))
+            }
+        }
+    }
+}
+
+/// [`KafkaTrigger`] 构建器 / Builder of a [`KafkaTrigger`]
+pub struct KafkaTriggerBuilder {
+    config: ClientConfig,
+    routes: HashMap<String, TriggerAction>,
+    converter: Arc<dyn DataConverter>,
+}
+
+impl KafkaTriggerBuilder {
+    /// 设置 librdkafka 配置项 / Set a librdkafka configuration property
+    pub fn set(mut self, key: &str, value: &str) -> Self {
+        self.config.set(key, value);
+        self
+    }
+
+    /// 主题上的消息执行 `action` / Messages on `topic` perform `action`
+    pub fn route(mut self, topic: impl Into<String>, action: TriggerAction) -> Self {
+        self.routes.insert(topic.into(), action);
+        self
+    }
+
+    /// 主题上的消息在默认任务队列启动工作流 / Messages on `topic` start a workflow on the default task queue
+    pub fn start_on(self, topic: impl Into<String>, workflow_type: impl Into<String>) -> Self {
+        let action = TriggerAction::Start {
+            workflow_type: workflow_type.into(),
+            task_queue: StartWorkflowOptions::default().task_queue,
+        };
+        self.route(topic, action)
+    }
+
+    /// 主题上的消息向工作流发送信号 / Messages on `topic` signal a workflow
+    pub fn signal_on(self, topic: impl Into<String>, signal_name: impl Into<String>) -> Self {
+        let action = TriggerAction::Signal {
+            signal_name: signal_name.into(),
+        };
+        self.route(topic, action)
+    }
+
+    /// 消息体的解码器，缺省为 JSON / Decoder of the payloads, JSON by default
+    pub fn converter(mut self, converter: Arc<dyn DataConverter>) -> Self {
+        self.converter = converter;
+        self
+    }
+
+    /// 创建消费者并订阅路由的主题 / Create the consumer and subscribe to the routed topics
+    pub fn build(self, client: WorkflowClient) -> Result<KafkaTrigger, WorkflowError> {
+        let consumer: StreamConsumer = self.config.create().map_err(kafka_error)?;
+        let topics: Vec<&str> = self.routes.keys().map(String::as_str).collect();
+        consumer.subscribe(&topics).map_err(kafka_error)?;
+        Ok(KafkaTrigger {
+            consumer,
+            dispatcher: Dispatcher {
+                client,
+                routes: self.routes,
+                converter: self.converter,
+            },
+            shutdown: watch::channel(false).0,
+        })
+    }
+}
+
+/// 由 Kafka 消息触发工作流 / Triggers workflows from Kafka messages
+///
+/// 偏移在消息处理后提交，崩溃后未处理的消息会重投；带键的启动消息重投时不会重复启动。无法处理的消息记录日志后跳过。
+/// Offsets are committed once a message was handled, so messages are redelivered after a crash; redelivered start
+/// messages with a key do not start the workflow twice. Messages that cannot be handled are logged and skipped.
+pub struct KafkaTrigger {
+    consumer: StreamConsumer,
+    dispatcher: Dispatcher,
+    shutdown: watch::Sender<bool>,
+}
+
+impl KafkaTrigger {
+    /// 连接 `brokers` 并加入消费组 `group_id` / Connect to `brokers` and join consumer group `group_id`
+    pub fn builder(brokers: &str, group_id: &str) -> KafkaTriggerBuilder {
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", brokers)
+            .set("group.id", group_id)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest");
+        KafkaTriggerBuilder {
+            config,
+            routes: HashMap::new(),
+            converter: Arc::new(JsonConverter),
+        }
+    }
+
+    /// 消费消息直到请求关闭 / Consume messages until shutdown is requested
+    pub async fn run(&self) -> Result<(), WorkflowError> {
+        let mut shutdown = self.shutdown.subscribe();
+        loop {
+            let received = tokio::select! {
+                _ = shutdown.wait_for(|stop| *stop) => return Ok(()),
+                received = self.consumer.recv() => received,
+            };
+            let message = match received {
+                Ok(message) => message,
+                Err(e) => {
+                    tracing::warn!(error = %e, "kafka receive failed");
+                    tokio::time::sleep(RECEIVE_BACKOFF).await;
+                    continue;
+                }
+            };
+            if let Err(e) = self.dispatcher.dispatch(message.topic(), message.key(), message.payload()).await {
+                tracing::warn!(
+                    topic = message.topic(),
+                    partition = message.partition(),
+                    offset = message.offset(),
+                    error = %e,
+                    "kafka message skipped"
+                );
+            }
+            if let Err(e) = self.consumer.commit_message(&message, CommitMode::Async) {
+                tracing::warn!(error = %e, "kafka offset commit failed");
+            }
+        }
+    }
+
+    /// 请求 [`run`](Self::run) 返回 / Ask [`run`](Self::run) to return
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+}
+
+/// 把引擎事件发布到 Kafka 主题 / Publishes engine events to a Kafka topic
+///
+/// 消息键为工作流 ID，消息头 `encoding` 与 `event` 给出编码与事件种类。
+/// Messages are keyed by workflow ID; the `encoding` and `event` headers name the encoding and the event.
+pub struct KafkaEventPublisher {
+    producer: FutureProducer,
+    topic: String,
+    converter: Arc<dyn DataConverter>,
+    timeout: Duration,
+}
+
+impl KafkaEventPublisher {
+    pub fn new(brokers: &str, topic: impl Into<String>) -> Result<Self, WorkflowError> {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", brokers);
+        Self::with_config(&config, topic)
+    }
+
+    /// 使用完整的 librdkafka 配置 / Use a full librdkafka configuration
+    pub fn with_config(config: &ClientConfig, topic: impl Into<String>) -> Result<Self, WorkflowError> {
+        Ok(Self {
+            producer: config.create().map_err(kafka_error)?,
+            topic: topic.into(),
+            converter: Arc::new(JsonConverter),
+            timeout: SEND_TIMEOUT,
+        })
+    }
+
+    /// 事件的编码器，缺省为 JSON / Encoder of the events, JSON by default
+    pub fn with_data_converter(mut self, converter: Arc<dyn DataConverter>) -> Self {
+        self.converter = converter;
+        self
+    }
+
+    /// 等待发送进入队列的最长时间 / How long a send may wait for room in the producer queue
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn publish(&self, event: &EngineEvent) -> Result<(), WorkflowError> {
+        let record = EventRecord::encode(event, &*self.converter)?;
+        let headers = OwnedHeaders::new()
+            .insert(Header {
+                key: ENCODING_HEADER,
+                value: Some(self.converter.encoding()),
+            })
+            .insert(Header {
+                key: EVENT_HEADER,
+                value: Some(record.event.as_str()),
+            });
+        let message = FutureRecord::to(&self.topic)
+            .key(&record.key)
+            .payload(&record.payload)
+            .headers(headers);
+        self.producer
+            .send(message, self.timeout)
+            .await
+            .map(|_| ())
+            .map_err(|(e, _)| kafka_error(e))
+    }
+
+    /// 订阅总线上的全部事件；发布失败记录日志 / Subscribe to every event of the bus; failed publications are logged
+    pub fn subscribe(self: Arc<Self>, bus: EventBusBuilder) -> EventBusBuilder {
+        bus.subscribe(move |event| {
+            let publisher = self.clone();
+            async move {
+                if let Err(e) = publisher.publish(&event).await {
+                    tracing::warn!(execution = %event.execution(), error = %e, "engine event not published to kafka");
+                }
+            }
+        })
+    }
+}
+
+/// 把 [`OutboxDestination::Kafka`] 消息发布到其主题 / Publishes [`OutboxDestination::Kafka`] messages to their topic
+///
+/// 消息键缺省为发件箱消息 ID，消费者可据此与 [`OUTBOX_ID_HEADER`] 去重。
+/// The message key defaults to the outbox message ID, which consumers may deduplicate on along with
+/// [`OUTBOX_ID_HEADER`].
+pub struct KafkaOutboxPublisher {
+    producer: FutureProducer,
+    converter: Arc<dyn DataConverter>,
+    timeout: Duration,
+}
+
+impl KafkaOutboxPublisher {
+    pub fn new(brokers: &str) -> Result<Self, WorkflowError> {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", brokers);
+        Self::with_config(&config)
+    }
+
+    /// 使用完整的 librdkafka 配置 / Use a full librdkafka configuration
+    pub fn with_config(config: &ClientConfig) -> Result<Self, WorkflowError> {
+        Ok(Self {
+            producer: config.create().map_err(kafka_error)?,
+            converter: Arc::new(JsonConverter),
+            timeout: SEND_TIMEOUT,
+        })
+    }
+
+    /// 消息体的编码器，缺省为 JSON / Encoder of the payloads, JSON by default
+    pub fn with_data_converter(mut self, converter: Arc<dyn DataConverter>) -> Self {
+        self.converter = converter;
+        self
+    }
+
+    /// 等待发送进入队列的最长时间 / How long a send may wait for room in the producer queue
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl OutboxPublisher for KafkaOutboxPublisher {
+    async fn publish(&self, message: &OutboxMessage) -> Result<(), WorkflowError> {
+        let OutboxDestination::Kafka { topic, key } = &message.destination else {
+            return Err(WorkflowError::Custom(format!("not a kafka message: {}", message.id)));
+        };
+        let payload = self.converter.encode_value(&message.payload)?;
+        let headers = OwnedHeaders::new()
+            .insert(Header {
+                key: ENCODING_HEADER,
+                value: Some(self.converter.encoding()),
+            })
+            .insert(Header {
+                key: OUTBOX_ID_HEADER,
+                value: Some(message.id.as_str()),
+            });
+        let record = FutureRecord::to(topic)
+            .key(key.as_deref().unwrap_or(message.id.as_str()))
+            .payload(&payload)
+            .headers(headers);
+        self.producer
+            .send(record, self.timeout)
+            .await
+            .map(|_| ())
+            .map_err(|(e, _)| kafka_error(e))
+    }
+}
+
+/// 编码后的事件消息 / Encoded event message
+struct EventRecord {
+    key: String,
+    event: String,
+    payload: Vec<u8>,
+}
+
+impl EventRecord {
+    fn encode(event: &EngineEvent, converter: &dyn DataConverter) -> Result<Self, WorkflowError> {
+        let value = serde_json::to_value(event)?;
+        Ok(Self {
+            key: event.execution().workflow_id.to_string(),
+            event: value["event"].as_str().unwrap_or_default().to_string(),
+            payload: converter.encode_value(&value)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::temporal::event::EventType;
+    use crate::temporal::{InMemoryStorage, InMemoryTaskQueue, MessagePackConverter, WorkflowExecution};
+
+    fn dispatcher(converter: Arc<dyn DataConverter>) -> Dispatcher {
+        let routes = HashMap::from([
+            (
+                "orders.created".to_string(),
+                TriggerAction::Start {
+                    workflow_type: "fulfil_order".to_string(),
+                    task_queue: "orders".to_string(),
+                },
+            ),
+            (
+                "payments.settled".to_string(),
+                TriggerAction::Signal {
+                    signal_name: "payment_settled".to_string(),
+                },
+            ),
+        ]);
+        let client = WorkflowClient::new(Arc::new(InMemoryTaskQueue::new()), Arc::new(InMemoryStorage::new()));
+        Dispatcher { client, routes, converter }
+    }
+
+    #[tokio::test]
+    async fn test_messages_start_and_signal_workflows() {
+        let converter: Arc<dyn DataConverter> = Arc::new(MessagePackConverter);
+        let dispatcher = dispatcher(converter.clone());
+        let order = converter.encode_value(&json!({"order": 7})).unwrap();
+
+        dispatcher.dispatch("orders.created", Some(b"order-7"), Some(&order)).await.unwrap();
+        // 重投不会再次启动 / A redelivery does not start it again
+        dispatcher.dispatch("orders.created", Some(b"order-7"), Some(&order)).await.unwrap();
+        let settled = converter.encode_value(&json!({"amount": 12})).unwrap();
+        dispatcher.dispatch("payments.settled", Some(b"order-7"), Some(&settled)).await.unwrap();
+
+        let (_, history) = dispatcher.client.load_workflow(&WorkflowId::new("order-7")).await.unwrap().unwrap();
+        assert_eq!(history.task_queue(), Some("orders"));
+        assert!(matches!(
+            &history.events()[0].event_type,
+            EventType::WorkflowExecutionStarted { workflow_type, input, .. } if workflow_type == "fulfil_order" && *input == json!({"order": 7})
+        ));
+
+        assert!(dispatcher.dispatch("payments.settled", None, Some(&settled)).await.is_err());
+        assert!(dispatcher.dispatch("payments.settled", Some(b"order-8"), None).await.is_err());
+        assert!(dispatcher.dispatch("unrouted", Some(b"order-7"), None).await.is_err());
+        assert!(dispatcher.dispatch("orders.created", None, Some(b"\xc1")).await.is_err());
+    }
+
+    #[test]
+    fn test_events_are_keyed_by_workflow_id() {
+        let event = EngineEvent::WorkflowFailed {
+            execution: WorkflowExecution::new(WorkflowId::new("order-7")),
+            workflow_type: "fulfil_order".to_string(),
+            failure: "out of stock".to_string(),
+        };
+        let record = EventRecord::encode(&event, &MessagePackConverter).unwrap();
+        assert_eq!((record.key.as_str(), record.event.as_str()), ("order-7", "workflow_failed"));
+        let decoded = MessagePackConverter.decode_value(&record.payload).unwrap();
+        assert_eq!(decoded["failure"], "out of stock");
+    }
+}
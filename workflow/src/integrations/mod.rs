@@ -0,0 +1,16 @@
+//! # 消息系统集成 / Messaging Integrations
+//!
+//! 把外部消息系统接入引擎：消息触发工作流启动或信号，引擎生命周期事件发布为消息，任务在进程间传递。每个集成由
+//! 各自的特性开启。
+//! Connects external messaging systems to the engine: messages trigger workflow starts or signals, engine
+//! lifecycle events are published as messages and tasks travel between processes. Each integration is enabled by
+//! its own feature.
+//!
+//! - `kafka`：Kafka 主题 / Kafka topics
+//! - `nats`：NATS JetStream 上的任务队列与查询 / Task queues and queries over NATS JetStream
+
+#[cfg(feature = "kafka")]
+pub mod kafka;
+
+#[cfg(feature = "nats")]
+pub mod nats;
@@ -0,0 +1,535 @@
+//! # NATS JetStream 传输 / NATS JetStream Transport
+//!
+//! 让一个进程中的 [`WorkflowClient`] 把任务交给其他进程中的 [`WorkflowWorker`]：每个任务队列的每条通道是 JetStream
+//! 流上的一个主题 `{prefix}.tasks.{queue}.{kind}`，由持久拉取消费者读取。任务在工作者完成后才确认，执行期间每隔
+//! 三分之一的确认期限报告一次进度；工作者停止报告（例如崩溃）的任务在确认期限后重投给其他工作者。查询通过
+//! `{prefix}.query` 上的请求-应答送达正在运行该执行的工作者。
+//! Lets a [`WorkflowClient`] in one process hand tasks to [`WorkflowWorker`]s in other processes: every lane of a
+//! task queue is a subject `{prefix}.tasks.{queue}.{kind}` on a JetStream stream, read by a durable pull consumer.
+//! Tasks are acknowledged once their worker completed them and report progress every third of the ack wait while
+//! they run; tasks whose worker stops reporting, for example because it crashed, are redelivered to another worker
+//! after the ack wait. Queries reach the worker running the execution through request-reply on `{prefix}.query`.
+//!
+//! 执行历史不经由 NATS，客户端与工作者须共享存储。
+//! Histories do not travel over NATS: clients and workers must share their storage.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use workflow::integrations::nats::NatsTransport;
+//! # use workflow::temporal::{InMemoryStorage, WorkerConfig, WorkflowWorker, WorkflowStorage};
+//! # async fn run(storage: Arc<dyn WorkflowStorage>) -> Result<(), workflow::temporal::WorkflowError> {
+//! let transport = NatsTransport::connect("nats://localhost:4222").await?;
+//!
+//! // 工作者进程 / Worker process
+//! let worker = Arc::new(
+//!     WorkflowWorker::new(WorkerConfig::default())
+//!         .with_task_queue(transport.task_queue())
+//!         .with_storage(storage.clone()),
+//! );
+//! let _queries = transport.serve_queries(&worker).await?;
+//!
+//! // 客户端进程 / Client process
+//! let client = transport.client(storage);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_nats::jetstream::{self, consumer, stream};
+use async_trait::async_trait;
+use futures::StreamExt;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::temporal::dead_letter::task_key;
+use crate::temporal::error::{QueryError, StorageError};
+use crate::temporal::query::QueryDispatcher;
+use crate::temporal::task_queue::{Task, TaskKind, TaskQueue};
+use crate::temporal::{WorkflowClient, WorkflowError, WorkflowId, WorkflowStorage, WorkflowWorker};
+
+const DEFAULT_PREFIX: &str = "workflow";
+const DEFAULT_STREAM: &str = "WORKFLOW_TASKS";
+const DEFAULT_ACK_WAIT: Duration = Duration::from_secs(60);
+const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 服务器接受的最短拉取期限 / Shortest pull expiry the server accepts
+const MIN_EXPIRES: Duration = Duration::from_millis(10);
+
+/// 每个确认期限内报告进度的次数，丢失一次仍不重投 / Progress reports per ack wait, so one lost report does not
+/// cause a redelivery
+const PROGRESS_REPORTS_PER_ACK_WAIT: u32 = 3;
+
+fn connection_error(e: impl std::fmt::Display) -> StorageError {
+    StorageError::ConnectionError(e.to_string())
+}
+
+fn query_error(e: impl std::fmt::Display) -> StorageError {
+    StorageError::QueryError(e.to_string())
+}
+
+/// 主题与消费者名中只保留字母、数字、`-` 与 `_` / Keep letters, digits, `-` and `_` in subjects and consumer names
+fn token(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// 到 NATS 服务器的连接 / Connection to a NATS server
+#[derive(Clone)]
+pub struct NatsTransport {
+    client: async_nats::Client,
+    jetstream: jetstream::Context,
+    prefix: String,
+    stream: String,
+    ack_wait: Duration,
+    query_timeout: Duration,
+}
+
+impl NatsTransport {
+    /// 连接 `url`，例如 `nats://localhost:4222` / Connect to `url`, e.g. `nats://localhost:4222`
+    pub async fn connect(url: &str) -> Result<Self, WorkflowError> {
+        let client = async_nats::connect(url).await.map_err(connection_error)?;
+        Ok(Self {
+            jetstream: jetstream::new(client.clone()),
+            client,
+            prefix: DEFAULT_PREFIX.to_string(),
+            stream: DEFAULT_STREAM.to_string(),
+            ack_wait: DEFAULT_ACK_WAIT,
+            query_timeout: DEFAULT_QUERY_TIMEOUT,
+        })
+    }
+
+    /// 主题前缀，缺省为 `workflow` / Subject prefix, `workflow` by default
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// 任务流的名称，缺省为 `WORKFLOW_TASKS` / Name of the task stream, `WORKFLOW_TASKS` by default
+    pub fn with_stream(mut self, stream: impl Into<String>) -> Self {
+        self.stream = stream.into();
+        self
+    }
+
+    /// 工作者停止报告进度后任务重投前的时长，缺省一分钟 / How long after its worker stopped reporting progress a
+    /// task is redelivered, one minute by default
+    pub fn with_ack_wait(mut self, ack_wait: Duration) -> Self {
+        self.ack_wait = ack_wait;
+        self
+    }
+
+    /// 等待查询应答的时长，缺省两秒 / How long to wait for a query answer, two seconds by default
+    pub fn with_query_timeout(mut self, query_timeout: Duration) -> Self {
+        self.query_timeout = query_timeout;
+        self
+    }
+
+    /// JetStream 上的任务队列 / Task queue on JetStream
+    pub fn task_queue(&self) -> Arc<JetStreamTaskQueue> {
+        Arc::new(JetStreamTaskQueue {
+            transport: self.clone(),
+            consumers: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 通过本传输派发任务与查询的客户端 / Client dispatching tasks and queries through this transport
+    pub fn client(&self, storage: Arc<dyn WorkflowStorage>) -> WorkflowClient {
+        WorkflowClient::new(self.task_queue(), storage).with_queries(Arc::new(NatsQueries {
+            transport: self.clone(),
+        }))
+    }
+
+    /// 回答 `worker` 正在运行的执行的查询，直到返回的任务被中止 / Answer queries against the executions `worker`
+    /// is running, until the returned task is aborted
+    pub async fn serve_queries(&self, worker: &WorkflowWorker) -> Result<tokio::task::JoinHandle<()>, WorkflowError> {
+        let mut requests = self
+            .client
+            .subscribe(self.query_subject())
+            .await
+            .map_err(connection_error)?;
+        let dispatcher = worker.query_dispatcher();
+        let client = self.client.clone();
+        Ok(tokio::spawn(async move {
+            while let Some(request) = requests.next().await {
+                let Some(reply_to) = request.reply else { continue };
+                if let Some(reply) = answer(&*dispatcher, &request.payload).await
+                    && let Err(e) = client.publish(reply_to, reply.into()).await
+                {
+                    tracing::warn!(error = %e, "query reply not sent");
+                }
+            }
+        }))
+    }
+
+    fn query_subject(&self) -> String {
+        format!("{}.query", self.prefix)
+    }
+
+    fn task_subject(&self, queue: &str, kind: TaskKind) -> String {
+        format!("{}.tasks.{}.{}", self.prefix, token(queue), kind.as_str())
+    }
+}
+
+/// 经由 NATS 的查询请求 / Query request over NATS
+#[derive(Debug, Serialize, Deserialize)]
+struct QueryRequest {
+    workflow_id: WorkflowId,
+    query_name: String,
+}
+
+/// 查询应答 / Query reply
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum QueryReply {
+    Answer(Value),
+    NotRegistered(String),
+    Failed(String),
+}
+
+/// 回答一个查询请求；执行不在此运行时不应答，由运行它的工作者回答 / Answer a query request; executions not
+/// running here get no reply, the worker running them answers
+async fn answer(dispatcher: &dyn QueryDispatcher, request: &[u8]) -> Option<Vec<u8>> {
+    let reply = match serde_json::from_slice::<QueryRequest>(request) {
+        Ok(request) => match dispatcher.query(&request.workflow_id, &request.query_name).await {
+            Ok(value) => QueryReply::Answer(value),
+            Err(QueryError::WorkflowNotRunning) => return None,
+            Err(QueryError::QueryNotRegistered(name)) => QueryReply::NotRegistered(name),
+            Err(e) => QueryReply::Failed(e.to_string()),
+        },
+        Err(e) => QueryReply::Failed(format!("malformed query request: {}", e)),
+    };
+    serde_json::to_vec(&reply).ok()
+}
+
+/// 通过 NATS 请求查询 / Sends queries over NATS
+struct NatsQueries {
+    transport: NatsTransport,
+}
+
+#[async_trait]
+impl QueryDispatcher for NatsQueries {
+    async fn query(&self, workflow_id: &WorkflowId, query_name: &str) -> Result<Value, QueryError> {
+        let request = QueryRequest {
+            workflow_id: workflow_id.clone(),
+            query_name: query_name.to_string(),
+        };
+        let payload = serde_json::to_vec(&request).map_err(|e| QueryError::SerializationError(e.to_string()))?;
+        let sent = self.transport.client.request(self.transport.query_subject(), payload.into());
+        // 无工作者应答即没有工作者在运行该执行 / No answer means no worker is running the execution
+        let message = match tokio::time::timeout(self.transport.query_timeout, sent).await {
+            Ok(Ok(message)) => message,
+            Ok(Err(e)) if e.kind() == async_nats::RequestErrorKind::NoResponders => {
+                return Err(QueryError::WorkflowNotRunning);
+            }
+            Ok(Err(e)) if e.kind() != async_nats::RequestErrorKind::TimedOut => {
+                return Err(QueryError::Custom(e.to_string()));
+            }
+            _ => return Err(QueryError::WorkflowNotRunning),
+        };
+        match serde_json::from_slice(&message.payload).map_err(|e| QueryError::SerializationError(e.to_string()))? {
+            QueryReply::Answer(value) => Ok(value),
+            QueryReply::NotRegistered(name) => Err(QueryError::QueryNotRegistered(name)),
+            QueryReply::Failed(message) => Err(QueryError::Custom(message)),
+        }
+    }
+}
+
+/// JetStream 上的任务队列 / Task queue on JetStream
+///
+/// 流采用工作队列保留策略：任务确认后即被删除。
+/// The stream uses work-queue retention: tasks are deleted once acknowledged.
+pub struct JetStreamTaskQueue {
+    transport: NatsTransport,
+    /// 各通道的持久消费者 / Durable consumer of each lane
+    consumers: Mutex<HashMap<String, consumer::PullConsumer>>,
+    /// 已拉取未完成的任务消息，按主题与任务 / Messages of the polled tasks not completed yet, by subject and task
+    in_flight: Mutex<HashMap<(String, String), VecDeque<InFlight>>>,
+}
+
+/// 已拉取未完成的任务消息 / Message of a polled task not completed yet
+struct InFlight {
+    message: Arc<jetstream::Message>,
+    /// 运行 [`report_progress`] 直到任务完成 / Runs [`report_progress`] until the task completes
+    progress: tokio::task::JoinHandle<()>,
+}
+
+/// 定期报告进度，使运行超过确认期限的任务不被重投给其他工作者 / Report progress periodically, so that tasks
+/// running longer than the ack wait are not redelivered to another worker meanwhile
+async fn report_progress(message: Arc<jetstream::Message>, every: Duration) {
+    loop {
+        tokio::time::sleep(every).await;
+        if let Err(e) = message.ack_with(jetstream::AckKind::Progress).await {
+            tracing::warn!(subject = %message.subject, error = %e, "failed to report task progress");
+        }
+    }
+}
+
+impl JetStreamTaskQueue {
+    async fn consumer(&self, queue: &str, kind: TaskKind) -> Result<consumer::PullConsumer, StorageError> {
+        let subject = self.transport.task_subject(queue, kind);
+        if let Some(consumer) = self.consumers.lock().get(&subject) {
+            return Ok(consumer.clone());
+        }
+        let transport = &self.transport;
+        let stream = transport
+            .jetstream
+            .get_or_create_stream(stream::Config {
+                name: transport.stream.clone(),
+                subjects: vec![format!("{}.tasks.>", transport.prefix)],
+                retention: stream::RetentionPolicy::WorkQueue,
+                ..Default::default()
+            })
+            .await
+            .map_err(connection_error)?;
+        let name = format!("{}_{}", token(queue), kind.as_str());
+        let consumer: consumer::PullConsumer = stream
+            .get_or_create_consumer(
+                &name,
+                consumer::pull::Config {
+                    durable_name: Some(name.clone()),
+                    filter_subject: subject.clone(),
+                    ack_policy: consumer::AckPolicy::Explicit,
+                    ack_wait: transport.ack_wait,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(query_error)?;
+        self.consumers.lock().insert(subject, consumer.clone());
+        Ok(consumer)
+    }
+}
+
+/// 未完成的任务停止报告进度，在确认期限后重投 / Tasks not completed stop reporting progress and are redelivered after
+/// the ack wait
+impl Drop for JetStreamTaskQueue {
+    fn drop(&mut self) {
+        for in_flight in self.in_flight.get_mut().values().flatten() {
+            in_flight.progress.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl TaskQueue for JetStreamTaskQueue {
+    async fn push(&self, queue: &str, task: Task) -> Result<(), StorageError> {
+        // 先建立流与消费者，否则没有流接收该主题 / Set up the stream and consumer first, or no stream takes the subject
+        self.consumer(queue, task.kind()).await?;
+        let payload = serde_json::to_vec(&task).map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        let subject = self.transport.task_subject(queue, task.kind());
+        let ack = self.transport.jetstream.publish(subject, payload.into()).await.map_err(query_error)?;
+        ack.await.map_err(query_error)?;
+        Ok(())
+    }
+
+    async fn poll(&self, queue: &str, kind: TaskKind, timeout: Duration) -> Result<Option<Task>, StorageError> {
+        let consumer = self.consumer(queue, kind).await?;
+        let batch = if timeout.is_zero() {
+            consumer.fetch().max_messages(1).messages().await
+        } else {
+            consumer.batch().max_messages(1).expires(timeout.max(MIN_EXPIRES)).messages().await
+        };
+        let Some(message) = batch.map_err(query_error)?.next().await else {
+            return Ok(None);
+        };
+        let message = message.map_err(query_error)?;
+        let task = match serde_json::from_slice::<Task>(&message.payload) {
+            Ok(task) => task,
+            Err(e) => {
+                // 否则会被无限重投 / Or it would be redelivered forever
+                tracing::warn!(subject = %message.subject, error = %e, "dropping malformed task message");
+                message.ack().await.map_err(query_error)?;
+                return Ok(None);
+            }
+        };
+        let key = (message.subject.to_string(), task_key(&task));
+        let message = Arc::new(message);
+        let every = self.transport.ack_wait / PROGRESS_REPORTS_PER_ACK_WAIT;
+        let progress = tokio::spawn(report_progress(message.clone(), every));
+        self.in_flight.lock().entry(key).or_default().push_back(InFlight { message, progress });
+        Ok(Some(task))
+    }
+
+    /// 尚未投递的任务 / Tasks not delivered yet
+    async fn len(&self, queue: &str, kind: TaskKind) -> Result<usize, StorageError> {
+        let info = self.consumer(queue, kind).await?.get_info().await.map_err(query_error)?;
+        Ok(info.num_pending as usize)
+    }
+
+    async fn complete(&self, queue: &str, task: &Task) -> Result<(), StorageError> {
+        let key = (self.transport.task_subject(queue, task.kind()), task_key(task));
+        let message = {
+            let mut in_flight = self.in_flight.lock();
+            let message = in_flight.get_mut(&key).and_then(VecDeque::pop_front);
+            if in_flight.get(&key).is_some_and(VecDeque::is_empty) {
+                in_flight.remove(&key);
+            }
+            message
+        };
+        match message {
+            Some(InFlight { message, progress }) => {
+                progress.abort();
+                message.ack().await.map_err(query_error)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// 客户端当前是否已连接服务器 / Whether the client is connected to the server right now
+    async fn ping(&self) -> Result<(), StorageError> {
+        match self.transport.client.connection_state() {
+            async_nats::connection::State::Connected => Ok(()),
+            state => Err(connection_error(format!("nats connection is {state}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::temporal::{StartWorkflowOptions, WorkerConfig, Workflow, WorkflowContext};
+
+    struct Status;
+
+    impl crate::temporal::Query for Status {
+        fn name() -> &'static str {
+            "status"
+        }
+
+        type Result = String;
+    }
+
+    struct Waiting;
+
+    impl Workflow for Waiting {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "waiting"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<(), WorkflowError> {
+            ctx.on_query::<Status>(|| "waiting".to_string())?;
+            ctx.sleep(Duration::from_secs(30)).await;
+            Ok(())
+        }
+    }
+
+    fn request(workflow_id: &str, query_name: &str) -> Vec<u8> {
+        serde_json::to_vec(&json!({"workflow_id": workflow_id, "query_name": query_name})).unwrap()
+    }
+
+    #[test]
+    fn test_subjects_are_single_tokens() {
+        assert_eq!(token("orders.eu west"), "orders_eu_west");
+        assert_eq!(token("billing-v2"), "billing-v2");
+    }
+
+    #[tokio::test]
+    async fn test_only_the_running_worker_answers() {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        }));
+        worker.register_workflow::<Waiting>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+        let options = StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new("wf-1")),
+            ..StartWorkflowOptions::default()
+        };
+        worker.client().start_workflow::<Waiting>((), options).await.unwrap();
+
+        let dispatcher = worker.query_dispatcher();
+        let reply = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(reply) = answer(&*dispatcher, &request("wf-1", "status")).await {
+                    break reply;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(matches!(serde_json::from_slice(&reply).unwrap(), QueryReply::Answer(answer) if answer == "waiting"));
+
+        let reply = answer(&*dispatcher, &request("wf-1", "unknown")).await.unwrap();
+        assert!(matches!(serde_json::from_slice(&reply).unwrap(), QueryReply::NotRegistered(_)));
+        assert!(answer(&*dispatcher, &request("wf-2", "status")).await.is_none());
+        let reply = answer(&*dispatcher, b"{").await.unwrap();
+        assert!(matches!(serde_json::from_slice(&reply).unwrap(), QueryReply::Failed(_)));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    /// 需要 `NATS_URL` 指向启用 JetStream 的服务器，否则跳过 / Runs against the JetStream-enabled server in
+    /// `NATS_URL`, skipped without one
+    #[tokio::test]
+    async fn test_unacknowledged_tasks_are_redelivered() {
+        let Ok(url) = std::env::var("NATS_URL") else { return };
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let transport = NatsTransport::connect(&url)
+            .await
+            .unwrap()
+            .with_prefix(format!("test{}", id))
+            .with_stream(format!("TEST_{}", id))
+            .with_ack_wait(Duration::from_millis(200));
+        let (crashing, survivor) = (transport.task_queue(), transport.task_queue());
+        let task = Task::Signal(crate::temporal::task_queue::SignalTask {
+            execution: crate::temporal::WorkflowExecution::new(WorkflowId::new("wf-1")),
+            signal_name: "approve".to_string(),
+            input: json!(true),
+            trace_context: Default::default(),
+        });
+
+        crashing.push("orders", task).await.unwrap();
+        assert_eq!(crashing.len("orders", TaskKind::Signal).await.unwrap(), 1);
+        crashing.poll("orders", TaskKind::Signal, Duration::from_secs(1)).await.unwrap().unwrap();
+        assert!(survivor.poll("orders", TaskKind::Signal, Duration::ZERO).await.unwrap().is_none());
+
+        // 轮询方停止报告进度，如同崩溃 / The poller stops reporting progress, as if it crashed
+        drop(crashing);
+        let redelivered = survivor.poll("orders", TaskKind::Signal, Duration::from_secs(2)).await.unwrap().unwrap();
+        survivor.complete("orders", &redelivered).await.unwrap();
+        assert!(survivor.poll("orders", TaskKind::Signal, Duration::from_millis(500)).await.unwrap().is_none());
+    }
+
+    /// 需要 `NATS_URL` 指向启用 JetStream 的服务器，否则跳过 / Runs against the JetStream-enabled server in
+    /// `NATS_URL`, skipped without one
+    #[tokio::test]
+    async fn test_tasks_outliving_the_ack_wait_are_not_redelivered() {
+        let Ok(url) = std::env::var("NATS_URL") else { return };
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let transport = NatsTransport::connect(&url)
+            .await
+            .unwrap()
+            .with_prefix(format!("test{}", id))
+            .with_stream(format!("TEST_{}", id))
+            .with_ack_wait(Duration::from_millis(300));
+        let (running, idle) = (transport.task_queue(), transport.task_queue());
+        let task = Task::Workflow(crate::temporal::task_queue::WorkflowTask {
+            execution: crate::temporal::WorkflowExecution::new(WorkflowId::new("wf-1")),
+            workflow_type: "long".to_string(),
+            task_queue: "orders".to_string(),
+            input: json!(null),
+            priority: Default::default(),
+            trace_context: Default::default(),
+        });
+
+        running.push("orders", task).await.unwrap();
+        let polled = running.poll("orders", TaskKind::Workflow, Duration::from_secs(1)).await.unwrap().unwrap();
+        // 运行三倍于确认期限 / The run takes three times the ack wait
+        assert!(idle.poll("orders", TaskKind::Workflow, Duration::from_millis(900)).await.unwrap().is_none());
+        running.complete("orders", &polled).await.unwrap();
+        assert!(idle.poll("orders", TaskKind::Workflow, Duration::from_millis(500)).await.unwrap().is_none());
+    }
+}
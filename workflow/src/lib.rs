@@ -427,6 +427,25 @@ pub mod error;
 pub mod state;
 pub mod tools;
 pub mod types;
+pub mod util;
+
+// 服务配置 / Service configuration
+pub mod config;
+
+// Temporal 风格工作流引擎 / Temporal-style workflow engine
+pub mod temporal;
+
+// 审计日志模块 / Audit Log Module
+pub mod audit;
+
+// 工作流测试工具 / Workflow Testing Utilities
+pub mod testing;
+
+// 声明式工作流定义 / Declarative Workflow Definitions
+pub mod dsl;
+
+// 规范工作流模型 / Canonical Workflow Model
+pub mod model;
 
 // 持久化模块 / Persistence Module
 #[cfg(feature = "persistence")]
@@ -444,6 +463,17 @@ pub mod patterns;
 #[cfg(feature = "middleware")]
 pub mod middleware;
 
+// 诊断模块 / Diagnostics Module
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+
+// 消息系统集成 / Messaging Integrations
+pub mod integrations;
+
+// gRPC 服务模块 / gRPC Service Module
+#[cfg(feature = "grpc")]
+pub mod grpc;
+
 // 国际标准对标模块 / International Standards Benchmarking Module
 #[cfg(feature = "international_standards")]
 pub mod international_standards;
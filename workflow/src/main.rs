@@ -3,9 +3,59 @@ use metrics_exporter_prometheus::PrometheusBuilder;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 use tracing::{info, warn, span, Level};
 
-use workflow::http::build_router;
+use workflow::http::{build_router_with_auth, build_router_with_workflows};
+use workflow::http::auth::Authenticator;
+use workflow::http::workflows::WorkflowApi;
 use workflow::http::set_start_time;
+use workflow::http::ReadOnlyMode;
+use workflow::http::health::StorageCheck;
+use workflow::config::{Config, ConfigReloader, MetricsConfig};
+#[cfg(feature = "sqlite")]
+use workflow::config::StorageBackend;
+use workflow::temporal::WorkflowWorker;
 
+/// 采样分配以提供 `/debug/pprof/heap` / Sample allocations for `/debug/pprof/heap`
+#[cfg(feature = "diagnostics")]
+#[global_allocator]
+static ALLOCATOR: workflow::diagnostics::heap::SamplingAllocator = workflow::diagnostics::heap::SamplingAllocator::system();
+
+/// 检查配置文件修改时间的间隔 / How often the config file's modification time is checked
+const CONFIG_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// 检查存储是否降级的间隔 / How often the storage is checked for degradation
+const STORAGE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// 设置了 `OTEL_EXPORTER_OTLP_ENDPOINT` 时以 OTLP 导出追踪 / Export traces over OTLP when `OTEL_EXPORTER_OTLP_ENDPOINT` is set
+#[cfg(feature = "otel")]
+fn otlp_tracer_provider() -> Option<opentelemetry_sdk::trace::SdkTracerProvider> {
+    std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT")?;
+    let service_name = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "workflow".to_string());
+    match workflow::temporal::telemetry::otlp_tracer_provider(&service_name) {
+        Ok(provider) => Some(provider),
+        Err(e) => {
+            eprintln!("OTLP exporter disabled: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+async fn init_tracing() -> Option<opentelemetry_sdk::trace::SdkTracerProvider> {
+    use opentelemetry::trace::TracerProvider as _;
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let provider = otlp_tracer_provider();
+    let otel = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer("workflow")));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_target(false))
+        .with(otel)
+        .init();
+    provider
+}
+
+#[cfg(not(feature = "otel"))]
 async fn init_tracing() {
     let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
     tracing_subscriber::registry()
@@ -14,39 +64,203 @@ async fn init_tracing() {
         .init();
 }
 
-fn init_metrics() {
+fn init_metrics(config: &MetricsConfig) {
+    if !config.enabled {
+        return;
+    }
     let builder = PrometheusBuilder::new();
-    let addr: std::net::SocketAddr = "0.0.0.0:9090".parse().expect("invalid metrics addr");
+    let addr: std::net::SocketAddr = config.listen.parse().expect("invalid metrics addr");
     let _ = builder
         .with_http_listener(addr)
         .install()
         .expect("install prometheus recorder");
+    workflow::temporal::metrics::describe();
+    #[cfg(feature = "middleware")]
+    workflow::middleware::describe_metrics();
+}
+
+/// 认证，以及按环境变量配置的角色授权 / Authentication, plus role-based authorization when configured in the environment
+fn secured_router(api: WorkflowApi, auth: Authenticator) -> axum::Router {
+    #[cfg(feature = "middleware")]
+    if let Some(policy) = workflow::middleware::rbac::RbacPolicy::from_env() {
+        return workflow::http::build_router_with_rbac(Some(api), auth, policy);
+    }
+    build_router_with_auth(Some(api), auth)
+}
+
+/// 未配置认证时的路由，`/debug` 须显式开启 / Router without authentication, where `/debug` needs an explicit opt-in
+fn open_router(api: WorkflowApi, debug_endpoints: bool) -> axum::Router {
+    #[cfg(feature = "diagnostics")]
+    if debug_endpoints {
+        warn!(message = "WORKFLOW_DEBUG_ENDPOINTS set without authentication, /debug routes are open to anyone");
+        return workflow::http::build_router_with_debug_endpoints(Some(api));
+    }
+    #[cfg(not(feature = "diagnostics"))]
+    if debug_endpoints {
+        warn!(message = "WORKFLOW_DEBUG_ENDPOINTS has no effect without the diagnostics feature");
+    }
+    build_router_with_workflows(api)
+}
+
+/// 每个客户端每分钟的请求数与可选的突发量 / Requests per minute per client, with an optional burst
+#[cfg(feature = "middleware")]
+fn rate_limiter(
+    config: &workflow::config::MiddlewareSettings,
+) -> Option<std::sync::Arc<workflow::middleware::rate_limit::RateLimiter>> {
+    use workflow::middleware::rate_limit::{RateLimiter, TokenBucketConfig};
+    let mut limit = TokenBucketConfig::per_minute(config.rate_limit_per_minute?);
+    if let Some(burst) = config.rate_limit_burst {
+        limit = limit.burst(burst);
+    }
+    Some(std::sync::Arc::new(RateLimiter::new(limit)))
+}
+
+/// 按存储与保留期配置创建进程内工作者，并从持久存储恢复只读开关 / In-process worker with the configured storage
+/// and retention, restoring the read-only switch from durable storage
+async fn build_worker(config: &Config, read_only: &ReadOnlyMode) -> WorkflowWorker {
+    let mut worker = WorkflowWorker::new(config.worker.worker_config());
+    #[cfg(not(feature = "sqlite"))]
+    let _ = read_only;
+    match (config.storage.backend, &config.storage.url) {
+        #[cfg(feature = "sqlite")]
+        (StorageBackend::Sqlite, Some(url)) => {
+            let storage = std::sync::Arc::new(
+                workflow::temporal::SqliteStorage::connect(url).await.expect("failed to open sqlite storage"),
+            );
+            // 管理端点设置的开关在重启后仍然有效 / the switch set through the admin endpoint survives restarts
+            match read_only.restore(storage.clone()).await {
+                Ok(true) => warn!(message = "read-only mode restored from storage"),
+                Ok(false) => {}
+                Err(e) => warn!(message = "failed to restore read-only mode", error = %e),
+            }
+            worker = worker.with_storage(storage);
+        }
+        // 校验保证其余组合都是内存存储 / validation leaves only the in-memory store otherwise
+        _ => {}
+    }
+    if let Some(policies) = config.retention.policies() {
+        worker = worker.with_retention(policies);
+    }
+    worker
 }
 
 #[tokio::main]
 async fn main() {
     set_start_time();
+    #[cfg(feature = "otel")]
+    let tracer_provider = init_tracing().await;
+    #[cfg(not(feature = "otel"))]
     init_tracing().await;
-    init_metrics();
-    let app = build_router();
+    // 文件由 WORKFLOW_CONFIG 指定，环境变量覆盖其中的值 / file named by WORKFLOW_CONFIG, overridden by the environment
+    let config = Config::from_env().expect("invalid configuration");
+    init_metrics(&config.metrics);
+    let read_only = ReadOnlyMode::new();
+    // 进程内工作者；嵌入方在此注册工作流与活动 / in-process worker; embedders register workflows and activities here
+    let worker = std::sync::Arc::new(build_worker(&config, &read_only).await);
+    if config.server.read_only {
+        read_only.set(true);
+    }
+    // 存储检查失败期间只读 / read-only while storage checks fail
+    let storage_check = std::sync::Arc::new(StorageCheck::for_client(&worker.client()));
+    let storage_task = read_only.follow_storage(storage_check, STORAGE_CHECK_INTERVAL);
+    let audit = workflow::audit::AuditLog::from_spec(&config.middleware.audit_log)
+        .await
+        .expect("invalid audit log configuration");
+    let mut api = WorkflowApi::from_worker(&worker).with_audit(audit.clone()).with_read_only(read_only.clone());
+    match &config.server.page_token_key {
+        Some(key) => api = api.with_page_token_key(key.as_bytes()),
+        // 各实例的随机密钥互不相认，翻页落到另一实例时返回 400 / per-process keys differ, so paging through another
+        // instance fails with 400
+        None if config.storage.is_shared() => {
+            warn!(message = "no WORKFLOW_PAGE_TOKEN_KEY with shared storage, page tokens fail on other instances")
+        }
+        None => {}
+    }
+    let app = match Authenticator::from_env() {
+        Some(auth) => secured_router(api, auth),
+        None => {
+            warn!(message = "no WORKFLOW_API_KEYS or WORKFLOW_JWT_* configured, /api routes are unauthenticated");
+            open_router(api, config.server.debug_endpoints)
+        }
+    };
+    #[cfg(feature = "middleware")]
+    let app = match rate_limiter(&config.middleware) {
+        Some(limiter) => workflow::http::with_rate_limit(app, limiter),
+        None => app,
+    };
+
+    let running = worker.clone();
+    let worker_task = tokio::spawn(async move { running.run().await });
+    let worker_shutdown = worker.shutdown_handle();
+    let grace_period = config.server.shutdown_grace_period();
+    // SIGHUP 或文件变化时调整并发上限与只读模式 / concurrency limits and read-only mode follow SIGHUP and file changes
+    let reloader = std::sync::Arc::new(ConfigReloader::new(
+        config.clone(),
+        std::env::var_os(workflow::config::CONFIG_PATH_ENV).map(Into::into),
+    ));
+    let mut reloaded = reloader.subscribe();
+    let mut applied = reloader.current();
+    let reload_task = reloader.spawn(CONFIG_POLL_INTERVAL);
+    let tuned = worker.clone();
+    let apply_task = tokio::spawn(async move {
+        while reloaded.changed().await.is_ok() {
+            let config = reloaded.borrow_and_update().clone();
+            workflow::config::reload::apply_tunables(&applied, &config, &tuned, &read_only);
+            applied = config;
+        }
+    });
+    let stop = tokio_util::sync::CancellationToken::new();
+    #[cfg(feature = "grpc")]
+    let grpc_task = {
+        // gRPC 与 HTTP 共享同一个工作者 / gRPC shares the worker with HTTP
+        let addr = config.server.grpc_addr().expect("invalid grpc bind addr");
+        info!(message = "starting grpc server", %addr);
+        let service = workflow::grpc::WorkflowGrpcService::from_worker(&worker).with_audit(audit.clone());
+        let stopped = stop.clone();
+        tokio::spawn(workflow::grpc::serve(service, addr, async move { stopped.cancelled().await }))
+    };
 
-    let host = std::env::var("WORKFLOW_HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
-    let port: u16 = std::env::var("WORKFLOW_PORT").ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(8080);
-    let addr: std::net::SocketAddr = format!("{}:{}", host, port).parse().expect("invalid bind addr");
+    let addr = config.server.http_addr().expect("invalid bind addr");
 
     let startup_span = span!(Level::INFO, "service.startup", version = workflow::VERSION, bind = %addr);
     let _enter = startup_span.enter();
     info!(message = "starting server", %addr);
     let listener = tokio::net::TcpListener::bind(addr).await.expect("bind failed");
-    axum::serve(listener, app)
-        .with_graceful_shutdown(async {
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .with_graceful_shutdown(async move {
             let _ = tokio::signal::ctrl_c().await;
             let shutdown_span = span!(Level::INFO, "service.shutdown");
             let _enter = shutdown_span.enter();
             warn!(message = "received shutdown signal");
+            // 工作者与 HTTP 连接同时排空 / the worker drains alongside the HTTP connections
+            worker_shutdown.shutdown(grace_period);
         })
         .await
         .expect("server failed");
+    stop.cancel();
+    reload_task.abort();
+    apply_task.abort();
+    storage_task.abort();
+    #[cfg(feature = "grpc")]
+    if let Ok(Err(e)) = grpc_task.await {
+        warn!(message = "grpc server failed", error = %e);
+    }
+    worker.shutdown(grace_period);
+    match worker_task.await {
+        Ok(Ok(report)) if !report.is_empty() => warn!(
+            message = "worker handed unfinished tasks back to the queue",
+            workflows = report.workflows.len(),
+            activities = report.activities.len(),
+            signals = report.signals.len()
+        ),
+        Ok(Err(e)) => warn!(message = "worker stopped with error", error = %e),
+        _ => {}
+    }
+    // 导出尚未发送的 span / flush spans not yet exported
+    #[cfg(feature = "otel")]
+    if let Some(provider) = tracer_provider
+        && let Err(e) = provider.shutdown()
+    {
+        warn!(message = "failed to flush traces", error = %e);
+    }
 }
@@ -0,0 +1,340 @@
+//! # 请求缓存 / Request Caching
+//!
+//! [`CachingMiddleware`] 为幂等请求（`Idempotent: true` 头）按工作流 ID 与请求数据计算缓存键：命中时以缓存的
+//! 响应短路链的其余部分，并在 `cache` 元数据中标记 `hit`；未命中时保存内层生成的响应。
+//! 存储可插拔：进程内 LRU [`InMemoryCacheStore`]，或启用 `database` 特性时的 [`RedisCacheStore`]。
+//! [`CachingMiddleware`] derives a cache key from the workflow ID and request data of idempotent requests (those with an
+//! `Idempotent: true` header): on a hit it short-circuits the rest of the chain with the cached response and marks the
+//! `cache` metadata `hit`; on a miss it stores the response produced further in. Stores are pluggable: the in-process
+//! LRU [`InMemoryCacheStore`], or [`RedisCacheStore`] with the `database` feature.
+
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+
+use crate::middleware::{MiddlewareContext, MiddlewarePriority, WorkflowMiddleware};
+
+/// 标记幂等请求的头 / Header marking an idempotent request
+pub const IDEMPOTENT_HEADER: &str = "Idempotent";
+/// 绕过缓存查找的头，值为 `no-cache` / Header bypassing the cache lookup, with the value `no-cache`
+pub const CACHE_CONTROL_HEADER: &str = "Cache-Control";
+
+/// 缓存存储 / Cache store
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    /// 未过期的缓存值 / Cached value, unless expired
+    async fn get(&self, key: &str) -> anyhow::Result<Option<serde_json::Value>>;
+    /// 保存 `ttl` 后过期的值 / Store a value expiring after `ttl`
+    async fn set(&self, key: &str, value: &serde_json::Value, ttl: Duration) -> anyhow::Result<()>;
+    async fn remove(&self, key: &str) -> anyhow::Result<()>;
+}
+
+struct Entry {
+    value: serde_json::Value,
+    expires: Instant,
+}
+
+/// 进程内 LRU 存储，超出容量时淘汰最久未用的项 / In-process LRU store evicting the least recently used entry when full
+pub struct InMemoryCacheStore {
+    capacity: NonZeroUsize,
+    /// 按最近使用排序，最久未用的在前 / Ordered by use, least recently used first
+    entries: Mutex<IndexMap<String, Entry>>,
+}
+
+impl Default for InMemoryCacheStore {
+    fn default() -> Self {
+        Self::new(NonZeroUsize::new(1024).unwrap())
+    }
+}
+
+impl InMemoryCacheStore {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(IndexMap::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl CacheStore for InMemoryCacheStore {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<serde_json::Value>> {
+        let mut entries = self.entries.lock();
+        let Some(index) = entries.get_index_of(key) else {
+            return Ok(None);
+        };
+        if entries[index].expires <= Instant::now() {
+            entries.shift_remove_index(index);
+            return Ok(None);
+        }
+        let last = entries.len() - 1;
+        entries.move_index(index, last);
+        Ok(Some(entries[last].value.clone()))
+    }
+
+    async fn set(&self, key: &str, value: &serde_json::Value, ttl: Duration) -> anyhow::Result<()> {
+        let mut entries = self.entries.lock();
+        entries.shift_remove(key);
+        entries.insert(
+            key.to_string(),
+            Entry {
+                value: value.clone(),
+                expires: Instant::now() + ttl,
+            },
+        );
+        while entries.len() > self.capacity.get() {
+            entries.shift_remove_index(0);
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        self.entries.lock().shift_remove(key);
+        Ok(())
+    }
+}
+
+/// Redis 存储（需启用 `database` 特性）/ Redis store (requires the `database` feature)
+#[cfg(feature = "database")]
+pub struct RedisCacheStore {
+    client: redis::Client,
+    namespace: String,
+}
+
+#[cfg(feature = "database")]
+impl RedisCacheStore {
+    pub fn new(url: &str, namespace: impl Into<String>) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: redis::Client::open(url)?,
+            namespace: namespace.into(),
+        })
+    }
+
+    fn key(&self, k: &str) -> String {
+        format!("{}:cache:{}", self.namespace, k)
+    }
+}
+
+#[cfg(feature = "database")]
+#[async_trait]
+impl CacheStore for RedisCacheStore {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<serde_json::Value>> {
+        use redis::AsyncCommands;
+
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let val: Option<String> = conn.get(self.key(key)).await?;
+        Ok(match val {
+            Some(v) => Some(serde_json::from_str(&v)?),
+            None => None,
+        })
+    }
+
+    async fn set(&self, key: &str, value: &serde_json::Value, ttl: Duration) -> anyhow::Result<()> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let millis = u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX).max(1);
+        let _: () = redis::cmd("SET")
+            .arg(self.key(key))
+            .arg(serde_json::to_string(value)?)
+            .arg("PX")
+            .arg(millis)
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        use redis::AsyncCommands;
+
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        conn.del::<_, ()>(self.key(key)).await?;
+        Ok(())
+    }
+}
+
+/// 缓存中间件 / Caching Middleware
+pub struct CachingMiddleware {
+    name: String,
+    version: String,
+    description: String,
+    priority: MiddlewarePriority,
+    store: Arc<dyn CacheStore>,
+    ttl: Duration,
+}
+
+impl Default for CachingMiddleware {
+    fn default() -> Self {
+        Self::new(Arc::new(InMemoryCacheStore::default()))
+    }
+}
+
+impl CachingMiddleware {
+    /// 以 `store` 缓存五分钟 / Cache in `store` for five minutes
+    pub fn new(store: Arc<dyn CacheStore>) -> Self {
+        Self {
+            name: "CachingMiddleware".to_string(),
+            version: "1.0.0".to_string(),
+            description: "工作流缓存中间件 / Workflow caching middleware".to_string(),
+            priority: MiddlewarePriority::Normal,
+            store,
+            ttl: Duration::from_secs(300),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// 幂等请求的缓存键 / Cache key of an idempotent request
+    pub fn cache_key(context: &MiddlewareContext) -> Option<String> {
+        if context.get_header(IDEMPOTENT_HEADER).map(String::as_str) != Some("true") {
+            return None;
+        }
+        // 对象键有序，同样的数据得到同样的编码 / Object keys are sorted, so equal data encodes the same
+        let digest = Sha256::digest(context.data.to_string().as_bytes());
+        let hex: String = digest.iter().take(16).map(|b| format!("{b:02x}")).collect();
+        Some(format!("{}:{}", context.workflow_id, hex))
+    }
+
+    /// 请求是否命中了缓存 / Whether the request was answered from the cache
+    pub fn is_hit(context: &MiddlewareContext) -> bool {
+        context.get_metadata("cache").map(String::as_str) == Some("hit")
+    }
+}
+
+#[async_trait]
+impl WorkflowMiddleware for CachingMiddleware {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn priority(&self) -> MiddlewarePriority {
+        self.priority
+    }
+
+    async fn before_request(&self, context: &mut MiddlewareContext) -> Result<(), String> {
+        let Some(key) = Self::cache_key(context) else {
+            return Ok(());
+        };
+        let bypass = context.get_header(CACHE_CONTROL_HEADER).map(String::as_str) == Some("no-cache");
+        let cached = if bypass {
+            None
+        } else {
+            // 缓存只是优化，存储故障时照常处理 / The cache is an optimization; on store failure carry on uncached
+            self.store.get(&key).await.unwrap_or_else(|e| {
+                tracing::warn!("缓存读取失败 / Cache read failed: {}", e);
+                None
+            })
+        };
+        let outcome = match cached {
+            Some(response) => {
+                context.respond(response);
+                "hit"
+            }
+            None => "miss",
+        };
+        context.set_metadata("cache".to_string(), outcome.to_string());
+        context.set_metadata("cache_key".to_string(), key);
+        Ok(())
+    }
+
+    async fn after_request(&self, context: &mut MiddlewareContext) -> Result<(), String> {
+        if context.get_metadata("cache").map(String::as_str) != Some("miss") {
+            return Ok(());
+        }
+        if let (Some(key), Some(response)) = (context.get_metadata("cache_key"), &context.response)
+            && let Err(e) = self.store.set(key, response, self.ttl).await
+        {
+            tracing::warn!("缓存写入失败 / Cache write failed: {}", e);
+        }
+        Ok(())
+    }
+
+    async fn handle_error(
+        &self,
+        _context: &mut MiddlewareContext,
+        error: &str,
+    ) -> Result<(), String> {
+        tracing::debug!("缓存中间件错误处理 / Caching middleware error handling: {}", error);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(data: serde_json::Value) -> MiddlewareContext {
+        let mut context = MiddlewareContext::new("req".to_string(), "wf-1".to_string(), data);
+        context.set_header(IDEMPOTENT_HEADER.to_string(), "true".to_string());
+        context
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store_evicts_least_recently_used_and_expires() {
+        let store = InMemoryCacheStore::new(NonZeroUsize::new(2).unwrap());
+        let ttl = Duration::from_secs(60);
+        store.set("a", &serde_json::json!(1), ttl).await.unwrap();
+        store.set("b", &serde_json::json!(2), ttl).await.unwrap();
+        assert_eq!(store.get("a").await.unwrap(), Some(serde_json::json!(1)));
+        store.set("c", &serde_json::json!(3), ttl).await.unwrap();
+        assert_eq!(store.get("b").await.unwrap(), None);
+        assert_eq!(store.get("a").await.unwrap(), Some(serde_json::json!(1)));
+        assert_eq!(store.len(), 2);
+
+        store.set("d", &serde_json::json!(4), Duration::ZERO).await.unwrap();
+        assert_eq!(store.get("d").await.unwrap(), None);
+        store.remove("a").await.unwrap();
+        assert_eq!(store.get("a").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_caching_middleware_answers_repeated_idempotent_requests() {
+        let store = Arc::new(InMemoryCacheStore::default());
+        let middleware = CachingMiddleware::new(store.clone());
+
+        let mut first = request(serde_json::json!({"order": 7, "sku": "x"}));
+        middleware.before_request(&mut first).await.unwrap();
+        assert!(!CachingMiddleware::is_hit(&first));
+        first.respond(serde_json::json!({"status": "accepted"}));
+        middleware.after_request(&mut first).await.unwrap();
+
+        // 键顺序不同的同样数据 / the same data with keys in another order
+        let mut second = request(serde_json::json!({"sku": "x", "order": 7}));
+        middleware.before_request(&mut second).await.unwrap();
+        assert!(CachingMiddleware::is_hit(&second));
+        assert_eq!(second.response, Some(serde_json::json!({"status": "accepted"})));
+
+        let mut bypass = request(serde_json::json!({"order": 7, "sku": "x"}));
+        bypass.set_header(CACHE_CONTROL_HEADER.to_string(), "no-cache".to_string());
+        middleware.before_request(&mut bypass).await.unwrap();
+        assert!(!CachingMiddleware::is_hit(&bypass));
+
+        let mut other = MiddlewareContext::new("req".to_string(), "wf-1".to_string(), serde_json::json!({"order": 7}));
+        middleware.before_request(&mut other).await.unwrap();
+        middleware.after_request(&mut other).await.unwrap();
+        assert!(other.get_metadata("cache").is_none());
+        assert_eq!(store.len(), 1);
+    }
+}
@@ -3,9 +3,28 @@
 //! 本模块实现了工作流系统的核心中间件，包括认证、授权、日志、监控等。
 //! This module implements core middleware for workflow systems, including authentication, authorization, logging, monitoring, etc.
 
+use crate::middleware::rate_limit::{retry_after_secs, RateLimiter};
 use crate::middleware::{MiddlewareContext, MiddlewarePriority, WorkflowMiddleware};
 use async_trait::async_trait;
+use metrics::{counter, describe_counter, describe_histogram, histogram, Unit};
 use std::collections::HashMap;
+use std::sync::Arc;
+
+pub const MIDDLEWARE_REQUESTS: &str = "workflow_middleware_requests_total";
+pub const MIDDLEWARE_REQUEST_DURATION: &str = "workflow_middleware_request_duration_seconds";
+
+/// 向已安装的记录器登记中间件指标的单位与说明 / Register units and descriptions of the middleware metrics
+pub fn describe_metrics() {
+    describe_counter!(
+        MIDDLEWARE_REQUESTS,
+        "Requests through the middleware chain, by workflow_id, workflow_name and outcome (ok or error)"
+    );
+    describe_histogram!(
+        MIDDLEWARE_REQUEST_DURATION,
+        Unit::Seconds,
+        "Time from a request's context creation to the end of its chain, by workflow_id, workflow_name and outcome"
+    );
+}
 
 /// 认证中间件 / Authentication Middleware
 ///
@@ -446,19 +465,18 @@ impl WorkflowMiddleware for MonitoringMiddleware {
 
 /// 限流中间件 / Rate Limiting Middleware
 ///
-/// 提供工作流请求的限流功能。
-/// Provides rate limiting functionality for workflow requests.
-#[allow(dead_code)]
+/// 提供工作流请求的限流功能。按 `X-API-Key` 头或 `client_ip` 元数据对每个客户端使用令牌桶，
+/// 可与 HTTP 层共享同一个 [`RateLimiter`]。
+/// Provides rate limiting functionality for workflow requests: one token bucket per client, keyed by the
+/// `X-API-Key` header or the `client_ip` metadata, optionally sharing a [`RateLimiter`] with the HTTP layer.
 pub struct RateLimitingMiddleware {
     name: String,
     version: String,
     description: String,
     priority: MiddlewarePriority,
-    rate_limits: HashMap<String, (u64, std::time::Duration)>,
-    request_counts: HashMap<String, (u64, std::time::Instant)>,
+    limiter: Arc<RateLimiter>,
 }
 
-#[allow(dead_code)]
 impl Default for RateLimitingMiddleware {
     fn default() -> Self {
         Self::new()
@@ -466,56 +484,32 @@ impl Default for RateLimitingMiddleware {
 }
 
 impl RateLimitingMiddleware {
-    /// 创建限流中间件 / Create rate limiting middleware
+    /// 创建限流中间件，每个客户端每分钟 100 个请求 / Create rate limiting middleware allowing 100 requests per minute per client
     pub fn new() -> Self {
-        let mut rate_limits = HashMap::new();
-        rate_limits.insert(
-            "default".to_string(),
-            (100, std::time::Duration::from_secs(60)),
-        );
-        rate_limits.insert(
-            "admin".to_string(),
-            (1000, std::time::Duration::from_secs(60)),
-        );
-        rate_limits.insert("user".to_string(), (50, std::time::Duration::from_secs(60)));
+        Self::with_limiter(Arc::new(RateLimiter::default()))
+    }
 
+    /// 使用给定的限流器 / Use the given limiter
+    pub fn with_limiter(limiter: Arc<RateLimiter>) -> Self {
         Self {
             name: "RateLimitingMiddleware".to_string(),
             version: "1.0.0".to_string(),
             description: "工作流限流中间件 / Workflow rate limiting middleware".to_string(),
             priority: MiddlewarePriority::High,
-            rate_limits,
-            request_counts: HashMap::new(),
+            limiter,
         }
     }
 
-    /// 检查限流 / Check rate limit
-    #[allow(dead_code)]
-    fn check_rate_limit(&mut self, key: &str) -> bool {
-        let now = std::time::Instant::now();
-        let (limit, window) = self
-            .rate_limits
-            .get(key)
-            .copied()
-            .unwrap_or((100, std::time::Duration::from_secs(60)));
-
-        let (count, reset_time) = self
-            .request_counts
-            .entry(key.to_string())
-            .or_insert((0, now));
-
-        // 检查是否需要重置计数器 / Check if counter needs to be reset
-        if now.duration_since(*reset_time) >= window {
-            *count = 0;
-            *reset_time = now;
-        }
-
-        if *count >= limit {
-            return false;
+    /// 上下文的限流键 / Rate-limit key of a context
+    fn key(context: &MiddlewareContext) -> String {
+        if let Some(key) = context.get_header("X-API-Key") {
+            return RateLimiter::api_key(key);
         }
-
-        *count += 1;
-        true
+        context
+            .get_metadata("client_ip")
+            .and_then(|ip| ip.parse().ok())
+            .map(RateLimiter::ip)
+            .unwrap_or_else(|| "anonymous".to_string())
     }
 }
 
@@ -540,14 +534,11 @@ impl WorkflowMiddleware for RateLimitingMiddleware {
     async fn before_request(&self, context: &mut MiddlewareContext) -> Result<(), String> {
         tracing::debug!("执行限流中间件 / Executing rate limiting middleware");
 
-        let _user_role = context
-            .get_metadata("user_role")
-            .unwrap_or(&"default".to_string());
-
-        // 注意：这里需要可变引用，但在 trait 中不能直接修改
-        // Note: This requires a mutable reference, but cannot directly modify in trait
-        // 在实际实现中，可能需要使用 Arc<Mutex<>> 或其他同步原语
-        // In actual implementation, might need to use Arc<Mutex<>> or other synchronization primitives
+        if let Err(limited) = self.limiter.check(&Self::key(context)) {
+            metrics::counter!("rate_limit_rejections_total", "source" => "middleware").increment(1);
+            context.set_metadata("retry_after_secs".to_string(), retry_after_secs(&limited).to_string());
+            return Err(limited.to_string());
+        }
 
         context.set_metadata("rate_limit_checked".to_string(), "true".to_string());
         Ok(())
@@ -571,6 +562,92 @@ impl WorkflowMiddleware for RateLimitingMiddleware {
     }
 }
 
+/// 指标中间件 / Metrics Middleware
+///
+/// 通过 [`metrics`] 门面记录请求数与耗时（自 [`MiddlewareContext::start_time`] 起），以 `workflow_id`、
+/// `workflow_name`（取自同名元数据）与 `outcome` 为标签；错误率即 `outcome="error"` 的占比。
+/// Records request counts and durations (from [`MiddlewareContext::start_time`]) through the [`metrics`] facade,
+/// labelled by `workflow_id`, `workflow_name` (from the metadata of that name) and `outcome`; the error rate is the
+/// share of `outcome="error"`. 以最高优先级包裹整个链，因此能看到每个请求的结果。
+/// It wraps the whole chain at the highest priority, so it sees the outcome of every request.
+pub struct MetricsMiddleware {
+    name: String,
+    version: String,
+    description: String,
+    priority: MiddlewarePriority,
+}
+
+impl Default for MetricsMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsMiddleware {
+    /// 创建指标中间件 / Create metrics middleware
+    pub fn new() -> Self {
+        Self {
+            name: "MetricsMiddleware".to_string(),
+            version: "1.0.0".to_string(),
+            description: "工作流指标中间件 / Workflow metrics middleware".to_string(),
+            priority: MiddlewarePriority::Critical,
+        }
+    }
+
+    /// 记录一个结束的请求 / Record a finished request
+    fn record(context: &MiddlewareContext, outcome: &'static str) {
+        let workflow_name = context
+            .get_metadata("workflow_name")
+            .cloned()
+            .unwrap_or_else(|| "unknown".to_string());
+        let labels = [
+            ("workflow_id", context.workflow_id.clone()),
+            ("workflow_name", workflow_name),
+            ("outcome", outcome.to_string()),
+        ];
+        counter!(MIDDLEWARE_REQUESTS, &labels).increment(1);
+        histogram!(MIDDLEWARE_REQUEST_DURATION, &labels).record(context.start_time.elapsed().as_secs_f64());
+    }
+}
+
+#[async_trait]
+impl WorkflowMiddleware for MetricsMiddleware {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn priority(&self) -> MiddlewarePriority {
+        self.priority
+    }
+
+    async fn before_request(&self, _context: &mut MiddlewareContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn after_request(&self, context: &mut MiddlewareContext) -> Result<(), String> {
+        // 最外层中间件，内层出错时不会到这里 / The outermost middleware; a failure further in skips this
+        Self::record(context, "ok");
+        Ok(())
+    }
+
+    async fn handle_error(
+        &self,
+        context: &mut MiddlewareContext,
+        _error: &str,
+    ) -> Result<(), String> {
+        Self::record(context, "error");
+        Ok(())
+    }
+}
+
 /// 初始化核心中间件 / Initialize core middleware
 pub fn init_core_middleware() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("初始化核心中间件 / Initializing core middleware");
@@ -681,4 +758,64 @@ mod tests {
         let result = middleware.before_request(&mut context).await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_rate_limiting_middleware_rejects_per_client() {
+        use crate::middleware::rate_limit::TokenBucketConfig;
+
+        let limiter = Arc::new(RateLimiter::new(TokenBucketConfig::per_minute(1)));
+        let middleware = RateLimitingMiddleware::with_limiter(limiter);
+        let context = |ip: &str| {
+            let mut context = MiddlewareContext::new("req".to_string(), "workflow_1".to_string(), serde_json::json!({}));
+            context.set_metadata("client_ip".to_string(), ip.to_string());
+            context
+        };
+
+        assert!(middleware.before_request(&mut context("10.0.0.1")).await.is_ok());
+        let mut limited = context("10.0.0.1");
+        assert!(middleware.before_request(&mut limited).await.is_err());
+        assert!(limited.get_metadata("retry_after_secs").is_some());
+        assert!(middleware.before_request(&mut context("10.0.0.2")).await.is_ok());
+    }
+
+    #[test]
+    fn test_metrics_middleware_records_requests_and_errors() {
+        let recorder = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            futures::executor::block_on(async {
+                let mut manager = crate::middleware::WorkflowMiddlewareManager::new();
+                manager.register_middleware(Box::new(AuthenticationMiddleware::new()));
+                let request = |token: &str| {
+                    let mut context =
+                        MiddlewareContext::new("req".to_string(), "wf-1".to_string(), serde_json::json!({}));
+                    context.set_header("Authorization".to_string(), token.to_string());
+                    context.set_metadata("workflow_name".to_string(), "order".to_string());
+                    context
+                };
+                manager.create_chain(request("admin_token_123")).await.unwrap().execute().await.unwrap();
+                manager.create_chain(request("admin_token_123")).await.unwrap().execute().await.unwrap();
+                assert!(manager.create_chain(request("forged")).await.unwrap().execute().await.is_err());
+            })
+        });
+        let rendered = handle.render();
+        assert!(
+            rendered.contains(
+                r#"workflow_middleware_requests_total{workflow_id="wf-1",workflow_name="order",outcome="ok"} 2"#
+            ),
+            "{rendered}"
+        );
+        assert!(
+            rendered.contains(
+                r#"workflow_middleware_requests_total{workflow_id="wf-1",workflow_name="order",outcome="error"} 1"#
+            ),
+            "{rendered}"
+        );
+        assert!(
+            rendered.contains(
+                r#"workflow_middleware_request_duration_seconds_count{workflow_id="wf-1",workflow_name="order",outcome="ok"} 2"#
+            ),
+            "{rendered}"
+        );
+    }
 }
@@ -5,7 +5,6 @@
 
 use crate::middleware::{MiddlewareContext, MiddlewarePriority, WorkflowMiddleware};
 use async_trait::async_trait;
-use std::collections::HashMap;
 
 /// 初始化扩展中间件 / Initialize extension middleware
 pub fn init_extension_middleware() -> Result<(), Box<dyn std::error::Error>> {
@@ -13,131 +12,8 @@ pub fn init_extension_middleware() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-/// 缓存中间件 / Caching Middleware
-///
-/// 提供工作流请求的缓存功能。
-/// Provides caching functionality for workflow requests.
-pub struct CachingMiddleware {
-    name: String,
-    version: String,
-    description: String,
-    priority: MiddlewarePriority,
-    cache: HashMap<String, CachedItem>,
-}
-
-/// 缓存项 / Cached Item
-#[derive(Debug, Clone)]
-struct CachedItem {
-    data: serde_json::Value,
-    timestamp: std::time::Instant,
-    ttl: std::time::Duration,
-}
-
-impl Default for CachingMiddleware {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-#[allow(dead_code)]
-impl CachingMiddleware {
-    /// 创建缓存中间件 / Create caching middleware
-    pub fn new() -> Self {
-        Self {
-            name: "CachingMiddleware".to_string(),
-            version: "1.0.0".to_string(),
-            description: "工作流缓存中间件 / Workflow caching middleware".to_string(),
-            priority: MiddlewarePriority::Normal,
-            cache: HashMap::new(),
-        }
-    }
-
-    /// 获取缓存项 / Get cached item
-    fn get_cached_item(&self, key: &str) -> Option<&CachedItem> {
-        self.cache.get(key).and_then(|item| {
-            if item.timestamp.elapsed() < item.ttl {
-                Some(item)
-            } else {
-                None
-            }
-        })
-    }
-
-    /// 设置缓存项 / Set cached item
-    fn set_cached_item(&mut self, key: String, data: serde_json::Value, ttl: std::time::Duration) {
-        let item = CachedItem {
-            data,
-            timestamp: std::time::Instant::now(),
-            ttl,
-        };
-        self.cache.insert(key, item);
-    }
-}
-
-#[async_trait]
-impl WorkflowMiddleware for CachingMiddleware {
-    fn name(&self) -> &str {
-        &self.name
-    }
-
-    fn version(&self) -> &str {
-        &self.version
-    }
-
-    fn description(&self) -> &str {
-        &self.description
-    }
-
-    fn priority(&self) -> MiddlewarePriority {
-        self.priority
-    }
-
-    async fn before_request(&self, context: &mut MiddlewareContext) -> Result<(), String> {
-        tracing::debug!("执行缓存中间件 / Executing caching middleware");
-
-        // 检查是否有缓存 / Check if there's a cache
-        let cache_key = format!("{}_{}", context.workflow_id, context.request_id);
-
-        if let Some(cached_item) = self.get_cached_item(&cache_key) {
-            tracing::info!("使用缓存数据 / Using cached data for key: {}", cache_key);
-            context.set_metadata("cache_hit".to_string(), "true".to_string());
-            context.set_metadata("cached_data".to_string(), cached_item.data.to_string());
-        } else {
-            context.set_metadata("cache_hit".to_string(), "false".to_string());
-        }
-
-        Ok(())
-    }
-
-    async fn after_request(&self, context: &mut MiddlewareContext) -> Result<(), String> {
-        tracing::debug!("缓存中间件请求后处理 / Caching middleware after request processing");
-
-        // 如果请求成功且没有缓存命中，则缓存结果 / If request succeeded and no cache hit, cache the result
-        if context.get_metadata("cache_hit") == Some(&"false".to_string()) {
-            let cache_key = format!("{}_{}", context.workflow_id, context.request_id);
-            let ttl = std::time::Duration::from_secs(300); // 5分钟缓存 / 5 minutes cache
-
-            // 注意：这里需要可变引用，但在 trait 中不能直接修改
-            // Note: This requires a mutable reference, but cannot directly modify in trait
-            context.set_metadata("cache_key".to_string(), cache_key);
-            context.set_metadata("cache_ttl".to_string(), ttl.as_secs().to_string());
-        }
-
-        Ok(())
-    }
-
-    async fn handle_error(
-        &self,
-        _context: &mut MiddlewareContext,
-        error: &str,
-    ) -> Result<(), String> {
-        tracing::error!(
-            "缓存中间件错误处理 / Caching middleware error handling: {}",
-            error
-        );
-        Ok(())
-    }
-}
+/// 缓存中间件，见 [`cache`](crate::middleware::cache) / Caching Middleware, see [`cache`](crate::middleware::cache)
+pub use crate::middleware::cache::CachingMiddleware;
 
 /// 压缩中间件 / Compression Middleware
 ///
@@ -611,26 +487,6 @@ impl WorkflowMiddleware for TimeoutMiddleware {
 mod tests {
     use super::*;
 
-    #[tokio::test]
-    async fn test_caching_middleware() {
-        let middleware = CachingMiddleware::new();
-        assert_eq!(middleware.name(), "CachingMiddleware");
-        assert_eq!(middleware.priority(), MiddlewarePriority::Normal);
-
-        let mut context = MiddlewareContext::new(
-            "req_1".to_string(),
-            "workflow_1".to_string(),
-            serde_json::json!({}),
-        );
-
-        let result = middleware.before_request(&mut context).await;
-        assert!(result.is_ok());
-        assert_eq!(
-            context.get_metadata("cache_hit"),
-            Some(&"false".to_string())
-        );
-    }
-
     #[tokio::test]
     async fn test_compression_middleware() {
         let middleware = CompressionMiddleware::new();
@@ -3,9 +3,13 @@
 //! 本模块提供了工作流中间件系统，包括认证、授权、日志、监控等功能
 //! This module provides a workflow middleware system including authentication, authorization, logging, monitoring, etc.
 
+pub mod cache;
 pub mod core;
 pub mod extensions;
 pub mod plugins;
+pub mod rate_limit;
+pub mod rbac;
+pub mod validation;
 
 // 重新导出主要类型 / Re-export main types
 pub use core::*;
@@ -43,6 +47,71 @@ pub trait WorkflowMiddleware: Send + Sync {
     async fn before_request(&self, context: &mut MiddlewareContext) -> Result<(), String>;
     async fn after_request(&self, context: &mut MiddlewareContext) -> Result<(), String>;
     async fn handle_error(&self, context: &mut MiddlewareContext, error: &str) -> Result<(), String>;
+
+    /// 包裹链的其余部分 / Wrap the rest of the chain
+    ///
+    /// 默认先调用 `before_request`；它未设置响应时运行 `next`，再调用 `after_request`。任一步出错时调用
+    /// `handle_error` 并把错误传给外层。
+    /// By default calls `before_request`, runs `next` unless that set a response, then calls `after_request`. When
+    /// any step fails it calls `handle_error` and passes the error on to the enclosing middleware.
+    async fn handle(&self, context: &mut MiddlewareContext, next: Next<'_>) -> Result<(), String> {
+        let result = async {
+            self.before_request(context).await?;
+            if context.response.is_none() {
+                next.run(context).await?;
+            }
+            self.after_request(context).await
+        }
+        .await;
+        if let Err(e) = &result {
+            let _ = self.handle_error(context, e).await;
+        }
+        result
+    }
+}
+
+/// 链末端生成响应的处理器 / Handler producing the response at the end of a chain
+#[async_trait::async_trait]
+pub trait MiddlewareHandler: Send + Sync {
+    async fn call(&self, context: &MiddlewareContext) -> Result<serde_json::Value, String>;
+}
+
+#[async_trait::async_trait]
+impl<F, Fut> MiddlewareHandler for F
+where
+    F: Fn(MiddlewareContext) -> Fut + Send + Sync,
+    Fut: std::future::Future<Output = Result<serde_json::Value, String>> + Send,
+{
+    async fn call(&self, context: &MiddlewareContext) -> Result<serde_json::Value, String> {
+        (self)(context.clone()).await
+    }
+}
+
+/// 链中尚未进入的部分 / The part of a chain not entered yet
+pub struct Next<'a> {
+    middlewares: &'a [std::sync::Arc<dyn WorkflowMiddleware>],
+    handler: Option<&'a dyn MiddlewareHandler>,
+}
+
+impl Next<'_> {
+    /// 运行其余中间件，最后由处理器设置响应 / Run the remaining middleware, then let the handler set the response
+    pub async fn run(self, context: &mut MiddlewareContext) -> Result<(), String> {
+        match self.middlewares.split_first() {
+            Some((middleware, rest)) => {
+                let next = Next {
+                    middlewares: rest,
+                    handler: self.handler,
+                };
+                middleware.handle(context, next).await
+            }
+            None => {
+                if let Some(handler) = self.handler {
+                    context.response = Some(handler.call(context).await?);
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 /// 中间件上下文 / Middleware Context
@@ -54,6 +123,8 @@ pub struct MiddlewareContext {
     pub start_time: std::time::Instant,
     pub headers: std::collections::HashMap<String, String>,
     pub metadata: std::collections::HashMap<String, String>,
+    /// 处理器或短路的中间件设置的响应 / Response set by the handler or a short-circuiting middleware
+    pub response: Option<serde_json::Value>,
 }
 
 impl MiddlewareContext {
@@ -65,8 +136,15 @@ impl MiddlewareContext {
             start_time: std::time::Instant::now(),
             headers: std::collections::HashMap::new(),
             metadata: std::collections::HashMap::new(),
+            response: None,
         }
     }
+
+    /// 设置响应；在 `before_request` 中设置时跳过链的其余部分 / Set the response; set in `before_request`, it skips
+    /// the rest of the chain
+    pub fn respond(&mut self, response: serde_json::Value) {
+        self.response = Some(response);
+    }
     
     pub fn set_header(&mut self, key: String, value: String) {
         self.headers.insert(key, value);
@@ -99,7 +177,15 @@ pub enum MiddlewareError {
 }
 
 impl WorkflowMiddlewareManager {
+    /// 创建管理器，默认注册 [`MetricsMiddleware`] / Create a manager with [`MetricsMiddleware`] registered
     pub fn new() -> Self {
+        Self {
+            middlewares: vec![std::sync::Arc::new(MetricsMiddleware::new())],
+        }
+    }
+
+    /// 不注册任何中间件的管理器 / Manager without any middleware registered
+    pub fn empty() -> Self {
         Self {
             middlewares: Vec::new(),
         }
@@ -122,35 +208,181 @@ impl WorkflowMiddlewareManager {
 }
 
 /// 中间件链 / Middleware Chain
+///
+/// 洋葱模型：按优先级排序的中间件依次包裹下一个，前置钩子按此顺序运行，后置钩子按相反顺序运行；
+/// 出错时只通知已进入的中间件。
+/// Onion model: middleware sorted by priority each wrap the next, so before hooks run in that order and after hooks
+/// in reverse; on failure only the middleware already entered are told.
 pub struct MiddlewareChain {
     middlewares: Vec<std::sync::Arc<dyn WorkflowMiddleware>>,
     context: MiddlewareContext,
 }
 
 impl MiddlewareChain {
+    /// 不带处理器运行链 / Run the chain without a handler
     pub async fn execute(&mut self) -> Result<MiddlewareContext, MiddlewareError> {
-        // 执行 before_request 阶段 / Execute before_request phase
-        for middleware in &self.middlewares {
-            if let Err(e) = middleware.before_request(&mut self.context).await {
-                // 处理错误 / Handle error
-                for error_middleware in &self.middlewares {
-                    let _ = error_middleware.handle_error(&mut self.context, &e).await;
-                }
-                return Err(MiddlewareError::ProcessingError(e));
+        self.run(None).await
+    }
+
+    /// 运行链，除非有中间件短路，由 `handler` 生成响应 / Run the chain, with `handler` producing the response unless
+    /// a middleware short-circuits
+    pub async fn execute_with(&mut self, handler: impl MiddlewareHandler) -> Result<MiddlewareContext, MiddlewareError> {
+        self.run(Some(&handler)).await
+    }
+
+    async fn run(&mut self, handler: Option<&dyn MiddlewareHandler>) -> Result<MiddlewareContext, MiddlewareError> {
+        let next = Next {
+            middlewares: &self.middlewares,
+            handler,
+        };
+        next.run(&mut self.context).await.map_err(MiddlewareError::ProcessingError)?;
+        Ok(self.context.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    /// 记录钩子调用，可在前置钩子中短路或失败 / Records hook calls; may short-circuit or fail in its before hook
+    struct Recorder {
+        name: &'static str,
+        priority: MiddlewarePriority,
+        calls: Arc<Mutex<Vec<String>>>,
+        short_circuit: bool,
+        fail: bool,
+    }
+
+    impl Recorder {
+        fn new(name: &'static str, priority: MiddlewarePriority, calls: &Arc<Mutex<Vec<String>>>) -> Self {
+            Self {
+                name,
+                priority,
+                calls: calls.clone(),
+                short_circuit: false,
+                fail: false,
             }
         }
-        
-        // 执行 after_request 阶段 / Execute after_request phase
-        for middleware in &self.middlewares {
-            if let Err(e) = middleware.after_request(&mut self.context).await {
-                // 处理错误 / Handle error
-                for error_middleware in &self.middlewares {
-                    let _ = error_middleware.handle_error(&mut self.context, &e).await;
-                }
-                return Err(MiddlewareError::ProcessingError(e));
+    }
+
+    #[async_trait::async_trait]
+    impl WorkflowMiddleware for Recorder {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+
+        fn description(&self) -> &str {
+            "test"
+        }
+
+        fn priority(&self) -> MiddlewarePriority {
+            self.priority
+        }
+
+        async fn before_request(&self, context: &mut MiddlewareContext) -> Result<(), String> {
+            self.calls.lock().push(format!("before {}", self.name));
+            if self.fail {
+                return Err(format!("{} failed", self.name));
+            }
+            if self.short_circuit {
+                context.respond(serde_json::json!({"from": self.name}));
             }
+            Ok(())
         }
-        
-        Ok(self.context.clone())
+
+        async fn after_request(&self, context: &mut MiddlewareContext) -> Result<(), String> {
+            self.calls.lock().push(format!("after {}", self.name));
+            if let Some(serde_json::Value::Object(response)) = &mut context.response {
+                response.insert(format!("seen_by_{}", self.name), true.into());
+            }
+            Ok(())
+        }
+
+        async fn handle_error(&self, _context: &mut MiddlewareContext, error: &str) -> Result<(), String> {
+            self.calls.lock().push(format!("error {}: {}", self.name, error));
+            Ok(())
+        }
+    }
+
+    fn manager(calls: &Arc<Mutex<Vec<String>>>, inner: Recorder) -> WorkflowMiddlewareManager {
+        let mut manager = WorkflowMiddlewareManager::empty();
+        manager.register_middleware(Box::new(inner));
+        manager.register_middleware(Box::new(Recorder::new("outer", MiddlewarePriority::Critical, calls)));
+        manager.register_middleware(Box::new(Recorder::new("middle", MiddlewarePriority::Normal, calls)));
+        manager
+    }
+
+    fn context() -> MiddlewareContext {
+        MiddlewareContext::new("req".to_string(), "wf-1".to_string(), serde_json::json!({"n": 1}))
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_chain_wraps_handler_and_runs_after_hooks_in_reverse() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let manager = manager(&calls, Recorder::new("inner", MiddlewarePriority::Low, &calls));
+        let handled = calls.clone();
+        let context = manager
+            .create_chain(context())
+            .await
+            .unwrap()
+            .execute_with(move |context: MiddlewareContext| {
+                let handled = handled.clone();
+                async move {
+                    handled.lock().push("handler".to_string());
+                    Ok::<_, String>(serde_json::json!({"n": context.data["n"]}))
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            *calls.lock(),
+            ["before outer", "before middle", "before inner", "handler", "after inner", "after middle", "after outer"]
+        );
+        assert_eq!(
+            context.response.unwrap(),
+            serde_json::json!({"n": 1, "seen_by_inner": true, "seen_by_middle": true, "seen_by_outer": true})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chain_short_circuits_with_response() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut middle = Recorder::new("cache", MiddlewarePriority::High, &calls);
+        middle.short_circuit = true;
+        let manager = manager(&calls, middle);
+        let context = manager
+            .create_chain(context())
+            .await
+            .unwrap()
+            .execute_with(|_: MiddlewareContext| async {
+                Err::<serde_json::Value, _>("handler must not run".to_string())
+            })
+            .await
+            .unwrap();
+        assert_eq!(*calls.lock(), ["before outer", "before cache", "after cache", "after outer"]);
+        assert_eq!(
+            context.response.unwrap(),
+            serde_json::json!({"from": "cache", "seen_by_cache": true, "seen_by_outer": true})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chain_errors_reach_only_entered_middleware() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut failing = Recorder::new("auth", MiddlewarePriority::High, &calls);
+        failing.fail = true;
+        let manager = manager(&calls, failing);
+        let error = manager.create_chain(context()).await.unwrap().execute().await.unwrap_err();
+        assert!(error.to_string().ends_with("auth failed"));
+        assert_eq!(
+            *calls.lock(),
+            ["before outer", "before auth", "error auth: auth failed", "error outer: auth failed"]
+        );
+    }
+}
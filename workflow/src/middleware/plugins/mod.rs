@@ -3,7 +3,7 @@
 //! 本模块实现了工作流系统的插件中间件，支持动态加载和插件生命周期管理。
 //! This module implements plugin middleware for workflow systems, supporting dynamic loading and plugin lifecycle management.
 
-use crate::middleware::{MiddlewareContext, MiddlewarePriority, WorkflowMiddleware};
+use crate::middleware::{MiddlewareContext, MiddlewarePriority, Next, WorkflowMiddleware};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -285,6 +285,17 @@ impl WorkflowMiddleware for PluginMiddlewareWrapper {
 
         self.plugin.handle_error(context, error).await
     }
+
+    async fn handle(&self, context: &mut MiddlewareContext, next: Next<'_>) -> Result<(), String> {
+        if !self.is_available() {
+            return Err(format!(
+                "插件 {} 不可用 / Plugin {} is not available",
+                self.plugin_id, self.plugin_id
+            ));
+        }
+
+        self.plugin.handle(context, next).await
+    }
 }
 
 /// 插件加载器 / Plugin Loader
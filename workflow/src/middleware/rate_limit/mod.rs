@@ -0,0 +1,267 @@
+//! # 令牌桶限流 / Token-Bucket Rate Limiting
+//!
+//! [`RateLimiter`] 为每个客户端（API Key 或 IP）维护一个令牌桶，同时供
+//! [`RateLimitingMiddleware`](crate::middleware::RateLimitingMiddleware) 与 HTTP 层 [`limit`] 使用。
+//! [`RateLimiter`] keeps one token bucket per client (API key or IP) and backs both the
+//! [`RateLimitingMiddleware`](crate::middleware::RateLimitingMiddleware) and the HTTP layer [`limit`].
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+
+/// 空闲桶的清理间隔 / How often idle buckets are dropped
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 令牌桶参数 / Token bucket parameters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenBucketConfig {
+    /// 桶容量，即允许的突发请求数 / Bucket capacity, i.e. the allowed burst
+    pub capacity: u32,
+    /// 每秒补充的令牌数 / Tokens added per second
+    pub refill_per_second: f64,
+}
+
+impl TokenBucketConfig {
+    /// 每秒 `n` 个请求，突发 `n` / `n` requests per second with a burst of `n`
+    pub fn per_second(n: u32) -> Self {
+        Self {
+            capacity: n,
+            refill_per_second: f64::from(n),
+        }
+    }
+
+    /// 每分钟 `n` 个请求，突发 `n` / `n` requests per minute with a burst of `n`
+    pub fn per_minute(n: u32) -> Self {
+        Self {
+            capacity: n,
+            refill_per_second: f64::from(n) / 60.0,
+        }
+    }
+
+    pub fn burst(mut self, capacity: u32) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// 空桶补满所需时间 / Time for an empty bucket to fill up
+    fn fill_time(&self) -> Duration {
+        Duration::from_secs_f64(f64::from(self.capacity) / self.refill_per_second.max(f64::MIN_POSITIVE))
+    }
+}
+
+impl Default for TokenBucketConfig {
+    fn default() -> Self {
+        Self::per_minute(100)
+    }
+}
+
+/// 被限流 / Request was rate limited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("请求过于频繁 / Rate limit exceeded, retry after {retry_after:?}")]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Buckets {
+    by_key: HashMap<String, Bucket>,
+    swept: Instant,
+}
+
+/// 按客户端键的令牌桶限流器 / Token-bucket limiter keyed by client
+pub struct RateLimiter {
+    default: TokenBucketConfig,
+    overrides: HashMap<String, TokenBucketConfig>,
+    buckets: Mutex<Buckets>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(TokenBucketConfig::default())
+    }
+}
+
+impl RateLimiter {
+    pub fn new(default: TokenBucketConfig) -> Self {
+        Self {
+            default,
+            overrides: HashMap::new(),
+            buckets: Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                swept: Instant::now(),
+            }),
+        }
+    }
+
+    /// 为某个键设置单独的限额 / Give one key its own limit
+    pub fn limit_for(mut self, key: impl Into<String>, config: TokenBucketConfig) -> Self {
+        self.overrides.insert(key.into(), config);
+        self
+    }
+
+    /// API Key 对应的键；不保存 Key 本身 / Key for an API key, without keeping the key itself
+    pub fn api_key(key: &str) -> String {
+        let digest = Sha256::digest(key.as_bytes());
+        let hex: String = digest.iter().take(8).map(|b| format!("{b:02x}")).collect();
+        format!("key:{hex}")
+    }
+
+    /// IP 对应的键 / Key for a client IP
+    pub fn ip(ip: IpAddr) -> String {
+        format!("ip:{ip}")
+    }
+
+    fn config(&self, key: &str) -> TokenBucketConfig {
+        self.overrides.get(key).copied().unwrap_or(self.default)
+    }
+
+    /// 为 `key` 消耗一个令牌 / Take one token for `key`
+    pub fn check(&self, key: &str) -> Result<(), RateLimited> {
+        let config = self.config(key);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock();
+        if now.duration_since(buckets.swept) >= SWEEP_INTERVAL {
+            self.sweep(&mut buckets, now);
+        }
+        let bucket = buckets.by_key.entry(key.to_string()).or_insert(Bucket {
+            tokens: f64::from(config.capacity),
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * config.refill_per_second).min(f64::from(config.capacity));
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let missing = 1.0 - bucket.tokens;
+        Err(RateLimited {
+            retry_after: Duration::from_secs_f64(missing / config.refill_per_second.max(f64::MIN_POSITIVE)),
+        })
+    }
+
+    /// 删除已补满的桶 / Drop buckets that have refilled completely
+    fn sweep(&self, buckets: &mut Buckets, now: Instant) {
+        buckets
+            .by_key
+            .retain(|key, bucket| now.duration_since(bucket.updated) < self.config(key).fill_time());
+        buckets.swept = now;
+    }
+
+    /// 当前跟踪的客户端数 / Number of clients currently tracked
+    pub fn tracked_clients(&self) -> usize {
+        self.buckets.lock().by_key.len()
+    }
+}
+
+/// Retry-After 头的秒数，向上取整 / Seconds for a Retry-After header, rounded up
+pub fn retry_after_secs(limited: &RateLimited) -> u64 {
+    limited.retry_after.as_secs_f64().ceil().max(1.0) as u64
+}
+
+/// 请求的限流键：`X-API-Key`，否则为客户端 IP / Rate-limit key of a request: its `X-API-Key`, else the client IP
+///
+/// IP 取自连接信息（需以 `into_make_service_with_connect_info` 提供），否则取 `X-Forwarded-For` 的第一项。
+/// The IP comes from the connection info (served with `into_make_service_with_connect_info`), else from the first
+/// `X-Forwarded-For` entry.
+pub fn request_key(req: &Request<Body>) -> String {
+    if let Some(key) = req.headers().get(crate::http::auth::API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+        return RateLimiter::api_key(key);
+    }
+    let ip = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+        .or_else(|| {
+            req.headers()
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .and_then(|ip| ip.trim().parse().ok())
+        });
+    match ip {
+        Some(ip) => RateLimiter::ip(ip),
+        None => "unknown".to_string(),
+    }
+}
+
+/// HTTP 限流中间件，探针除外 / HTTP rate-limiting middleware, except for the probes
+pub async fn limit(State(limiter): State<Arc<RateLimiter>>, req: Request<Body>, next: Next) -> Response {
+    if matches!(req.uri().path(), "/health" | "/livez" | "/readyz") {
+        return next.run(req).await;
+    }
+    match limiter.check(&request_key(&req)) {
+        Ok(()) => next.run(req).await,
+        Err(limited) => {
+            metrics::counter!("rate_limit_rejections_total", "source" => "http").increment(1);
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(serde_json::json!({ "code": "RATE_LIMITED", "message": limited.to_string() })),
+            )
+                .into_response();
+            response.headers_mut().insert(header::RETRY_AFTER, retry_after_secs(&limited).into());
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_burst_then_refills() {
+        let limiter = RateLimiter::new(TokenBucketConfig::per_second(20).burst(2));
+        assert!(limiter.check("a").is_ok());
+        assert!(limiter.check("a").is_ok());
+        let limited = limiter.check("a").unwrap_err();
+        assert!(limited.retry_after <= Duration::from_millis(50));
+        assert_eq!(retry_after_secs(&limited), 1);
+        // 其他客户端不受影响 / other clients are unaffected
+        assert!(limiter.check("b").is_ok());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(limiter.check("a").is_ok());
+    }
+
+    #[test]
+    fn test_overrides_and_keys() {
+        let key = RateLimiter::api_key("s3cret");
+        assert!(!key.contains("s3cret"));
+        let limiter = RateLimiter::new(TokenBucketConfig::per_minute(1)).limit_for(key.clone(), TokenBucketConfig::per_minute(3));
+        for _ in 0..3 {
+            assert!(limiter.check(&key).is_ok());
+        }
+        assert!(limiter.check(&key).is_err());
+
+        let ip = RateLimiter::ip("10.0.0.1".parse().unwrap());
+        assert!(limiter.check(&ip).is_ok());
+        assert!(limiter.check(&ip).unwrap_err().retry_after > Duration::from_secs(50));
+        assert_eq!(limiter.tracked_clients(), 2);
+    }
+
+    #[test]
+    fn test_request_key_prefers_api_key() {
+        let req = Request::get("/api/versions")
+            .header("x-forwarded-for", "203.0.113.7, 10.0.0.1")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(request_key(&req), "ip:203.0.113.7");
+        let req = Request::get("/api/versions").header("x-api-key", "k").body(Body::empty()).unwrap();
+        assert_eq!(request_key(&req), RateLimiter::api_key("k"));
+    }
+}
@@ -0,0 +1,444 @@
+//! # 基于角色的访问控制 / Role-Based Access Control
+//!
+//! 角色按权限递增为 viewer、operator、admin：viewer 可查询与列出，operator 还可启动、发送信号与取消，
+//! admin 还可终止、删除及修改服务设置。角色从 JWT 声明或 API Key 名称映射而来，按路由对应的操作校验。
+//! Roles are, by increasing power, viewer, operator and admin: viewers may describe, query and list; operators may
+//! also start, signal and cancel; admins may also terminate, delete and change service settings. Roles are mapped
+//! from JWT claims or API key names and checked against the operation a route performs.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+
+use crate::http::auth::{requires_auth, AuthMethod, Principal, SignedHookRequest};
+use crate::middleware::{MiddlewareContext, MiddlewareError, MiddlewarePriority, WorkflowMiddleware};
+
+/// 角色 / Role; each role may do everything the roles below it may
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Viewer,
+    Operator,
+    Admin,
+}
+
+impl std::str::FromStr for Role {
+    type Err = MiddlewareError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "operator" => Ok(Role::Operator),
+            "admin" => Ok(Role::Admin),
+            _ => Err(MiddlewareError::AuthorizationFailed(format!("unknown role: {}", s))),
+        }
+    }
+}
+
+/// 受控操作 / Operation subject to authorization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Describe,
+    List,
+    Query,
+    History,
+    Start,
+    Signal,
+    Cancel,
+    Terminate,
+    Delete,
+    /// 修改服务设置，如只读开关 / Change service settings such as the read-only switch
+    Administer,
+}
+
+impl Operation {
+    /// 默认所需角色 / Role required by default
+    pub fn default_role(self) -> Role {
+        match self {
+            Operation::Describe | Operation::List | Operation::Query | Operation::History => Role::Viewer,
+            Operation::Start | Operation::Signal | Operation::Cancel => Role::Operator,
+            Operation::Terminate | Operation::Delete | Operation::Administer => Role::Admin,
+        }
+    }
+
+    /// 路由执行的操作；未知的写请求与读取审计日志视为管理操作 /
+    /// Operation performed by a route; unknown writes and reading the audit log count as administration
+    pub fn for_route(method: &Method, path: &str) -> Operation {
+        let mut segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        // 命名空间内的路由与根路由同义 / Routes inside a namespace mean the same as the root routes
+        if let ["api", "v1", "namespaces", _, _, ..] = segments.as_slice() {
+            segments.drain(2..4);
+        }
+        let workflow = match segments.as_slice() {
+            ["api", "v1", "workflows", rest @ ..] => Some(rest),
+            ["api", "v1", "audit", ..] => return Operation::Administer,
+            // 剖析端点占用 CPU 并暴露内部细节 / Profiling endpoints load the CPU and expose internals
+            ["debug", ..] => return Operation::Administer,
+            // 内省端点暴露部署细节 / Introspection endpoints expose deployment details
+            ["api", "v1", "admin", rest @ ..] if rest != ["read-only"] => return Operation::Administer,
+            // 完成人工任务即向其工作流发送信号 / Completing a human task signals its workflow
+            ["api", "v1", "tasks", _, "complete"] if method == Method::POST => return Operation::Signal,
+            ["api", "v1", "tasks"] if method == Method::GET => return Operation::List,
+            ["api", "v1", "batches"] if method == Method::GET => return Operation::List,
+            // Webhook 将回调转为信号 / Hooks turn callbacks into signals
+            ["api", "v1", "hooks", _] if method == Method::POST => return Operation::Signal,
+            _ => None,
+        };
+        match (method, workflow) {
+            (&Method::DELETE, _) => Operation::Delete,
+            (&Method::POST, Some([])) => Operation::Start,
+            // 更新与信号一样改变运行中的工作流 / Updates change a running workflow like signals do
+            (&Method::POST, Some([_, "signal" | "update", _])) => Operation::Signal,
+            (&Method::POST, Some([_, "cancel"])) => Operation::Cancel,
+            (&Method::POST, Some([_, "terminate"])) => Operation::Terminate,
+            (&Method::POST, Some([_, "query", _])) => Operation::Query,
+            (&Method::GET, Some([])) => Operation::List,
+            // 事件流同样读出完整历史，HEAD 由 GET 处理函数应答 / The event stream reads the full history too; HEAD runs GET
+            (&Method::GET | &Method::HEAD, Some([_, "history", ..])) => Operation::History,
+            (&Method::GET | &Method::HEAD | &Method::OPTIONS, _) => Operation::Describe,
+            _ => Operation::Administer,
+        }
+    }
+}
+
+/// 从调用方得到角色的规则 / Rules deriving roles from a principal
+#[derive(Debug, Clone)]
+pub struct RoleMapping {
+    /// 承载角色的 JWT 声明，支持点分路径 / JWT claim carrying roles, dotted paths allowed
+    pub claim: String,
+    /// 声明值到角色；缺省时按角色名匹配 / Claim values to roles; role names match when a value is absent
+    pub claim_values: HashMap<String, Role>,
+    /// API Key 名称到角色 / API key names to roles
+    pub api_keys: HashMap<String, Role>,
+    /// 无匹配时的角色 / Role when nothing matches
+    pub default_role: Option<Role>,
+}
+
+impl Default for RoleMapping {
+    fn default() -> Self {
+        Self {
+            claim: "roles".to_string(),
+            claim_values: HashMap::new(),
+            api_keys: HashMap::new(),
+            default_role: None,
+        }
+    }
+}
+
+impl RoleMapping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn claim(mut self, claim: impl Into<String>) -> Self {
+        self.claim = claim.into();
+        self
+    }
+
+    /// 将声明值 `value` 映射为 `role` / Map the claim value `value` to `role`
+    pub fn claim_value(mut self, value: impl Into<String>, role: Role) -> Self {
+        self.claim_values.insert(value.into(), role);
+        self
+    }
+
+    /// 为名为 `name` 的 API Key 赋予 `role` / Give the API key named `name` the role `role`
+    pub fn api_key(mut self, name: impl Into<String>, role: Role) -> Self {
+        self.api_keys.insert(name.into(), role);
+        self
+    }
+
+    pub fn default_role(mut self, role: Role) -> Self {
+        self.default_role = Some(role);
+        self
+    }
+
+    /// 调用方的最高角色 / Highest role of a principal
+    pub fn role_of(&self, principal: &Principal) -> Option<Role> {
+        let mapped = match principal.method {
+            AuthMethod::ApiKey => self.api_keys.get(&principal.id).copied(),
+            AuthMethod::Jwt => {
+                let claim = self
+                    .claim
+                    .split('.')
+                    .try_fold(&principal.claims, |value, key| value.get(key));
+                let values: Vec<&str> = match claim {
+                    Some(serde_json::Value::String(value)) => value.split_whitespace().collect(),
+                    Some(serde_json::Value::Array(values)) => values.iter().filter_map(|v| v.as_str()).collect(),
+                    _ => Vec::new(),
+                };
+                values
+                    .into_iter()
+                    .filter_map(|value| match self.claim_values.get(value) {
+                        Some(role) => Some(*role),
+                        None => value.parse().ok(),
+                    })
+                    .max()
+            }
+        };
+        mapped.or(self.default_role)
+    }
+}
+
+/// 授权策略 / Authorization policy
+#[derive(Debug, Clone, Default)]
+pub struct RbacPolicy {
+    pub mapping: RoleMapping,
+    /// 覆盖操作的默认角色 / Overrides of the default role of operations
+    pub required: HashMap<Operation, Role>,
+}
+
+impl RbacPolicy {
+    pub fn new(mapping: RoleMapping) -> Self {
+        Self {
+            mapping,
+            required: HashMap::new(),
+        }
+    }
+
+    /// 从环境变量读取；均未设置时返回 `None` / Read from the environment; `None` if nothing is set
+    ///
+    /// - `WORKFLOW_RBAC_CLAIM`: 承载角色的 JWT 声明 / JWT claim carrying roles
+    /// - `WORKFLOW_RBAC_API_KEYS`: `name:role` 逗号分隔 / comma-separated `name:role` pairs
+    /// - `WORKFLOW_RBAC_DEFAULT_ROLE`
+    pub fn from_env() -> Option<Self> {
+        let claim = std::env::var("WORKFLOW_RBAC_CLAIM").ok();
+        let api_keys = std::env::var("WORKFLOW_RBAC_API_KEYS").ok();
+        let default_role = std::env::var("WORKFLOW_RBAC_DEFAULT_ROLE").ok();
+        if claim.is_none() && api_keys.is_none() && default_role.is_none() {
+            return None;
+        }
+        let mut mapping = RoleMapping::new();
+        if let Some(claim) = claim {
+            mapping = mapping.claim(claim);
+        }
+        for entry in api_keys.iter().flat_map(|keys| keys.split(',')).map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.split_once(':').map(|(name, role)| (name, role.parse::<Role>())) {
+                Some((name, Ok(role))) => mapping = mapping.api_key(name, role),
+                _ => tracing::warn!(entry, "ignoring invalid WORKFLOW_RBAC_API_KEYS entry"),
+            }
+        }
+        match default_role.map(|role| role.parse::<Role>()) {
+            Some(Ok(role)) => mapping = mapping.default_role(role),
+            Some(Err(e)) => tracing::warn!(error = %e, "ignoring WORKFLOW_RBAC_DEFAULT_ROLE"),
+            None => {}
+        }
+        Some(Self::new(mapping))
+    }
+
+    /// 要求 `operation` 至少具有 `role` / Require at least `role` for `operation`
+    pub fn require(mut self, operation: Operation, role: Role) -> Self {
+        self.required.insert(operation, role);
+        self
+    }
+
+    pub fn required_role(&self, operation: Operation) -> Role {
+        self.required.get(&operation).copied().unwrap_or(operation.default_role())
+    }
+
+    /// 角色是否允许执行操作 / Whether a role may perform an operation
+    pub fn allows(&self, role: Role, operation: Operation) -> bool {
+        role >= self.required_role(operation)
+    }
+
+    /// 校验调用方 / Check a principal
+    pub fn authorize(&self, principal: &Principal, operation: Operation) -> Result<Role, MiddlewareError> {
+        let role = self.mapping.role_of(principal).ok_or_else(|| {
+            MiddlewareError::AuthorizationFailed(format!("{} has no role", principal.id))
+        })?;
+        if !self.allows(role, operation) {
+            return Err(MiddlewareError::AuthorizationFailed(format!(
+                "{} ({:?}) may not {:?}, requires {:?}",
+                principal.id,
+                role,
+                operation,
+                self.required_role(operation)
+            )));
+        }
+        Ok(role)
+    }
+}
+
+/// HTTP 授权中间件，需位于认证之后 / HTTP authorization middleware, to run after authentication
+pub async fn authorize(State(policy): State<Arc<RbacPolicy>>, req: Request<Body>, next: Next) -> Response {
+    // 签名即授权，由 Webhook 校验 / the signature, which the hook checks, is the authorization
+    if !requires_auth(req.uri().path()) || req.extensions().get::<SignedHookRequest>().is_some() {
+        return next.run(req).await;
+    }
+    let operation = Operation::for_route(req.method(), req.uri().path());
+    let result = match req.extensions().get::<Principal>() {
+        Some(principal) => policy.authorize(principal, operation),
+        None => Err(MiddlewareError::AuthorizationFailed("request is not authenticated".to_string())),
+    };
+    match result {
+        Ok(_) => next.run(req).await,
+        Err(e) => {
+            metrics::counter!("http_authorization_denied_total", "operation" => format!("{:?}", operation)).increment(1);
+            (
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({ "code": "FORBIDDEN", "message": e.to_string() })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// 工作流中间件形式的授权 / Authorization as a workflow middleware
+///
+/// 从上下文元数据读取 `user_role` 与 `operation`（如 `signal`）。
+/// Reads `user_role` and `operation` (such as `signal`) from the context metadata.
+pub struct RbacMiddleware {
+    policy: RbacPolicy,
+}
+
+impl RbacMiddleware {
+    pub fn new(policy: RbacPolicy) -> Self {
+        Self { policy }
+    }
+}
+
+#[async_trait]
+impl WorkflowMiddleware for RbacMiddleware {
+    fn name(&self) -> &str {
+        "RbacMiddleware"
+    }
+
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+
+    fn description(&self) -> &str {
+        "基于角色的访问控制 / Role-based access control"
+    }
+
+    fn priority(&self) -> MiddlewarePriority {
+        MiddlewarePriority::High
+    }
+
+    async fn before_request(&self, context: &mut MiddlewareContext) -> Result<(), String> {
+        let role: Role = context
+            .get_metadata("user_role")
+            .ok_or("用户角色未找到 / User role not found")?
+            .parse()
+            .map_err(|e: MiddlewareError| e.to_string())?;
+        let operation = context.get_metadata("operation").ok_or("操作未指定 / Operation not set")?;
+        let operation: Operation = serde_json::from_value(serde_json::Value::String(operation.clone()))
+            .map_err(|_| format!("未知操作 / Unknown operation: {}", operation))?;
+        if !self.policy.allows(role, operation) {
+            return Err(format!("角色 {:?} 不能执行 {:?} / Role {:?} may not {:?}", role, operation, role, operation));
+        }
+        context.set_metadata("authorized".to_string(), "true".to_string());
+        Ok(())
+    }
+
+    async fn after_request(&self, _context: &mut MiddlewareContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn handle_error(&self, _context: &mut MiddlewareContext, _error: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jwt(claims: serde_json::Value) -> Principal {
+        Principal {
+            id: "alice".to_string(),
+            method: AuthMethod::Jwt,
+            claims,
+        }
+    }
+
+    #[test]
+    fn test_routes_map_to_operations() {
+        let cases = [
+            (Method::GET, "/api/v1/workflows", Operation::List),
+            (Method::GET, "/api/v1/workflows/o-1", Operation::Describe),
+            (Method::GET, "/api/v1/workflows/o-1/history", Operation::History),
+            (Method::GET, "/api/v1/workflows/o-1/history/export", Operation::History),
+            (Method::GET, "/api/v1/workflows/o-1/history/stream", Operation::History),
+            (Method::HEAD, "/api/v1/workflows/o-1/history/stream", Operation::History),
+            (Method::POST, "/api/v1/workflows/o-1/history/import", Operation::Administer),
+            (Method::POST, "/api/v1/workflows", Operation::Start),
+            (Method::POST, "/api/v1/workflows/o-1/signal/approve", Operation::Signal),
+            (Method::POST, "/api/v1/workflows/o-1/update/add_item", Operation::Signal),
+            (Method::POST, "/api/v1/workflows/o-1/cancel", Operation::Cancel),
+            (Method::DELETE, "/api/v1/workflows/o-1", Operation::Delete),
+            (Method::POST, "/api/v1/admin/read-only", Operation::Administer),
+            (Method::GET, "/api/v1/audit", Operation::Administer),
+            (Method::GET, "/debug/pprof/profile", Operation::Administer),
+            (Method::GET, "/debug/pprof/heap", Operation::Administer),
+            (Method::POST, "/debug/pprof/executions/o-1", Operation::Administer),
+            (Method::GET, "/api/v1/admin/read-only", Operation::Describe),
+            (Method::GET, "/api/v1/admin/queues", Operation::Administer),
+            (Method::GET, "/api/v1/namespaces/acme/admin/worker", Operation::Administer),
+            (Method::GET, "/api/v1/tasks", Operation::List),
+            (Method::GET, "/api/v1/batches", Operation::List),
+            (Method::GET, "/api/v1/admin/dead-letters", Operation::Administer),
+            (Method::POST, "/api/v1/tasks/t-1/complete", Operation::Signal),
+            (Method::POST, "/api/v1/hooks/gateway", Operation::Signal),
+            (Method::POST, "/api/v1/namespaces/acme/hooks/gateway", Operation::Signal),
+            (Method::POST, "/api/v1/namespaces/acme/workflows", Operation::Start),
+            (Method::GET, "/api/v1/namespaces/acme/workflows/o-1/history", Operation::History),
+            (Method::GET, "/api/v1/namespaces/acme/workflows/o-1/history/stream", Operation::History),
+        ];
+        for (method, path, operation) in cases {
+            assert_eq!(Operation::for_route(&method, path), operation, "{method} {path}");
+        }
+    }
+
+    #[test]
+    fn test_roles_from_claims_and_api_keys() {
+        let mapping = RoleMapping::new()
+            .claim("realm_access.roles")
+            .claim_value("wf-ops", Role::Operator)
+            .api_key("ci", Role::Operator);
+        let principal = jwt(serde_json::json!({ "realm_access": { "roles": ["offline", "wf-ops", "viewer"] } }));
+        assert_eq!(mapping.role_of(&principal), Some(Role::Operator));
+        assert_eq!(mapping.role_of(&jwt(serde_json::json!({ "realm_access": { "roles": "admin" } }))), Some(Role::Admin));
+        assert_eq!(mapping.role_of(&jwt(serde_json::json!({}))), None);
+        assert_eq!(mapping.clone().default_role(Role::Viewer).role_of(&jwt(serde_json::json!({}))), Some(Role::Viewer));
+
+        let key = Principal {
+            id: "ci".to_string(),
+            method: AuthMethod::ApiKey,
+            claims: serde_json::json!({}),
+        };
+        assert_eq!(mapping.role_of(&key), Some(Role::Operator));
+    }
+
+    #[test]
+    fn test_policy_checks_role_hierarchy_and_overrides() {
+        let policy = RbacPolicy::new(RoleMapping::new());
+        let operator = jwt(serde_json::json!({ "roles": ["operator"] }));
+        assert!(policy.authorize(&operator, Operation::Query).is_ok());
+        assert!(policy.authorize(&operator, Operation::Signal).is_ok());
+        assert!(policy.authorize(&operator, Operation::Terminate).is_err());
+        assert!(policy.authorize(&jwt(serde_json::json!({})), Operation::Describe).is_err());
+
+        let strict = policy.require(Operation::Cancel, Role::Admin);
+        assert!(strict.authorize(&operator, Operation::Cancel).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rbac_middleware() {
+        let middleware = RbacMiddleware::new(RbacPolicy::default());
+        let mut context = MiddlewareContext::new("req_1".to_string(), "workflow_1".to_string(), serde_json::json!({}));
+        context.set_metadata("user_role".to_string(), "viewer".to_string());
+        context.set_metadata("operation".to_string(), "start".to_string());
+        assert!(middleware.before_request(&mut context).await.is_err());
+        context.set_metadata("user_role".to_string(), "operator".to_string());
+        assert!(middleware.before_request(&mut context).await.is_ok());
+        assert_eq!(context.get_metadata("authorized"), Some(&"true".to_string()));
+    }
+}
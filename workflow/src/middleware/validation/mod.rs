@@ -0,0 +1,369 @@
+//! # 请求校验 / Request Validation
+//!
+//! [`ValidationMiddleware`] 按工作流 ID 或工作流名（`workflow_name` 元数据）查找注册的 JSON Schema，
+//! 在请求到达引擎之前校验 `data`，拒绝时列出每处违规及其 JSON Pointer 路径。
+//! [`ValidationMiddleware`] looks up the JSON Schema registered for the workflow ID or workflow name (the
+//! `workflow_name` metadata) and validates `data` before the request reaches the engine, listing every violation with
+//! its JSON Pointer path when it rejects one.
+//!
+//! 支持的关键字 / Supported keywords: `type`, `enum`, `const`, `required`, `properties`, `additionalProperties`,
+//! `items`, `minItems`, `maxItems`, `minLength`, `maxLength`, `minimum`, `maximum`, `exclusiveMinimum`,
+//! `exclusiveMaximum`, `allOf`, `anyOf`, `oneOf` and `not`; other keywords are ignored.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use serde_json::Value;
+
+use crate::middleware::{MiddlewareContext, MiddlewareError, MiddlewarePriority, WorkflowMiddleware};
+
+/// 一处违规 / One schema violation
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SchemaViolation {
+    /// 违规值的 JSON Pointer，根为 `/` / JSON Pointer of the offending value, `/` for the root
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// 以 `schema` 校验 `value`，返回全部违规 / Validate `value` against `schema`, returning every violation
+pub fn validate(schema: &Value, value: &Value) -> Result<(), Vec<SchemaViolation>> {
+    let mut violations = Vec::new();
+    check(schema, value, "", &mut violations);
+    if violations.is_empty() { Ok(()) } else { Err(violations) }
+}
+
+/// RFC 6901 转义 / RFC 6901 escaping
+fn pointer(path: &str, token: &str) -> String {
+    format!("{}/{}", path, token.replace('~', "~0").replace('/', "~1"))
+}
+
+fn type_matches(kind: &str, value: &Value) -> bool {
+    match kind {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+    let Some(schema) = schema.as_object() else {
+        // `false` 拒绝一切，`true` 接受一切 / `false` rejects everything, `true` accepts everything
+        if schema == &Value::Bool(false) {
+            report(violations, path, "no value is allowed here".to_string());
+        }
+        return;
+    };
+    let mut fail = |message: String| report(violations, path, message);
+
+    match schema.get("type") {
+        Some(Value::String(kind)) if !type_matches(kind, value) => fail(format!("must be of type {}", kind)),
+        Some(Value::Array(kinds)) if !kinds.iter().filter_map(Value::as_str).any(|kind| type_matches(kind, value)) => {
+            fail(format!("must be of type {}", Value::Array(kinds.clone())))
+        }
+        _ => {}
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array)
+        && !options.contains(value)
+    {
+        fail(format!("must be one of {}", Value::Array(options.clone())));
+    }
+    if let Some(expected) = schema.get("const")
+        && expected != value
+    {
+        fail(format!("must be {}", expected));
+    }
+    if let Some(s) = value.as_str() {
+        let length = s.chars().count() as u64;
+        if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+            && length < min
+        {
+            fail(format!("must be at least {} characters long", min));
+        }
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+            && length > max
+        {
+            fail(format!("must be at most {} characters long", max));
+        }
+    }
+    if let Some(n) = value.as_f64() {
+        let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+        if let Some(min) = bound("minimum")
+            && n < min
+        {
+            fail(format!("must be at least {}", min));
+        }
+        if let Some(max) = bound("maximum")
+            && n > max
+        {
+            fail(format!("must be at most {}", max));
+        }
+        if let Some(min) = bound("exclusiveMinimum")
+            && n <= min
+        {
+            fail(format!("must be greater than {}", min));
+        }
+        if let Some(max) = bound("exclusiveMaximum")
+            && n >= max
+        {
+            fail(format!("must be less than {}", max));
+        }
+    }
+    if let Some(array) = value.as_array() {
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+            && (array.len() as u64) < min
+        {
+            fail(format!("must have at least {} items", min));
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+            && (array.len() as u64) > max
+        {
+            fail(format!("must have at most {} items", max));
+        }
+    }
+    if let Some(object) = value.as_object() {
+        let required = schema.get("required").and_then(Value::as_array).into_iter().flatten();
+        for missing in required.filter_map(Value::as_str).filter(|name| !object.contains_key(*name)) {
+            report(violations, &pointer(path, missing), "is required".to_string());
+        }
+    }
+
+    let properties = schema.get("properties").and_then(Value::as_object);
+    if let Some(object) = value.as_object() {
+        for (name, property) in properties.into_iter().flatten() {
+            if let Some(value) = object.get(name) {
+                check(property, value, &pointer(path, name), violations);
+            }
+        }
+        if let Some(additional) = schema.get("additionalProperties") {
+            let extra = object.iter().filter(|(name, _)| !properties.is_some_and(|p| p.contains_key(*name)));
+            for (name, value) in extra {
+                match additional {
+                    Value::Bool(false) => report(violations, &pointer(path, name), "is not allowed".to_string()),
+                    additional => check(additional, value, &pointer(path, name), violations),
+                }
+            }
+        }
+    }
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (index, item) in array.iter().enumerate() {
+            check(items, item, &pointer(path, &index.to_string()), violations);
+        }
+    }
+
+    let subschemas = |keyword: &str| schema.get(keyword).and_then(Value::as_array).cloned().unwrap_or_default();
+    for subschema in subschemas("allOf") {
+        check(&subschema, value, path, violations);
+    }
+    let any_of = subschemas("anyOf");
+    if !any_of.is_empty() && !any_of.iter().any(|s| validate(s, value).is_ok()) {
+        report(violations, path, "must match at least one schema of anyOf".to_string());
+    }
+    let one_of = subschemas("oneOf");
+    if !one_of.is_empty() {
+        let matched = one_of.iter().filter(|s| validate(s, value).is_ok()).count();
+        if matched != 1 {
+            report(violations, path, format!("must match exactly one schema of oneOf, matched {}", matched));
+        }
+    }
+    if let Some(not) = schema.get("not")
+        && validate(not, value).is_ok()
+    {
+        report(violations, path, "must not match the schema of not".to_string());
+    }
+}
+
+fn report(violations: &mut Vec<SchemaViolation>, path: &str, message: String) {
+    let path = if path.is_empty() { "/".to_string() } else { path.to_string() };
+    violations.push(SchemaViolation { path, message });
+}
+
+/// 校验中间件 / Validation Middleware
+pub struct ValidationMiddleware {
+    name: String,
+    version: String,
+    description: String,
+    priority: MiddlewarePriority,
+    /// 按工作流 ID 或工作流名注册的模式 / Schemas registered by workflow ID or workflow name
+    schemas: RwLock<HashMap<String, Value>>,
+}
+
+impl Default for ValidationMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ValidationMiddleware {
+    /// 创建校验中间件 / Create validation middleware
+    pub fn new() -> Self {
+        Self {
+            name: "ValidationMiddleware".to_string(),
+            version: "1.0.0".to_string(),
+            description: "工作流请求校验中间件 / Workflow request validation middleware".to_string(),
+            priority: MiddlewarePriority::High,
+            schemas: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 为工作流 ID 或工作流名注册模式，替换已有的 / Register the schema of a workflow ID or name, replacing any earlier one
+    pub fn register_schema(&self, workflow: impl Into<String>, schema: Value) -> Result<(), MiddlewareError> {
+        if !schema.is_object() && !schema.is_boolean() {
+            return Err(MiddlewareError::ProcessingError(format!(
+                "JSON Schema 必须是对象或布尔值 / JSON Schema must be an object or a boolean, got {}",
+                schema
+            )));
+        }
+        self.schemas.write().insert(workflow.into(), schema);
+        Ok(())
+    }
+
+    pub fn with_schema(self, workflow: impl Into<String>, schema: Value) -> Result<Self, MiddlewareError> {
+        self.register_schema(workflow, schema)?;
+        Ok(self)
+    }
+
+    /// 以请求对应的模式校验其数据，工作流 ID 优先于工作流名；未注册模式的请求通过
+    /// Validate a request's data against its schema, by workflow ID before workflow name; requests without one pass
+    pub fn validate(&self, context: &MiddlewareContext) -> Result<(), Vec<SchemaViolation>> {
+        let schemas = self.schemas.read();
+        let schema = schemas
+            .get(&context.workflow_id)
+            .or_else(|| context.get_metadata("workflow_name").and_then(|name| schemas.get(name)));
+        match schema {
+            Some(schema) => validate(schema, &context.data),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl WorkflowMiddleware for ValidationMiddleware {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn priority(&self) -> MiddlewarePriority {
+        self.priority
+    }
+
+    async fn before_request(&self, context: &mut MiddlewareContext) -> Result<(), String> {
+        if let Err(violations) = self.validate(context) {
+            let details = violations.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ");
+            context.set_metadata(
+                "validation_errors".to_string(),
+                serde_json::to_string(&violations).unwrap_or_default(),
+            );
+            return Err(format!("请求数据不符合模式 / Request data does not match the schema: {}", details));
+        }
+        context.set_metadata("validated".to_string(), "true".to_string());
+        Ok(())
+    }
+
+    async fn after_request(&self, _context: &mut MiddlewareContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn handle_error(
+        &self,
+        _context: &mut MiddlewareContext,
+        error: &str,
+    ) -> Result<(), String> {
+        tracing::debug!("校验中间件错误处理 / Validation middleware error handling: {}", error);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn order_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "items"],
+            "additionalProperties": false,
+            "properties": {
+                "id": {"type": "string", "minLength": 1},
+                "priority": {"enum": ["low", "high"]},
+                "items": {
+                    "type": "array",
+                    "minItems": 1,
+                    "items": {
+                        "type": "object",
+                        "required": ["sku"],
+                        "properties": {"sku": {"type": "string"}, "qty": {"type": "integer", "minimum": 1}}
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_reports_every_violation_with_its_path() {
+        let valid = json!({"id": "o-1", "items": [{"sku": "a", "qty": 2}]});
+        assert!(validate(&order_schema(), &valid).is_ok());
+
+        let invalid = json!({"id": "", "priority": "urgent", "items": [{"qty": 0}, {"sku": 3}], "note": "x"});
+        let violations = validate(&order_schema(), &invalid).unwrap_err();
+        let paths: Vec<_> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, ["/id", "/items/0/sku", "/items/0/qty", "/items/1/sku", "/priority", "/note"]);
+        assert_eq!(violations[2].to_string(), "/items/0/qty: must be at least 1");
+
+        let root = validate(&order_schema(), &json!([])).unwrap_err();
+        assert_eq!(root[0].to_string(), "/: must be of type object");
+        assert!(validate(&json!({"oneOf": [{"type": "number"}, {"type": "integer"}]}), &json!(1)).is_err());
+        assert!(validate(&json!({"anyOf": [{"type": "number"}, {"type": "null"}]}), &Value::Null).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validation_middleware_rejects_by_workflow_id_and_name() {
+        let middleware = ValidationMiddleware::new().with_schema("order", order_schema()).unwrap();
+        middleware.register_schema("wf-strict", json!({"type": "null"})).unwrap();
+        assert!(middleware.register_schema("bad", json!("object")).is_err());
+
+        let context = |workflow_id: &str, data: Value| {
+            let mut context = MiddlewareContext::new("req".to_string(), workflow_id.to_string(), data);
+            context.set_metadata("workflow_name".to_string(), "order".to_string());
+            context
+        };
+
+        let mut ok = context("wf-1", json!({"id": "o-1", "items": [{"sku": "a"}]}));
+        assert!(middleware.before_request(&mut ok).await.is_ok());
+        assert_eq!(ok.get_metadata("validated").map(String::as_str), Some("true"));
+
+        let mut rejected = context("wf-1", json!({"id": "o-1", "items": []}));
+        let error = middleware.before_request(&mut rejected).await.unwrap_err();
+        assert!(error.ends_with("/items: must have at least 1 items"), "{}", error);
+        let violations: Vec<SchemaViolation> =
+            serde_json::from_str(rejected.get_metadata("validation_errors").unwrap()).unwrap();
+        assert_eq!(violations.len(), 1);
+
+        // 工作流 ID 的模式优先 / the workflow ID's schema wins
+        let mut strict = context("wf-strict", Value::Null);
+        assert!(middleware.before_request(&mut strict).await.is_ok());
+        let mut unregistered = MiddlewareContext::new("req".to_string(), "wf-2".to_string(), json!(42));
+        assert!(middleware.before_request(&mut unregistered).await.is_ok());
+    }
+}
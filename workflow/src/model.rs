@@ -0,0 +1,452 @@
+//! # 规范工作流模型 / Canonical Workflow Model
+//!
+//! `rust190` 的各个演示引擎各自定义了互不兼容的 `WorkflowDefinition` / `WorkflowStep`。本模块提供唯一的规范
+//! 定义与流式构建器，并可从这些旧结构转换；旧结构已弃用。
+//! The demo engines of `rust190` each defined their own incompatible `WorkflowDefinition` / `WorkflowStep`. This
+//! module provides the one canonical definition with a fluent builder and conversions from those legacy structs,
+//! which are deprecated.
+//!
+//! ```
+//! use std::time::Duration;
+//! use workflow::model::{WorkflowDefinition, WorkflowStep};
+//!
+//! let definition = WorkflowDefinition::builder("order")
+//!     .timeout(Duration::from_secs(300))
+//!     .retries(3)
+//!     .step(WorkflowStep::builder("charge", "payment.charge").timeout(Duration::from_secs(30)))
+//!     .step(WorkflowStep::builder("ship", "warehouse.ship").depends_on("charge"))
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(definition.steps[1].dependencies, ["charge"]);
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::WorkflowError;
+
+/// 工作流定义 / Workflow definition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub version: String,
+    pub steps: Vec<WorkflowStep>,
+    /// 整个工作流的超时 / Timeout of the whole workflow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Duration>,
+    /// 步骤的默认重试次数 / Default retries of the steps
+    #[serde(default)]
+    pub retries: u32,
+    /// 数值越小越优先 / Lower values run first
+    #[serde(default)]
+    pub priority: u8,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
+}
+
+/// 工作流步骤 / Workflow step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowStep {
+    pub name: String,
+    /// 步骤执行的动作，例如活动类型 / Action the step runs, such as an activity type
+    pub action: String,
+    /// 须先完成的步骤名 / Names of the steps that must finish first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Duration>,
+    /// 覆盖工作流的默认重试次数 / Overrides the workflow's default retries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+impl WorkflowDefinition {
+    pub fn builder(name: impl Into<String>) -> WorkflowDefinitionBuilder {
+        WorkflowDefinitionBuilder {
+            definition: WorkflowDefinition {
+                name: name.into(),
+                description: None,
+                version: "1.0.0".to_string(),
+                steps: Vec::new(),
+                timeout: None,
+                retries: 0,
+                priority: 0,
+                metadata: HashMap::new(),
+            },
+        }
+    }
+
+    pub fn step(&self, name: &str) -> Option<&WorkflowStep> {
+        self.steps.iter().find(|step| step.name == name)
+    }
+
+    /// 步骤的有效重试次数 / Effective retries of a step
+    pub fn retries_of(&self, step: &WorkflowStep) -> u32 {
+        step.retries.unwrap_or(self.retries)
+    }
+
+    /// 检查名称、步骤名唯一、依赖存在且无环 / Check the names, unique step names and known, acyclic dependencies
+    pub fn validate(&self) -> Result<(), WorkflowError> {
+        let invalid = |message: String| Err(WorkflowError::ValidationError(message));
+        if self.name.trim().is_empty() {
+            return invalid("workflow name is empty".to_string());
+        }
+        let mut names = HashSet::new();
+        for step in &self.steps {
+            if step.name.trim().is_empty() {
+                return invalid(format!("workflow {} has a step without a name", self.name));
+            }
+            if !names.insert(step.name.as_str()) {
+                return invalid(format!("step {} is defined twice", step.name));
+            }
+        }
+        for step in &self.steps {
+            if let Some(unknown) = step.dependencies.iter().find(|dependency| !names.contains(dependency.as_str())) {
+                return invalid(format!("step {} depends on unknown step {}", step.name, unknown));
+            }
+        }
+        if self.execution_order().is_none() {
+            return invalid(format!("steps of workflow {} have cyclic dependencies", self.name));
+        }
+        Ok(())
+    }
+
+    /// 满足依赖的执行顺序，尽量保持声明顺序；有环时为 `None`
+    /// Order satisfying the dependencies, keeping declaration order where possible; `None` if they have a cycle
+    pub fn execution_order(&self) -> Option<Vec<&WorkflowStep>> {
+        let mut done: HashSet<&str> = HashSet::new();
+        let mut order = Vec::with_capacity(self.steps.len());
+        while order.len() < self.steps.len() {
+            let next = self.steps.iter().find(|step| {
+                !done.contains(step.name.as_str())
+                    && step.dependencies.iter().all(|dependency| done.contains(dependency.as_str()))
+            })?;
+            done.insert(&next.name);
+            order.push(next);
+        }
+        Some(order)
+    }
+}
+
+impl WorkflowStep {
+    pub fn builder(name: impl Into<String>, action: impl Into<String>) -> WorkflowStepBuilder {
+        WorkflowStepBuilder {
+            step: WorkflowStep::new(name, action),
+        }
+    }
+
+    pub fn new(name: impl Into<String>, action: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            action: action.into(),
+            dependencies: Vec::new(),
+            timeout: None,
+            retries: None,
+            input: None,
+            output: None,
+        }
+    }
+}
+
+/// [`WorkflowDefinition`] 构建器 / Builder of a [`WorkflowDefinition`]
+#[derive(Debug, Clone)]
+pub struct WorkflowDefinitionBuilder {
+    definition: WorkflowDefinition,
+}
+
+impl WorkflowDefinitionBuilder {
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.definition.description = Some(description.into());
+        self
+    }
+
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.definition.version = version.into();
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.definition.timeout = Some(timeout);
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.definition.retries = retries;
+        self
+    }
+
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.definition.priority = priority;
+        self
+    }
+
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<serde_json::Value>) -> Self {
+        self.definition.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// 追加步骤，接受 [`WorkflowStep`] 或其构建器 / Append a step, given as a [`WorkflowStep`] or its builder
+    pub fn step(mut self, step: impl Into<WorkflowStep>) -> Self {
+        self.definition.steps.push(step.into());
+        self
+    }
+
+    pub fn steps(mut self, steps: impl IntoIterator<Item = WorkflowStep>) -> Self {
+        self.definition.steps.extend(steps);
+        self
+    }
+
+    /// 构建并校验，见 [`WorkflowDefinition::validate`] / Build and validate, see [`WorkflowDefinition::validate`]
+    pub fn build(self) -> Result<WorkflowDefinition, WorkflowError> {
+        self.definition.validate()?;
+        Ok(self.definition)
+    }
+}
+
+/// [`WorkflowStep`] 构建器 / Builder of a [`WorkflowStep`]
+#[derive(Debug, Clone)]
+pub struct WorkflowStepBuilder {
+    step: WorkflowStep,
+}
+
+impl WorkflowStepBuilder {
+    pub fn depends_on(mut self, step: impl Into<String>) -> Self {
+        self.step.dependencies.push(step.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.step.timeout = Some(timeout);
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.step.retries = Some(retries);
+        self
+    }
+
+    pub fn input(mut self, input: impl Into<String>) -> Self {
+        self.step.input = Some(input.into());
+        self
+    }
+
+    pub fn output(mut self, output: impl Into<String>) -> Self {
+        self.step.output = Some(output.into());
+        self
+    }
+
+    pub fn build(self) -> WorkflowStep {
+        self.step
+    }
+}
+
+impl From<WorkflowStepBuilder> for WorkflowStep {
+    fn from(builder: WorkflowStepBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// 旧 `rust190` 结构的转换 / Conversions from the legacy `rust190` structs
+#[cfg(feature = "rust190")]
+#[allow(deprecated)]
+mod legacy {
+    use super::*;
+    use crate::rust190::{async_features, const_features, performance, stable_apis};
+
+    /// 空定义，转换后直接填入字段而不校验 / Empty definition, filled in by the conversions without validation
+    fn definition(name: impl Into<String>, steps: Vec<WorkflowStep>) -> WorkflowDefinition {
+        let mut definition = WorkflowDefinition::builder(name).definition;
+        definition.steps = steps;
+        definition
+    }
+
+    impl From<async_features::WorkflowStep> for WorkflowStep {
+        fn from(step: async_features::WorkflowStep) -> Self {
+            Self {
+                dependencies: step.dependencies,
+                timeout: Some(step.timeout),
+                ..WorkflowStep::new(step.name, step.action)
+            }
+        }
+    }
+
+    impl From<async_features::WorkflowDefinition> for WorkflowDefinition {
+        fn from(legacy: async_features::WorkflowDefinition) -> Self {
+            let mut definition = definition(legacy.name, legacy.steps.into_iter().map(Into::into).collect());
+            definition.timeout = Some(legacy.timeout);
+            definition.retries = legacy.retry_count;
+            definition
+        }
+    }
+
+    impl From<performance::WorkflowStep> for WorkflowStep {
+        fn from(step: performance::WorkflowStep) -> Self {
+            Self {
+                timeout: Some(step.timeout),
+                retries: Some(step.retries),
+                ..WorkflowStep::new(step.name, step.action)
+            }
+        }
+    }
+
+    impl From<performance::WorkflowDefinition> for WorkflowDefinition {
+        fn from(legacy: performance::WorkflowDefinition) -> Self {
+            let mut definition = definition(legacy.name, legacy.steps.into_iter().map(Into::into).collect());
+            definition.timeout = Some(legacy.timeout);
+            definition.retries = legacy.retries;
+            definition.priority = legacy.priority;
+            definition
+        }
+    }
+
+    impl From<stable_apis::WorkflowStep> for WorkflowStep {
+        fn from(step: stable_apis::WorkflowStep) -> Self {
+            Self {
+                input: Some(step.input),
+                output: Some(step.output),
+                ..WorkflowStep::new(step.name, step.action)
+            }
+        }
+    }
+
+    impl From<stable_apis::WorkflowDefinition> for WorkflowDefinition {
+        fn from(legacy: stable_apis::WorkflowDefinition) -> Self {
+            let mut definition = definition(legacy.name, legacy.steps.into_iter().map(Into::into).collect());
+            definition.timeout = Some(Duration::from_secs(legacy.config.timeout));
+            definition.retries = legacy.config.retries;
+            definition.metadata.insert("enable_debug".to_string(), legacy.config.enable_debug.into());
+            definition
+        }
+    }
+
+    /// const 步骤没有动作，以步骤名作为动作 / Const steps have no action, so the name is used as the action
+    impl From<&const_features::ConstWorkflowStep> for WorkflowStep {
+        fn from(step: &const_features::ConstWorkflowStep) -> Self {
+            Self {
+                timeout: Some(Duration::from_secs(step.timeout)),
+                retries: Some(step.retries),
+                ..WorkflowStep::new(step.name, step.name)
+            }
+        }
+    }
+
+    impl From<&const_features::ConstWorkflowDefinition> for WorkflowDefinition {
+        fn from(legacy: &const_features::ConstWorkflowDefinition) -> Self {
+            let mut definition = definition(legacy.name, legacy.steps.iter().map(Into::into).collect());
+            definition.timeout = Some(Duration::from_secs(legacy.config.timeout_seconds));
+            definition.retries = legacy.config.max_retries;
+            definition.metadata.insert("batch_size".to_string(), legacy.config.batch_size.into());
+            definition.metadata.insert("enable_logging".to_string(), legacy.config.enable_logging.into());
+            definition
+        }
+    }
+
+    #[cfg(feature = "session_types")]
+    impl From<crate::rust190::session_types::WorkflowStep> for WorkflowStep {
+        fn from(step: crate::rust190::session_types::WorkflowStep) -> Self {
+            Self {
+                timeout: Some(step.timeout),
+                input: Some(step.input_type),
+                output: Some(step.output_type),
+                ..WorkflowStep::new(step.name, step.action)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_validates_steps() {
+        let definition = WorkflowDefinition::builder("order")
+            .description("Order fulfilment")
+            .retries(2)
+            .metadata("team", "payments")
+            .step(WorkflowStep::builder("ship", "warehouse.ship").depends_on("charge").retries(5))
+            .step(WorkflowStep::new("charge", "payment.charge"))
+            .build()
+            .unwrap();
+        let order: Vec<_> = definition.execution_order().unwrap().iter().map(|step| step.name.as_str()).collect();
+        assert_eq!(order, ["charge", "ship"]);
+        assert_eq!(definition.retries_of(&definition.steps[0]), 5);
+        assert_eq!(definition.retries_of(definition.step("charge").unwrap()), 2);
+        let json = serde_json::to_value(&definition).unwrap();
+        assert_eq!(serde_json::from_value::<WorkflowDefinition>(json).unwrap(), definition);
+
+        let error = |builder: WorkflowDefinitionBuilder| match builder.build() {
+            Err(WorkflowError::ValidationError(message)) => message,
+            other => panic!("expected a validation error, got {:?}", other),
+        };
+        let builder = WorkflowDefinition::builder("loop");
+        assert_eq!(
+            error(builder.clone().step(WorkflowStep::new("a", "x")).step(WorkflowStep::new("a", "y"))),
+            "step a is defined twice"
+        );
+        assert_eq!(
+            error(builder.clone().step(WorkflowStep::builder("a", "x").depends_on("b"))),
+            "step a depends on unknown step b"
+        );
+        assert_eq!(
+            error(
+                builder
+                    .step(WorkflowStep::builder("a", "x").depends_on("b"))
+                    .step(WorkflowStep::builder("b", "y").depends_on("a"))
+            ),
+            "steps of workflow loop have cyclic dependencies"
+        );
+        assert_eq!(error(WorkflowDefinition::builder(" ")), "workflow name is empty");
+    }
+
+    #[cfg(feature = "rust190")]
+    #[test]
+    #[allow(deprecated)]
+    fn test_converts_legacy_definitions() {
+        use crate::rust190::{async_features, const_features, stable_apis};
+
+        let definition = WorkflowDefinition::from(async_features::WorkflowDefinition {
+            name: "etl".to_string(),
+            steps: vec![async_features::WorkflowStep {
+                name: "load".to_string(),
+                action: "db.load".to_string(),
+                dependencies: vec![],
+                timeout: Duration::from_secs(10),
+            }],
+            timeout: Duration::from_secs(60),
+            retry_count: 3,
+        });
+        assert_eq!((definition.retries, definition.timeout), (3, Some(Duration::from_secs(60))));
+        assert_eq!(definition.steps[0].timeout, Some(Duration::from_secs(10)));
+        definition.validate().unwrap();
+
+        let definition = WorkflowDefinition::from(stable_apis::WorkflowDefinition {
+            name: "report".to_string(),
+            steps: vec![stable_apis::WorkflowStep {
+                name: "render".to_string(),
+                action: "pdf".to_string(),
+                input: "rows".to_string(),
+                output: "document".to_string(),
+            }],
+            config: stable_apis::WorkflowConfig {
+                timeout: 30,
+                retries: 1,
+                enable_debug: true,
+            },
+        });
+        assert_eq!(definition.steps[0].output.as_deref(), Some("document"));
+        assert_eq!(definition.metadata["enable_debug"], true);
+
+        const STEPS: &[const_features::ConstWorkflowStep] = &[const_features::ConstWorkflowStep::new(1, "compile", 30, 2)];
+        let definition = WorkflowDefinition::from(&const_features::ConstWorkflowDefinition::new("build", STEPS));
+        assert_eq!(definition.steps[0], WorkflowStep::builder("compile", "compile").timeout(Duration::from_secs(30)).retries(2).build());
+    }
+}
@@ -0,0 +1,338 @@
+//! # 引擎事件总线 / Engine Event Bus
+//!
+//! 观察者模式：工作者把工作流启动、活动失败、工作流结束等生命周期事件发布到 [`EventBus`]，订阅者以异步回调
+//! 接收，例如在失败时发送告警。每个订阅者按发布顺序逐个处理事件，且在自己的任务中运行，不会阻塞工作者；
+//! 回调 panic 只会丢弃该事件。
+//! Observer pattern: workers publish lifecycle events such as workflow starts, activity failures and workflow
+//! outcomes to an [`EventBus`], and subscribers receive them in async listeners, for example to send alerts on
+//! failures. Each subscriber handles events one at a time in publication order on its own task, so listeners never
+//! block the worker; a panicking listener only loses that event.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use workflow::patterns::{EngineEvent, EngineEventKind, EventBus};
+//! # use workflow::temporal::{WorkerConfig, WorkflowWorker};
+//! let bus = EventBus::builder()
+//!     .on(EngineEventKind::WorkflowFailed, |event| async move {
+//!         if let EngineEvent::WorkflowFailed { execution, failure, .. } = event {
+//!             // post to a chat webhook
+//!             tracing::warn!(%execution, %failure, "workflow failed");
+//!         }
+//!     })
+//!     .build();
+//! let worker = WorkflowWorker::new(WorkerConfig::default()).with_event_bus(Arc::new(bus));
+//! ```
+//!
+//! 事件至少发布一次：工作流任务崩溃重试时可能再次发布 `WorkflowStarted`。
+//! Events are published at least once: a retried crashed workflow task may publish `WorkflowStarted` again.
+
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use crate::temporal::{ActivityId, WorkflowExecution};
+
+/// 引擎生命周期事件 / Engine lifecycle event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EngineEvent {
+    WorkflowStarted {
+        execution: WorkflowExecution,
+        workflow_type: String,
+        task_queue: String,
+    },
+    /// 一次活动尝试失败；之后可能重试 / One activity attempt failed; it may be retried
+    ActivityFailed {
+        execution: WorkflowExecution,
+        activity_id: ActivityId,
+        activity_type: String,
+        attempt: u32,
+        error: String,
+    },
+    WorkflowCompleted {
+        execution: WorkflowExecution,
+        workflow_type: String,
+        result: Value,
+    },
+    WorkflowFailed {
+        execution: WorkflowExecution,
+        workflow_type: String,
+        failure: String,
+    },
+}
+
+/// 事件种类，用于按种类订阅 / Kind of an event, to subscribe by kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EngineEventKind {
+    WorkflowStarted,
+    ActivityFailed,
+    WorkflowCompleted,
+    WorkflowFailed,
+}
+
+impl EngineEvent {
+    pub fn kind(&self) -> EngineEventKind {
+        match self {
+            EngineEvent::WorkflowStarted { .. } => EngineEventKind::WorkflowStarted,
+            EngineEvent::ActivityFailed { .. } => EngineEventKind::ActivityFailed,
+            EngineEvent::WorkflowCompleted { .. } => EngineEventKind::WorkflowCompleted,
+            EngineEvent::WorkflowFailed { .. } => EngineEventKind::WorkflowFailed,
+        }
+    }
+
+    pub fn execution(&self) -> &WorkflowExecution {
+        match self {
+            EngineEvent::WorkflowStarted { execution, .. }
+            | EngineEvent::ActivityFailed { execution, .. }
+            | EngineEvent::WorkflowCompleted { execution, .. }
+            | EngineEvent::WorkflowFailed { execution, .. } => execution,
+        }
+    }
+}
+
+type Listener = Arc<dyn Fn(EngineEvent) -> BoxFuture<'static, ()> + Send + Sync>;
+
+struct Subscriber {
+    /// `None` 表示接收全部事件 / `None` receives every event
+    kinds: Option<Vec<EngineEventKind>>,
+    sender: mpsc::UnboundedSender<EngineEvent>,
+    /// 首次发布时交给订阅者任务 / Handed to the subscriber's task on the first publication
+    receiver: Mutex<Option<mpsc::UnboundedReceiver<EngineEvent>>>,
+    listener: Listener,
+}
+
+impl Subscriber {
+    fn accepts(&self, kind: EngineEventKind) -> bool {
+        self.kinds.as_ref().is_none_or(|kinds| kinds.contains(&kind))
+    }
+
+    fn deliver(&self, event: EngineEvent) {
+        if let Some(mut receiver) = self.receiver.lock().take() {
+            let listener = self.listener.clone();
+            tokio::spawn(async move {
+                while let Some(event) = receiver.recv().await {
+                    let kind = event.kind();
+                    if AssertUnwindSafe(listener(event)).catch_unwind().await.is_err() {
+                        tracing::error!(?kind, "engine event listener panicked");
+                    }
+                }
+            });
+        }
+        // The subscriber's task holds the receiver for as long as the bus lives
+        let _ = self.sender.send(event);
+    }
+}
+
+/// 向订阅者分发引擎事件 / Dispatches engine events to subscribers
+pub struct EventBus {
+    subscribers: Vec<Subscriber>,
+}
+
+impl EventBus {
+    pub fn builder() -> EventBusBuilder {
+        EventBusBuilder::default()
+    }
+
+    /// 发布事件；须在 Tokio 运行时中调用 / Publish an event; must be called within a Tokio runtime
+    pub fn publish(&self, event: EngineEvent) {
+        let kind = event.kind();
+        for subscriber in self.subscribers.iter().filter(|subscriber| subscriber.accepts(kind)) {
+            subscriber.deliver(event.clone());
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.len()
+    }
+}
+
+/// [`EventBus`] 构建器 / Builder of an [`EventBus`]
+#[derive(Default)]
+pub struct EventBusBuilder {
+    subscribers: Vec<Subscriber>,
+}
+
+impl EventBusBuilder {
+    /// 订阅全部事件 / Subscribe to every event
+    pub fn subscribe<F, Fut>(self, listener: F) -> Self
+    where
+        F: Fn(EngineEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.add(None, listener)
+    }
+
+    /// 订阅一种事件 / Subscribe to one kind of event
+    pub fn on<F, Fut>(self, kind: EngineEventKind, listener: F) -> Self
+    where
+        F: Fn(EngineEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.add(Some(vec![kind]), listener)
+    }
+
+    /// 订阅若干种事件 / Subscribe to several kinds of event
+    pub fn on_any<F, Fut>(self, kinds: impl IntoIterator<Item = EngineEventKind>, listener: F) -> Self
+    where
+        F: Fn(EngineEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.add(Some(kinds.into_iter().collect()), listener)
+    }
+
+    fn add<F, Fut>(mut self, kinds: Option<Vec<EngineEventKind>>, listener: F) -> Self
+    where
+        F: Fn(EngineEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.subscribers.push(Subscriber {
+            kinds,
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            listener: Arc::new(move |event| listener(event).boxed()),
+        });
+        self
+    }
+
+    pub fn build(self) -> EventBus {
+        EventBus {
+            subscribers: self.subscribers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::temporal::{
+        Activity, ActivityContext, ActivityError, ActivityOptions, RetryPolicy, StartWorkflowOptions, WorkerConfig,
+        Workflow, WorkflowContext, WorkflowError, WorkflowWorker,
+    };
+
+    struct Charge;
+
+    impl Activity for Charge {
+        type Input = i64;
+        type Output = i64;
+
+        fn name() -> &'static str {
+            "charge"
+        }
+
+        async fn execute(_ctx: ActivityContext, amount: i64) -> Result<i64, ActivityError> {
+            if amount > 100 {
+                return Err(ActivityError::TemporaryFailure("card declined".to_string()));
+            }
+            Ok(amount)
+        }
+    }
+
+    struct Checkout;
+
+    impl Workflow for Checkout {
+        type Input = i64;
+        type Output = i64;
+
+        fn name() -> &'static str {
+            "checkout"
+        }
+
+        async fn execute(ctx: WorkflowContext, amount: i64) -> Result<i64, WorkflowError> {
+            let options = ActivityOptions {
+                retry_policy: Some(RetryPolicy {
+                    max_attempts: 2,
+                    initial_interval: Duration::from_millis(1),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            ctx.execute_activity::<Charge>(amount, options).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_listeners_receive_engine_events() {
+        let all = Arc::new(Mutex::new(Vec::new()));
+        let (alerts, mut alerted) = mpsc::unbounded_channel();
+        let seen = all.clone();
+        let bus = EventBus::builder()
+            .subscribe(move |event| {
+                let seen = seen.clone();
+                async move { seen.lock().push(event) }
+            })
+            .on(EngineEventKind::WorkflowFailed, move |event| {
+                let alerts = alerts.clone();
+                async move {
+                    let _ = alerts.send(event);
+                }
+            })
+            .on(EngineEventKind::ActivityFailed, |_| async { panic!("listener bug") })
+            .build();
+        assert_eq!(bus.subscriber_count(), 3);
+
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                ..WorkerConfig::default()
+            })
+            .with_event_bus(Arc::new(bus)),
+        );
+        worker.register_workflow::<Checkout>();
+        worker.register_activity::<Charge>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let client = worker.client();
+        let paid = client.start_workflow::<Checkout>(20, StartWorkflowOptions::default()).await.unwrap();
+        assert_eq!(paid.result().await.unwrap(), 20);
+        let declined = client.start_workflow::<Checkout>(500, StartWorkflowOptions::default()).await.unwrap();
+        assert!(declined.result().await.is_err());
+
+        let alert = tokio::time::timeout(Duration::from_secs(5), alerted.recv()).await.unwrap().unwrap();
+        assert!(
+            matches!(&alert, EngineEvent::WorkflowFailed { workflow_type, failure, .. }
+                if workflow_type == "checkout" && failure.contains("card declined")),
+            "{:?}",
+            alert
+        );
+        for _ in 0..100 {
+            if all.lock().len() == 6 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let events = all.lock().clone();
+        let kinds: Vec<_> = events.iter().map(EngineEvent::kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                EngineEventKind::WorkflowStarted,
+                EngineEventKind::WorkflowCompleted,
+                EngineEventKind::WorkflowStarted,
+                EngineEventKind::ActivityFailed,
+                EngineEventKind::ActivityFailed,
+                EngineEventKind::WorkflowFailed,
+            ]
+        );
+        assert_eq!(events[1], EngineEvent::WorkflowCompleted {
+            execution: paid.execution().clone(),
+            workflow_type: "checkout".to_string(),
+            result: serde_json::json!(20),
+        });
+        assert!(matches!(&events[4], EngineEvent::ActivityFailed { attempt: 2, activity_type, .. } if activity_type == "charge"));
+        assert_eq!(serde_json::to_value(&events[3]).unwrap()["event"], "activity_failed");
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
@@ -6,6 +6,12 @@
 use crate::patterns::{PatternCategory, WorkflowContext, WorkflowPattern, WorkflowResult, PatternError};
 use serde_json::json;
 
+pub mod event_bus;
+pub mod state_machine;
+
+pub use event_bus::{EngineEvent, EngineEventKind, EventBus, EventBusBuilder};
+pub use state_machine::{CurrentState, MachineState, StateMachineBuilder, StateMachineOutput, StateMachineWorkflow};
+
 /// 初始化行为型模式 / Initialize behavioral patterns
 pub fn init_behavioral_patterns() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("初始化行为型工作流模式 / Initializing behavioral workflow patterns");
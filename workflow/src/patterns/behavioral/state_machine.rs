@@ -0,0 +1,413 @@
+//! # 状态机工作流 / State Machine Workflows
+//!
+//! 以状态、转换、守卫与进入活动声明工作流，而不必编写 `Workflow::execute`。事件即同名信号：当前状态没有
+//! 该事件的转换时，信号保留到某个状态接受它为止；有转换但守卫全部不成立时，信号被丢弃。每次转换记录一条
+//! `StateTransitioned` 历史事件，[`CurrentState`] 查询返回当前状态。
+//! Declares a workflow as states, transitions, guards and on-entry activities instead of writing
+//! `Workflow::execute`. Events are signals of the same name: a signal the current state has no transition for stays
+//! queued until a state accepts it, and one whose transitions all have failing guards is dropped. Every transition
+//! records a `StateTransitioned` history event, and the [`CurrentState`] query returns the current state.
+//!
+//! 工作流携带一份 JSON 数据，初始为工作流输入。进入活动以 `{"data", "event"}` 为输入（`event` 为触发转换的
+//! 信号内容），其非空结果成为新的数据。工作流在终止状态结束，结果为 [`StateMachineOutput`]。
+//! The workflow carries JSON data, initially the workflow input. On-entry activities take `{"data", "event"}` as
+//! input, where `event` is the payload of the signal that caused the transition, and a non-null result becomes the
+//! new data. The workflow ends in a final state with a [`StateMachineOutput`].
+//!
+//! ```no_run
+//! # use serde::{Deserialize, Serialize};
+//! # use workflow::patterns::StateMachineWorkflow;
+//! # use workflow::temporal::WorkflowWorker;
+//! #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+//! enum Order {
+//!     Submitted,
+//!     Approved,
+//!     Escalated,
+//!     Rejected,
+//!     Shipped,
+//! }
+//!
+//! # fn register(worker: &WorkflowWorker) -> Result<(), workflow::patterns::PatternError> {
+//! let approval = StateMachineWorkflow::builder("order_approval", Order::Submitted)
+//!     .transition_if(Order::Submitted, "approve", Order::Escalated, |order, _| order["amount"].as_f64() > Some(1000.0))
+//!     .transition(Order::Submitted, "approve", Order::Approved)
+//!     .transition(Order::Escalated, "approve", Order::Approved)
+//!     .transition(Order::Submitted, "reject", Order::Rejected)
+//!     .on_entry(Order::Approved, "reserve_stock")
+//!     .transition(Order::Approved, "ship", Order::Shipped)
+//!     .final_state(Order::Shipped)
+//!     .final_state(Order::Rejected)
+//!     .build()?;
+//! approval.register(worker);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use futures::FutureExt;
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::patterns::PatternError;
+use crate::temporal::event::EventType;
+use crate::temporal::{ActivityOptions, Query, WorkflowContext, WorkflowError, WorkflowWorker};
+
+/// 可作为状态的类型，通常是无字段的枚举 / Types usable as states, usually fieldless enums
+pub trait MachineState: Clone + Eq + Hash + Debug + Serialize + DeserializeOwned + Send + Sync + 'static {}
+
+impl<S: Clone + Eq + Hash + Debug + Serialize + DeserializeOwned + Send + Sync + 'static> MachineState for S {}
+
+/// 守卫：以工作流数据与事件内容判断转换能否发生 / Guard deciding from the workflow data and the event payload whether a transition applies
+type Guard = Arc<dyn Fn(&Value, &Value) -> bool + Send + Sync>;
+
+struct Transition<S> {
+    from: S,
+    event: String,
+    to: S,
+    guard: Option<Guard>,
+}
+
+/// 进入状态时执行的活动 / Activity executed when a state is entered
+#[derive(Clone)]
+struct EntryActivity {
+    activity_type: String,
+    options: ActivityOptions,
+}
+
+/// 查询状态机的当前状态 / Query for the current state of a state machine
+pub struct CurrentState<S>(PhantomData<fn() -> S>);
+
+impl<S: MachineState> Query for CurrentState<S> {
+    fn name() -> &'static str {
+        "current_state"
+    }
+
+    type Result = S;
+}
+
+/// 状态机工作流的结果 / Result of a state machine workflow
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateMachineOutput<S> {
+    /// 结束时的终止状态 / Final state the machine ended in
+    pub state: S,
+    pub data: Value,
+}
+
+/// 状态机工作流构建器 / State machine workflow builder
+pub struct StateMachineBuilder<S> {
+    name: String,
+    initial: S,
+    transitions: Vec<Transition<S>>,
+    on_entry: HashMap<S, EntryActivity>,
+    finals: HashSet<S>,
+}
+
+impl<S: MachineState> StateMachineBuilder<S> {
+    /// 在 `event` 上从 `from` 转换到 `to` / Move from `from` to `to` on `event`
+    ///
+    /// 同一状态与事件的多个转换按声明顺序检查，第一个守卫成立的生效。
+    /// Several transitions for the same state and event are checked in declaration order, and the first whose guard
+    /// holds is taken.
+    pub fn transition(mut self, from: S, event: impl Into<String>, to: S) -> Self {
+        self.transitions.push(Transition {
+            from,
+            event: event.into(),
+            to,
+            guard: None,
+        });
+        self
+    }
+
+    /// 仅当 `guard(数据, 事件内容)` 成立时转换 / Transition only when `guard(data, event payload)` holds
+    pub fn transition_if(
+        mut self,
+        from: S,
+        event: impl Into<String>,
+        to: S,
+        guard: impl Fn(&Value, &Value) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.transitions.push(Transition {
+            from,
+            event: event.into(),
+            to,
+            guard: Some(Arc::new(guard)),
+        });
+        self
+    }
+
+    /// 进入 `state` 时以缺省选项执行活动 / Execute an activity with default options on entering `state`
+    pub fn on_entry(self, state: S, activity_type: impl Into<String>) -> Self {
+        self.on_entry_with_options(state, activity_type, ActivityOptions::default())
+    }
+
+    /// 进入 `state` 时执行活动 / Execute an activity on entering `state`
+    pub fn on_entry_with_options(mut self, state: S, activity_type: impl Into<String>, options: ActivityOptions) -> Self {
+        self.on_entry.insert(
+            state,
+            EntryActivity {
+                activity_type: activity_type.into(),
+                options,
+            },
+        );
+        self
+    }
+
+    /// 到达 `state` 时工作流结束 / The workflow ends on reaching `state`
+    pub fn final_state(mut self, state: S) -> Self {
+        self.finals.insert(state);
+        self
+    }
+
+    /// 检查定义：须有终止状态，终止状态没有转换，每个可达的非终止状态都有转换，每个终止状态都可达
+    /// Check the definition: there must be a final state, final states have no transitions, every reachable
+    /// non-final state has a transition and every final state is reachable
+    pub fn build(self) -> Result<StateMachineWorkflow<S>, PatternError> {
+        let invalid = |message: String| Err(PatternError::InvalidDefinition(format!("{}: {}", self.name, message)));
+        if self.finals.is_empty() {
+            return invalid("no final state".to_string());
+        }
+        if let Some(transition) = self.transitions.iter().find(|t| self.finals.contains(&t.from)) {
+            return invalid(format!("final state {:?} has a transition on `{}`", transition.from, transition.event));
+        }
+        let mut reachable = HashSet::from([self.initial.clone()]);
+        let mut queue = VecDeque::from([self.initial.clone()]);
+        while let Some(state) = queue.pop_front() {
+            if !self.finals.contains(&state) && !self.transitions.iter().any(|t| t.from == state) {
+                return invalid(format!("state {:?} is neither final nor has transitions", state));
+            }
+            for transition in self.transitions.iter().filter(|t| t.from == state) {
+                if reachable.insert(transition.to.clone()) {
+                    queue.push_back(transition.to.clone());
+                }
+            }
+        }
+        if let Some(state) = self.finals.iter().find(|state| !reachable.contains(*state)) {
+            return invalid(format!("final state {:?} is unreachable from {:?}", state, self.initial));
+        }
+        Ok(StateMachineWorkflow {
+            definition: Arc::new(self),
+        })
+    }
+}
+
+/// 由状态与转换声明的工作流 / Workflow declared by states and transitions
+pub struct StateMachineWorkflow<S> {
+    definition: Arc<StateMachineBuilder<S>>,
+}
+
+impl<S> Clone for StateMachineWorkflow<S> {
+    fn clone(&self) -> Self {
+        Self {
+            definition: self.definition.clone(),
+        }
+    }
+}
+
+impl<S: MachineState> StateMachineWorkflow<S> {
+    /// 以工作流类型名与初始状态开始声明 / Start declaring a machine with its workflow type name and initial state
+    pub fn builder(name: impl Into<String>, initial: S) -> StateMachineBuilder<S> {
+        StateMachineBuilder {
+            name: name.into(),
+            initial,
+            transitions: Vec::new(),
+            on_entry: HashMap::new(),
+            finals: HashSet::new(),
+        }
+    }
+
+    /// 工作流类型名 / Workflow type name
+    pub fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    /// 以工作流类型名注册到工作者，之后用 `start_dynamic_workflow` 启动
+    /// Register with a worker under the workflow type name; start it with `start_dynamic_workflow`
+    pub fn register(&self, worker: &WorkflowWorker) {
+        let machine = self.clone();
+        worker.register_workflow_fn(
+            self.name(),
+            Arc::new(move |ctx, input| {
+                let machine = machine.clone();
+                async move { Ok(serde_json::to_value(machine.execute(ctx, input).await?)?) }.boxed()
+            }),
+        );
+    }
+
+    /// 从初始状态运行到终止状态 / Run from the initial state to a final state
+    pub async fn execute(&self, ctx: WorkflowContext, input: Value) -> Result<StateMachineOutput<S>, WorkflowError> {
+        let machine = &self.definition;
+        let current = Arc::new(Mutex::new(machine.initial.clone()));
+        let answer = current.clone();
+        ctx.on_query::<CurrentState<S>>(move || answer.lock().clone())?;
+
+        let mut state = machine.initial.clone();
+        let mut data = self.enter(&ctx, &state, input, Value::Null).await?;
+        while !machine.finals.contains(&state) {
+            let mut events: Vec<&str> = Vec::new();
+            for transition in machine.transitions.iter().filter(|t| t.from == state) {
+                if !events.contains(&transition.event.as_str()) {
+                    events.push(&transition.event);
+                }
+            }
+            let (event, payload) = ctx.wait_for_any_signal(&events).await?;
+            let taken = machine.transitions.iter().find(|t| {
+                t.from == state && t.event == event && t.guard.as_ref().is_none_or(|guard| guard(&data, &payload))
+            });
+            let Some(transition) = taken else {
+                tracing::debug!(workflow = %machine.name, state = ?state, event = %event, "no guard holds; dropping event");
+                continue;
+            };
+            ctx.record(EventType::StateTransitioned {
+                from: label(&state),
+                to: label(&transition.to),
+                event,
+            })
+            .await?;
+            state = transition.to.clone();
+            *current.lock() = state.clone();
+            data = self.enter(&ctx, &state, data, payload).await?;
+        }
+        Ok(StateMachineOutput { state, data })
+    }
+
+    /// 执行状态的进入活动并返回新的数据 / Run the state's on-entry activity and return the new data
+    async fn enter(&self, ctx: &WorkflowContext, state: &S, data: Value, event: Value) -> Result<Value, WorkflowError> {
+        let Some(entry) = self.definition.on_entry.get(state) else {
+            return Ok(data);
+        };
+        let input = serde_json::json!({ "data": data, "event": event });
+        let result = ctx.execute_activity_value(&entry.activity_type, input, &entry.options).await?;
+        Ok(if result.is_null() { data } else { result })
+    }
+}
+
+/// 状态在历史中的名称 / Name of a state in the history
+fn label<S: Serialize + Debug>(state: &S) -> String {
+    match serde_json::to_value(state) {
+        Ok(Value::String(name)) => name,
+        _ => format!("{:?}", state),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::temporal::{Activity, ActivityContext, ActivityError, StartWorkflowOptions, WorkerConfig, WorkflowId};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    enum Order {
+        Submitted,
+        Approved,
+        Escalated,
+        Rejected,
+        Shipped,
+    }
+
+    struct ReserveStock;
+
+    impl Activity for ReserveStock {
+        type Input = Value;
+        type Output = Value;
+
+        fn name() -> &'static str {
+            "reserve_stock"
+        }
+
+        async fn execute(_ctx: ActivityContext, input: Value) -> Result<Value, ActivityError> {
+            let mut order = input["data"].clone();
+            order["approved_by"] = input["event"]["by"].clone();
+            Ok(order)
+        }
+    }
+
+    fn approval() -> StateMachineBuilder<Order> {
+        StateMachineWorkflow::builder("order_approval", Order::Submitted)
+            .transition_if(Order::Submitted, "approve", Order::Escalated, |order, _| order["amount"].as_f64() > Some(1000.0))
+            .transition(Order::Submitted, "approve", Order::Approved)
+            .transition_if(Order::Escalated, "approve", Order::Approved, |_, approval| approval["by"] == "cfo")
+            .transition(Order::Submitted, "reject", Order::Rejected)
+            .on_entry(Order::Approved, "reserve_stock")
+            .transition(Order::Approved, "ship", Order::Shipped)
+            .final_state(Order::Shipped)
+            .final_state(Order::Rejected)
+    }
+
+    #[test]
+    fn test_build_rejects_dead_ends() {
+        assert!(approval().build().is_ok());
+        let dead_end = approval().transition(Order::Submitted, "hold", Order::Escalated).final_state(Order::Escalated);
+        assert!(matches!(dead_end.build(), Err(PatternError::InvalidDefinition(message)) if message.contains("Escalated has a transition")));
+        let stuck = StateMachineWorkflow::builder("stuck", Order::Submitted)
+            .transition(Order::Submitted, "approve", Order::Approved)
+            .final_state(Order::Shipped);
+        assert!(matches!(stuck.build(), Err(PatternError::InvalidDefinition(message)) if message.contains("Approved is neither final")));
+    }
+
+    #[tokio::test]
+    async fn test_transitions_on_signals_with_guards_and_entry_activities() {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        }));
+        approval().build().unwrap().register(&worker);
+        worker.register_activity::<ReserveStock>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let client = worker.client();
+        let id = WorkflowId::new("order-1");
+        let options = StartWorkflowOptions {
+            workflow_id: Some(id.clone()),
+            ..StartWorkflowOptions::default()
+        };
+        let handle = client
+            .start_dynamic_workflow("order_approval", serde_json::json!({"amount": 5000}), options)
+            .await
+            .unwrap();
+        // "ship" waits until a state accepts it; the manager cannot approve an escalated order
+        let manager = serde_json::json!({"by": "manager"});
+        for (event, payload) in [("ship", Value::Null), ("approve", manager.clone()), ("approve", manager)] {
+            client.signal_workflow_value(&id, event, payload).await.unwrap();
+        }
+        let mut state = None;
+        for _ in 0..100 {
+            state = client.query_workflow::<CurrentState<Order>>(&id).await.ok();
+            if state == Some(Order::Escalated) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(state, Some(Order::Escalated));
+        client.signal_workflow_value(&id, "approve", serde_json::json!({"by": "cfo"})).await.unwrap();
+
+        let output: StateMachineOutput<Order> = serde_json::from_value(handle.result().await.unwrap()).unwrap();
+        assert_eq!(output.state, Order::Shipped);
+        assert_eq!(output.data, serde_json::json!({"amount": 5000, "approved_by": "cfo"}));
+        let transitions: Vec<_> = handle
+            .history()
+            .await
+            .unwrap()
+            .events()
+            .iter()
+            .filter_map(|event| match &event.event_type {
+                EventType::StateTransitioned { from, to, event } => Some(format!("{} -{}-> {}", from, event, to)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            transitions,
+            ["Submitted -approve-> Escalated", "Escalated -approve-> Approved", "Approved -ship-> Shipped"]
+        );
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
@@ -8,6 +8,10 @@ use serde_json::json;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 
+pub mod work_pool;
+
+pub use work_pool::{JobHandle, JobPriority, WorkPool, WorkPoolConfig, WorkPoolError, WorkPoolStats};
+
 /// 初始化并发模式 / Initialize concurrent patterns
 pub fn init_concurrent_patterns() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("初始化并发工作流模式 / Initializing concurrent workflow patterns");
@@ -0,0 +1,429 @@
+//! # 优先级工作池 / Priority Work Pool
+//!
+//! 生产者提交带优先级的任务，固定数量（可动态调整）的工作者按优先级取出执行。为防止饥饿，任务每等待一个
+//! `aging_interval` 便提升一级；同一有效优先级按提交顺序执行。
+//! Producers submit jobs with a priority and a resizable set of workers runs them highest priority first. To
+//! prevent starvation a job moves up one level for every `aging_interval` it waits; jobs of the same effective
+//! priority run in submission order.
+//!
+//! 队列深度、等待时间与工作者数通过 [`metrics`] 门面记录，以 `pool` 与 `priority` 为标签。
+//! Queue depth, wait time and worker count are recorded through the [`metrics`] facade, labelled by `pool` and
+//! `priority`.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use metrics::{describe_gauge, describe_histogram, gauge, histogram, Unit};
+use parking_lot::Mutex;
+use tokio::sync::{oneshot, Notify};
+use tokio::time::Instant;
+
+pub const QUEUE_DEPTH: &str = "work_pool_queue_depth";
+pub const WAIT_TIME: &str = "work_pool_wait_seconds";
+pub const WORKERS: &str = "work_pool_workers";
+
+/// 向已安装的记录器登记工作池指标的单位与说明 / Register units and descriptions of the work pool metrics
+pub fn describe_metrics() {
+    describe_gauge!(QUEUE_DEPTH, "Jobs waiting in a work pool, by pool and priority");
+    describe_histogram!(WAIT_TIME, Unit::Seconds, "Time jobs waited before a worker started them, by pool and priority");
+    describe_gauge!(WORKERS, "Workers of a work pool, by pool");
+}
+
+/// 任务优先级，与 `MiddlewarePriority` 的级别相同 / Job priority, with the levels of `MiddlewarePriority`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum JobPriority {
+    Critical = 0,
+    High = 1,
+    #[default]
+    Normal = 2,
+    Low = 3,
+}
+
+impl JobPriority {
+    pub const ALL: [JobPriority; 4] = [JobPriority::Critical, JobPriority::High, JobPriority::Normal, JobPriority::Low];
+
+    fn label(self) -> &'static str {
+        match self {
+            JobPriority::Critical => "critical",
+            JobPriority::High => "high",
+            JobPriority::Normal => "normal",
+            JobPriority::Low => "low",
+        }
+    }
+}
+
+#[cfg(feature = "middleware")]
+impl From<crate::middleware::MiddlewarePriority> for JobPriority {
+    fn from(priority: crate::middleware::MiddlewarePriority) -> Self {
+        use crate::middleware::MiddlewarePriority;
+        match priority {
+            MiddlewarePriority::Critical => JobPriority::Critical,
+            MiddlewarePriority::High => JobPriority::High,
+            MiddlewarePriority::Normal => JobPriority::Normal,
+            MiddlewarePriority::Low => JobPriority::Low,
+        }
+    }
+}
+
+/// 工作池错误 / Work pool error
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WorkPoolError {
+    #[error("工作池已关闭 / Work pool is shut down")]
+    ShutDown,
+
+    #[error("任务发生 panic / Job panicked")]
+    JobPanicked,
+}
+
+/// 工作池配置 / Work pool configuration
+#[derive(Debug, Clone)]
+pub struct WorkPoolConfig {
+    /// 指标中的 `pool` 标签 / `pool` label of the metrics
+    pub name: String,
+    pub workers: usize,
+    /// 任务每等待这么久提升一级优先级 / A waiting job moves up one priority level per interval
+    pub aging_interval: Duration,
+}
+
+impl Default for WorkPoolConfig {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            workers: 4,
+            aging_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// 工作池的当前状况 / Current state of a work pool
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WorkPoolStats {
+    /// 各优先级排队的任务数，按 [`JobPriority::ALL`] 顺序 / Queued jobs per priority, in [`JobPriority::ALL`] order
+    pub queued: [usize; 4],
+    pub running: usize,
+    pub workers: usize,
+    pub completed: u64,
+    /// 已开始任务的平均等待时间 / Average wait of the jobs started so far
+    pub average_wait: Duration,
+}
+
+impl WorkPoolStats {
+    pub fn queue_depth(&self) -> usize {
+        self.queued.iter().sum()
+    }
+}
+
+struct Job {
+    seq: u64,
+    enqueued: Instant,
+    run: BoxFuture<'static, ()>,
+}
+
+#[derive(Default)]
+struct Queue {
+    levels: [VecDeque<Job>; 4],
+    next_seq: u64,
+}
+
+struct Shared {
+    config: WorkPoolConfig,
+    queue: Mutex<Queue>,
+    available: Notify,
+    target_workers: AtomicUsize,
+    workers: AtomicUsize,
+    running: AtomicUsize,
+    completed: AtomicU64,
+    started: AtomicU64,
+    waited_micros: AtomicU64,
+    shut_down: AtomicBool,
+    /// 最后一个工作者退出时通知 / Notified when the last worker exits
+    drained: Notify,
+}
+
+/// 带优先级队列的生产者-消费者工作池 / Producer-consumer work pool with priority queueing
+#[derive(Clone)]
+pub struct WorkPool {
+    shared: Arc<Shared>,
+}
+
+impl WorkPool {
+    /// 启动工作者；须在 Tokio 运行时中调用 / Start the workers; must be called within a Tokio runtime
+    pub fn new(config: WorkPoolConfig) -> Self {
+        let workers = config.workers;
+        let pool = Self {
+            shared: Arc::new(Shared {
+                config,
+                queue: Mutex::new(Queue::default()),
+                available: Notify::new(),
+                target_workers: AtomicUsize::new(0),
+                workers: AtomicUsize::new(0),
+                running: AtomicUsize::new(0),
+                completed: AtomicU64::new(0),
+                started: AtomicU64::new(0),
+                waited_micros: AtomicU64::new(0),
+                shut_down: AtomicBool::new(false),
+                drained: Notify::new(),
+            }),
+        };
+        pool.resize(workers);
+        pool
+    }
+
+    /// 提交任务，返回其结果的句柄 / Submit a job and get a handle to its result
+    pub fn submit<F, T>(&self, priority: JobPriority, job: F) -> Result<JobHandle<T>, WorkPoolError>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        if self.shared.shut_down.load(Ordering::Acquire) {
+            return Err(WorkPoolError::ShutDown);
+        }
+        let (sender, receiver) = oneshot::channel();
+        let run = async move {
+            if let Ok(output) = AssertUnwindSafe(job).catch_unwind().await {
+                let _ = sender.send(output);
+            }
+        }
+        .boxed();
+        let depth = {
+            let mut queue = self.shared.queue.lock();
+            let seq = queue.next_seq;
+            queue.next_seq += 1;
+            let level = &mut queue.levels[priority as usize];
+            level.push_back(Job {
+                seq,
+                enqueued: Instant::now(),
+                run,
+            });
+            level.len()
+        };
+        gauge!(QUEUE_DEPTH, "pool" => self.shared.config.name.clone(), "priority" => priority.label()).set(depth as f64);
+        self.shared.available.notify_one();
+        Ok(JobHandle { receiver })
+    }
+
+    /// 调整工作者数；减少时，工作者在完成当前任务后退出 / Change the number of workers; surplus workers exit after their current job
+    pub fn resize(&self, workers: usize) {
+        let shared = &self.shared;
+        if shared.shut_down.load(Ordering::Acquire) {
+            return;
+        }
+        shared.target_workers.store(workers, Ordering::Release);
+        loop {
+            let current = shared.workers.load(Ordering::Acquire);
+            if current >= workers {
+                break;
+            }
+            if shared.workers.compare_exchange(current, current + 1, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                tokio::spawn(work(shared.clone()));
+            }
+        }
+        gauge!(WORKERS, "pool" => shared.config.name.clone()).set(workers as f64);
+        // Idle workers look at the target again
+        shared.available.notify_waiters();
+    }
+
+    pub fn stats(&self) -> WorkPoolStats {
+        let shared = &self.shared;
+        let queued = {
+            let queue = shared.queue.lock();
+            [0, 1, 2, 3].map(|level| queue.levels[level].len())
+        };
+        let started = shared.started.load(Ordering::Relaxed);
+        let waited = shared.waited_micros.load(Ordering::Relaxed);
+        WorkPoolStats {
+            queued,
+            running: shared.running.load(Ordering::Relaxed),
+            workers: shared.workers.load(Ordering::Relaxed),
+            completed: shared.completed.load(Ordering::Relaxed),
+            average_wait: Duration::from_micros(waited.checked_div(started).unwrap_or_default()),
+        }
+    }
+
+    /// 排队的任务数 / Number of queued jobs
+    pub fn queue_depth(&self) -> usize {
+        self.shared.queue.lock().levels.iter().map(VecDeque::len).sum()
+    }
+
+    /// 停止接受任务，执行完已排队的任务后等待所有工作者退出 / Stop accepting jobs, run the queued ones and wait for all workers to exit
+    pub async fn shutdown(&self) {
+        let shared = &self.shared;
+        shared.shut_down.store(true, Ordering::Release);
+        shared.available.notify_waiters();
+        loop {
+            let drained = shared.drained.notified();
+            tokio::pin!(drained);
+            drained.as_mut().enable();
+            if shared.workers.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            drained.await;
+        }
+    }
+}
+
+impl Shared {
+    /// 取出有效优先级最高的任务 / Take the job with the highest effective priority
+    ///
+    /// 每一级中最早的任务等待最久，因此只需比较各级队首。
+    /// The oldest job of each level has waited longest, so comparing the fronts of the levels is enough.
+    fn take(&self) -> Option<(JobPriority, Job)> {
+        let now = Instant::now();
+        let aging = self.config.aging_interval.as_nanos().max(1);
+        let mut queue = self.queue.lock();
+        let (level, _) = JobPriority::ALL
+            .iter()
+            .filter_map(|priority| {
+                let job = queue.levels[*priority as usize].front()?;
+                let promoted = (now.duration_since(job.enqueued).as_nanos() / aging).min(*priority as u128) as usize;
+                Some((*priority, (*priority as usize - promoted, job.seq)))
+            })
+            .min_by_key(|(_, rank)| *rank)?;
+        let job = queue.levels[level as usize].pop_front()?;
+        let depth = queue.levels[level as usize].len();
+        drop(queue);
+        gauge!(QUEUE_DEPTH, "pool" => self.config.name.clone(), "priority" => level.label()).set(depth as f64);
+        Some((level, job))
+    }
+
+    /// 工作者数超过目标时让一个工作者退出 / Let one worker exit while there are more than the target
+    fn retire(&self) -> bool {
+        let current = self.workers.load(Ordering::Acquire);
+        let surplus = current > self.target_workers.load(Ordering::Acquire) || self.shut_down.load(Ordering::Acquire);
+        surplus && self.workers.compare_exchange(current, current - 1, Ordering::AcqRel, Ordering::Acquire).is_ok()
+    }
+}
+
+async fn work(shared: Arc<Shared>) {
+    loop {
+        let available = shared.available.notified();
+        tokio::pin!(available);
+        available.as_mut().enable();
+        let job = shared.take();
+        let Some((priority, job)) = job else {
+            // Shutting down drains the queue before workers retire
+            if shared.retire() {
+                break;
+            }
+            available.await;
+            continue;
+        };
+        let waited = job.enqueued.elapsed();
+        histogram!(WAIT_TIME, "pool" => shared.config.name.clone(), "priority" => priority.label()).record(waited.as_secs_f64());
+        shared.started.fetch_add(1, Ordering::Relaxed);
+        shared.waited_micros.fetch_add(waited.as_micros() as u64, Ordering::Relaxed);
+        shared.running.fetch_add(1, Ordering::AcqRel);
+        job.run.await;
+        shared.running.fetch_sub(1, Ordering::AcqRel);
+        shared.completed.fetch_add(1, Ordering::Relaxed);
+        if !shared.shut_down.load(Ordering::Acquire) && shared.retire() {
+            break;
+        }
+    }
+    if shared.workers.load(Ordering::Acquire) == 0 {
+        shared.drained.notify_waiters();
+    }
+}
+
+/// 任务结果的句柄 / Handle to the result of a job
+pub struct JobHandle<T> {
+    receiver: oneshot::Receiver<T>,
+}
+
+impl<T> Future for JobHandle<T> {
+    type Output = Result<T, WorkPoolError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver).poll(cx).map(|result| result.map_err(|_| WorkPoolError::JobPanicked))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::{Barrier, Semaphore};
+
+    fn pool(workers: usize) -> WorkPool {
+        WorkPool::new(WorkPoolConfig {
+            name: "test".to_string(),
+            workers,
+            aging_interval: Duration::from_secs(10),
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_priority_order_with_aging() {
+        let pool = pool(1);
+        let gate = Arc::new(Semaphore::new(0));
+        let blocker = gate.clone();
+        let blocked = pool.submit(JobPriority::Critical, async move { drop(blocker.acquire().await) }).unwrap();
+        tokio::task::yield_now().await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let submit = |priority: JobPriority, name: &'static str| {
+            let order = order.clone();
+            pool.submit(priority, async move { order.lock().push(name) }).unwrap()
+        };
+        // Waiting 30s promotes the old low priority job to critical, ahead of the newer critical one
+        let aged = submit(JobPriority::Low, "aged");
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        let handles = [
+            aged,
+            submit(JobPriority::Low, "low"),
+            submit(JobPriority::Normal, "normal"),
+            submit(JobPriority::Critical, "critical"),
+            submit(JobPriority::High, "high"),
+        ];
+        assert_eq!(pool.stats().queued, [1, 1, 1, 2]);
+
+        gate.add_permits(1);
+        blocked.await.unwrap();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(*order.lock(), ["aged", "critical", "high", "normal", "low"]);
+        let stats = pool.stats();
+        assert_eq!((stats.completed, stats.queue_depth()), (6, 0));
+        assert!(stats.average_wait >= Duration::from_secs(5), "{:?}", stats);
+
+        let panicked = pool.submit(JobPriority::Normal, async { panic!("job failed") }).unwrap();
+        assert_eq!(panicked.await, Err(WorkPoolError::JobPanicked));
+        pool.shutdown().await;
+        assert_eq!(pool.submit(JobPriority::Normal, async {}).err(), Some(WorkPoolError::ShutDown));
+    }
+
+    #[tokio::test]
+    async fn test_resizes_worker_count() {
+        let pool = pool(1);
+        pool.resize(3);
+        // Only completes if three jobs run at the same time
+        let barrier = Arc::new(Barrier::new(3));
+        let jobs: Vec<_> = (0..3)
+            .map(|_| {
+                let barrier = barrier.clone();
+                pool.submit(JobPriority::Normal, async move { barrier.wait().await.is_leader() }).unwrap()
+            })
+            .collect();
+        let leaders = futures::future::try_join_all(jobs).await.unwrap();
+        assert_eq!(leaders.iter().filter(|leader| **leader).count(), 1);
+
+        pool.resize(1);
+        for _ in 0..100 {
+            if pool.stats().workers == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(pool.stats().workers, 1);
+        pool.shutdown().await;
+        assert_eq!(pool.stats().workers, 0);
+    }
+}
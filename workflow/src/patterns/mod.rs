@@ -64,6 +64,9 @@ pub enum PatternError {
     
     #[error("模式不支持 / Pattern not supported: {0}")]
     PatternNotSupported(String),
+
+    #[error("定义无效 / Invalid definition: {0}")]
+    InvalidDefinition(String),
 }
 
 impl From<String> for PatternError {
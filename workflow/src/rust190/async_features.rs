@@ -3,6 +3,9 @@
 //! 本模块展示了 Rust 1.90 的异步迭代器改进和流处理增强
 //! This module demonstrates Rust 1.90's async iterator improvements and stream processing enhancements
 
+// 旧的定义结构保留给这些演示引擎，新代码使用 crate::model / The legacy definition structs stay for these demo engines, new code uses crate::model
+#![allow(deprecated)]
+
 // 移除未使用的导入 / Remove unused imports
 use std::time::Duration;
 use tokio::time::sleep;
@@ -186,6 +189,7 @@ pub struct AsyncWorkflowEngine {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
+#[deprecated(note = "use workflow::model::WorkflowDefinition instead")]
 pub struct WorkflowDefinition {
     pub name: String,
     pub steps: Vec<WorkflowStep>,
@@ -195,6 +199,7 @@ pub struct WorkflowDefinition {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
+#[deprecated(note = "use workflow::model::WorkflowStep instead")]
 pub struct WorkflowStep {
     pub name: String,
     pub action: String,
@@ -3,6 +3,9 @@
 //! 本模块展示了 Rust 1.90 的 const 特性增强
 //! This module demonstrates Rust 1.90's enhanced const features
 
+// 旧的定义结构保留给这些演示引擎，新代码使用 crate::model / The legacy definition structs stay for these demo engines, new code uses crate::model
+#![allow(deprecated)]
+
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
@@ -106,6 +109,7 @@ impl ConstWorkflowEngine {
 
 /// const 工作流步骤 / const Workflow Step
 #[derive(Debug, Clone, Copy)]
+#[deprecated(note = "use workflow::model::WorkflowStep instead")]
 pub struct ConstWorkflowStep {
     pub id: u32,
     pub name: &'static str,
@@ -131,6 +135,7 @@ impl ConstWorkflowStep {
 }
 
 /// const 工作流定义 / const Workflow Definition
+#[deprecated(note = "use workflow::model::WorkflowDefinition instead")]
 pub struct ConstWorkflowDefinition {
     pub name: &'static str,
     pub steps: &'static [ConstWorkflowStep],
@@ -26,12 +26,14 @@ pub mod session_types;
 // Note: Avoid glob re-exports to prevent type name conflicts
 
 // 解决类型冲突，使用明确的类型别名 / Resolve type conflicts with explicit type aliases
+// 其中的工作流定义结构已弃用，见 crate::model / Their workflow definition structs are deprecated, see crate::model
 pub use features::{
     JITOptimizedProcessor, SmallObjectManager, TypeCheckerOptimized,
     Rust190WorkflowEngine, WorkflowResult, ObjectStats, CompilationStats,
     ModuleInfo, SmallObject,
 };
 
+#[allow(deprecated)]
 pub use async_features::{
     AsyncData, AsyncStreamProcessor, HighPerformanceStreamProcessor,
     AsyncWorkflowEngine as AsyncWorkflowEngine190,
@@ -39,6 +41,7 @@ pub use async_features::{
     WorkflowStep as AsyncWorkflowStep,
 };
 
+#[allow(deprecated)]
 pub use performance::{
     PerformanceMonitor, PerformanceMetrics, OverallPerformanceStats,
     HighPerformanceWorkflowEngine as HighPerformanceWorkflowEngine190,
@@ -48,6 +51,7 @@ pub use performance::{
     PerformanceBenchmark, BenchmarkData, BenchmarkResult,
 };
 
+#[allow(deprecated)]
 pub use stable_apis::{
     BufReadProcessor, ControlFlowProcessor, DebugListProcessor,
     StableAPIWorkflowEngine as StableAPIWorkflowEngine190,
@@ -56,12 +60,14 @@ pub use stable_apis::{
     WorkflowConfig as StableWorkflowConfig,
 };
 
+#[allow(deprecated)]
 pub use const_features::{
     ConstContextProcessor, ConstWorkflowEngine, ConstWorkflowStep,
     WorkflowConfig as ConstWorkflowConfig,
     ExecutionStatus as ConstExecutionStatus,
 };
 
+#[allow(deprecated)]
 #[cfg(feature = "session_types")]
 pub use session_types::{
     SessionTypesWorkflowEngine, WorkflowSession, Participant, ParticipantRole,
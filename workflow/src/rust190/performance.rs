@@ -3,6 +3,9 @@
 //! 本模块展示了 Rust 1.90 的性能改进和优化
 //! This module demonstrates Rust 1.90's performance improvements and optimizations
 
+// 旧的定义结构保留给这些演示引擎，新代码使用 crate::model / The legacy definition structs stay for these demo engines, new code uses crate::model
+#![allow(deprecated)]
+
 use std::time::{Duration, Instant};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -111,6 +114,7 @@ pub struct HighPerformanceWorkflowEngine {
 
 /// 工作流定义 / Workflow Definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[deprecated(note = "use workflow::model::WorkflowDefinition instead")]
 pub struct WorkflowDefinition {
     pub name: String,
     pub steps: Vec<WorkflowStep>,
@@ -121,6 +125,7 @@ pub struct WorkflowDefinition {
 
 /// 工作流步骤 / Workflow Step
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[deprecated(note = "use workflow::model::WorkflowStep instead")]
 pub struct WorkflowStep {
     pub name: String,
     pub action: String,
@@ -6,6 +6,9 @@
 //! 会话类型是一种类型系统，用于确保并发程序中的通信安全
 //! Session types are a type system for ensuring communication safety in concurrent programs
 
+// 旧的定义结构保留给这些演示引擎，新代码使用 crate::model / The legacy definition structs stay for these demo engines, new code uses crate::model
+#![allow(deprecated)]
+
 use std::sync::Arc;
 // 移除未使用的导入 / Remove unused imports
 use serde::{Deserialize, Serialize};
@@ -260,6 +263,7 @@ pub enum WorkflowProtocol {
 
 /// 工作流步骤 / Workflow Step
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[deprecated(note = "use workflow::model::WorkflowStep instead")]
 pub struct WorkflowStep {
     pub name: String,
     pub action: String,
@@ -3,6 +3,9 @@
 //! 本模块展示了 Rust 1.90 中新稳定的 API
 //! This module demonstrates newly stabilized APIs in Rust 1.90
 
+// 旧的定义结构保留给这些演示引擎，新代码使用 crate::model / The legacy definition structs stay for these demo engines, new code uses crate::model
+#![allow(deprecated)]
+
 use std::io::{BufRead, BufReader, Cursor};
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
@@ -235,6 +238,7 @@ pub struct StableAPIWorkflowEngine {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[deprecated(note = "use workflow::model::WorkflowDefinition instead")]
 pub struct WorkflowDefinition {
     pub name: String,
     pub steps: Vec<WorkflowStep>,
@@ -242,6 +246,7 @@ pub struct WorkflowDefinition {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[deprecated(note = "use workflow::model::WorkflowStep instead")]
 pub struct WorkflowStep {
     pub name: String,
     pub action: String,
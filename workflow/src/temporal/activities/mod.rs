@@ -0,0 +1,424 @@
+//! Built-in activities for outbound HTTP calls and notifications
+//!
+//! [`HttpRequestActivity`] sends an arbitrary request and [`WebhookNotifyActivity`] posts an
+//! HMAC-signed event to a webhook, so workflows reach external services without hand-written
+//! HTTP plumbing. Register them on a worker like any other activity:
+//!
+//! ```no_run
+//! # use workflow::temporal::{WorkflowWorker, WorkerConfig};
+//! use workflow::temporal::activities::{HttpRequestActivity, WebhookNotifyActivity};
+//!
+//! let worker = WorkflowWorker::new(WorkerConfig::default());
+//! worker.register_activity::<HttpRequestActivity>();
+//! worker.register_activity::<WebhookNotifyActivity>();
+//! WebhookNotifyActivity::register_secret("orders", "s3cret");
+//! ```
+//!
+//! Failures map onto the worker's retries: connection errors, timeouts and the statuses in
+//! [`RETRYABLE_STATUSES`] fail with [`ActivityError::TemporaryFailure`], any other error status
+//! fails with [`ActivityError::ExecutionFailed`], which [`http_retry_policy`] does not retry.
+//! Webhook signing secrets are registered by name in the worker process and never enter the
+//! workflow history.
+//!
+//! With the `notifications` feature, [`notifications`] adds email and SMS activities.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+
+use super::{Activity, ActivityContext, ActivityError, ActivityOptions};
+use super::activity::RetryPolicy;
+
+#[cfg(feature = "notifications")]
+pub mod notifications;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Response statuses worth another attempt
+pub const RETRYABLE_STATUSES: [u16; 7] = [408, 425, 429, 500, 502, 503, 504];
+
+/// Header carrying the webhook event name
+pub const WEBHOOK_EVENT_HEADER: &str = "x-webhook-event";
+/// Header carrying an id identical across attempts of one delivery, for deduplication
+pub const WEBHOOK_ID_HEADER: &str = "x-webhook-id";
+/// Header carrying the signing time in Unix seconds
+pub const WEBHOOK_TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+/// Header carrying `sha256=<hex HMAC of "{timestamp}.{body}">`
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+/// `policy` with error statuses other than [`RETRYABLE_STATUSES`] made non-retryable
+pub fn http_retry_policy(mut policy: RetryPolicy) -> RetryPolicy {
+    let execution_failed = ActivityError::ExecutionFailed(String::new()).error_type().to_string();
+    if !policy.non_retryable_error_types.contains(&execution_failed) {
+        policy.non_retryable_error_types.push(execution_failed);
+    }
+    policy
+}
+
+/// Default options with the retry policy mapped by [`http_retry_policy`]
+pub fn http_activity_options() -> ActivityOptions {
+    let options = ActivityOptions::default();
+    ActivityOptions {
+        retry_policy: options.retry_policy.map(http_retry_policy),
+        ..options
+    }
+}
+
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(reqwest::Client::new)
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// Input of [`HttpRequestActivity`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpRequest {
+    /// Method, `GET` by default
+    #[serde(default = "default_method")]
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// JSON body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+impl HttpRequest {
+    pub fn new(method: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            method: method.into(),
+            url: url.into(),
+            headers: BTreeMap::new(),
+            body: None,
+        }
+    }
+
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new("GET", url)
+    }
+
+    pub fn post(url: impl Into<String>, body: Value) -> Self {
+        Self::new("POST", url).with_body(body)
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn with_body(mut self, body: Value) -> Self {
+        self.body = Some(body);
+        self
+    }
+}
+
+/// Output of [`HttpRequestActivity`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    /// Body parsed as JSON, or as a string when it is not JSON, `null` when empty
+    pub body: Value,
+}
+
+/// Send `body` as raw bytes, failing on error statuses
+async fn send(ctx: &ActivityContext, request: &HttpRequest, body: Option<Vec<u8>>) -> Result<HttpResponse, ActivityError> {
+    let method = reqwest::Method::from_bytes(request.method.to_ascii_uppercase().as_bytes())
+        .map_err(|_| ActivityError::InvalidInput(format!("invalid HTTP method {:?}", request.method)))?;
+    let url = reqwest::Url::parse(&request.url)
+        .map_err(|e| ActivityError::InvalidInput(format!("invalid URL {:?}: {}", request.url, e)))?;
+    let mut builder = http_client().request(method, url);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    if let Some(body) = body {
+        builder = builder.header(reqwest::header::CONTENT_TYPE, "application/json").body(body);
+    }
+    let response = tokio::select! {
+        response = builder.send() => response,
+        _ = ctx.cancelled() => return Err(ActivityError::Cancelled),
+    }
+    .map_err(|e| {
+        if e.is_builder() {
+            ActivityError::InvalidInput(e.to_string())
+        } else {
+            ActivityError::TemporaryFailure(e.to_string())
+        }
+    })?;
+
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let bytes = response.bytes().await.map_err(|e| ActivityError::TemporaryFailure(e.to_string()))?;
+    let body = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&bytes).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+    };
+    if status < 400 {
+        return Ok(HttpResponse { status, headers, body });
+    }
+    let message = format!("{} {} returned {}: {}", request.method, request.url, status, body);
+    if RETRYABLE_STATUSES.contains(&status) {
+        Err(ActivityError::TemporaryFailure(message))
+    } else {
+        Err(ActivityError::ExecutionFailed(message))
+    }
+}
+
+/// Sends an HTTP request and returns the response; error statuses fail the attempt
+pub struct HttpRequestActivity;
+
+impl Activity for HttpRequestActivity {
+    type Input = HttpRequest;
+    type Output = HttpResponse;
+
+    fn name() -> &'static str {
+        "http_request"
+    }
+
+    async fn execute(ctx: ActivityContext, input: HttpRequest) -> Result<HttpResponse, ActivityError> {
+        let body = input
+            .body
+            .as_ref()
+            .map(serde_json::to_vec)
+            .transpose()
+            .map_err(|e| ActivityError::InvalidInput(e.to_string()))?;
+        send(&ctx, &input, body).await
+    }
+}
+
+/// Input of [`WebhookNotifyActivity`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookNotification {
+    pub url: String,
+    pub event: String,
+    pub payload: Value,
+    /// Name of the registered secret signing the delivery; unsigned without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl WebhookNotification {
+    pub fn new(url: impl Into<String>, event: impl Into<String>, payload: Value) -> Self {
+        Self {
+            url: url.into(),
+            event: event.into(),
+            payload,
+            secret: None,
+            headers: BTreeMap::new(),
+        }
+    }
+
+    /// Sign with the secret registered as `name`
+    pub fn signed_with(mut self, name: impl Into<String>) -> Self {
+        self.secret = Some(name.into());
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+}
+
+/// Output of [`WebhookNotifyActivity`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub status: u16,
+}
+
+fn secrets() -> &'static RwLock<HashMap<String, Vec<u8>>> {
+    static SECRETS: OnceLock<RwLock<HashMap<String, Vec<u8>>>> = OnceLock::new();
+    SECRETS.get_or_init(Default::default)
+}
+
+/// Secret registered as `name` with [`WebhookNotifyActivity::register_secret`]
+pub(crate) fn registered_secret(name: &str) -> Option<Vec<u8>> {
+    secrets().read().get(name).cloned()
+}
+
+fn mac(secret: &[u8], timestamp: &str, body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Posts an event as JSON to a webhook, signed with a registered secret
+///
+/// The body is `{"event": ..., "payload": ...}`. Every attempt carries the same
+/// [`WEBHOOK_ID_HEADER`] so receivers can drop duplicate deliveries.
+pub struct WebhookNotifyActivity;
+
+impl WebhookNotifyActivity {
+    /// Register `secret` as `name` for signing in this process, replacing any secret of that name
+    pub fn register_secret(name: impl Into<String>, secret: impl AsRef<[u8]>) {
+        secrets().write().insert(name.into(), secret.as_ref().to_vec());
+    }
+
+    /// Signature header value for `body` signed at `timestamp`
+    pub fn sign(secret: impl AsRef<[u8]>, timestamp: &str, body: &[u8]) -> String {
+        let digest = mac(secret.as_ref(), timestamp, body).finalize().into_bytes();
+        let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("sha256={}", hex)
+    }
+
+    /// Check a received signature header in constant time; for receivers
+    pub fn verify(secret: impl AsRef<[u8]>, timestamp: &str, body: &[u8], signature: &str) -> bool {
+        let Some(hex) = signature.strip_prefix("sha256=") else { return false };
+        if hex.len() != 64 || !hex.is_ascii() {
+            return false;
+        }
+        let Ok(expected) = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+        else {
+            return false;
+        };
+        mac(secret.as_ref(), timestamp, body).verify_slice(&expected).is_ok()
+    }
+}
+
+impl Activity for WebhookNotifyActivity {
+    type Input = WebhookNotification;
+    type Output = WebhookDelivery;
+
+    fn name() -> &'static str {
+        "webhook_notify"
+    }
+
+    async fn execute(ctx: ActivityContext, input: WebhookNotification) -> Result<WebhookDelivery, ActivityError> {
+        let body = serde_json::to_vec(&serde_json::json!({"event": input.event, "payload": input.payload}))
+            .map_err(|e| ActivityError::InvalidInput(e.to_string()))?;
+        let id = format!("{}/{}", ctx.workflow_execution().workflow_id.as_str(), ctx.activity_id().as_str());
+        let mut request = HttpRequest::new("POST", input.url.clone());
+        request.headers = input.headers.clone();
+        request.headers.insert(WEBHOOK_EVENT_HEADER.to_string(), input.event.clone());
+        request.headers.insert(WEBHOOK_ID_HEADER.to_string(), id.clone());
+        if let Some(name) = &input.secret {
+            let secret = registered_secret(name)
+                .ok_or_else(|| ActivityError::InvalidInput(format!("webhook secret {:?} is not registered", name)))?;
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs().to_string();
+            request.headers.insert(WEBHOOK_SIGNATURE_HEADER.to_string(), Self::sign(secret, &timestamp, &body));
+            request.headers.insert(WEBHOOK_TIMESTAMP_HEADER.to_string(), timestamp);
+        }
+        let response = send(&ctx, &request, Some(body)).await?;
+        Ok(WebhookDelivery { id, status: response.status })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use axum::body::Bytes;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::{get, post};
+    use serde_json::json;
+
+    use crate::temporal::{ActivityId, WorkflowExecution, WorkflowId};
+
+    fn context() -> ActivityContext {
+        ActivityContext::new(ActivityId::new("notify"), WorkflowExecution::new(WorkflowId::new("order-1")))
+    }
+
+    async fn serve(router: axum::Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{}", address)
+    }
+
+    #[test]
+    fn test_signatures_verify() {
+        let signature = WebhookNotifyActivity::sign("s3cret", "1700000000", b"{}");
+        assert!(WebhookNotifyActivity::verify("s3cret", "1700000000", b"{}", &signature));
+        assert!(!WebhookNotifyActivity::verify("other", "1700000000", b"{}", &signature));
+        assert!(!WebhookNotifyActivity::verify("s3cret", "1700000001", b"{}", &signature));
+        assert!(!WebhookNotifyActivity::verify("s3cret", "1700000000", b"{}", "sha256=zz"));
+
+        let policy = http_activity_options().retry_policy.unwrap();
+        assert!(policy.should_retry(1, &ActivityError::TemporaryFailure("503".into())));
+        assert!(!policy.should_retry(1, &ActivityError::ExecutionFailed("404".into())));
+    }
+
+    #[tokio::test]
+    async fn test_http_statuses_map_onto_retries() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let url = serve(
+            axum::Router::new()
+                .route("/echo", post(|headers: HeaderMap, body: Bytes| async move {
+                    let tag = headers["x-tag"].to_str().unwrap().to_string();
+                    axum::Json(json!({"tag": tag, "body": serde_json::from_slice::<Value>(&body).unwrap()}))
+                }))
+                .route("/flaky", get(move || async move {
+                    if counter.fetch_add(1, Ordering::SeqCst) == 0 { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::OK }
+                }))
+                .route("/missing", get(|| async { (StatusCode::NOT_FOUND, "no such order") })),
+        )
+        .await;
+
+        let request = HttpRequest::post(format!("{}/echo", url), json!({"id": 7})).with_header("x-tag", "a");
+        let response = HttpRequestActivity::execute(context(), request).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, json!({"tag": "a", "body": {"id": 7}}));
+
+        let flaky = HttpRequest::get(format!("{}/flaky", url));
+        assert!(matches!(HttpRequestActivity::execute(context(), flaky.clone()).await, Err(ActivityError::TemporaryFailure(_))));
+        assert_eq!(HttpRequestActivity::execute(context(), flaky).await.unwrap().body, Value::Null);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let missing = HttpRequestActivity::execute(context(), HttpRequest::get(format!("{}/missing", url))).await;
+        assert!(matches!(missing, Err(ActivityError::ExecutionFailed(message)) if message.contains("no such order")));
+        let invalid = HttpRequestActivity::execute(context(), HttpRequest::get("not a url")).await;
+        assert!(matches!(invalid, Err(ActivityError::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn test_webhooks_are_signed() {
+        let url = serve(axum::Router::new().route("/hook", post(|headers: HeaderMap, body: Bytes| async move {
+            let header = |name: &str| headers[name].to_str().unwrap().to_string();
+            let valid = WebhookNotifyActivity::verify(
+                "hook-secret",
+                &header(WEBHOOK_TIMESTAMP_HEADER),
+                &body,
+                &header(WEBHOOK_SIGNATURE_HEADER),
+            );
+            assert_eq!(header(WEBHOOK_EVENT_HEADER), "order.shipped");
+            assert_eq!(header(WEBHOOK_ID_HEADER), "order-1/notify");
+            assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), json!({"event": "order.shipped", "payload": {"id": 1}}));
+            if valid { StatusCode::ACCEPTED } else { StatusCode::UNAUTHORIZED }
+        })))
+        .await;
+
+        WebhookNotifyActivity::register_secret("test-hook", "hook-secret");
+        let notification = WebhookNotification::new(format!("{}/hook", url), "order.shipped", json!({"id": 1}));
+        let delivery = WebhookNotifyActivity::execute(context(), notification.clone().signed_with("test-hook")).await.unwrap();
+        assert_eq!((delivery.id.as_str(), delivery.status), ("order-1/notify", 202));
+
+        let unknown = WebhookNotifyActivity::execute(context(), notification.signed_with("unregistered")).await;
+        assert!(matches!(unknown, Err(ActivityError::InvalidInput(_))));
+    }
+}
@@ -0,0 +1,450 @@
+//! Email and SMS notification activities
+//!
+//! [`SendEmailActivity`] delivers mail through an [`EmailTransport`], normally an [`SmtpTransport`],
+//! and [`SendSmsActivity`] texts through an [`SmsProvider`] plugged in for the gateway at hand. Both
+//! fill `{{name}}` placeholders in their messages from the variables passed by the workflow, with
+//! dotted names reaching into objects (`{{order.id}}`); a placeholder without a variable fails the
+//! activity without retries.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! use workflow::temporal::activities::notifications::{EmailNotification, SendEmailActivity, SmtpConfig, SmtpTransport};
+//!
+//! # fn setup() -> Result<(), workflow::temporal::WorkflowError> {
+//! let smtp = SmtpTransport::new(SmtpConfig::new("smtp.example.com", "shop@example.com").with_credentials("shop", "secret"))?;
+//! SendEmailActivity::use_transport(Arc::new(smtp));
+//!
+//! let email = EmailNotification::new("ada@example.com", "Order {{order.id}} shipped", "Tracking: {{tracking}}")
+//!     .with_variable("order", serde_json::json!({"id": "1042"}))
+//!     .with_variable("tracking", "TRK-7");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Transports and providers are installed per process, like webhook secrets, so credentials never
+//! enter the workflow history.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
+
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::temporal::{Activity, ActivityContext, ActivityError, WorkflowError};
+
+/// Fill the `{{name}}` placeholders of `template` from `variables`
+///
+/// Dotted names look into nested objects and arrays; strings are inserted as they are, other
+/// values as JSON.
+pub fn render_template(template: &str, variables: &BTreeMap<String, Value>) -> Result<String, ActivityError> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| ActivityError::InvalidInput(format!("unclosed placeholder in {:?}", template)))?;
+        let name = rest[start + 2..start + end].trim();
+        let mut path = name.split('.');
+        let value = path
+            .next()
+            .and_then(|first| variables.get(first))
+            .and_then(|value| path.try_fold(value, |value, key| match value {
+                Value::Array(items) => key.parse::<usize>().ok().and_then(|index| items.get(index)),
+                _ => value.get(key),
+            }))
+            .ok_or_else(|| ActivityError::InvalidInput(format!("no variable for placeholder {{{{{}}}}}", name)))?;
+        match value {
+            Value::String(text) => rendered.push_str(text),
+            other => rendered.push_str(&other.to_string()),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+fn variable(value: impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+/// Rendered email handed to a transport
+#[derive(Debug, Clone, PartialEq)]
+pub struct Email {
+    pub to: Vec<String>,
+    pub subject: String,
+    pub body: String,
+    pub html: bool,
+}
+
+/// Delivers emails
+#[async_trait]
+pub trait EmailTransport: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), ActivityError>;
+}
+
+/// Sends texts through an SMS gateway
+#[async_trait]
+pub trait SmsProvider: Send + Sync {
+    /// Send `body` to `to`, returning the gateway's message id if it has one
+    async fn send(&self, to: &str, body: &str) -> Result<Option<String>, ActivityError>;
+}
+
+/// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// TLS from the start, port 465 by default
+    #[default]
+    Tls,
+    /// Upgrade with STARTTLS, port 587 by default
+    StartTls,
+    /// Unencrypted, port 25 by default; for relays on the local network only
+    None,
+}
+
+/// SMTP server settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// Defaults to the port of the security mode
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Sender, as `address` or `Name <address>`
+    pub from: String,
+}
+
+impl SmtpConfig {
+    pub fn new(host: impl Into<String>, from: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            port: None,
+            security: SmtpSecurity::default(),
+            username: None,
+            password: None,
+            from: from.into(),
+        }
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn with_security(mut self, security: SmtpSecurity) -> Self {
+        self.security = security;
+        self
+    }
+
+    pub fn with_credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+}
+
+/// [`EmailTransport`] submitting mail to an SMTP server over a connection pool
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpTransport {
+    /// Must be called within a Tokio runtime, which runs the connection pool
+    pub fn new(config: SmtpConfig) -> Result<Self, WorkflowError> {
+        let from = config
+            .from
+            .parse::<Mailbox>()
+            .map_err(|e| WorkflowError::InvalidInput(format!("invalid sender {:?}: {}", config.from, e)))?;
+        let builder = match config.security {
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host),
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host),
+            SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)),
+        }
+        .map_err(|e| WorkflowError::InvalidInput(format!("invalid SMTP host {:?}: {}", config.host, e)))?;
+        let builder = match config.port {
+            Some(port) => builder.port(port),
+            None => builder,
+        };
+        let builder = match (config.username, config.password) {
+            (Some(username), Some(password)) => builder.credentials(Credentials::new(username, password)),
+            _ => builder,
+        };
+        Ok(Self {
+            mailer: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, email: &Email) -> Result<(), ActivityError> {
+        let mut message = Message::builder().from(self.from.clone()).subject(&email.subject);
+        for recipient in &email.to {
+            let mailbox = recipient
+                .parse::<Mailbox>()
+                .map_err(|e| ActivityError::InvalidInput(format!("invalid recipient {:?}: {}", recipient, e)))?;
+            message = message.to(mailbox);
+        }
+        let content_type = if email.html { ContentType::TEXT_HTML } else { ContentType::TEXT_PLAIN };
+        let message = message
+            .header(content_type)
+            .body(email.body.clone())
+            .map_err(|e| ActivityError::InvalidInput(e.to_string()))?;
+        self.mailer.send(message).await.map(drop).map_err(|e| {
+            if e.is_permanent() {
+                ActivityError::ExecutionFailed(e.to_string())
+            } else {
+                ActivityError::TemporaryFailure(e.to_string())
+            }
+        })
+    }
+}
+
+fn email_transport() -> &'static RwLock<Option<Arc<dyn EmailTransport>>> {
+    static TRANSPORT: OnceLock<RwLock<Option<Arc<dyn EmailTransport>>>> = OnceLock::new();
+    TRANSPORT.get_or_init(Default::default)
+}
+
+fn sms_provider() -> &'static RwLock<Option<Arc<dyn SmsProvider>>> {
+    static PROVIDER: OnceLock<RwLock<Option<Arc<dyn SmsProvider>>>> = OnceLock::new();
+    PROVIDER.get_or_init(Default::default)
+}
+
+/// Input of [`SendEmailActivity`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailNotification {
+    pub to: Vec<String>,
+    /// Subject template
+    pub subject: String,
+    /// Body template
+    pub body: String,
+    /// Send the body as HTML rather than plain text
+    #[serde(default)]
+    pub html: bool,
+    #[serde(default)]
+    pub variables: BTreeMap<String, Value>,
+}
+
+impl EmailNotification {
+    pub fn new(to: impl Into<String>, subject: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            to: vec![to.into()],
+            subject: subject.into(),
+            body: body.into(),
+            html: false,
+            variables: BTreeMap::new(),
+        }
+    }
+
+    pub fn also_to(mut self, to: impl Into<String>) -> Self {
+        self.to.push(to.into());
+        self
+    }
+
+    pub fn html(mut self) -> Self {
+        self.html = true;
+        self
+    }
+
+    pub fn with_variable(mut self, name: impl Into<String>, value: impl Serialize) -> Self {
+        self.variables.insert(name.into(), variable(value));
+        self
+    }
+}
+
+/// Input of [`SendSmsActivity`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmsNotification {
+    pub to: String,
+    /// Message template
+    pub body: String,
+    #[serde(default)]
+    pub variables: BTreeMap<String, Value>,
+}
+
+impl SmsNotification {
+    pub fn new(to: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            to: to.into(),
+            body: body.into(),
+            variables: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_variable(mut self, name: impl Into<String>, value: impl Serialize) -> Self {
+        self.variables.insert(name.into(), variable(value));
+        self
+    }
+}
+
+/// Output of the notification activities
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationReceipt {
+    pub recipients: Vec<String>,
+    /// Message id assigned by the SMS gateway
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+}
+
+/// Sends a templated email through the installed [`EmailTransport`]
+pub struct SendEmailActivity;
+
+impl SendEmailActivity {
+    /// Deliver emails of this process through `transport`, replacing the previous one
+    pub fn use_transport(transport: Arc<dyn EmailTransport>) {
+        *email_transport().write() = Some(transport);
+    }
+}
+
+impl Activity for SendEmailActivity {
+    type Input = EmailNotification;
+    type Output = NotificationReceipt;
+
+    fn name() -> &'static str {
+        "send_email"
+    }
+
+    async fn execute(_ctx: ActivityContext, input: EmailNotification) -> Result<NotificationReceipt, ActivityError> {
+        if input.to.is_empty() {
+            return Err(ActivityError::InvalidInput("email has no recipients".to_string()));
+        }
+        let email = Email {
+            to: input.to,
+            subject: render_template(&input.subject, &input.variables)?,
+            body: render_template(&input.body, &input.variables)?,
+            html: input.html,
+        };
+        let transport = email_transport()
+            .read()
+            .clone()
+            .ok_or_else(|| ActivityError::ExecutionFailed("no email transport installed".to_string()))?;
+        transport.send(&email).await?;
+        Ok(NotificationReceipt {
+            recipients: email.to,
+            message_id: None,
+        })
+    }
+}
+
+/// Sends a templated text through the installed [`SmsProvider`]
+pub struct SendSmsActivity;
+
+impl SendSmsActivity {
+    /// Send texts of this process through `provider`, replacing the previous one
+    pub fn use_provider(provider: Arc<dyn SmsProvider>) {
+        *sms_provider().write() = Some(provider);
+    }
+}
+
+impl Activity for SendSmsActivity {
+    type Input = SmsNotification;
+    type Output = NotificationReceipt;
+
+    fn name() -> &'static str {
+        "send_sms"
+    }
+
+    async fn execute(_ctx: ActivityContext, input: SmsNotification) -> Result<NotificationReceipt, ActivityError> {
+        let body = render_template(&input.body, &input.variables)?;
+        let provider = sms_provider()
+            .read()
+            .clone()
+            .ok_or_else(|| ActivityError::ExecutionFailed("no SMS provider installed".to_string()))?;
+        let message_id = provider.send(&input.to, &body).await?;
+        Ok(NotificationReceipt {
+            recipients: vec![input.to],
+            message_id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use serde_json::json;
+
+    use crate::temporal::{ActivityId, WorkflowExecution, WorkflowId};
+
+    #[derive(Default)]
+    struct Outbox {
+        emails: Mutex<Vec<Email>>,
+        texts: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl EmailTransport for Outbox {
+        async fn send(&self, email: &Email) -> Result<(), ActivityError> {
+            self.emails.lock().push(email.clone());
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl SmsProvider for Outbox {
+        async fn send(&self, to: &str, body: &str) -> Result<Option<String>, ActivityError> {
+            let mut texts = self.texts.lock();
+            texts.push((to.to_string(), body.to_string()));
+            Ok(Some(format!("sms-{}", texts.len())))
+        }
+    }
+
+    fn context() -> ActivityContext {
+        ActivityContext::new(ActivityId::new("notify"), WorkflowExecution::new(WorkflowId::new("order-1")))
+    }
+
+    #[test]
+    fn test_templates_render_workflow_variables() {
+        let variables = BTreeMap::from([
+            ("order".to_string(), json!({"id": "1042", "items": [{"sku": "A-1"}], "total": 99.5})),
+            ("name".to_string(), json!("Ada")),
+        ]);
+        let rendered = render_template("Hi {{ name }}, order {{order.id}} ({{order.items.0.sku}}) costs {{order.total}}", &variables);
+        assert_eq!(rendered.unwrap(), "Hi Ada, order 1042 (A-1) costs 99.5");
+        assert!(matches!(render_template("{{order.missing}}", &variables), Err(ActivityError::InvalidInput(_))));
+        assert!(matches!(render_template("{{name", &variables), Err(ActivityError::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn test_notifications_go_through_installed_providers() {
+        let config: SmtpConfig = serde_json::from_value(json!({"host": "localhost", "security": "none", "port": 2525, "from": "Shop <shop@example.com>"})).unwrap();
+        assert!(SmtpTransport::new(config).is_ok());
+        assert!(SmtpTransport::new(SmtpConfig::new("localhost", "not an address")).is_err());
+
+        let outbox = Arc::new(Outbox::default());
+        SendEmailActivity::use_transport(outbox.clone());
+        SendSmsActivity::use_provider(outbox.clone());
+
+        let email = EmailNotification::new("ada@example.com", "Order {{order}} shipped", "Tracking: {{tracking}}")
+            .also_to("ops@example.com")
+            .with_variable("order", 1042)
+            .with_variable("tracking", "TRK-7");
+        let receipt = SendEmailActivity::execute(context(), email).await.unwrap();
+        assert_eq!(receipt.recipients, ["ada@example.com", "ops@example.com"]);
+        assert_eq!(outbox.emails.lock()[0].subject, "Order 1042 shipped");
+        assert_eq!(outbox.emails.lock()[0].body, "Tracking: TRK-7");
+
+        let text = SmsNotification::new("+15550100", "Order {{order}} is on its way").with_variable("order", 1042);
+        let receipt = SendSmsActivity::execute(context(), text).await.unwrap();
+        assert_eq!(receipt.message_id.as_deref(), Some("sms-1"));
+        assert_eq!(outbox.texts.lock()[0], ("+15550100".to_string(), "Order 1042 is on its way".to_string()));
+
+        let missing = SmsNotification::new("+15550100", "{{tracking}}");
+        assert!(matches!(SendSmsActivity::execute(context(), missing).await, Err(ActivityError::InvalidInput(_))));
+        assert_eq!(outbox.texts.lock().len(), 1);
+    }
+}
@@ -1,20 +1,31 @@
 //! Activity definitions and execution context
 
 use std::future::Future;
+use std::sync::Arc;
 use std::time::Duration;
+use parking_lot::Mutex;
 use serde::{Serialize, de::DeserializeOwned};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use super::outbox::{OutboxDestination, StagedMessages};
+use super::task_queue::Priority;
 use super::{ActivityId, WorkflowExecution, ActivityError};
 
 /// Activity trait - defines the activity interface
 pub trait Activity: Send + Sync + 'static {
     /// Input type
-    type Input: DeserializeOwned + Send + 'static;
+    type Input: Serialize + DeserializeOwned + Send + 'static;
     
     /// Output type
-    type Output: Serialize + Send + 'static;
+    type Output: Serialize + DeserializeOwned + Send + 'static;
     
     /// Activity name
     fn name() -> &'static str;
+
+    /// [Resource pools](super::resource_pool) an attempt takes a permit of before it starts
+    fn resources() -> &'static [&'static str] {
+        &[]
+    }
     
     /// Execute the activity
     fn execute(
@@ -23,12 +34,48 @@ pub trait Activity: Send + Sync + 'static {
     ) -> impl Future<Output = Result<Self::Output, ActivityError>> + Send;
 }
 
+/// Time and details of the latest heartbeat of a running activity attempt
+pub(crate) struct HeartbeatTracker {
+    last: Mutex<(Instant, Option<serde_json::Value>)>,
+}
+
+impl HeartbeatTracker {
+    pub(crate) fn new() -> Self {
+        Self {
+            last: Mutex::new((Instant::now(), None)),
+        }
+    }
+
+    fn beat(&self, details: Option<serde_json::Value>) {
+        let mut last = self.last.lock();
+        last.0 = Instant::now();
+        if details.is_some() {
+            last.1 = details;
+        }
+    }
+
+    /// Resolve once no heartbeat has arrived for `timeout`, returning the last reported details
+    pub(crate) async fn expired(&self, timeout: Duration) -> Option<serde_json::Value> {
+        loop {
+            let deadline = self.last.lock().0 + timeout;
+            tokio::time::sleep_until(deadline).await;
+            let last = self.last.lock();
+            if last.0.elapsed() >= timeout {
+                return last.1.clone();
+            }
+        }
+    }
+}
+
 /// Activity context - provides activity execution environment
 #[derive(Clone)]
 pub struct ActivityContext {
     activity_id: ActivityId,
     workflow_execution: WorkflowExecution,
-    // Additional fields will be added as implementation progresses
+    attempt: u32,
+    cancellation: CancellationToken,
+    heartbeat: Arc<HeartbeatTracker>,
+    outbox: Arc<StagedMessages>,
 }
 
 impl ActivityContext {
@@ -37,8 +84,34 @@ impl ActivityContext {
         Self {
             activity_id,
             workflow_execution,
+            attempt: 1,
+            cancellation: CancellationToken::new(),
+            heartbeat: Arc::new(HeartbeatTracker::new()),
+            outbox: Arc::new(StagedMessages::default()),
         }
     }
+
+    pub(crate) fn attached(mut self, cancellation: CancellationToken, heartbeat: Arc<HeartbeatTracker>) -> Self {
+        self.cancellation = cancellation;
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    pub(crate) fn with_outbox(mut self, outbox: Arc<StagedMessages>) -> Self {
+        self.outbox = outbox;
+        self
+    }
+
+    /// Set the attempt number (starting at 1)
+    pub fn with_attempt(mut self, attempt: u32) -> Self {
+        self.attempt = attempt;
+        self
+    }
+
+    /// Get the attempt number (starting at 1)
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
     
     /// Get activity ID
     pub fn activity_id(&self) -> &ActivityId {
@@ -51,24 +124,54 @@ impl ActivityContext {
     }
     
     /// Record heartbeat
+    ///
+    /// Keeps the attempt alive under `heartbeat_timeout`. Fails with [`ActivityError::Cancelled`]
+    /// once the workflow stopped waiting for the activity, so `?` aborts the activity.
     pub async fn heartbeat(&self) -> Result<(), ActivityError> {
-        // Placeholder implementation
-        Ok(())
+        self.heartbeat.beat(None);
+        self.check_cancelled()
     }
     
     /// Record heartbeat with details
+    ///
+    /// The latest details are reported if the attempt later misses its heartbeat timeout.
     pub async fn heartbeat_with_details<T: Serialize>(
         &self,
-        _details: T,
+        details: T,
     ) -> Result<(), ActivityError> {
-        // Placeholder implementation
-        Ok(())
+        let details = serde_json::to_value(details).map_err(|e| ActivityError::HeartbeatFailed(e.to_string()))?;
+        self.heartbeat.beat(Some(details));
+        self.check_cancelled()
     }
     
     /// Check if cancelled
+    ///
+    /// An attempt is cancelled when the workflow gives up waiting for it (start-to-close
+    /// timeout, workflow closed or the waiting future dropped) or it missed its heartbeat timeout.
     pub fn is_cancelled(&self) -> bool {
-        // Placeholder implementation
-        false
+        self.cancellation.is_cancelled()
+    }
+
+    /// Wait until the attempt is cancelled
+    pub async fn cancelled(&self) {
+        self.cancellation.cancelled().await
+    }
+
+    /// Stage a message for the [outbox](super::outbox), returning its ID
+    ///
+    /// The message is saved with the attempt's completion if the attempt succeeds and dropped if it
+    /// fails, and published later by an outbox relay.
+    pub fn stage_message(&self, destination: OutboxDestination, payload: serde_json::Value) -> String {
+        self.outbox
+            .stage(&self.workflow_execution, self.activity_id.as_str(), destination, payload)
+    }
+
+    fn check_cancelled(&self) -> Result<(), ActivityError> {
+        if self.is_cancelled() {
+            Err(ActivityError::Cancelled)
+        } else {
+            Ok(())
+        }
     }
 }
 
@@ -90,11 +193,18 @@ pub struct ActivityOptions {
     /// Schedule to close timeout
     pub schedule_to_close_timeout: Option<Duration>,
     
-    /// Heartbeat timeout
+    /// Heartbeat timeout; an attempt that does not heartbeat within it fails and is cancelled
     pub heartbeat_timeout: Option<Duration>,
     
     /// Retry policy
     pub retry_policy: Option<RetryPolicy>,
+
+    /// Reuse a result of the same activity type and input recorded within this long instead of
+    /// running the activity again; for expensive, pure activities, see [`memo`](super::memo)
+    pub memoize: Option<Duration>,
+
+    /// Dequeue priority of the attempts; the workflow's priority if unset
+    pub priority: Option<Priority>,
 }
 
 impl Default for ActivityOptions {
@@ -105,8 +215,10 @@ impl Default for ActivityOptions {
             schedule_to_start_timeout: Some(Duration::from_secs(60)),
             start_to_close_timeout: Some(Duration::from_secs(300)),
             schedule_to_close_timeout: None,
-            heartbeat_timeout: Some(Duration::from_secs(30)),
+            heartbeat_timeout: None,
             retry_policy: Some(RetryPolicy::default()),
+            memoize: None,
+            priority: None,
         }
     }
 }
@@ -142,6 +254,29 @@ impl Default for RetryPolicy {
     }
 }
 
+impl RetryPolicy {
+    /// Policy that runs an activity exactly once
+    pub fn no_retry() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Whether another attempt should follow a failed `attempt`
+    pub fn should_retry(&self, attempt: u32, error: &ActivityError) -> bool {
+        attempt < self.max_attempts
+            && error.is_retryable()
+            && !self.non_retryable_error_types.iter().any(|t| t == error.error_type())
+    }
+
+    /// Delay before the attempt following `attempt`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.backoff_coefficient.max(1.0).powi(attempt.saturating_sub(1) as i32);
+        self.initial_interval.mul_f64(factor).min(self.max_interval)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -159,11 +294,40 @@ mod tests {
         assert_eq!(ctx.activity_id(), &activity_id);
     }
 
+    #[tokio::test]
+    async fn test_heartbeat_reports_cancellation() {
+        let execution = WorkflowExecution::new(WorkflowId::new("test-workflow"));
+        let cancellation = CancellationToken::new();
+        let tracker = Arc::new(HeartbeatTracker::new());
+        let ctx = ActivityContext::new(ActivityId::new("a"), execution).attached(cancellation.clone(), tracker.clone());
+
+        ctx.heartbeat_with_details(7).await.unwrap();
+        assert!(!ctx.is_cancelled());
+        cancellation.cancel();
+        assert!(ctx.is_cancelled());
+        assert!(matches!(ctx.heartbeat().await, Err(ActivityError::Cancelled)));
+        assert_eq!(tracker.expired(Duration::from_millis(5)).await, Some(serde_json::json!(7)));
+    }
+
     #[test]
     fn test_retry_policy_default() {
         let policy = RetryPolicy::default();
         assert_eq!(policy.max_attempts, 3);
         assert_eq!(policy.backoff_coefficient, 2.0);
     }
+
+    #[test]
+    fn test_retry_policy_decisions() {
+        let policy = RetryPolicy {
+            non_retryable_error_types: vec!["ExecutionFailed".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert!(policy.should_retry(1, &ActivityError::TemporaryFailure("x".into())));
+        assert!(!policy.should_retry(3, &ActivityError::TemporaryFailure("x".into())));
+        assert!(!policy.should_retry(1, &ActivityError::ValidationFailed("x".into())));
+        assert!(!policy.should_retry(1, &ActivityError::ExecutionFailed("x".into())));
+    }
 }
 
@@ -0,0 +1,340 @@
+//! Batched execution of small activities
+//!
+//! Some activities are cheap per item but costly per call: checking the stock of one SKU is one
+//! round trip to the inventory service, checking fifty is the same round trip. A [`BatchActivity`]
+//! takes a `Vec` of inputs and returns one output per input, in order. Workflows call it through
+//! [`Batched`] like any other activity, one input at a time; a worker that registered it with
+//! [`register_batch_activity`](super::WorkflowWorker::register_batch_activity) holds the attempts
+//! arriving for it and runs them together once [`BatchingOptions::max_batch_size`] are waiting or
+//! the first of them has waited [`BatchingOptions::max_delay`].
+//!
+//! Each call stays its own activity in the history, with its own timeouts and retries. A batch
+//! that fails fails every call in it; an input that does not decode fails only its own call.
+//! Attempts keep their activity slot while they wait, so a batch never grows beyond
+//! [`WorkerConfig::max_concurrent_activity_tasks`](super::WorkerConfig::max_concurrent_activity_tasks).
+//!
+//! ```rust,ignore
+//! let sku_in_stock = ctx.execute_activity::<Batched<CheckStock>>(sku, ActivityOptions::default()).await?;
+//! ```
+
+use std::future::Future;
+use std::marker::PhantomData;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::oneshot;
+
+use super::metrics::ACTIVITY_BATCH_SIZE;
+use super::{Activity, ActivityContext, ActivityError};
+
+type ItemResult = Result<serde_json::Value, ActivityError>;
+type BatchFn = Arc<dyn Fn(Vec<serde_json::Value>) -> BoxFuture<'static, Vec<ItemResult>> + Send + Sync>;
+
+/// Activity run on many inputs at once
+pub trait BatchActivity: Send + Sync + 'static {
+    /// Input of one call
+    type Input: Serialize + DeserializeOwned + Send + 'static;
+
+    /// Output of one call
+    type Output: Serialize + DeserializeOwned + Send + 'static;
+
+    /// Activity name
+    fn name() -> &'static str;
+
+    /// How the worker groups calls into batches
+    fn batching() -> BatchingOptions {
+        BatchingOptions::default()
+    }
+
+    /// Run a batch, returning the output of each input in the same order
+    fn execute_batch(
+        inputs: Vec<Self::Input>,
+    ) -> impl Future<Output = Result<Vec<Self::Output>, ActivityError>> + Send;
+}
+
+/// Limits of the batches a worker forms
+#[derive(Debug, Clone, Copy)]
+pub struct BatchingOptions {
+    /// Run the batch as soon as this many calls wait
+    pub max_batch_size: usize,
+    /// Run the batch once its first call has waited this long
+    pub max_delay: Duration,
+}
+
+impl Default for BatchingOptions {
+    fn default() -> Self {
+        Self {
+            max_batch_size: 100,
+            max_delay: Duration::from_millis(10),
+        }
+    }
+}
+
+/// A [`BatchActivity`] called one input at a time
+///
+/// Run as a batch of one where the activity was registered with
+/// [`register_activity`](super::WorkflowWorker::register_activity) instead of
+/// [`register_batch_activity`](super::WorkflowWorker::register_batch_activity).
+pub struct Batched<A>(PhantomData<fn() -> A>);
+
+impl<A: BatchActivity> Activity for Batched<A> {
+    type Input = A::Input;
+    type Output = A::Output;
+
+    fn name() -> &'static str {
+        A::name()
+    }
+
+    async fn execute(_ctx: ActivityContext, input: A::Input) -> Result<A::Output, ActivityError> {
+        let outputs = A::execute_batch(vec![input]).await?;
+        let count = outputs.len();
+        let mut outputs = outputs.into_iter();
+        match (outputs.next(), count) {
+            (Some(output), 1) => Ok(output),
+            _ => Err(mismatch(1, count)),
+        }
+    }
+}
+
+fn mismatch(inputs: usize, outputs: usize) -> ActivityError {
+    ActivityError::ExecutionFailed(format!("batch of {} inputs returned {} outputs", inputs, outputs))
+}
+
+/// Run one batch of encoded inputs, decoding and encoding each call on its own
+async fn run_batch<A: BatchActivity>(inputs: Vec<serde_json::Value>) -> Vec<ItemResult> {
+    let mut results: Vec<Option<ItemResult>> = Vec::with_capacity(inputs.len());
+    let mut decoded = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        match serde_json::from_value::<A::Input>(input) {
+            Ok(input) => {
+                decoded.push(input);
+                results.push(None);
+            }
+            Err(e) => results.push(Some(Err(ActivityError::InvalidInput(e.to_string())))),
+        }
+    }
+    let count = decoded.len();
+    let outputs: Vec<ItemResult> = match count {
+        0 => Vec::new(),
+        _ => match A::execute_batch(decoded).await {
+            Ok(outputs) if outputs.len() == count => outputs
+                .into_iter()
+                .map(|output| serde_json::to_value(output).map_err(|e| ActivityError::ExecutionFailed(e.to_string())))
+                .collect(),
+            Ok(outputs) => vec![Err(mismatch(count, outputs.len())); count],
+            Err(e) => vec![Err(e); count],
+        },
+    };
+    let mut outputs = outputs.into_iter();
+    results
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|| outputs.next().expect("one output per decoded input")))
+        .collect()
+}
+
+/// Calls waiting for the next batch
+#[derive(Default)]
+struct Open {
+    /// Bumped whenever a batch is taken, so a stale timer does not flush the next one
+    generation: u64,
+    calls: Vec<(serde_json::Value, oneshot::Sender<ItemResult>)>,
+}
+
+impl Open {
+    fn take(&mut self) -> Vec<(serde_json::Value, oneshot::Sender<ItemResult>)> {
+        self.generation += 1;
+        std::mem::take(&mut self.calls)
+    }
+}
+
+/// Groups the calls of one batch activity type arriving on a worker into batches
+pub(crate) struct BatchDispatcher {
+    activity_type: &'static str,
+    options: BatchingOptions,
+    run: BatchFn,
+    open: Mutex<Open>,
+}
+
+impl BatchDispatcher {
+    pub(crate) fn new<A: BatchActivity>() -> Self {
+        Self {
+            activity_type: A::name(),
+            options: A::batching(),
+            run: Arc::new(|inputs| run_batch::<A>(inputs).boxed()),
+            open: Mutex::new(Open::default()),
+        }
+    }
+
+    /// Add a call to the open batch and wait for its result
+    pub(crate) async fn submit(self: Arc<Self>, input: serde_json::Value) -> ItemResult {
+        let (sender, receiver) = oneshot::channel();
+        let full = {
+            let mut open = self.open.lock();
+            open.calls.push((input, sender));
+            if open.calls.len() >= self.options.max_batch_size.max(1) {
+                Some(open.take())
+            } else {
+                if open.calls.len() == 1 {
+                    let generation = open.generation;
+                    let dispatcher = self.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(dispatcher.options.max_delay).await;
+                        let due = {
+                            let mut open = dispatcher.open.lock();
+                            (open.generation == generation).then(|| open.take())
+                        };
+                        if let Some(calls) = due {
+                            dispatcher.flush(calls).await;
+                        }
+                    });
+                }
+                None
+            }
+        };
+        // Flushed on its own task, so a caller giving up does not drop the other calls of the batch
+        if let Some(calls) = full {
+            let dispatcher = self.clone();
+            tokio::spawn(async move { dispatcher.flush(calls).await });
+        }
+        receiver
+            .await
+            .unwrap_or_else(|_| Err(ActivityError::ExecutionFailed("batch was dropped".to_string())))
+    }
+
+    async fn flush(&self, calls: Vec<(serde_json::Value, oneshot::Sender<ItemResult>)>) {
+        let (inputs, senders): (Vec<_>, Vec<_>) = calls.into_iter().unzip();
+        let count = inputs.len();
+        metrics::histogram!(ACTIVITY_BATCH_SIZE, "activity_type" => self.activity_type).record(count as f64);
+        let results = match AssertUnwindSafe((self.run)(inputs)).catch_unwind().await {
+            Ok(results) => results,
+            Err(_) => vec![Err(ActivityError::ExecutionFailed("activity batch panicked".to_string())); count],
+        };
+        for (sender, result) in senders.into_iter().zip(results) {
+            // The caller may have timed out or been cancelled meanwhile
+            let _ = sender.send(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::future::join_all;
+
+    use crate::temporal::{ActivityOptions, StartWorkflowOptions, WorkerConfig, Workflow, WorkflowContext, WorkflowError, WorkflowWorker};
+
+    static BATCHES: Mutex<Vec<usize>> = parking_lot::const_mutex(Vec::new());
+
+    /// Stock of each SKU, out of stock when it starts with `x`
+    struct CheckStock;
+
+    impl BatchActivity for CheckStock {
+        type Input = String;
+        type Output = bool;
+
+        fn name() -> &'static str {
+            "check_stock"
+        }
+
+        fn batching() -> BatchingOptions {
+            BatchingOptions {
+                max_batch_size: 5,
+                max_delay: Duration::from_secs(5),
+            }
+        }
+
+        async fn execute_batch(skus: Vec<String>) -> Result<Vec<bool>, ActivityError> {
+            BATCHES.lock().push(skus.len());
+            Ok(skus.iter().map(|sku| !sku.starts_with('x')).collect())
+        }
+    }
+
+    struct Restock;
+
+    impl Workflow for Restock {
+        type Input = Vec<String>;
+        type Output = Vec<String>;
+
+        fn name() -> &'static str {
+            "restock"
+        }
+
+        async fn execute(ctx: WorkflowContext, skus: Vec<String>) -> Result<Vec<String>, WorkflowError> {
+            let checks = skus
+                .iter()
+                .map(|sku| ctx.execute_activity::<Batched<CheckStock>>(sku.clone(), ActivityOptions::default()));
+            let mut missing = Vec::new();
+            for (sku, in_stock) in skus.iter().zip(join_all(checks).await) {
+                if !in_stock? {
+                    missing.push(sku.clone());
+                }
+            }
+            Ok(missing)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_calls_run_as_one_batch() {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        }));
+        worker.register_workflow::<Restock>();
+        worker.register_batch_activity::<CheckStock>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let skus: Vec<String> = ["a-1", "x-2", "a-3", "a-4", "x-5"].iter().map(|sku| sku.to_string()).collect();
+        let handle = worker.client().start_workflow::<Restock>(skus, StartWorkflowOptions::default()).await.unwrap();
+        assert_eq!(handle.result().await.unwrap(), vec!["x-2".to_string(), "x-5".to_string()]);
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+        assert_eq!(*BATCHES.lock(), vec![5]);
+    }
+
+    struct Double;
+
+    static DOUBLED: AtomicUsize = AtomicUsize::new(0);
+
+    impl BatchActivity for Double {
+        type Input = u32;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "double"
+        }
+
+        async fn execute_batch(inputs: Vec<u32>) -> Result<Vec<u32>, ActivityError> {
+            DOUBLED.fetch_add(1, Ordering::SeqCst);
+            match inputs.contains(&0) {
+                true => Ok(Vec::new()),
+                false => Ok(inputs.iter().map(|input| input * 2).collect()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delayed_batches_fail_per_call() {
+        let dispatcher = Arc::new(BatchDispatcher::new::<Double>());
+        let calls = [serde_json::json!(1), serde_json::json!("two"), serde_json::json!(3)]
+            .map(|input| dispatcher.clone().submit(input));
+        let results = join_all(calls).await;
+        assert_eq!(results[0].as_ref().unwrap(), &serde_json::json!(2));
+        assert!(matches!(results[1], Err(ActivityError::InvalidInput(_))));
+        assert_eq!(results[2].as_ref().unwrap(), &serde_json::json!(6));
+        assert_eq!(DOUBLED.load(Ordering::SeqCst), 1);
+
+        // A batch returning the wrong number of outputs fails every call in it
+        let results = join_all([0, 1].map(|input| dispatcher.clone().submit(serde_json::json!(input)))).await;
+        for result in results {
+            assert!(result.unwrap_err().to_string().contains("batch of 2 inputs returned 0 outputs"));
+        }
+    }
+}
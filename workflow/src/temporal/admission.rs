@@ -0,0 +1,436 @@
+//! Admission control of workflow starts and activity attempts
+//!
+//! An [`AdmissionControl`] caps the runs executing concurrently in a namespace, overall and per
+//! workflow type, and likewise the activity attempts those runs have in flight. Starts beyond the
+//! limit fail with [`WorkflowError::QuotaExceeded`], or wait for a slot up to a timeout when the
+//! limits queue instead of rejecting. The HTTP API answers rejected starts with 429.
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use std::time::Duration;
+//! # use workflow::temporal::admission::{AdmissionControl, AdmissionLimits};
+//! # use workflow::temporal::namespace::Namespace;
+//! # use workflow::temporal::storage::InMemoryStorage;
+//! # use workflow::temporal::{WorkerConfig, WorkflowWorker};
+//! let storage = Arc::new(InMemoryStorage::new());
+//! let limits = AdmissionLimits::new()
+//!     .max_workflows(500)
+//!     .max_workflows_of_type("video_transcode", 20)
+//!     .max_activities_of_type("video_transcode", 40)
+//!     .queue_for(Duration::from_secs(30));
+//! let admission = Arc::new(AdmissionControl::new(Namespace::default(), storage.clone(), limits));
+//! let worker = WorkflowWorker::new(WorkerConfig::default())
+//!     .with_storage(storage)
+//!     .with_admission(admission);
+//! ```
+//!
+//! Running workflows are counted in storage before each start, so concurrent starts from several
+//! clients may briefly exceed a workflow limit. Activity slots are counted by the worker running
+//! the workflows, per [`AdmissionControl`]; share one control between the workers of a namespace
+//! to cap their activities together.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use super::interceptor::{ClientInterceptor, StartWorkflowRequest};
+use super::namespace::Namespace;
+use super::search::{WorkflowExecutionStatus, WorkflowFilter};
+use super::storage::WorkflowStorage;
+use super::WorkflowError;
+
+/// Time between recounts of running workflows while a start waits for a slot
+pub const DEFAULT_RECOUNT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What happens to starts and activity attempts beyond a limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Fail with [`WorkflowError::QuotaExceeded`]
+    #[default]
+    Reject,
+    /// Wait up to `timeout` for a slot, then fail with [`WorkflowError::QuotaExceeded`]
+    Queue { timeout: Duration },
+}
+
+/// Concurrency limits of a namespace; unset limits do not apply
+#[derive(Debug, Clone, Default)]
+pub struct AdmissionLimits {
+    pub max_workflows: Option<usize>,
+    pub max_workflows_by_type: HashMap<String, usize>,
+    /// Activity attempts in flight for all runs
+    pub max_activities: Option<usize>,
+    /// Activity attempts in flight for the runs of a workflow type
+    pub max_activities_by_type: HashMap<String, usize>,
+    pub overflow: Overflow,
+    pub recount_interval: Duration,
+}
+
+impl AdmissionLimits {
+    /// No limits, rejecting once limits are added
+    pub fn new() -> Self {
+        Self {
+            recount_interval: DEFAULT_RECOUNT_INTERVAL,
+            ..Self::default()
+        }
+    }
+
+    pub fn max_workflows(mut self, max: usize) -> Self {
+        self.max_workflows = Some(max);
+        self
+    }
+
+    pub fn max_workflows_of_type(mut self, workflow_type: impl Into<String>, max: usize) -> Self {
+        self.max_workflows_by_type.insert(workflow_type.into(), max);
+        self
+    }
+
+    pub fn max_activities(mut self, max: usize) -> Self {
+        self.max_activities = Some(max);
+        self
+    }
+
+    pub fn max_activities_of_type(mut self, workflow_type: impl Into<String>, max: usize) -> Self {
+        self.max_activities_by_type.insert(workflow_type.into(), max);
+        self
+    }
+
+    /// Wait up to `timeout` for a slot instead of rejecting right away
+    pub fn queue_for(mut self, timeout: Duration) -> Self {
+        self.overflow = Overflow::Queue { timeout };
+        self
+    }
+
+    /// Time between recounts of running workflows while a start waits, [`DEFAULT_RECOUNT_INTERVAL`] by default
+    pub fn recount_every(mut self, interval: Duration) -> Self {
+        self.recount_interval = interval;
+        self
+    }
+}
+
+/// Activity attempts in flight
+#[derive(Debug, Default)]
+struct InFlight {
+    total: usize,
+    by_type: HashMap<String, usize>,
+}
+
+/// Enforces [`AdmissionLimits`] on the starts of a namespace and the activities of its runs
+///
+/// As a [`ClientInterceptor`] it admits starts; given to a worker with
+/// [`WorkflowWorker::with_admission`](super::WorkflowWorker::with_admission) it also admits the
+/// activity attempts of the runs that worker executes.
+pub struct AdmissionControl {
+    namespace: Namespace,
+    storage: Arc<dyn WorkflowStorage>,
+    limits: AdmissionLimits,
+    in_flight: Mutex<InFlight>,
+    released: Notify,
+}
+
+/// Slot of an admitted activity attempt, released when dropped
+pub(crate) struct ActivitySlot<'a> {
+    control: &'a AdmissionControl,
+    workflow_type: String,
+}
+
+impl AdmissionControl {
+    /// `storage` holds the executions of `namespace` only, as a
+    /// [`NamespacedStorage`](super::namespace::NamespacedStorage) does
+    pub fn new(namespace: Namespace, storage: Arc<dyn WorkflowStorage>, limits: AdmissionLimits) -> Self {
+        Self {
+            namespace,
+            storage,
+            limits,
+            in_flight: Mutex::new(InFlight::default()),
+            released: Notify::new(),
+        }
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn limits(&self) -> &AdmissionLimits {
+        &self.limits
+    }
+
+    /// Activity attempts in flight for the runs of `workflow_type`
+    pub fn activities_in_flight(&self, workflow_type: &str) -> usize {
+        self.in_flight.lock().by_type.get(workflow_type).copied().unwrap_or(0)
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        match self.limits.overflow {
+            Overflow::Reject => None,
+            Overflow::Queue { timeout } => Some(Instant::now() + timeout),
+        }
+    }
+
+    fn reject(&self, kind: &'static str, workflow_type: &str, reason: String) -> WorkflowError {
+        metrics::counter!(
+            super::metrics::QUOTA_REJECTIONS,
+            "namespace" => self.namespace.to_string(),
+            "workflow_type" => workflow_type.to_string(),
+            "kind" => kind
+        )
+        .increment(1);
+        WorkflowError::QuotaExceeded(format!("namespace {}: {}", self.namespace, reason))
+    }
+
+    /// The limit a new run of `workflow_type` would exceed, if any
+    async fn workflow_limit_reached(&self, workflow_type: &str) -> Result<Option<String>, WorkflowError> {
+        let by_type = self.limits.max_workflows_by_type.get(workflow_type);
+        if self.limits.max_workflows.is_none() && by_type.is_none() {
+            return Ok(None);
+        }
+        let running = self
+            .storage
+            .list_workflow_executions(&WorkflowFilter::new().status(WorkflowExecutionStatus::Running))
+            .await?;
+        if let Some(max) = self.limits.max_workflows
+            && running.len() >= max
+        {
+            return Ok(Some(format!("at most {} concurrent runs", max)));
+        }
+        if let Some(&max) = by_type
+            && running.iter().filter(|info| info.workflow_type == workflow_type).count() >= max
+        {
+            return Ok(Some(format!("at most {} concurrent runs of {}", max, workflow_type)));
+        }
+        Ok(None)
+    }
+
+    /// Admit the start of a run of `workflow_type`, waiting for a slot if the limits queue
+    pub async fn admit_workflow(&self, workflow_type: &str) -> Result<(), WorkflowError> {
+        let deadline = self.deadline();
+        loop {
+            let Some(reason) = self.workflow_limit_reached(workflow_type).await? else {
+                return Ok(());
+            };
+            match deadline {
+                Some(deadline) if Instant::now() < deadline => {
+                    tokio::time::sleep_until(deadline.min(Instant::now() + self.limits.recount_interval)).await;
+                }
+                _ => return Err(self.reject("workflow", workflow_type, reason)),
+            }
+        }
+    }
+
+    fn try_acquire_activity(&self, workflow_type: &str) -> Result<(), String> {
+        let mut in_flight = self.in_flight.lock();
+        if let Some(max) = self.limits.max_activities
+            && in_flight.total >= max
+        {
+            return Err(format!("at most {} concurrent activities", max));
+        }
+        let of_type = in_flight.by_type.get(workflow_type).copied().unwrap_or(0);
+        if let Some(&max) = self.limits.max_activities_by_type.get(workflow_type)
+            && of_type >= max
+        {
+            return Err(format!("at most {} concurrent activities for {}", max, workflow_type));
+        }
+        in_flight.total += 1;
+        *in_flight.by_type.entry(workflow_type.to_string()).or_default() += 1;
+        Ok(())
+    }
+
+    /// Admit an activity attempt of a run of `workflow_type`, waiting for a slot if the limits queue
+    pub(crate) async fn admit_activity(&self, workflow_type: &str) -> Result<ActivitySlot<'_>, WorkflowError> {
+        let deadline = self.deadline();
+        loop {
+            // Register for wake-ups before checking, so a release in between is not missed
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            let reason = match self.try_acquire_activity(workflow_type) {
+                Ok(()) => {
+                    return Ok(ActivitySlot {
+                        control: self,
+                        workflow_type: workflow_type.to_string(),
+                    });
+                }
+                Err(reason) => reason,
+            };
+            match deadline {
+                Some(deadline) if Instant::now() < deadline => {
+                    let _ = tokio::time::timeout_at(deadline, released).await;
+                }
+                _ => return Err(self.reject("activity", workflow_type, reason)),
+            }
+        }
+    }
+}
+
+impl Drop for ActivitySlot<'_> {
+    fn drop(&mut self) {
+        {
+            let mut in_flight = self.control.in_flight.lock();
+            in_flight.total -= 1;
+            if let Some(count) = in_flight.by_type.get_mut(&self.workflow_type) {
+                *count -= 1;
+                if *count == 0 {
+                    in_flight.by_type.remove(&self.workflow_type);
+                }
+            }
+        }
+        self.control.released.notify_waiters();
+    }
+}
+
+#[async_trait]
+impl ClientInterceptor for AdmissionControl {
+    async fn before_start(&self, request: &mut StartWorkflowRequest) -> Result<(), WorkflowError> {
+        self.admit_workflow(&request.workflow_type).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::storage::InMemoryStorage;
+    use crate::temporal::{
+        ActivityContext, ActivityError, ActivityOptions, StartWorkflowOptions, WorkerConfig, Workflow, WorkflowContext,
+        WorkflowId, WorkflowWorker,
+    };
+
+    /// Sleeps long enough to stay running for the test
+    struct Hold;
+
+    impl Workflow for Hold {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "hold"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<(), WorkflowError> {
+            ctx.sleep(Duration::from_secs(60)).await;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_starts_beyond_limits_are_rejected_or_queued() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let limits = AdmissionLimits::new().max_workflows(3).max_workflows_of_type("hold", 1);
+        let admission = Arc::new(AdmissionControl::new(Namespace::default(), storage.clone(), limits));
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                ..WorkerConfig::default()
+            })
+            .with_storage(storage)
+            .with_admission(admission.clone()),
+        );
+        worker.register_workflow::<Hold>();
+        let client = worker.client();
+        let start = |id: &str| StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new(id)),
+            ..StartWorkflowOptions::default()
+        };
+
+        client.start_workflow::<Hold>((), start("first")).await.unwrap();
+        let rejected = client.start_workflow::<Hold>((), start("second")).await;
+        assert!(matches!(rejected, Err(WorkflowError::QuotaExceeded(_))));
+
+        // A queued start is admitted once the running run is gone
+        let queued = Arc::new(AdmissionControl::new(
+            Namespace::default(),
+            admission.storage.clone(),
+            AdmissionLimits::new()
+                .max_workflows_of_type("hold", 1)
+                .queue_for(Duration::from_secs(5))
+                .recount_every(Duration::from_millis(10)),
+        ));
+        let waiting = tokio::spawn({
+            let queued = queued.clone();
+            async move { queued.admit_workflow("hold").await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+        admission.storage.delete_workflow_execution(&WorkflowId::new("first")).await.unwrap();
+        waiting.await.unwrap().unwrap();
+    }
+
+    /// Runs two activities at once
+    struct FanOut;
+
+    impl Workflow for FanOut {
+        type Input = ();
+        type Output = Vec<String>;
+
+        fn name() -> &'static str {
+            "fan_out"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<Vec<String>, WorkflowError> {
+            let (a, b) = futures::join!(
+                ctx.execute_activity::<Nap>("a".to_string(), ActivityOptions::default()),
+                ctx.execute_activity::<Nap>("b".to_string(), ActivityOptions::default()),
+            );
+            Ok(vec![a?, b?])
+        }
+    }
+
+    struct Nap;
+
+    impl crate::temporal::Activity for Nap {
+        type Input = String;
+        type Output = String;
+
+        fn name() -> &'static str {
+            "nap"
+        }
+
+        async fn execute(_ctx: ActivityContext, input: String) -> Result<String, ActivityError> {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(input)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_activities_beyond_limits_wait_or_fail_the_run() {
+        for (limits, admitted) in [
+            (AdmissionLimits::new().max_activities_of_type("fan_out", 1).queue_for(Duration::from_secs(5)), true),
+            (AdmissionLimits::new().max_activities(1), false),
+        ] {
+            let storage = Arc::new(InMemoryStorage::new());
+            let admission = Arc::new(AdmissionControl::new(Namespace::default(), storage.clone(), limits));
+            let worker = Arc::new(
+                WorkflowWorker::new(WorkerConfig {
+                    poll_timeout: Duration::from_millis(50),
+                    ..WorkerConfig::default()
+                })
+                .with_storage(storage)
+                .with_admission(admission.clone()),
+            );
+            worker.register_workflow::<FanOut>();
+            worker.register_activity::<Nap>();
+            let running = worker.clone();
+            let run = tokio::spawn(async move { running.run().await });
+
+            let result = worker
+                .client()
+                .start_workflow::<FanOut>((), StartWorkflowOptions::default())
+                .await
+                .unwrap()
+                .result()
+                .await;
+            if admitted {
+                assert_eq!(result.unwrap(), ["a", "b"]);
+            } else {
+                assert!(result.unwrap_err().to_string().contains("Quota exceeded"));
+            }
+            assert_eq!(admission.activities_in_flight("fan_out"), 0);
+
+            worker.shutdown(Duration::ZERO);
+            run.await.unwrap().unwrap();
+        }
+    }
+}
@@ -0,0 +1,568 @@
+//! Archival of closed workflow histories to object storage
+//!
+//! [`ArchivingStorage`] wraps the primary [`WorkflowStorage`] and moves the histories of runs
+//! closed for longer than a retention period to an [`ObjectStore`]: a local directory
+//! ([`LocalObjectStore`]) or an S3-compatible bucket ([`S3ObjectStore`]). Loading a workflow falls
+//! back to the archive, so clients, handles and history export read archived runs transparently;
+//! listings cover the primary store only.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use std::time::Duration;
+//! # use workflow::temporal::archival::{ArchivingStorage, LocalObjectStore};
+//! # use workflow::temporal::storage::InMemoryStorage;
+//! # use workflow::temporal::{WorkerConfig, WorkflowWorker};
+//! # async fn run() {
+//! let storage = Arc::new(ArchivingStorage::new(
+//!     Arc::new(InMemoryStorage::new()),
+//!     Arc::new(LocalObjectStore::new("/var/lib/workflow/archive")),
+//!     Duration::from_secs(30 * 24 * 3600),
+//! ));
+//! let archiver = storage.clone();
+//! tokio::spawn(async move { archiver.run_archival(Duration::from_secs(3600)).await });
+//! let worker = WorkflowWorker::new(WorkerConfig::default()).with_storage(storage);
+//! # }
+//! ```
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{Method, StatusCode, Url};
+use sha2::{Digest, Sha256};
+
+use super::error::StorageError;
+use super::event::EventHistory;
+use super::history_export::{HistoryExport, HistoryFormat};
+use super::outbox::OutboxMessage;
+use super::search::{WorkflowExecutionInfo, WorkflowFilter};
+use super::storage::{HistoryPage, WorkflowStorage};
+use super::{EventId, WorkflowExecution, WorkflowId};
+
+/// Default prefix of the keys of archived histories
+pub const DEFAULT_ARCHIVE_PREFIX: &str = "histories/";
+
+/// Flat key-value store for archived histories
+///
+/// Keys are `/`-separated and consist of ASCII letters, digits, `-_.~%` and `/`.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), StorageError>;
+
+    /// `None` if there is no object under `key`
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    /// Deleting a missing object succeeds
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+/// Objects as files below a root directory, key segments as path components
+pub struct LocalObjectStore {
+    root: PathBuf,
+}
+
+impl LocalObjectStore {
+    /// The root directory is created on the first write
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        if key.split('/').any(|segment| matches!(segment, "" | "." | "..")) {
+            return Err(StorageError::Custom(format!("invalid object key: {}", key)));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+fn io_error(path: &std::path::Path, e: std::io::Error) -> StorageError {
+    StorageError::Custom(format!("{}: {}", path.display(), e))
+}
+
+#[async_trait]
+impl ObjectStore for LocalObjectStore {
+    /// Writes a sibling file and renames it, so readers never see a partial object
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|e| io_error(parent, e))?;
+        }
+        let partial = path.with_extension("partial");
+        tokio::fs::write(&partial, bytes).await.map_err(|e| io_error(&partial, e))?;
+        tokio::fs::rename(&partial, &path).await.map_err(|e| io_error(&path, e))
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        let path = self.path(key)?;
+        match tokio::fs::read(&path).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error(&path, e)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(io_error(&path, e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Objects in a bucket of an S3-compatible service, such as AWS S3, MinIO or Ceph
+///
+/// Uses path-style URLs (`{endpoint}/{bucket}/{key}`) and signs requests with AWS Signature Version 4.
+#[derive(Clone)]
+pub struct S3ObjectStore {
+    http: reqwest::Client,
+    endpoint: String,
+    bucket: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+impl S3ObjectStore {
+    /// Bucket at `endpoint`, such as `https://s3.eu-west-1.amazonaws.com` or `http://minio:9000`, in region `us-east-1`
+    pub fn new(
+        endpoint: impl Into<String>,
+        bucket: impl Into<String>,
+        access_key_id: impl Into<String>,
+        secret_access_key: impl Into<String>,
+    ) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            endpoint: endpoint.into().trim_end_matches('/').to_string(),
+            bucket: bucket.into(),
+            region: "us-east-1".to_string(),
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+        }
+    }
+
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = region.into();
+        self
+    }
+
+    fn url(&self, key: &str) -> Result<Url, StorageError> {
+        Url::parse(&format!("{}/{}/{}", self.endpoint, uri_encode(&self.bucket), uri_encode(key)))
+            .map_err(|e| StorageError::ConnectionError(format!("invalid S3 endpoint {}: {}", self.endpoint, e)))
+    }
+
+    /// `Authorization` header value of a request without query string
+    fn authorization(&self, method: &Method, url: &Url, payload_hash: &str, now: DateTime<Utc>) -> String {
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (host, None) => host.unwrap_or_default().to_string(),
+            (None, Some(_)) => String::new(),
+        };
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method,
+            url.path(),
+            host,
+            payload_hash,
+            amz_date,
+            signed_headers,
+            payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let mut key = hmac(format!("AWS4{}", self.secret_access_key).as_bytes(), &date);
+        for part in [self.region.as_str(), "s3", "aws4_request"] {
+            key = hmac(&key, part);
+        }
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key_id,
+            scope,
+            signed_headers,
+            hex(&hmac(&key, &string_to_sign))
+        )
+    }
+
+    async fn send(&self, method: Method, key: &str, body: Vec<u8>) -> Result<reqwest::Response, StorageError> {
+        let url = self.url(key)?;
+        let payload_hash = hex(&Sha256::digest(&body));
+        let now = Utc::now();
+        let authorization = self.authorization(&method, &url, &payload_hash, now);
+        self.http
+            .request(method, url)
+            .header("x-amz-date", now.format("%Y%m%dT%H%M%SZ").to_string())
+            .header("x-amz-content-sha256", payload_hash)
+            .header(reqwest::header::AUTHORIZATION, authorization)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| StorageError::ConnectionError(e.to_string()))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encode everything but unreserved characters and `/`, as SigV4 canonical URIs require
+fn uri_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+async fn failed(operation: &str, key: &str, response: reqwest::Response) -> StorageError {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    StorageError::QueryError(format!("S3 {} {} failed with {}: {}", operation, key, status, body))
+}
+
+#[async_trait]
+impl ObjectStore for S3ObjectStore {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), StorageError> {
+        let response = self.send(Method::PUT, key, bytes).await?;
+        if !response.status().is_success() {
+            return Err(failed("PUT", key, response).await);
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        let response = self.send(Method::GET, key, Vec::new()).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => {
+                let bytes = response.bytes().await.map_err(|e| StorageError::ConnectionError(e.to_string()))?;
+                Ok(Some(bytes.to_vec()))
+            }
+            _ => Err(failed("GET", key, response).await),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let response = self.send(Method::DELETE, key, Vec::new()).await?;
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            return Err(failed("DELETE", key, response).await);
+        }
+        Ok(())
+    }
+}
+
+/// Storage that archives closed runs older than a retention period and reads archived runs through
+///
+/// Archived runs are stored as JSON [`HistoryExport`]s under `{prefix}{workflow ID}.json`, with the
+/// workflow ID percent-encoded.
+pub struct ArchivingStorage {
+    primary: Arc<dyn WorkflowStorage>,
+    archive: Arc<dyn ObjectStore>,
+    retention: Duration,
+    prefix: String,
+}
+
+impl ArchivingStorage {
+    pub fn new(primary: Arc<dyn WorkflowStorage>, archive: Arc<dyn ObjectStore>, retention: Duration) -> Self {
+        Self {
+            primary,
+            archive,
+            retention,
+            prefix: DEFAULT_ARCHIVE_PREFIX.to_string(),
+        }
+    }
+
+    /// Store archived histories under `prefix` instead of [`DEFAULT_ARCHIVE_PREFIX`]
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn key(&self, workflow_id: &WorkflowId) -> String {
+        format!("{}{}.json", self.prefix, uri_encode(workflow_id.as_str()).replace('/', "%2F"))
+    }
+
+    /// Archive the runs closed longer than the retention period ago; returns their workflow IDs
+    pub async fn archive_expired(&self) -> Result<Vec<WorkflowId>, StorageError> {
+        let retention = chrono::Duration::from_std(self.retention).unwrap_or(chrono::Duration::MAX);
+        let cutoff = Utc::now().checked_sub_signed(retention).unwrap_or(DateTime::<Utc>::MIN_UTC);
+        self.archive_closed_before(cutoff).await
+    }
+
+    /// Archive the runs closed before `cutoff`; returns their workflow IDs
+    pub async fn archive_closed_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<WorkflowId>, StorageError> {
+        let expired = self
+            .primary
+            .list_workflow_executions(&WorkflowFilter::new())
+            .await?
+            .into_iter()
+            .filter(|info| info.close_time.is_some_and(|closed| closed < cutoff));
+        let mut archived = Vec::new();
+        for info in expired {
+            if self.archive_run(&info).await? {
+                archived.push(info.execution.workflow_id);
+            }
+        }
+        Ok(archived)
+    }
+
+    /// Move a listed run from the primary store to the archive; `false` if it is no longer the
+    /// stored run of its workflow ID or not closed
+    ///
+    /// The history is written to the archive before it is removed from the primary store, so a
+    /// failure leaves it in the primary store.
+    pub async fn archive_run(&self, info: &WorkflowExecutionInfo) -> Result<bool, StorageError> {
+        let workflow_id = &info.execution.workflow_id;
+        let (execution, history) = match self.primary.load_workflow_execution(workflow_id).await {
+            Ok(found) => found,
+            Err(StorageError::NotFound) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        // A new run may have started since the listing
+        if execution.run_id != info.execution.run_id || !history.is_closed() {
+            return Ok(false);
+        }
+        let bytes = HistoryExport::new(execution, history)
+            .encode(HistoryFormat::Json)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        self.archive.put(&self.key(workflow_id), bytes).await?;
+        self.primary.delete_workflow_execution(workflow_id).await?;
+        metrics::counter!(super::metrics::HISTORIES_ARCHIVED, "workflow_type" => info.workflow_type.clone()).increment(1);
+        Ok(true)
+    }
+
+    /// Archive expired runs every `interval` until the task is aborted, logging failures
+    pub async fn run_archival(&self, interval: Duration) {
+        loop {
+            match self.archive_expired().await {
+                Ok(archived) if !archived.is_empty() => tracing::info!(count = archived.len(), "archived workflow histories"),
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, "workflow history archival failed"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Archived run of a workflow; `None` if it is not archived
+    pub async fn load_archived(
+        &self,
+        workflow_id: &WorkflowId,
+    ) -> Result<Option<(WorkflowExecution, EventHistory)>, StorageError> {
+        let Some(bytes) = self.archive.get(&self.key(workflow_id)).await? else {
+            return Ok(None);
+        };
+        let export = HistoryExport::decode(HistoryFormat::Json, &bytes)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        Ok(Some((export.execution, export.history)))
+    }
+}
+
+#[async_trait]
+impl WorkflowStorage for ArchivingStorage {
+    async fn save_workflow_execution(
+        &self,
+        execution: &WorkflowExecution,
+        history: &EventHistory,
+    ) -> Result<(), StorageError> {
+        self.primary.save_workflow_execution(execution, history).await
+    }
+
+    async fn save_with_outbox(
+        &self,
+        execution: &WorkflowExecution,
+        history: &EventHistory,
+        outbox: &[OutboxMessage],
+    ) -> Result<(), StorageError> {
+        self.primary.save_with_outbox(execution, history, outbox).await
+    }
+
+    async fn pending_outbox(&self, limit: usize) -> Result<Vec<OutboxMessage>, StorageError> {
+        self.primary.pending_outbox(limit).await
+    }
+
+    async fn lease_lock(&self, name: &str, owner: &str, lease: Duration) -> Result<bool, StorageError> {
+        self.primary.lease_lock(name, owner, lease).await
+    }
+
+    async fn release_lock(&self, name: &str, owner: &str) -> Result<(), StorageError> {
+        self.primary.release_lock(name, owner).await
+    }
+
+    async fn mark_outbox_sent(&self, ids: &[String]) -> Result<(), StorageError> {
+        self.primary.mark_outbox_sent(ids).await
+    }
+
+    /// The run in the primary store, or else the archived one
+    async fn load_workflow_execution(
+        &self,
+        workflow_id: &WorkflowId,
+    ) -> Result<(WorkflowExecution, EventHistory), StorageError> {
+        match self.primary.load_workflow_execution(workflow_id).await {
+            Err(StorageError::NotFound) => self.load_archived(workflow_id).await?.ok_or(StorageError::NotFound),
+            loaded => loaded,
+        }
+    }
+
+    /// Lists the primary store only
+    async fn list_workflow_executions(
+        &self,
+        filter: &WorkflowFilter,
+    ) -> Result<Vec<WorkflowExecutionInfo>, StorageError> {
+        self.primary.list_workflow_executions(filter).await
+    }
+
+    /// Removes the run from the primary store and the archive
+    async fn delete_workflow_execution(&self, workflow_id: &WorkflowId) -> Result<(), StorageError> {
+        self.primary.delete_workflow_execution(workflow_id).await?;
+        self.archive.delete(&self.key(workflow_id)).await
+    }
+
+    /// Pages of the primary store, or else pages cut out of the archived history
+    async fn load_events(
+        &self,
+        workflow_id: &WorkflowId,
+        after_event_id: EventId,
+        limit: usize,
+    ) -> Result<HistoryPage, StorageError> {
+        match self.primary.load_events(workflow_id, after_event_id, limit).await {
+            Err(StorageError::NotFound) => {
+                let (execution, history) = self.load_archived(workflow_id).await?.ok_or(StorageError::NotFound)?;
+                Ok(HistoryPage::of(execution, &history, after_event_id, limit))
+            }
+            loaded => loaded,
+        }
+    }
+
+    /// Pings the primary store only; the archive is read on demand
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.primary.ping().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use axum::body::Bytes;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, Uri};
+    use parking_lot::Mutex;
+
+    use crate::temporal::event::EventType;
+    use crate::temporal::storage::InMemoryStorage;
+
+    async fn save(storage: &dyn WorkflowStorage, id: &str, closed: bool) -> WorkflowExecution {
+        let execution = WorkflowExecution::new(WorkflowId::new(id));
+        let mut history = EventHistory::new();
+        history.append(EventType::WorkflowExecutionStarted {
+            workflow_type: "report".to_string(),
+            task_queue: "default".to_string(),
+            input: serde_json::json!({}),
+        });
+        if closed {
+            history.append(EventType::WorkflowExecutionCompleted { result: serde_json::json!(7) });
+        }
+        storage.save_workflow_execution(&execution, &history).await.unwrap();
+        execution
+    }
+
+    #[tokio::test]
+    async fn test_archival_moves_closed_runs_and_reads_through() {
+        let root = std::env::temp_dir().join(format!("workflow-archive-{}", uuid::Uuid::new_v4()));
+        let primary = Arc::new(InMemoryStorage::new());
+        let storage = ArchivingStorage::new(primary.clone(), Arc::new(LocalObjectStore::new(&root)), Duration::from_secs(3600));
+        let closed = save(&storage, "reports/2024", true).await;
+        save(&storage, "open", false).await;
+
+        assert!(storage.archive_expired().await.unwrap().is_empty());
+        let archived = storage.archive_closed_before(Utc::now() + chrono::Duration::minutes(1)).await.unwrap();
+        assert_eq!(archived, vec![closed.workflow_id.clone()]);
+        assert!(root.join("histories/reports%2F2024.json").is_file());
+        assert!(matches!(primary.load_workflow_execution(&closed.workflow_id).await, Err(StorageError::NotFound)));
+        assert_eq!(storage.list_workflow_executions(&WorkflowFilter::new()).await.unwrap().len(), 1);
+
+        let (execution, history) = storage.load_workflow_execution(&closed.workflow_id).await.unwrap();
+        assert_eq!(execution, closed);
+        assert_eq!(history.outcome(), Some(Ok(serde_json::json!(7))));
+
+        storage.delete_workflow_execution(&closed.workflow_id).await.unwrap();
+        assert!(matches!(storage.load_workflow_execution(&closed.workflow_id).await, Err(StorageError::NotFound)));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    type Bucket = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// In-memory bucket checking the signature headers of each request
+    async fn bucket(State(objects): State<Bucket>, method: axum::http::Method, uri: Uri, headers: HeaderMap, body: Bytes) -> (axum::http::StatusCode, Vec<u8>) {
+        let authorization = headers["authorization"].to_str().unwrap();
+        assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKID/"), "{authorization}");
+        assert!(authorization.contains("/eu-central-1/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature="));
+        assert_eq!(headers["x-amz-content-sha256"], hex(&Sha256::digest(&body)));
+        let key = uri.path().to_string();
+        let mut objects = objects.lock();
+        match method {
+            axum::http::Method::PUT => {
+                objects.insert(key, body.to_vec());
+                (axum::http::StatusCode::OK, Vec::new())
+            }
+            axum::http::Method::GET => match objects.get(&key) {
+                Some(object) => (axum::http::StatusCode::OK, object.clone()),
+                None => (axum::http::StatusCode::NOT_FOUND, Vec::new()),
+            },
+            _ => {
+                objects.remove(&key);
+                (axum::http::StatusCode::NO_CONTENT, Vec::new())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_s3_store_roundtrip() {
+        let objects = Bucket::default();
+        let app = axum::Router::new().fallback(bucket).with_state(objects.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let store = S3ObjectStore::new(format!("http://{}/", address), "archive", "AKID", "secret").with_region("eu-central-1");
+        store.put("histories/a%2Fb.json", b"{}".to_vec()).await.unwrap();
+        assert!(objects.lock().contains_key("/archive/histories/a%252Fb.json"));
+        assert_eq!(store.get("histories/a%2Fb.json").await.unwrap(), Some(b"{}".to_vec()));
+        store.delete("histories/a%2Fb.json").await.unwrap();
+        assert_eq!(store.get("histories/a%2Fb.json").await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_signature_matches_reference() {
+        // Computed with botocore's S3SigV4Auth for the same request
+        let store = S3ObjectStore::new("https://s3.amazonaws.com", "bucket", "AKID", "secret");
+        let now = DateTime::parse_from_rfc3339("2026-10-15T15:54:17Z").unwrap().with_timezone(&Utc);
+        let url = store.url("histories/x.json").unwrap();
+        assert_eq!(
+            store.authorization(&Method::GET, &url, &hex(&Sha256::digest(b"")), now),
+            "AWS4-HMAC-SHA256 Credential=AKID/20261015/us-east-1/s3/aws4_request, \
+             SignedHeaders=host;x-amz-content-sha256;x-amz-date, \
+             Signature=64e786e56150608e58659b934aeacd67ca373acbb3e871e3cb5e802e96000754"
+        );
+    }
+}
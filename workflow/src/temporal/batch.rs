@@ -0,0 +1,433 @@
+//! Batch operations: one cancel, terminate or signal applied to many workflows
+//!
+//! [`WorkflowClient::batch`] starts a system workflow of type [`BATCH_WORKFLOW_TYPE`] that every
+//! worker runs without registration. It resolves the target, either a list of workflow IDs or a
+//! [`WorkflowFilter`] matched against running executions, and applies the operation in pages of
+//! [`BATCH_PAGE_SIZE`] through a built-in activity. Progress is upserted as the search attributes
+//! `BatchTotal`, `BatchSucceeded` and `BatchFailed` after every page, so
+//! [`WorkflowClient::batch_progress`] and workflow listing see it while the batch runs; the
+//! handle's result is the final [`BatchReport`].
+//!
+//! ```no_run
+//! # use workflow::temporal::{BatchRequest, StartWorkflowOptions, WorkflowClient, WorkflowError, WorkflowFilter};
+//! # async fn cleanup(client: WorkflowClient) -> Result<(), WorkflowError> {
+//! let filter = WorkflowFilter::new().workflow_type("OrderProcessing");
+//! let handle = client
+//!     .batch(BatchRequest::terminate(filter, "orders migrated"), StartWorkflowOptions::default())
+//!     .await?;
+//! let report = handle.result().await?;
+//! println!("{} of {} terminated", report.succeeded, report.total);
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::client::{StartWorkflowOptions, WorkflowClient, WorkflowHandle};
+use super::error::SignalError;
+use super::search::{SearchAttributeValue, SearchAttributes, WorkflowExecutionInfo, WorkflowExecutionStatus, WorkflowFilter};
+use super::{ActivityContext, ActivityError, ActivityOptions, RetryPolicy, Signal, Workflow, WorkflowContext, WorkflowError, WorkflowId};
+
+/// Workflow type of batch operations, run by every worker
+pub const BATCH_WORKFLOW_TYPE: &str = "__batch";
+
+/// Activity type of the steps of a batch operation
+pub(crate) const BATCH_ACTIVITY_TYPE: &str = "__batch_step";
+
+/// Workflows a batch operation applies to per activity
+pub const BATCH_PAGE_SIZE: usize = 100;
+
+/// Operation applied to every workflow of a batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchOperation {
+    /// Request cancellation, like [`WorkflowClient::cancel_workflow`]
+    Cancel,
+    /// Terminate, like [`WorkflowClient::terminate_workflow`]
+    Terminate { reason: String },
+    /// Send a signal
+    Signal { signal_name: String, input: serde_json::Value },
+}
+
+/// Workflows a batch operation applies to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchTarget {
+    /// These workflow IDs, running or not
+    WorkflowIds(Vec<WorkflowId>),
+    /// Running executions matching the filter when the batch starts
+    Filter(WorkflowFilter),
+}
+
+impl From<Vec<WorkflowId>> for BatchTarget {
+    fn from(workflow_ids: Vec<WorkflowId>) -> Self {
+        Self::WorkflowIds(workflow_ids)
+    }
+}
+
+impl From<WorkflowFilter> for BatchTarget {
+    fn from(filter: WorkflowFilter) -> Self {
+        Self::Filter(filter)
+    }
+}
+
+/// Input of a batch operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub operation: BatchOperation,
+    pub target: BatchTarget,
+}
+
+impl BatchRequest {
+    /// Request cancellation of the target workflows
+    pub fn cancel(target: impl Into<BatchTarget>) -> Self {
+        Self {
+            operation: BatchOperation::Cancel,
+            target: target.into(),
+        }
+    }
+
+    /// Terminate the target workflows
+    pub fn terminate(target: impl Into<BatchTarget>, reason: impl Into<String>) -> Self {
+        Self {
+            operation: BatchOperation::Terminate { reason: reason.into() },
+            target: target.into(),
+        }
+    }
+
+    /// Send `signal` to the target workflows
+    pub fn signal<S: Signal>(target: impl Into<BatchTarget>, signal: S) -> Result<Self, WorkflowError> {
+        Ok(Self::signal_value(target, S::name(), serde_json::to_value(signal)?))
+    }
+
+    /// Untyped [`signal`](Self::signal), for callers that only know the signal name
+    pub fn signal_value(target: impl Into<BatchTarget>, signal_name: impl Into<String>, input: serde_json::Value) -> Self {
+        Self {
+            operation: BatchOperation::Signal {
+                signal_name: signal_name.into(),
+                input,
+            },
+            target: target.into(),
+        }
+    }
+}
+
+/// Workflow the operation failed on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchFailure {
+    pub workflow_id: WorkflowId,
+    pub error: String,
+}
+
+/// Outcome of a batch operation
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchReport {
+    /// Workflows the target resolved to
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: Vec<BatchFailure>,
+}
+
+/// Progress of a batch operation, read from the search attributes of its workflow
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchProgress {
+    pub batch_id: WorkflowId,
+    pub status: WorkflowExecutionStatus,
+    /// Workflows the target resolved to; zero until it is resolved
+    pub total: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+}
+
+impl BatchProgress {
+    pub(crate) fn from_info(info: &WorkflowExecutionInfo) -> Self {
+        let count = |name: &str| match info.search_attributes.get(name) {
+            Some(SearchAttributeValue::Int(value)) => u64::try_from(*value).unwrap_or_default(),
+            _ => 0,
+        };
+        Self {
+            batch_id: info.execution.workflow_id.clone(),
+            status: info.status,
+            total: count("BatchTotal"),
+            succeeded: count("BatchSucceeded"),
+            failed: count("BatchFailed"),
+        }
+    }
+}
+
+/// Step of a batch operation, run by the built-in activity
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+enum BatchStep {
+    /// Running executions matching a filter
+    Resolve { filter: WorkflowFilter },
+    /// Apply the operation to one page of workflows, returning the failures
+    Apply {
+        operation: BatchOperation,
+        workflow_ids: Vec<WorkflowId>,
+    },
+}
+
+/// System workflow running a batch operation
+pub struct BatchWorkflow;
+
+impl BatchWorkflow {
+    async fn step<T: serde::de::DeserializeOwned>(
+        ctx: &WorkflowContext,
+        step: BatchStep,
+        options: &ActivityOptions,
+    ) -> Result<T, WorkflowError> {
+        let output = ctx
+            .execute_activity_value(BATCH_ACTIVITY_TYPE, serde_json::to_value(step)?, options)
+            .await?;
+        Ok(serde_json::from_value(output)?)
+    }
+
+    async fn report_progress(ctx: &WorkflowContext, report: &BatchReport) -> Result<(), WorkflowError> {
+        let count = |n: usize| SearchAttributeValue::Int(i64::try_from(n).unwrap_or(i64::MAX));
+        ctx.upsert_search_attributes(SearchAttributes::from([
+            ("BatchTotal".to_string(), count(report.total)),
+            ("BatchSucceeded".to_string(), count(report.succeeded)),
+            ("BatchFailed".to_string(), count(report.failed.len())),
+        ]))
+        .await
+    }
+}
+
+impl Workflow for BatchWorkflow {
+    type Input = BatchRequest;
+    type Output = BatchReport;
+
+    fn name() -> &'static str {
+        BATCH_WORKFLOW_TYPE
+    }
+
+    async fn execute(ctx: WorkflowContext, request: BatchRequest) -> Result<BatchReport, WorkflowError> {
+        let mut workflow_ids = match request.target {
+            BatchTarget::WorkflowIds(workflow_ids) => workflow_ids,
+            BatchTarget::Filter(filter) => {
+                Self::step(&ctx, BatchStep::Resolve { filter }, &ActivityOptions::default()).await?
+            }
+        };
+        // A filter broad enough to match the batch itself must not cancel it halfway
+        workflow_ids.retain(|id| *id != ctx.execution().workflow_id);
+
+        let mut report = BatchReport {
+            total: workflow_ids.len(),
+            ..Default::default()
+        };
+        Self::report_progress(&ctx, &report).await?;
+        // Signals are not idempotent, so a failed page is reported rather than applied again
+        let apply = ActivityOptions {
+            retry_policy: Some(RetryPolicy::no_retry()),
+            ..Default::default()
+        };
+        for page in workflow_ids.chunks(BATCH_PAGE_SIZE) {
+            let step = BatchStep::Apply {
+                operation: request.operation.clone(),
+                workflow_ids: page.to_vec(),
+            };
+            let failures: Vec<BatchFailure> = Self::step(&ctx, step, &apply).await?;
+            report.succeeded += page.len() - failures.len();
+            report.failed.extend(failures);
+            Self::report_progress(&ctx, &report).await?;
+        }
+        Ok(report)
+    }
+}
+
+/// Built-in activity running the steps of batch operations with the worker's client
+pub(crate) async fn apply(
+    client: WorkflowClient,
+    _ctx: ActivityContext,
+    input: serde_json::Value,
+) -> Result<serde_json::Value, ActivityError> {
+    let step: BatchStep = serde_json::from_value(input).map_err(|e| ActivityError::InvalidInput(e.to_string()))?;
+    let output = match step {
+        BatchStep::Resolve { filter } => {
+            let running: Vec<WorkflowId> = client
+                .list_workflows(&filter)
+                .await
+                .map_err(|e| ActivityError::TemporaryFailure(e.to_string()))?
+                .into_iter()
+                .filter(|info| info.status == WorkflowExecutionStatus::Running)
+                .map(|info| info.execution.workflow_id)
+                .collect();
+            serde_json::to_value(running)
+        }
+        BatchStep::Apply { operation, workflow_ids } => {
+            let mut failures = Vec::new();
+            for workflow_id in workflow_ids {
+                let result: Result<(), SignalError> = match &operation {
+                    BatchOperation::Cancel => client.cancel_workflow(&workflow_id).await,
+                    BatchOperation::Terminate { reason } => client.terminate_workflow(&workflow_id, reason).await,
+                    BatchOperation::Signal { signal_name, input } => {
+                        client.signal_workflow_value(&workflow_id, signal_name, input.clone()).await
+                    }
+                };
+                if let Err(e) = result {
+                    failures.push(BatchFailure {
+                        workflow_id,
+                        error: e.to_string(),
+                    });
+                }
+            }
+            serde_json::to_value(failures)
+        }
+    };
+    output.map_err(|e| ActivityError::ExecutionFailed(e.to_string()))
+}
+
+impl WorkflowClient {
+    /// Start a batch operation in the background
+    ///
+    /// The batch runs as a workflow on the options' task queue, under the options' workflow ID or
+    /// `batch-{uuid}`, which is the ID [`batch_progress`](Self::batch_progress) takes.
+    pub async fn batch(
+        &self,
+        request: BatchRequest,
+        options: StartWorkflowOptions,
+    ) -> Result<WorkflowHandle<BatchReport>, WorkflowError> {
+        let workflow_id = options
+            .workflow_id
+            .clone()
+            .unwrap_or_else(|| WorkflowId::new(format!("batch-{}", Uuid::new_v4())));
+        let options = StartWorkflowOptions {
+            workflow_id: Some(workflow_id),
+            ..options
+        };
+        self.start_workflow::<BatchWorkflow>(request, options).await
+    }
+
+    /// Progress of a batch operation; `None` if no batch has this ID
+    pub async fn batch_progress(&self, batch_id: &WorkflowId) -> Result<Option<BatchProgress>, WorkflowError> {
+        let Some((execution, history)) = self.load_workflow(batch_id).await? else {
+            return Ok(None);
+        };
+        Ok(WorkflowExecutionInfo::from_history(execution, &history)
+            .filter(|info| info.workflow_type == BATCH_WORKFLOW_TYPE)
+            .map(|info| BatchProgress::from_info(&info)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::temporal::search::Comparison;
+    use crate::temporal::{WorkerConfig, WorkflowWorker};
+
+    /// Releases a parked workflow
+    #[derive(Serialize, Deserialize)]
+    struct Release {
+        amount: i64,
+    }
+
+    impl Signal for Release {
+        fn name() -> &'static str {
+            "release"
+        }
+    }
+
+    /// Waits to be released
+    struct Parked;
+
+    impl Workflow for Parked {
+        type Input = ();
+        type Output = i64;
+
+        fn name() -> &'static str {
+            "parked"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<i64, WorkflowError> {
+            Ok(ctx.wait_for_signal::<Release>(None).await?.amount)
+        }
+    }
+
+    fn spawn_worker() -> (Arc<WorkflowWorker>, tokio::task::JoinHandle<()>) {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..Default::default()
+        }));
+        worker.register_workflow::<Parked>();
+        let running = worker.clone();
+        (worker, tokio::spawn(async move { running.run().await.map(drop).unwrap() }))
+    }
+
+    async fn park(client: &WorkflowClient, id: &str, group: &str) -> WorkflowHandle<i64> {
+        let options = StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new(id)),
+            search_attributes: SearchAttributes::from([("Group".to_string(), SearchAttributeValue::from(group))]),
+            ..Default::default()
+        };
+        client.start_workflow::<Parked>((), options).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_batch_signal_and_cancel_by_workflow_ids() {
+        let (worker, run) = spawn_worker();
+        let client = worker.client();
+        let first = park(&client, "parked-1", "a").await;
+        let second = park(&client, "parked-2", "a").await;
+        let third = park(&client, "parked-3", "a").await;
+
+        let ids = vec![WorkflowId::new("parked-1"), WorkflowId::new("parked-2"), WorkflowId::new("missing")];
+        let request = BatchRequest::signal(ids, Release { amount: 7 }).unwrap();
+        let report = client.batch(request, StartWorkflowOptions::default()).await.unwrap().result().await.unwrap();
+        assert_eq!((report.total, report.succeeded), (3, 2));
+        assert_eq!(report.failed[0].workflow_id, WorkflowId::new("missing"));
+        assert_eq!(first.result().await.unwrap(), 7);
+        assert_eq!(second.result().await.unwrap(), 7);
+
+        let handle = client
+            .batch(BatchRequest::cancel(vec![WorkflowId::new("parked-3")]), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(handle.result().await.unwrap().succeeded, 1);
+        let failure = third.result().await.unwrap_err().to_string();
+        assert!(failure.contains("cancelled"), "{}", failure);
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_batch_terminate_by_filter_reports_progress() {
+        let (worker, run) = spawn_worker();
+        let client = worker.client();
+        let mut doomed = Vec::new();
+        for i in 0..3 {
+            doomed.push(park(&client, &format!("doomed-{}", i), "old").await);
+        }
+        let spared = park(&client, "spared", "new").await;
+
+        let filter = WorkflowFilter::new().attribute("Group", Comparison::Eq, "old");
+        let options = StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new("cleanup")),
+            ..Default::default()
+        };
+        let handle = client.batch(BatchRequest::terminate(filter, "migrated"), options).await.unwrap();
+        let report = handle.result().await.unwrap();
+        assert_eq!(report, BatchReport { total: 3, succeeded: 3, failed: Vec::new() });
+
+        for handle in doomed {
+            let failure = handle.result().await.unwrap_err().to_string();
+            assert!(failure.contains("terminated: migrated"), "{}", failure);
+            assert!(handle.history().await.unwrap().is_terminated());
+        }
+        let progress = client.batch_progress(&WorkflowId::new("cleanup")).await.unwrap().unwrap();
+        assert_eq!(progress.status, WorkflowExecutionStatus::Completed);
+        assert_eq!((progress.total, progress.succeeded, progress.failed), (3, 3, 0));
+        assert!(client.batch_progress(&WorkflowId::new("spared")).await.unwrap().is_none());
+
+        client.signal_workflow(&WorkflowId::new("spared"), Release { amount: 1 }).await.unwrap();
+        assert_eq!(spared.result().await.unwrap(), 1);
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap();
+    }
+}
@@ -0,0 +1,318 @@
+//! Circuit breaker for activity invocations
+//!
+//! A [`CircuitBreaker`] tracks the outcomes of recent attempts per activity type. Once the failure
+//! rate over its window crosses the threshold, the circuit opens and the worker fails attempts of
+//! that type fast with [`ActivityError::TemporaryFailure`] instead of running them, leaving it to
+//! the retry policy to come back later. After a cool-down the circuit half-opens and lets a probe
+//! attempt through: its success closes the circuit again, its failure reopens it.
+//!
+//! State is exported as the `temporal_circuit_breaker_state` gauge per activity type
+//! (0 = closed, 1 = half-open, 2 = open).
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use super::ActivityError;
+
+/// When a circuit opens and for how long
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircuitBreakerConfig {
+    /// Failure rate over the window, between 0 and 1, at which the circuit opens
+    pub failure_rate_threshold: f64,
+    /// Attempts recorded before the failure rate is considered at all
+    pub minimum_calls: usize,
+    /// Number of most recent attempts the failure rate is computed over
+    pub window_size: usize,
+    /// Time an open circuit waits before letting a probe through
+    pub cool_down: Duration,
+    /// Probe attempts let through concurrently while half-open
+    pub half_open_max_calls: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_rate_threshold: 0.5,
+            minimum_calls: 10,
+            window_size: 20,
+            cool_down: Duration::from_secs(30),
+            half_open_max_calls: 1,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn failure_rate_threshold(mut self, threshold: f64) -> Self {
+        self.failure_rate_threshold = threshold.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn minimum_calls(mut self, calls: usize) -> Self {
+        self.minimum_calls = calls.max(1);
+        self
+    }
+
+    pub fn window_size(mut self, size: usize) -> Self {
+        self.window_size = size.max(1);
+        self
+    }
+
+    pub fn cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    pub fn half_open_max_calls(mut self, calls: u32) -> Self {
+        self.half_open_max_calls = calls.max(1);
+        self
+    }
+}
+
+/// State of the circuit for one activity type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Attempts run normally
+    Closed,
+    /// Probe attempts run to find out whether the activity recovered
+    HalfOpen,
+    /// Attempts fail fast
+    Open,
+}
+
+impl CircuitState {
+    fn gauge(self) -> f64 {
+        match self {
+            CircuitState::Closed => 0.0,
+            CircuitState::HalfOpen => 1.0,
+            CircuitState::Open => 2.0,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::HalfOpen => "half_open",
+            CircuitState::Open => "open",
+        }
+    }
+}
+
+struct Circuit {
+    state: CircuitState,
+    /// Recent outcomes while closed, `true` for a failure
+    outcomes: VecDeque<bool>,
+    opened_at: Option<Instant>,
+    probes_in_flight: u32,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            outcomes: VecDeque::new(),
+            opened_at: None,
+            probes_in_flight: 0,
+        }
+    }
+
+    fn failure_rate(&self) -> f64 {
+        let failures = self.outcomes.iter().filter(|failed| **failed).count();
+        failures as f64 / self.outcomes.len().max(1) as f64
+    }
+}
+
+/// Whether an activity error says something about the health of the activity
+///
+/// Invalid input, validation failures and cancellations are the caller's doing and leave the
+/// circuit alone.
+fn counts_as_failure(error: &ActivityError) -> bool {
+    !matches!(
+        error,
+        ActivityError::InvalidInput(_)
+            | ActivityError::ValidationFailed(_)
+            | ActivityError::Cancelled
+            | ActivityError::DeadLettered(_)
+    )
+}
+
+/// Per-activity-type circuit breakers, shared by a worker's activity tasks
+pub struct CircuitBreaker {
+    default: CircuitBreakerConfig,
+    overrides: HashMap<String, CircuitBreakerConfig>,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(CircuitBreakerConfig::default())
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(default: CircuitBreakerConfig) -> Self {
+        Self {
+            default,
+            overrides: HashMap::new(),
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Use a separate configuration for one activity type
+    pub fn configure(mut self, activity_type: impl Into<String>, config: CircuitBreakerConfig) -> Self {
+        self.overrides.insert(activity_type.into(), config);
+        self
+    }
+
+    fn config(&self, activity_type: &str) -> CircuitBreakerConfig {
+        self.overrides.get(activity_type).copied().unwrap_or(self.default)
+    }
+
+    /// Current state of the circuit for `activity_type`
+    pub fn state(&self, activity_type: &str) -> CircuitState {
+        self.circuits
+            .lock()
+            .get(activity_type)
+            .map_or(CircuitState::Closed, |circuit| circuit.state)
+    }
+
+    /// Ask to run an attempt of `activity_type`
+    ///
+    /// Fails with [`ActivityError::TemporaryFailure`] while the circuit is open. An admitted
+    /// attempt must be reported back through [`record`](Self::record).
+    pub fn acquire(&self, activity_type: &str) -> Result<(), ActivityError> {
+        let config = self.config(activity_type);
+        let mut circuits = self.circuits.lock();
+        let circuit = circuits.entry(activity_type.to_string()).or_insert_with(Circuit::new);
+        if circuit.state == CircuitState::Open
+            && circuit.opened_at.is_some_and(|opened| opened.elapsed() >= config.cool_down)
+        {
+            transition(activity_type, circuit, CircuitState::HalfOpen);
+        }
+        let admitted = match circuit.state {
+            CircuitState::Closed => true,
+            CircuitState::HalfOpen if circuit.probes_in_flight < config.half_open_max_calls => {
+                circuit.probes_in_flight += 1;
+                true
+            }
+            CircuitState::HalfOpen | CircuitState::Open => false,
+        };
+        if admitted {
+            return Ok(());
+        }
+        metrics::counter!("temporal_circuit_breaker_rejections_total", "activity_type" => activity_type.to_string())
+            .increment(1);
+        Err(ActivityError::TemporaryFailure(format!("circuit open for activity {}", activity_type)))
+    }
+
+    /// Report the outcome of an attempt admitted by [`acquire`](Self::acquire)
+    pub fn record<T>(&self, activity_type: &str, result: &Result<T, ActivityError>) {
+        let failed = result.as_ref().err().is_some_and(counts_as_failure);
+        let config = self.config(activity_type);
+        let mut circuits = self.circuits.lock();
+        let circuit = circuits.entry(activity_type.to_string()).or_insert_with(Circuit::new);
+        match circuit.state {
+            CircuitState::Closed => {
+                circuit.outcomes.push_back(failed);
+                while circuit.outcomes.len() > config.window_size {
+                    circuit.outcomes.pop_front();
+                }
+                if circuit.outcomes.len() >= config.minimum_calls
+                    && circuit.failure_rate() >= config.failure_rate_threshold
+                {
+                    transition(activity_type, circuit, CircuitState::Open);
+                }
+            }
+            CircuitState::HalfOpen => {
+                circuit.probes_in_flight = circuit.probes_in_flight.saturating_sub(1);
+                let next = if failed { CircuitState::Open } else { CircuitState::Closed };
+                transition(activity_type, circuit, next);
+            }
+            // A straggler admitted before the circuit opened
+            CircuitState::Open => {}
+        }
+    }
+}
+
+fn transition(activity_type: &str, circuit: &mut Circuit, state: CircuitState) {
+    match state {
+        CircuitState::Open => circuit.opened_at = Some(Instant::now()),
+        CircuitState::Closed => {
+            circuit.outcomes.clear();
+            circuit.opened_at = None;
+        }
+        CircuitState::HalfOpen => {}
+    }
+    if state != CircuitState::HalfOpen {
+        circuit.probes_in_flight = 0;
+    }
+    tracing::info!(activity_type, from = circuit.state.as_str(), to = state.as_str(), "circuit breaker transition");
+    circuit.state = state;
+    metrics::gauge!("temporal_circuit_breaker_state", "activity_type" => activity_type.to_string()).set(state.gauge());
+    metrics::counter!(
+        "temporal_circuit_breaker_transitions_total",
+        "activity_type" => activity_type.to_string(),
+        "state" => state.as_str()
+    )
+    .increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure() -> Result<(), ActivityError> {
+        Err(ActivityError::ExecutionFailed("down".into()))
+    }
+
+    #[test]
+    fn test_opens_after_failure_rate_and_fails_fast() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig::default().minimum_calls(4).window_size(4));
+        for outcome in [Ok(()), failure(), failure()] {
+            breaker.acquire("charge").unwrap();
+            breaker.record("charge", &outcome);
+        }
+        assert_eq!(breaker.state("charge"), CircuitState::Closed);
+        breaker.acquire("charge").unwrap();
+        breaker.record("charge", &failure());
+        assert_eq!(breaker.state("charge"), CircuitState::Open);
+
+        let rejected = breaker.acquire("charge").unwrap_err();
+        assert!(matches!(rejected, ActivityError::TemporaryFailure(_)));
+        // Other activity types keep their own circuit
+        assert!(breaker.acquire("refund").is_ok());
+    }
+
+    #[test]
+    fn test_half_open_probe_closes_or_reopens() {
+        let config = CircuitBreakerConfig::default()
+            .minimum_calls(1)
+            .cool_down(Duration::from_millis(20));
+        let breaker = CircuitBreaker::new(config);
+        breaker.acquire("charge").unwrap();
+        breaker.record("charge", &failure());
+        assert_eq!(breaker.state("charge"), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(30));
+        breaker.acquire("charge").unwrap();
+        assert_eq!(breaker.state("charge"), CircuitState::HalfOpen);
+        // Only one probe at a time
+        assert!(breaker.acquire("charge").is_err());
+        breaker.record("charge", &failure());
+        assert_eq!(breaker.state("charge"), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(30));
+        breaker.acquire("charge").unwrap();
+        breaker.record("charge", &Ok::<_, ActivityError>(()));
+        assert_eq!(breaker.state("charge"), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_caller_errors_do_not_trip() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig::default().minimum_calls(1));
+        breaker.acquire("charge").unwrap();
+        breaker.record::<()>("charge", &Err(ActivityError::InvalidInput("bad".into())));
+        assert_eq!(breaker.state("charge"), CircuitState::Closed);
+    }
+}
@@ -1,22 +1,740 @@
 //! Workflow client for starting workflows and sending signals
 
-use super::{WorkflowId, WorkflowExecution};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::stream::{self, Stream, TryStreamExt};
+use serde::de::DeserializeOwned;
+use tracing::Instrument;
+use uuid::Uuid;
+use super::dead_letter::{DeadLetter, DeadLetterQueue};
+use super::query::{Query, QueryDispatcher};
+use super::update::{Update, UpdateDispatcher};
+use super::event::{EventHistory, EventType, WorkflowEvent};
+use super::history_export::{HistoryExport, HistoryFormat};
+use super::converter::{self, DataConverter};
+use super::interceptor::{ClientInterceptor, SignalWorkflowRequest, StartWorkflowRequest};
+use super::namespace::Namespace;
+use super::schedule::{ScheduleDescription, ScheduleOverlapPolicy, Schedules};
+use super::signal::{CANCEL_REQUEST_SIGNAL, TERMINATE_SIGNAL};
+use super::search::{Comparison, Memo, SearchAttributes, WorkflowExecutionInfo, WorkflowExecutionStatus, WorkflowFilter};
+use super::storage::{HistoryPage, WorkflowStorage};
+use super::task_queue::{Priority, SignalTask, Task, TaskQueue, WorkflowTask};
+use super::telemetry;
+use super::{EventId, RunId, Signal, Workflow, WorkflowError, WorkflowId, WorkflowExecution};
+use super::error::{QueryError, SignalError, StorageError, UpdateError};
+#[cfg(feature = "persistence")]
+use crate::persistence::{PersistenceAdapter, StateSnapshot};
+
+/// How often [`WorkflowHandle::result`] checks storage for the outcome
+const RESULT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Search attribute holding the [dedup key](StartWorkflowOptions::dedup_key) of an execution
+pub const DEDUP_KEY_ATTRIBUTE: &str = "DedupKey";
+
+/// How long after it closed a run still takes in starts with its dedup key, unless configured otherwise
+pub const DEFAULT_DEDUP_WINDOW: Duration = Duration::from_secs(24 * 3600);
 
 /// Workflow client
+///
+/// Talks to workers only through the shared task queue, storage, schedules and dead-letter queue.
+/// Queries are answered by the worker the client was obtained from, if any.
+#[derive(Clone)]
 pub struct WorkflowClient {
-    // Client implementation will be added later
+    task_queue: Arc<dyn TaskQueue>,
+    storage: Arc<dyn WorkflowStorage>,
+    schedules: Arc<Schedules>,
+    dead_letters: Arc<DeadLetterQueue>,
+    queries: Option<Arc<dyn QueryDispatcher>>,
+    updates: Option<Arc<dyn UpdateDispatcher>>,
+    interceptors: Vec<Arc<dyn ClientInterceptor>>,
+    converter: Arc<dyn DataConverter>,
+    namespace: Namespace,
+    #[cfg(feature = "persistence")]
+    idempotency: Option<(Arc<dyn PersistenceAdapter>, Duration)>,
+    dedup_window: Duration,
+    /// Held while a start with a dedup key looks for an earlier run and starts its own
+    dedup_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl WorkflowClient {
     /// Create a new workflow client
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(task_queue: Arc<dyn TaskQueue>, storage: Arc<dyn WorkflowStorage>) -> Self {
+        Self {
+            task_queue,
+            storage,
+            schedules: Arc::new(Schedules::new()),
+            dead_letters: Arc::new(DeadLetterQueue::new()),
+            queries: None,
+            updates: None,
+            interceptors: Vec::new(),
+            converter: converter::default_converter(),
+            namespace: Namespace::default(),
+            #[cfg(feature = "persistence")]
+            idempotency: None,
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+            dedup_lock: Arc::new(tokio::sync::Mutex::new(())),
+        }
     }
-}
 
-impl Default for WorkflowClient {
-    fn default() -> Self {
-        Self::new()
+    /// Use the given schedule registry, normally the one of the workers that fire the schedules
+    pub fn with_schedules(mut self, schedules: Arc<Schedules>) -> Self {
+        self.schedules = schedules;
+        self
+    }
+
+    /// Use the given dead-letter queue, normally the one of the workers that run the tasks
+    pub fn with_dead_letters(mut self, dead_letters: Arc<DeadLetterQueue>) -> Self {
+        self.dead_letters = dead_letters;
+        self
+    }
+
+    /// Add an interceptor around starts and signals; see [`interceptor`](super::interceptor) for the order
+    pub fn with_interceptor(mut self, interceptor: Arc<dyn ClientInterceptor>) -> Self {
+        self.interceptors.push(interceptor);
+        self
+    }
+
+    /// Encode workflow and signal inputs with `converter` and decode results with it
+    pub fn with_data_converter(mut self, converter: Arc<dyn DataConverter>) -> Self {
+        self.converter = converter;
+        self
+    }
+
+    /// Label the metrics of this client with `namespace`; see [`Namespaces`](super::namespace::Namespaces)
+    pub fn with_namespace(mut self, namespace: Namespace) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    /// Remember [idempotency keys](StartWorkflowOptions::idempotency_key) in `store` for `ttl`
+    ///
+    /// Clients sharing the store recognize each other's keys.
+    #[cfg(feature = "persistence")]
+    pub fn with_idempotency_store(mut self, store: Arc<dyn PersistenceAdapter>, ttl: Duration) -> Self {
+        self.idempotency = Some((store, ttl));
+        self
+    }
+
+    /// Let closed runs take in starts with their [dedup key](StartWorkflowOptions::dedup_key) for `window`,
+    /// [`DEFAULT_DEDUP_WINDOW`] by default
+    pub fn with_dedup_window(mut self, window: Duration) -> Self {
+        self.dedup_window = window;
+        self
+    }
+
+    pub(crate) fn with_queries(mut self, queries: Arc<dyn QueryDispatcher>) -> Self {
+        self.queries = Some(queries);
+        self
+    }
+
+    pub(crate) fn with_updates(mut self, updates: Arc<dyn UpdateDispatcher>) -> Self {
+        self.updates = Some(updates);
+        self
+    }
+
+    /// Task queue shared with the workers
+    pub(crate) fn task_queue(&self) -> &Arc<dyn TaskQueue> {
+        &self.task_queue
+    }
+
+    /// Storage shared with the workers
+    pub(crate) fn storage(&self) -> &Arc<dyn WorkflowStorage> {
+        &self.storage
+    }
+
+    /// Start a workflow execution
+    ///
+    /// Fails with [`WorkflowError::AlreadyStarted`] if an execution with the same workflow ID is still open,
+    /// or if a closed one is not to be reused under the options' [`WorkflowIdReusePolicy`].
+    /// Options with a `cron_schedule` are rejected; use [`schedule_workflow`](Self::schedule_workflow) instead.
+    pub async fn start_workflow<W: Workflow>(
+        &self,
+        input: W::Input,
+        options: StartWorkflowOptions,
+    ) -> Result<WorkflowHandle<W::Output>, WorkflowError> {
+        if options.cron_schedule.is_some() {
+            return Err(WorkflowError::InvalidInput(
+                "options with a cron_schedule must be passed to schedule_workflow".to_string(),
+            ));
+        }
+        let execution = self
+            .start_workflow_value(W::name(), serde_json::to_value(input)?, &options)
+            .await?;
+        Ok(WorkflowHandle::new(execution, self.storage.clone()).with_converter(self.converter.clone()))
+    }
+
+    /// Start a workflow registered by name, such as a [`DynamicWorkflow`](crate::dsl::DynamicWorkflow)
+    pub async fn start_dynamic_workflow(
+        &self,
+        workflow_type: &str,
+        input: serde_json::Value,
+        options: StartWorkflowOptions,
+    ) -> Result<WorkflowHandle<serde_json::Value>, WorkflowError> {
+        if options.cron_schedule.is_some() {
+            return Err(WorkflowError::InvalidInput(
+                "options with a cron_schedule must be passed to schedule_workflow".to_string(),
+            ));
+        }
+        let execution = self.start_workflow_value(workflow_type, input, &options).await?;
+        Ok(WorkflowHandle::new(execution, self.storage.clone()).with_converter(self.converter.clone()))
+    }
+
+    /// Untyped [`start_workflow`](Self::start_workflow), for callers that only know the workflow type name
+    pub(crate) async fn start_workflow_value(
+        &self,
+        workflow_type: &str,
+        input: serde_json::Value,
+        options: &StartWorkflowOptions,
+    ) -> Result<WorkflowExecution, WorkflowError> {
+        match &options.idempotency_key {
+            Some(key) => self.start_idempotent(key, workflow_type, input, options).await,
+            None => self.start_deduplicated(workflow_type, input, options).await,
+        }
+    }
+
+    /// Start unless a run of the same type and dedup key is open or closed within the dedup window, whose
+    /// execution is returned instead
+    ///
+    /// Starts through this client are serialized while they look for an earlier run; a start racing
+    /// one on a client of another process may still start a second run.
+    async fn start_deduplicated(
+        &self,
+        workflow_type: &str,
+        input: serde_json::Value,
+        options: &StartWorkflowOptions,
+    ) -> Result<WorkflowExecution, WorkflowError> {
+        let Some(key) = &options.dedup_key else {
+            return self.start_intercepted(workflow_type, input, options).await;
+        };
+        let _serialized = self.dedup_lock.lock().await;
+        if let Some(existing) = self.deduplicated(workflow_type, key).await? {
+            tracing::debug!(execution = %existing, dedup_key = %key, "start deduplicated");
+            metrics::counter!(
+                super::metrics::WORKFLOW_STARTS_DEDUPLICATED,
+                "namespace" => self.namespace.to_string(),
+                "workflow_type" => workflow_type.to_string()
+            )
+            .increment(1);
+            return Ok(existing);
+        }
+        let mut options = options.clone();
+        options.search_attributes.insert(DEDUP_KEY_ATTRIBUTE.to_string(), key.clone().into());
+        self.start_intercepted(workflow_type, input, &options).await
+    }
+
+    /// Latest run of `workflow_type` with dedup key `key` that is open or closed within the dedup window
+    async fn deduplicated(&self, workflow_type: &str, key: &str) -> Result<Option<WorkflowExecution>, WorkflowError> {
+        let filter = WorkflowFilter::new()
+            .workflow_type(workflow_type)
+            .attribute(DEDUP_KEY_ATTRIBUTE, Comparison::Eq, key);
+        let window = chrono::Duration::from_std(self.dedup_window).unwrap_or(chrono::Duration::MAX);
+        let now = chrono::Utc::now();
+        let latest = self.list_workflows(&filter).await?.into_iter().find(|info| match info.close_time {
+            Some(closed) if info.status != WorkflowExecutionStatus::ContinuedAsNew => now - closed < window,
+            _ => true,
+        });
+        Ok(latest.map(|info| info.execution))
+    }
+
+    /// Start once per idempotency key; repeated requests return the execution the first one started
+    ///
+    /// A key whose start failed may be used again. A repeat that arrives while the first request is
+    /// still starting fails with [`WorkflowError::AlreadyStarted`].
+    #[cfg(feature = "persistence")]
+    async fn start_idempotent(
+        &self,
+        key: &str,
+        workflow_type: &str,
+        input: serde_json::Value,
+        options: &StartWorkflowOptions,
+    ) -> Result<WorkflowExecution, WorkflowError> {
+        let Some((store, ttl)) = &self.idempotency else {
+            return Err(WorkflowError::InvalidInput(
+                "idempotency keys need a client with an idempotency store".to_string(),
+            ));
+        };
+        let record = format!("idempotency:{}", key);
+        let storage_error = |e: anyhow::Error| WorkflowError::StorageError(e.to_string());
+        if !store.put_idempotency_key(key, ttl.as_secs()).await.map_err(storage_error)? {
+            match store.load_state(&record).await.map_err(storage_error)? {
+                Some(snapshot) if !snapshot.state.is_null() => return Ok(serde_json::from_value(snapshot.state)?),
+                // The first request failed to start, this one takes over
+                Some(_) => {}
+                None => {
+                    return Err(WorkflowError::AlreadyStarted(format!("request with idempotency key {}", key)));
+                }
+            }
+        }
+        let result = self.start_deduplicated(workflow_type, input, options).await;
+        let state = match &result {
+            Ok(execution) => serde_json::to_value(execution)?,
+            Err(_) => serde_json::Value::Null,
+        };
+        let snapshot = StateSnapshot {
+            workflow_id: record,
+            state,
+            updated_at: chrono::Utc::now().timestamp(),
+        };
+        store.save_state(snapshot).await.map_err(storage_error)?;
+        result
+    }
+
+    #[cfg(not(feature = "persistence"))]
+    async fn start_idempotent(
+        &self,
+        _key: &str,
+        _workflow_type: &str,
+        _input: serde_json::Value,
+        _options: &StartWorkflowOptions,
+    ) -> Result<WorkflowExecution, WorkflowError> {
+        Err(WorkflowError::InvalidInput(
+            "idempotency keys need the persistence feature".to_string(),
+        ))
+    }
+
+    async fn start_intercepted(
+        &self,
+        workflow_type: &str,
+        input: serde_json::Value,
+        options: &StartWorkflowOptions,
+    ) -> Result<WorkflowExecution, WorkflowError> {
+        let mut request = StartWorkflowRequest {
+            workflow_type: workflow_type.to_string(),
+            input,
+            options: options.clone(),
+        };
+        for interceptor in &self.interceptors {
+            interceptor.before_start(&mut request).await?;
+        }
+        let result = self.start_requested(&request).await;
+        for interceptor in self.interceptors.iter().rev() {
+            interceptor.after_start(&request, &result).await;
+        }
+        result
+    }
+
+    async fn start_requested(&self, request: &StartWorkflowRequest) -> Result<WorkflowExecution, WorkflowError> {
+        let StartWorkflowRequest { workflow_type, input, options } = request;
+        let input = converter::encode(&*self.converter, input.clone())?;
+        let workflow_id = options.workflow_id.clone().unwrap_or_else(WorkflowId::generate);
+        match self.storage.load_workflow_execution(&workflow_id).await {
+            Ok((_, history)) if !options.id_reuse_policy.allows(&history) => {
+                return Err(WorkflowError::AlreadyStarted(workflow_id.to_string()));
+            }
+            Ok(_) | Err(StorageError::NotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let execution = WorkflowExecution::new(workflow_id);
+        let mut history = EventHistory::new();
+        history.append(EventType::WorkflowExecutionStarted {
+            workflow_type: workflow_type.to_string(),
+            task_queue: options.task_queue.clone(),
+            input: input.clone(),
+        });
+        if !options.search_attributes.is_empty() {
+            history.append(EventType::UpsertSearchAttributes {
+                attributes: options.search_attributes.clone(),
+            });
+        }
+        if !options.memo.is_empty() {
+            history.append(EventType::WorkflowMemoSet {
+                memo: options.memo.clone(),
+            });
+        }
+        self.storage.save_workflow_execution(&execution, &history).await?;
+        let span = telemetry::start_workflow_span(workflow_type, &execution);
+        self.task_queue
+            .push(
+                &options.task_queue,
+                Task::Workflow(WorkflowTask {
+                    execution: execution.clone(),
+                    workflow_type: workflow_type.to_string(),
+                    task_queue: options.task_queue.clone(),
+                    input,
+                    priority: options.priority,
+                    trace_context: telemetry::context_of(&span),
+                }),
+            )
+            .instrument(span)
+            .await?;
+
+        metrics::counter!(
+            super::metrics::WORKFLOWS_STARTED,
+            "namespace" => self.namespace.to_string(),
+            "workflow_type" => workflow_type.to_string()
+        ).increment(1);
+        Ok(execution)
+    }
+
+    /// Latest stored run of a workflow and its history; `None` if the workflow ID is unknown
+    pub async fn load_workflow(
+        &self,
+        workflow_id: &WorkflowId,
+    ) -> Result<Option<(WorkflowExecution, EventHistory)>, WorkflowError> {
+        match self.storage.load_workflow_execution(workflow_id).await {
+            Ok(found) => Ok(Some(found)),
+            Err(StorageError::NotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Page of the history of a workflow's latest run; `None` if the workflow ID is unknown
+    ///
+    /// Pages are read as described for [`WorkflowStorage::load_events`]; payloads are returned as stored.
+    pub async fn load_events(
+        &self,
+        workflow_id: &WorkflowId,
+        after_event_id: EventId,
+        limit: usize,
+    ) -> Result<Option<HistoryPage>, WorkflowError> {
+        match self.storage.load_events(workflow_id, after_event_id, limit).await {
+            Ok(page) => Ok(Some(page)),
+            Err(StorageError::NotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Events of the latest run of a workflow, read from storage `page_size` at a time
+    ///
+    /// Only one page is held in memory, so histories of any length can be walked. The stream fails
+    /// if the workflow ID is unknown, or if a new run replaces the one being read before its last page.
+    pub fn stream_history(
+        &self,
+        workflow_id: &WorkflowId,
+        page_size: usize,
+    ) -> impl Stream<Item = Result<WorkflowEvent, WorkflowError>> + Send + 'static {
+        let storage = self.storage.clone();
+        let workflow_id = workflow_id.clone();
+        let pages = stream::try_unfold((Some(EventId::zero()), None), move |(after, run_id): (Option<EventId>, Option<RunId>)| {
+            let storage = storage.clone();
+            let workflow_id = workflow_id.clone();
+            async move {
+                let Some(after) = after else {
+                    return Ok(None);
+                };
+                let page = storage.load_events(&workflow_id, after, page_size).await?;
+                if run_id.is_some_and(|run_id| run_id != page.execution.run_id) {
+                    return Err(WorkflowError::Custom(format!(
+                        "the run of {} was replaced while its history was read",
+                        workflow_id
+                    )));
+                }
+                Ok(Some((page.events, (page.next_after, Some(page.execution.run_id)))))
+            }
+        });
+        pages.map_ok(|events| stream::iter(events.into_iter().map(Ok))).try_flatten()
+    }
+
+    /// Executions matching `filter`, most recently started first
+    ///
+    /// Storage keeps the latest run of each workflow ID, so earlier runs are not listed.
+    pub async fn list_workflows(&self, filter: &WorkflowFilter) -> Result<Vec<WorkflowExecutionInfo>, WorkflowError> {
+        let mut infos = self.storage.list_workflow_executions(filter).await?;
+        infos.sort_by_key(|info| std::cmp::Reverse(info.start_time));
+        Ok(infos)
+    }
+
+    /// History of the latest run of a workflow, encoded in `format`; `None` if the workflow ID is unknown
+    pub async fn export_history(
+        &self,
+        workflow_id: &WorkflowId,
+        format: HistoryFormat,
+    ) -> Result<Option<Vec<u8>>, WorkflowError> {
+        match self.load_workflow(workflow_id).await? {
+            Some((execution, history)) => HistoryExport::new(execution, history).encode(format).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Store an exported run as the latest run of its workflow ID
+    ///
+    /// A closed run is only stored. An open run is also handed to a worker on the task queue from
+    /// its start event, which replays the imported history and carries on from there. Fails with
+    /// [`WorkflowError::AlreadyStarted`] if an execution with the same workflow ID is still open.
+    pub async fn import_history(&self, export: HistoryExport) -> Result<WorkflowExecution, WorkflowError> {
+        let HistoryExport { execution, history } = export;
+        let Some(EventType::WorkflowExecutionStarted { workflow_type, task_queue, input }) =
+            history.events().first().map(|event| event.event_type.clone())
+        else {
+            return Err(WorkflowError::InvalidInput(format!(
+                "history of {} does not begin with a start event",
+                execution
+            )));
+        };
+        match self.storage.load_workflow_execution(&execution.workflow_id).await {
+            Ok((_, stored)) if !stored.is_closed() => {
+                return Err(WorkflowError::AlreadyStarted(execution.workflow_id.to_string()));
+            }
+            Ok(_) | Err(StorageError::NotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        self.storage.save_workflow_execution(&execution, &history).await?;
+        if !history.is_closed() {
+            let span = telemetry::start_workflow_span(&workflow_type, &execution);
+            self.task_queue
+                .push(
+                    &task_queue,
+                    Task::Workflow(WorkflowTask {
+                        execution: execution.clone(),
+                        workflow_type,
+                        task_queue: task_queue.clone(),
+                        input,
+                        priority: Priority::DEFAULT,
+                        trace_context: telemetry::context_of(&span),
+                    }),
+                )
+                .instrument(span)
+                .await?;
+        }
+        Ok(execution)
+    }
+
+    /// Tasks moved to the dead-letter queue, oldest first
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.list()
+    }
+
+    /// Push a dead-lettered task back on its queue, with its crash count reset
+    pub async fn retry_dead_letter(&self, id: &Uuid) -> Result<(), WorkflowError> {
+        let letter = self.dead_letters.take_for_retry(id)?;
+        self.task_queue.push(&letter.task_queue, letter.task).await?;
+        Ok(())
+    }
+
+    /// Drop a dead-lettered task and fail what waits on it
+    ///
+    /// A discarded workflow task fails its execution; a discarded activity task fails the
+    /// activity with [`ActivityError::DeadLettered`](super::ActivityError::DeadLettered), which is not retried.
+    pub async fn discard_dead_letter(&self, id: &Uuid, reason: &str) -> Result<(), WorkflowError> {
+        let letter = self.dead_letters.take_for_discard(id, reason)?;
+        let Task::Workflow(task) = letter.task else {
+            return Ok(());
+        };
+        let (execution, mut history) = self.storage.load_workflow_execution(&task.execution.workflow_id).await?;
+        if execution.run_id == task.execution.run_id && !history.is_closed() {
+            history.append(EventType::WorkflowExecutionFailed {
+                failure: format!("discarded from dead-letter queue: {}", reason),
+            });
+            self.storage.save_workflow_execution(&execution, &history).await?;
+        }
+        Ok(())
+    }
+
+    /// Create a schedule that starts a run of `W` at every time matching `options.cron_schedule`
+    ///
+    /// The schedule ID is `options.workflow_id` (generated if absent) and every run uses it as
+    /// its workflow ID. Runs are started by the workers polling `options.task_queue` that share
+    /// this client's schedules.
+    pub async fn schedule_workflow<W: Workflow>(
+        &self,
+        input: W::Input,
+        options: StartWorkflowOptions,
+    ) -> Result<ScheduleDescription, WorkflowError> {
+        let cron_schedule = options
+            .cron_schedule
+            .as_deref()
+            .ok_or_else(|| WorkflowError::InvalidInput("schedule_workflow requires a cron_schedule".to_string()))?;
+        self.schedules.create(
+            options.workflow_id.clone().unwrap_or_else(WorkflowId::generate),
+            cron_schedule,
+            W::name(),
+            &options.task_queue,
+            serde_json::to_value(input)?,
+            options.overlap_policy,
+        )
+    }
+
+    /// Stop a schedule from starting runs
+    pub fn pause_schedule(&self, schedule_id: &WorkflowId) -> Result<(), WorkflowError> {
+        self.schedules.pause(schedule_id)
+    }
+
+    /// Let a paused schedule start runs again
+    pub fn resume_schedule(&self, schedule_id: &WorkflowId) -> Result<(), WorkflowError> {
+        self.schedules.resume(schedule_id)
+    }
+
+    /// Delete a schedule
+    pub fn delete_schedule(&self, schedule_id: &WorkflowId) -> Result<(), WorkflowError> {
+        self.schedules.delete(schedule_id)
+    }
+
+    /// Describe a schedule
+    pub fn describe_schedule(&self, schedule_id: &WorkflowId) -> Option<ScheduleDescription> {
+        self.schedules.describe(schedule_id)
+    }
+
+    /// Send a signal to the latest run of a workflow
+    ///
+    /// The signal is queued on the task queue the execution was started on; the worker running
+    /// the execution records it in the history and hands it to [`WorkflowContext::wait_for_signal`].
+    ///
+    /// [`WorkflowContext::wait_for_signal`]: super::WorkflowContext::wait_for_signal
+    pub async fn signal_workflow<S: Signal>(&self, workflow_id: &WorkflowId, signal: S) -> Result<(), SignalError> {
+        let input = serde_json::to_value(signal).map_err(|e| SignalError::SerializationError(e.to_string()))?;
+        self.signal_workflow_value(workflow_id, S::name(), input).await
+    }
+
+    /// Request cancellation of the latest run of a workflow
+    ///
+    /// The request travels like a signal. The worker running the execution records it and cancels
+    /// the workflow's root cancellation scope; the workflow decides how to wind down.
+    pub async fn cancel_workflow(&self, workflow_id: &WorkflowId) -> Result<(), SignalError> {
+        self.signal_workflow_value(workflow_id, CANCEL_REQUEST_SIGNAL, serde_json::Value::Null)
+            .await
+    }
+
+    /// Terminate the latest run of a workflow
+    ///
+    /// Unlike cancellation the workflow gets no chance to clean up: the worker running the execution
+    /// drops the workflow code wherever it waits and closes the run as terminated with the reason.
+    pub async fn terminate_workflow(&self, workflow_id: &WorkflowId, reason: &str) -> Result<(), SignalError> {
+        self.signal_workflow_value(workflow_id, TERMINATE_SIGNAL, serde_json::Value::String(reason.to_string()))
+            .await
+    }
+
+    /// Query the running execution of a workflow through the handler it registered with
+    /// [`WorkflowContext::on_query`](super::WorkflowContext::on_query)
+    ///
+    /// Only executions running on the worker this client came from, or on the workers its query
+    /// transport reaches, can be queried.
+    pub async fn query_workflow<Q: Query>(&self, workflow_id: &WorkflowId) -> Result<Q::Result, QueryError> {
+        let result = self.query_workflow_value(workflow_id, Q::name()).await?;
+        serde_json::from_value(result).map_err(|e| QueryError::SerializationError(e.to_string()))
+    }
+
+    /// Untyped [`query_workflow`](Self::query_workflow)
+    pub(crate) async fn query_workflow_value(
+        &self,
+        workflow_id: &WorkflowId,
+        query_name: &str,
+    ) -> Result<serde_json::Value, QueryError> {
+        let history = match self.storage.load_workflow_execution(workflow_id).await {
+            Ok((_, history)) => history,
+            Err(StorageError::NotFound) => return Err(QueryError::WorkflowNotFound),
+            Err(e) => return Err(QueryError::Custom(e.to_string())),
+        };
+        if history.is_closed() {
+            return Err(QueryError::WorkflowNotRunning);
+        }
+        let queries = self
+            .queries
+            .as_ref()
+            .ok_or_else(|| QueryError::Custom("client is not attached to a worker".to_string()))?;
+        let result = queries.query(workflow_id, query_name).await;
+        let outcome = if result.is_ok() { "ok" } else { "error" };
+        metrics::counter!("temporal_queries_total", "query" => query_name.to_string(), "result" => outcome).increment(1);
+        result
+    }
+
+    /// Send an update to the running execution of a workflow and wait for the result of its handler
+    ///
+    /// The handler is the one registered with [`WorkflowContext::on_update`](super::WorkflowContext::on_update).
+    /// Fails with [`UpdateError::Rejected`] if its validator rejected the arguments. Like queries, only
+    /// executions running on the worker this client came from can be updated.
+    pub async fn update_workflow<U: Update>(
+        &self,
+        workflow_id: &WorkflowId,
+        args: U::Args,
+    ) -> Result<U::Result, UpdateError> {
+        let args = serde_json::to_value(args).map_err(|e| UpdateError::SerializationError(e.to_string()))?;
+        let result = self.update_workflow_value(workflow_id, U::name(), args).await?;
+        serde_json::from_value(result).map_err(|e| UpdateError::SerializationError(e.to_string()))
+    }
+
+    /// Untyped [`update_workflow`](Self::update_workflow)
+    pub(crate) async fn update_workflow_value(
+        &self,
+        workflow_id: &WorkflowId,
+        update_name: &str,
+        args: serde_json::Value,
+    ) -> Result<serde_json::Value, UpdateError> {
+        let history = match self.storage.load_workflow_execution(workflow_id).await {
+            Ok((_, history)) => history,
+            Err(StorageError::NotFound) => return Err(UpdateError::WorkflowNotFound),
+            Err(e) => return Err(UpdateError::Custom(e.to_string())),
+        };
+        if history.is_closed() {
+            return Err(UpdateError::WorkflowNotRunning);
+        }
+        let updates = self
+            .updates
+            .as_ref()
+            .ok_or_else(|| UpdateError::Custom("client is not attached to a worker".to_string()))?;
+        let args =
+            converter::encode(&*self.converter, args).map_err(|e| UpdateError::SerializationError(e.to_string()))?;
+        let result = updates.update(workflow_id, update_name, args).await;
+        let outcome = match &result {
+            Ok(_) => "completed",
+            Err(UpdateError::Rejected(_)) => "rejected",
+            Err(_) => "error",
+        };
+        metrics::counter!(super::metrics::WORKFLOW_UPDATES, "update" => update_name.to_string(), "outcome" => outcome)
+            .increment(1);
+        result
+    }
+
+    /// Untyped [`signal_workflow`](Self::signal_workflow), for callers that only know the signal name
+    pub(crate) async fn signal_workflow_value(
+        &self,
+        workflow_id: &WorkflowId,
+        signal_name: &str,
+        input: serde_json::Value,
+    ) -> Result<(), SignalError> {
+        let mut request = SignalWorkflowRequest {
+            workflow_id: workflow_id.clone(),
+            signal_name: signal_name.to_string(),
+            input,
+        };
+        for interceptor in &self.interceptors {
+            interceptor.before_signal(&mut request).await?;
+        }
+        let result = self.signal_requested(&request).await;
+        for interceptor in self.interceptors.iter().rev() {
+            interceptor.after_signal(&request, &result).await;
+        }
+        result
+    }
+
+    async fn signal_requested(&self, request: &SignalWorkflowRequest) -> Result<(), SignalError> {
+        let SignalWorkflowRequest { workflow_id, signal_name, input } = request;
+        let input = converter::encode(&*self.converter, input.clone()).map_err(|e| SignalError::SerializationError(e.to_string()))?;
+        let (execution, history) = match self.storage.load_workflow_execution(workflow_id).await {
+            Ok(found) => found,
+            Err(StorageError::NotFound) => return Err(SignalError::WorkflowNotFound),
+            Err(e) => return Err(SignalError::Custom(e.to_string())),
+        };
+        if history.is_closed() {
+            return Err(SignalError::WorkflowClosed(workflow_id.to_string()));
+        }
+        let task_queue = history
+            .task_queue()
+            .ok_or_else(|| SignalError::Custom(format!("history of {} has no start event", workflow_id)))?
+            .to_string();
+
+        let span = telemetry::signal_workflow_span(signal_name, &execution);
+        self.task_queue
+            .push(
+                &task_queue,
+                Task::Signal(SignalTask {
+                    execution,
+                    signal_name: signal_name.to_string(),
+                    input,
+                    trace_context: telemetry::context_of(&span),
+                }),
+            )
+            .instrument(span)
+            .await
+            .map_err(|e| SignalError::Custom(e.to_string()))?;
+        metrics::counter!("temporal_signals_sent_total", "signal" => signal_name.to_string()).increment(1);
+        Ok(())
     }
 }
 
@@ -37,6 +755,37 @@ pub struct StartWorkflowOptions {
     
     /// Workflow task timeout
     pub workflow_task_timeout: Option<std::time::Duration>,
+
+    /// Cron expression for [`WorkflowClient::schedule_workflow`], five fields or six with seconds first
+    pub cron_schedule: Option<String>,
+
+    /// What a schedule does when it fires while its previous run is still open
+    pub overlap_policy: ScheduleOverlapPolicy,
+
+    /// Search attributes set when the execution starts
+    pub search_attributes: SearchAttributes,
+
+    /// Metadata returned with the execution when it is described or listed, but not filtered on
+    pub memo: Memo,
+
+    /// Whether a closed execution with the same workflow ID may be followed by a new one
+    pub id_reuse_policy: WorkflowIdReusePolicy,
+
+    /// Key identifying the start request, such as an HTTP `Idempotency-Key` header
+    ///
+    /// Retries of a request with the same key return the execution the first one started instead
+    /// of starting another. Needs a client [with an idempotency store](WorkflowClient::with_idempotency_store).
+    pub idempotency_key: Option<String>,
+
+    /// Business key of the run, such as an order ID
+    ///
+    /// A start finding a run of the same workflow type and key that is still open, or closed within the
+    /// client's [dedup window](WorkflowClient::with_dedup_window), returns that run instead of starting
+    /// another. The key is kept as the [`DEDUP_KEY_ATTRIBUTE`] search attribute.
+    pub dedup_key: Option<String>,
+
+    /// Dequeue priority of the run's workflow tasks and, unless they set their own, its activities
+    pub priority: Priority,
 }
 
 impl Default for StartWorkflowOptions {
@@ -47,6 +796,42 @@ impl Default for StartWorkflowOptions {
             workflow_execution_timeout: None,
             workflow_run_timeout: None,
             workflow_task_timeout: Some(std::time::Duration::from_secs(10)),
+            cron_schedule: None,
+            overlap_policy: ScheduleOverlapPolicy::default(),
+            search_attributes: SearchAttributes::new(),
+            memo: Memo::new(),
+            id_reuse_policy: WorkflowIdReusePolicy::default(),
+            idempotency_key: None,
+            dedup_key: None,
+            priority: Priority::DEFAULT,
+        }
+    }
+}
+
+/// Whether a workflow ID may be started again once its previous execution has closed
+///
+/// An execution that is still open always rejects a start with its ID.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum WorkflowIdReusePolicy {
+    /// Start a new execution whatever the outcome of the previous one
+    #[default]
+    AllowDuplicate,
+    /// Start a new execution only if the previous one failed, was cancelled or timed out
+    AllowDuplicateFailedOnly,
+    /// Never start another execution with the same workflow ID
+    RejectDuplicate,
+}
+
+impl WorkflowIdReusePolicy {
+    /// Whether a new execution may follow the stored `history` of the workflow ID
+    fn allows(self, history: &EventHistory) -> bool {
+        if !history.is_closed() {
+            return false;
+        }
+        match self {
+            WorkflowIdReusePolicy::AllowDuplicate => true,
+            WorkflowIdReusePolicy::AllowDuplicateFailedOnly => matches!(history.outcome(), Some(Err(_))),
+            WorkflowIdReusePolicy::RejectDuplicate => false,
         }
     }
 }
@@ -54,31 +839,85 @@ impl Default for StartWorkflowOptions {
 /// Workflow handle
 pub struct WorkflowHandle<O> {
     execution: WorkflowExecution,
+    storage: Arc<dyn WorkflowStorage>,
+    converter: Arc<dyn DataConverter>,
     _phantom: std::marker::PhantomData<O>,
 }
 
 impl<O> WorkflowHandle<O> {
     /// Create a new workflow handle
-    pub fn new(execution: WorkflowExecution) -> Self {
+    pub fn new(execution: WorkflowExecution, storage: Arc<dyn WorkflowStorage>) -> Self {
         Self {
             execution,
+            storage,
+            converter: converter::default_converter(),
             _phantom: std::marker::PhantomData,
         }
     }
     
+    /// Decode the result with `converter`
+    pub(crate) fn with_converter(mut self, converter: Arc<dyn DataConverter>) -> Self {
+        self.converter = converter;
+        self
+    }
+
     /// Get workflow execution
     pub fn execution(&self) -> &WorkflowExecution {
         &self.execution
     }
+
+    /// Current event history of the latest run of the workflow
+    pub async fn history(&self) -> Result<EventHistory, WorkflowError> {
+        let (_, history) = self.storage.load_workflow_execution(&self.execution.workflow_id).await?;
+        Ok(history)
+    }
+
+    /// [`history`](Self::history) with its payloads decoded by the client's converter
+    ///
+    /// Compressed or encrypted inputs and results read as the values the workflow saw.
+    pub async fn decoded_history(&self) -> Result<EventHistory, WorkflowError> {
+        converter::decode_history(&*self.converter, self.history().await?)
+    }
+
+    /// Latest run of the workflow, which differs from [`execution`](Self::execution) once it continued as new
+    pub async fn latest_run(&self) -> Result<WorkflowExecution, WorkflowError> {
+        let (execution, _) = self.storage.load_workflow_execution(&self.execution.workflow_id).await?;
+        Ok(execution)
+    }
+}
+
+impl<O: DeserializeOwned> WorkflowHandle<O> {
+    /// Wait for the execution to close and return its result
+    ///
+    /// Follows runs that continued as new and returns the outcome of the last run of the chain.
+    pub async fn result(&self) -> Result<O, WorkflowError> {
+        loop {
+            match self.try_result().await? {
+                Some(result) => return Ok(result),
+                None => tokio::time::sleep(RESULT_POLL_INTERVAL).await,
+            }
+        }
+    }
+
+    /// Result of the execution if it has closed, without waiting
+    pub async fn try_result(&self) -> Result<Option<O>, WorkflowError> {
+        match self.history().await?.outcome() {
+            Some(Ok(result)) => Ok(Some(serde_json::from_value(converter::decode(&*self.converter, result)?)?)),
+            Some(Err(failure)) => Err(WorkflowError::Custom(failure)),
+            None => Ok(None),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::temporal::storage::InMemoryStorage;
+    use crate::temporal::task_queue::{InMemoryTaskQueue, TaskKind};
 
     #[test]
     fn test_client_creation() {
-        let _client = WorkflowClient::new();
+        let _client = WorkflowClient::new(Arc::new(InMemoryTaskQueue::new()), Arc::new(InMemoryStorage::new()));
     }
 
     #[test]
@@ -86,5 +925,257 @@ mod tests {
         let options = StartWorkflowOptions::default();
         assert_eq!(options.task_queue, "default");
     }
-}
 
+    struct Echo;
+
+    impl Workflow for Echo {
+        type Input = String;
+        type Output = String;
+
+        fn name() -> &'static str {
+            "echo"
+        }
+
+        async fn execute(_ctx: super::super::WorkflowContext, input: String) -> Result<String, WorkflowError> {
+            Ok(input)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_start_enqueues_task_and_rejects_duplicates() {
+        let queue = Arc::new(InMemoryTaskQueue::new());
+        let client = WorkflowClient::new(queue.clone(), Arc::new(InMemoryStorage::new()));
+        let options = StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new("wf-1")),
+            ..Default::default()
+        };
+
+        let handle = client.start_workflow::<Echo>("hi".to_string(), options.clone()).await.unwrap();
+        assert_eq!(handle.history().await.unwrap().len(), 1);
+        assert_eq!(queue.len("default", TaskKind::Workflow).await.unwrap(), 1);
+
+        let duplicate = client.start_workflow::<Echo>("again".to_string(), options).await;
+        assert!(matches!(duplicate, Err(WorkflowError::AlreadyStarted(_))));
+    }
+
+    async fn close(storage: &InMemoryStorage, id: &str, outcome: EventType) {
+        let (execution, mut history) = storage.load_workflow_execution(&WorkflowId::new(id)).await.unwrap();
+        history.append(outcome);
+        storage.save_workflow_execution(&execution, &history).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_id_reuse_policies() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let client = WorkflowClient::new(Arc::new(InMemoryTaskQueue::new()), storage.clone());
+        let options = |id: &str, id_reuse_policy| StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new(id)),
+            id_reuse_policy,
+            ..Default::default()
+        };
+        let completed = || EventType::WorkflowExecutionCompleted { result: "done".into() };
+        let failed = || EventType::WorkflowExecutionFailed { failure: "boom".to_string() };
+
+        for (policy, after_completed, after_failed) in [
+            (WorkflowIdReusePolicy::AllowDuplicate, true, true),
+            (WorkflowIdReusePolicy::AllowDuplicateFailedOnly, false, true),
+            (WorkflowIdReusePolicy::RejectDuplicate, false, false),
+        ] {
+            for (id, outcome, allowed) in [("completed", completed(), after_completed), ("failed", failed(), after_failed)] {
+                let id = format!("{}-{:?}", id, policy);
+                let first = client.start_workflow::<Echo>("hi".to_string(), options(&id, policy)).await.unwrap();
+                // Open runs are never reused
+                let open = client.start_workflow::<Echo>("hi".to_string(), options(&id, policy)).await;
+                assert!(matches!(open, Err(WorkflowError::AlreadyStarted(_))));
+
+                close(&storage, &id, outcome).await;
+                match client.start_workflow::<Echo>("hi".to_string(), options(&id, policy)).await {
+                    Ok(second) => {
+                        assert!(allowed, "{} reused", id);
+                        assert_ne!(second.execution().run_id, first.execution().run_id);
+                    }
+                    Err(e) => assert!(!allowed && matches!(e, WorkflowError::AlreadyStarted(_)), "{}: {}", id, e),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dedup_keys_return_recent_runs() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let client = WorkflowClient::new(Arc::new(InMemoryTaskQueue::new()), storage.clone());
+        let options = |key: &str| StartWorkflowOptions {
+            dedup_key: Some(key.to_string()),
+            ..Default::default()
+        };
+
+        let first = client.start_workflow::<Echo>("hi".to_string(), options("order-1")).await.unwrap();
+        let open = client.start_workflow::<Echo>("hi".to_string(), options("order-1")).await.unwrap();
+        assert_eq!(open.execution(), first.execution());
+        let keyed = WorkflowFilter::new().attribute(DEDUP_KEY_ATTRIBUTE, Comparison::Eq, "order-1");
+        assert_eq!(client.list_workflows(&keyed).await.unwrap().len(), 1);
+
+        let other = client.start_workflow::<Echo>("hi".to_string(), options("order-2")).await.unwrap();
+        assert_ne!(other.execution().workflow_id, first.execution().workflow_id);
+
+        let id = first.execution().workflow_id.to_string();
+        close(&storage, &id, EventType::WorkflowExecutionCompleted { result: "done".into() }).await;
+        let closed = client.start_workflow::<Echo>("hi".to_string(), options("order-1")).await.unwrap();
+        assert_eq!(closed.execution(), first.execution());
+
+        // Outside the window the key starts a new run
+        let expired = client.clone().with_dedup_window(Duration::ZERO);
+        let second = expired.start_workflow::<Echo>("hi".to_string(), options("order-1")).await.unwrap();
+        assert_ne!(second.execution().workflow_id, first.execution().workflow_id);
+        let again = expired.start_workflow::<Echo>("hi".to_string(), options("order-1")).await.unwrap();
+        assert_eq!(again.execution(), second.execution());
+    }
+
+    #[cfg(feature = "persistence")]
+    #[tokio::test]
+    async fn test_idempotency_keys_start_once() {
+        use crate::persistence::InMemoryAdapter;
+
+        let queue = Arc::new(InMemoryTaskQueue::new());
+        let storage = Arc::new(InMemoryStorage::new());
+        let plain = WorkflowClient::new(queue.clone(), storage.clone());
+        let client = plain
+            .clone()
+            .with_idempotency_store(Arc::new(InMemoryAdapter::new()), Duration::from_secs(3600));
+        let options = StartWorkflowOptions {
+            idempotency_key: Some("order-7".to_string()),
+            ..Default::default()
+        };
+
+        let first = client.start_workflow::<Echo>("hi".to_string(), options.clone()).await.unwrap();
+        let retry = client.start_workflow::<Echo>("hi".to_string(), options.clone()).await.unwrap();
+        assert_eq!(retry.execution(), first.execution());
+        assert_eq!(queue.len("default", TaskKind::Workflow).await.unwrap(), 1);
+
+        let other = StartWorkflowOptions {
+            idempotency_key: Some("order-8".to_string()),
+            ..Default::default()
+        };
+        let second = client.start_workflow::<Echo>("hi".to_string(), other).await.unwrap();
+        assert_ne!(second.execution(), first.execution());
+
+        // A key whose start failed is free for the retry
+        let workflow_id = first.execution().workflow_id.clone();
+        let reusing = StartWorkflowOptions {
+            workflow_id: Some(workflow_id.clone()),
+            idempotency_key: Some("order-9".to_string()),
+            ..Default::default()
+        };
+        assert!(client.start_workflow::<Echo>("hi".to_string(), reusing.clone()).await.is_err());
+        close(&storage, workflow_id.as_str(), EventType::WorkflowExecutionCompleted { result: "done".into() }).await;
+        let reused = client.start_workflow::<Echo>("hi".to_string(), reusing).await.unwrap();
+        assert_eq!(reused.execution().workflow_id, workflow_id);
+
+        assert!(matches!(
+            plain.start_workflow::<Echo>("hi".to_string(), options).await,
+            Err(WorkflowError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_imported_open_run_is_resumed_on_its_queue() {
+        let source = WorkflowClient::new(Arc::new(InMemoryTaskQueue::new()), Arc::new(InMemoryStorage::new()));
+        let options = StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new("wf-1")),
+            task_queue: "orders".to_string(),
+            ..Default::default()
+        };
+        let handle = source.start_workflow::<Echo>("hi".to_string(), options).await.unwrap();
+        let exported = source
+            .export_history(&WorkflowId::new("wf-1"), HistoryFormat::Protobuf)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let queue = Arc::new(InMemoryTaskQueue::new());
+        let target = WorkflowClient::new(queue.clone(), Arc::new(InMemoryStorage::new()));
+        let export = HistoryExport::decode(HistoryFormat::Protobuf, &exported).unwrap();
+        assert_eq!(&target.import_history(export.clone()).await.unwrap(), handle.execution());
+        assert_eq!(queue.len("orders", TaskKind::Workflow).await.unwrap(), 1);
+        assert!(matches!(target.import_history(export).await, Err(WorkflowError::AlreadyStarted(_))));
+        assert!(target.export_history(&WorkflowId::new("wf-2"), HistoryFormat::Json).await.unwrap().is_none());
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Ping;
+
+    impl Signal for Ping {
+        fn name() -> &'static str {
+            "ping"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history_streamed_in_pages() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let client = WorkflowClient::new(Arc::new(InMemoryTaskQueue::new()), storage.clone());
+        let options = StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new("long")),
+            ..Default::default()
+        };
+        client.start_workflow::<Echo>("hi".to_string(), options).await.unwrap();
+        let (execution, mut history) = storage.load_workflow_execution(&WorkflowId::new("long")).await.unwrap();
+        for seq in 0..9 {
+            history.append(EventType::SideEffectRecorded { seq, value: seq.into() });
+        }
+        storage.save_workflow_execution(&execution, &history).await.unwrap();
+
+        let events: Vec<WorkflowEvent> = client.stream_history(&WorkflowId::new("long"), 4).try_collect().await.unwrap();
+        assert_eq!(events.iter().map(|e| e.event_id.0).collect::<Vec<_>>(), (1..=10).collect::<Vec<_>>());
+        let page = client.load_events(&WorkflowId::new("long"), EventId(8), 4).await.unwrap().unwrap();
+        assert_eq!((page.events.len(), page.next_after), (2, None));
+        assert!(client.load_events(&WorkflowId::new("missing"), EventId::zero(), 4).await.unwrap().is_none());
+        let missing: Result<Vec<_>, _> = client.stream_history(&WorkflowId::new("missing"), 4).try_collect().await;
+        assert!(missing.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_signal_routes_to_start_queue() {
+        let queue = Arc::new(InMemoryTaskQueue::new());
+        let client = WorkflowClient::new(queue.clone(), Arc::new(InMemoryStorage::new()));
+        let missing = client.signal_workflow(&WorkflowId::new("wf-1"), Ping).await;
+        assert!(matches!(missing, Err(SignalError::WorkflowNotFound)));
+
+        let options = StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new("wf-1")),
+            task_queue: "orders".to_string(),
+            ..Default::default()
+        };
+        client.start_workflow::<Echo>("hi".to_string(), options).await.unwrap();
+        client.signal_workflow(&WorkflowId::new("wf-1"), Ping).await.unwrap();
+
+        let task = queue.poll("orders", TaskKind::Signal, Duration::from_millis(10)).await.unwrap();
+        assert!(matches!(task, Some(Task::Signal(t)) if t.signal_name == "ping"));
+    }
+
+    #[tokio::test]
+    async fn test_list_workflows_filters_on_start_attributes() {
+        use crate::temporal::search::{Comparison, WorkflowExecutionStatus};
+
+        let client = WorkflowClient::new(Arc::new(InMemoryTaskQueue::new()), Arc::new(InMemoryStorage::new()));
+        for (id, amount) in [("order-1", 50), ("order-2", 500)] {
+            let options = StartWorkflowOptions {
+                workflow_id: Some(WorkflowId::new(id)),
+                search_attributes: SearchAttributes::from([("Amount".to_string(), amount.into())]),
+                memo: Memo::new().with("correlation_id", format!("req-{}", amount)).unwrap(),
+                ..Default::default()
+            };
+            client.start_workflow::<Echo>("hi".to_string(), options).await.unwrap();
+        }
+
+        let running = WorkflowFilter::new().workflow_type("echo").status(WorkflowExecutionStatus::Running);
+        assert_eq!(client.list_workflows(&running).await.unwrap().len(), 2);
+        let large = client
+            .list_workflows(&running.attribute("Amount", Comparison::Gt, 100))
+            .await
+            .unwrap();
+        assert_eq!(large.len(), 1);
+        assert_eq!(large[0].execution.workflow_id, WorkflowId::new("order-2"));
+        assert_eq!(large[0].memo.get::<String>("correlation_id").unwrap().as_deref(), Some("req-500"));
+    }
+}
@@ -0,0 +1,95 @@
+//! Virtual clock for workflow timers
+//!
+//! Workflow timers (`WorkflowContext::sleep`, signal and condition timeouts, retry backoffs, local
+//! activity timeouts) run on the Tokio clock unless the worker was given a [`VirtualClock`]. A
+//! virtual clock only moves when told to, firing the timers that come due in the order of their
+//! deadlines, which lets tests skip over days of workflow time. Activities keep running in real time.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+#[derive(Default)]
+struct State {
+    now: Duration,
+    /// Pending timers by deadline and registration order
+    timers: BTreeMap<(Duration, u64), oneshot::Sender<()>>,
+    registered: u64,
+}
+
+/// Clock whose time only advances on request
+#[derive(Default)]
+pub struct VirtualClock {
+    state: Mutex<State>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time passed since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().now
+    }
+
+    /// Deadline of the earliest timer still awaited
+    pub fn next_deadline(&self) -> Option<Duration> {
+        let mut state = self.state.lock();
+        state.timers.retain(|_, fired| !fired.is_closed());
+        state.timers.keys().next().map(|(deadline, _)| *deadline)
+    }
+
+    /// Move the clock to `deadline` (if later than now) and fire every timer due by then
+    pub fn advance_to(&self, deadline: Duration) {
+        let mut state = self.state.lock();
+        let now = state.now.max(deadline);
+        state.now = now;
+        let pending = state.timers.split_off(&(now, u64::MAX));
+        for (_, fired) in std::mem::replace(&mut state.timers, pending) {
+            let _ = fired.send(());
+        }
+    }
+
+    /// Wait until the clock has advanced by `duration`
+    pub(crate) async fn sleep(&self, duration: Duration) {
+        let fired = {
+            let mut state = self.state.lock();
+            if duration.is_zero() {
+                return;
+            }
+            let (sender, receiver) = oneshot::channel();
+            state.registered += 1;
+            let key = (state.now + duration, state.registered);
+            state.timers.insert(key, sender);
+            receiver
+        };
+        let _ = fired.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    #[test]
+    fn test_timers_fire_when_their_deadline_passes() {
+        let clock = VirtualClock::new();
+        let mut long = clock.sleep(Duration::from_secs(60)).boxed();
+        let mut short = clock.sleep(Duration::from_secs(10)).boxed();
+        assert!((&mut long).now_or_never().is_none());
+        assert!((&mut short).now_or_never().is_none());
+        assert_eq!(clock.next_deadline(), Some(Duration::from_secs(10)));
+
+        clock.advance_to(Duration::from_secs(10));
+        assert!((&mut short).now_or_never().is_some());
+        assert!((&mut long).now_or_never().is_none());
+        assert_eq!(clock.elapsed(), Duration::from_secs(10));
+
+        drop(long);
+        assert_eq!(clock.next_deadline(), None);
+    }
+}
@@ -0,0 +1,262 @@
+//! Payload codecs
+//!
+//! A [`PayloadCodec`] transforms a payload after its [`DataConverter`] encoded it and undoes the
+//! transformation before decoding, recording what it did in the payload metadata. Codecs are
+//! attached to a converter with a [`CodecConverter`]; they apply in the order they were added and
+//! are undone in reverse.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use workflow::temporal::{CodecConverter, CompressionCodec, JsonConverter};
+//!
+//! // Order documents above 4 KiB are stored zstd-compressed, smaller payloads stay inline JSON
+//! let converter = CodecConverter::new(Arc::new(JsonConverter)).codec(Arc::new(CompressionCodec::zstd(4096)));
+//! # let _ = converter;
+//! ```
+
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use flate2::Compression as GzipLevel;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde_json::Value;
+
+use super::converter::{DataConverter, Payload};
+use super::WorkflowError;
+
+/// Transforms encoded payloads
+pub trait PayloadCodec: Send + Sync {
+    fn encode(&self, payload: Payload) -> Result<Payload, WorkflowError>;
+
+    /// Undo [`encode`](Self::encode); payloads this codec did not transform pass through
+    fn decode(&self, payload: Payload) -> Result<Payload, WorkflowError>;
+}
+
+/// A converter with codecs applied to its payloads
+pub struct CodecConverter {
+    converter: Arc<dyn DataConverter>,
+    codecs: Vec<Arc<dyn PayloadCodec>>,
+}
+
+impl CodecConverter {
+    pub fn new(converter: Arc<dyn DataConverter>) -> Self {
+        Self {
+            converter,
+            codecs: Vec::new(),
+        }
+    }
+
+    /// Add a codec applied after the ones added before
+    pub fn codec(mut self, codec: Arc<dyn PayloadCodec>) -> Self {
+        self.codecs.push(codec);
+        self
+    }
+}
+
+impl DataConverter for CodecConverter {
+    fn encoding(&self) -> &str {
+        self.converter.encoding()
+    }
+
+    fn encode_value(&self, value: &Value) -> Result<Vec<u8>, WorkflowError> {
+        self.converter.encode_value(value)
+    }
+
+    fn decode_value(&self, data: &[u8]) -> Result<Value, WorkflowError> {
+        self.converter.decode_value(data)
+    }
+
+    fn codecs(&self) -> &[Arc<dyn PayloadCodec>] {
+        &self.codecs
+    }
+}
+
+/// Compression algorithm of a [`CompressionCodec`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Zstandard at the given level, 1 (fastest) to 22
+    Zstd(i32),
+    /// Gzip at the given level, 0 to 9
+    Gzip(u32),
+}
+
+impl Compression {
+    fn name(self) -> &'static str {
+        match self {
+            Compression::Zstd(_) => "zstd",
+            Compression::Gzip(_) => "gzip",
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Compression::Zstd(level) => zstd::encode_all(data, level),
+            Compression::Gzip(level) => {
+                let mut encoder = GzEncoder::new(Vec::new(), GzipLevel::new(level));
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Compresses payloads of at least `threshold` bytes
+///
+/// The algorithm is recorded in the `compression` metadata entry. Payloads that do not shrink are
+/// stored as they are. Decoding handles both algorithms, whichever the codec compresses with.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionCodec {
+    compression: Compression,
+    threshold: usize,
+}
+
+impl CompressionCodec {
+    /// Metadata entry naming the compression algorithm
+    pub const METADATA: &'static str = "compression";
+
+    pub fn new(compression: Compression, threshold: usize) -> Self {
+        Self { compression, threshold }
+    }
+
+    /// Zstandard at its default level
+    pub fn zstd(threshold: usize) -> Self {
+        Self::new(Compression::Zstd(zstd::DEFAULT_COMPRESSION_LEVEL), threshold)
+    }
+
+    /// Gzip at its default level
+    pub fn gzip(threshold: usize) -> Self {
+        Self::new(Compression::Gzip(GzipLevel::default().level()), threshold)
+    }
+}
+
+fn compression_error(e: std::io::Error) -> WorkflowError {
+    WorkflowError::SerializationError(format!("payload compression: {}", e))
+}
+
+impl PayloadCodec for CompressionCodec {
+    fn encode(&self, mut payload: Payload) -> Result<Payload, WorkflowError> {
+        if payload.data.len() < self.threshold || payload.metadata.contains_key(Self::METADATA) {
+            return Ok(payload);
+        }
+        let compressed = self.compression.compress(&payload.data).map_err(compression_error)?;
+        if compressed.len() < payload.data.len() {
+            payload.data = compressed;
+            payload.metadata.insert(Self::METADATA.to_string(), self.compression.name().to_string());
+        }
+        Ok(payload)
+    }
+
+    fn decode(&self, mut payload: Payload) -> Result<Payload, WorkflowError> {
+        let Some(algorithm) = payload.metadata.remove(Self::METADATA) else {
+            return Ok(payload);
+        };
+        payload.data = match algorithm.as_str() {
+            "zstd" => zstd::decode_all(payload.data.as_slice()).map_err(compression_error)?,
+            "gzip" => {
+                let mut data = Vec::new();
+                GzDecoder::new(payload.data.as_slice()).read_to_end(&mut data).map_err(compression_error)?;
+                data
+            }
+            other => {
+                return Err(WorkflowError::SerializationError(format!("unknown payload compression {}", other)));
+            }
+        };
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::temporal::converter::{decode, encode};
+    use crate::temporal::event::EventType;
+    use crate::temporal::{
+        JsonConverter, MessagePackConverter, StartWorkflowOptions, WorkerConfig, Workflow, WorkflowContext,
+        WorkflowWorker,
+    };
+
+    fn order(lines: usize) -> Value {
+        let lines: Vec<_> = (0..lines).map(|line| json!({"sku": format!("SKU-{:05}", line), "quantity": 1})).collect();
+        json!({ "customer": "ada", "lines": lines })
+    }
+
+    #[test]
+    fn test_compresses_large_payloads_only() {
+        for codec in [CompressionCodec::zstd(1024), CompressionCodec::gzip(1024)] {
+            let converter = CodecConverter::new(Arc::new(JsonConverter)).codec(Arc::new(codec));
+            let small = order(2);
+            assert_eq!(encode(&converter, small.clone()).unwrap(), small);
+
+            let large = order(500);
+            let encoded = encode(&converter, large.clone()).unwrap();
+            let payload = Payload::from_value(&encoded).unwrap();
+            assert_eq!(payload.metadata["compression"], codec.compression.name());
+            assert!(encoded.to_string().len() * 5 < large.to_string().len(), "{}", encoded);
+            assert_eq!(decode(&converter, encoded.clone()).unwrap(), large);
+            // Reading compressed payloads needs a codec
+            assert!(decode(&JsonConverter, encoded).is_err());
+        }
+
+        // Compression stacks on other encodings and either algorithm decodes
+        let zstd = CodecConverter::new(Arc::new(MessagePackConverter)).codec(Arc::new(CompressionCodec::zstd(64)));
+        let gzip = CodecConverter::new(Arc::new(JsonConverter)).codec(Arc::new(CompressionCodec::gzip(64)));
+        let encoded = encode(&zstd, order(50)).unwrap();
+        assert_eq!(Payload::from_value(&encoded).unwrap().encoding, "binary/msgpack");
+        assert_eq!(decode(&gzip, encoded).unwrap(), order(50));
+    }
+
+    struct CountLines;
+
+    impl Workflow for CountLines {
+        type Input = Value;
+        type Output = Value;
+
+        fn name() -> &'static str {
+            "count_lines"
+        }
+
+        async fn execute(_ctx: WorkflowContext, order: Value) -> Result<Value, WorkflowError> {
+            Ok(json!({ "lines": order["lines"].as_array().map_or(0, Vec::len), "order": order }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_histories_store_compressed_documents() {
+        let converter: Arc<dyn DataConverter> =
+            Arc::new(CodecConverter::new(Arc::new(JsonConverter)).codec(Arc::new(CompressionCodec::zstd(1024))));
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                ..WorkerConfig::default()
+            })
+            .with_data_converter(converter.clone()),
+        );
+        worker.register_workflow::<CountLines>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let handle = worker
+            .client()
+            .start_workflow::<CountLines>(order(300), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(handle.result().await.unwrap()["lines"], 300);
+
+        let history = handle.history().await.unwrap();
+        for event in history.events() {
+            if let EventType::WorkflowExecutionStarted { input: payload, .. }
+            | EventType::WorkflowExecutionCompleted { result: payload } = &event.event_type
+            {
+                assert_eq!(Payload::from_value(payload).unwrap().metadata["compression"], "zstd");
+            }
+        }
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,372 @@
+//! Data converters for workflow and activity payloads
+//!
+//! A [`DataConverter`] turns the JSON values the engine passes around into bytes and back. It
+//! applies to workflow inputs and results, signal inputs and activity inputs and results, which
+//! are encoded by the client or the scheduling workflow and decoded by the code that consumes
+//! them. Local activities, side effects, queries and search attributes always stay JSON.
+//!
+//! The default [`JsonConverter`] leaves payloads inline, so histories stay readable and
+//! compatible. Other converters store a payload as a [`Payload`] envelope,
+//! `{"_payload": {"encoding": ..., "data": <base64>}}`, in histories and tasks. Envelopes of the
+//! built-in encodings decode whichever converter is configured, so replaying or inspecting an
+//! encoded history needs no configuration; a custom encoding decodes only with its converter.
+//!
+//! Clients and workers exchanging payloads must use converters that can decode each other's
+//! encodings. [`PayloadCodec`]s, attached with a [`CodecConverter`](super::codec::CodecConverter),
+//! further transform the encoded bytes and record what they did in the payload metadata.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::codec::PayloadCodec;
+use super::event::{EventHistory, EventType};
+use super::WorkflowError;
+
+/// Key of the envelope wrapping an encoded payload
+const ENVELOPE: &str = "_payload";
+
+/// Converts payloads between JSON values and bytes
+pub trait DataConverter: Send + Sync {
+    /// Name of the encoding, recorded with every payload, such as `binary/msgpack`
+    fn encoding(&self) -> &str;
+
+    fn encode_value(&self, value: &Value) -> Result<Vec<u8>, WorkflowError>;
+
+    fn decode_value(&self, data: &[u8]) -> Result<Value, WorkflowError>;
+
+    /// Codecs applied to the encoded bytes, in encoding order
+    fn codecs(&self) -> &[Arc<dyn PayloadCodec>] {
+        &[]
+    }
+}
+
+/// Plain JSON; payloads stay inline instead of being wrapped in an envelope
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonConverter;
+
+impl JsonConverter {
+    pub const ENCODING: &'static str = "json/plain";
+}
+
+impl DataConverter for JsonConverter {
+    fn encoding(&self) -> &str {
+        Self::ENCODING
+    }
+
+    fn encode_value(&self, value: &Value) -> Result<Vec<u8>, WorkflowError> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn decode_value(&self, data: &[u8]) -> Result<Value, WorkflowError> {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+/// MessagePack
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePackConverter;
+
+impl MessagePackConverter {
+    pub const ENCODING: &'static str = "binary/msgpack";
+}
+
+impl DataConverter for MessagePackConverter {
+    fn encoding(&self) -> &str {
+        Self::ENCODING
+    }
+
+    fn encode_value(&self, value: &Value) -> Result<Vec<u8>, WorkflowError> {
+        rmp_serde::to_vec(value).map_err(|e| WorkflowError::SerializationError(e.to_string()))
+    }
+
+    fn decode_value(&self, data: &[u8]) -> Result<Value, WorkflowError> {
+        rmp_serde::from_slice(data).map_err(|e| WorkflowError::SerializationError(e.to_string()))
+    }
+}
+
+/// Protobuf, as a `google.protobuf.Value`
+///
+/// Protobuf numbers are doubles: integers beyond ±2^53 lose precision, and integral numbers
+/// decode as integers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProtobufConverter;
+
+impl ProtobufConverter {
+    pub const ENCODING: &'static str = "binary/protobuf";
+}
+
+impl DataConverter for ProtobufConverter {
+    fn encoding(&self) -> &str {
+        Self::ENCODING
+    }
+
+    fn encode_value(&self, value: &Value) -> Result<Vec<u8>, WorkflowError> {
+        Ok(to_protobuf(value).encode_to_vec())
+    }
+
+    fn decode_value(&self, data: &[u8]) -> Result<Value, WorkflowError> {
+        let value = prost_types::Value::decode(data).map_err(|e| WorkflowError::SerializationError(e.to_string()))?;
+        Ok(from_protobuf(value))
+    }
+}
+
+fn to_protobuf(value: &Value) -> prost_types::Value {
+    use prost_types::value::Kind;
+    let kind = match value {
+        Value::Null => Kind::NullValue(0),
+        Value::Bool(flag) => Kind::BoolValue(*flag),
+        Value::Number(number) => Kind::NumberValue(number.as_f64().unwrap_or_default()),
+        Value::String(text) => Kind::StringValue(text.clone()),
+        Value::Array(items) => Kind::ListValue(prost_types::ListValue {
+            values: items.iter().map(to_protobuf).collect(),
+        }),
+        Value::Object(fields) => Kind::StructValue(prost_types::Struct {
+            fields: fields.iter().map(|(key, value)| (key.clone(), to_protobuf(value))).collect::<BTreeMap<_, _>>(),
+        }),
+    };
+    prost_types::Value { kind: Some(kind) }
+}
+
+fn from_protobuf(value: prost_types::Value) -> Value {
+    use prost_types::value::Kind;
+    /// Largest integer a double holds exactly
+    const EXACT: f64 = 9_007_199_254_740_992.0;
+    match value.kind {
+        None | Some(Kind::NullValue(_)) => Value::Null,
+        Some(Kind::BoolValue(flag)) => Value::Bool(flag),
+        Some(Kind::NumberValue(number)) if number.fract() == 0.0 && number.abs() <= EXACT => Value::from(number as i64),
+        Some(Kind::NumberValue(number)) => serde_json::Number::from_f64(number).map_or(Value::Null, Value::Number),
+        Some(Kind::StringValue(text)) => Value::String(text),
+        Some(Kind::ListValue(list)) => Value::Array(list.values.into_iter().map(from_protobuf).collect()),
+        Some(Kind::StructValue(fields)) => {
+            Value::Object(fields.fields.into_iter().map(|(key, value)| (key, from_protobuf(value))).collect())
+        }
+    }
+}
+
+/// Encoded payload with the name of its encoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payload {
+    pub encoding: String,
+    pub data: Vec<u8>,
+    /// Set by codecs, such as the compression applied to `data`
+    pub metadata: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    encoding: String,
+    data: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<String, String>,
+}
+
+impl Payload {
+    pub fn new(encoding: impl Into<String>, data: Vec<u8>) -> Self {
+        Self {
+            encoding: encoding.into(),
+            data,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// The payload's envelope, as stored in histories and tasks
+    pub fn to_value(&self) -> Value {
+        let envelope = Envelope {
+            encoding: self.encoding.clone(),
+            data: STANDARD.encode(&self.data),
+            metadata: self.metadata.clone(),
+        };
+        serde_json::json!({ ENVELOPE: envelope })
+    }
+
+    /// Payload of an envelope; `None` if `value` is not one
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let object = value.as_object().filter(|object| object.len() == 1)?;
+        let envelope = Envelope::deserialize(object.get(ENVELOPE)?).ok()?;
+        Some(Self {
+            encoding: envelope.encoding,
+            data: STANDARD.decode(envelope.data).ok()?,
+            metadata: envelope.metadata,
+        })
+    }
+}
+
+/// Converter used when none is configured
+pub(crate) fn default_converter() -> Arc<dyn DataConverter> {
+    Arc::new(JsonConverter)
+}
+
+/// Encode `value` with `converter` for a history or task
+pub(crate) fn encode(converter: &dyn DataConverter, value: Value) -> Result<Value, WorkflowError> {
+    let codecs = converter.codecs();
+    if converter.encoding() == JsonConverter::ENCODING && codecs.is_empty() {
+        return Ok(value);
+    }
+    let mut payload = Payload::new(converter.encoding(), converter.encode_value(&value)?);
+    for codec in codecs {
+        payload = codec.encode(payload)?;
+    }
+    // Codecs may leave small payloads alone
+    if payload.encoding == JsonConverter::ENCODING && payload.metadata.is_empty() {
+        return Ok(value);
+    }
+    Ok(payload.to_value())
+}
+
+/// Decode a value from a history or task; values that are not envelopes are inline JSON
+pub(crate) fn decode(converter: &dyn DataConverter, value: Value) -> Result<Value, WorkflowError> {
+    let Some(mut payload) = Payload::from_value(&value) else {
+        return Ok(value);
+    };
+    for codec in converter.codecs().iter().rev() {
+        payload = codec.decode(payload)?;
+    }
+    if !payload.metadata.is_empty() {
+        return Err(WorkflowError::SerializationError(format!(
+            "no codec handles payload metadata {:?}",
+            payload.metadata
+        )));
+    }
+    match payload.encoding.as_str() {
+        encoding if encoding == converter.encoding() => converter.decode_value(&payload.data),
+        JsonConverter::ENCODING => JsonConverter.decode_value(&payload.data),
+        MessagePackConverter::ENCODING => MessagePackConverter.decode_value(&payload.data),
+        ProtobufConverter::ENCODING => ProtobufConverter.decode_value(&payload.data),
+        encoding => Err(WorkflowError::SerializationError(format!("no converter for payload encoding {}", encoding))),
+    }
+}
+
+/// Decode the payloads recorded in `history`
+pub(crate) fn decode_history(converter: &dyn DataConverter, history: EventHistory) -> Result<EventHistory, WorkflowError> {
+    let mut decoded = EventHistory::new();
+    for mut event in history.events().iter().cloned() {
+        match &mut event.event_type {
+            EventType::WorkflowExecutionStarted { input, .. }
+            | EventType::WorkflowExecutionContinuedAsNew { input, .. }
+            | EventType::WorkflowExecutionSignaled { input, .. }
+            | EventType::SignalExternalWorkflow { input, .. }
+            | EventType::WorkflowExecutionUpdateAccepted { input, .. }
+            | EventType::ActivityTaskScheduled { input, .. } => *input = decode(converter, input.take())?,
+            EventType::WorkflowExecutionCompleted { result }
+            | EventType::ActivityTaskCompleted { result, .. }
+            | EventType::WorkflowExecutionUpdateCompleted { result, .. }
+            | EventType::LocalActivityMarker { result: Ok(result), .. }
+            | EventType::ActivityMemoMarker { result: Some(result), .. }
+            | EventType::SideEffectRecorded { value: result, .. } => *result = decode(converter, result.take())?,
+            _ => {}
+        }
+        decoded.add_event(event);
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::temporal::{
+        Activity, ActivityContext, ActivityError, ActivityOptions, StartWorkflowOptions, WorkerConfig, Workflow,
+        WorkflowContext, WorkflowWorker,
+    };
+
+    #[test]
+    fn test_codecs_round_trip() {
+        let value = json!({"order": 7, "price": 12.5, "tags": ["a", null, true], "nested": {"ok": false}});
+        let converters: [&dyn DataConverter; 3] = [&JsonConverter, &MessagePackConverter, &ProtobufConverter];
+        for converter in converters {
+            let bytes = converter.encode_value(&value).unwrap();
+            assert_eq!(converter.decode_value(&bytes).unwrap(), value, "{}", converter.encoding());
+        }
+        assert!(ProtobufConverter.decode_value(b"\xff\xff").is_err());
+
+        // Inline JSON for the default converter, envelopes decodable by any converter otherwise
+        assert_eq!(encode(&JsonConverter, value.clone()).unwrap(), value);
+        let encoded = encode(&MessagePackConverter, value.clone()).unwrap();
+        assert_eq!(encoded["_payload"]["encoding"], "binary/msgpack");
+        assert_eq!(decode(&JsonConverter, encoded).unwrap(), value);
+        let unknown = Payload::new("binary/custom", vec![1]);
+        assert!(decode(&JsonConverter, unknown.to_value()).is_err());
+    }
+
+    struct Total;
+
+    impl Activity for Total {
+        type Input = Vec<u32>;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "total"
+        }
+
+        async fn execute(_ctx: ActivityContext, prices: Vec<u32>) -> Result<u32, ActivityError> {
+            Ok(prices.iter().sum())
+        }
+    }
+
+    struct Invoice;
+
+    impl Workflow for Invoice {
+        type Input = Vec<u32>;
+        type Output = String;
+
+        fn name() -> &'static str {
+            "invoice"
+        }
+
+        async fn execute(ctx: WorkflowContext, prices: Vec<u32>) -> Result<String, WorkflowError> {
+            let total = ctx.execute_activity::<Total>(prices, ActivityOptions::default()).await?;
+            Ok(format!("total {}", total))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_worker_and_client_encode_payloads() {
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                ..WorkerConfig::default()
+            })
+            .with_data_converter(Arc::new(ProtobufConverter)),
+        );
+        worker.register_workflow::<Invoice>();
+        worker.register_activity::<Total>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        // The client encodes with MessagePack, which the worker decodes too
+        let client = worker.client().with_data_converter(Arc::new(MessagePackConverter));
+        let handle = client
+            .start_workflow::<Invoice>(vec![3, 4], StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(handle.result().await.unwrap(), "total 7");
+
+        let history = handle.history().await.unwrap();
+        let encodings: Vec<_> = history
+            .events()
+            .iter()
+            .filter_map(|event| match &event.event_type {
+                EventType::WorkflowExecutionStarted { input: payload, .. }
+                | EventType::ActivityTaskScheduled { input: payload, .. }
+                | EventType::ActivityTaskCompleted { result: payload, .. }
+                | EventType::WorkflowExecutionCompleted { result: payload } => Payload::from_value(payload),
+                _ => None,
+            })
+            .map(|payload| payload.encoding)
+            .collect();
+        assert_eq!(encodings, ["binary/msgpack", "binary/protobuf", "binary/protobuf", "binary/protobuf"]);
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
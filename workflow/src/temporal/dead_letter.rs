@@ -0,0 +1,201 @@
+//! Dead-letter queue for poisoned tasks
+//!
+//! A task whose attempts keep crashing the worker (panicking) is moved here once it reaches
+//! [`WorkerConfig::max_task_failures`](super::WorkerConfig::max_task_failures) instead of being
+//! retried forever. The execution waiting on it stays open until the dead letter is retried or
+//! discarded through the [`WorkflowClient`](super::WorkflowClient).
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::WorkflowError;
+use super::task_queue::Task;
+
+/// A task moved out of its queue after repeated crashes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub id: Uuid,
+    /// Queue the task is pushed back on when retried
+    pub task_queue: String,
+    pub task: Task,
+    /// Crashes counted before the task was dead-lettered
+    pub failures: u32,
+    pub last_error: String,
+    pub dead_lettered_at: DateTime<Utc>,
+}
+
+/// Called with the reason when a dead letter is discarded, to fail whoever waits on the task
+pub(crate) type DiscardHook = Box<dyn FnOnce(String) + Send + Sync>;
+
+struct Entry {
+    letter: DeadLetter,
+    on_discard: Option<DiscardHook>,
+}
+
+/// Identity of a task across attempts, used to count its crashes
+pub(crate) fn task_key(task: &Task) -> String {
+    match task {
+        Task::Workflow(t) => format!("workflow/{}", t.execution.run_id),
+        Task::Activity(t) => format!("activity/{}/{}", t.workflow_execution.run_id, t.activity_id),
+        Task::Signal(t) => format!("signal/{}/{}", t.execution.run_id, t.signal_name),
+    }
+}
+
+fn kind_label(task: &Task) -> &'static str {
+    match task {
+        Task::Workflow(_) => "workflow",
+        Task::Activity(_) => "activity",
+        Task::Signal(_) => "signal",
+    }
+}
+
+/// Dead letters and per-task crash counts, shared by a worker and the clients it hands out
+#[derive(Default)]
+pub struct DeadLetterQueue {
+    entries: Mutex<HashMap<Uuid, Entry>>,
+    failures: Mutex<HashMap<String, u32>>,
+}
+
+impl DeadLetterQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a crash of the task with `key`, returning its crashes so far
+    pub(crate) fn record_failure(&self, key: &str) -> u32 {
+        let mut failures = self.failures.lock();
+        let count = failures.entry(key.to_string()).or_default();
+        *count += 1;
+        *count
+    }
+
+    /// Forget the crashes of a task, e.g. once it succeeded
+    pub(crate) fn clear_failures(&self, key: &str) {
+        self.failures.lock().remove(key);
+    }
+
+    /// Move a task into the queue
+    pub(crate) fn add(&self, task_queue: &str, task: Task, last_error: String, on_discard: Option<DiscardHook>) -> Uuid {
+        let key = task_key(&task);
+        let failures = self.failures.lock().remove(&key).unwrap_or_default();
+        metrics::counter!("temporal_dead_letters_total", "kind" => kind_label(&task)).increment(1);
+        let letter = DeadLetter {
+            id: Uuid::new_v4(),
+            task_queue: task_queue.to_string(),
+            task,
+            failures,
+            last_error,
+            dead_lettered_at: Utc::now(),
+        };
+        let id = letter.id;
+        tracing::warn!(%id, %key, failures, "task moved to dead-letter queue");
+        let mut entries = self.entries.lock();
+        entries.insert(id, Entry { letter, on_discard });
+        metrics::gauge!("temporal_dead_letter_queue_size").set(entries.len() as f64);
+        id
+    }
+
+    /// Dead letters, oldest first
+    pub fn list(&self) -> Vec<DeadLetter> {
+        let mut letters: Vec<_> = self.entries.lock().values().map(|e| e.letter.clone()).collect();
+        letters.sort_by_key(|letter| letter.dead_lettered_at);
+        letters
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<DeadLetter> {
+        self.entries.lock().get(id).map(|e| e.letter.clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn remove(&self, id: &Uuid) -> Result<Entry, WorkflowError> {
+        let mut entries = self.entries.lock();
+        let entry = entries
+            .remove(id)
+            .ok_or_else(|| WorkflowError::Custom(format!("dead letter not found: {}", id)))?;
+        metrics::gauge!("temporal_dead_letter_queue_size").set(entries.len() as f64);
+        Ok(entry)
+    }
+
+    /// Remove a dead letter so its task can be pushed back on its queue with a fresh crash count
+    pub(crate) fn take_for_retry(&self, id: &Uuid) -> Result<DeadLetter, WorkflowError> {
+        let entry = self.remove(id)?;
+        self.clear_failures(&task_key(&entry.letter.task));
+        Ok(entry.letter)
+    }
+
+    /// Remove a dead letter for good, running its discard hook
+    pub(crate) fn take_for_discard(&self, id: &Uuid, reason: &str) -> Result<DeadLetter, WorkflowError> {
+        let entry = self.remove(id)?;
+        if let Some(on_discard) = entry.on_discard {
+            on_discard(reason.to_string());
+        }
+        Ok(entry.letter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::task_queue::WorkflowTask;
+    use crate::temporal::{WorkflowExecution, WorkflowId};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn task() -> Task {
+        Task::Workflow(WorkflowTask {
+            execution: WorkflowExecution::new(WorkflowId::new("poisoned")),
+            workflow_type: "poisoned".to_string(),
+            task_queue: "default".to_string(),
+            input: serde_json::json!(null),
+            priority: Default::default(),
+            trace_context: Default::default(),
+        })
+    }
+
+    #[test]
+    fn test_failures_are_counted_per_task_and_moved_with_it() {
+        let queue = DeadLetterQueue::new();
+        let task = task();
+        let key = task_key(&task);
+        assert_eq!(queue.record_failure(&key), 1);
+        assert_eq!(queue.record_failure(&key), 2);
+
+        let id = queue.add("default", task, "workflow panicked".to_string(), None);
+        let letter = queue.get(&id).unwrap();
+        assert_eq!(letter.failures, 2);
+        assert_eq!(queue.record_failure(&key), 1);
+
+        assert_eq!(queue.take_for_retry(&id).unwrap().id, id);
+        assert!(queue.is_empty());
+        assert_eq!(queue.record_failure(&key), 1);
+        assert!(queue.take_for_retry(&id).is_err());
+    }
+
+    #[test]
+    fn test_discard_runs_hook() {
+        let queue = DeadLetterQueue::new();
+        let discarded = Arc::new(AtomicBool::new(false));
+        let flag = discarded.clone();
+        let id = queue.add(
+            "default",
+            task(),
+            "boom".to_string(),
+            Some(Box::new(move |reason| flag.store(reason == "operator", Ordering::SeqCst))),
+        );
+        assert_eq!(queue.list().len(), 1);
+        queue.take_for_discard(&id, "operator").unwrap();
+        assert!(discarded.load(Ordering::SeqCst));
+        assert!(queue.is_empty());
+    }
+}
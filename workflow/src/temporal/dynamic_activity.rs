@@ -0,0 +1,176 @@
+//! Activities registered by name with JSON input and output
+//!
+//! Alongside the strongly typed [`Activity`](super::Activity) trait, a [`DynamicActivityRegistry`]
+//! holds activities known only by name at runtime, each taking and returning a `serde_json::Value`.
+//! Workers given the registry run them for activity tasks of that name, so declarative workflows
+//! can call them, and the HTTP API can invoke them directly. Each activity may validate its input
+//! before running and its output before returning it.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use parking_lot::RwLock;
+use serde_json::Value;
+
+use super::{ActivityContext, ActivityError, ActivityId, WorkflowExecution, WorkflowId};
+
+type Handler = Arc<dyn Fn(ActivityContext, Value) -> BoxFuture<'static, Result<Value, ActivityError>> + Send + Sync>;
+
+/// Check of an activity payload; the error explains what is wrong with it
+pub type PayloadValidator = Arc<dyn Fn(&Value) -> Result<(), String> + Send + Sync>;
+
+/// Activity implemented on JSON values
+#[derive(Clone)]
+pub struct DynamicActivity {
+    name: String,
+    handler: Handler,
+    input_validator: Option<PayloadValidator>,
+    output_validator: Option<PayloadValidator>,
+}
+
+impl DynamicActivity {
+    pub fn new<F, Fut>(name: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(ActivityContext, Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, ActivityError>> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            handler: Arc::new(move |ctx, input| handler(ctx, input).boxed()),
+            input_validator: None,
+            output_validator: None,
+        }
+    }
+
+    /// Reject inputs failing `validator` with [`ActivityError::ValidationFailed`] before running
+    pub fn validate_input(mut self, validator: impl Fn(&Value) -> Result<(), String> + Send + Sync + 'static) -> Self {
+        self.input_validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Fail attempts whose output fails `validator` with [`ActivityError::ValidationFailed`]
+    pub fn validate_output(mut self, validator: impl Fn(&Value) -> Result<(), String> + Send + Sync + 'static) -> Self {
+        self.output_validator = Some(Arc::new(validator));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run the input validator, if any
+    pub fn check_input(&self, input: &Value) -> Result<(), ActivityError> {
+        check(&self.input_validator, input, "input")
+    }
+
+    /// Validate the input, run the activity and validate its output
+    pub async fn execute(&self, ctx: ActivityContext, input: Value) -> Result<Value, ActivityError> {
+        self.check_input(&input)?;
+        let output = (self.handler)(ctx, input).await?;
+        check(&self.output_validator, &output, "output")?;
+        Ok(output)
+    }
+}
+
+fn check(validator: &Option<PayloadValidator>, payload: &Value, what: &str) -> Result<(), ActivityError> {
+    match validator {
+        Some(validator) => validator(payload).map_err(|reason| ActivityError::ValidationFailed(format!("{}: {}", what, reason))),
+        None => Ok(()),
+    }
+}
+
+impl std::fmt::Debug for DynamicActivity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DynamicActivity").field("name", &self.name).finish_non_exhaustive()
+    }
+}
+
+/// Dynamic activities by name
+#[derive(Debug, Default)]
+pub struct DynamicActivityRegistry {
+    activities: RwLock<BTreeMap<String, DynamicActivity>>,
+}
+
+impl DynamicActivityRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `activity`, replacing any activity of the same name
+    pub fn register(&self, activity: DynamicActivity) {
+        self.activities.write().insert(activity.name.clone(), activity);
+    }
+
+    /// Remove an activity; returns whether it was registered
+    pub fn unregister(&self, name: &str) -> bool {
+        self.activities.write().remove(name).is_some()
+    }
+
+    pub fn get(&self, name: &str) -> Option<DynamicActivity> {
+        self.activities.read().get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.activities.read().contains_key(name)
+    }
+
+    /// Registered names in order
+    pub fn names(&self) -> Vec<String> {
+        self.activities.read().keys().cloned().collect()
+    }
+
+    /// Run an activity once, outside any workflow
+    pub async fn invoke(&self, name: &str, input: Value) -> Result<Value, ActivityError> {
+        let activity = self
+            .get(name)
+            .ok_or_else(|| ActivityError::ExecutionFailed(format!("activity type not registered: {}", name)))?;
+        let activity_id = ActivityId::new(format!("{}-{}", name, uuid::Uuid::new_v4()));
+        let ctx = ActivityContext::new(activity_id, WorkflowExecution::new(WorkflowId::generate()));
+        activity.execute(ctx, input).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resize() -> DynamicActivity {
+        DynamicActivity::new("resize", |_ctx, input: Value| async move {
+            let width = input["width"].as_u64().unwrap_or_default();
+            Ok(serde_json::json!({"width": width / 2}))
+        })
+        .validate_input(|input| match input["width"].as_u64() {
+            Some(_) => Ok(()),
+            None => Err("width must be a number".to_string()),
+        })
+        .validate_output(|output| match output["width"].as_u64() {
+            Some(0) => Err("image collapsed".to_string()),
+            _ => Ok(()),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_invoke_validates_payloads() {
+        let registry = DynamicActivityRegistry::new();
+        registry.register(resize());
+        assert_eq!(registry.names(), ["resize"]);
+
+        let output = registry.invoke("resize", serde_json::json!({"width": 640})).await.unwrap();
+        assert_eq!(output, serde_json::json!({"width": 320}));
+        assert!(matches!(
+            registry.invoke("resize", serde_json::json!({"width": "wide"})).await,
+            Err(ActivityError::ValidationFailed(reason)) if reason == "input: width must be a number"
+        ));
+        assert!(matches!(
+            registry.invoke("resize", serde_json::json!({"width": 1})).await,
+            Err(ActivityError::ValidationFailed(reason)) if reason == "output: image collapsed"
+        ));
+        assert!(matches!(registry.invoke("crop", Value::Null).await, Err(ActivityError::ExecutionFailed(_))));
+
+        assert!(registry.unregister("resize"));
+        assert!(!registry.contains("resize"));
+    }
+}
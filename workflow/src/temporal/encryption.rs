@@ -0,0 +1,492 @@
+//! Payload encryption at rest
+//!
+//! [`EncryptionCodec`] encrypts payloads with AES-256-GCM before they reach histories, task queues
+//! or storage, and tags them with the ID of the key used. Keys come from a [`KeyProvider`]:
+//! new payloads are encrypted with its current key while older payloads keep decrypting with the
+//! key named in their metadata, so keys can be rotated without re-encrypting histories.
+//!
+//! Attach the codec to the converters of the workers and of every client that reads results or
+//! histories, see [`WorkflowHandle::decoded_history`](super::WorkflowHandle::decoded_history).
+//! Put it after a compression codec: encrypted bytes do not compress.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use workflow::temporal::{CodecConverter, EncryptionCodec, EncryptionKey, JsonConverter, StaticKeyProvider};
+//!
+//! let keys = StaticKeyProvider::new("2025-10", EncryptionKey::from_bytes(&[7; 32]).unwrap());
+//! let converter = CodecConverter::new(Arc::new(JsonConverter)).codec(Arc::new(EncryptionCodec::new(Arc::new(keys))));
+//! # let _ = converter;
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use parking_lot::RwLock;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use super::codec::PayloadCodec;
+use super::converter::Payload;
+use super::WorkflowError;
+
+/// Value of the `encryption` metadata entry
+const ALGORITHM: &str = "AES-256-GCM";
+
+/// 256-bit AES key
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WorkflowError> {
+        let key = bytes
+            .try_into()
+            .map_err(|_| WorkflowError::InvalidInput(format!("encryption keys have 32 bytes, not {}", bytes.len())))?;
+        Ok(Self(key))
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, WorkflowError> {
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| WorkflowError::InvalidInput(format!("encryption key is not base64: {}", e)))?;
+        Self::from_bytes(&bytes)
+    }
+
+    fn cipher(&self) -> LessSafeKey {
+        LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &self.0).expect("AES-256 keys have 32 bytes"))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// Source of encryption keys
+pub trait KeyProvider: Send + Sync {
+    /// ID and key that new payloads are encrypted with
+    fn current_key(&self) -> Result<(String, EncryptionKey), WorkflowError>;
+
+    /// Key with the given ID, for payloads encrypted earlier
+    fn key(&self, key_id: &str) -> Result<EncryptionKey, WorkflowError>;
+}
+
+fn unknown_key(key_id: &str) -> WorkflowError {
+    WorkflowError::SerializationError(format!("unknown encryption key {}", key_id))
+}
+
+/// Keys held in memory: the current one and those it replaced
+#[derive(Debug, Clone)]
+pub struct StaticKeyProvider {
+    current: String,
+    keys: HashMap<String, EncryptionKey>,
+}
+
+impl StaticKeyProvider {
+    pub fn new(key_id: impl Into<String>, key: EncryptionKey) -> Self {
+        let current = key_id.into();
+        Self {
+            keys: HashMap::from([(current.clone(), key)]),
+            current,
+        }
+    }
+
+    /// Keep decrypting payloads encrypted with a retired key
+    pub fn with_previous(mut self, key_id: impl Into<String>, key: EncryptionKey) -> Self {
+        self.keys.insert(key_id.into(), key);
+        self
+    }
+}
+
+impl KeyProvider for StaticKeyProvider {
+    fn current_key(&self) -> Result<(String, EncryptionKey), WorkflowError> {
+        Ok((self.current.clone(), self.keys[&self.current].clone()))
+    }
+
+    fn key(&self, key_id: &str) -> Result<EncryptionKey, WorkflowError> {
+        self.keys.get(key_id).cloned().ok_or_else(|| unknown_key(key_id))
+    }
+}
+
+/// Keys read from environment variables on every use
+///
+/// With prefix `WORKFLOW_ENCRYPTION`, the current key ID is read from `WORKFLOW_ENCRYPTION_KEY_ID`
+/// and the base64 key with ID `2025-10` from `WORKFLOW_ENCRYPTION_KEY_2025_10`: key IDs are
+/// upper-cased and other characters than letters and digits become `_`.
+#[derive(Debug, Clone)]
+pub struct EnvKeyProvider {
+    prefix: String,
+    lookup: fn(&str) -> Option<String>,
+}
+
+impl EnvKeyProvider {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            lookup: |name| std::env::var(name).ok(),
+        }
+    }
+
+    fn variable(&self, name: &str) -> Result<String, WorkflowError> {
+        (self.lookup)(name).ok_or_else(|| WorkflowError::SerializationError(format!("environment variable {} is not set", name)))
+    }
+}
+
+impl KeyProvider for EnvKeyProvider {
+    fn current_key(&self) -> Result<(String, EncryptionKey), WorkflowError> {
+        let key_id = self.variable(&format!("{}_KEY_ID", self.prefix))?;
+        let key = self.key(&key_id)?;
+        Ok((key_id, key))
+    }
+
+    fn key(&self, key_id: &str) -> Result<EncryptionKey, WorkflowError> {
+        let suffix: String = key_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+            .collect();
+        EncryptionKey::from_base64(&self.variable(&format!("{}_KEY_{}", self.prefix, suffix))?)
+    }
+}
+
+type FetchKey = dyn Fn(&str) -> Result<EncryptionKey, WorkflowError> + Send + Sync;
+
+/// Keys fetched from an external key management service, cached after the first fetch
+pub struct KmsKeyProvider {
+    current: RwLock<String>,
+    fetch: Box<FetchKey>,
+    cache: RwLock<HashMap<String, EncryptionKey>>,
+}
+
+impl KmsKeyProvider {
+    /// `fetch` returns the key with the given ID, for example by decrypting a data key with the KMS
+    pub fn new(
+        current_key_id: impl Into<String>,
+        fetch: impl Fn(&str) -> Result<EncryptionKey, WorkflowError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            current: RwLock::new(current_key_id.into()),
+            fetch: Box::new(fetch),
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Encrypt new payloads with another key
+    pub fn rotate(&self, key_id: impl Into<String>) {
+        *self.current.write() = key_id.into();
+    }
+}
+
+impl KeyProvider for KmsKeyProvider {
+    fn current_key(&self) -> Result<(String, EncryptionKey), WorkflowError> {
+        let key_id = self.current.read().clone();
+        let key = self.key(&key_id)?;
+        Ok((key_id, key))
+    }
+
+    fn key(&self, key_id: &str) -> Result<EncryptionKey, WorkflowError> {
+        if let Some(key) = self.cache.read().get(key_id) {
+            return Ok(key.clone());
+        }
+        let key = (self.fetch)(key_id)?;
+        self.cache.write().insert(key_id.to_string(), key.clone());
+        Ok(key)
+    }
+}
+
+/// Encrypts payloads with AES-256-GCM
+///
+/// Sets the `encryption` and `key_id` metadata entries. The payload's encoding is authenticated
+/// with the data, and every payload gets a random nonce, stored in front of the ciphertext.
+pub struct EncryptionCodec {
+    keys: Arc<dyn KeyProvider>,
+    random: SystemRandom,
+}
+
+impl EncryptionCodec {
+    pub const METADATA: &'static str = "encryption";
+    pub const KEY_ID: &'static str = "key_id";
+
+    pub fn new(keys: Arc<dyn KeyProvider>) -> Self {
+        Self {
+            keys,
+            random: SystemRandom::new(),
+        }
+    }
+}
+
+fn encryption_error(message: &str) -> WorkflowError {
+    WorkflowError::SerializationError(format!("payload encryption: {}", message))
+}
+
+impl PayloadCodec for EncryptionCodec {
+    fn encode(&self, mut payload: Payload) -> Result<Payload, WorkflowError> {
+        let (key_id, key) = self.keys.current_key()?;
+        let mut nonce = [0; NONCE_LEN];
+        self.random.fill(&mut nonce).map_err(|_| encryption_error("no random nonce"))?;
+        let mut sealed = payload.data;
+        key.cipher()
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(payload.encoding.as_bytes()),
+                &mut sealed,
+            )
+            .map_err(|_| encryption_error("sealing failed"))?;
+        payload.data = [nonce.as_slice(), &sealed].concat();
+        payload.metadata.insert(Self::METADATA.to_string(), ALGORITHM.to_string());
+        payload.metadata.insert(Self::KEY_ID.to_string(), key_id);
+        Ok(payload)
+    }
+
+    fn decode(&self, mut payload: Payload) -> Result<Payload, WorkflowError> {
+        match payload.metadata.remove(Self::METADATA) {
+            None => return Ok(payload),
+            Some(algorithm) if algorithm == ALGORITHM => {}
+            Some(other) => return Err(encryption_error(&format!("unsupported algorithm {}", other))),
+        }
+        let key_id = payload
+            .metadata
+            .remove(Self::KEY_ID)
+            .ok_or_else(|| encryption_error("no key_id in payload metadata"))?;
+        let key = self.keys.key(&key_id)?;
+        if payload.data.len() < NONCE_LEN {
+            return Err(encryption_error("payload is too short"));
+        }
+        let mut sealed = payload.data.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&payload.data).map_err(|_| encryption_error("bad nonce"))?;
+        let opened = key
+            .cipher()
+            .open_in_place(nonce, Aad::from(payload.encoding.as_bytes()), &mut sealed)
+            .map_err(|_| encryption_error(&format!("payload does not decrypt with key {}", key_id)))?
+            .len();
+        sealed.truncate(opened);
+        payload.data = sealed;
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use serde_json::{json, Value};
+
+    use crate::temporal::converter::{decode, encode};
+    use crate::temporal::event::EventType;
+    use crate::temporal::{
+        Activity, ActivityContext, ActivityError, ActivityOptions, CodecConverter, CompressionCodec, DataConverter,
+        JsonConverter, StartWorkflowOptions, WorkerConfig, Workflow, WorkflowContext, WorkflowWorker,
+    };
+
+    fn key(byte: u8) -> EncryptionKey {
+        EncryptionKey::from_bytes(&[byte; 32]).unwrap()
+    }
+
+    fn encrypting(keys: impl KeyProvider + 'static) -> CodecConverter {
+        CodecConverter::new(Arc::new(JsonConverter)).codec(Arc::new(EncryptionCodec::new(Arc::new(keys))))
+    }
+
+    #[test]
+    fn test_encrypts_and_rotates_keys() {
+        let card = json!({"card": "4111 1111 1111 1111"});
+        let old = encrypting(StaticKeyProvider::new("k1", key(1)));
+        let encrypted = encode(&old, card.clone()).unwrap();
+        let payload = Payload::from_value(&encrypted).unwrap();
+        assert_eq!((payload.metadata["encryption"].as_str(), payload.metadata["key_id"].as_str()), ("AES-256-GCM", "k1"));
+        assert!(!encrypted.to_string().contains("4111"));
+        // Random nonces: the same payload never encrypts the same way twice
+        assert_ne!(encode(&old, card.clone()).unwrap(), encrypted);
+
+        let rotated = encrypting(StaticKeyProvider::new("k2", key(2)).with_previous("k1", key(1)));
+        assert_eq!(decode(&rotated, encrypted.clone()).unwrap(), card);
+        assert_eq!(Payload::from_value(&encode(&rotated, card.clone()).unwrap()).unwrap().metadata["key_id"], "k2");
+
+        let wrong = encrypting(StaticKeyProvider::new("k1", key(3)));
+        assert!(decode(&wrong, encrypted.clone()).is_err());
+        assert!(decode(&JsonConverter, encrypted.clone()).is_err());
+        let mut tampered = payload.clone();
+        tampered.encoding = "binary/msgpack".to_string();
+        assert!(decode(&old, tampered.to_value()).is_err());
+
+        // Compression first, then encryption
+        let both = CodecConverter::new(Arc::new(JsonConverter))
+            .codec(Arc::new(CompressionCodec::gzip(16)))
+            .codec(Arc::new(EncryptionCodec::new(Arc::new(StaticKeyProvider::new("k1", key(1))))));
+        let document = json!({"lines": vec!["same line"; 100]});
+        let encoded = encode(&both, document.clone()).unwrap();
+        assert_eq!(Payload::from_value(&encoded).unwrap().metadata.len(), 3);
+        assert_eq!(decode(&both, encoded).unwrap(), document);
+    }
+
+    #[test]
+    fn test_key_providers() {
+        let env = EnvKeyProvider {
+            prefix: "APP".to_string(),
+            lookup: |name| match name {
+                "APP_KEY_ID" => Some("2025-10".to_string()),
+                "APP_KEY_2025_10" => Some(STANDARD.encode([5; 32])),
+                "APP_KEY_SHORT" => Some(STANDARD.encode([5; 16])),
+                _ => None,
+            },
+        };
+        assert_eq!(env.current_key().unwrap(), ("2025-10".to_string(), key(5)));
+        assert!(env.key("short").is_err());
+        assert!(env.key("missing").is_err());
+
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let counter = fetches.clone();
+        let kms = KmsKeyProvider::new("a", move |key_id| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            match key_id {
+                "a" => Ok(key(1)),
+                "b" => Ok(key(2)),
+                other => Err(unknown_key(other)),
+            }
+        });
+        assert_eq!(kms.current_key().unwrap().1, key(1));
+        kms.rotate("b");
+        assert_eq!(kms.current_key().unwrap().0, "b");
+        assert_eq!(kms.key("a").unwrap(), key(1));
+        assert!(kms.key("c").is_err());
+        assert_eq!(fetches.load(Ordering::Relaxed), 3);
+    }
+
+    struct Echo;
+
+    impl Workflow for Echo {
+        type Input = Value;
+        type Output = Value;
+
+        fn name() -> &'static str {
+            "echo"
+        }
+
+        async fn execute(_ctx: WorkflowContext, input: Value) -> Result<Value, WorkflowError> {
+            Ok(input)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clients_decrypt_results_and_histories() {
+        let converter: Arc<dyn DataConverter> = Arc::new(encrypting(StaticKeyProvider::new("k1", key(1))));
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                ..WorkerConfig::default()
+            })
+            .with_data_converter(converter.clone()),
+        );
+        worker.register_workflow::<Echo>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let secret = json!({"ssn": "078-05-1120"});
+        let handle = worker
+            .client()
+            .start_workflow::<Echo>(secret.clone(), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(handle.result().await.unwrap(), secret);
+
+        let stored = handle.history().await.unwrap();
+        assert!(!serde_json::to_string(&stored).unwrap().contains("078-05-1120"));
+        let decoded = handle.decoded_history().await.unwrap();
+        assert!(matches!(
+            &decoded.events()[0].event_type,
+            EventType::WorkflowExecutionStarted { input, .. } if *input == secret
+        ));
+        assert_eq!(decoded.outcome(), Some(Ok(secret)));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    struct LookupCard;
+
+    impl Activity for LookupCard {
+        type Input = String;
+        type Output = String;
+
+        fn name() -> &'static str {
+            "lookup_card"
+        }
+
+        async fn execute(_ctx: ActivityContext, customer: String) -> Result<String, ActivityError> {
+            Ok(format!("{}:4111-1111-1111-1111", customer))
+        }
+    }
+
+    /// Keeps secrets in every kind of marker
+    struct Checkout;
+
+    impl Workflow for Checkout {
+        type Input = String;
+        type Output = Vec<String>;
+
+        fn name() -> &'static str {
+            "checkout"
+        }
+
+        async fn execute(ctx: WorkflowContext, customer: String) -> Result<Vec<String>, WorkflowError> {
+            let otp = ctx.side_effect(|| "otp-493817".to_string()).await?;
+            let local = ctx.execute_local_activity::<LookupCard>(customer.clone(), ActivityOptions::default()).await?;
+            let memoized = ActivityOptions {
+                memoize: Some(Duration::from_secs(3600)),
+                ..ActivityOptions::default()
+            };
+            let card = ctx.execute_activity::<LookupCard>(customer, memoized).await?;
+            Ok(vec![otp, local, card])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_marker_payloads_are_encrypted() {
+        let converter: Arc<dyn DataConverter> = Arc::new(encrypting(StaticKeyProvider::new("k1", key(1))));
+        let worker = WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        })
+        .with_data_converter(converter);
+        #[cfg(feature = "persistence")]
+        let worker = worker.with_activity_memo(Arc::new(crate::persistence::InMemoryAdapter::new()));
+        let worker = Arc::new(worker);
+        worker.register_workflow::<Checkout>();
+        worker.register_activity::<LookupCard>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        // The second run reuses the memoized card, so its memo marker carries the result
+        for _ in 0..2 {
+            let handle = worker
+                .client()
+                .start_workflow::<Checkout>("alice".to_string(), StartWorkflowOptions::default())
+                .await
+                .unwrap();
+            let expected = vec!["otp-493817", "alice:4111-1111-1111-1111", "alice:4111-1111-1111-1111"];
+            assert_eq!(handle.result().await.unwrap(), expected);
+
+            let stored = serde_json::to_string(&handle.history().await.unwrap()).unwrap();
+            assert!(!stored.contains("493817") && !stored.contains("4111"));
+            let decoded = handle.decoded_history().await.unwrap();
+            for event in decoded.events() {
+                match &event.event_type {
+                    EventType::SideEffectRecorded { value, .. } => assert_eq!(value, &json!("otp-493817")),
+                    EventType::LocalActivityMarker { result, .. } => {
+                        assert_eq!(result, &Ok(json!("alice:4111-1111-1111-1111")))
+                    }
+                    EventType::ActivityMemoMarker { result: Some(result), .. } => {
+                        assert_eq!(result, &json!("alice:4111-1111-1111-1111"))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
@@ -24,6 +24,28 @@ pub enum WorkflowError {
     /// Invalid input
     InvalidInput(String),
     
+    /// A workflow with the same ID is already running
+    AlreadyStarted(String),
+
+    /// A namespace reached its limit of concurrent runs
+    QuotaExceeded(String),
+
+    /// The run was terminated with this reason; see `WorkflowClient::terminate_workflow`
+    Terminated(String),
+
+    /// An activity session ended, or could not be created; see `WorkflowContext::create_session`
+    SessionFailed(String),
+
+    /// A workflow lock held by the run expired and was taken by another owner; see `WorkflowContext::acquire_lock`
+    LockLost(String),
+
+    /// A signal or cancellation request for another workflow could not be delivered; see
+    /// `WorkflowContext::get_external_workflow_handle`
+    ExternalWorkflowFailed(String),
+
+    /// The run asked to continue as a new run with this input; see `WorkflowContext::continue_as_new`
+    ContinuedAsNew(serde_json::Value),
+    
     /// Storage error
     StorageError(String),
     
@@ -43,6 +65,13 @@ impl fmt::Display for WorkflowError {
             WorkflowError::Cancelled => write!(f, "Workflow cancelled"),
             WorkflowError::SignalChannelClosed => write!(f, "Signal channel closed"),
             WorkflowError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            WorkflowError::AlreadyStarted(id) => write!(f, "Workflow already started: {}", id),
+            WorkflowError::QuotaExceeded(msg) => write!(f, "Quota exceeded: {}", msg),
+            WorkflowError::SessionFailed(msg) => write!(f, "Session failed: {}", msg),
+            WorkflowError::LockLost(name) => write!(f, "Lock lost: {}", name),
+            WorkflowError::ExternalWorkflowFailed(msg) => write!(f, "External workflow failed: {}", msg),
+            WorkflowError::Terminated(reason) => write!(f, "Workflow terminated: {}", reason),
+            WorkflowError::ContinuedAsNew(_) => write!(f, "Workflow continued as new"),
             WorkflowError::StorageError(msg) => write!(f, "Storage error: {}", msg),
             WorkflowError::SerializationError(msg) => write!(f, "Serialization error: {}", msg),
             WorkflowError::Custom(msg) => write!(f, "{}", msg),
@@ -53,7 +82,7 @@ impl fmt::Display for WorkflowError {
 impl Error for WorkflowError {}
 
 /// Activity error type
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ActivityError {
     /// Temporary failure (will be retried)
     TemporaryFailure(String),
@@ -75,6 +104,9 @@ pub enum ActivityError {
     
     /// Invalid input
     InvalidInput(String),
+
+    /// Task was discarded from the dead-letter queue (will not be retried)
+    DeadLettered(String),
     
     /// Custom error
     Custom(String),
@@ -90,6 +122,7 @@ impl fmt::Display for ActivityError {
             ActivityError::Timeout => write!(f, "Activity timeout"),
             ActivityError::HeartbeatFailed(msg) => write!(f, "Heartbeat failed: {}", msg),
             ActivityError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            ActivityError::DeadLettered(msg) => write!(f, "Discarded from dead-letter queue: {}", msg),
             ActivityError::Custom(msg) => write!(f, "{}", msg),
         }
     }
@@ -97,11 +130,42 @@ impl fmt::Display for ActivityError {
 
 impl Error for ActivityError {}
 
+impl ActivityError {
+    /// Variant name, matched against `RetryPolicy::non_retryable_error_types`
+    pub fn error_type(&self) -> &'static str {
+        match self {
+            ActivityError::TemporaryFailure(_) => "TemporaryFailure",
+            ActivityError::ValidationFailed(_) => "ValidationFailed",
+            ActivityError::ExecutionFailed(_) => "ExecutionFailed",
+            ActivityError::Cancelled => "Cancelled",
+            ActivityError::Timeout => "Timeout",
+            ActivityError::HeartbeatFailed(_) => "HeartbeatFailed",
+            ActivityError::InvalidInput(_) => "InvalidInput",
+            ActivityError::DeadLettered(_) => "DeadLettered",
+            ActivityError::Custom(_) => "Custom",
+        }
+    }
+
+    /// Whether the error may succeed on another attempt
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            ActivityError::ValidationFailed(_)
+                | ActivityError::InvalidInput(_)
+                | ActivityError::Cancelled
+                | ActivityError::DeadLettered(_)
+        )
+    }
+}
+
 /// Signal error type
 #[derive(Debug)]
 pub enum SignalError {
     /// Workflow not found
     WorkflowNotFound,
+
+    /// Workflow execution has already completed or failed
+    WorkflowClosed(String),
     
     /// Signal not registered
     SignalNotRegistered(String),
@@ -117,6 +181,7 @@ impl fmt::Display for SignalError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             SignalError::WorkflowNotFound => write!(f, "Workflow not found"),
+            SignalError::WorkflowClosed(id) => write!(f, "Workflow execution already closed: {}", id),
             SignalError::SignalNotRegistered(name) => write!(f, "Signal not registered: {}", name),
             SignalError::SerializationError(msg) => write!(f, "Serialization error: {}", msg),
             SignalError::Custom(msg) => write!(f, "{}", msg),
@@ -159,6 +224,68 @@ impl fmt::Display for QueryError {
 
 impl Error for QueryError {}
 
+/// Update error type
+#[derive(Debug)]
+pub enum UpdateError {
+    /// Workflow not found
+    WorkflowNotFound,
+
+    /// Workflow not running
+    WorkflowNotRunning,
+
+    /// No handler for the update is registered
+    UpdateNotRegistered(String),
+
+    /// The update's validator rejected it; nothing was recorded
+    Rejected(String),
+
+    /// Serialization error
+    SerializationError(String),
+
+    /// Custom error
+    Custom(String),
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateError::WorkflowNotFound => write!(f, "Workflow not found"),
+            UpdateError::WorkflowNotRunning => write!(f, "Workflow not running"),
+            UpdateError::UpdateNotRegistered(name) => write!(f, "Update not registered: {}", name),
+            UpdateError::Rejected(reason) => write!(f, "Update rejected: {}", reason),
+            UpdateError::SerializationError(msg) => write!(f, "Serialization error: {}", msg),
+            UpdateError::Custom(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl Error for UpdateError {}
+
+/// Human task error type
+#[derive(Debug)]
+pub enum HumanTaskError {
+    /// No open task has this ID
+    TaskNotFound(String),
+
+    /// Form data does not match the task's form schema
+    InvalidForm(String),
+
+    /// Custom error
+    Custom(String),
+}
+
+impl fmt::Display for HumanTaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HumanTaskError::TaskNotFound(id) => write!(f, "Human task not found: {}", id),
+            HumanTaskError::InvalidForm(msg) => write!(f, "Invalid form data: {}", msg),
+            HumanTaskError::Custom(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl Error for HumanTaskError {}
+
 /// Storage error type
 #[derive(Debug)]
 pub enum StorageError {
@@ -192,3 +319,15 @@ impl fmt::Display for StorageError {
 
 impl Error for StorageError {}
 
+impl From<StorageError> for WorkflowError {
+    fn from(error: StorageError) -> Self {
+        WorkflowError::StorageError(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for WorkflowError {
+    fn from(error: serde_json::Error) -> Self {
+        WorkflowError::SerializationError(error.to_string())
+    }
+}
+
@@ -2,7 +2,8 @@
 
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Utc};
-use super::{EventId, ActivityId};
+use super::{EventId, ActivityId, RunId};
+use super::search::{Memo, SearchAttributes};
 
 /// Event history
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +38,291 @@ impl EventHistory {
     pub fn is_empty(&self) -> bool {
         self.events.is_empty()
     }
+
+    /// Append an event, assigning the next event ID and the current time
+    pub fn append(&mut self, event_type: EventType) -> EventId {
+        let event_id = self
+            .events
+            .last()
+            .map(|e| e.event_id.next())
+            .unwrap_or_else(|| EventId::zero().next());
+        self.events.push(WorkflowEvent {
+            event_id,
+            timestamp: Utc::now(),
+            event_type,
+        });
+        event_id
+    }
+
+    /// Outcome of the execution if it has closed: the result on completion, the failure otherwise
+    pub fn outcome(&self) -> Option<Result<serde_json::Value, String>> {
+        self.events.iter().rev().find_map(|e| match &e.event_type {
+            EventType::WorkflowExecutionCompleted { result } => Some(Ok(result.clone())),
+            EventType::WorkflowExecutionFailed { failure } => Some(Err(failure.clone())),
+            EventType::WorkflowExecutionTerminated { reason } => Some(Err(format!("terminated: {}", reason))),
+            _ => None,
+        })
+    }
+
+    /// Whether the run was terminated, see [`WorkflowClient::terminate_workflow`](super::WorkflowClient::terminate_workflow)
+    pub fn is_terminated(&self) -> bool {
+        self.events
+            .iter()
+            .any(|e| matches!(e.event_type, EventType::WorkflowExecutionTerminated { .. }))
+    }
+
+    /// Task queue the execution was started on
+    pub fn task_queue(&self) -> Option<&str> {
+        self.events.iter().find_map(|e| match &e.event_type {
+            EventType::WorkflowExecutionStarted { task_queue, .. } => Some(task_queue.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Version recorded for a code change, if any
+    pub fn version_marker(&self, change_id: &str) -> Option<i32> {
+        self.events.iter().find_map(|e| match &e.event_type {
+            EventType::VersionMarker { change_id: id, version } if id == change_id => Some(*version),
+            _ => None,
+        })
+    }
+
+    /// Recorded result of the side effect with the given sequence number
+    pub fn side_effect(&self, seq: u64) -> Option<&serde_json::Value> {
+        self.events.iter().find_map(|e| match &e.event_type {
+            EventType::SideEffectRecorded { seq: recorded, value } if *recorded == seq => Some(value),
+            _ => None,
+        })
+    }
+
+    /// Recorded outcome of the local activity with the given sequence number
+    pub fn local_activity(&self, seq: u64) -> Option<&Result<serde_json::Value, String>> {
+        self.events.iter().find_map(|e| match &e.event_type {
+            EventType::LocalActivityMarker { seq: recorded, result, .. } if *recorded == seq => Some(result),
+            _ => None,
+        })
+    }
+
+    /// Recorded memo lookup of the memoized activity call with the given sequence number
+    pub fn activity_memo(&self, seq: u64) -> Option<&Option<serde_json::Value>> {
+        self.events.iter().find_map(|e| match &e.event_type {
+            EventType::ActivityMemoMarker { seq: recorded, result, .. } if *recorded == seq => Some(result),
+            _ => None,
+        })
+    }
+
+    /// Search attributes as of the latest upsert
+    pub fn search_attributes(&self) -> SearchAttributes {
+        let mut merged = SearchAttributes::new();
+        for event in &self.events {
+            if let EventType::UpsertSearchAttributes { attributes } = &event.event_type {
+                merged.extend(attributes.clone());
+            }
+        }
+        merged
+    }
+
+    /// Memo the execution was started with
+    pub fn memo(&self) -> Memo {
+        self.events
+            .iter()
+            .find_map(|e| match &e.event_type {
+                EventType::WorkflowMemoSet { memo } => Some(memo.clone()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Recorded outcome of an attempt of the activity with the given ID, counting attempts from 1: the encoded
+    /// result, or the failure recorded as `attempt N: <error>`
+    pub fn activity_attempt(&self, activity_id: &ActivityId, attempt: u32) -> Option<Result<serde_json::Value, String>> {
+        self.events
+            .iter()
+            .filter_map(|e| match &e.event_type {
+                EventType::ActivityTaskCompleted { activity_id: id, result } if id == activity_id => {
+                    Some(Ok(result.clone()))
+                }
+                EventType::ActivityTaskFailed { activity_id: id, failure } if id == activity_id => {
+                    Some(Err(failure.clone()))
+                }
+                _ => None,
+            })
+            .nth(attempt.saturating_sub(1) as usize)
+    }
+
+    /// When the timer with the given ID started, and whether it then fired (`Some(true)`) or was
+    /// cancelled (`Some(false)`)
+    pub fn timer(&self, timer_id: &str) -> Option<(DateTime<Utc>, Option<bool>)> {
+        let started = self.events.iter().find_map(|e| match &e.event_type {
+            EventType::TimerStarted { timer_id: id, .. } if id == timer_id => Some(e.timestamp),
+            _ => None,
+        })?;
+        let outcome = self.events.iter().find_map(|e| match &e.event_type {
+            EventType::TimerFired { timer_id: id } if id == timer_id => Some(true),
+            EventType::TimerCancelled { timer_id: id } if id == timer_id => Some(false),
+            _ => None,
+        });
+        Some((started, outcome))
+    }
+
+    /// Recorded outcome of the condition wait with the given sequence number
+    pub fn condition(&self, seq: u64) -> Option<bool> {
+        self.events.iter().find_map(|e| match &e.event_type {
+            EventType::ConditionMarker { seq: recorded, satisfied } if *recorded == seq => Some(*satisfied),
+            _ => None,
+        })
+    }
+
+    /// Recorded outcome of the lock acquisition with the given ID: `true` if the lock was taken,
+    /// `false` if the acquisition timed out
+    pub fn lock_acquisition(&self, lock_id: &str) -> Option<bool> {
+        self.events.iter().find_map(|e| match &e.event_type {
+            EventType::LockAcquired { lock_id: id, .. } if id == lock_id => Some(true),
+            EventType::LockTimedOut { lock_id: id, .. } if id == lock_id => Some(false),
+            _ => None,
+        })
+    }
+
+    /// Recorded outcome of the external signal with the given sequence number: `Err` with the reason
+    /// it was not delivered
+    pub fn external_signal(&self, seq: u64) -> Option<Result<(), String>> {
+        self.events.iter().find_map(|e| match &e.event_type {
+            EventType::SignalExternalWorkflow { seq: recorded, failure, .. } if *recorded == seq => {
+                Some(failure.clone().map_or(Ok(()), Err))
+            }
+            _ => None,
+        })
+    }
+
+    /// Whether lock `lock_id` was released or lost
+    pub fn lock_ended(&self, lock_id: &str) -> bool {
+        self.events.iter().any(|e| match &e.event_type {
+            EventType::LockReleased { lock_id: id, .. } | EventType::LockLost { lock_id: id, .. } => id == lock_id,
+            _ => false,
+        })
+    }
+
+    /// Whether the lock with the given ID was lost to another owner
+    pub fn lock_lost(&self, lock_id: &str) -> bool {
+        self.events.iter().any(|e| match &e.event_type {
+            EventType::LockLost { lock_id: id, .. } => id == lock_id,
+            _ => false,
+        })
+    }
+
+    /// Host task queue of a session whose latest creation has not been followed by its end
+    pub fn open_session(&self, session_id: &str) -> Option<&str> {
+        let mut open = None;
+        for event in &self.events {
+            match &event.event_type {
+                EventType::SessionCreated { session_id: id, host_task_queue } if id == session_id => {
+                    open = Some(host_task_queue.as_str())
+                }
+                EventType::SessionCompleted { session_id: id }
+                | EventType::SessionExpired { session_id: id }
+                | EventType::SessionFailed { session_id: id, .. }
+                    if id == session_id =>
+                {
+                    open = None
+                }
+                _ => {}
+            }
+        }
+        open
+    }
+
+    /// Run that succeeded this one if it closed by continuing as new
+    pub fn continued_as_new(&self) -> Option<RunId> {
+        self.events.iter().rev().find_map(|e| match &e.event_type {
+            EventType::WorkflowExecutionContinuedAsNew { new_run_id, .. } => Some(*new_run_id),
+            _ => None,
+        })
+    }
+
+    /// Whether the run has completed, failed or continued as new
+    pub fn is_closed(&self) -> bool {
+        self.outcome().is_some() || self.continued_as_new().is_some()
+    }
+
+    /// Serialize as JSON, `{"events": [...]}`
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    /// Parse a history serialized by [`to_json`](Self::to_json)
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// Bytes of the events encoded as JSON, the measure [`HistoryLimits`] applies to
+    pub fn size_bytes(&self) -> usize {
+        self.events.iter().map(WorkflowEvent::size_bytes).sum()
+    }
+}
+
+/// Limits on the length and size of the history of one run
+///
+/// Past a soft limit the worker logs a warning, counts it in
+/// [`HISTORY_LIMIT_EXCEEDED`](super::metrics::HISTORY_LIMIT_EXCEEDED) and
+/// [`WorkflowContext::continue_as_new_suggested`](super::WorkflowContext::continue_as_new_suggested)
+/// turns true; a long-running workflow should then continue as new. Past a hard limit the run is
+/// terminated, so a workflow stuck in a loop cannot grow its history without bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+pub struct HistoryLimits {
+    pub soft_events: usize,
+    pub hard_events: usize,
+    /// Bytes of the events encoded as JSON, see [`EventHistory::size_bytes`]
+    pub soft_bytes: usize,
+    pub hard_bytes: usize,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        Self {
+            soft_events: 10_240,
+            hard_events: 51_200,
+            soft_bytes: 10 * 1024 * 1024,
+            hard_bytes: 50 * 1024 * 1024,
+        }
+    }
+}
+
+impl HistoryLimits {
+    /// No limits
+    pub fn unlimited() -> Self {
+        Self {
+            soft_events: usize::MAX,
+            hard_events: usize::MAX,
+            soft_bytes: usize::MAX,
+            hard_bytes: usize::MAX,
+        }
+    }
+
+    /// Description of the soft limit a history of `events` events and `bytes` bytes is over
+    pub(crate) fn soft_exceeded(&self, events: usize, bytes: usize) -> Option<(&'static str, String)> {
+        Self::exceeded(events, bytes, self.soft_events, self.soft_bytes, "soft")
+    }
+
+    /// Description of the hard limit a history of `events` events and `bytes` bytes is over
+    pub(crate) fn hard_exceeded(&self, events: usize, bytes: usize) -> Option<(&'static str, String)> {
+        Self::exceeded(events, bytes, self.hard_events, self.hard_bytes, "hard")
+    }
+
+    fn exceeded(
+        events: usize,
+        bytes: usize,
+        max_events: usize,
+        max_bytes: usize,
+        severity: &str,
+    ) -> Option<(&'static str, String)> {
+        if events > max_events {
+            Some(("events", format!("history has {} events, over the {} limit of {}", events, severity, max_events)))
+        } else if bytes > max_bytes {
+            Some(("bytes", format!("history has {} bytes, over the {} limit of {}", bytes, severity, max_bytes)))
+        } else {
+            None
+        }
+    }
 }
 
 impl Default for EventHistory {
@@ -58,12 +344,31 @@ pub struct WorkflowEvent {
     pub event_type: EventType,
 }
 
+impl WorkflowEvent {
+    /// Bytes of the event encoded as JSON
+    pub fn size_bytes(&self) -> usize {
+        serde_json::to_vec(self).map_or(0, |encoded| encoded.len())
+    }
+
+    /// Whether the event completes, fails, terminates or continues the run as new
+    pub fn closes_run(&self) -> bool {
+        matches!(
+            self.event_type,
+            EventType::WorkflowExecutionCompleted { .. }
+                | EventType::WorkflowExecutionFailed { .. }
+                | EventType::WorkflowExecutionTerminated { .. }
+                | EventType::WorkflowExecutionContinuedAsNew { .. }
+        )
+    }
+}
+
 /// Event type
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum EventType {
     /// Workflow execution started
     WorkflowExecutionStarted {
         workflow_type: String,
+        task_queue: String,
         input: serde_json::Value,
     },
     
@@ -76,6 +381,36 @@ pub enum EventType {
     WorkflowExecutionFailed {
         failure: String,
     },
+
+    /// Run stopped from outside without the workflow code's involvement
+    WorkflowExecutionTerminated {
+        reason: String,
+    },
+
+    /// Run closed and handed over to a new run of the same workflow ID
+    WorkflowExecutionContinuedAsNew {
+        new_run_id: RunId,
+        input: serde_json::Value,
+    },
+
+    /// Signal delivered to the workflow execution
+    WorkflowExecutionSignaled {
+        signal_name: String,
+        input: serde_json::Value,
+    },
+
+    /// Update validated and handed to its handler
+    WorkflowExecutionUpdateAccepted {
+        update_id: String,
+        update_name: String,
+        input: serde_json::Value,
+    },
+
+    /// Result an update handler returned to the caller
+    WorkflowExecutionUpdateCompleted {
+        update_id: String,
+        result: serde_json::Value,
+    },
     
     /// Activity task scheduled
     ActivityTaskScheduled {
@@ -101,6 +436,89 @@ pub enum EventType {
         failure: String,
     },
     
+    /// Saga compensation for the given step (in registration order) started
+    CompensationStarted {
+        step: usize,
+        activity_type: String,
+    },
+
+    /// Saga compensation completed
+    CompensationCompleted {
+        step: usize,
+        activity_type: String,
+    },
+
+    /// Saga compensation failed
+    CompensationFailed {
+        step: usize,
+        activity_type: String,
+        failure: String,
+    },
+
+    /// Version chosen for a code change, see `WorkflowContext::get_version`
+    VersionMarker {
+        change_id: String,
+        version: i32,
+    },
+
+    /// Result of a side effect, keyed by the execution's sequence number
+    SideEffectRecorded {
+        seq: u64,
+        value: serde_json::Value,
+    },
+
+    /// Outcome of a local activity, keyed by the execution's sequence number
+    LocalActivityMarker {
+        seq: u64,
+        activity_type: String,
+        result: Result<serde_json::Value, String>,
+    },
+
+    /// Result reused for a memoized activity call, keyed by the execution's sequence number;
+    /// `None` means the activity had to run
+    ActivityMemoMarker {
+        seq: u64,
+        activity_type: String,
+        input_hash: String,
+        result: Option<serde_json::Value>,
+    },
+
+    /// Outcome of an `await_condition`, keyed by the execution's sequence number; `false` means it timed out
+    ConditionMarker {
+        seq: u64,
+        satisfied: bool,
+    },
+
+    /// Signal or cancellation request sent to another workflow, keyed by the execution's sequence number;
+    /// `failure` tells why it could not be delivered
+    SignalExternalWorkflow {
+        seq: u64,
+        workflow_id: String,
+        signal_name: String,
+        input: serde_json::Value,
+        failure: Option<String>,
+    },
+
+    /// Cancellation of the execution was requested
+    WorkflowExecutionCancelRequested,
+
+    /// Search attributes added or replaced
+    UpsertSearchAttributes {
+        attributes: SearchAttributes,
+    },
+
+    /// Memo passed when starting the execution
+    WorkflowMemoSet {
+        memo: Memo,
+    },
+
+    /// A state machine workflow moved between states on an event
+    StateTransitioned {
+        from: String,
+        to: String,
+        event: String,
+    },
+
     /// Timer started
     TimerStarted {
         timer_id: String,
@@ -111,6 +529,82 @@ pub enum EventType {
     TimerFired {
         timer_id: String,
     },
+
+    /// Timer cancelled before firing
+    TimerCancelled {
+        timer_id: String,
+    },
+
+    /// Human task created, waiting for its assignee to submit the form
+    HumanTaskCreated {
+        task_id: String,
+        assignee: String,
+        title: Option<String>,
+        form_schema: serde_json::Value,
+        timeout_ms: Option<u64>,
+    },
+
+    /// Human task reassigned after its assignee did not complete it in time
+    HumanTaskEscalated {
+        task_id: String,
+        assignee: String,
+    },
+
+    /// Human task completed; the form data is the payload of the preceding signal
+    HumanTaskCompleted {
+        task_id: String,
+    },
+
+    /// Human task not completed before its timeout
+    HumanTaskTimedOut {
+        task_id: String,
+    },
+
+    /// Activity session opened on the worker polling `host_task_queue`
+    SessionCreated {
+        session_id: String,
+        host_task_queue: String,
+    },
+
+    /// Activity session completed by the workflow
+    SessionCompleted {
+        session_id: String,
+    },
+
+    /// Activity session ended by its execution timeout
+    SessionExpired {
+        session_id: String,
+    },
+
+    /// Activity session ended because its host went away
+    SessionFailed {
+        session_id: String,
+        failure: String,
+    },
+
+    /// Workflow lock taken by the run
+    LockAcquired {
+        lock_id: String,
+        name: String,
+    },
+
+    /// Workflow lock not taken before the acquisition timeout
+    LockTimedOut {
+        lock_id: String,
+        name: String,
+    },
+
+    /// Workflow lock given up by the run
+    LockReleased {
+        lock_id: String,
+        name: String,
+    },
+
+    /// Lease of a workflow lock expired and was taken by another owner
+    LockLost {
+        lock_id: String,
+        name: String,
+    },
 }
 
 #[cfg(test)]
@@ -128,6 +622,7 @@ mod tests {
             timestamp: Utc::now(),
             event_type: EventType::WorkflowExecutionStarted {
                 workflow_type: "TestWorkflow".to_string(),
+                task_queue: "default".to_string(),
                 input: serde_json::json!({}),
             },
         };
@@ -136,5 +631,49 @@ mod tests {
         assert_eq!(history.len(), 1);
         assert!(!history.is_empty());
     }
+
+    #[test]
+    fn test_append_assigns_ids_and_outcome() {
+        let mut history = EventHistory::new();
+        let first = history.append(EventType::WorkflowExecutionStarted {
+            workflow_type: "TestWorkflow".to_string(),
+            task_queue: "default".to_string(),
+            input: serde_json::json!(1),
+        });
+        assert!(!history.is_closed());
+        let second = history.append(EventType::WorkflowExecutionCompleted {
+            result: serde_json::json!(2),
+        });
+        assert_eq!(first, EventId(1));
+        assert_eq!(second, EventId(2));
+        assert_eq!(history.outcome(), Some(Ok(serde_json::json!(2))));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut history = EventHistory::new();
+        history.append(EventType::WorkflowExecutionStarted {
+            workflow_type: "TestWorkflow".to_string(),
+            task_queue: "default".to_string(),
+            input: serde_json::json!({"order": 7}),
+        });
+        let decoded = EventHistory::from_json(&history.to_json().unwrap()).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded.events()[0].timestamp, history.events()[0].timestamp);
+        assert!(EventHistory::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn test_continued_as_new_closes_without_outcome() {
+        let mut history = EventHistory::new();
+        let next = RunId::generate();
+        history.append(EventType::WorkflowExecutionContinuedAsNew {
+            new_run_id: next,
+            input: serde_json::json!(1),
+        });
+        assert!(history.is_closed());
+        assert_eq!(history.outcome(), None);
+        assert_eq!(history.continued_as_new(), Some(next));
+    }
 }
 
@@ -0,0 +1,213 @@
+//! Handles of other workflows, for signalling and cancelling them from workflow code
+//!
+//! [`WorkflowContext::get_external_workflow_handle`] names another workflow by ID, such as the
+//! fraud check an order workflow started alongside itself. Signals and cancellation requests sent
+//! through the handle reach the latest run of that workflow as if sent by a client, and their
+//! outcome is recorded in the sender's history as a `SignalExternalWorkflow` marker, so a resumed or
+//! replayed run does not send them again.
+//!
+//! ```no_run
+//! # use serde::{Deserialize, Serialize};
+//! # use workflow::temporal::{Signal, WorkflowContext, WorkflowError};
+//! #[derive(Serialize, Deserialize)]
+//! struct OrderShipped {
+//!     tracking: String,
+//! }
+//!
+//! impl Signal for OrderShipped {
+//!     fn name() -> &'static str {
+//!         "order_shipped"
+//!     }
+//! }
+//!
+//! # async fn ship(ctx: WorkflowContext, order: u64) -> Result<(), WorkflowError> {
+//! let fraud_check = ctx.get_external_workflow_handle(format!("fraud-check-{}", order));
+//! fraud_check.request_cancel().await?;
+//! let notifier = ctx.get_external_workflow_handle(format!("notify-{}", order));
+//! notifier.signal(OrderShipped { tracking: "1Z999".to_string() }).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::Serialize;
+
+use super::event::EventType;
+use super::signal::{Signal, CANCEL_REQUEST_SIGNAL};
+use super::{WorkflowContext, WorkflowError, WorkflowId};
+
+/// Another workflow, addressed from workflow code; see [`WorkflowContext::get_external_workflow_handle`]
+#[derive(Clone)]
+pub struct ExternalWorkflowHandle {
+    ctx: WorkflowContext,
+    workflow_id: WorkflowId,
+}
+
+impl ExternalWorkflowHandle {
+    pub fn workflow_id(&self) -> &WorkflowId {
+        &self.workflow_id
+    }
+
+    /// Send `signal` to the latest run of the workflow
+    ///
+    /// Fails with [`WorkflowError::ExternalWorkflowFailed`] if the workflow does not exist or has
+    /// closed.
+    pub async fn signal<S: Signal>(&self, signal: S) -> Result<(), WorkflowError> {
+        self.send(S::name(), signal).await
+    }
+
+    /// Ask the latest run of the workflow to cancel, as [`WorkflowClient::cancel_workflow`] does
+    ///
+    /// [`WorkflowClient::cancel_workflow`]: super::WorkflowClient::cancel_workflow
+    pub async fn request_cancel(&self) -> Result<(), WorkflowError> {
+        self.send(CANCEL_REQUEST_SIGNAL, serde_json::Value::Null).await
+    }
+
+    async fn send<T: Serialize>(&self, signal_name: &str, input: T) -> Result<(), WorkflowError> {
+        let runtime = self.ctx.runtime()?;
+        let seq = runtime.next_sequence();
+        let recorded = runtime.history.lock().await.external_signal(seq);
+        let outcome = match recorded {
+            Some(outcome) => outcome,
+            None => {
+                let input = serde_json::to_value(input)?;
+                let outcome = if runtime.is_replaying() {
+                    Ok(())
+                } else {
+                    runtime
+                        .client()
+                        .signal_workflow_value(&self.workflow_id, signal_name, input.clone())
+                        .await
+                        .map_err(|e| e.to_string())
+                };
+                runtime
+                    .record(EventType::SignalExternalWorkflow {
+                        seq,
+                        workflow_id: self.workflow_id.to_string(),
+                        signal_name: signal_name.to_string(),
+                        input: runtime.encode(input)?,
+                        failure: outcome.clone().err(),
+                    })
+                    .await?;
+                outcome
+            }
+        };
+        outcome.map_err(|failure| WorkflowError::ExternalWorkflowFailed(format!("{}: {}", self.workflow_id, failure)))
+    }
+}
+
+impl WorkflowContext {
+    /// Handle of the workflow `workflow_id`, for signalling or cancelling it
+    ///
+    /// Only workflows in the storage of this execution's worker can be reached.
+    pub fn get_external_workflow_handle(&self, workflow_id: impl Into<WorkflowId>) -> ExternalWorkflowHandle {
+        ExternalWorkflowHandle {
+            ctx: self.clone(),
+            workflow_id: workflow_id.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use serde::Deserialize;
+
+    use crate::temporal::storage::InMemoryStorage;
+    use crate::temporal::{StartWorkflowOptions, WorkerConfig, Workflow, WorkflowWorker};
+
+    #[derive(Serialize, Deserialize)]
+    struct Approved {
+        by: String,
+    }
+
+    impl Signal for Approved {
+        fn name() -> &'static str {
+            "approved"
+        }
+    }
+
+    /// Waits for an approval, or until cancelled
+    struct FraudCheck;
+
+    impl Workflow for FraudCheck {
+        type Input = ();
+        type Output = String;
+
+        fn name() -> &'static str {
+            "fraud_check"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<String, WorkflowError> {
+            let approved = ctx.wait_for_signal::<Approved>(None).await?;
+            Ok(approved.by)
+        }
+    }
+
+    /// Approves one fraud check, cancels another and signals a missing one
+    struct Order;
+
+    impl Workflow for Order {
+        type Input = ();
+        type Output = String;
+
+        fn name() -> &'static str {
+            "order"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<String, WorkflowError> {
+            let approval = Approved { by: "order".to_string() };
+            ctx.get_external_workflow_handle("check-approved").signal(approval).await?;
+            ctx.get_external_workflow_handle("check-cancelled").request_cancel().await?;
+            let missing = ctx.get_external_workflow_handle("check-missing").request_cancel().await;
+            Ok(missing.unwrap_err().to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_workflows_signal_and_cancel_other_workflows() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(20),
+                ..WorkerConfig::default()
+            })
+            .with_storage(storage),
+        );
+        worker.register_workflow::<FraudCheck>();
+        worker.register_workflow::<Order>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let client = worker.client();
+        let mut checks = Vec::new();
+        for id in ["check-approved", "check-cancelled"] {
+            let options = StartWorkflowOptions {
+                workflow_id: Some(WorkflowId::new(id)),
+                ..Default::default()
+            };
+            checks.push(client.start_workflow::<FraudCheck>((), options).await.unwrap());
+        }
+        let order = client.start_workflow::<Order>((), StartWorkflowOptions::default()).await.unwrap();
+        assert!(order.result().await.unwrap().contains("check-missing"));
+
+        assert_eq!(checks[0].result().await.unwrap(), "order");
+        assert!(checks[1].result().await.unwrap_err().to_string().contains("cancelled"));
+        let history = order.history().await.unwrap();
+        let sent: Vec<_> = history
+            .events()
+            .iter()
+            .filter_map(|e| match &e.event_type {
+                EventType::SignalExternalWorkflow { workflow_id, failure, .. } => {
+                    Some((workflow_id.as_str(), failure.is_some()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sent, vec![("check-approved", false), ("check-cancelled", false), ("check-missing", true)]);
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+//! Export and import of workflow event histories
+//!
+//! A run's history travels together with its execution, as JSON (`{"execution": ..., "events": [...]}`)
+//! or as a compact protobuf encoding of
+//!
+//! ```text
+//! message WorkflowHistory { string workflow_id = 1; string run_id = 2; repeated HistoryEvent events = 3; }
+//! message HistoryEvent { uint64 event_id = 1; int64 seconds = 2; uint32 nanos = 3; bytes event_type = 4; }
+//! ```
+//!
+//! where `event_type` holds the JSON encoding of the [`EventType`]. Exports can be replayed offline
+//! or imported into another deployment with [`WorkflowClient::import_history`](super::WorkflowClient::import_history).
+
+use std::str::FromStr;
+
+use chrono::DateTime;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::event::{EventHistory, EventType, WorkflowEvent};
+use super::{EventId, RunId, WorkflowError, WorkflowExecution, WorkflowId};
+
+/// Encoding of an exported history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryFormat {
+    #[default]
+    Json,
+    Protobuf,
+}
+
+impl HistoryFormat {
+    /// MIME type of the encoding
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Protobuf => "application/x-protobuf",
+        }
+    }
+
+    /// Format for a `Content-Type` header value, ignoring parameters such as `charset`
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type.split(';').next().unwrap_or_default().trim() {
+            "application/json" => Some(Self::Json),
+            "application/x-protobuf" | "application/protobuf" => Some(Self::Protobuf),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for HistoryFormat {
+    type Err = WorkflowError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "protobuf" | "proto" => Ok(Self::Protobuf),
+            other => Err(WorkflowError::InvalidInput(format!("unknown history format: {}", other))),
+        }
+    }
+}
+
+/// A run's event history together with the execution it belongs to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryExport {
+    pub execution: WorkflowExecution,
+    #[serde(flatten)]
+    pub history: EventHistory,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoHistory {
+    #[prost(string, tag = "1")]
+    workflow_id: String,
+    #[prost(string, tag = "2")]
+    run_id: String,
+    #[prost(message, repeated, tag = "3")]
+    events: Vec<ProtoEvent>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoEvent {
+    #[prost(uint64, tag = "1")]
+    event_id: u64,
+    #[prost(int64, tag = "2")]
+    seconds: i64,
+    #[prost(uint32, tag = "3")]
+    nanos: u32,
+    #[prost(bytes = "vec", tag = "4")]
+    event_type: Vec<u8>,
+}
+
+fn serialization_error(e: impl std::fmt::Display) -> WorkflowError {
+    WorkflowError::SerializationError(e.to_string())
+}
+
+impl HistoryExport {
+    pub fn new(execution: WorkflowExecution, history: EventHistory) -> Self {
+        Self { execution, history }
+    }
+
+    /// Encode in `format`
+    pub fn encode(&self, format: HistoryFormat) -> Result<Vec<u8>, WorkflowError> {
+        match format {
+            HistoryFormat::Json => serde_json::to_vec(self).map_err(serialization_error),
+            HistoryFormat::Protobuf => {
+                let events = self
+                    .history
+                    .events()
+                    .iter()
+                    .map(|event| {
+                        Ok(ProtoEvent {
+                            event_id: event.event_id.0,
+                            seconds: event.timestamp.timestamp(),
+                            nanos: event.timestamp.timestamp_subsec_nanos(),
+                            event_type: serde_json::to_vec(&event.event_type).map_err(serialization_error)?,
+                        })
+                    })
+                    .collect::<Result<_, WorkflowError>>()?;
+                let message = ProtoHistory {
+                    workflow_id: self.execution.workflow_id.0.clone(),
+                    run_id: self.execution.run_id.to_string(),
+                    events,
+                };
+                Ok(message.encode_to_vec())
+            }
+        }
+    }
+
+    /// Decode an export encoded in `format`
+    pub fn decode(format: HistoryFormat, bytes: &[u8]) -> Result<Self, WorkflowError> {
+        match format {
+            HistoryFormat::Json => serde_json::from_slice(bytes).map_err(serialization_error),
+            HistoryFormat::Protobuf => {
+                let message = ProtoHistory::decode(bytes).map_err(serialization_error)?;
+                let run_id = Uuid::parse_str(&message.run_id).map_err(serialization_error)?;
+                let mut history = EventHistory::new();
+                for event in message.events {
+                    let timestamp = DateTime::from_timestamp(event.seconds, event.nanos)
+                        .ok_or_else(|| serialization_error(format!("event {} has an invalid timestamp", event.event_id)))?;
+                    history.add_event(WorkflowEvent {
+                        event_id: EventId(event.event_id),
+                        timestamp,
+                        event_type: serde_json::from_slice::<EventType>(&event.event_type).map_err(serialization_error)?,
+                    });
+                }
+                Ok(Self::new(
+                    WorkflowExecution::with_run_id(WorkflowId(message.workflow_id), RunId(run_id)),
+                    history,
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export() -> HistoryExport {
+        let mut history = EventHistory::new();
+        history.append(EventType::WorkflowExecutionStarted {
+            workflow_type: "OrderWorkflow".to_string(),
+            task_queue: "orders".to_string(),
+            input: serde_json::json!({"order": 7}),
+        });
+        history.append(EventType::WorkflowExecutionCompleted { result: serde_json::json!("shipped") });
+        HistoryExport::new(WorkflowExecution::new(WorkflowId::new("order-7")), history)
+    }
+
+    #[test]
+    fn test_round_trips_in_both_formats() {
+        let original = export();
+        for format in [HistoryFormat::Json, HistoryFormat::Protobuf] {
+            let decoded = HistoryExport::decode(format, &original.encode(format).unwrap()).unwrap();
+            assert_eq!(decoded.execution, original.execution);
+            assert_eq!(decoded.history.len(), 2);
+            assert_eq!(decoded.history.events()[0].timestamp, original.history.events()[0].timestamp);
+            assert_eq!(decoded.history.events()[1].event_id, EventId(2));
+            assert_eq!(decoded.history.outcome(), Some(Ok(serde_json::json!("shipped"))));
+        }
+    }
+
+    #[test]
+    fn test_protobuf_is_smaller_and_rejects_garbage() {
+        let original = export();
+        let json = original.encode(HistoryFormat::Json).unwrap();
+        let proto = original.encode(HistoryFormat::Protobuf).unwrap();
+        assert!(proto.len() < json.len());
+        assert!(matches!(
+            HistoryExport::decode(HistoryFormat::Protobuf, b"\xff\xff"),
+            Err(WorkflowError::SerializationError(_))
+        ));
+        assert_eq!(HistoryFormat::from_content_type("application/json; charset=utf-8"), Some(HistoryFormat::Json));
+    }
+}
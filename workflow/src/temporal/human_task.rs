@@ -0,0 +1,447 @@
+//! Human tasks: workflow steps completed by people
+//!
+//! [`WorkflowContext::create_human_task`] records a task for an assignee and suspends the workflow
+//! until the task is completed with form data, through [`WorkflowClient::complete_human_task`] or
+//! `POST /api/v1/tasks/{id}/complete`. Tasks not completed in time can be escalated to other
+//! assignees and finally time out. Tasks live in the history of their execution, so
+//! [`WorkflowClient::human_tasks`] lists the open tasks of running executions with any storage
+//! backend.
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use serde::Deserialize;
+//! # use workflow::temporal::{HumanTaskOptions, WorkflowContext, WorkflowError};
+//! #[derive(Deserialize)]
+//! struct Approval {
+//!     approved: bool,
+//! }
+//!
+//! # async fn approve(ctx: WorkflowContext) -> Result<bool, WorkflowError> {
+//! let form = serde_json::json!({
+//!     "type": "object",
+//!     "required": ["approved"],
+//!     "properties": {"approved": {"type": "boolean"}}
+//! });
+//! let options = HumanTaskOptions::new("team-lead", form)
+//!     .with_title("Approve refund")
+//!     .escalate_after(Duration::from_secs(4 * 3600), "finance-manager")
+//!     .with_timeout(Duration::from_secs(24 * 3600));
+//! let approval: Approval = ctx.create_human_task_with(options).await?;
+//! # Ok(approval.approved)
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::error::{HumanTaskError, SignalError};
+use super::event::{EventHistory, EventType};
+use super::search::{WorkflowExecutionInfo, WorkflowExecutionStatus, WorkflowFilter};
+use super::{WorkflowClient, WorkflowContext, WorkflowError, WorkflowId};
+
+/// Prefix of the signal completing a human task, followed by the task ID
+pub(crate) const HUMAN_TASK_SIGNAL_PREFIX: &str = "__human_task:";
+
+fn completion_signal(task_id: &str) -> String {
+    format!("{}{}", HUMAN_TASK_SIGNAL_PREFIX, task_id)
+}
+
+/// Reassignment of a task still open some time after its creation
+#[derive(Debug, Clone)]
+pub struct Escalation {
+    /// Time since the task was created
+    pub after: Duration,
+    pub assignee: String,
+}
+
+/// Options of a human task
+#[derive(Debug, Clone)]
+pub struct HumanTaskOptions {
+    pub assignee: String,
+    pub title: Option<String>,
+    /// JSON schema the form data must match; see [`validate_form`] for the supported keywords
+    pub form_schema: Value,
+    /// Time after creation at which the task times out
+    pub timeout: Option<Duration>,
+    pub escalations: Vec<Escalation>,
+}
+
+impl HumanTaskOptions {
+    pub fn new(assignee: impl Into<String>, form_schema: Value) -> Self {
+        Self {
+            assignee: assignee.into(),
+            title: None,
+            form_schema,
+            timeout: None,
+            escalations: Vec::new(),
+        }
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Reassign the task to `assignee` if still open `after` its creation
+    pub fn escalate_after(mut self, after: Duration, assignee: impl Into<String>) -> Self {
+        self.escalations.push(Escalation {
+            after,
+            assignee: assignee.into(),
+        });
+        self
+    }
+}
+
+/// Open human task of a running execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HumanTask {
+    pub task_id: String,
+    pub workflow_id: WorkflowId,
+    pub workflow_type: String,
+    /// Current assignee, after any escalations
+    pub assignee: String,
+    pub title: Option<String>,
+    pub form_schema: Value,
+    pub created_at: DateTime<Utc>,
+    pub due_at: Option<DateTime<Utc>>,
+    /// Number of escalations so far
+    pub escalations: u32,
+}
+
+/// Open human tasks in the history of an execution
+fn open_tasks(info: &WorkflowExecutionInfo, history: &EventHistory) -> Vec<HumanTask> {
+    let mut tasks: Vec<HumanTask> = Vec::new();
+    for event in history.events() {
+        match &event.event_type {
+            EventType::HumanTaskCreated {
+                task_id,
+                assignee,
+                title,
+                form_schema,
+                timeout_ms,
+            } => tasks.push(HumanTask {
+                task_id: task_id.clone(),
+                workflow_id: info.execution.workflow_id.clone(),
+                workflow_type: info.workflow_type.clone(),
+                assignee: assignee.clone(),
+                title: title.clone(),
+                form_schema: form_schema.clone(),
+                created_at: event.timestamp,
+                due_at: timeout_ms.map(|ms| event.timestamp + chrono::Duration::milliseconds(ms as i64)),
+                escalations: 0,
+            }),
+            EventType::HumanTaskEscalated { task_id, assignee } => {
+                if let Some(task) = tasks.iter_mut().find(|task| task.task_id == *task_id) {
+                    task.assignee = assignee.clone();
+                    task.escalations += 1;
+                }
+            }
+            EventType::HumanTaskCompleted { task_id } | EventType::HumanTaskTimedOut { task_id } => {
+                tasks.retain(|task| task.task_id != *task_id);
+            }
+            // Completed but not yet taken by the workflow
+            EventType::WorkflowExecutionSignaled { signal_name, .. } => {
+                if let Some(task_id) = signal_name.strip_prefix(HUMAN_TASK_SIGNAL_PREFIX) {
+                    tasks.retain(|task| task.task_id != task_id);
+                }
+            }
+            _ => {}
+        }
+    }
+    tasks
+}
+
+/// Check form data against a JSON schema
+///
+/// Supports the keywords `type`, `enum`, `required`, `properties` and `items`; others are
+/// ignored. The error names the offending value by its JSON pointer.
+pub fn validate_form(schema: &Value, data: &Value) -> Result<(), String> {
+    check(schema, data, "")
+}
+
+fn check(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    let Some(schema) = schema.as_object() else {
+        return Ok(());
+    };
+    let at = if path.is_empty() { "form" } else { path };
+    if let Some(kind) = schema.get("type").and_then(Value::as_str) {
+        let matches = match kind {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "boolean" => value.is_boolean(),
+            "number" => value.is_number(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "null" => value.is_null(),
+            _ => true,
+        };
+        if !matches {
+            return Err(format!("{} must be of type {}", at, kind));
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array)
+        && !options.contains(value)
+    {
+        return Err(format!("{} must be one of {}", at, Value::Array(options.clone())));
+    }
+    if let Some(object) = value.as_object() {
+        let required = schema.get("required").and_then(Value::as_array).into_iter().flatten();
+        if let Some(missing) = required.filter_map(Value::as_str).find(|name| !object.contains_key(*name)) {
+            return Err(format!("{}/{} is required", path, missing));
+        }
+        for (name, property) in schema.get("properties").and_then(Value::as_object).into_iter().flatten() {
+            if let Some(value) = object.get(name) {
+                check(property, value, &format!("{}/{}", path, name))?;
+            }
+        }
+    }
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (index, item) in array.iter().enumerate() {
+            check(items, item, &format!("{}/{}", path, index))?;
+        }
+    }
+    Ok(())
+}
+
+impl WorkflowContext {
+    /// Create a task for `assignee` and wait until it is completed with data matching `form_schema`
+    ///
+    /// Fails with [`WorkflowError::Timeout`] if the task is not completed within `timeout`. See
+    /// [`create_human_task_with`](Self::create_human_task_with) for titles and escalations.
+    pub async fn create_human_task<T: DeserializeOwned>(
+        &self,
+        assignee: impl Into<String>,
+        form_schema: Value,
+        timeout: Option<Duration>,
+    ) -> Result<T, WorkflowError> {
+        let options = HumanTaskOptions {
+            timeout,
+            ..HumanTaskOptions::new(assignee, form_schema)
+        };
+        self.create_human_task_with(options).await
+    }
+
+    /// Create a human task and wait until it is completed, escalating it while it stays open
+    ///
+    /// Escalations due at or after the timeout never happen.
+    pub async fn create_human_task_with<T: DeserializeOwned>(&self, options: HumanTaskOptions) -> Result<T, WorkflowError> {
+        let task_id = self.random_uuid().await?.to_string();
+        self.record(EventType::HumanTaskCreated {
+            task_id: task_id.clone(),
+            assignee: options.assignee,
+            title: options.title,
+            form_schema: options.form_schema,
+            timeout_ms: options.timeout.map(|timeout| timeout.as_millis() as u64),
+        })
+        .await?;
+
+        let signal = completion_signal(&task_id);
+        let signals = [signal.as_str()];
+        let mut escalations = options.escalations;
+        escalations.sort_by_key(|escalation| escalation.after);
+        let mut escalations = escalations.into_iter().peekable();
+        let mut waited = Duration::ZERO;
+        loop {
+            let completed = self.wait_for_any_signal(&signals);
+            let deadline = match (escalations.peek().map(|escalation| escalation.after), options.timeout) {
+                (Some(escalation), Some(timeout)) => Some(escalation.min(timeout)),
+                (escalation, timeout) => escalation.or(timeout),
+            };
+            let Some(deadline) = deadline else {
+                let (_, data) = completed.await?;
+                self.record(EventType::HumanTaskCompleted { task_id }).await?;
+                return Ok(serde_json::from_value(data)?);
+            };
+            if let Some(completed) = self.within(deadline.saturating_sub(waited), completed).await {
+                let (_, data) = completed?;
+                self.record(EventType::HumanTaskCompleted { task_id }).await?;
+                return Ok(serde_json::from_value(data)?);
+            }
+            waited = deadline;
+            if options.timeout.is_some_and(|timeout| timeout <= waited) {
+                self.record(EventType::HumanTaskTimedOut { task_id: task_id.clone() }).await?;
+                return Err(WorkflowError::Timeout(format!("human task {}", task_id)));
+            }
+            if let Some(escalation) = escalations.next() {
+                self.record(EventType::HumanTaskEscalated {
+                    task_id: task_id.clone(),
+                    assignee: escalation.assignee,
+                })
+                .await?;
+            }
+        }
+    }
+}
+
+impl WorkflowClient {
+    /// Open human tasks of running executions, oldest first; only those of `assignee` if given
+    ///
+    /// Reads the history of every running execution.
+    pub async fn human_tasks(&self, assignee: Option<&str>) -> Result<Vec<HumanTask>, WorkflowError> {
+        let running = self
+            .list_workflows(&WorkflowFilter::new().status(WorkflowExecutionStatus::Running))
+            .await?;
+        let mut tasks = Vec::new();
+        for info in running {
+            if let Some((_, history)) = self.load_workflow(&info.execution.workflow_id).await? {
+                tasks.extend(
+                    open_tasks(&info, &history)
+                        .into_iter()
+                        .filter(|task| assignee.is_none_or(|assignee| task.assignee == assignee)),
+                );
+            }
+        }
+        tasks.sort_by_key(|task| task.created_at);
+        Ok(tasks)
+    }
+
+    /// Open human task with the given ID
+    pub async fn human_task(&self, task_id: &str) -> Result<Option<HumanTask>, WorkflowError> {
+        Ok(self.human_tasks(None).await?.into_iter().find(|task| task.task_id == task_id))
+    }
+
+    /// Complete an open task with form data, resuming the workflow waiting for it
+    pub async fn complete_human_task(&self, task_id: &str, data: Value) -> Result<HumanTask, HumanTaskError> {
+        let task = self
+            .human_task(task_id)
+            .await
+            .map_err(|e| HumanTaskError::Custom(e.to_string()))?
+            .ok_or_else(|| HumanTaskError::TaskNotFound(task_id.to_string()))?;
+        validate_form(&task.form_schema, &data).map_err(HumanTaskError::InvalidForm)?;
+        self.signal_workflow_value(&task.workflow_id, &completion_signal(task_id), data)
+            .await
+            .map_err(|e| match e {
+                SignalError::WorkflowNotFound | SignalError::WorkflowClosed(_) => {
+                    HumanTaskError::TaskNotFound(task_id.to_string())
+                }
+                e => HumanTaskError::Custom(e.to_string()),
+            })?;
+        Ok(task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use crate::temporal::{StartWorkflowOptions, WorkerConfig, Workflow, WorkflowWorker};
+
+    #[derive(Deserialize)]
+    struct Approval {
+        approved: bool,
+    }
+
+    fn approval_form() -> Value {
+        json!({
+            "type": "object",
+            "required": ["approved"],
+            "properties": {
+                "approved": {"type": "boolean"},
+                "reason": {"type": "string", "enum": ["price", "stock"]}
+            }
+        })
+    }
+
+    /// Approved by the team lead, escalated to the manager after an hour, timing out after two
+    struct Refund;
+
+    impl Workflow for Refund {
+        type Input = ();
+        type Output = bool;
+
+        fn name() -> &'static str {
+            "refund"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<bool, WorkflowError> {
+            let options = HumanTaskOptions::new("team-lead", approval_form())
+                .with_title("Approve refund")
+                .escalate_after(Duration::from_secs(3600), "manager")
+                .with_timeout(Duration::from_secs(7200));
+            let approval: Approval = ctx.create_human_task_with(options).await?;
+            Ok(approval.approved)
+        }
+    }
+
+    #[test]
+    fn test_form_validation() {
+        assert!(validate_form(&approval_form(), &json!({"approved": true, "reason": "price"})).is_ok());
+        assert_eq!(validate_form(&approval_form(), &json!({})).unwrap_err(), "/approved is required");
+        assert_eq!(
+            validate_form(&approval_form(), &json!({"approved": "yes"})).unwrap_err(),
+            "/approved must be of type boolean"
+        );
+        assert!(validate_form(&approval_form(), &json!({"approved": false, "reason": "other"})).is_err());
+        assert_eq!(validate_form(&approval_form(), &json!([])).unwrap_err(), "form must be of type object");
+        assert!(validate_form(&json!({"type": "array", "items": {"type": "integer"}}), &json!([1, 2.5])).is_err());
+    }
+
+    async fn wait_for_tasks(client: &WorkflowClient, assignee: &str) -> Vec<HumanTask> {
+        for _ in 0..250 {
+            let tasks = client.human_tasks(Some(assignee)).await.unwrap();
+            if !tasks.is_empty() {
+                return tasks;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("no task for {assignee}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_human_tasks_escalate_and_complete() {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        }));
+        worker.register_workflow::<Refund>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+        let client = worker.client();
+        let start = |id: &str| StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new(id)),
+            ..StartWorkflowOptions::default()
+        };
+
+        let approved = client.start_workflow::<Refund>((), start("refund-1")).await.unwrap();
+        let task = wait_for_tasks(&client, "team-lead").await.remove(0);
+        assert_eq!((task.workflow_id.as_str(), task.title.as_deref()), ("refund-1", Some("Approve refund")));
+        assert!(task.due_at.is_some());
+        assert!(matches!(
+            client.complete_human_task(&task.task_id, json!({"approved": 1})).await,
+            Err(HumanTaskError::InvalidForm(_))
+        ));
+
+        tokio::time::sleep(Duration::from_secs(3601)).await;
+        let escalated = wait_for_tasks(&client, "manager").await.remove(0);
+        assert_eq!((escalated.task_id.as_str(), escalated.escalations), (task.task_id.as_str(), 1));
+        assert!(client.human_tasks(Some("team-lead")).await.unwrap().is_empty());
+
+        client.complete_human_task(&task.task_id, json!({"approved": true})).await.unwrap();
+        assert!(approved.result().await.unwrap());
+        assert!(matches!(
+            client.complete_human_task(&task.task_id, json!({"approved": true})).await,
+            Err(HumanTaskError::TaskNotFound(_))
+        ));
+
+        let ignored = client.start_workflow::<Refund>((), start("refund-2")).await.unwrap();
+        wait_for_tasks(&client, "team-lead").await;
+        tokio::time::sleep(Duration::from_secs(7201)).await;
+        assert!(matches!(ignored.result().await, Err(WorkflowError::Custom(message)) if message.contains("human task")));
+        assert!(client.human_tasks(None).await.unwrap().is_empty());
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
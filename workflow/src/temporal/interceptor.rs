@@ -0,0 +1,257 @@
+//! Interceptors around the worker and client calls
+//!
+//! Interceptors plug cross-cutting concerns such as tracing, metrics or payload encryption into
+//! the engine without changing it. Every hook has a no-op default, so an interceptor implements
+//! only the calls it cares about.
+//!
+//! `before_*` hooks may rewrite the payload or reject the call; they run in registration order.
+//! `after_*` hooks may rewrite the outcome; they run in reverse registration order, so the first
+//! registered interceptor wraps all others.
+//!
+//! Workflow tasks may run more than once for an execution, for example after a crash, and every
+//! run passes through the workflow hooks again.
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::client::StartWorkflowOptions;
+use super::error::SignalError;
+use super::{ActivityError, ActivityInfo, WorkflowError, WorkflowExecution, WorkflowInfo};
+
+/// Hooks around the tasks a [`WorkflowWorker`](super::WorkflowWorker) runs
+#[async_trait]
+pub trait WorkerInterceptor: Send + Sync {
+    /// Before a workflow task runs the workflow with `input`; an error fails the execution
+    async fn before_workflow_task(&self, _info: &WorkflowInfo, _input: &mut Value) -> Result<(), WorkflowError> {
+        Ok(())
+    }
+
+    /// After a workflow task, with the execution's outcome
+    ///
+    /// A [`WorkflowError::ContinuedAsNew`] outcome carries the input of the next run.
+    async fn after_workflow_task(&self, _info: &WorkflowInfo, _result: &mut Result<Value, WorkflowError>) {}
+
+    /// Before an activity attempt runs with `input`; an error fails the attempt
+    async fn before_activity(&self, _info: &ActivityInfo, _input: &mut Value) -> Result<(), ActivityError> {
+        Ok(())
+    }
+
+    /// After an activity attempt, with its result
+    async fn after_activity(&self, _info: &ActivityInfo, _result: &mut Result<Value, ActivityError>) {}
+
+    /// Before a signal reaches its execution; an error drops the signal
+    async fn before_signal(
+        &self,
+        _execution: &WorkflowExecution,
+        _signal_name: &str,
+        _input: &mut Value,
+    ) -> Result<(), SignalError> {
+        Ok(())
+    }
+}
+
+/// Request to start a workflow, as seen by [`ClientInterceptor`]s
+#[derive(Debug, Clone)]
+pub struct StartWorkflowRequest {
+    pub workflow_type: String,
+    pub input: Value,
+    pub options: StartWorkflowOptions,
+}
+
+/// Request to signal a workflow, as seen by [`ClientInterceptor`]s
+///
+/// Cancellation requests pass through as signals too.
+#[derive(Debug, Clone)]
+pub struct SignalWorkflowRequest {
+    pub workflow_id: super::WorkflowId,
+    pub signal_name: String,
+    pub input: Value,
+}
+
+/// Hooks around the calls of a [`WorkflowClient`](super::WorkflowClient)
+#[async_trait]
+pub trait ClientInterceptor: Send + Sync {
+    /// Before a workflow is started; an error is returned to the caller
+    async fn before_start(&self, _request: &mut StartWorkflowRequest) -> Result<(), WorkflowError> {
+        Ok(())
+    }
+
+    /// After a start attempt, with the started execution
+    async fn after_start(&self, _request: &StartWorkflowRequest, _result: &Result<WorkflowExecution, WorkflowError>) {}
+
+    /// Before a signal is sent; an error is returned to the caller
+    async fn before_signal(&self, _request: &mut SignalWorkflowRequest) -> Result<(), SignalError> {
+        Ok(())
+    }
+
+    /// After a signal attempt
+    async fn after_signal(&self, _request: &SignalWorkflowRequest, _result: &Result<(), SignalError>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::temporal::{
+        Activity, ActivityContext, ActivityOptions, Signal, StartWorkflowOptions, WorkerConfig, Workflow,
+        WorkflowContext, WorkflowId, WorkflowWorker,
+    };
+
+    /// Stands in for encryption: payloads travel as `{"sealed": payload}`
+    fn seal(value: Value) -> Value {
+        json!({ "sealed": value })
+    }
+
+    fn open(value: &mut Value) -> Result<(), String> {
+        match value.get_mut("sealed") {
+            Some(sealed) => {
+                *value = sealed.take();
+                Ok(())
+            }
+            None => Err(format!("payload is not sealed: {}", value)),
+        }
+    }
+
+    struct Sealing;
+
+    #[async_trait]
+    impl ClientInterceptor for Sealing {
+        async fn before_start(&self, request: &mut StartWorkflowRequest) -> Result<(), WorkflowError> {
+            if request.workflow_type == "forbidden" {
+                return Err(WorkflowError::InvalidInput("forbidden workflow".to_string()));
+            }
+            request.input = seal(request.input.take());
+            Ok(())
+        }
+
+        async fn before_signal(&self, request: &mut SignalWorkflowRequest) -> Result<(), SignalError> {
+            request.input = seal(request.input.take());
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Unsealing {
+        activity_attempts: AtomicU32,
+    }
+
+    #[async_trait]
+    impl WorkerInterceptor for Unsealing {
+        async fn before_workflow_task(&self, _info: &WorkflowInfo, input: &mut Value) -> Result<(), WorkflowError> {
+            open(input).map_err(WorkflowError::InvalidInput)
+        }
+
+        async fn after_workflow_task(&self, _info: &WorkflowInfo, result: &mut Result<Value, WorkflowError>) {
+            if let Ok(value) = result {
+                *value = seal(value.take());
+            }
+        }
+
+        async fn before_activity(&self, info: &ActivityInfo, _input: &mut Value) -> Result<(), ActivityError> {
+            assert_eq!(info.activity_type, "greet");
+            self.activity_attempts.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+
+        async fn after_activity(&self, _info: &ActivityInfo, result: &mut Result<Value, ActivityError>) {
+            if let Ok(Value::String(greeting)) = result {
+                greeting.push('!');
+            }
+        }
+
+        async fn before_signal(&self, _execution: &WorkflowExecution, _name: &str, input: &mut Value) -> Result<(), SignalError> {
+            open(input).map_err(SignalError::Custom)
+        }
+    }
+
+    struct Greet;
+
+    impl Activity for Greet {
+        type Input = String;
+        type Output = String;
+
+        fn name() -> &'static str {
+            "greet"
+        }
+
+        async fn execute(_ctx: ActivityContext, name: String) -> Result<String, ActivityError> {
+            Ok(format!("hello {}", name))
+        }
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Punctuation(String);
+
+    impl Signal for Punctuation {
+        fn name() -> &'static str {
+            "punctuation"
+        }
+    }
+
+    struct Greeting;
+
+    impl Workflow for Greeting {
+        type Input = String;
+        type Output = String;
+
+        fn name() -> &'static str {
+            "greeting"
+        }
+
+        async fn execute(ctx: WorkflowContext, name: String) -> Result<String, WorkflowError> {
+            let greeting = ctx.execute_activity::<Greet>(name, ActivityOptions::default()).await?;
+            let Punctuation(mark) = ctx.wait_for_signal::<Punctuation>(None).await?;
+            Ok(greeting + &mark)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interceptors_rewrite_payloads() {
+        let unsealing = Arc::new(Unsealing::default());
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                ..WorkerConfig::default()
+            })
+            .with_interceptor(unsealing.clone()),
+        );
+        worker.register_workflow::<Greeting>();
+        worker.register_activity::<Greet>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let client = worker.client().with_interceptor(Arc::new(Sealing));
+        let id = WorkflowId::new("greeting-1");
+        let options = StartWorkflowOptions {
+            workflow_id: Some(id.clone()),
+            ..StartWorkflowOptions::default()
+        };
+        let handle = client
+            .start_dynamic_workflow("greeting", json!("ada"), options)
+            .await
+            .unwrap();
+        client.signal_workflow(&id, Punctuation("?".to_string())).await.unwrap();
+
+        // The history holds the sealed payloads, the workflow and activity saw plain ones
+        assert_eq!(handle.result().await.unwrap(), json!({ "sealed": "hello ada!?" }));
+        let history = handle.history().await.unwrap();
+        assert!(matches!(
+            &history.events()[0].event_type,
+            crate::temporal::event::EventType::WorkflowExecutionStarted { input, .. } if *input == json!({ "sealed": "ada" })
+        ));
+        assert_eq!(unsealing.activity_attempts.load(Ordering::Relaxed), 1);
+
+        let rejected = client
+            .start_dynamic_workflow("forbidden", Value::Null, StartWorkflowOptions::default())
+            .await;
+        assert!(matches!(rejected, Err(WorkflowError::InvalidInput(message)) if message == "forbidden workflow"));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
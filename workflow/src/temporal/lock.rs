@@ -0,0 +1,387 @@
+//! Workflow locks: mutexes shared by the workflows of a deployment
+//!
+//! [`WorkflowContext::acquire_lock`] takes a named lock, so that, say, only one reconciliation
+//! workflow touches an account at a time. Locks are leases in the workflow storage, owned by the
+//! run and renewed in the background while the lock is held; taking, timing out on, releasing and
+//! losing a lock are recorded in the history, and a run taken over by another worker takes the
+//! lock it held again instead of waiting from scratch.
+//!
+//! A lock the run does not [release](WorkflowLock::release) stays taken until its lease runs out,
+//! [`DEFAULT_LOCK_LEASE`] after the last renewal. A lease that could not be renewed in time, e.g.
+//! because the worker stalled, may be taken by another run; the holder then finds the lock
+//! [lost](WorkflowLock::ensure_held). Critical sections should check before every step that must
+//! not run concurrently.
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use workflow::temporal::{WorkflowContext, WorkflowError};
+//! # async fn reconcile(ctx: WorkflowContext, account: String) -> Result<(), WorkflowError> {
+//! let lock = ctx.acquire_lock(&format!("account-{}", account), Duration::from_secs(300)).await?;
+//! // ... activities touching the account, each after `lock.ensure_held().await?`
+//! lock.release().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+use super::error::StorageError;
+use super::event::EventType;
+use super::storage::WorkflowStorage;
+use super::{WorkflowContext, WorkflowError};
+
+/// How long a lock stays taken after its last renewal
+pub const DEFAULT_LOCK_LEASE: Duration = Duration::from_secs(30);
+
+/// How often a waiting acquisition checks whether the lock is free
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A lock taken with [`WorkflowContext::acquire_lock`]
+///
+/// Dropping the lock stops renewing it without releasing it.
+pub struct WorkflowLock {
+    ctx: WorkflowContext,
+    lock_id: String,
+    name: String,
+    owner: String,
+    storage: Arc<dyn WorkflowStorage>,
+    /// Cancelled once a renewal found the lease taken by another owner
+    lost: CancellationToken,
+    /// Set once the release or loss of the lock is in the history
+    ended: AtomicBool,
+    /// Cancelled when the lock is released or dropped, stopping the renewals
+    stopped: CancellationToken,
+    /// Held by renewals, so none runs while the lock is released
+    renewing: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl WorkflowLock {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the run still holds the lock, as of its last renewal
+    pub fn is_held(&self) -> bool {
+        !self.lost.is_cancelled()
+    }
+
+    /// Fail with [`WorkflowError::LockLost`] if the lease was taken by another owner, recording the loss once
+    pub async fn ensure_held(&self) -> Result<(), WorkflowError> {
+        if self.is_held() {
+            return Ok(());
+        }
+        if !self.ended.swap(true, Ordering::SeqCst) {
+            metrics::counter!(super::metrics::WORKFLOW_LOCKS, "outcome" => "lost").increment(1);
+            self.ctx
+                .record(EventType::LockLost {
+                    lock_id: self.lock_id.clone(),
+                    name: self.name.clone(),
+                })
+                .await?;
+        }
+        Err(WorkflowError::LockLost(self.name.clone()))
+    }
+
+    /// Release the lock; fails with [`WorkflowError::LockLost`] if the run no longer held it
+    pub async fn release(self) -> Result<(), WorkflowError> {
+        self.stopped.cancel();
+        self.ensure_held().await?;
+        if self.ended.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        if !self.ctx.runtime()?.is_replaying() {
+            let _renewal = self.renewing.lock().await;
+            self.storage.release_lock(&self.name, &self.owner).await?;
+        }
+        self.ctx
+            .record(EventType::LockReleased {
+                lock_id: self.lock_id.clone(),
+                name: self.name.clone(),
+            })
+            .await
+    }
+
+    /// Renew the lease every third of it until stopped or lost
+    fn renew(&self, lease: Duration) {
+        let storage = self.storage.clone();
+        let (name, owner) = (self.name.clone(), self.owner.clone());
+        let (lost, stopped, renewing) = (self.lost.clone(), self.stopped.clone(), self.renewing.clone());
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = stopped.cancelled() => return,
+                    _ = tokio::time::sleep(lease / 3) => {}
+                }
+                let _renewal = renewing.lock().await;
+                if stopped.is_cancelled() {
+                    return;
+                }
+                match storage.lease_lock(&name, &owner, lease).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        tracing::warn!(lock = %name, %owner, "workflow lock lost to another owner");
+                        lost.cancel();
+                        return;
+                    }
+                    Err(e) => tracing::warn!(lock = %name, %owner, error = %e, "failed to renew workflow lock"),
+                }
+            }
+        });
+    }
+}
+
+impl Drop for WorkflowLock {
+    fn drop(&mut self) {
+        self.stopped.cancel();
+    }
+}
+
+/// Wait until `owner` holds lock `name`
+async fn take(storage: &dyn WorkflowStorage, name: &str, owner: &str, lease: Duration) -> Result<(), StorageError> {
+    while !storage.lease_lock(name, owner, lease).await? {
+        tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+    }
+    Ok(())
+}
+
+impl WorkflowContext {
+    /// Take the lock `name`, shared by all workflows using the same storage, waiting up to `timeout`
+    ///
+    /// Fails with [`WorkflowError::Timeout`] if another run held the lock throughout, and with
+    /// [`WorkflowError::Cancelled`] if the scope is cancelled while waiting. The run holds the
+    /// lock until it [releases](WorkflowLock::release) it or its lease runs out, see the
+    /// [module docs](super::lock).
+    pub async fn acquire_lock(&self, name: &str, timeout: Duration) -> Result<WorkflowLock, WorkflowError> {
+        let runtime = self.runtime()?;
+        let lock_id = format!("lock-{}", runtime.next_sequence());
+        let execution = self.execution();
+        let owner = format!("{}/{}", execution.workflow_id, execution.run_id);
+        let storage = runtime.storage.clone();
+        let lease = DEFAULT_LOCK_LEASE;
+        let (recorded, ended, lost) = {
+            let history = runtime.history.lock().await;
+            (history.lock_acquisition(&lock_id), history.lock_ended(&lock_id), history.lock_lost(&lock_id))
+        };
+        // A run taken over by this worker re-executes over its history, recording the same events again
+        let resumed = recorded.is_some() && !runtime.is_replaying();
+
+        let acquired = match recorded {
+            // Replays answer from the history without touching the storage
+            Some(acquired) if runtime.is_replaying() => acquired,
+            None if runtime.is_replaying() => true,
+            Some(true) if ended => true,
+            // Taken before the run was taken over, so it is ours to take again
+            Some(true) => {
+                self.unless_cancelled(async { Ok(take(&*storage, name, &owner, lease).await?) }).await?;
+                true
+            }
+            Some(false) => false,
+            None => {
+                let waited = self
+                    .unless_cancelled(async { Ok(self.within(timeout, take(&*storage, name, &owner, lease)).await) })
+                    .await?;
+                match waited {
+                    Some(taken) => taken.map(|()| true)?,
+                    None => false,
+                }
+            }
+        };
+        if !resumed {
+            let outcome = if acquired { "acquired" } else { "timed_out" };
+            metrics::counter!(super::metrics::WORKFLOW_LOCKS, "outcome" => outcome).increment(1);
+        }
+        let (recorded_id, recorded_name) = (lock_id.clone(), name.to_string());
+        self.record(if acquired {
+            EventType::LockAcquired {
+                lock_id: recorded_id,
+                name: recorded_name,
+            }
+        } else {
+            EventType::LockTimedOut {
+                lock_id: recorded_id,
+                name: recorded_name,
+            }
+        })
+        .await?;
+        if !acquired {
+            return Err(WorkflowError::Timeout(format!("lock {}", name)));
+        }
+
+        let lock = WorkflowLock {
+            ctx: self.clone(),
+            lock_id,
+            name: name.to_string(),
+            owner,
+            storage,
+            lost: CancellationToken::new(),
+            ended: AtomicBool::new(false),
+            stopped: CancellationToken::new(),
+            renewing: Arc::new(tokio::sync::Mutex::new(())),
+        };
+        // A lock lost before the takeover is found lost again, and one released is not renewed
+        if resumed && lost {
+            lock.lost.cancel();
+        }
+        if !(runtime.is_replaying() || (resumed && ended)) {
+            lock.renew(lease);
+        }
+        Ok(lock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    use crate::temporal::event::EventHistory;
+    use crate::temporal::storage::InMemoryStorage;
+    use crate::temporal::{
+        Activity, ActivityContext, ActivityError, ActivityOptions, StartWorkflowOptions, WorkerConfig, Workflow,
+        WorkflowWorker,
+    };
+
+    static INSIDE: AtomicUsize = AtomicUsize::new(0);
+    static MOST_INSIDE: AtomicUsize = AtomicUsize::new(0);
+
+    /// Counts how many attempts run at once
+    struct TouchAccount;
+
+    impl Activity for TouchAccount {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "touch_account"
+        }
+
+        async fn execute(_ctx: ActivityContext, _: ()) -> Result<(), ActivityError> {
+            let inside = INSIDE.fetch_add(1, Ordering::SeqCst) + 1;
+            MOST_INSIDE.fetch_max(inside, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            INSIDE.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Touches an account while holding its lock
+    struct Reconcile;
+
+    impl Workflow for Reconcile {
+        type Input = String;
+        type Output = ();
+
+        fn name() -> &'static str {
+            "reconcile"
+        }
+
+        async fn execute(ctx: WorkflowContext, account: String) -> Result<(), WorkflowError> {
+            let lock = ctx.acquire_lock(&account, Duration::from_secs(10)).await?;
+            lock.ensure_held().await?;
+            ctx.execute_activity::<TouchAccount>((), ActivityOptions::default()).await?;
+            lock.release().await
+        }
+    }
+
+    /// Gives up on a lock after a short wait
+    struct Impatient;
+
+    impl Workflow for Impatient {
+        type Input = String;
+        type Output = ();
+
+        fn name() -> &'static str {
+            "impatient"
+        }
+
+        async fn execute(ctx: WorkflowContext, account: String) -> Result<(), WorkflowError> {
+            ctx.acquire_lock(&account, Duration::from_millis(50)).await?.release().await
+        }
+    }
+
+    fn lock_events(history: &EventHistory) -> Vec<&'static str> {
+        history
+            .events()
+            .iter()
+            .filter_map(|e| match e.event_type {
+                EventType::LockAcquired { .. } => Some("acquired"),
+                EventType::LockTimedOut { .. } => Some("timed_out"),
+                EventType::LockReleased { .. } => Some("released"),
+                EventType::LockLost { .. } => Some("lost"),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_lock_holders_run_one_at_a_time() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                ..WorkerConfig::default()
+            })
+            .with_storage(storage.clone()),
+        );
+        worker.register_workflow::<Reconcile>();
+        worker.register_workflow::<Impatient>();
+        worker.register_activity::<TouchAccount>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let client = worker.client();
+        let mut handles = Vec::new();
+        for _ in 0..3 {
+            let start = client.start_workflow::<Reconcile>("account-1".to_string(), StartWorkflowOptions::default());
+            handles.push(start.await.unwrap());
+        }
+        for handle in &handles {
+            handle.result().await.unwrap();
+            assert_eq!(lock_events(&handle.history().await.unwrap()), vec!["acquired", "released"]);
+        }
+        assert_eq!(MOST_INSIDE.load(Ordering::SeqCst), 1);
+
+        assert!(storage.lease_lock("account-2", "operator", Duration::from_secs(60)).await.unwrap());
+        let handle = client
+            .start_workflow::<Impatient>("account-2".to_string(), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        assert!(handle.result().await.unwrap_err().to_string().contains("lock account-2"));
+        assert_eq!(lock_events(&handle.history().await.unwrap()), vec!["timed_out"]);
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_lost_leases_are_reported() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let lease = Duration::from_millis(30);
+        assert!(storage.lease_lock("account-3", "run-a", lease).await.unwrap());
+        let lock = WorkflowLock {
+            ctx: WorkflowContext::new(crate::temporal::WorkflowExecution::new(crate::temporal::WorkflowId::new("a"))),
+            lock_id: "lock-1".to_string(),
+            name: "account-3".to_string(),
+            owner: "run-a".to_string(),
+            storage: storage.clone(),
+            lost: CancellationToken::new(),
+            ended: AtomicBool::new(false),
+            stopped: CancellationToken::new(),
+            renewing: Arc::new(tokio::sync::Mutex::new(())),
+        };
+        lock.renew(lease);
+        tokio::time::sleep(lease * 2).await;
+        assert!(lock.is_held());
+
+        // Another owner can only take the lock once renewals stop
+        assert!(!storage.lease_lock("account-3", "run-b", lease).await.unwrap());
+        lock.stopped.cancel();
+        tokio::time::sleep(lease * 2).await;
+        assert!(storage.lease_lock("account-3", "run-b", lease).await.unwrap());
+        lock.lost.cancel();
+        assert!(!lock.is_held());
+    }
+}
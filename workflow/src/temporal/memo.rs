@@ -0,0 +1,206 @@
+//! Memoized activity results
+//!
+//! Expensive, pure activities (a tax calculation, a price quote) can opt in with
+//! [`ActivityOptions::memoize`](super::ActivityOptions::memoize): their result is kept in the
+//! worker's memo store, keyed by activity type and a hash of the input, and later calls with the
+//! same input within the TTL reuse it instead of scheduling the activity. Whether a call was
+//! served from the store is recorded as an `ActivityMemoMarker` event, so executions of the same
+//! history take the same path even after the entry expires.
+//!
+//! The memo store needs the `persistence` feature; on a worker without one, memoized calls always
+//! run the activity.
+//!
+//! ```rust,ignore
+//! use workflow::persistence::InMemoryAdapter;
+//! use workflow::temporal::{ActivityOptions, WorkerConfig, WorkflowWorker};
+//!
+//! let worker = WorkflowWorker::new(WorkerConfig::default()).with_activity_memo(Arc::new(InMemoryAdapter::new()));
+//! let options = ActivityOptions {
+//!     memoize: Some(Duration::from_secs(3600)),
+//!     ..ActivityOptions::default()
+//! };
+//! ```
+
+#[cfg(feature = "persistence")]
+use std::sync::Arc;
+#[cfg(feature = "persistence")]
+use std::time::Duration;
+
+#[cfg(feature = "persistence")]
+use crate::persistence::{PersistenceAdapter, StateSnapshot};
+use crate::util::pagination::filter_hash;
+
+/// Activity type and input hash a result is memoized under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MemoKey {
+    pub(crate) activity_type: String,
+    /// SHA-256 of the input as canonical (key-sorted) JSON
+    pub(crate) input_hash: String,
+}
+
+impl MemoKey {
+    pub(crate) fn new(activity_type: &str, input: &serde_json::Value) -> Self {
+        Self {
+            activity_type: activity_type.to_string(),
+            input_hash: filter_hash(input),
+        }
+    }
+
+    #[cfg(feature = "persistence")]
+    fn record(&self) -> String {
+        format!("memo:{}:{}", self.activity_type, self.input_hash)
+    }
+}
+
+/// Memo store of a worker, kept through a [`PersistenceAdapter`]
+///
+/// Workers sharing the adapter reuse each other's results. Store errors are logged and treated as
+/// misses: memoization only saves work, it never fails an activity.
+#[cfg(feature = "persistence")]
+pub(crate) struct ActivityMemo {
+    store: Arc<dyn PersistenceAdapter>,
+}
+
+#[cfg(feature = "persistence")]
+impl ActivityMemo {
+    pub(crate) fn new(store: Arc<dyn PersistenceAdapter>) -> Self {
+        Self { store }
+    }
+
+    /// Result stored for `key` that has not expired
+    pub(crate) async fn get(&self, key: &MemoKey) -> Option<serde_json::Value> {
+        let now = chrono::Utc::now().timestamp();
+        let hit = match self.store.load_state(&key.record()).await {
+            Ok(Some(snapshot)) => match snapshot.state["expires_at"].as_i64() {
+                Some(expires_at) if expires_at > now => snapshot.state.get("result").cloned(),
+                _ => None,
+            },
+            Ok(None) => None,
+            Err(e) => {
+                tracing::warn!(activity_type = %key.activity_type, error = %e, "memo lookup failed");
+                None
+            }
+        };
+        let outcome = if hit.is_some() { "hit" } else { "miss" };
+        metrics::counter!("temporal_activity_memo_lookups_total", "activity_type" => key.activity_type.clone(), "result" => outcome)
+            .increment(1);
+        hit
+    }
+
+    /// Keep `result` for `ttl`
+    pub(crate) async fn put(&self, key: &MemoKey, result: &serde_json::Value, ttl: Duration) {
+        let now = chrono::Utc::now().timestamp();
+        let snapshot = StateSnapshot {
+            workflow_id: key.record(),
+            state: serde_json::json!({ "result": result, "expires_at": now + ttl.as_secs() as i64 }),
+            updated_at: now,
+        };
+        if let Err(e) = self.store.save_state(snapshot).await {
+            tracing::warn!(activity_type = %key.activity_type, error = %e, "failed to memoize activity result");
+        }
+    }
+}
+
+#[cfg(all(test, feature = "persistence"))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::persistence::InMemoryAdapter;
+    use crate::temporal::event::EventType;
+    use crate::temporal::{
+        Activity, ActivityContext, ActivityError, ActivityOptions, StartWorkflowOptions, WorkerConfig, Workflow,
+        WorkflowContext, WorkflowError, WorkflowWorker,
+    };
+
+    static TAX_CALCULATIONS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Tax;
+
+    impl Activity for Tax {
+        type Input = u64;
+        type Output = u64;
+
+        fn name() -> &'static str {
+            "tax"
+        }
+
+        async fn execute(_ctx: ActivityContext, cents: u64) -> Result<u64, ActivityError> {
+            TAX_CALCULATIONS.fetch_add(1, Ordering::SeqCst);
+            Ok(cents / 5)
+        }
+    }
+
+    struct Invoice;
+
+    impl Workflow for Invoice {
+        type Input = u64;
+        type Output = u64;
+
+        fn name() -> &'static str {
+            "invoice"
+        }
+
+        async fn execute(ctx: WorkflowContext, cents: u64) -> Result<u64, WorkflowError> {
+            let options = ActivityOptions {
+                memoize: Some(Duration::from_secs(3600)),
+                ..ActivityOptions::default()
+            };
+            Ok(cents + ctx.execute_activity::<Tax>(cents, options).await?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memoized_results_are_reused_and_recorded() {
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                ..WorkerConfig::default()
+            })
+            .with_activity_memo(Arc::new(InMemoryAdapter::new())),
+        );
+        worker.register_workflow::<Invoice>();
+        worker.register_activity::<Tax>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let client = worker.client();
+        let mut histories = Vec::new();
+        for cents in [500, 500, 1000] {
+            let handle = client.start_workflow::<Invoice>(cents, StartWorkflowOptions::default()).await.unwrap();
+            assert_eq!(handle.result().await.unwrap(), cents + cents / 5);
+            histories.push(handle.history().await.unwrap());
+        }
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+        assert_eq!(TAX_CALCULATIONS.load(Ordering::SeqCst), 2);
+
+        let markers: Vec<Option<serde_json::Value>> = histories
+            .iter()
+            .map(|history| {
+                let marker = history.events().iter().find_map(|event| match &event.event_type {
+                    EventType::ActivityMemoMarker { result, .. } => Some(result.clone()),
+                    _ => None,
+                });
+                marker.unwrap()
+            })
+            .collect();
+        assert_eq!(markers, vec![None, Some(serde_json::json!(100)), None]);
+        let scheduled = |history: &crate::temporal::event::EventHistory| {
+            history.events().iter().any(|event| matches!(event.event_type, EventType::ActivityTaskScheduled { .. }))
+        };
+        assert!(scheduled(&histories[0]) && !scheduled(&histories[1]));
+    }
+
+    #[tokio::test]
+    async fn test_entries_expire() {
+        let memo = ActivityMemo::new(Arc::new(InMemoryAdapter::new()));
+        let key = MemoKey::new("tax", &serde_json::json!({"b": 1, "a": 2}));
+        assert_eq!(key, MemoKey::new("tax", &serde_json::json!({"a": 2, "b": 1})));
+        memo.put(&key, &serde_json::json!(7), Duration::ZERO).await;
+        assert_eq!(memo.get(&key).await, None);
+        memo.put(&key, &serde_json::json!(7), Duration::from_secs(60)).await;
+        assert_eq!(memo.get(&key).await, Some(serde_json::json!(7)));
+        assert_eq!(memo.get(&MemoKey::new("tax", &serde_json::json!(1))).await, None);
+    }
+}
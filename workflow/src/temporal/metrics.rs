@@ -0,0 +1,98 @@
+//! Engine metrics
+//!
+//! The engine records through the [`metrics`] facade, so whatever recorder the process installs
+//! (Prometheus in the service binary) picks them up. Workflow metrics are labelled by
+//! `workflow_type`, activity metrics by `activity_type`, and both by `namespace`. Call [`describe`] once after installing
+//! the recorder to attach units and help texts.
+
+use metrics::{describe_counter, describe_gauge, describe_histogram, Unit};
+
+pub const WORKFLOWS_STARTED: &str = "temporal_workflows_started_total";
+pub const WORKFLOW_STARTS_DEDUPLICATED: &str = "temporal_workflow_starts_deduplicated_total";
+pub const WORKFLOWS_CLOSED: &str = "temporal_workflows_closed_total";
+pub const WORKFLOW_DURATION: &str = "temporal_workflow_execution_duration_seconds";
+pub const WORKFLOW_TASKS: &str = "temporal_workflow_tasks_total";
+pub const ACTIVITY_ATTEMPTS: &str = "temporal_activity_tasks_total";
+pub const ACTIVITY_DURATION: &str = "temporal_activity_attempt_duration_seconds";
+pub const ACTIVITY_BATCH_SIZE: &str = "temporal_activity_batch_size";
+pub const ACTIVITY_RATE_LIMITED: &str = "temporal_activity_rate_limited_total";
+pub const OUTBOX_PUBLICATIONS: &str = "temporal_outbox_publications_total";
+pub const WORKFLOW_LOCKS: &str = "temporal_workflow_locks_total";
+pub const WORKFLOW_UPDATES: &str = "temporal_workflow_updates_total";
+pub const RESOURCE_POOL_PERMITS: &str = "temporal_resource_pool_permits";
+pub const RESOURCE_POOL_WAIT: &str = "temporal_resource_pool_wait_seconds";
+pub const TASK_QUEUE_DEPTH: &str = "temporal_task_queue_depth";
+pub const SCHEDULE_TO_START: &str = "temporal_task_schedule_to_start_seconds";
+pub const TASK_PRIORITY_WAIT: &str = "temporal_task_priority_wait_seconds";
+pub const STICKY_CACHE_REQUESTS: &str = "temporal_sticky_cache_total";
+pub const STICKY_CACHE_SIZE: &str = "temporal_sticky_cache_size";
+pub const HISTORIES_ARCHIVED: &str = "temporal_histories_archived_total";
+pub const RETENTION_RECLAIMED: &str = "temporal_retention_reclaimed_total";
+pub const QUOTA_REJECTIONS: &str = "temporal_namespace_quota_rejections_total";
+pub const WORKER_TASK_SLOTS: &str = "temporal_worker_task_slots";
+pub const HISTORY_LIMIT_EXCEEDED: &str = "temporal_history_limit_exceeded_total";
+
+/// Register units and descriptions of the engine metrics with the installed recorder
+pub fn describe() {
+    describe_counter!(WORKFLOWS_STARTED, "Workflow executions started, by workflow_type");
+    describe_counter!(
+        WORKFLOW_STARTS_DEDUPLICATED,
+        "Starts answered with an earlier run of the same dedup key, by workflow_type"
+    );
+    describe_counter!(
+        WORKFLOWS_CLOSED,
+        "Workflow executions closed, by workflow_type and outcome (completed, failed, terminated, continued_as_new)"
+    );
+    describe_histogram!(
+        WORKFLOW_DURATION,
+        Unit::Seconds,
+        "Time from the start of a workflow execution until it closed, by workflow_type and outcome"
+    );
+    describe_counter!(WORKFLOW_TASKS, "Workflow tasks handled, by workflow_type and outcome");
+    describe_counter!(ACTIVITY_ATTEMPTS, "Activity attempts, by activity_type and outcome");
+    describe_histogram!(ACTIVITY_DURATION, Unit::Seconds, "Duration of activity attempts, by activity_type and outcome");
+    describe_histogram!(ACTIVITY_BATCH_SIZE, Unit::Count, "Calls run together by batch activities, by activity_type");
+    describe_counter!(ACTIVITY_RATE_LIMITED, "Activity attempts put back on their queue by a rate limit, by activity_type");
+    describe_counter!(
+        OUTBOX_PUBLICATIONS,
+        "Outbox messages handed to publishers, by destination and outcome (published, failed)"
+    );
+    describe_counter!(WORKFLOW_LOCKS, "Workflow lock acquisitions and losses, by outcome (acquired, timed_out, lost)");
+    describe_counter!(
+        WORKFLOW_UPDATES,
+        "Updates sent to running workflows, by update and outcome (completed, rejected, error)"
+    );
+    describe_gauge!(RESOURCE_POOL_PERMITS, "Permits of resource pools held by activity attempts, by pool");
+    describe_histogram!(
+        RESOURCE_POOL_WAIT,
+        Unit::Seconds,
+        "Time activity attempts queued for a resource pool permit, by pool"
+    );
+    describe_gauge!(TASK_QUEUE_DEPTH, "Tasks waiting in the in-memory task queue, by task_queue and kind");
+    describe_histogram!(
+        SCHEDULE_TO_START,
+        Unit::Seconds,
+        "Time tasks waited in the in-memory task queue before a worker picked them up, by task_queue, kind and type"
+    );
+    describe_histogram!(
+        TASK_PRIORITY_WAIT,
+        Unit::Seconds,
+        "Time tasks waited in their task queue before a worker picked them up, by task_queue, kind and priority"
+    );
+    describe_counter!(STICKY_CACHE_REQUESTS, "Sticky history cache lookups, by workflow_type and result (hit, miss)");
+    describe_gauge!(STICKY_CACHE_SIZE, "Runs whose history is held in the sticky history cache");
+    describe_gauge!(WORKER_TASK_SLOTS, "Task slots of workers, by task_queue, kind and slots (used or max)");
+    describe_counter!(HISTORIES_ARCHIVED, "Closed run histories moved to the archive, by workflow_type");
+    describe_counter!(
+        QUOTA_REJECTIONS,
+        "Workflow starts and activity attempts rejected by admission control, by namespace, workflow_type and kind"
+    );
+    describe_counter!(
+        HISTORY_LIMIT_EXCEEDED,
+        "Runs whose history went over a size limit, by workflow_type, limit (events or bytes) and severity (soft or hard)"
+    );
+    describe_counter!(
+        RETENTION_RECLAIMED,
+        "Expired executions deleted or archived by retention sweeps, by workflow_type, action and dry_run"
+    );
+}
@@ -8,32 +8,132 @@
 //! - `types`: Core type definitions (WorkflowId, RunId, etc.)
 //! - `workflow`: Workflow trait and execution context
 //! - `activity`: Activity trait and execution context
+//! - `dynamic_activity`: Activities registered by name with JSON input and output
+//! - `activity_batch`: Small activities run together in batches
+//! - `memo`: Memoized results of expensive, pure activities
+//! - `outbox`: Outbound messages saved with activity completions and relayed afterwards
+//! - `activities`: Built-in HTTP request, webhook and notification activities
+//! - `saga`: Saga steps with reverse-order compensation
+//! - `schedule`: Cron schedules that start workflow runs
+//! - `search`: Search attributes, memos and workflow listing
 //! - `signal`: Signal definitions and handling
+//! - `external`: Handles for signalling and cancelling other workflows from workflow code
+//! - `human_task`: Tasks completed by people, with escalations
+//! - `session`: Sequences of activities pinned to one worker
+//! - `lock`: Durable mutexes shared by workflows
+//! - `batch`: Cancel, terminate or signal many workflows at once
+//! - `dead_letter`: Dead-letter queue for poisoned tasks
 //! - `query`: Query definitions and handling
+//! - `update`: Validated updates that change running workflows and answer the caller
 //! - `client`: Client for starting workflows and sending signals
+//! - `namespace`: Namespaces isolating teams that share one deployment
+//! - `admission`: Concurrency limits on workflow starts and activity attempts
+//! - `rate_limit`: Rate limits of activity types
+//! - `resource_pool`: Semaphores bounding activities against shared resources
+//! - `converter`: Encodings of workflow and activity payloads
+//! - `codec`: Transformations of encoded payloads, such as compression
+//! - `encryption`: Payload encryption with rotating keys
+//! - `interceptor`: Hooks around worker tasks and client calls
 //! - `worker`: Worker for processing workflow and activity tasks
+//! - `tuning`: Worker saturation signals and runtime slot tuning
+//! - `sticky`: Cache of the histories of recently active runs
 //! - `storage`: Persistence layer abstraction
+//! - `archival`: Archival of closed histories to object storage
+//! - `retention`: Retention policies and cleanup of closed executions
+//! - `task_queue`: Task queues connecting clients and workers
 //! - `event`: Event sourcing and history
 //! - `error`: Error types
 
 pub mod types;
 pub mod workflow;
 pub mod activity;
+pub mod dynamic_activity;
+pub mod activity_batch;
+pub mod memo;
+pub mod outbox;
+pub mod activities;
+pub mod saga;
+pub mod schedule;
+pub mod search;
 pub mod signal;
+pub mod external;
+pub mod human_task;
+pub mod session;
+pub mod lock;
+pub mod batch;
+pub mod dead_letter;
+pub mod circuit_breaker;
+pub mod clock;
 pub mod query;
+pub mod update;
+pub mod replay;
 pub mod client;
+pub mod namespace;
+pub mod admission;
+pub mod rate_limit;
+pub mod resource_pool;
+pub mod converter;
+pub mod codec;
+pub mod encryption;
+pub mod interceptor;
 pub mod worker;
+pub mod tuning;
+pub(crate) mod sticky;
 pub mod storage;
+pub mod archival;
+pub mod retention;
+pub mod task_queue;
+pub mod metrics;
+pub mod telemetry;
 pub mod event;
+pub mod history_export;
 pub mod error;
 
 // Re-export commonly used items
 pub use self::types::*;
-pub use self::workflow::{Workflow, WorkflowContext};
+pub use self::workflow::{CancellationScope, Workflow, WorkflowContext, DEFAULT_VERSION};
 pub use self::activity::{Activity, ActivityContext, ActivityOptions};
+pub use self::dynamic_activity::{DynamicActivity, DynamicActivityRegistry, PayloadValidator};
+pub use self::activity_batch::{BatchActivity, Batched, BatchingOptions};
+pub use self::activities::{HttpRequestActivity, WebhookNotifyActivity};
+pub use self::outbox::{OutboxDestination, OutboxMessage, OutboxPublisher, OutboxRelay, WebhookPublisher};
+pub use self::saga::Saga;
+pub use self::schedule::{ScheduleDescription, ScheduleOverlapPolicy, Schedules};
+pub use self::search::{
+    Memo, SearchAttributeValue, SearchAttributes, WorkflowExecutionInfo, WorkflowExecutionStatus, WorkflowFilter,
+};
 pub use self::signal::Signal;
+pub use self::external::ExternalWorkflowHandle;
+pub use self::human_task::{Escalation, HumanTask, HumanTaskOptions};
+pub use self::session::{Session, SessionInfo, SessionOptions};
+pub use self::lock::WorkflowLock;
+pub use self::batch::{BatchFailure, BatchOperation, BatchProgress, BatchReport, BatchRequest, BatchTarget};
+pub use self::dead_letter::{DeadLetter, DeadLetterQueue};
+pub use self::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+pub use self::rate_limit::{LocalRateLimiter, RateLimit, RateLimiter};
+pub use self::resource_pool::{Fairness, ResourcePool, ResourcePools};
+#[cfg(feature = "database")]
+pub use self::rate_limit::RedisRateLimiter;
 pub use self::query::Query;
-pub use self::client::WorkflowClient;
-pub use self::worker::WorkflowWorker;
-pub use self::error::{WorkflowError, ActivityError};
+pub use self::update::Update;
+pub use self::replay::ReplayError;
+pub use self::event::HistoryLimits;
+pub use self::history_export::{HistoryExport, HistoryFormat};
+pub use self::client::{WorkflowClient, WorkflowHandle, StartWorkflowOptions, WorkflowIdReusePolicy};
+pub use self::codec::{CodecConverter, Compression, CompressionCodec, PayloadCodec};
+pub use self::encryption::{EncryptionCodec, EncryptionKey, EnvKeyProvider, KeyProvider, KmsKeyProvider, StaticKeyProvider};
+pub use self::converter::{DataConverter, JsonConverter, MessagePackConverter, Payload, ProtobufConverter};
+pub use self::interceptor::{ClientInterceptor, SignalWorkflowRequest, StartWorkflowRequest, WorkerInterceptor};
+pub use self::worker::{
+    WorkflowWorker, WorkerConfig, WorkerStatus, InFlightCounts, QueueDepths, PauseHandle, ShutdownHandle, ShutdownReport,
+};
+pub use self::sticky::StickyCacheStats;
+pub use self::storage::{HistoryPage, WorkflowStorage, InMemoryStorage};
+#[cfg(feature = "sqlite")]
+pub use self::storage::SqliteStorage;
+pub use self::task_queue::{TaskQueue, InMemoryTaskQueue, Priority};
+#[cfg(feature = "database")]
+pub use self::task_queue::{RedisStreamsTaskQueue, StreamStats};
+pub use self::activity::RetryPolicy;
+pub use self::error::{WorkflowError, ActivityError, UpdateError};
 
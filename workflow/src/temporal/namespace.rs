@@ -0,0 +1,401 @@
+//! Namespaces isolating teams that share one deployment
+//!
+//! [`Namespaces`] hands out workers and clients whose storage and task queues are views of a
+//! shared backend, scoped to one [`Namespace`]: workflow IDs are stored as `{namespace}/{id}` and
+//! task queues polled as `{namespace}/{queue}`. Each namespace thus has its own workflow IDs,
+//! registrations, schedules and dead letters; its runs and activities can be capped with
+//! [admission limits](super::admission), and the engine metrics of its workers and clients carry
+//! a `namespace` label.
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use workflow::temporal::namespace::{Namespace, Namespaces};
+//! # use workflow::temporal::storage::InMemoryStorage;
+//! # use workflow::temporal::task_queue::InMemoryTaskQueue;
+//! # use workflow::temporal::WorkerConfig;
+//! let payments = Namespace::new("payments").unwrap();
+//! let namespaces = Namespaces::new(Arc::new(InMemoryStorage::new()), Arc::new(InMemoryTaskQueue::new()))
+//!     .with_quota(payments.clone(), 100);
+//! let worker = namespaces.worker(&payments, WorkerConfig::default());
+//! ```
+//!
+//! Workers created directly, outside [`Namespaces`], keep using unscoped IDs and queues and belong
+//! to the [`DEFAULT_NAMESPACE`] for metrics only.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::client::WorkflowClient;
+use super::error::StorageError;
+use super::event::EventHistory;
+use super::admission::{AdmissionControl, AdmissionLimits};
+use super::outbox::OutboxMessage;
+use super::search::{WorkflowExecutionInfo, WorkflowFilter};
+use super::storage::{HistoryPage, WorkflowStorage};
+use super::task_queue::{Task, TaskKind, TaskQueue};
+use super::{EventId, WorkerConfig, WorkflowError, WorkflowExecution, WorkflowId, WorkflowWorker};
+
+/// Namespace of workers and clients not scoped to another one
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// Longest namespace name
+pub const MAX_NAMESPACE_LEN: usize = 64;
+
+/// Name of a namespace: ASCII letters, digits, `-` and `_`
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Namespace(String);
+
+impl Namespace {
+    pub fn new(name: impl Into<String>) -> Result<Self, WorkflowError> {
+        let name = name.into();
+        let valid = !name.is_empty()
+            && name.len() <= MAX_NAMESPACE_LEN
+            && name.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_');
+        if !valid {
+            return Err(WorkflowError::InvalidInput(format!(
+                "invalid namespace {:?}: expected 1 to {} ASCII letters, digits, '-' or '_'",
+                name, MAX_NAMESPACE_LEN
+            )));
+        }
+        Ok(Self(name))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn scope(&self, name: &str) -> String {
+        format!("{}/{}", self.0, name)
+    }
+
+    fn unscope<'a>(&self, name: &'a str) -> Option<&'a str> {
+        name.strip_prefix(self.0.as_str())?.strip_prefix('/')
+    }
+}
+
+impl Default for Namespace {
+    fn default() -> Self {
+        Self(DEFAULT_NAMESPACE.to_string())
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl TryFrom<String> for Namespace {
+    type Error = WorkflowError;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Self::new(name)
+    }
+}
+
+impl From<Namespace> for String {
+    fn from(namespace: Namespace) -> Self {
+        namespace.0
+    }
+}
+
+/// The executions of one namespace in a shared storage
+pub struct NamespacedStorage {
+    namespace: Namespace,
+    inner: Arc<dyn WorkflowStorage>,
+}
+
+impl NamespacedStorage {
+    pub fn new(namespace: Namespace, inner: Arc<dyn WorkflowStorage>) -> Self {
+        Self { namespace, inner }
+    }
+
+    fn scoped(&self, workflow_id: &WorkflowId) -> WorkflowId {
+        WorkflowId::new(self.namespace.scope(workflow_id.as_str()))
+    }
+
+    fn unscoped(&self, mut execution: WorkflowExecution) -> Option<WorkflowExecution> {
+        execution.workflow_id = WorkflowId::new(self.namespace.unscope(execution.workflow_id.as_str())?);
+        Some(execution)
+    }
+}
+
+#[async_trait]
+impl WorkflowStorage for NamespacedStorage {
+    async fn save_workflow_execution(
+        &self,
+        execution: &WorkflowExecution,
+        history: &EventHistory,
+    ) -> Result<(), StorageError> {
+        let execution = WorkflowExecution {
+            workflow_id: self.scoped(&execution.workflow_id),
+            run_id: execution.run_id,
+        };
+        self.inner.save_workflow_execution(&execution, history).await
+    }
+
+    async fn save_with_outbox(
+        &self,
+        execution: &WorkflowExecution,
+        history: &EventHistory,
+        outbox: &[OutboxMessage],
+    ) -> Result<(), StorageError> {
+        let execution = WorkflowExecution {
+            workflow_id: self.scoped(&execution.workflow_id),
+            run_id: execution.run_id,
+        };
+        self.inner.save_with_outbox(&execution, history, outbox).await
+    }
+
+    async fn lease_lock(&self, name: &str, owner: &str, lease: Duration) -> Result<bool, StorageError> {
+        self.inner.lease_lock(&self.namespace.scope(name), owner, lease).await
+    }
+
+    async fn release_lock(&self, name: &str, owner: &str) -> Result<(), StorageError> {
+        self.inner.release_lock(&self.namespace.scope(name), owner).await
+    }
+
+    /// The outbox is shared by the namespaces of the backend
+    async fn pending_outbox(&self, limit: usize) -> Result<Vec<OutboxMessage>, StorageError> {
+        self.inner.pending_outbox(limit).await
+    }
+
+    async fn mark_outbox_sent(&self, ids: &[String]) -> Result<(), StorageError> {
+        self.inner.mark_outbox_sent(ids).await
+    }
+
+    async fn load_workflow_execution(
+        &self,
+        workflow_id: &WorkflowId,
+    ) -> Result<(WorkflowExecution, EventHistory), StorageError> {
+        let (mut execution, history) = self.inner.load_workflow_execution(&self.scoped(workflow_id)).await?;
+        execution.workflow_id = workflow_id.clone();
+        Ok((execution, history))
+    }
+
+    async fn list_workflow_executions(
+        &self,
+        filter: &WorkflowFilter,
+    ) -> Result<Vec<WorkflowExecutionInfo>, StorageError> {
+        let infos = self.inner.list_workflow_executions(filter).await?;
+        Ok(infos
+            .into_iter()
+            .filter_map(|mut info| {
+                info.execution = self.unscoped(info.execution)?;
+                Some(info)
+            })
+            .collect())
+    }
+
+    async fn delete_workflow_execution(&self, workflow_id: &WorkflowId) -> Result<(), StorageError> {
+        self.inner.delete_workflow_execution(&self.scoped(workflow_id)).await
+    }
+
+    async fn load_events(
+        &self,
+        workflow_id: &WorkflowId,
+        after_event_id: EventId,
+        limit: usize,
+    ) -> Result<HistoryPage, StorageError> {
+        let mut page = self.inner.load_events(&self.scoped(workflow_id), after_event_id, limit).await?;
+        page.execution.workflow_id = workflow_id.clone();
+        Ok(page)
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.inner.ping().await
+    }
+}
+
+/// The task queues of one namespace in a shared task queue backend
+pub struct NamespacedTaskQueue {
+    namespace: Namespace,
+    inner: Arc<dyn TaskQueue>,
+}
+
+impl NamespacedTaskQueue {
+    pub fn new(namespace: Namespace, inner: Arc<dyn TaskQueue>) -> Self {
+        Self { namespace, inner }
+    }
+}
+
+#[async_trait]
+impl TaskQueue for NamespacedTaskQueue {
+    async fn push(&self, queue: &str, task: Task) -> Result<(), StorageError> {
+        self.inner.push(&self.namespace.scope(queue), task).await
+    }
+
+    async fn poll(&self, queue: &str, kind: TaskKind, timeout: Duration) -> Result<Option<Task>, StorageError> {
+        self.inner.poll(&self.namespace.scope(queue), kind, timeout).await
+    }
+
+    async fn poll_with_latency(
+        &self,
+        queue: &str,
+        kind: TaskKind,
+        timeout: Duration,
+    ) -> Result<Option<(Task, Option<Duration>)>, StorageError> {
+        self.inner.poll_with_latency(&self.namespace.scope(queue), kind, timeout).await
+    }
+
+    async fn len(&self, queue: &str, kind: TaskKind) -> Result<usize, StorageError> {
+        self.inner.len(&self.namespace.scope(queue), kind).await
+    }
+
+    async fn complete(&self, queue: &str, task: &Task) -> Result<(), StorageError> {
+        self.inner.complete(&self.namespace.scope(queue), task).await
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.inner.ping().await
+    }
+}
+
+/// Namespaces sharing one storage and task queue backend
+#[derive(Clone)]
+pub struct Namespaces {
+    storage: Arc<dyn WorkflowStorage>,
+    task_queue: Arc<dyn TaskQueue>,
+    admission: HashMap<Namespace, Arc<AdmissionControl>>,
+}
+
+impl Namespaces {
+    pub fn new(storage: Arc<dyn WorkflowStorage>, task_queue: Arc<dyn TaskQueue>) -> Self {
+        Self {
+            storage,
+            task_queue,
+            admission: HashMap::new(),
+        }
+    }
+
+    /// Allow at most `max_concurrent_runs` running executions in `namespace`
+    pub fn with_quota(self, namespace: Namespace, max_concurrent_runs: usize) -> Self {
+        self.with_limits(namespace, AdmissionLimits::new().max_workflows(max_concurrent_runs))
+    }
+
+    /// Enforce `limits` on the starts and activities of `namespace`, shared by all its workers and clients
+    pub fn with_limits(mut self, namespace: Namespace, limits: AdmissionLimits) -> Self {
+        let admission = AdmissionControl::new(namespace.clone(), self.storage(&namespace), limits);
+        self.admission.insert(namespace, Arc::new(admission));
+        self
+    }
+
+    pub fn storage(&self, namespace: &Namespace) -> Arc<dyn WorkflowStorage> {
+        Arc::new(NamespacedStorage::new(namespace.clone(), self.storage.clone()))
+    }
+
+    pub fn task_queue(&self, namespace: &Namespace) -> Arc<dyn TaskQueue> {
+        Arc::new(NamespacedTaskQueue::new(namespace.clone(), self.task_queue.clone()))
+    }
+
+    /// Worker of `namespace`, with its own registrations, observing the namespace's limits
+    pub fn worker(&self, namespace: &Namespace, config: WorkerConfig) -> WorkflowWorker {
+        let worker = WorkflowWorker::new(config)
+            .with_namespace(namespace.clone())
+            .with_storage(self.storage(namespace))
+            .with_task_queue(self.task_queue(namespace));
+        match self.admission.get(namespace) {
+            Some(admission) => worker.with_admission(admission.clone()),
+            None => worker,
+        }
+    }
+
+    /// Client of `namespace` without a worker, so without queries
+    pub fn client(&self, namespace: &Namespace) -> WorkflowClient {
+        let client = WorkflowClient::new(self.task_queue(namespace), self.storage(namespace)).with_namespace(namespace.clone());
+        match self.admission.get(namespace) {
+            Some(admission) => client.with_interceptor(admission.clone()),
+            None => client,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::storage::InMemoryStorage;
+    use crate::temporal::task_queue::InMemoryTaskQueue;
+    use crate::temporal::{StartWorkflowOptions, Workflow, WorkflowContext};
+
+    /// Sleeps long enough to stay running for the test
+    struct Wait;
+
+    impl Workflow for Wait {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "wait"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<(), WorkflowError> {
+            ctx.sleep(Duration::from_secs(60)).await;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_namespace_names() {
+        assert_eq!(Namespace::default().as_str(), DEFAULT_NAMESPACE);
+        assert!(Namespace::new("team-a_2").is_ok());
+        for invalid in ["", "a/b", "a b", &"x".repeat(MAX_NAMESPACE_LEN + 1)] {
+            assert!(matches!(Namespace::new(invalid), Err(WorkflowError::InvalidInput(_))), "{invalid}");
+        }
+        assert!(serde_json::from_str::<Namespace>("\"a.b\"").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_are_isolated_and_capped() {
+        let shared = Arc::new(InMemoryStorage::new());
+        let (a, b) = (Namespace::new("a").unwrap(), Namespace::new("b").unwrap());
+        let namespaces = Namespaces::new(shared.clone(), Arc::new(InMemoryTaskQueue::new())).with_quota(a.clone(), 1);
+        let config = WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        };
+        let (worker_a, worker_b) = (Arc::new(namespaces.worker(&a, config.clone())), Arc::new(namespaces.worker(&b, config)));
+        worker_a.register_workflow::<Wait>();
+        let runs: Vec<_> = [worker_a.clone(), worker_b.clone()]
+            .into_iter()
+            .map(|worker| tokio::spawn(async move { worker.run().await }))
+            .collect();
+
+        let options = StartWorkflowOptions {
+            workflow_id: Some(WorkflowId::new("job")),
+            ..StartWorkflowOptions::default()
+        };
+        worker_a.client().start_workflow::<Wait>((), options.clone()).await.unwrap();
+        // The same workflow ID is free in another namespace
+        namespaces.client(&b).start_workflow::<Wait>((), options).await.unwrap();
+        assert!(matches!(
+            namespaces.client(&a).start_workflow::<Wait>((), StartWorkflowOptions::default()).await,
+            Err(WorkflowError::QuotaExceeded(_))
+        ));
+
+        let stored = shared.list_workflow_executions(&WorkflowFilter::new()).await.unwrap();
+        let mut ids: Vec<_> = stored.iter().map(|info| info.execution.workflow_id.to_string()).collect();
+        ids.sort();
+        assert_eq!(ids, ["a/job", "b/job"]);
+        let listed = namespaces.storage(&a).list_workflow_executions(&WorkflowFilter::new()).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].execution.workflow_id.as_str(), "job");
+
+        // b never registered the type, and a's worker does not run b's tasks
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let (_, history) = namespaces.storage(&b).load_workflow_execution(&WorkflowId::new("job")).await.unwrap();
+        assert!(history.is_closed());
+        assert!(history.outcome().is_some_and(|outcome| outcome.is_err()));
+
+        worker_a.shutdown(Duration::ZERO);
+        worker_b.shutdown(Duration::ZERO);
+        for run in runs {
+            run.await.unwrap().unwrap();
+        }
+    }
+}
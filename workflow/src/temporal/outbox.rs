@@ -0,0 +1,411 @@
+//! Transactional outbox for external effects of activities
+//!
+//! An activity that must notify the outside world (a webhook, a Kafka topic) stages the message
+//! with [`ActivityContext::stage_message`](super::ActivityContext::stage_message) instead of
+//! sending it. Messages staged by a successful attempt are saved together with the attempt's
+//! `ActivityTaskCompleted` event in one storage write; messages of failed attempts are dropped, so
+//! retries never stage a message twice. An [`OutboxRelay`] then publishes the saved messages and
+//! marks them sent.
+//!
+//! Delivery is at least once: a relay that stops between publishing and marking publishes the
+//! message again. Message IDs are stable, and publishers pass them on (the webhook ID header, the
+//! Kafka message key by default) so receivers can drop duplicates.
+//!
+//! Messages staged by local activities are not kept. The outbox lives in the workflow storage,
+//! which needs to support it; [`InMemoryStorage`](super::InMemoryStorage) and
+//! `SqliteStorage` do.
+//!
+//! ```rust,ignore
+//! use workflow::temporal::outbox::{OutboxDestination, OutboxRelay};
+//!
+//! async fn execute(ctx: ActivityContext, order: Order) -> Result<(), ActivityError> {
+//!     charge(&order).await?;
+//!     ctx.stage_message(OutboxDestination::kafka("orders.charged"), serde_json::json!({ "order": order.id }));
+//!     Ok(())
+//! }
+//!
+//! let relay = OutboxRelay::new(storage).with_kafka_publisher(Arc::new(KafkaOutboxPublisher::new("localhost:9092")?));
+//! tokio::spawn(async move { relay.run_relay(Duration::from_secs(1)).await });
+//! ```
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use super::activities::{
+    self, WEBHOOK_EVENT_HEADER, WEBHOOK_ID_HEADER, WEBHOOK_SIGNATURE_HEADER, WEBHOOK_TIMESTAMP_HEADER,
+};
+use super::error::StorageError;
+use super::storage::WorkflowStorage;
+use super::{WebhookNotifyActivity, WorkflowError, WorkflowExecution};
+
+/// Messages a relay reads from storage at a time, unless configured otherwise
+pub const DEFAULT_RELAY_BATCH: usize = 100;
+
+/// Where a staged message is published
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OutboxDestination {
+    /// Posted as `{"event": ..., "payload": ...}`, like [`WebhookNotifyActivity`]
+    Webhook {
+        url: String,
+        event: String,
+        /// Name of the registered secret signing the delivery; unsigned without one
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        secret: Option<String>,
+    },
+    /// Sent to a Kafka topic, keyed by `key` or else the message ID
+    Kafka {
+        topic: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key: Option<String>,
+    },
+}
+
+impl OutboxDestination {
+    pub fn webhook(url: impl Into<String>, event: impl Into<String>) -> Self {
+        Self::Webhook {
+            url: url.into(),
+            event: event.into(),
+            secret: None,
+        }
+    }
+
+    pub fn kafka(topic: impl Into<String>) -> Self {
+        Self::Kafka {
+            topic: topic.into(),
+            key: None,
+        }
+    }
+
+    /// `webhook` or `kafka`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Webhook { .. } => "webhook",
+            Self::Kafka { .. } => "kafka",
+        }
+    }
+}
+
+/// Message staged by an activity, waiting in the outbox until a relay publishes it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutboxMessage {
+    /// `{run_id}/{activity_id}/{n}`, the same for every publication of the message
+    pub id: String,
+    /// Execution whose activity staged the message
+    pub execution: WorkflowExecution,
+    pub destination: OutboxDestination,
+    pub payload: serde_json::Value,
+    pub staged_at: DateTime<Utc>,
+}
+
+/// Messages staged by one activity attempt
+#[derive(Debug, Default)]
+pub(crate) struct StagedMessages {
+    messages: Mutex<Vec<OutboxMessage>>,
+}
+
+impl StagedMessages {
+    pub(crate) fn stage(
+        &self,
+        execution: &WorkflowExecution,
+        activity_id: &str,
+        destination: OutboxDestination,
+        payload: serde_json::Value,
+    ) -> String {
+        let mut messages = self.messages.lock();
+        let id = format!("{}/{}/{}", execution.run_id, activity_id, messages.len());
+        messages.push(OutboxMessage {
+            id: id.clone(),
+            execution: execution.clone(),
+            destination,
+            payload,
+            staged_at: Utc::now(),
+        });
+        id
+    }
+
+    pub(crate) fn take(&self) -> Vec<OutboxMessage> {
+        std::mem::take(&mut *self.messages.lock())
+    }
+}
+
+/// Publishes outbox messages to their destination
+#[async_trait]
+pub trait OutboxPublisher: Send + Sync {
+    async fn publish(&self, message: &OutboxMessage) -> Result<(), WorkflowError>;
+}
+
+/// Posts [`OutboxDestination::Webhook`] messages, with the message ID as [`WEBHOOK_ID_HEADER`]
+///
+/// Deliveries are signed with the secrets registered through
+/// [`WebhookNotifyActivity::register_secret`]. Error statuses fail the publication.
+#[derive(Default)]
+pub struct WebhookPublisher {
+    http: reqwest::Client,
+}
+
+impl WebhookPublisher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl OutboxPublisher for WebhookPublisher {
+    async fn publish(&self, message: &OutboxMessage) -> Result<(), WorkflowError> {
+        let OutboxDestination::Webhook { url, event, secret } = &message.destination else {
+            return Err(WorkflowError::Custom(format!("not a webhook message: {}", message.id)));
+        };
+        let body = serde_json::to_vec(&serde_json::json!({"event": event, "payload": message.payload}))?;
+        let mut request = self
+            .http
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(WEBHOOK_EVENT_HEADER, event)
+            .header(WEBHOOK_ID_HEADER, &message.id);
+        if let Some(name) = secret {
+            let secret = activities::registered_secret(name)
+                .ok_or_else(|| WorkflowError::Custom(format!("webhook secret {:?} is not registered", name)))?;
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs().to_string();
+            request = request
+                .header(WEBHOOK_SIGNATURE_HEADER, WebhookNotifyActivity::sign(secret, &timestamp, &body))
+                .header(WEBHOOK_TIMESTAMP_HEADER, timestamp);
+        }
+        let response = request
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| WorkflowError::Custom(format!("POST {}: {}", url, e)))?;
+        if !response.status().is_success() {
+            return Err(WorkflowError::Custom(format!("POST {} returned {}", url, response.status())));
+        }
+        Ok(())
+    }
+}
+
+/// Publishes the messages in the outbox of a workflow storage and marks them sent
+///
+/// Webhook messages go through a [`WebhookPublisher`]; Kafka messages need a publisher such as
+/// `KafkaOutboxPublisher` of the `kafka` feature and stay in the outbox without one. Run one relay
+/// per storage backend: relays sharing a backend publish the same messages.
+pub struct OutboxRelay {
+    storage: Arc<dyn WorkflowStorage>,
+    webhooks: Arc<dyn OutboxPublisher>,
+    kafka: Option<Arc<dyn OutboxPublisher>>,
+    batch_size: usize,
+}
+
+impl OutboxRelay {
+    pub fn new(storage: Arc<dyn WorkflowStorage>) -> Self {
+        Self {
+            storage,
+            webhooks: Arc::new(WebhookPublisher::new()),
+            kafka: None,
+            batch_size: DEFAULT_RELAY_BATCH,
+        }
+    }
+
+    pub fn with_webhook_publisher(mut self, publisher: Arc<dyn OutboxPublisher>) -> Self {
+        self.webhooks = publisher;
+        self
+    }
+
+    pub fn with_kafka_publisher(mut self, publisher: Arc<dyn OutboxPublisher>) -> Self {
+        self.kafka = Some(publisher);
+        self
+    }
+
+    /// Messages read from storage at a time, [`DEFAULT_RELAY_BATCH`] by default
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    fn publisher(&self, destination: &OutboxDestination) -> Option<&Arc<dyn OutboxPublisher>> {
+        match destination {
+            OutboxDestination::Webhook { .. } => Some(&self.webhooks),
+            OutboxDestination::Kafka { .. } => self.kafka.as_ref(),
+        }
+    }
+
+    /// Publish a batch of pending messages, oldest first; returns how many were published
+    ///
+    /// Messages that fail to publish are logged and stay in the outbox for the next pass.
+    pub async fn relay_pending(&self) -> Result<usize, StorageError> {
+        let pending = self.storage.pending_outbox(self.batch_size).await?;
+        let mut sent = Vec::new();
+        for message in pending {
+            let kind = message.destination.kind();
+            let Some(publisher) = self.publisher(&message.destination) else {
+                tracing::warn!(message = %message.id, destination = kind, "no publisher for outbox message");
+                continue;
+            };
+            let outcome = match publisher.publish(&message).await {
+                Ok(()) => {
+                    sent.push(message.id);
+                    "published"
+                }
+                Err(e) => {
+                    let execution = &message.execution;
+                    tracing::warn!(message = %message.id, %execution, error = %e, "outbox message not published");
+                    "failed"
+                }
+            };
+            metrics::counter!(super::metrics::OUTBOX_PUBLICATIONS, "destination" => kind, "outcome" => outcome)
+                .increment(1);
+        }
+        if !sent.is_empty() {
+            self.storage.mark_outbox_sent(&sent).await?;
+        }
+        Ok(sent.len())
+    }
+
+    /// Publish pending messages every `interval` until the task is aborted, logging failures
+    ///
+    /// A full batch is followed by the next one right away.
+    pub async fn run_relay(&self, interval: Duration) {
+        loop {
+            match self.relay_pending().await {
+                Ok(sent) if sent >= self.batch_size => continue,
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, "outbox relay failed"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::temporal::event::EventType;
+    use crate::temporal::{
+        Activity, ActivityContext, ActivityError, ActivityOptions, InMemoryStorage, RetryPolicy, StartWorkflowOptions,
+        WorkerConfig, Workflow, WorkflowContext, WorkflowWorker,
+    };
+
+    /// Records publications, failing the first `failures` of them
+    #[derive(Default)]
+    struct Recorder {
+        published: Mutex<Vec<OutboxMessage>>,
+        failures: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl OutboxPublisher for Recorder {
+        async fn publish(&self, message: &OutboxMessage) -> Result<(), WorkflowError> {
+            let mut failures = self.failures.lock();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(WorkflowError::Custom("broker unavailable".to_string()));
+            }
+            self.published.lock().push(message.clone());
+            Ok(())
+        }
+    }
+
+    struct Charge;
+
+    impl Activity for Charge {
+        type Input = u64;
+        type Output = u64;
+
+        fn name() -> &'static str {
+            "charge"
+        }
+
+        async fn execute(ctx: ActivityContext, cents: u64) -> Result<u64, ActivityError> {
+            ctx.stage_message(OutboxDestination::kafka("payments"), serde_json::json!({ "cents": cents }));
+            if ctx.attempt() < 2 {
+                return Err(ActivityError::TemporaryFailure("gateway timeout".to_string()));
+            }
+            Ok(cents)
+        }
+    }
+
+    struct Checkout;
+
+    impl Workflow for Checkout {
+        type Input = u64;
+        type Output = u64;
+
+        fn name() -> &'static str {
+            "checkout"
+        }
+
+        async fn execute(ctx: WorkflowContext, cents: u64) -> Result<u64, WorkflowError> {
+            let options = ActivityOptions {
+                retry_policy: Some(RetryPolicy {
+                    initial_interval: Duration::from_millis(1),
+                    ..RetryPolicy::default()
+                }),
+                ..ActivityOptions::default()
+            };
+            ctx.execute_activity::<Charge>(cents, options).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_messages_of_successful_attempts_are_relayed_once() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                ..WorkerConfig::default()
+            })
+            .with_storage(storage.clone()),
+        );
+        worker.register_workflow::<Checkout>();
+        worker.register_activity::<Charge>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+        let handle = worker.client().start_workflow::<Checkout>(250, StartWorkflowOptions::default()).await.unwrap();
+        assert_eq!(handle.result().await.unwrap(), 250);
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+
+        // Only the second attempt's message is staged, with its completion
+        let pending = storage.pending_outbox(10).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].execution, *handle.execution());
+        assert_eq!(pending[0].payload, serde_json::json!({ "cents": 250 }));
+        let history = handle.history().await.unwrap();
+        assert!(history.events().iter().any(|e| matches!(e.event_type, EventType::ActivityTaskCompleted { .. })));
+
+        let kafka = Arc::new(Recorder::default());
+        *kafka.failures.lock() = 1;
+        let relay = OutboxRelay::new(storage.clone()).with_kafka_publisher(kafka.clone());
+        assert_eq!(relay.relay_pending().await.unwrap(), 0);
+        assert_eq!(relay.relay_pending().await.unwrap(), 1);
+        assert_eq!(relay.relay_pending().await.unwrap(), 0);
+        assert_eq!(*kafka.published.lock(), pending);
+    }
+
+    #[tokio::test]
+    async fn test_messages_without_publisher_stay_pending() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let execution = WorkflowExecution::new(crate::temporal::WorkflowId::new("order-9"));
+        let staged = StagedMessages::default();
+        let first = staged.stage(&execution, "notify-1", OutboxDestination::kafka("orders"), serde_json::json!(1));
+        let webhook = OutboxDestination::webhook("http://hooks", "paid");
+        let second = staged.stage(&execution, "notify-1", webhook, serde_json::json!(2));
+        assert_eq!(first, format!("{}/notify-1/0", execution.run_id));
+        assert_eq!(second, format!("{}/notify-1/1", execution.run_id));
+        let history = crate::temporal::event::EventHistory::new();
+        storage.save_with_outbox(&execution, &history, &staged.take()).await.unwrap();
+        assert!(staged.take().is_empty());
+
+        let webhooks = Arc::new(Recorder::default());
+        let relay = OutboxRelay::new(storage.clone()).with_webhook_publisher(webhooks.clone());
+        assert_eq!(relay.relay_pending().await.unwrap(), 1);
+        let pending = storage.pending_outbox(10).await.unwrap();
+        assert_eq!(pending.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec![first.as_str()]);
+        assert_eq!(webhooks.published.lock()[0].id, second);
+    }
+}
@@ -1,7 +1,15 @@
 //! Query definitions and handling
 
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
 use serde::{Serialize, de::DeserializeOwned};
 
+use super::WorkflowId;
+use super::error::QueryError;
+
 /// Query trait - defines the query interface
 pub trait Query: Send + 'static {
     /// Query name
@@ -11,6 +19,39 @@ pub trait Query: Send + 'static {
     type Result: Serialize + DeserializeOwned + Send;
 }
 
+/// Callback answering one query from the current workflow state
+pub(crate) type QueryHandler = Arc<dyn Fn() -> Result<serde_json::Value, QueryError> + Send + Sync>;
+
+/// Query handlers registered by one execution
+#[derive(Default)]
+pub(crate) struct QueryHandlers {
+    handlers: Mutex<HashMap<String, QueryHandler>>,
+}
+
+impl QueryHandlers {
+    /// Answer queries of a name with `handler`, replacing any earlier handler
+    pub(crate) fn set_handler(&self, query_name: &str, handler: QueryHandler) {
+        self.handlers.lock().insert(query_name.to_string(), handler);
+    }
+
+    pub(crate) fn answer(&self, query_name: &str) -> Result<serde_json::Value, QueryError> {
+        let handler = self
+            .handlers
+            .lock()
+            .get(query_name)
+            .cloned()
+            .ok_or_else(|| QueryError::QueryNotRegistered(query_name.to_string()))?;
+        handler()
+    }
+}
+
+/// Answers queries against the executions a worker is running
+#[async_trait]
+pub(crate) trait QueryDispatcher: Send + Sync {
+    /// [`QueryError::WorkflowNotRunning`] if the execution is not running here
+    async fn query(&self, workflow_id: &WorkflowId, query_name: &str) -> Result<serde_json::Value, QueryError>;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -35,5 +76,13 @@ mod tests {
     fn test_query_name() {
         assert_eq!(TestQuery::name(), "test_query");
     }
+
+    #[test]
+    fn test_handlers_answer_by_name() {
+        let handlers = QueryHandlers::default();
+        assert!(matches!(handlers.answer("test_query"), Err(QueryError::QueryNotRegistered(_))));
+        handlers.set_handler("test_query", Arc::new(|| Ok(serde_json::json!({ "value": 7 }))));
+        assert_eq!(handlers.answer("test_query").unwrap()["value"], 7);
+    }
 }
 
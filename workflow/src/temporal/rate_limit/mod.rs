@@ -0,0 +1,225 @@
+//! Rate limits of activity types
+//!
+//! Activities calling third-party systems often have to stay under a rate, such as 50 payments a
+//! second against a payment gateway. A worker given a [`RateLimiter`] asks it for a permit before
+//! starting each activity attempt; an attempt over the limit is not run but put back on its task
+//! queue once the limiter expects a permit to be free, without counting as a failed attempt.
+//!
+//! [`LocalRateLimiter`] keeps a token bucket per activity type in the worker's process, so the
+//! limits hold per worker. With the `database` feature, [`RedisRateLimiter`] keeps the buckets in
+//! Redis, so they hold for all workers together.
+//!
+//! ```rust,ignore
+//! let limiter = LocalRateLimiter::new().limit("process_payment", RateLimit::per_second(50));
+//! let worker = WorkflowWorker::new(WorkerConfig::default()).with_rate_limiter(Arc::new(limiter));
+//! ```
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+
+#[cfg(feature = "database")]
+pub mod redis_bucket;
+#[cfg(feature = "database")]
+pub use self::redis_bucket::RedisRateLimiter;
+
+/// Sustained rate and burst of an activity type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Attempts started per second in the long run
+    pub per_second: f64,
+    /// Attempts that may start at once after a quiet period
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// `n` attempts a second with a burst of `n`
+    pub fn per_second(n: u32) -> Self {
+        Self {
+            per_second: f64::from(n),
+            burst: n.max(1),
+        }
+    }
+
+    /// `n` attempts a minute with a burst of `n`
+    pub fn per_minute(n: u32) -> Self {
+        Self {
+            per_second: f64::from(n) / 60.0,
+            burst: n.max(1),
+        }
+    }
+
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// Time until a bucket holding `tokens` has a whole one
+    pub(crate) fn wait_for_token(&self, tokens: f64) -> Duration {
+        Duration::from_secs_f64((1.0 - tokens).max(0.0) / self.per_second.max(f64::MIN_POSITIVE))
+    }
+}
+
+/// Decides whether an activity attempt may start now
+#[async_trait]
+pub trait RateLimiter: Send + Sync {
+    /// Take a permit for an attempt of `activity_type`, or return how long to wait before trying again
+    ///
+    /// Activity types without a limit always get one.
+    async fn acquire(&self, activity_type: &str) -> Result<(), Duration>;
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets per activity type, kept in this process
+#[derive(Default)]
+pub struct LocalRateLimiter {
+    limits: HashMap<String, RateLimit>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl LocalRateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the attempts of `activity_type`
+    pub fn limit(mut self, activity_type: impl Into<String>, limit: RateLimit) -> Self {
+        self.limits.insert(activity_type.into(), limit);
+        self
+    }
+
+    /// Limit configured for `activity_type`, if any
+    pub fn limit_of(&self, activity_type: &str) -> Option<RateLimit> {
+        self.limits.get(activity_type).copied()
+    }
+}
+
+#[async_trait]
+impl RateLimiter for LocalRateLimiter {
+    async fn acquire(&self, activity_type: &str) -> Result<(), Duration> {
+        let Some(limit) = self.limit_of(activity_type) else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock();
+        let bucket = buckets.entry(activity_type.to_string()).or_insert(Bucket {
+            tokens: f64::from(limit.burst),
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.per_second).min(f64::from(limit.burst));
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(limit.wait_for_token(bucket.tokens))
+        }
+    }
+}
+
+impl std::fmt::Debug for LocalRateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalRateLimiter").field("limits", &self.limits).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::future::join_all;
+
+    use crate::temporal::{
+        Activity, ActivityContext, ActivityError, ActivityOptions, StartWorkflowOptions, WorkerConfig, Workflow,
+        WorkflowContext, WorkflowError, WorkflowWorker,
+    };
+
+    #[tokio::test]
+    async fn test_buckets_refill_per_activity_type() {
+        let limiter = LocalRateLimiter::new().limit("charge", RateLimit::per_second(20).burst(2));
+        assert!(limiter.acquire("charge").await.is_ok());
+        assert!(limiter.acquire("charge").await.is_ok());
+        let retry_after = limiter.acquire("charge").await.unwrap_err();
+        assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_millis(50), "{retry_after:?}");
+        for _ in 0..10 {
+            assert!(limiter.acquire("refund").await.is_ok());
+        }
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(limiter.acquire("charge").await.is_ok());
+    }
+
+    static CHARGES: AtomicUsize = AtomicUsize::new(0);
+
+    struct Charge;
+
+    impl Activity for Charge {
+        type Input = u32;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "charge"
+        }
+
+        async fn execute(_ctx: ActivityContext, cents: u32) -> Result<u32, ActivityError> {
+            CHARGES.fetch_add(1, Ordering::SeqCst);
+            Ok(cents)
+        }
+    }
+
+    struct Checkout;
+
+    impl Workflow for Checkout {
+        type Input = Vec<u32>;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "checkout"
+        }
+
+        async fn execute(ctx: WorkflowContext, payments: Vec<u32>) -> Result<u32, WorkflowError> {
+            let charges = payments
+                .into_iter()
+                .map(|cents| ctx.execute_activity::<Charge>(cents, ActivityOptions::default()));
+            join_all(charges).await.into_iter().sum()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_attempts_over_the_limit_are_rescheduled() {
+        let limiter = LocalRateLimiter::new().limit("charge", RateLimit::per_second(20).burst(1));
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(20),
+                ..WorkerConfig::default()
+            })
+            .with_rate_limiter(Arc::new(limiter)),
+        );
+        worker.register_workflow::<Checkout>();
+        worker.register_activity::<Charge>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let started = Instant::now();
+        let handle = worker
+            .client()
+            .start_workflow::<Checkout>(vec![100, 200, 300, 400], StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(handle.result().await.unwrap(), 1000);
+        // One charge right away, the others one per 50ms
+        assert!(started.elapsed() >= Duration::from_millis(140), "{:?}", started.elapsed());
+        assert_eq!(CHARGES.load(Ordering::SeqCst), 4);
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+//! Rate limits shared by all workers through Redis
+//!
+//! Each limited activity type has a token bucket in a Redis hash, `{prefix}:{activity_type}`,
+//! refilled and drawn from by a Lua script, so the limit holds for the whole fleet however many
+//! workers run. The script reads the time from Redis, so the clocks of the workers do not matter.
+//! Buckets expire once they would have refilled completely.
+//!
+//! When Redis cannot be reached, attempts are put back on their queue for a second rather than run
+//! unlimited.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+
+use super::{RateLimit, RateLimiter};
+use crate::temporal::error::StorageError;
+
+const DEFAULT_PREFIX: &str = "workflow:rate";
+
+/// How long attempts wait while the buckets cannot be reached
+const RETRY_ON_ERROR: Duration = Duration::from_secs(1);
+
+/// Take a token from the bucket in KEYS[1], refilled at ARGV[1] a second up to ARGV[2]; returns the
+/// milliseconds to wait for one, 0 when it was taken
+const TAKE_TOKEN: &str = r"
+local rate = tonumber(ARGV[1])
+local burst = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'updated')
+local tokens = tonumber(bucket[1]) or burst
+local updated = tonumber(bucket[2]) or now
+tokens = math.min(burst, tokens + math.max(0, now - updated) * rate / 1000)
+local wait = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+else
+    wait = math.ceil((1 - tokens) * 1000 / rate)
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(burst * 1000 / rate) + 1000)
+return wait
+";
+
+/// Token buckets per activity type, kept in Redis
+pub struct RedisRateLimiter {
+    commands: ConnectionManager,
+    prefix: String,
+    limits: HashMap<String, RateLimit>,
+    script: redis::Script,
+}
+
+impl RedisRateLimiter {
+    /// Connect to the Redis server at `url`, e.g. `redis://127.0.0.1/`
+    pub async fn connect(url: &str) -> Result<Self, StorageError> {
+        let client = redis::Client::open(url).map_err(|e| StorageError::ConnectionError(e.to_string()))?;
+        let commands = ConnectionManager::new(client)
+            .await
+            .map_err(|e| StorageError::ConnectionError(e.to_string()))?;
+        Ok(Self {
+            commands,
+            prefix: DEFAULT_PREFIX.to_string(),
+            limits: HashMap::new(),
+            script: redis::Script::new(TAKE_TOKEN),
+        })
+    }
+
+    /// Prefix of the bucket keys, `workflow:rate` by default
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Limit the attempts of `activity_type` across all workers using the same buckets
+    pub fn limit(mut self, activity_type: impl Into<String>, limit: RateLimit) -> Self {
+        self.limits.insert(activity_type.into(), limit);
+        self
+    }
+
+    /// Limit configured for `activity_type`, if any
+    pub fn limit_of(&self, activity_type: &str) -> Option<RateLimit> {
+        self.limits.get(activity_type).copied()
+    }
+}
+
+#[async_trait]
+impl RateLimiter for RedisRateLimiter {
+    async fn acquire(&self, activity_type: &str) -> Result<(), Duration> {
+        let Some(limit) = self.limit_of(activity_type) else {
+            return Ok(());
+        };
+        let mut conn = self.commands.clone();
+        let wait: Result<u64, _> = self
+            .script
+            .key(format!("{}:{}", self.prefix, activity_type))
+            .arg(limit.per_second)
+            .arg(limit.burst)
+            .invoke_async(&mut conn)
+            .await;
+        match wait {
+            Ok(0) => Ok(()),
+            Ok(ms) => Err(Duration::from_millis(ms)),
+            Err(e) => {
+                tracing::warn!(activity_type, error = %e, "rate limit bucket unreachable");
+                Err(RETRY_ON_ERROR)
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for RedisRateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisRateLimiter")
+            .field("prefix", &self.prefix)
+            .field("limits", &self.limits)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs against the server in `REDIS_URL`, skipped without one
+    async fn limiter(prefix: &str) -> Option<RedisRateLimiter> {
+        let url = std::env::var("REDIS_URL").ok()?;
+        let limiter = RedisRateLimiter::connect(&url).await.unwrap().with_prefix(prefix);
+        Some(limiter.limit("charge", RateLimit::per_second(10).burst(3)))
+    }
+
+    #[tokio::test]
+    async fn test_workers_share_the_bucket() {
+        let prefix = format!("test:{}", uuid::Uuid::new_v4());
+        let Some(first) = limiter(&prefix).await else { return };
+        let second = limiter(&prefix).await.unwrap();
+
+        assert!(first.acquire("charge").await.is_ok());
+        assert!(second.acquire("charge").await.is_ok());
+        assert!(first.acquire("charge").await.is_ok());
+        let retry_after = second.acquire("charge").await.unwrap_err();
+        assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_millis(100), "{retry_after:?}");
+        assert!(second.acquire("refund").await.is_ok());
+
+        tokio::time::sleep(retry_after).await;
+        assert!(first.acquire("charge").await.is_ok());
+    }
+}
@@ -0,0 +1,530 @@
+//! Replaying recorded histories to check workflow code for non-determinism
+//!
+//! A replaying execution runs the workflow code against a recorded history instead of a worker.
+//! Every event the code records is compared with the next event of the history rather than
+//! appended; signals, updates and cancellation requests are handed to the code once the events
+//! recorded before them have been matched, and activity attempts complete with their recorded outcome.
+//! Marker events (side effects, versions, local activities, conditions, external signals) are answered
+//! from the history as usual, so a marker the code records during replay is one the history lacks.
+//!
+//! A worker taking over an open run, e.g. one handed back at shutdown or retried after a crash,
+//! re-executes its code [resuming](Replay::resuming) from the stored history: events the code
+//! records again are matched with the stored ones instead of being appended, activities and timers
+//! that already finished return their recorded outcome, recorded signals are queued for the code
+//! again, and recorded updates and cancellation requests are handed to it as it catches up. Events
+//! the history lacks are appended as usual.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+
+use super::error::StorageError;
+use super::event::{EventHistory, EventType, WorkflowEvent};
+use super::task_queue::{Task, TaskKind, TaskQueue};
+use super::worker::PendingActivities;
+use super::{ActivityError, EventId, WorkflowError};
+
+/// Why replaying a history failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The history cannot be replayed at all, e.g. it does not start with a start event of the workflow type
+    InvalidHistory(String),
+    /// The workflow code recorded a different event than the one at `event_id`
+    Mismatch {
+        event_id: EventId,
+        expected: String,
+        produced: String,
+    },
+    /// The workflow code closed or stopped making progress without recording the event at `event_id`
+    MissingEvent { event_id: EventId, expected: String },
+    /// The workflow code recorded an event where the history had already closed, at `event_id`
+    UnexpectedEvent { event_id: EventId, produced: String },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::InvalidHistory(msg) => write!(f, "invalid history: {}", msg),
+            ReplayError::Mismatch { event_id, expected, produced } => write!(
+                f,
+                "non-deterministic workflow: event {} is {} in the history but the workflow produced {}",
+                event_id, expected, produced
+            ),
+            ReplayError::MissingEvent { event_id, expected } => write!(
+                f,
+                "non-deterministic workflow: event {} ({}) is in the history but the workflow did not produce it",
+                event_id, expected
+            ),
+            ReplayError::UnexpectedEvent { event_id, produced } => write!(
+                f,
+                "non-deterministic workflow: the workflow produced {} as event {}, after the history closed",
+                produced, event_id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Short description of an event: its kind and the IDs that tie it to the workflow code
+pub(crate) fn describe(event_type: &EventType) -> String {
+    match event_type {
+        EventType::WorkflowExecutionStarted { workflow_type, .. } => format!("WorkflowExecutionStarted({})", workflow_type),
+        EventType::WorkflowExecutionCompleted { .. } => "WorkflowExecutionCompleted".to_string(),
+        EventType::WorkflowExecutionFailed { failure } => format!("WorkflowExecutionFailed({})", failure),
+        EventType::WorkflowExecutionTerminated { reason } => format!("WorkflowExecutionTerminated({})", reason),
+        EventType::WorkflowExecutionContinuedAsNew { .. } => "WorkflowExecutionContinuedAsNew".to_string(),
+        EventType::WorkflowExecutionSignaled { signal_name, .. } => format!("WorkflowExecutionSignaled({})", signal_name),
+        EventType::WorkflowExecutionUpdateAccepted { update_id, update_name, .. } => {
+            format!("WorkflowExecutionUpdateAccepted({}, {})", update_name, update_id)
+        }
+        EventType::WorkflowExecutionUpdateCompleted { update_id, .. } => {
+            format!("WorkflowExecutionUpdateCompleted({})", update_id)
+        }
+        EventType::ActivityTaskScheduled { activity_id, activity_type, .. } => {
+            format!("ActivityTaskScheduled({}, {})", activity_type, activity_id.0)
+        }
+        EventType::ActivityTaskStarted { activity_id } => format!("ActivityTaskStarted({})", activity_id.0),
+        EventType::ActivityTaskCompleted { activity_id, .. } => format!("ActivityTaskCompleted({})", activity_id.0),
+        EventType::ActivityTaskFailed { activity_id, .. } => format!("ActivityTaskFailed({})", activity_id.0),
+        EventType::CompensationStarted { step, activity_type } => format!("CompensationStarted({}, {})", step, activity_type),
+        EventType::CompensationCompleted { step, activity_type } => {
+            format!("CompensationCompleted({}, {})", step, activity_type)
+        }
+        EventType::CompensationFailed { step, activity_type, .. } => format!("CompensationFailed({}, {})", step, activity_type),
+        EventType::VersionMarker { change_id, .. } => format!("VersionMarker({})", change_id),
+        EventType::SideEffectRecorded { seq, .. } => format!("SideEffectRecorded({})", seq),
+        EventType::LocalActivityMarker { seq, activity_type, .. } => format!("LocalActivityMarker({}, {})", activity_type, seq),
+        EventType::ActivityMemoMarker { seq, activity_type, .. } => format!("ActivityMemoMarker({}, {})", activity_type, seq),
+        EventType::ConditionMarker { seq, .. } => format!("ConditionMarker({})", seq),
+        EventType::SignalExternalWorkflow { workflow_id, signal_name, .. } => {
+            format!("SignalExternalWorkflow({}, {})", workflow_id, signal_name)
+        }
+        EventType::WorkflowExecutionCancelRequested => "WorkflowExecutionCancelRequested".to_string(),
+        EventType::UpsertSearchAttributes { .. } => "UpsertSearchAttributes".to_string(),
+        EventType::WorkflowMemoSet { .. } => "WorkflowMemoSet".to_string(),
+        EventType::StateTransitioned { from, to, event } => format!("StateTransitioned({} -{}-> {})", from, event, to),
+        EventType::TimerStarted { timer_id, .. } => format!("TimerStarted({})", timer_id),
+        EventType::TimerFired { timer_id } => format!("TimerFired({})", timer_id),
+        EventType::TimerCancelled { timer_id } => format!("TimerCancelled({})", timer_id),
+        EventType::HumanTaskCreated { task_id, assignee, .. } => format!("HumanTaskCreated({}, {})", task_id, assignee),
+        EventType::HumanTaskEscalated { task_id, assignee } => format!("HumanTaskEscalated({}, {})", task_id, assignee),
+        EventType::HumanTaskCompleted { task_id } => format!("HumanTaskCompleted({})", task_id),
+        EventType::HumanTaskTimedOut { task_id } => format!("HumanTaskTimedOut({})", task_id),
+        EventType::SessionCreated { session_id, .. } => format!("SessionCreated({})", session_id),
+        EventType::SessionCompleted { session_id } => format!("SessionCompleted({})", session_id),
+        EventType::SessionExpired { session_id } => format!("SessionExpired({})", session_id),
+        EventType::SessionFailed { session_id, .. } => format!("SessionFailed({})", session_id),
+        EventType::LockAcquired { lock_id, name } => format!("LockAcquired({}, {})", lock_id, name),
+        EventType::LockTimedOut { lock_id, name } => format!("LockTimedOut({}, {})", lock_id, name),
+        EventType::LockReleased { lock_id, name } => format!("LockReleased({}, {})", lock_id, name),
+        EventType::LockLost { lock_id, name } => format!("LockLost({}, {})", lock_id, name),
+    }
+}
+
+/// Whether the code recording `produced` matches the recorded event
+///
+/// Activities, timers and compensations must keep their IDs and types; inputs and results may
+/// change. The outcome of the workflow itself must stay the same.
+fn same_event(recorded: &EventType, produced: &EventType) -> bool {
+    use EventType::*;
+
+    match (recorded, produced) {
+        (
+            ActivityTaskScheduled { activity_id: a, activity_type: t, .. },
+            ActivityTaskScheduled { activity_id: b, activity_type: u, .. },
+        ) => a == b && t == u,
+        (ActivityTaskStarted { activity_id: a }, ActivityTaskStarted { activity_id: b })
+        | (ActivityTaskCompleted { activity_id: a, .. }, ActivityTaskCompleted { activity_id: b, .. })
+        | (ActivityTaskFailed { activity_id: a, .. }, ActivityTaskFailed { activity_id: b, .. }) => a == b,
+        (CompensationStarted { step: a, activity_type: t }, CompensationStarted { step: b, activity_type: u })
+        | (CompensationCompleted { step: a, activity_type: t }, CompensationCompleted { step: b, activity_type: u })
+        | (
+            CompensationFailed { step: a, activity_type: t, .. },
+            CompensationFailed { step: b, activity_type: u, .. },
+        ) => a == b && t == u,
+        (TimerStarted { timer_id: a, .. }, TimerStarted { timer_id: b, .. })
+        | (TimerFired { timer_id: a }, TimerFired { timer_id: b })
+        | (TimerCancelled { timer_id: a }, TimerCancelled { timer_id: b }) => a == b,
+        (
+            HumanTaskCreated { task_id: a, assignee: t, .. },
+            HumanTaskCreated { task_id: b, assignee: u, .. },
+        )
+        | (HumanTaskEscalated { task_id: a, assignee: t }, HumanTaskEscalated { task_id: b, assignee: u }) => {
+            a == b && t == u
+        }
+        (HumanTaskCompleted { task_id: a }, HumanTaskCompleted { task_id: b })
+        | (HumanTaskTimedOut { task_id: a }, HumanTaskTimedOut { task_id: b }) => a == b,
+        // Sessions land on whichever worker runs the code, so only their IDs must match
+        (SessionCreated { session_id: a, .. }, SessionCreated { session_id: b, .. })
+        | (SessionCompleted { session_id: a }, SessionCompleted { session_id: b })
+        | (SessionExpired { session_id: a }, SessionExpired { session_id: b })
+        | (SessionFailed { session_id: a, .. }, SessionFailed { session_id: b, .. }) => a == b,
+        (LockAcquired { lock_id: a, name: t }, LockAcquired { lock_id: b, name: u })
+        | (LockTimedOut { lock_id: a, name: t }, LockTimedOut { lock_id: b, name: u })
+        | (LockReleased { lock_id: a, name: t }, LockReleased { lock_id: b, name: u })
+        | (LockLost { lock_id: a, name: t }, LockLost { lock_id: b, name: u }) => a == b && t == u,
+        (UpsertSearchAttributes { .. }, UpsertSearchAttributes { .. }) => true,
+        (StateTransitioned { from: a, to: b, event: e }, StateTransitioned { from: c, to: d, event: f }) => {
+            a == c && b == d && e == f
+        }
+        (WorkflowExecutionCompleted { result: a }, WorkflowExecutionCompleted { result: b }) => a == b,
+        (WorkflowExecutionFailed { failure: a }, WorkflowExecutionFailed { failure: b }) => a == b,
+        (WorkflowExecutionContinuedAsNew { input: a, .. }, WorkflowExecutionContinuedAsNew { input: b, .. }) => a == b,
+        _ => false,
+    }
+}
+
+/// Whether a resumed run reproduces `recorded`; a session recreated on another host replaces the one that went away
+fn resumes(recorded: &EventType, produced: &EventType) -> bool {
+    match (recorded, produced) {
+        (
+            EventType::SessionCreated { host_task_queue: a, .. },
+            EventType::SessionCreated { host_task_queue: b, .. },
+        ) if a != b => false,
+        _ => same_event(recorded, produced),
+    }
+}
+
+fn is_input(event_type: &EventType) -> bool {
+    matches!(
+        event_type,
+        EventType::WorkflowExecutionSignaled { .. }
+            | EventType::WorkflowExecutionCancelRequested
+            | EventType::WorkflowExecutionUpdateAccepted { .. }
+            | EventType::WorkflowExecutionUpdateCompleted { .. }
+    )
+}
+
+fn is_marker(event_type: &EventType) -> bool {
+    matches!(
+        event_type,
+        EventType::VersionMarker { .. }
+            | EventType::SideEffectRecorded { .. }
+            | EventType::LocalActivityMarker { .. }
+            | EventType::ActivityMemoMarker { .. }
+            | EventType::ConditionMarker { .. }
+            | EventType::SignalExternalWorkflow { .. }
+    )
+}
+
+fn is_terminated(event_type: &EventType) -> bool {
+    matches!(event_type, EventType::WorkflowExecutionTerminated { .. })
+}
+
+fn is_closing(event_type: &EventType) -> bool {
+    matches!(
+        event_type,
+        EventType::WorkflowExecutionCompleted { .. }
+            | EventType::WorkflowExecutionFailed { .. }
+            | EventType::WorkflowExecutionContinuedAsNew { .. }
+    )
+}
+
+struct Cursor {
+    /// Recorded events after the start event, without markers
+    expected: Vec<WorkflowEvent>,
+    position: usize,
+    /// ID the next event appended to the history would get
+    next_event_id: EventId,
+    error: Option<ReplayError>,
+}
+
+/// Replay state of an execution, see the [module docs](self)
+pub(crate) struct Replay {
+    cursor: Mutex<Cursor>,
+    closed: bool,
+    /// Whether the code runs on from the end of the history instead of only checking it
+    resuming: bool,
+    /// Notified once the history of an open run is used up or replay has failed
+    finished: Notify,
+}
+
+impl Replay {
+    pub(crate) fn new(history: &EventHistory) -> Self {
+        let expected = history
+            .events()
+            .iter()
+            .skip(1)
+            // The memo is recorded by the client starting the run, never by the code
+            .filter(|event| !matches!(event.event_type, EventType::WorkflowMemoSet { .. }))
+            .filter(|event| !is_marker(&event.event_type) && !is_terminated(&event.event_type))
+            .cloned()
+            .collect();
+        Self {
+            cursor: Mutex::new(Cursor {
+                expected,
+                position: 0,
+                next_event_id: history.events().last().map_or(EventId(1), |event| event.event_id.next()),
+                error: None,
+            }),
+            // The code of a terminated run never closed it, replay it as the open run it was
+            closed: history.is_closed() && !history.is_terminated(),
+            resuming: false,
+            finished: Notify::new(),
+        }
+    }
+
+    /// Replay state of an open run taken over by a worker
+    ///
+    /// Events recorded again are matched with the stored ones in any order, and events the
+    /// history lacks are appended instead of failing the replay.
+    pub(crate) fn resuming(history: &EventHistory) -> Self {
+        let mut replay = Self::new(history);
+        replay.resuming = true;
+        let expected = &mut replay.cursor.get_mut().expected;
+        // Search attributes passed when starting are never recorded by the code
+        expected.retain(|event| {
+            !(event.event_id == EventId(2) && matches!(event.event_type, EventType::UpsertSearchAttributes { .. }))
+        });
+        // Signals queue until the code takes them, so the recorded ones are handed over first,
+        // ahead of any arriving after the takeover
+        let (signals, rest): (Vec<_>, Vec<_>) = std::mem::take(expected)
+            .into_iter()
+            .partition(|event| matches!(event.event_type, EventType::WorkflowExecutionSignaled { .. }));
+        *expected = signals.into_iter().chain(rest).collect();
+        replay
+    }
+
+    pub(crate) fn is_resuming(&self) -> bool {
+        self.resuming
+    }
+
+    /// Signals and cancellation requests recorded after the events matched so far
+    pub(crate) fn take_inputs(&self) -> Vec<EventType> {
+        let mut cursor = self.cursor.lock();
+        let mut inputs = Vec::new();
+        while let Some(event) = cursor.expected.get(cursor.position)
+            && is_input(&event.event_type)
+        {
+            inputs.push(event.event_type.clone());
+            cursor.position += 1;
+        }
+        if !self.closed && cursor.position == cursor.expected.len() {
+            self.finished.notify_one();
+        }
+        inputs
+    }
+
+    /// Match an event the workflow code records against the history
+    ///
+    /// `false` if a resuming run should append the event, as the history lacks it.
+    pub(crate) fn produced(&self, produced: &EventType) -> Result<bool, WorkflowError> {
+        let mut cursor = self.cursor.lock();
+        if let Some(error) = &cursor.error {
+            return Err(WorkflowError::Custom(error.to_string()));
+        }
+        if self.resuming {
+            // Inputs recorded now arrived after the takeover
+            if is_input(produced) {
+                return Ok(false);
+            }
+            let position = cursor.position;
+            let found = cursor.expected[position..]
+                .iter()
+                .position(|expected| !is_input(&expected.event_type) && resumes(&expected.event_type, produced));
+            match found {
+                Some(0) => cursor.position += 1,
+                Some(offset) => {
+                    cursor.expected.remove(position + offset);
+                }
+                None => return Ok(false),
+            }
+            return Ok(true);
+        }
+        loop {
+            let Some(expected) = cursor.expected.get(cursor.position) else {
+                // Progress past the end of an open run's history
+                return Ok(true);
+            };
+            if same_event(&expected.event_type, produced) {
+                cursor.position += 1;
+                return Ok(true);
+            }
+            // Search attributes passed when starting follow the start event without the code recording them
+            if expected.event_id == EventId(2) && matches!(expected.event_type, EventType::UpsertSearchAttributes { .. }) {
+                cursor.position += 1;
+                continue;
+            }
+            let error = if is_closing(&expected.event_type) {
+                ReplayError::UnexpectedEvent {
+                    event_id: expected.event_id,
+                    produced: describe(produced),
+                }
+            } else {
+                ReplayError::Mismatch {
+                    event_id: expected.event_id,
+                    expected: describe(&expected.event_type),
+                    produced: describe(produced),
+                }
+            };
+            let message = error.to_string();
+            cursor.error = Some(error);
+            self.finished.notify_one();
+            return Err(WorkflowError::Custom(message));
+        }
+    }
+
+    /// Match the outcome of the workflow code against the history
+    pub(crate) fn close(&self, produced: &EventType) -> Result<(), ReplayError> {
+        let mut cursor = self.cursor.lock();
+        if let Some(error) = cursor.error.take() {
+            return Err(error);
+        }
+        let Some(expected) = cursor.expected.get(cursor.position) else {
+            return Ok(());
+        };
+        if !is_closing(&expected.event_type) {
+            return Err(ReplayError::MissingEvent {
+                event_id: expected.event_id,
+                expected: describe(&expected.event_type),
+            });
+        }
+        if !same_event(&expected.event_type, produced) {
+            return Err(ReplayError::Mismatch {
+                event_id: expected.event_id,
+                expected: describe(&expected.event_type),
+                produced: describe(produced),
+            });
+        }
+        Ok(())
+    }
+
+    /// Outcome once [`finished`](Self::finished) fired or the workflow code stopped making progress
+    pub(crate) fn outcome(&self) -> Result<(), ReplayError> {
+        let mut cursor = self.cursor.lock();
+        if let Some(error) = cursor.error.take() {
+            return Err(error);
+        }
+        match cursor.expected.get(cursor.position) {
+            Some(expected) => Err(ReplayError::MissingEvent {
+                event_id: expected.event_id,
+                expected: describe(&expected.event_type),
+            }),
+            None if self.closed => Err(ReplayError::MissingEvent {
+                event_id: cursor.next_event_id,
+                expected: "the closing event".to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Wait until the history of an open run is used up or replay has failed
+    pub(crate) async fn finished(&self) {
+        self.finished.notified().await;
+    }
+}
+
+/// Task queue completing activity attempts with their recorded outcome
+///
+/// Attempts without a recorded outcome stay pending, as they were when the history was recorded.
+pub(crate) struct ReplayTaskQueue {
+    history: EventHistory,
+    activities: Arc<PendingActivities>,
+}
+
+impl ReplayTaskQueue {
+    pub(crate) fn new(history: EventHistory, activities: Arc<PendingActivities>) -> Self {
+        Self { history, activities }
+    }
+}
+
+/// Rebuild the error of a failed attempt from its `attempt N: <error>` record, so retries are decided alike
+pub(crate) fn recorded_failure(failure: &str) -> ActivityError {
+    let error = failure.split_once(": ").map_or(failure, |(_, error)| error);
+    let prefixed = |prefix: &str| error.strip_prefix(prefix).map(str::to_string);
+    if let Some(msg) = prefixed("Temporary failure: ") {
+        ActivityError::TemporaryFailure(msg)
+    } else if let Some(msg) = prefixed("Validation failed: ") {
+        ActivityError::ValidationFailed(msg)
+    } else if let Some(msg) = prefixed("Execution failed: ") {
+        ActivityError::ExecutionFailed(msg)
+    } else if let Some(msg) = prefixed("Heartbeat failed: ") {
+        ActivityError::HeartbeatFailed(msg)
+    } else if let Some(msg) = prefixed("Invalid input: ") {
+        ActivityError::InvalidInput(msg)
+    } else if let Some(msg) = prefixed("Discarded from dead-letter queue: ") {
+        ActivityError::DeadLettered(msg)
+    } else if error == "Activity cancelled" {
+        ActivityError::Cancelled
+    } else if error == "Activity timeout" {
+        ActivityError::Timeout
+    } else {
+        ActivityError::Custom(error.to_string())
+    }
+}
+
+#[async_trait]
+impl TaskQueue for ReplayTaskQueue {
+    async fn push(&self, _queue: &str, task: Task) -> Result<(), StorageError> {
+        let Task::Activity(task) = task else {
+            return Ok(());
+        };
+        let outcome = self.history.activity_attempt(&task.activity_id, task.attempt);
+        if let Some(outcome) = outcome.map(|outcome| outcome.map_err(|failure| recorded_failure(&failure))) {
+            self.activities
+                .complete(task.workflow_execution.run_id, &task.activity_id, outcome);
+        }
+        Ok(())
+    }
+
+    async fn poll(&self, _queue: &str, _kind: TaskKind, _timeout: Duration) -> Result<Option<Task>, StorageError> {
+        Ok(None)
+    }
+
+    async fn len(&self, _queue: &str, _kind: TaskKind) -> Result<usize, StorageError> {
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::ActivityId;
+
+    fn scheduled(id: &str) -> EventType {
+        EventType::ActivityTaskScheduled {
+            activity_id: ActivityId::new(id),
+            activity_type: "charge".to_string(),
+            input: serde_json::Value::Null,
+        }
+    }
+
+    #[test]
+    fn test_reports_first_diverging_event() {
+        let mut history = EventHistory::new();
+        history.append(EventType::WorkflowExecutionStarted {
+            workflow_type: "order".to_string(),
+            task_queue: "default".to_string(),
+            input: serde_json::Value::Null,
+        });
+        history.append(EventType::SideEffectRecorded { seq: 1, value: serde_json::json!(3) });
+        history.append(scheduled("charge-2"));
+        history.append(EventType::WorkflowExecutionSignaled { signal_name: "go".to_string(), input: serde_json::Value::Null });
+        history.append(scheduled("charge-3"));
+        let replay = Replay::new(&history);
+
+        assert!(replay.take_inputs().is_empty());
+        replay.produced(&scheduled("charge-2")).unwrap();
+        assert_eq!(replay.take_inputs().len(), 1);
+        assert!(replay.produced(&scheduled("charge-4")).is_err());
+        assert_eq!(
+            replay.outcome(),
+            Err(ReplayError::Mismatch {
+                event_id: EventId(5),
+                expected: "ActivityTaskScheduled(charge, charge-3)".to_string(),
+                produced: "ActivityTaskScheduled(charge, charge-4)".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_recorded_failures_keep_their_kind() {
+        assert!(matches!(recorded_failure("attempt 2: Validation failed: bad card"), ActivityError::ValidationFailed(m) if m == "bad card"));
+        assert!(matches!(recorded_failure("attempt 1: Activity timeout"), ActivityError::Timeout));
+    }
+}
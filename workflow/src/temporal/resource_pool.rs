@@ -0,0 +1,429 @@
+//! Resource pools: counting semaphores bounding activities against shared downstream systems
+//!
+//! A pool holds a number of permits for a resource such as a warehouse API taking at most 10
+//! requests at once. Activities declare the pools they need with [`Activity::resources`], or the
+//! pools name them with [`ResourcePools::require`]; a worker given the pools takes one permit of
+//! each before starting an attempt and returns them once the attempt is done, whichever workflow
+//! the attempt belongs to.
+//!
+//! Attempts that find a pool exhausted queue for a permit, handed out first come first served or
+//! shared fairly between the runs waiting, see [`Fairness`]. An attempt still waiting after the
+//! pool's queue timeout is put back on its task queue, without counting as a failed attempt; while
+//! it queues, it holds one of its worker's activity slots.
+//! Share one [`ResourcePools`] between the workers of a process to bound them together.
+//!
+//! ```rust,ignore
+//! struct CheckStock;
+//!
+//! impl Activity for CheckStock {
+//!     // ...
+//!     fn resources() -> &'static [&'static str] {
+//!         &["warehouse-api"]
+//!     }
+//! }
+//!
+//! let pools = ResourcePools::new().pool("warehouse-api", ResourcePool::new(10).fairness(Fairness::FairShare));
+//! let worker = WorkflowWorker::new(WorkerConfig::default()).with_resource_pools(Arc::new(pools));
+//! ```
+//!
+//! [`Activity::resources`]: super::Activity::resources
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+use super::RunId;
+
+/// How long an attempt queues for a permit before it is put back on its task queue
+pub const DEFAULT_POOL_QUEUE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Which waiting attempt gets a permit that is returned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Fairness {
+    /// The attempt that has waited longest
+    #[default]
+    Fifo,
+    /// An attempt of the waiting run holding the fewest permits of the pool, the longest waiting
+    /// one among those; keeps a run fanning out many activities from starving the others
+    FairShare,
+}
+
+/// Permits and queueing of a pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourcePool {
+    pub permits: usize,
+    pub fairness: Fairness,
+    pub queue_timeout: Duration,
+}
+
+impl ResourcePool {
+    /// `permits` attempts at once, first come first served
+    pub fn new(permits: usize) -> Self {
+        Self {
+            permits,
+            fairness: Fairness::default(),
+            queue_timeout: DEFAULT_POOL_QUEUE_TIMEOUT,
+        }
+    }
+
+    pub fn fairness(mut self, fairness: Fairness) -> Self {
+        self.fairness = fairness;
+        self
+    }
+
+    /// Queue for a permit up to `timeout`, [`DEFAULT_POOL_QUEUE_TIMEOUT`] by default
+    pub fn queue_for(mut self, timeout: Duration) -> Self {
+        self.queue_timeout = timeout;
+        self
+    }
+}
+
+struct Waiter {
+    id: u64,
+    run_id: RunId,
+    granted: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+struct PoolState {
+    in_use: usize,
+    /// Permits in use by run
+    held: HashMap<RunId, usize>,
+    waiters: VecDeque<Waiter>,
+    next_waiter: u64,
+}
+
+impl PoolState {
+    fn take(&mut self, run_id: RunId) {
+        self.in_use += 1;
+        *self.held.entry(run_id).or_default() += 1;
+    }
+
+    fn put_back(&mut self, run_id: RunId) {
+        self.in_use -= 1;
+        if let Some(held) = self.held.get_mut(&run_id) {
+            *held -= 1;
+            if *held == 0 {
+                self.held.remove(&run_id);
+            }
+        }
+    }
+
+    /// Position of the waiter to hand the next permit to
+    fn next(&self, fairness: Fairness) -> Option<usize> {
+        match fairness {
+            Fairness::Fifo => (!self.waiters.is_empty()).then_some(0),
+            Fairness::FairShare => self
+                .waiters
+                .iter()
+                .enumerate()
+                .min_by_key(|(position, waiter)| (self.held.get(&waiter.run_id).copied().unwrap_or(0), *position))
+                .map(|(position, _)| position),
+        }
+    }
+}
+
+/// A named pool and its permits
+struct Pool {
+    name: String,
+    config: ResourcePool,
+    state: Mutex<PoolState>,
+}
+
+impl Pool {
+    /// Take a permit for `run_id`, queueing up to the pool's queue timeout
+    async fn acquire(self: &Arc<Self>, run_id: RunId, deadline: Instant) -> Option<ResourcePermit> {
+        let (id, mut granted) = {
+            let mut state = self.state.lock();
+            // Waiters whose attempts went away would otherwise hold up new ones
+            state.waiters.retain(|waiter| !waiter.granted.is_closed());
+            if state.in_use < self.config.permits && state.waiters.is_empty() {
+                state.take(run_id);
+                return Some(self.permit(run_id));
+            }
+            let (sender, granted) = oneshot::channel();
+            let id = state.next_waiter;
+            state.next_waiter += 1;
+            state.waiters.push_back(Waiter {
+                id,
+                run_id,
+                granted: sender,
+            });
+            (id, granted)
+        };
+        if tokio::time::timeout_at(deadline, &mut granted).await.is_ok() {
+            return Some(self.permit(run_id));
+        }
+        let mut state = self.state.lock();
+        if let Some(position) = state.waiters.iter().position(|waiter| waiter.id == id) {
+            state.waiters.remove(position);
+            return None;
+        }
+        // Granted just as the wait timed out
+        drop(state);
+        granted.try_recv().ok().map(|()| self.permit(run_id))
+    }
+
+    fn permit(self: &Arc<Self>, run_id: RunId) -> ResourcePermit {
+        metrics::gauge!(super::metrics::RESOURCE_POOL_PERMITS, "pool" => self.name.clone()).increment(1.0);
+        ResourcePermit {
+            pool: self.clone(),
+            run_id,
+        }
+    }
+
+    fn release(&self, run_id: RunId) {
+        metrics::gauge!(super::metrics::RESOURCE_POOL_PERMITS, "pool" => self.name.clone()).decrement(1.0);
+        let mut state = self.state.lock();
+        state.put_back(run_id);
+        while state.in_use < self.config.permits
+            && let Some(position) = state.next(self.config.fairness)
+        {
+            let Some(waiter) = state.waiters.remove(position) else {
+                break;
+            };
+            state.take(waiter.run_id);
+            // A waiter that stopped waiting gives its permit back right away
+            if waiter.granted.send(()).is_err() {
+                state.put_back(waiter.run_id);
+            }
+        }
+    }
+}
+
+/// Permit of a pool, returned when dropped
+pub struct ResourcePermit {
+    pool: Arc<Pool>,
+    run_id: RunId,
+}
+
+impl ResourcePermit {
+    pub fn pool(&self) -> &str {
+        &self.pool.name
+    }
+}
+
+impl Drop for ResourcePermit {
+    fn drop(&mut self) {
+        self.pool.release(self.run_id);
+    }
+}
+
+impl std::fmt::Debug for ResourcePermit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResourcePermit").field("pool", &self.pool.name).field("run_id", &self.run_id).finish()
+    }
+}
+
+/// Named pools of a worker and the activity types requiring them
+#[derive(Default)]
+pub struct ResourcePools {
+    pools: HashMap<String, Arc<Pool>>,
+    required: HashMap<String, Vec<String>>,
+}
+
+impl ResourcePools {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the pool `name`
+    pub fn pool(mut self, name: impl Into<String>, config: ResourcePool) -> Self {
+        let name = name.into();
+        let pool = Pool {
+            name: name.clone(),
+            config,
+            state: Mutex::new(PoolState::default()),
+        };
+        self.pools.insert(name, Arc::new(pool));
+        self
+    }
+
+    /// Require a permit of `pool` for the attempts of `activity_type`, in addition to the pools the
+    /// activity declares itself; for activities registered without a typed [`Activity`](super::Activity)
+    pub fn require(mut self, activity_type: impl Into<String>, pool: impl Into<String>) -> Self {
+        self.required.entry(activity_type.into()).or_default().push(pool.into());
+        self
+    }
+
+    /// Configuration of the pool `name`, if any
+    pub fn config(&self, name: &str) -> Option<ResourcePool> {
+        self.pools.get(name).map(|pool| pool.config)
+    }
+
+    /// Permits of the pool `name` in use
+    pub fn in_use(&self, name: &str) -> usize {
+        self.pools.get(name).map_or(0, |pool| pool.state.lock().in_use)
+    }
+
+    /// Pools the attempts of `activity_type` require besides `declared`, without duplicates
+    pub(crate) fn required_by(&self, activity_type: &str, declared: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = declared.iter().map(|name| name.to_string()).collect();
+        names.extend(self.required.get(activity_type).into_iter().flatten().cloned());
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Take a permit of each pool in `names` for an attempt of `run_id`
+    ///
+    /// Pools are taken in name order, so attempts requiring the same pools cannot deadlock. Names
+    /// without a pool are not limited. Fails with the name of the pool whose queue timeout ran
+    /// out, returning the permits taken so far.
+    pub async fn acquire(&self, names: &[String], run_id: RunId) -> Result<Vec<ResourcePermit>, String> {
+        let mut names: Vec<&String> = names.iter().collect();
+        names.sort();
+        names.dedup();
+        let mut permits = Vec::with_capacity(names.len());
+        for name in names {
+            let Some(pool) = self.pools.get(name) else {
+                tracing::debug!(pool = %name, "no resource pool configured, not limiting");
+                continue;
+            };
+            let waiting = Instant::now();
+            let permit = pool.acquire(run_id, waiting + pool.config.queue_timeout).await;
+            metrics::histogram!(super::metrics::RESOURCE_POOL_WAIT, "pool" => name.clone())
+                .record(waiting.elapsed().as_secs_f64());
+            match permit {
+                Some(permit) => permits.push(permit),
+                None => return Err(name.clone()),
+            }
+        }
+        Ok(permits)
+    }
+}
+
+impl std::fmt::Debug for ResourcePools {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let configs: HashMap<&str, ResourcePool> =
+            self.pools.iter().map(|(name, pool)| (name.as_str(), pool.config)).collect();
+        f.debug_struct("ResourcePools")
+            .field("pools", &configs)
+            .field("required", &self.required)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::future::join_all;
+
+    use crate::temporal::{
+        Activity, ActivityContext, ActivityError, ActivityOptions, StartWorkflowOptions, WorkerConfig, Workflow,
+        WorkflowContext, WorkflowError, WorkflowWorker,
+    };
+
+    #[tokio::test]
+    async fn test_fair_share_hands_permits_to_the_run_holding_fewest() {
+        let pools = Arc::new(ResourcePools::new().pool("api", ResourcePool::new(2).fairness(Fairness::FairShare)));
+        let (busy, quiet) = (RunId::generate(), RunId::generate());
+        let names = vec!["api".to_string()];
+        let first = pools.acquire(&names, busy).await.unwrap();
+        let second = pools.acquire(&names, busy).await.unwrap();
+
+        // The busy run queues first, but once it is down to one permit the quiet run holds fewer
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut waiting = Vec::new();
+        for (run_id, label) in [(busy, "busy"), (busy, "busy"), (quiet, "quiet")] {
+            let (pools, names, order) = (pools.clone(), names.clone(), order.clone());
+            waiting.push(tokio::spawn(async move {
+                let permit = pools.acquire(&names, run_id).await.unwrap();
+                order.lock().push(label);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                drop(permit);
+            }));
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        drop(first);
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        drop(second);
+        join_all(waiting).await.into_iter().for_each(Result::unwrap);
+        assert_eq!(order.lock()[0], "quiet");
+        assert_eq!(pools.in_use("api"), 0);
+
+        let pools = ResourcePools::new().pool("api", ResourcePool::new(1).queue_for(Duration::from_millis(20)));
+        let _held = pools.acquire(&names, busy).await.unwrap();
+        assert_eq!(pools.acquire(&names, quiet).await.unwrap_err(), "api");
+        assert!(pools.acquire(&["unlimited".to_string()], quiet).await.unwrap().is_empty());
+    }
+
+    static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+    static MOST_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+    /// Calls the warehouse API, counting concurrent calls
+    struct CheckStock;
+
+    impl Activity for CheckStock {
+        type Input = u32;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "check_stock"
+        }
+
+        fn resources() -> &'static [&'static str] {
+            &["warehouse-api"]
+        }
+
+        async fn execute(_ctx: ActivityContext, sku: u32) -> Result<u32, ActivityError> {
+            let in_flight = IN_FLIGHT.fetch_add(1, Ordering::SeqCst) + 1;
+            MOST_IN_FLIGHT.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+            Ok(sku)
+        }
+    }
+
+    struct Restock;
+
+    impl Workflow for Restock {
+        type Input = Vec<u32>;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "restock"
+        }
+
+        async fn execute(ctx: WorkflowContext, skus: Vec<u32>) -> Result<u32, WorkflowError> {
+            let checks = skus
+                .into_iter()
+                .map(|sku| ctx.execute_activity::<CheckStock>(sku, ActivityOptions::default()));
+            join_all(checks).await.into_iter().sum()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool_bounds_attempts_across_workflows() {
+        let pools = Arc::new(ResourcePools::new().pool("warehouse-api", ResourcePool::new(2)));
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(20),
+                ..WorkerConfig::default()
+            })
+            .with_resource_pools(pools.clone()),
+        );
+        worker.register_workflow::<Restock>();
+        worker.register_activity::<CheckStock>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let client = worker.client();
+        let mut handles = Vec::new();
+        for skus in [vec![1, 2, 3], vec![4, 5], vec![6]] {
+            handles.push(client.start_workflow::<Restock>(skus, StartWorkflowOptions::default()).await.unwrap());
+        }
+        let totals = join_all(handles.iter().map(|handle| handle.result())).await;
+        assert_eq!(totals.into_iter().map(Result::unwrap).collect::<Vec<_>>(), vec![6, 9, 6]);
+        assert_eq!(MOST_IN_FLIGHT.load(Ordering::SeqCst), 2);
+        assert_eq!(pools.in_use("warehouse-api"), 0);
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,302 @@
+//! Retention of closed executions
+//!
+//! [`RetentionPolicies`] say how long closed runs stay in storage, per workflow type and outcome.
+//! A worker given policies with [`WorkflowWorker::with_retention`](super::WorkflowWorker::with_retention)
+//! runs a janitor that periodically deletes the expired runs, or moves them to an
+//! [`ArchivingStorage`]. In dry-run mode the janitor only logs and counts what it would reclaim.
+//!
+//! ```
+//! # use std::time::Duration;
+//! # use workflow::temporal::retention::{RetentionPolicies, RetentionPolicy};
+//! const DAY: Duration = Duration::from_secs(24 * 3600);
+//!
+//! let policies = RetentionPolicies::new()
+//!     .default_policy(RetentionPolicy::new().keep_completed(7 * DAY).keep_failed(30 * DAY))
+//!     .for_type("nightly_report", RetentionPolicy::new().keep_completed(DAY).keep_failed(7 * DAY))
+//!     .dry_run(true);
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use super::archival::ArchivingStorage;
+use super::error::StorageError;
+use super::search::{WorkflowExecutionInfo, WorkflowExecutionStatus, WorkflowFilter};
+use super::storage::WorkflowStorage;
+use super::WorkflowId;
+
+/// Default time between janitor sweeps
+pub const DEFAULT_CLEANUP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// How long closed runs of a workflow type are kept; unset durations keep runs forever
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Retention of completed runs and runs that continued as new
+    pub completed: Option<Duration>,
+    /// Retention of failed runs
+    pub failed: Option<Duration>,
+}
+
+impl RetentionPolicy {
+    /// Keep every run
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn keep_completed(mut self, retention: Duration) -> Self {
+        self.completed = Some(retention);
+        self
+    }
+
+    pub fn keep_failed(mut self, retention: Duration) -> Self {
+        self.failed = Some(retention);
+        self
+    }
+
+    fn retention_of(&self, status: WorkflowExecutionStatus) -> Option<Duration> {
+        match status {
+            WorkflowExecutionStatus::Running => None,
+            WorkflowExecutionStatus::Completed | WorkflowExecutionStatus::ContinuedAsNew => self.completed,
+            WorkflowExecutionStatus::Failed | WorkflowExecutionStatus::Terminated => self.failed,
+        }
+    }
+}
+
+/// What happens to expired runs
+#[derive(Clone)]
+pub enum CleanupAction {
+    Delete,
+    /// Move them to the archive of an [`ArchivingStorage`] wrapping the swept storage
+    Archive(Arc<ArchivingStorage>),
+}
+
+impl CleanupAction {
+    fn label(&self) -> &'static str {
+        match self {
+            CleanupAction::Delete => "delete",
+            CleanupAction::Archive(_) => "archive",
+        }
+    }
+}
+
+/// Retention policies by workflow type, with the cleanup they drive
+#[derive(Clone)]
+pub struct RetentionPolicies {
+    default: RetentionPolicy,
+    by_type: HashMap<String, RetentionPolicy>,
+    action: CleanupAction,
+    dry_run: bool,
+    interval: Duration,
+}
+
+/// Outcome of a sweep
+#[derive(Debug, Clone, Default)]
+pub struct CleanupReport {
+    /// Closed runs examined
+    pub examined: usize,
+    /// Runs deleted or archived; in dry-run mode, the runs that would have been
+    pub reclaimed: Vec<WorkflowId>,
+    pub dry_run: bool,
+}
+
+impl RetentionPolicies {
+    /// Keep every run, delete expired runs once policies are added, sweep hourly
+    pub fn new() -> Self {
+        Self {
+            default: RetentionPolicy::default(),
+            by_type: HashMap::new(),
+            action: CleanupAction::Delete,
+            dry_run: false,
+            interval: DEFAULT_CLEANUP_INTERVAL,
+        }
+    }
+
+    /// Policy of workflow types without their own
+    pub fn default_policy(mut self, policy: RetentionPolicy) -> Self {
+        self.default = policy;
+        self
+    }
+
+    pub fn for_type(mut self, workflow_type: impl Into<String>, policy: RetentionPolicy) -> Self {
+        self.by_type.insert(workflow_type.into(), policy);
+        self
+    }
+
+    /// Archive expired runs instead of deleting them
+    pub fn archive_to(mut self, archive: Arc<ArchivingStorage>) -> Self {
+        self.action = CleanupAction::Archive(archive);
+        self
+    }
+
+    /// Only report the runs that would be reclaimed
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Time between janitor sweeps, [`DEFAULT_CLEANUP_INTERVAL`] by default
+    pub fn every(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn policy_for(&self, workflow_type: &str) -> &RetentionPolicy {
+        self.by_type.get(workflow_type).unwrap_or(&self.default)
+    }
+
+    /// Whether a run has outlived the retention of its type and outcome at `now`
+    pub fn is_expired(&self, info: &WorkflowExecutionInfo, now: DateTime<Utc>) -> bool {
+        let Some(retention) = self.policy_for(&info.workflow_type).retention_of(info.status) else {
+            return false;
+        };
+        let retention = chrono::Duration::from_std(retention).unwrap_or(chrono::Duration::MAX);
+        info.close_time
+            .is_some_and(|closed| closed.checked_add_signed(retention).is_some_and(|expiry| expiry <= now))
+    }
+
+    /// Delete or archive the runs of `storage` expired at `now`
+    ///
+    /// A run is skipped if a new run of its workflow ID started since the listing.
+    pub async fn sweep(&self, storage: &dyn WorkflowStorage, now: DateTime<Utc>) -> Result<CleanupReport, StorageError> {
+        let closed: Vec<_> = storage
+            .list_workflow_executions(&WorkflowFilter::new())
+            .await?
+            .into_iter()
+            .filter(|info| info.close_time.is_some())
+            .collect();
+        let mut report = CleanupReport {
+            examined: closed.len(),
+            dry_run: self.dry_run,
+            ..CleanupReport::default()
+        };
+        for info in closed.into_iter().filter(|info| self.is_expired(info, now)) {
+            let reclaimed = if self.dry_run {
+                true
+            } else {
+                match &self.action {
+                    CleanupAction::Delete => delete_run(storage, &info).await?,
+                    CleanupAction::Archive(archive) => archive.archive_run(&info).await?,
+                }
+            };
+            if reclaimed {
+                metrics::counter!(
+                    super::metrics::RETENTION_RECLAIMED,
+                    "workflow_type" => info.workflow_type.clone(),
+                    "action" => self.action.label(),
+                    "dry_run" => if self.dry_run { "true" } else { "false" }
+                )
+                .increment(1);
+                report.reclaimed.push(info.execution.workflow_id);
+            }
+        }
+        Ok(report)
+    }
+}
+
+impl Default for RetentionPolicies {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn delete_run(storage: &dyn WorkflowStorage, info: &WorkflowExecutionInfo) -> Result<bool, StorageError> {
+    let workflow_id = &info.execution.workflow_id;
+    match storage.load_workflow_execution(workflow_id).await {
+        Ok((execution, history)) if execution.run_id == info.execution.run_id && history.is_closed() => {
+            storage.delete_workflow_execution(workflow_id).await?;
+            Ok(true)
+        }
+        Ok(_) | Err(StorageError::NotFound) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::archival::LocalObjectStore;
+    use crate::temporal::event::{EventHistory, EventType};
+    use crate::temporal::storage::InMemoryStorage;
+    use crate::temporal::WorkflowExecution;
+
+    const DAY: Duration = Duration::from_secs(24 * 3600);
+
+    async fn save(storage: &dyn WorkflowStorage, id: &str, workflow_type: &str, outcome: Option<EventType>) {
+        let mut history = EventHistory::new();
+        history.append(EventType::WorkflowExecutionStarted {
+            workflow_type: workflow_type.to_string(),
+            task_queue: "default".to_string(),
+            input: serde_json::json!({}),
+        });
+        if let Some(outcome) = outcome {
+            history.append(outcome);
+        }
+        storage
+            .save_workflow_execution(&WorkflowExecution::new(WorkflowId::new(id)), &history)
+            .await
+            .unwrap();
+    }
+
+    async fn seed() -> Arc<InMemoryStorage> {
+        let storage = Arc::new(InMemoryStorage::new());
+        let completed = || Some(EventType::WorkflowExecutionCompleted { result: serde_json::json!(1) });
+        let failed = || Some(EventType::WorkflowExecutionFailed { failure: "boom".to_string() });
+        save(&*storage, "order-done", "order", completed()).await;
+        save(&*storage, "order-failed", "order", failed()).await;
+        save(&*storage, "order-open", "order", None).await;
+        save(&*storage, "report-done", "report", completed()).await;
+        storage
+    }
+
+    fn policies() -> RetentionPolicies {
+        RetentionPolicies::new()
+            .default_policy(RetentionPolicy::new().keep_completed(DAY).keep_failed(7 * DAY))
+            .for_type("report", RetentionPolicy::new().keep_failed(DAY))
+    }
+
+    fn sorted(mut ids: Vec<WorkflowId>) -> Vec<String> {
+        ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        ids.into_iter().map(|id| id.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_sweep_applies_policies_per_type_and_outcome() {
+        let storage = seed().await;
+        let in_two_days = Utc::now() + chrono::Duration::days(2);
+
+        let report = policies().dry_run(true).sweep(&*storage, in_two_days).await.unwrap();
+        assert_eq!((report.examined, report.dry_run), (3, true));
+        assert_eq!(sorted(report.reclaimed), ["order-done"]);
+        assert!(storage.load_workflow_execution(&WorkflowId::new("order-done")).await.is_ok());
+
+        let report = policies().sweep(&*storage, in_two_days).await.unwrap();
+        assert_eq!(sorted(report.reclaimed), ["order-done"]);
+        assert!(storage.load_workflow_execution(&WorkflowId::new("order-done")).await.is_err());
+
+        let report = policies().sweep(&*storage, Utc::now() + chrono::Duration::days(8)).await.unwrap();
+        assert_eq!(sorted(report.reclaimed), ["order-failed"]);
+        assert_eq!(storage.list_workflow_executions(&WorkflowFilter::new()).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_archives_expired_runs() {
+        let root = std::env::temp_dir().join(format!("workflow-retention-{}", uuid::Uuid::new_v4()));
+        let archive = Arc::new(ArchivingStorage::new(seed().await, Arc::new(LocalObjectStore::new(&root)), DAY * 365));
+        let report = policies()
+            .archive_to(archive.clone())
+            .sweep(&*archive, Utc::now() + chrono::Duration::days(2))
+            .await
+            .unwrap();
+        assert_eq!(sorted(report.reclaimed), ["order-done"]);
+        let archived = archive.load_archived(&WorkflowId::new("order-done")).await.unwrap();
+        assert!(archived.is_some_and(|(_, history)| history.is_closed()));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,283 @@
+//! Saga steps with reverse-order compensation
+//!
+//! Each successful step registers a compensation activity. When a later step fails, the
+//! registered compensations run in reverse order, either one after another or all at once.
+
+use futures::future::join_all;
+
+use super::event::EventType;
+use super::{Activity, ActivityOptions, WorkflowContext, WorkflowError};
+
+/// A registered compensation activity
+struct Compensation {
+    activity_type: &'static str,
+    input: serde_json::Value,
+}
+
+/// Saga builder, created with [`WorkflowContext::saga`]
+pub struct Saga {
+    ctx: WorkflowContext,
+    compensations: Vec<Compensation>,
+    compensation_options: ActivityOptions,
+    parallel: bool,
+    continue_on_error: bool,
+}
+
+impl Saga {
+    pub(crate) fn new(ctx: WorkflowContext) -> Self {
+        Self {
+            ctx,
+            compensations: Vec::new(),
+            compensation_options: ActivityOptions::default(),
+            parallel: false,
+            continue_on_error: false,
+        }
+    }
+
+    /// Run compensations concurrently instead of one after another in reverse order
+    pub fn parallel_compensation(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Keep running the remaining sequential compensations after one fails
+    pub fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Activity options used for compensation activities
+    pub fn compensation_options(mut self, options: ActivityOptions) -> Self {
+        self.compensation_options = options;
+        self
+    }
+
+    /// Number of registered compensations that have not run yet
+    pub fn pending_compensations(&self) -> usize {
+        self.compensations.len()
+    }
+
+    /// Register a compensation activity to run if the saga is compensated
+    pub fn add_compensation<C: Activity>(&mut self, input: C::Input) -> Result<(), WorkflowError> {
+        self.compensations.push(Compensation {
+            activity_type: C::name(),
+            input: serde_json::to_value(input)?,
+        });
+        Ok(())
+    }
+
+    /// Run activity `A` and, if it succeeds, register `C` with the input built from its output
+    ///
+    /// If `A` fails, the compensations registered so far run before its error is returned.
+    pub async fn step<A: Activity, C: Activity>(
+        &mut self,
+        input: A::Input,
+        options: ActivityOptions,
+        compensation: impl FnOnce(&A::Output) -> C::Input,
+    ) -> Result<A::Output, WorkflowError> {
+        match self.ctx.execute_activity::<A>(input, options).await {
+            Ok(output) => {
+                self.add_compensation::<C>(compensation(&output))?;
+                Ok(output)
+            }
+            Err(error) => {
+                if let Err(e) = self.compensate().await {
+                    tracing::warn!(step = A::name(), error = %e, "saga compensation incomplete");
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Run the registered compensations, newest first, and clear them
+    ///
+    /// Returns the first compensation failure. Sequential compensation stops at that failure
+    /// unless [`continue_on_error`](Self::continue_on_error) is set; parallel compensation always runs every step.
+    pub async fn compensate(&mut self) -> Result<(), WorkflowError> {
+        let compensations: Vec<_> = std::mem::take(&mut self.compensations).into_iter().enumerate().rev().collect();
+        if self.parallel {
+            let results = join_all(compensations.iter().map(|(step, c)| self.run_compensation(*step, c))).await;
+            return results.into_iter().collect();
+        }
+
+        let mut first_error = None;
+        for (step, compensation) in &compensations {
+            if let Err(e) = self.run_compensation(*step, compensation).await {
+                if !self.continue_on_error {
+                    return Err(e);
+                }
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    async fn run_compensation(&self, step: usize, compensation: &Compensation) -> Result<(), WorkflowError> {
+        // Compensations must run even when the saga's scope was cancelled
+        let ctx = self.ctx.non_cancellable();
+        let activity_type = compensation.activity_type.to_string();
+        ctx.record(EventType::CompensationStarted {
+            step,
+            activity_type: activity_type.clone(),
+        })
+        .await?;
+        let result = ctx
+            .execute_activity_value(compensation.activity_type, compensation.input.clone(), &self.compensation_options)
+            .await;
+        let event = match &result {
+            Ok(_) => EventType::CompensationCompleted { step, activity_type },
+            Err(e) => EventType::CompensationFailed {
+                step,
+                activity_type,
+                failure: e.to_string(),
+            },
+        };
+        ctx.record(event).await?;
+        result.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::{
+        ActivityContext, ActivityError, RetryPolicy, StartWorkflowOptions, Workflow, WorkerConfig, WorkflowWorker,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    struct Reserve;
+
+    impl Activity for Reserve {
+        type Input = u32;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "reserve"
+        }
+
+        async fn execute(_ctx: ActivityContext, input: u32) -> Result<u32, ActivityError> {
+            Ok(input)
+        }
+    }
+
+    struct Release;
+
+    impl Activity for Release {
+        type Input = u32;
+        type Output = ();
+
+        fn name() -> &'static str {
+            "release"
+        }
+
+        async fn execute(_ctx: ActivityContext, _input: u32) -> Result<(), ActivityError> {
+            Ok(())
+        }
+    }
+
+    struct Charge;
+
+    impl Activity for Charge {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "charge"
+        }
+
+        async fn execute(_ctx: ActivityContext, _input: ()) -> Result<(), ActivityError> {
+            Err(ActivityError::ValidationFailed("card declined".to_string()))
+        }
+    }
+
+    /// Reserves twice, then fails to charge; the input selects parallel compensation
+    struct Checkout;
+
+    impl Workflow for Checkout {
+        type Input = bool;
+        type Output = ();
+
+        fn name() -> &'static str {
+            "checkout"
+        }
+
+        async fn execute(ctx: WorkflowContext, parallel: bool) -> Result<(), WorkflowError> {
+            let options = ActivityOptions {
+                retry_policy: Some(RetryPolicy::no_retry()),
+                ..Default::default()
+            };
+            let mut saga = ctx.saga().parallel_compensation(parallel);
+            saga.step::<Reserve, Release>(1, options.clone(), |id| *id).await?;
+            saga.step::<Reserve, Release>(2, options.clone(), |id| *id).await?;
+            saga.step::<Charge, Release>((), options, |_| 0).await?;
+            Ok(())
+        }
+    }
+
+    async fn compensation_events(parallel: bool) -> Vec<EventType> {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..Default::default()
+        }));
+        worker.register_workflow::<Checkout>();
+        worker.register_activity::<Reserve>();
+        worker.register_activity::<Release>();
+        worker.register_activity::<Charge>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let handle = worker
+            .client()
+            .start_workflow::<Checkout>(parallel, StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        let error = handle.result().await.unwrap_err();
+        assert!(error.to_string().contains("card declined"));
+        let events = handle
+            .history()
+            .await
+            .unwrap()
+            .events()
+            .iter()
+            .map(|e| e.event_type.clone())
+            .filter(|e| {
+                matches!(
+                    e,
+                    EventType::CompensationStarted { .. }
+                        | EventType::CompensationCompleted { .. }
+                        | EventType::CompensationFailed { .. }
+                )
+            })
+            .collect();
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+        events
+    }
+
+    #[tokio::test]
+    async fn test_failed_step_compensates_in_reverse_order() {
+        let steps: Vec<_> = compensation_events(false)
+            .await
+            .into_iter()
+            .map(|e| match e {
+                EventType::CompensationStarted { step, .. } => format!("start {}", step),
+                EventType::CompensationCompleted { step, .. } => format!("done {}", step),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(steps, ["start 1", "done 1", "start 0", "done 0"]);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_compensation_runs_every_step() {
+        let events = compensation_events(true).await;
+        let completed = events
+            .iter()
+            .filter(|e| matches!(e, EventType::CompensationCompleted { activity_type, .. } if activity_type == "release"))
+            .count();
+        assert_eq!(events.len(), 4);
+        assert_eq!(completed, 2);
+    }
+}
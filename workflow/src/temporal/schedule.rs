@@ -0,0 +1,246 @@
+//! Cron schedules that start workflow runs
+//!
+//! A schedule is created with [`WorkflowClient::schedule_workflow`](super::WorkflowClient::schedule_workflow)
+//! and fired by the workers polling its task queue. Every fire starts a new run of the same
+//! workflow ID; the [`ScheduleOverlapPolicy`] decides what happens while the previous run is still open.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use super::{WorkflowError, WorkflowId};
+
+/// What to do when a schedule fires while its previous run is still open
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScheduleOverlapPolicy {
+    /// Drop the fire
+    #[default]
+    Skip,
+    /// Start one run as soon as the previous run closes; further fires meanwhile are dropped
+    BufferOne,
+    /// Cancel the previous run and start a new one once it has closed
+    CancelPrevious,
+}
+
+/// Action taken for a fire, given whether the previous run is still open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FireAction {
+    Start,
+    Skip,
+    Buffer,
+    CancelAndBuffer,
+}
+
+impl ScheduleOverlapPolicy {
+    pub(crate) fn on_fire(self, previous_open: bool) -> FireAction {
+        match (previous_open, self) {
+            (false, _) => FireAction::Start,
+            (true, ScheduleOverlapPolicy::Skip) => FireAction::Skip,
+            (true, ScheduleOverlapPolicy::BufferOne) => FireAction::Buffer,
+            (true, ScheduleOverlapPolicy::CancelPrevious) => FireAction::CancelAndBuffer,
+        }
+    }
+}
+
+/// Parse a cron expression
+///
+/// Accepts the classic five fields (minute to day of week) as well as the six or seven field
+/// form with seconds (and year) first.
+pub fn parse_cron(expression: &str) -> Result<cron::Schedule, WorkflowError> {
+    let expression = expression.trim();
+    let normalized = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+    cron::Schedule::from_str(&normalized)
+        .map_err(|e| WorkflowError::InvalidInput(format!("invalid cron expression '{}': {}", expression, e)))
+}
+
+/// Public view of a schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleDescription {
+    pub schedule_id: WorkflowId,
+    pub cron_schedule: String,
+    pub workflow_type: String,
+    pub task_queue: String,
+    pub overlap_policy: ScheduleOverlapPolicy,
+    pub paused: bool,
+    /// Next fire time; `None` while paused or once the expression has no future times
+    pub next_fire: Option<DateTime<Utc>>,
+    /// Whether a run is waiting for the previous one to close
+    pub buffered: bool,
+}
+
+struct ScheduleEntry {
+    cron: cron::Schedule,
+    description: ScheduleDescription,
+    input: serde_json::Value,
+}
+
+/// A schedule whose fire time has come or that has a buffered run
+pub(crate) struct DueSchedule {
+    pub(crate) schedule_id: WorkflowId,
+    pub(crate) workflow_type: String,
+    pub(crate) task_queue: String,
+    pub(crate) input: serde_json::Value,
+    pub(crate) overlap_policy: ScheduleOverlapPolicy,
+    /// Whether a fire time passed, as opposed to only a buffered run waiting
+    pub(crate) fired: bool,
+}
+
+/// Registry of schedules, shared by a worker and the clients it hands out
+#[derive(Default)]
+pub struct Schedules {
+    entries: RwLock<HashMap<WorkflowId, ScheduleEntry>>,
+}
+
+impl Schedules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn create(
+        &self,
+        schedule_id: WorkflowId,
+        cron_schedule: &str,
+        workflow_type: &str,
+        task_queue: &str,
+        input: serde_json::Value,
+        overlap_policy: ScheduleOverlapPolicy,
+    ) -> Result<ScheduleDescription, WorkflowError> {
+        let cron = parse_cron(cron_schedule)?;
+        let mut entries = self.entries.write();
+        if entries.contains_key(&schedule_id) {
+            return Err(WorkflowError::AlreadyStarted(schedule_id.to_string()));
+        }
+        let description = ScheduleDescription {
+            schedule_id: schedule_id.clone(),
+            cron_schedule: cron_schedule.to_string(),
+            workflow_type: workflow_type.to_string(),
+            task_queue: task_queue.to_string(),
+            overlap_policy,
+            paused: false,
+            next_fire: cron.after(&Utc::now()).next(),
+            buffered: false,
+        };
+        entries.insert(schedule_id, ScheduleEntry { cron, description: description.clone(), input });
+        Ok(description)
+    }
+
+    fn update<T>(&self, schedule_id: &WorkflowId, f: impl FnOnce(&mut ScheduleEntry) -> T) -> Result<T, WorkflowError> {
+        self.entries
+            .write()
+            .get_mut(schedule_id)
+            .map(f)
+            .ok_or_else(|| WorkflowError::Custom(format!("schedule not found: {}", schedule_id)))
+    }
+
+    /// Stop firing; a buffered run is dropped
+    pub fn pause(&self, schedule_id: &WorkflowId) -> Result<(), WorkflowError> {
+        self.update(schedule_id, |entry| {
+            entry.description.paused = true;
+            entry.description.next_fire = None;
+            entry.description.buffered = false;
+        })
+    }
+
+    /// Fire again from the next matching time; times missed while paused are skipped
+    pub fn resume(&self, schedule_id: &WorkflowId) -> Result<(), WorkflowError> {
+        self.update(schedule_id, |entry| {
+            entry.description.paused = false;
+            entry.description.next_fire = entry.cron.after(&Utc::now()).next();
+        })
+    }
+
+    /// Remove a schedule; runs already started are not affected
+    pub fn delete(&self, schedule_id: &WorkflowId) -> Result<(), WorkflowError> {
+        self.entries
+            .write()
+            .remove(schedule_id)
+            .map(|_| ())
+            .ok_or_else(|| WorkflowError::Custom(format!("schedule not found: {}", schedule_id)))
+    }
+
+    pub fn describe(&self, schedule_id: &WorkflowId) -> Option<ScheduleDescription> {
+        self.entries.read().get(schedule_id).map(|entry| entry.description.clone())
+    }
+
+    pub fn list(&self) -> Vec<ScheduleDescription> {
+        self.entries.read().values().map(|entry| entry.description.clone()).collect()
+    }
+
+    /// Schedules on `task_queue` that fired by `now` or have a buffered run, advancing their next fire time
+    pub(crate) fn take_due(&self, task_queue: &str, now: DateTime<Utc>) -> Vec<DueSchedule> {
+        let mut entries = self.entries.write();
+        entries
+            .values_mut()
+            .filter(|entry| entry.description.task_queue == task_queue && !entry.description.paused)
+            .filter_map(|entry| {
+                let fired = entry.description.next_fire.is_some_and(|next| next <= now);
+                if fired {
+                    entry.description.next_fire = entry.cron.after(&now).next();
+                }
+                (fired || entry.description.buffered).then(|| DueSchedule {
+                    schedule_id: entry.description.schedule_id.clone(),
+                    workflow_type: entry.description.workflow_type.clone(),
+                    task_queue: entry.description.task_queue.clone(),
+                    input: entry.input.clone(),
+                    overlap_policy: entry.description.overlap_policy,
+                    fired,
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) fn set_buffered(&self, schedule_id: &WorkflowId, buffered: bool) {
+        let _ = self.update(schedule_id, |entry| entry.description.buffered = buffered);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cron_accepts_five_and_six_fields() {
+        assert!(parse_cron("*/5 * * * *").is_ok());
+        assert!(parse_cron("0 30 9 * * Mon-Fri").is_ok());
+        assert!(matches!(parse_cron("not a cron"), Err(WorkflowError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_overlap_policies() {
+        for policy in [ScheduleOverlapPolicy::Skip, ScheduleOverlapPolicy::BufferOne, ScheduleOverlapPolicy::CancelPrevious] {
+            assert_eq!(policy.on_fire(false), FireAction::Start);
+        }
+        assert_eq!(ScheduleOverlapPolicy::Skip.on_fire(true), FireAction::Skip);
+        assert_eq!(ScheduleOverlapPolicy::BufferOne.on_fire(true), FireAction::Buffer);
+        assert_eq!(ScheduleOverlapPolicy::CancelPrevious.on_fire(true), FireAction::CancelAndBuffer);
+    }
+
+    #[test]
+    fn test_take_due_advances_and_respects_pause() {
+        let schedules = Schedules::new();
+        let id = WorkflowId::new("nightly");
+        schedules
+            .create(id.clone(), "* * * * * *", "report", "q", serde_json::json!(null), ScheduleOverlapPolicy::Skip)
+            .unwrap();
+        let later = Utc::now() + chrono::Duration::seconds(2);
+
+        assert!(schedules.take_due("other", later).is_empty());
+        let due = schedules.take_due("q", later);
+        assert_eq!(due.len(), 1);
+        assert!(due[0].fired);
+        assert!(schedules.describe(&id).unwrap().next_fire.unwrap() > later);
+        assert!(schedules.take_due("q", later).is_empty());
+
+        schedules.pause(&id).unwrap();
+        assert!(schedules.take_due("q", later + chrono::Duration::seconds(5)).is_empty());
+        schedules.delete(&id).unwrap();
+        assert!(schedules.resume(&id).is_err());
+    }
+}
@@ -0,0 +1,373 @@
+//! Search attributes and workflow listing
+//!
+//! Search attributes are typed values attached to an execution at start or with
+//! [`WorkflowContext::upsert_search_attributes`](super::WorkflowContext::upsert_search_attributes).
+//! They are recorded in the history, so every storage backend persists them, and
+//! [`WorkflowClient::list_workflows`](super::WorkflowClient::list_workflows) filters on them.
+//!
+//! A [`Memo`] carries metadata that is shown with an execution but never filtered on, such as a
+//! correlation ID, a customer name or a link to a ticket. It is set when the execution starts.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::WorkflowExecution;
+use super::event::{EventHistory, EventType};
+
+/// Value of a search attribute
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum SearchAttributeValue {
+    String(String),
+    Int(i64),
+    Datetime(DateTime<Utc>),
+    KeywordList(Vec<String>),
+}
+
+impl SearchAttributeValue {
+    /// Order of two values of the same type; keyword lists and mixed types are not ordered
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::String(a), Self::String(b)) => Some(a.cmp(b)),
+            (Self::Int(a), Self::Int(b)) => Some(a.cmp(b)),
+            (Self::Datetime(a), Self::Datetime(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for SearchAttributeValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for SearchAttributeValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<i64> for SearchAttributeValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<DateTime<Utc>> for SearchAttributeValue {
+    fn from(value: DateTime<Utc>) -> Self {
+        Self::Datetime(value)
+    }
+}
+
+impl From<Vec<String>> for SearchAttributeValue {
+    fn from(value: Vec<String>) -> Self {
+        Self::KeywordList(value)
+    }
+}
+
+/// Search attributes by name
+pub type SearchAttributes = BTreeMap<String, SearchAttributeValue>;
+
+/// Metadata attached to an execution, returned with it but not indexed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Memo(BTreeMap<String, serde_json::Value>);
+
+impl Memo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `value` under `key`, replacing any earlier value
+    pub fn with(mut self, key: impl Into<String>, value: impl Serialize) -> Result<Self, serde_json::Error> {
+        self.insert(key, value)?;
+        Ok(self)
+    }
+
+    /// Set `value` under `key`, replacing any earlier value
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Serialize) -> Result<(), serde_json::Error> {
+        self.0.insert(key.into(), serde_json::to_value(value)?);
+        Ok(())
+    }
+
+    /// Value under `key`; `None` if there is none, an error if it is not a `T`
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, serde_json::Error> {
+        self.0.get(key).cloned().map(serde_json::from_value).transpose()
+    }
+
+    /// Undecoded value under `key`
+    pub fn get_value(&self, key: &str) -> Option<&serde_json::Value> {
+        self.0.get(key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &serde_json::Value)> {
+        self.0.iter()
+    }
+}
+
+impl From<BTreeMap<String, serde_json::Value>> for Memo {
+    fn from(entries: BTreeMap<String, serde_json::Value>) -> Self {
+        Self(entries)
+    }
+}
+
+/// Status of an execution, derived from its history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorkflowExecutionStatus {
+    Running,
+    Completed,
+    Failed,
+    ContinuedAsNew,
+    Terminated,
+}
+
+/// Summary of an execution returned by workflow listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowExecutionInfo {
+    pub execution: WorkflowExecution,
+    pub workflow_type: String,
+    pub task_queue: String,
+    pub status: WorkflowExecutionStatus,
+    pub start_time: DateTime<Utc>,
+    pub close_time: Option<DateTime<Utc>>,
+    pub search_attributes: SearchAttributes,
+    #[serde(default)]
+    pub memo: Memo,
+}
+
+impl WorkflowExecutionInfo {
+    /// Summarize an execution; `None` if the history has no start event
+    pub fn from_history(execution: WorkflowExecution, history: &EventHistory) -> Option<Self> {
+        let (start_time, workflow_type, task_queue) = history.events().iter().find_map(|e| match &e.event_type {
+            EventType::WorkflowExecutionStarted { workflow_type, task_queue, .. } => {
+                Some((e.timestamp, workflow_type.clone(), task_queue.clone()))
+            }
+            _ => None,
+        })?;
+        let status = match history.outcome() {
+            Some(Ok(_)) => WorkflowExecutionStatus::Completed,
+            Some(Err(_)) if history.is_terminated() => WorkflowExecutionStatus::Terminated,
+            Some(Err(_)) => WorkflowExecutionStatus::Failed,
+            None if history.continued_as_new().is_some() => WorkflowExecutionStatus::ContinuedAsNew,
+            None => WorkflowExecutionStatus::Running,
+        };
+        let close_time = history
+            .is_closed()
+            .then(|| history.events().last().map(|e| e.timestamp))
+            .flatten();
+        Some(Self {
+            execution,
+            workflow_type,
+            task_queue,
+            status,
+            start_time,
+            close_time,
+            search_attributes: history.search_attributes(),
+            memo: history.memo(),
+        })
+    }
+}
+
+/// Comparison applied by an attribute filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Comparison {
+    /// Equal; a keyword list matches a string it contains
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AttributeFilter {
+    name: String,
+    comparison: Comparison,
+    value: SearchAttributeValue,
+}
+
+impl AttributeFilter {
+    fn matches(&self, attributes: &SearchAttributes) -> bool {
+        let Some(actual) = attributes.get(&self.name) else {
+            return false;
+        };
+        if let (Comparison::Eq, SearchAttributeValue::KeywordList(keywords), SearchAttributeValue::String(keyword)) =
+            (self.comparison, actual, &self.value)
+        {
+            return keywords.contains(keyword);
+        }
+        match self.comparison {
+            Comparison::Eq => actual == &self.value,
+            Comparison::Lt => actual.compare(&self.value) == Some(Ordering::Less),
+            Comparison::Le => matches!(actual.compare(&self.value), Some(Ordering::Less | Ordering::Equal)),
+            Comparison::Gt => actual.compare(&self.value) == Some(Ordering::Greater),
+            Comparison::Ge => matches!(actual.compare(&self.value), Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+/// Filter for [`WorkflowClient::list_workflows`](super::WorkflowClient::list_workflows); all conditions must hold
+///
+/// ```
+/// use workflow::temporal::search::{Comparison, WorkflowExecutionStatus, WorkflowFilter};
+///
+/// let yesterday = chrono::Utc::now() - chrono::Duration::days(1);
+/// let filter = WorkflowFilter::new()
+///     .workflow_type("OrderProcessing")
+///     .status(WorkflowExecutionStatus::Failed)
+///     .started_after(yesterday)
+///     .attribute("CustomerTier", Comparison::Eq, "gold");
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkflowFilter {
+    workflow_type: Option<String>,
+    status: Option<WorkflowExecutionStatus>,
+    started_after: Option<DateTime<Utc>>,
+    started_before: Option<DateTime<Utc>>,
+    attributes: Vec<AttributeFilter>,
+}
+
+impl WorkflowFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn workflow_type(mut self, workflow_type: impl Into<String>) -> Self {
+        self.workflow_type = Some(workflow_type.into());
+        self
+    }
+
+    pub fn status(mut self, status: WorkflowExecutionStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Only executions started at or after `time`
+    pub fn started_after(mut self, time: DateTime<Utc>) -> Self {
+        self.started_after = Some(time);
+        self
+    }
+
+    /// Only executions started before `time`
+    pub fn started_before(mut self, time: DateTime<Utc>) -> Self {
+        self.started_before = Some(time);
+        self
+    }
+
+    /// Only executions whose attribute `name` compares to `value` as given; executions without it never match
+    pub fn attribute(
+        mut self,
+        name: impl Into<String>,
+        comparison: Comparison,
+        value: impl Into<SearchAttributeValue>,
+    ) -> Self {
+        self.attributes.push(AttributeFilter {
+            name: name.into(),
+            comparison,
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn matches(&self, info: &WorkflowExecutionInfo) -> bool {
+        self.workflow_type.as_ref().is_none_or(|t| *t == info.workflow_type)
+            && self.status.is_none_or(|s| s == info.status)
+            && self.started_after.is_none_or(|t| info.start_time >= t)
+            && self.started_before.is_none_or(|t| info.start_time < t)
+            && self.attributes.iter().all(|f| f.matches(&info.search_attributes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::WorkflowId;
+
+    fn info(status_event: Option<EventType>, attributes: SearchAttributes) -> WorkflowExecutionInfo {
+        let mut history = EventHistory::new();
+        history.append(EventType::WorkflowExecutionStarted {
+            workflow_type: "OrderProcessing".to_string(),
+            task_queue: "orders".to_string(),
+            input: serde_json::json!(null),
+        });
+        history.append(EventType::UpsertSearchAttributes { attributes });
+        history.append(EventType::WorkflowMemoSet {
+            memo: Memo::new().with("customer", "Acme").unwrap(),
+        });
+        if let Some(event) = status_event {
+            history.append(event);
+        }
+        WorkflowExecutionInfo::from_history(WorkflowExecution::new(WorkflowId::new("order-1")), &history).unwrap()
+    }
+
+    #[test]
+    fn test_status_and_attributes_from_history() {
+        let attributes = SearchAttributes::from([("Amount".to_string(), SearchAttributeValue::Int(120))]);
+        let running = info(None, attributes.clone());
+        assert_eq!(running.status, WorkflowExecutionStatus::Running);
+        assert!(running.close_time.is_none());
+
+        let failed = info(Some(EventType::WorkflowExecutionFailed { failure: "boom".to_string() }), attributes);
+        assert_eq!(failed.status, WorkflowExecutionStatus::Failed);
+        assert!(failed.close_time.is_some());
+        assert_eq!(failed.search_attributes["Amount"], SearchAttributeValue::Int(120));
+        assert_eq!(failed.memo.get::<String>("customer").unwrap().as_deref(), Some("Acme"));
+    }
+
+    #[test]
+    fn test_memo_values_are_typed() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Link {
+            url: String,
+        }
+
+        let link = Link {
+            url: "https://tickets.example.com/42".to_string(),
+        };
+        let memo = Memo::new().with("ticket", &link).unwrap().with("attempt", 3).unwrap();
+        assert_eq!(memo.get::<Link>("ticket").unwrap(), Some(link));
+        assert_eq!(memo.get::<u32>("attempt").unwrap(), Some(3));
+        assert_eq!(memo.get::<u32>("missing").unwrap(), None);
+        assert!(memo.get::<Link>("attempt").is_err());
+
+        let decoded: Memo = serde_json::from_value(serde_json::to_value(&memo).unwrap()).unwrap();
+        assert_eq!(decoded, memo);
+        assert_eq!(decoded.get_value("attempt"), Some(&serde_json::json!(3)));
+    }
+
+    #[test]
+    fn test_filter_equality_and_ranges() {
+        let attributes = SearchAttributes::from([
+            ("Amount".to_string(), 120.into()),
+            ("Tags".to_string(), vec!["vip".to_string(), "eu".to_string()].into()),
+        ]);
+        let info = info(Some(EventType::WorkflowExecutionFailed { failure: "boom".to_string() }), attributes);
+        let yesterday = Utc::now() - chrono::Duration::days(1);
+
+        let filter = WorkflowFilter::new()
+            .workflow_type("OrderProcessing")
+            .status(WorkflowExecutionStatus::Failed)
+            .started_after(yesterday);
+        assert!(filter.matches(&info));
+        assert!(!filter.clone().started_before(yesterday).matches(&info));
+        assert!(filter.clone().attribute("Amount", Comparison::Ge, 100).matches(&info));
+        assert!(!filter.clone().attribute("Amount", Comparison::Lt, 100).matches(&info));
+        assert!(filter.clone().attribute("Tags", Comparison::Eq, "vip").matches(&info));
+        assert!(!filter.clone().attribute("Amount", Comparison::Gt, "100").matches(&info));
+        assert!(!filter.attribute("Missing", Comparison::Eq, 1).matches(&info));
+    }
+}
@@ -0,0 +1,566 @@
+//! Activity sessions: sequences of activities pinned to one worker
+//!
+//! [`WorkflowContext::create_session`] opens a session on the worker running the workflow, and
+//! activities executed through the returned [`Session`] are pushed to that worker's own task queue
+//! (`{task_queue}@{identity}`) instead of the shared one, so each of them finds the files or
+//! caches the previous ones left on the host. Workers host sessions when
+//! [`WorkerConfig::max_concurrent_sessions`](super::WorkerConfig::max_concurrent_sessions) is above zero.
+//!
+//! A session ends when the workflow completes it, when its execution timeout passes, or when its
+//! host goes away: a worker shutting down fails its open sessions, and a run taken over by another
+//! worker records the sessions it had open on the old host as failed. Activities of an ended
+//! session fail with [`WorkflowError::SessionFailed`]; the workflow may create a new session and
+//! start the sequence over.
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use workflow::temporal::{Activity, ActivityContext, ActivityError, ActivityOptions, SessionOptions, WorkflowContext, WorkflowError};
+//! # struct Download;
+//! # impl Activity for Download {
+//! #     type Input = String;
+//! #     type Output = String;
+//! #     fn name() -> &'static str { "download" }
+//! #     async fn execute(_: ActivityContext, url: String) -> Result<String, ActivityError> { Ok(url) }
+//! # }
+//! # struct Process;
+//! # impl Activity for Process {
+//! #     type Input = String;
+//! #     type Output = String;
+//! #     fn name() -> &'static str { "process" }
+//! #     async fn execute(_: ActivityContext, path: String) -> Result<String, ActivityError> { Ok(path) }
+//! # }
+//! # async fn convert(ctx: WorkflowContext, url: String) -> Result<String, WorkflowError> {
+//! let session = ctx
+//!     .create_session(SessionOptions::default().with_execution_timeout(Duration::from_secs(600)))
+//!     .await?;
+//! let path = session.execute_activity::<Download>(url, ActivityOptions::default()).await?;
+//! let output = session.execute_activity::<Process>(path, ActivityOptions::default()).await?;
+//! session.complete().await?;
+//! # Ok(output)
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+use super::event::EventType;
+use super::{Activity, ActivityOptions, RunId, WorkflowContext, WorkflowError};
+
+/// Options of an activity session
+#[derive(Debug, Clone)]
+pub struct SessionOptions {
+    /// How long to wait for the host to have room for another session
+    pub creation_timeout: Duration,
+    /// Time after creation at which the session expires
+    pub execution_timeout: Option<Duration>,
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        Self {
+            creation_timeout: Duration::from_secs(60),
+            execution_timeout: None,
+        }
+    }
+}
+
+impl SessionOptions {
+    pub fn with_creation_timeout(mut self, timeout: Duration) -> Self {
+        self.creation_timeout = timeout;
+        self
+    }
+
+    pub fn with_execution_timeout(mut self, timeout: Duration) -> Self {
+        self.execution_timeout = Some(timeout);
+        self
+    }
+}
+
+/// Identity of a session and the worker hosting it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub session_id: String,
+    /// Task queue only the host polls
+    pub host_task_queue: String,
+}
+
+/// Sessions open on a worker, bounded by its session slots
+pub(crate) struct SessionHost {
+    task_queue: String,
+    slots: Arc<Semaphore>,
+    open: Mutex<HashMap<(RunId, String), OwnedSemaphorePermit>>,
+    /// Cancelled when the worker stops
+    gone: CancellationToken,
+}
+
+impl SessionHost {
+    pub(crate) fn new(task_queue: String, max_sessions: usize) -> Self {
+        Self {
+            task_queue,
+            slots: Arc::new(Semaphore::new(max_sessions.min(Semaphore::MAX_PERMITS))),
+            open: Mutex::new(HashMap::new()),
+            gone: CancellationToken::new(),
+        }
+    }
+
+    pub(crate) fn task_queue(&self) -> &str {
+        &self.task_queue
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.open.lock().len()
+    }
+
+    /// Take a slot for a session, waiting up to `timeout` for one to free up
+    async fn open(&self, run_id: RunId, session_id: &str, timeout: Duration) -> Result<(), WorkflowError> {
+        let acquired = tokio::select! {
+            _ = self.gone.cancelled() => return Err(self.gone_error()),
+            acquired = tokio::time::timeout(timeout, self.slots.clone().acquire_owned()) => acquired,
+        };
+        let Ok(Ok(slot)) = acquired else {
+            return Err(WorkflowError::SessionFailed(format!(
+                "no session slot free on {} within {:?}",
+                self.task_queue, timeout
+            )));
+        };
+        self.open.lock().insert((run_id, session_id.to_string()), slot);
+        Ok(())
+    }
+
+    fn close(&self, run_id: RunId, session_id: &str) {
+        self.open.lock().remove(&(run_id, session_id.to_string()));
+    }
+
+    /// Free the slots of the sessions a run left open, once it stopped on this worker
+    pub(crate) fn close_run(&self, run_id: RunId) {
+        self.open.lock().retain(|(run, _), _| *run != run_id);
+    }
+
+    /// Stop hosting sessions; open ones fail at their next activity
+    pub(crate) fn shut_down(&self) {
+        self.gone.cancel();
+    }
+
+    fn gone_error(&self) -> WorkflowError {
+        WorkflowError::SessionFailed(format!("session host {} is shutting down", self.task_queue))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SessionState {
+    Open,
+    Completed,
+    Expired,
+    Failed(String),
+}
+
+/// An activity session created with [`WorkflowContext::create_session`]
+#[derive(Clone)]
+pub struct Session {
+    ctx: WorkflowContext,
+    info: SessionInfo,
+    host: Arc<SessionHost>,
+    state: Arc<Mutex<SessionState>>,
+    /// Cancelled when the execution timeout passes
+    expired: CancellationToken,
+    /// Cancelled when the session ends, stopping the expiry timer
+    ended: CancellationToken,
+}
+
+impl Session {
+    pub fn info(&self) -> &SessionInfo {
+        &self.info
+    }
+
+    /// Whether the session still accepts activities
+    pub fn is_open(&self) -> bool {
+        *self.state.lock() == SessionState::Open && !self.expired.is_cancelled() && !self.host.gone.is_cancelled()
+    }
+
+    /// Execute an activity on the session's host
+    ///
+    /// Fails with [`WorkflowError::SessionFailed`] if the session has ended, and cancels the
+    /// attempt in flight when the session expires. The options' task queue is ignored.
+    pub async fn execute_activity<A: Activity>(
+        &self,
+        input: A::Input,
+        options: ActivityOptions,
+    ) -> Result<A::Output, WorkflowError> {
+        self.ensure_open().await?;
+        let options = ActivityOptions {
+            task_queue: Some(self.info.host_task_queue.clone()),
+            ..options
+        };
+        tokio::select! {
+            biased;
+            _ = self.expired.cancelled() => Err(self.end(SessionState::Expired).await?),
+            result = self.ctx.execute_activity::<A>(input, options) => result,
+        }
+    }
+
+    /// End the session and free its slot on the host; does nothing if it already ended
+    pub async fn complete(&self) -> Result<(), WorkflowError> {
+        self.end(SessionState::Completed).await.map(drop)
+    }
+
+    async fn ensure_open(&self) -> Result<(), WorkflowError> {
+        let ended = self.state.lock().clone();
+        match ended {
+            SessionState::Open if self.expired.is_cancelled() => Err(self.end(SessionState::Expired).await?),
+            SessionState::Open if self.host.gone.is_cancelled() => {
+                Err(self.end(SessionState::Failed(self.host.gone_error().to_string())).await?)
+            }
+            SessionState::Open => Ok(()),
+            ended => Err(self.error(&ended)),
+        }
+    }
+
+    /// Move an open session to `state`, recording the event; returns the error later activities fail with
+    async fn end(&self, state: SessionState) -> Result<WorkflowError, WorkflowError> {
+        {
+            let mut current = self.state.lock();
+            if *current != SessionState::Open {
+                return Ok(self.error(&current));
+            }
+            *current = state.clone();
+        }
+        self.ended.cancel();
+        self.host.close(self.ctx.execution().run_id, &self.info.session_id);
+        let session_id = self.info.session_id.clone();
+        self.ctx
+            .record(match &state {
+                SessionState::Completed | SessionState::Open => EventType::SessionCompleted { session_id },
+                SessionState::Expired => EventType::SessionExpired { session_id },
+                SessionState::Failed(failure) => EventType::SessionFailed {
+                    session_id,
+                    failure: failure.clone(),
+                },
+            })
+            .await?;
+        Ok(self.error(&state))
+    }
+
+    fn error(&self, state: &SessionState) -> WorkflowError {
+        let session_id = &self.info.session_id;
+        WorkflowError::SessionFailed(match state {
+            SessionState::Open | SessionState::Completed => format!("session {} is completed", session_id),
+            SessionState::Expired => format!("session {} expired", session_id),
+            SessionState::Failed(failure) => format!("session {}: {}", session_id, failure),
+        })
+    }
+}
+
+impl WorkflowContext {
+    /// Open an activity session on the worker running this workflow
+    ///
+    /// Fails with [`WorkflowError::SessionFailed`] if the worker hosts no sessions, is shutting
+    /// down, or has no session slot free within the creation timeout. A session of the same run
+    /// still open on another worker, which must have gone away, is recorded as failed first.
+    pub async fn create_session(&self, options: SessionOptions) -> Result<Session, WorkflowError> {
+        let runtime = self.runtime()?;
+        let Some(host) = runtime.sessions.clone() else {
+            return Err(WorkflowError::SessionFailed("the worker does not host sessions".to_string()));
+        };
+        let session_id = format!("session-{}", runtime.next_sequence());
+        let stale = runtime.history.lock().await.open_session(&session_id).map(str::to_string);
+        if let Some(previous_host) = stale.filter(|previous| previous != host.task_queue() && !runtime.is_replaying()) {
+            self.record(EventType::SessionFailed {
+                session_id: session_id.clone(),
+                failure: format!("session host {} went away", previous_host),
+            })
+            .await?;
+        }
+
+        let run_id = self.execution().run_id;
+        host.open(run_id, &session_id, options.creation_timeout).await?;
+        let info = SessionInfo {
+            session_id,
+            host_task_queue: host.task_queue().to_string(),
+        };
+        if let Err(e) = self
+            .record(EventType::SessionCreated {
+                session_id: info.session_id.clone(),
+                host_task_queue: info.host_task_queue.clone(),
+            })
+            .await
+        {
+            host.close(run_id, &info.session_id);
+            return Err(e);
+        }
+
+        let session = Session {
+            ctx: self.clone(),
+            info,
+            host,
+            state: Arc::new(Mutex::new(SessionState::Open)),
+            expired: CancellationToken::new(),
+            ended: CancellationToken::new(),
+        };
+        if let Some(timeout) = options.execution_timeout {
+            let (ctx, expired, ended) = (self.clone(), session.expired.clone(), session.ended.clone());
+            tokio::spawn(async move {
+                if ctx.within(timeout, ended.cancelled()).await.is_none() {
+                    expired.cancel();
+                }
+            });
+        }
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::temporal::event::EventHistory;
+    use crate::temporal::storage::{InMemoryStorage, WorkflowStorage};
+    use crate::temporal::task_queue::{InMemoryTaskQueue, TaskQueue};
+    use crate::temporal::{
+        ActivityContext, ActivityError, StartWorkflowOptions, WorkerConfig, Workflow, WorkflowHandle, WorkflowWorker,
+    };
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Echoes its input
+    struct OnHost;
+
+    impl Activity for OnHost {
+        type Input = u32;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "on_host"
+        }
+
+        async fn execute(_ctx: ActivityContext, step: u32) -> Result<u32, ActivityError> {
+            Ok(step)
+        }
+    }
+
+    static STALLED: AtomicBool = AtomicBool::new(false);
+
+    /// Stalls the first time it runs
+    struct StallsOnce;
+
+    impl Activity for StallsOnce {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "session_stalls_once"
+        }
+
+        async fn execute(_ctx: ActivityContext, _: ()) -> Result<(), ActivityError> {
+            if !STALLED.swap(true, Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+            Ok(())
+        }
+    }
+
+    /// Runs `steps` activities in one session
+    struct Pipeline;
+
+    impl Workflow for Pipeline {
+        type Input = u32;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "pipeline"
+        }
+
+        async fn execute(ctx: WorkflowContext, steps: u32) -> Result<u32, WorkflowError> {
+            let session = ctx.create_session(SessionOptions::default()).await?;
+            let mut sum = 0;
+            for step in 0..steps {
+                sum += session.execute_activity::<OnHost>(step, ActivityOptions::default()).await?;
+            }
+            session.complete().await?;
+            Ok(sum)
+        }
+    }
+
+    /// Holds a session past its execution timeout
+    struct Overstays;
+
+    impl Workflow for Overstays {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "overstays"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<(), WorkflowError> {
+            let session = ctx
+                .create_session(SessionOptions::default().with_execution_timeout(Duration::from_millis(50)))
+                .await?;
+            ctx.sleep(Duration::from_millis(100)).await;
+            session.execute_activity::<OnHost>(1, ActivityOptions::default()).await.map(drop)
+        }
+    }
+
+    /// Stalls inside a session, so the run is still in it when its worker stops
+    struct Interrupted;
+
+    impl Workflow for Interrupted {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "interrupted"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<(), WorkflowError> {
+            let session = ctx.create_session(SessionOptions::default()).await?;
+            session.execute_activity::<StallsOnce>((), ActivityOptions::default()).await?;
+            session.complete().await
+        }
+    }
+
+    fn spawn_host(
+        sessions: usize,
+        task_queue: Arc<dyn TaskQueue>,
+        storage: Arc<dyn WorkflowStorage>,
+    ) -> (Arc<WorkflowWorker>, tokio::task::JoinHandle<()>) {
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                max_concurrent_sessions: sessions,
+                ..Default::default()
+            })
+            .with_task_queue(task_queue)
+            .with_storage(storage),
+        );
+        worker.register_workflow::<Pipeline>();
+        worker.register_workflow::<Overstays>();
+        worker.register_workflow::<Interrupted>();
+        worker.register_activity::<OnHost>();
+        worker.register_activity::<StallsOnce>();
+        let running = worker.clone();
+        (worker, tokio::spawn(async move { running.run().await.map(drop).unwrap() }))
+    }
+
+    fn session_events(history: &EventHistory) -> Vec<EventType> {
+        history
+            .events()
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e.event_type,
+                    EventType::SessionCreated { .. }
+                        | EventType::SessionCompleted { .. }
+                        | EventType::SessionExpired { .. }
+                        | EventType::SessionFailed { .. }
+                )
+            })
+            .map(|e| e.event_type.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_session_activities_stay_on_host() {
+        let task_queue: Arc<dyn TaskQueue> = Arc::new(InMemoryTaskQueue::new());
+        let storage: Arc<dyn WorkflowStorage> = Arc::new(InMemoryStorage::new());
+        // Both workers poll the shared queue, only the host polls the session's queue
+        let (host, host_run) = spawn_host(1, task_queue.clone(), storage.clone());
+        let (other, other_run) = spawn_host(1, task_queue, storage);
+
+        let handle: WorkflowHandle<u32> = host
+            .client()
+            .start_workflow::<Pipeline>(20, StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(handle.result().await.unwrap(), (0..20).sum::<u32>());
+
+        let history = handle.history().await.unwrap();
+        let events = session_events(&history);
+        let EventType::SessionCreated { host_task_queue, .. } = &events[0] else {
+            panic!("expected a created session, got {:?}", events);
+        };
+        let session_host = [&host, &other]
+            .into_iter()
+            .find(|worker| worker.session_task_queue() == Some(host_task_queue.as_str()))
+            .unwrap();
+        assert_eq!(session_host.open_sessions(), 0);
+        assert!(matches!(&events[1..], [EventType::SessionCompleted { session_id }] if session_id == "session-1"));
+
+        for worker in [host, other] {
+            worker.shutdown(Duration::ZERO);
+        }
+        host_run.await.unwrap();
+        other_run.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_expires_and_requires_a_host() {
+        let (worker, run) = spawn_host(1, Arc::new(InMemoryTaskQueue::new()), Arc::new(InMemoryStorage::new()));
+        let handle: WorkflowHandle<()> = worker
+            .client()
+            .start_workflow::<Overstays>((), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        let failure = handle.result().await.unwrap_err().to_string();
+        assert!(failure.contains("session session-1 expired"), "{}", failure);
+        let events = session_events(&handle.history().await.unwrap());
+        assert!(matches!(events[..], [EventType::SessionCreated { .. }, EventType::SessionExpired { .. }]));
+        assert_eq!(worker.open_sessions(), 0);
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap();
+
+        let (worker, run) = spawn_host(0, Arc::new(InMemoryTaskQueue::new()), Arc::new(InMemoryStorage::new()));
+        let handle: WorkflowHandle<u32> = worker
+            .client()
+            .start_workflow::<Pipeline>(1, StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        let failure = handle.result().await.unwrap_err().to_string();
+        assert!(failure.contains("does not host sessions"), "{}", failure);
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_fails_when_its_host_goes_away() {
+        let task_queue: Arc<dyn TaskQueue> = Arc::new(InMemoryTaskQueue::new());
+        let storage: Arc<dyn WorkflowStorage> = Arc::new(InMemoryStorage::new());
+        let (first, run) = spawn_host(1, task_queue.clone(), storage.clone());
+        let handle: WorkflowHandle<()> = first
+            .client()
+            .start_workflow::<Interrupted>((), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !STALLED.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let old_host = first.session_task_queue().unwrap().to_string();
+        first.shutdown(Duration::ZERO);
+        run.await.unwrap();
+
+        let (second, run) = spawn_host(1, task_queue, storage);
+        tokio::time::timeout(Duration::from_secs(5), handle.result()).await.unwrap().unwrap();
+        let events = session_events(&handle.history().await.unwrap());
+        let new_host = second.session_task_queue().unwrap();
+        assert!(
+            matches!(&events[..], [
+                EventType::SessionCreated { host_task_queue: a, .. },
+                EventType::SessionFailed { failure, .. },
+                EventType::SessionCreated { host_task_queue: b, .. },
+                EventType::SessionCompleted { .. },
+            ] if *a == old_host && failure.contains(&old_host) && b == new_host),
+            "{:?}",
+            events
+        );
+        second.shutdown(Duration::ZERO);
+        run.await.unwrap();
+    }
+}
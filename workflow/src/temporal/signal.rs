@@ -1,6 +1,18 @@
 //! Signal definitions and handling
 
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::Mutex;
 use serde::{Serialize, de::DeserializeOwned};
+use tokio::sync::Notify;
+
+/// Reserved signal name carrying a cancellation request from [`WorkflowClient::cancel_workflow`](super::WorkflowClient::cancel_workflow)
+pub(crate) const CANCEL_REQUEST_SIGNAL: &str = "__cancel_requested";
+
+/// Reserved signal name carrying the reason of [`WorkflowClient::terminate_workflow`](super::WorkflowClient::terminate_workflow)
+pub(crate) const TERMINATE_SIGNAL: &str = "__terminate";
 
 /// Signal trait - defines the signal interface
 pub trait Signal: Serialize + DeserializeOwned + Send + 'static {
@@ -8,6 +20,111 @@ pub trait Signal: Serialize + DeserializeOwned + Send + 'static {
     fn name() -> &'static str;
 }
 
+/// Callback invoked with the payload of every signal of one name
+pub(crate) type SignalHandler = Arc<dyn Fn(serde_json::Value) + Send + Sync>;
+
+/// Signals delivered to one execution and not yet consumed, queued per signal name
+///
+/// Signals with a registered handler are passed to it instead of being queued.
+#[derive(Default)]
+pub(crate) struct SignalMailbox {
+    /// Payloads by signal name, numbered in order of arrival across all names
+    pending: Mutex<HashMap<String, VecDeque<(u64, serde_json::Value)>>>,
+    arrivals: AtomicU64,
+    handlers: Mutex<HashMap<String, SignalHandler>>,
+    arrived: Notify,
+}
+
+impl SignalMailbox {
+    /// Queue a signal payload, or pass it to the signal's handler, and wake any waiters
+    pub(crate) fn deliver(&self, signal_name: &str, input: serde_json::Value) {
+        let handler = self.handlers.lock().get(signal_name).cloned();
+        match handler {
+            Some(handler) => handler(input),
+            None => self
+                .pending
+                .lock()
+                .entry(signal_name.to_string())
+                .or_default()
+                .push_back((self.arrivals.fetch_add(1, Ordering::Relaxed), input)),
+        }
+        self.arrived.notify_waiters();
+    }
+
+    /// Handle every signal of a name with `handler`, starting with those already queued
+    pub(crate) fn set_handler(&self, signal_name: &str, handler: SignalHandler) {
+        self.handlers.lock().insert(signal_name.to_string(), handler.clone());
+        let queued = self.pending.lock().remove(signal_name).unwrap_or_default();
+        for (_, input) in queued {
+            handler(input);
+        }
+        self.arrived.notify_waiters();
+    }
+
+    /// Wake waiters to check their conditions again, after workflow state changed outside a signal
+    pub(crate) fn wake(&self) {
+        self.arrived.notify_waiters();
+    }
+
+    /// Wait until `condition` holds, checking it again after every delivered signal
+    pub(crate) async fn wait_until(&self, mut condition: impl FnMut() -> bool) {
+        loop {
+            let arrived = self.arrived.notified();
+            tokio::pin!(arrived);
+            arrived.as_mut().enable();
+            if condition() {
+                return;
+            }
+            arrived.await;
+        }
+    }
+
+    /// Take the oldest pending payload for a signal name
+    pub(crate) fn take(&self, signal_name: &str) -> Option<serde_json::Value> {
+        self.pending.lock().get_mut(signal_name)?.pop_front().map(|(_, input)| input)
+    }
+
+    /// Take the payload that arrived first among several signal names, with its name
+    #[cfg_attr(not(feature = "patterns"), allow(dead_code))]
+    fn take_any(&self, signal_names: &[&str]) -> Option<(String, serde_json::Value)> {
+        let mut pending = self.pending.lock();
+        let name = signal_names
+            .iter()
+            .filter_map(|name| pending.get(*name)?.front().map(|(arrival, _)| (*arrival, *name)))
+            .min()?
+            .1;
+        let (_, input) = pending.get_mut(name)?.pop_front()?;
+        Some((name.to_string(), input))
+    }
+
+    /// Wait until a payload for the signal name is available and take it
+    pub(crate) async fn receive(&self, signal_name: &str) -> serde_json::Value {
+        loop {
+            let arrived = self.arrived.notified();
+            tokio::pin!(arrived);
+            arrived.as_mut().enable();
+            if let Some(input) = self.take(signal_name) {
+                return input;
+            }
+            arrived.await;
+        }
+    }
+
+    /// Wait until a payload for any of the signal names is available and take the oldest one
+    #[cfg_attr(not(feature = "patterns"), allow(dead_code))]
+    pub(crate) async fn receive_any(&self, signal_names: &[&str]) -> (String, serde_json::Value) {
+        loop {
+            let arrived = self.arrived.notified();
+            tokio::pin!(arrived);
+            arrived.as_mut().enable();
+            if let Some(signal) = self.take_any(signal_names) {
+                return signal;
+            }
+            arrived.await;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -28,5 +145,46 @@ mod tests {
     fn test_signal_name() {
         assert_eq!(TestSignal::name(), "test_signal");
     }
+
+    #[tokio::test]
+    async fn test_mailbox_queues_per_name_and_wakes_receivers() {
+        let mailbox = std::sync::Arc::new(SignalMailbox::default());
+        mailbox.deliver("other", serde_json::json!(0));
+        let receiver = {
+            let mailbox = mailbox.clone();
+            tokio::spawn(async move { mailbox.receive("test_signal").await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        mailbox.deliver("test_signal", serde_json::json!(1));
+        mailbox.deliver("test_signal", serde_json::json!(2));
+
+        assert_eq!(receiver.await.unwrap(), serde_json::json!(1));
+        assert_eq!(mailbox.take("test_signal"), Some(serde_json::json!(2)));
+        assert_eq!(mailbox.take("other"), Some(serde_json::json!(0)));
+        assert_eq!(mailbox.take("test_signal"), None);
+    }
+
+    #[tokio::test]
+    async fn test_handler_receives_queued_and_new_signals() {
+        let mailbox = std::sync::Arc::new(SignalMailbox::default());
+        let total = std::sync::Arc::new(std::sync::atomic::AtomicI64::new(0));
+        mailbox.deliver("add", serde_json::json!(1));
+        let sum = total.clone();
+        mailbox.set_handler(
+            "add",
+            Arc::new(move |input| {
+                sum.fetch_add(input.as_i64().unwrap(), std::sync::atomic::Ordering::SeqCst);
+            }),
+        );
+        assert_eq!(mailbox.take("add"), None);
+
+        let waiter = {
+            let (mailbox, total) = (mailbox.clone(), total.clone());
+            tokio::spawn(async move { mailbox.wait_until(|| total.load(std::sync::atomic::Ordering::SeqCst) >= 3).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        mailbox.deliver("add", serde_json::json!(2));
+        waiter.await.unwrap();
+    }
 }
 
@@ -0,0 +1,156 @@
+//! Sticky history cache
+//!
+//! Keeps the histories of recently active runs in memory, keyed by run ID, so a worker that
+//! receives another task for a run it executed recently reads the history from memory instead of
+//! loading it from storage. Only the history is reused: the workflow code of the earlier task is
+//! gone, so the run is rebuilt and replayed from that history like after a cache miss. The
+//! `sticky` names of the configuration, statistics and metrics predate this description.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::RunId;
+
+struct Entries<V> {
+    values: HashMap<RunId, V>,
+    /// Least recently used first
+    order: VecDeque<RunId>,
+}
+
+/// Usage of a sticky cache since its worker was created
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct StickyCacheStats {
+    pub capacity: usize,
+    pub size: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// Least-recently-used cache of per-run histories
+pub(crate) struct HistoryCache<V> {
+    capacity: usize,
+    entries: Mutex<Entries<V>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl<V: Clone> HistoryCache<V> {
+    /// Cache holding at most `capacity` runs; zero disables caching
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(Entries {
+                values: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Cached value for a run, marking it as most recently used
+    pub(crate) fn get(&self, run_id: &RunId) -> Option<V> {
+        let mut entries = self.entries.lock();
+        let Some(value) = entries.values.get(run_id).cloned() else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        entries.order.retain(|id| id != run_id);
+        entries.order.push_back(*run_id);
+        Some(value)
+    }
+
+    /// Cache a value for a run, evicting the least recently used runs beyond capacity
+    pub(crate) fn insert(&self, run_id: RunId, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock();
+        if entries.values.insert(run_id, value).is_some() {
+            entries.order.retain(|id| *id != run_id);
+        }
+        entries.order.push_back(run_id);
+        while entries.order.len() > self.capacity {
+            if let Some(evicted) = entries.order.pop_front() {
+                entries.values.remove(&evicted);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+                metrics::counter!("temporal_sticky_cache_evictions_total").increment(1);
+            }
+        }
+    }
+
+    /// Drop a run that will get no more tasks; unlike capacity evictions this is not counted
+    pub(crate) fn remove(&self, run_id: &RunId) {
+        let mut entries = self.entries.lock();
+        if entries.values.remove(run_id).is_some() {
+            entries.order.retain(|id| id != run_id);
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.lock().values.len()
+    }
+
+    pub(crate) fn stats(&self) -> StickyCacheStats {
+        StickyCacheStats {
+            capacity: self.capacity,
+            size: self.len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = HistoryCache::new(2);
+        let (a, b, c) = (RunId::generate(), RunId::generate(), RunId::generate());
+        cache.insert(a, "a");
+        cache.insert(b, "b");
+        assert_eq!(cache.get(&a), Some("a"));
+        cache.insert(c, "c");
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&b), None);
+        assert_eq!(cache.get(&a), Some("a"));
+        assert_eq!(cache.get(&c), Some("c"));
+        let stats = cache.stats();
+        assert_eq!((stats.size, stats.hits, stats.misses, stats.evictions), (2, 3, 1, 1));
+    }
+
+    #[test]
+    fn test_removed_runs_free_their_slot() {
+        let cache = HistoryCache::new(2);
+        let (a, b, c) = (RunId::generate(), RunId::generate(), RunId::generate());
+        cache.insert(a, "a");
+        cache.insert(b, "b");
+        cache.remove(&a);
+        cache.insert(c, "c");
+
+        assert_eq!(cache.get(&a), None);
+        assert_eq!(cache.get(&b), Some("b"));
+        assert_eq!(cache.get(&c), Some("c"));
+        assert_eq!(cache.stats().evictions, 0);
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_cache() {
+        let cache = HistoryCache::new(0);
+        let run_id = RunId::generate();
+        cache.insert(run_id, 1);
+        assert_eq!(cache.get(&run_id), None);
+    }
+}
@@ -0,0 +1,344 @@
+//! Storage abstraction for workflow persistence
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use super::{EventId, WorkflowId, WorkflowExecution, error::StorageError};
+use super::event::{EventHistory, WorkflowEvent};
+use super::outbox::OutboxMessage;
+use super::search::{WorkflowExecutionInfo, WorkflowFilter};
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteStorage;
+
+/// Workflow storage trait
+#[async_trait]
+pub trait WorkflowStorage: Send + Sync {
+    /// Save workflow execution
+    async fn save_workflow_execution(
+        &self,
+        execution: &WorkflowExecution,
+        history: &EventHistory,
+    ) -> Result<(), StorageError>;
+    
+    /// Load workflow execution
+    async fn load_workflow_execution(
+        &self,
+        workflow_id: &WorkflowId,
+    ) -> Result<(WorkflowExecution, EventHistory), StorageError>;
+
+    /// Summaries of the stored executions matching `filter`
+    async fn list_workflow_executions(
+        &self,
+        filter: &WorkflowFilter,
+    ) -> Result<Vec<WorkflowExecutionInfo>, StorageError>;
+
+    /// Save an execution together with the [outbox](super::outbox) messages staged by its
+    /// activities, in one write
+    ///
+    /// The default saves executions without messages and fails otherwise; backends with an
+    /// outbox override it along with [`pending_outbox`](Self::pending_outbox) and
+    /// [`mark_outbox_sent`](Self::mark_outbox_sent).
+    async fn save_with_outbox(
+        &self,
+        execution: &WorkflowExecution,
+        history: &EventHistory,
+        outbox: &[OutboxMessage],
+    ) -> Result<(), StorageError> {
+        if !outbox.is_empty() {
+            return Err(StorageError::Custom("storage has no outbox".to_string()));
+        }
+        self.save_workflow_execution(execution, history).await
+    }
+
+    /// Up to `limit` outbox messages not yet marked sent, oldest first
+    async fn pending_outbox(&self, _limit: usize) -> Result<Vec<OutboxMessage>, StorageError> {
+        Ok(Vec::new())
+    }
+
+    /// Remove published messages from the outbox; unknown IDs are ignored
+    async fn mark_outbox_sent(&self, _ids: &[String]) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    /// Take [lock](super::lock) `name` for `owner`, or extend the lease `owner` holds, until `lease`
+    /// from now; `false` if another owner holds an unexpired lease
+    ///
+    /// The default fails; backends shared by the workers of a deployment override it along with
+    /// [`release_lock`](Self::release_lock).
+    async fn lease_lock(&self, name: &str, owner: &str, _lease: Duration) -> Result<bool, StorageError> {
+        Err(StorageError::Custom(format!("storage cannot lock {} for {}", name, owner)))
+    }
+
+    /// Give up the lease `owner` holds on lock `name`; does nothing if it holds none
+    async fn release_lock(&self, name: &str, owner: &str) -> Result<(), StorageError> {
+        Err(StorageError::Custom(format!("storage cannot unlock {} for {}", name, owner)))
+    }
+
+    /// Remove the stored execution of a workflow ID; removing an unknown ID succeeds
+    async fn delete_workflow_execution(&self, workflow_id: &WorkflowId) -> Result<(), StorageError> {
+        Err(StorageError::Custom(format!("storage cannot delete executions: {}", workflow_id)))
+    }
+
+    /// Up to `limit` events of the latest run of a workflow following `after_event_id`, oldest first
+    ///
+    /// Pass [`EventId::zero`] for the first page and the `next_after` of a page for the one after it.
+    /// The default loads the whole history and cuts the page out of it; backends able to read a
+    /// slice of the history override it.
+    async fn load_events(
+        &self,
+        workflow_id: &WorkflowId,
+        after_event_id: EventId,
+        limit: usize,
+    ) -> Result<HistoryPage, StorageError> {
+        let (execution, history) = self.load_workflow_execution(workflow_id).await?;
+        Ok(HistoryPage::of(execution, &history, after_event_id, limit))
+    }
+
+    /// Check that the backend is reachable, for readiness probes
+    ///
+    /// The default reports in-process storage as always reachable.
+    async fn ping(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+/// Page of the history of a workflow's latest run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub execution: WorkflowExecution,
+    pub events: Vec<WorkflowEvent>,
+    /// Event ID to read the next page after; `None` on the last page
+    pub next_after: Option<EventId>,
+}
+
+impl HistoryPage {
+    /// Cut a page out of a whole history
+    pub fn of(execution: WorkflowExecution, history: &EventHistory, after_event_id: EventId, limit: usize) -> Self {
+        let events = history.events();
+        let start = events.partition_point(|e| e.event_id <= after_event_id);
+        let end = events.len().min(start.saturating_add(limit.max(1)).saturating_add(1));
+        Self::from_events(execution, events[start..end].to_vec(), limit)
+    }
+
+    /// Page of up to `limit` events out of `events`, which holds one more event if there are further pages
+    pub(crate) fn from_events(execution: WorkflowExecution, mut events: Vec<WorkflowEvent>, limit: usize) -> Self {
+        let limit = limit.max(1);
+        let more = events.len() > limit;
+        events.truncate(limit);
+        let next_after = events.last().map(|e| e.event_id).filter(|_| more);
+        Self {
+            execution,
+            events,
+            next_after,
+        }
+    }
+}
+
+/// In-memory storage (for testing and single-process use)
+///
+/// Keeps the latest run of each workflow ID.
+#[derive(Default)]
+pub struct InMemoryStorage {
+    executions: RwLock<HashMap<WorkflowId, (WorkflowExecution, EventHistory)>>,
+    /// Pending outbox messages by staging order
+    outbox: RwLock<BTreeMap<u64, OutboxMessage>>,
+    /// Owner and lease expiry of each held lock
+    locks: RwLock<HashMap<String, (String, Instant)>>,
+}
+
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WorkflowStorage for InMemoryStorage {
+    async fn save_workflow_execution(
+        &self,
+        execution: &WorkflowExecution,
+        history: &EventHistory,
+    ) -> Result<(), StorageError> {
+        self.executions
+            .write()
+            .insert(execution.workflow_id.clone(), (execution.clone(), history.clone()));
+        Ok(())
+    }
+    
+    /// Holds the execution and outbox locks together, so readers see both or neither; a message ID
+    /// already in the outbox is kept
+    async fn save_with_outbox(
+        &self,
+        execution: &WorkflowExecution,
+        history: &EventHistory,
+        outbox: &[OutboxMessage],
+    ) -> Result<(), StorageError> {
+        let mut executions = self.executions.write();
+        let mut pending = self.outbox.write();
+        let mut seq = pending.last_key_value().map_or(0, |(seq, _)| seq + 1);
+        for message in outbox {
+            if pending.values().any(|staged| staged.id == message.id) {
+                continue;
+            }
+            pending.insert(seq, message.clone());
+            seq += 1;
+        }
+        executions.insert(execution.workflow_id.clone(), (execution.clone(), history.clone()));
+        Ok(())
+    }
+
+    async fn pending_outbox(&self, limit: usize) -> Result<Vec<OutboxMessage>, StorageError> {
+        Ok(self.outbox.read().values().take(limit).cloned().collect())
+    }
+
+    async fn mark_outbox_sent(&self, ids: &[String]) -> Result<(), StorageError> {
+        self.outbox.write().retain(|_, message| !ids.contains(&message.id));
+        Ok(())
+    }
+
+    async fn lease_lock(&self, name: &str, owner: &str, lease: Duration) -> Result<bool, StorageError> {
+        let now = Instant::now();
+        let mut locks = self.locks.write();
+        if let Some((holder, expires)) = locks.get(name)
+            && holder != owner
+            && *expires > now
+        {
+            return Ok(false);
+        }
+        locks.insert(name.to_string(), (owner.to_string(), now + lease));
+        Ok(true)
+    }
+
+    async fn release_lock(&self, name: &str, owner: &str) -> Result<(), StorageError> {
+        let mut locks = self.locks.write();
+        if locks.get(name).is_some_and(|(holder, _)| holder == owner) {
+            locks.remove(name);
+        }
+        Ok(())
+    }
+
+    async fn load_workflow_execution(
+        &self,
+        workflow_id: &WorkflowId,
+    ) -> Result<(WorkflowExecution, EventHistory), StorageError> {
+        self.executions
+            .read()
+            .get(workflow_id)
+            .cloned()
+            .ok_or(StorageError::NotFound)
+    }
+
+    async fn list_workflow_executions(
+        &self,
+        filter: &WorkflowFilter,
+    ) -> Result<Vec<WorkflowExecutionInfo>, StorageError> {
+        Ok(self
+            .executions
+            .read()
+            .values()
+            .filter_map(|(execution, history)| WorkflowExecutionInfo::from_history(execution.clone(), history))
+            .filter(|info| filter.matches(info))
+            .collect())
+    }
+
+    async fn delete_workflow_execution(&self, workflow_id: &WorkflowId) -> Result<(), StorageError> {
+        self.executions.write().remove(workflow_id);
+        Ok(())
+    }
+
+    /// Clones only the events of the page
+    async fn load_events(
+        &self,
+        workflow_id: &WorkflowId,
+        after_event_id: EventId,
+        limit: usize,
+    ) -> Result<HistoryPage, StorageError> {
+        let executions = self.executions.read();
+        let (execution, history) = executions.get(workflow_id).ok_or(StorageError::NotFound)?;
+        Ok(HistoryPage::of(execution.clone(), history, after_event_id, limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::event::EventType;
+
+    #[tokio::test]
+    async fn test_in_memory_storage() {
+        let storage = InMemoryStorage::new();
+        let workflow_id = WorkflowId::new("test");
+        let result = storage.load_workflow_execution(&workflow_id).await;
+        
+        assert!(result.is_err());
+
+        let execution = WorkflowExecution::new(workflow_id.clone());
+        let mut history = EventHistory::new();
+        history.append(EventType::WorkflowExecutionStarted {
+            workflow_type: "TestWorkflow".to_string(),
+            task_queue: "default".to_string(),
+            input: serde_json::json!({}),
+        });
+        storage.save_workflow_execution(&execution, &history).await.unwrap();
+
+        let (loaded, loaded_history) = storage.load_workflow_execution(&workflow_id).await.unwrap();
+        assert_eq!(loaded, execution);
+        assert_eq!(loaded_history.len(), 1);
+
+        storage.delete_workflow_execution(&workflow_id).await.unwrap();
+        assert!(matches!(storage.load_workflow_execution(&workflow_id).await, Err(StorageError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_locks_are_leased_to_one_owner() {
+        let storage = InMemoryStorage::new();
+        let lease = Duration::from_millis(50);
+        assert!(storage.lease_lock("account-1", "a", lease).await.unwrap());
+        assert!(!storage.lease_lock("account-1", "b", lease).await.unwrap());
+        // Renewing extends the lease of the holder
+        assert!(storage.lease_lock("account-1", "a", lease).await.unwrap());
+        assert!(storage.lease_lock("account-2", "b", lease).await.unwrap());
+
+        storage.release_lock("account-1", "b").await.unwrap();
+        assert!(!storage.lease_lock("account-1", "b", lease).await.unwrap());
+        storage.release_lock("account-1", "a").await.unwrap();
+        assert!(storage.lease_lock("account-1", "b", lease).await.unwrap());
+
+        tokio::time::sleep(lease * 2).await;
+        assert!(storage.lease_lock("account-2", "a", lease).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_events_read_in_pages() {
+        let storage = InMemoryStorage::new();
+        let execution = WorkflowExecution::new(WorkflowId::new("paged"));
+        let mut history = EventHistory::new();
+        for seq in 0..5 {
+            history.append(EventType::SideEffectRecorded { seq, value: serde_json::json!(seq) });
+        }
+        storage.save_workflow_execution(&execution, &history).await.unwrap();
+
+        let mut after = EventId::zero();
+        let mut pages = Vec::new();
+        loop {
+            let page = storage.load_events(&execution.workflow_id, after, 2).await.unwrap();
+            assert_eq!(page.execution, execution);
+            pages.push(page.events.iter().map(|e| e.event_id.0).collect::<Vec<_>>());
+            match page.next_after {
+                Some(next) => after = next,
+                None => break,
+            }
+        }
+        assert_eq!(pages, vec![vec![1, 2], vec![3, 4], vec![5]]);
+        let page = storage.load_events(&execution.workflow_id, EventId(5), 2).await.unwrap();
+        assert!(page.events.is_empty() && page.next_after.is_none());
+        let missing = storage.load_events(&WorkflowId::new("missing"), EventId::zero(), 2).await;
+        assert!(matches!(missing, Err(StorageError::NotFound)));
+    }
+}
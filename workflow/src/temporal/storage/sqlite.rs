@@ -0,0 +1,495 @@
+//! SQLite storage backend
+//!
+//! Runs the engine as a single binary without external infrastructure. The schema is created on
+//! connect and file databases use WAL journaling so readers do not block the writer.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sqlx::Row;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+
+use super::{HistoryPage, WorkflowStorage};
+use crate::persistence::{PersistenceAdapter, StateSnapshot};
+use crate::temporal::error::StorageError;
+use crate::temporal::event::{EventHistory, WorkflowEvent};
+use crate::temporal::outbox::OutboxMessage;
+use crate::temporal::search::{WorkflowExecutionInfo, WorkflowFilter};
+use crate::temporal::{EventId, RunId, WorkflowExecution, WorkflowId};
+
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS workflow_executions (
+        workflow_id TEXT PRIMARY KEY,
+        run_id TEXT NOT NULL,
+        history TEXT NOT NULL,
+        closed INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS workflow_states (
+        workflow_id TEXT PRIMARY KEY,
+        state TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS idempotency_keys (
+        key TEXT PRIMARY KEY,
+        expires_at INTEGER NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS workflow_locks (
+        name TEXT PRIMARY KEY,
+        owner TEXT NOT NULL,
+        expires_at INTEGER NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS outbox_messages (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        id TEXT NOT NULL UNIQUE,
+        message TEXT NOT NULL
+    )",
+];
+
+/// How long a connection waits for the write lock before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SQLite-backed [`WorkflowStorage`] and [`PersistenceAdapter`]
+///
+/// Like [`InMemoryStorage`](super::InMemoryStorage), keeps the latest run of each workflow ID.
+#[derive(Clone)]
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    /// Open (creating if needed) the database at `url`, e.g. `sqlite://workflows.db`, and bootstrap the schema
+    pub async fn connect(url: &str) -> Result<Self, StorageError> {
+        let options = SqliteConnectOptions::from_str(url)
+            .map_err(|e| StorageError::ConnectionError(e.to_string()))?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(BUSY_TIMEOUT);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .map_err(|e| StorageError::ConnectionError(e.to_string()))?;
+        Self::with_pool(pool).await
+    }
+
+    /// Private in-memory database, for tests
+    ///
+    /// Every SQLite connection to `:memory:` sees its own database, so the pool holds exactly one.
+    pub async fn in_memory() -> Result<Self, StorageError> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .map_err(|e| StorageError::ConnectionError(e.to_string()))?;
+        Self::with_pool(pool).await
+    }
+
+    /// Use an existing pool, bootstrapping the schema
+    pub async fn with_pool(pool: SqlitePool) -> Result<Self, StorageError> {
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&pool).await.map_err(query_error)?;
+        }
+        Ok(Self { pool })
+    }
+
+    /// Underlying connection pool
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+}
+
+fn query_error(e: sqlx::Error) -> StorageError {
+    StorageError::QueryError(e.to_string())
+}
+
+fn serialization_error(e: impl std::fmt::Display) -> StorageError {
+    StorageError::SerializationError(e.to_string())
+}
+
+async fn upsert_execution<'e, E: sqlx::SqliteExecutor<'e>>(
+    executor: E,
+    execution: &WorkflowExecution,
+    history: &EventHistory,
+) -> Result<(), StorageError> {
+    let encoded = serde_json::to_string(history).map_err(serialization_error)?;
+    sqlx::query(
+        "INSERT INTO workflow_executions (workflow_id, run_id, history, closed, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(workflow_id) DO UPDATE SET
+            run_id = excluded.run_id,
+            history = excluded.history,
+            closed = excluded.closed,
+            updated_at = excluded.updated_at",
+    )
+    .bind(execution.workflow_id.as_str())
+    .bind(execution.run_id.to_string())
+    .bind(encoded)
+    .bind(history.is_closed())
+    .bind(chrono::Utc::now().timestamp_millis())
+    .execute(executor)
+    .await
+    .map_err(query_error)?;
+    Ok(())
+}
+
+#[async_trait]
+impl WorkflowStorage for SqliteStorage {
+    async fn save_workflow_execution(
+        &self,
+        execution: &WorkflowExecution,
+        history: &EventHistory,
+    ) -> Result<(), StorageError> {
+        upsert_execution(&self.pool, execution, history).await
+    }
+
+    /// Writes the execution and the messages in one transaction; a message ID already in the outbox is kept
+    async fn save_with_outbox(
+        &self,
+        execution: &WorkflowExecution,
+        history: &EventHistory,
+        outbox: &[OutboxMessage],
+    ) -> Result<(), StorageError> {
+        let mut tx = self.pool.begin().await.map_err(query_error)?;
+        upsert_execution(&mut *tx, execution, history).await?;
+        for message in outbox {
+            sqlx::query("INSERT INTO outbox_messages (id, message) VALUES (?1, ?2) ON CONFLICT(id) DO NOTHING")
+                .bind(&message.id)
+                .bind(serde_json::to_string(message).map_err(serialization_error)?)
+                .execute(&mut *tx)
+                .await
+                .map_err(query_error)?;
+        }
+        tx.commit().await.map_err(query_error)
+    }
+
+    async fn lease_lock(&self, name: &str, owner: &str, lease: Duration) -> Result<bool, StorageError> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let expires_at = now.saturating_add(i64::try_from(lease.as_millis()).unwrap_or(i64::MAX));
+        // Taken over when free, expired, or already held by the owner
+        let result = sqlx::query(
+            "INSERT INTO workflow_locks (name, owner, expires_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(name) DO UPDATE SET owner = excluded.owner, expires_at = excluded.expires_at
+             WHERE workflow_locks.owner = excluded.owner OR workflow_locks.expires_at <= ?4",
+        )
+        .bind(name)
+        .bind(owner)
+        .bind(expires_at)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map_err(query_error)?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn release_lock(&self, name: &str, owner: &str) -> Result<(), StorageError> {
+        sqlx::query("DELETE FROM workflow_locks WHERE name = ?1 AND owner = ?2")
+            .bind(name)
+            .bind(owner)
+            .execute(&self.pool)
+            .await
+            .map_err(query_error)?;
+        Ok(())
+    }
+
+    async fn pending_outbox(&self, limit: usize) -> Result<Vec<OutboxMessage>, StorageError> {
+        let rows = sqlx::query("SELECT message FROM outbox_messages ORDER BY seq LIMIT ?1")
+            .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(query_error)?;
+        rows.iter()
+            .map(|row| {
+                let message: String = row.try_get("message").map_err(query_error)?;
+                serde_json::from_str(&message).map_err(serialization_error)
+            })
+            .collect()
+    }
+
+    async fn mark_outbox_sent(&self, ids: &[String]) -> Result<(), StorageError> {
+        sqlx::query("DELETE FROM outbox_messages WHERE id IN (SELECT value FROM json_each(?1))")
+            .bind(serde_json::to_string(ids).map_err(serialization_error)?)
+            .execute(&self.pool)
+            .await
+            .map_err(query_error)?;
+        Ok(())
+    }
+
+    async fn load_workflow_execution(
+        &self,
+        workflow_id: &WorkflowId,
+    ) -> Result<(WorkflowExecution, EventHistory), StorageError> {
+        let row = sqlx::query("SELECT run_id, history FROM workflow_executions WHERE workflow_id = ?1")
+            .bind(workflow_id.as_str())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(query_error)?
+            .ok_or(StorageError::NotFound)?;
+        let run_id: String = row.try_get("run_id").map_err(query_error)?;
+        let history: String = row.try_get("history").map_err(query_error)?;
+        let execution = WorkflowExecution {
+            workflow_id: workflow_id.clone(),
+            run_id: RunId::parse(&run_id).map_err(serialization_error)?,
+        };
+        Ok((execution, serde_json::from_str(&history).map_err(serialization_error)?))
+    }
+
+    /// Scans every stored execution; search attributes live in the history, not in columns
+    async fn list_workflow_executions(
+        &self,
+        filter: &WorkflowFilter,
+    ) -> Result<Vec<WorkflowExecutionInfo>, StorageError> {
+        let rows = sqlx::query("SELECT workflow_id, run_id, history FROM workflow_executions")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(query_error)?;
+        let mut infos = Vec::new();
+        for row in rows {
+            let workflow_id: String = row.try_get("workflow_id").map_err(query_error)?;
+            let run_id: String = row.try_get("run_id").map_err(query_error)?;
+            let history: String = row.try_get("history").map_err(query_error)?;
+            let execution = WorkflowExecution {
+                workflow_id: WorkflowId::new(workflow_id),
+                run_id: RunId::parse(&run_id).map_err(serialization_error)?,
+            };
+            let history: EventHistory = serde_json::from_str(&history).map_err(serialization_error)?;
+            if let Some(info) = WorkflowExecutionInfo::from_history(execution, &history)
+                && filter.matches(&info)
+            {
+                infos.push(info);
+            }
+        }
+        Ok(infos)
+    }
+
+    async fn delete_workflow_execution(&self, workflow_id: &WorkflowId) -> Result<(), StorageError> {
+        sqlx::query("DELETE FROM workflow_executions WHERE workflow_id = ?1")
+            .bind(workflow_id.as_str())
+            .execute(&self.pool)
+            .await
+            .map_err(query_error)?;
+        Ok(())
+    }
+
+    /// Picks the page out of the stored history with SQLite's JSON functions, so only its events are decoded
+    async fn load_events(
+        &self,
+        workflow_id: &WorkflowId,
+        after_event_id: EventId,
+        limit: usize,
+    ) -> Result<HistoryPage, StorageError> {
+        // One row more than the page tells whether another page follows; a run without matching events yields one
+        // row with a null event
+        let rows = sqlx::query(
+            "SELECT w.run_id, e.value AS event
+             FROM workflow_executions w
+             LEFT JOIN json_each(w.history, '$.events') e ON json_extract(e.value, '$.event_id') > ?2
+             WHERE w.workflow_id = ?1
+             ORDER BY e.key
+             LIMIT ?3",
+        )
+        .bind(workflow_id.as_str())
+        .bind(i64::try_from(after_event_id.0).unwrap_or(i64::MAX))
+        .bind(i64::try_from(limit.max(1)).unwrap_or(i64::MAX).saturating_add(1))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(query_error)?;
+        let Some(first) = rows.first() else {
+            return Err(StorageError::NotFound);
+        };
+        let run_id: String = first.try_get("run_id").map_err(query_error)?;
+        let execution = WorkflowExecution {
+            workflow_id: workflow_id.clone(),
+            run_id: RunId::parse(&run_id).map_err(serialization_error)?,
+        };
+        let mut events = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let event: Option<String> = row.try_get("event").map_err(query_error)?;
+            if let Some(event) = event {
+                events.push(serde_json::from_str::<WorkflowEvent>(&event).map_err(serialization_error)?);
+            }
+        }
+        Ok(HistoryPage::from_events(execution, events, limit))
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| StorageError::ConnectionError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PersistenceAdapter for SqliteStorage {
+    async fn save_state(&self, snapshot: StateSnapshot) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO workflow_states (workflow_id, state, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(workflow_id) DO UPDATE SET state = excluded.state, updated_at = excluded.updated_at",
+        )
+        .bind(&snapshot.workflow_id)
+        .bind(serde_json::to_string(&snapshot.state)?)
+        .bind(snapshot.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn load_state(&self, workflow_id: &str) -> anyhow::Result<Option<StateSnapshot>> {
+        let row = sqlx::query("SELECT state, updated_at FROM workflow_states WHERE workflow_id = ?1")
+            .bind(workflow_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(row) = row else { return Ok(None) };
+        let state: String = row.try_get("state")?;
+        Ok(Some(StateSnapshot {
+            workflow_id: workflow_id.to_string(),
+            state: serde_json::from_str(&state)?,
+            updated_at: row.try_get("updated_at")?,
+        }))
+    }
+
+    async fn put_idempotency_key(&self, key: &str, ttl_seconds: u64) -> anyhow::Result<bool> {
+        let now = chrono::Utc::now().timestamp();
+        let expires_at = now.saturating_add(i64::try_from(ttl_seconds).unwrap_or(i64::MAX));
+        // An expired key is taken over as if it were absent
+        let result = sqlx::query(
+            "INSERT INTO idempotency_keys (key, expires_at) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET expires_at = excluded.expires_at
+             WHERE idempotency_keys.expires_at <= ?3",
+        )
+        .bind(key)
+        .bind(expires_at)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::event::EventType;
+
+    #[tokio::test]
+    async fn test_execution_roundtrip_keeps_latest_run() {
+        let storage = SqliteStorage::in_memory().await.unwrap();
+        let workflow_id = WorkflowId::new("wf-1");
+        assert!(matches!(
+            storage.load_workflow_execution(&workflow_id).await,
+            Err(StorageError::NotFound)
+        ));
+
+        let mut history = EventHistory::new();
+        history.append(EventType::WorkflowExecutionStarted {
+            workflow_type: "test".to_string(),
+            task_queue: "default".to_string(),
+            input: serde_json::json!({"n": 1}),
+        });
+        let first = WorkflowExecution::new(workflow_id.clone());
+        storage.save_workflow_execution(&first, &history).await.unwrap();
+        let second = WorkflowExecution::new(workflow_id.clone());
+        history.append(EventType::WorkflowExecutionCompleted { result: serde_json::json!(2) });
+        storage.save_workflow_execution(&second, &history).await.unwrap();
+
+        let (execution, loaded) = storage.load_workflow_execution(&workflow_id).await.unwrap();
+        assert_eq!(execution, second);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.outcome(), Some(Ok(serde_json::json!(2))));
+
+        let listed = storage
+            .list_workflow_executions(&WorkflowFilter::new().workflow_type("test"))
+            .await
+            .unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].execution, second);
+        assert!(storage.list_workflow_executions(&WorkflowFilter::new().workflow_type("other")).await.unwrap().is_empty());
+
+        let page = storage.load_events(&workflow_id, EventId::zero(), 1).await.unwrap();
+        assert_eq!((page.execution, page.events.len(), page.next_after), (second, 1, Some(EventId(1))));
+        let page = storage.load_events(&workflow_id, EventId(1), 1).await.unwrap();
+        assert!(matches!(page.events[0].event_type, EventType::WorkflowExecutionCompleted { .. }));
+        assert_eq!(page.next_after, None);
+        assert!(storage.load_events(&workflow_id, EventId(2), 1).await.unwrap().events.is_empty());
+
+        storage.delete_workflow_execution(&workflow_id).await.unwrap();
+        assert!(matches!(
+            storage.load_workflow_execution(&workflow_id).await,
+            Err(StorageError::NotFound)
+        ));
+        assert!(matches!(storage.load_events(&workflow_id, EventId::zero(), 1).await, Err(StorageError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_persistence_adapter() {
+        let storage = SqliteStorage::in_memory().await.unwrap();
+        let snapshot = StateSnapshot { workflow_id: "wf1".into(), state: serde_json::json!({"s": "ok"}), updated_at: 7 };
+        storage.save_state(snapshot).await.unwrap();
+        let loaded = storage.load_state("wf1").await.unwrap().unwrap();
+        assert_eq!(loaded.state, serde_json::json!({"s": "ok"}));
+        assert_eq!(loaded.updated_at, 7);
+        assert!(storage.load_state("missing").await.unwrap().is_none());
+
+        assert!(storage.put_idempotency_key("k", 60).await.unwrap());
+        assert!(!storage.put_idempotency_key("k", 60).await.unwrap());
+        assert!(storage.put_idempotency_key("expired", 0).await.unwrap());
+        assert!(storage.put_idempotency_key("expired", 60).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_outbox_is_written_with_the_execution() {
+        use crate::temporal::outbox::{OutboxDestination, OutboxMessage};
+
+        let storage = SqliteStorage::in_memory().await.unwrap();
+        let execution = WorkflowExecution::new(WorkflowId::new("order-1"));
+        let staged_at = chrono::Utc::now();
+        let message = |n: u32| OutboxMessage {
+            id: format!("{}/charge-1/{}", execution.run_id, n),
+            execution: execution.clone(),
+            destination: OutboxDestination::kafka("payments"),
+            payload: serde_json::json!(n),
+            staged_at,
+        };
+        let history = EventHistory::new();
+        storage.save_with_outbox(&execution, &history, &[message(0), message(1)]).await.unwrap();
+        storage.save_with_outbox(&execution, &history, &[message(1)]).await.unwrap();
+        assert!(storage.load_workflow_execution(&execution.workflow_id).await.is_ok());
+        assert_eq!(storage.pending_outbox(10).await.unwrap(), vec![message(0), message(1)]);
+        assert_eq!(storage.pending_outbox(1).await.unwrap(), vec![message(0)]);
+
+        storage.mark_outbox_sent(&[message(0).id, "unknown".to_string()]).await.unwrap();
+        assert_eq!(storage.pending_outbox(10).await.unwrap(), vec![message(1)]);
+    }
+
+    #[tokio::test]
+    async fn test_locks_are_leased_to_one_owner() {
+        let storage = SqliteStorage::in_memory().await.unwrap();
+        let lease = Duration::from_secs(60);
+        assert!(storage.lease_lock("account-1", "a", lease).await.unwrap());
+        assert!(!storage.lease_lock("account-1", "b", lease).await.unwrap());
+        assert!(storage.lease_lock("account-1", "a", lease).await.unwrap());
+        storage.release_lock("account-1", "b").await.unwrap();
+        assert!(!storage.lease_lock("account-1", "b", lease).await.unwrap());
+        storage.release_lock("account-1", "a").await.unwrap();
+        assert!(storage.lease_lock("account-1", "b", Duration::ZERO).await.unwrap());
+        // An expired lease is taken over
+        assert!(storage.lease_lock("account-1", "a", lease).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_file_database_uses_wal() {
+        let path = std::env::temp_dir().join(format!("workflow-sqlite-{}.db", uuid::Uuid::new_v4()));
+        let storage = SqliteStorage::connect(&format!("sqlite://{}", path.display())).await.unwrap();
+        let mode: String = sqlx::query_scalar("PRAGMA journal_mode").fetch_one(storage.pool()).await.unwrap();
+        assert_eq!(mode, "wal");
+        storage.pool().close().await;
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+}
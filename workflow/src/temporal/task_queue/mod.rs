@@ -0,0 +1,473 @@
+//! Task queues connecting clients, workflow contexts and workers
+//!
+//! Workflow, activity and signal tasks travel on separate lanes of the same named queue so that
+//! a worker whose workflow slots are all busy can still pick up the activities and signals those
+//! workflows wait on.
+//!
+//! Within a lane, workflow and activity tasks are handed out by [`Priority`], oldest first within
+//! a priority. Tasks age while they wait: every aging interval counts as one level more urgent, so
+//! a backlog of urgent work delays low-priority tasks without starving them.
+//!
+//! [`InMemoryTaskQueue`] serves a single process; with the `database` feature,
+//! [`RedisStreamsTaskQueue`] shares queues between processes.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use super::error::StorageError;
+use super::metrics::{SCHEDULE_TO_START, TASK_PRIORITY_WAIT, TASK_QUEUE_DEPTH};
+use super::telemetry::TraceContext;
+use super::{ActivityId, WorkflowExecution, WorkflowId};
+
+#[cfg(feature = "database")]
+pub mod redis_streams;
+#[cfg(feature = "database")]
+pub use self::redis_streams::{RedisStreamsTaskQueue, StreamStats};
+
+/// Kind of task, selects the lane a task is queued on
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum TaskKind {
+    Workflow,
+    Activity,
+    Signal,
+}
+
+impl TaskKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TaskKind::Workflow => "workflow",
+            TaskKind::Activity => "activity",
+            TaskKind::Signal => "signal",
+        }
+    }
+}
+
+/// How long a task waits before it counts as one priority level more urgent, unless configured otherwise
+pub const DEFAULT_AGING: Duration = Duration::from_secs(10);
+
+/// Dequeue priority of a task, from [`Priority::HIGHEST`] (1) to [`Priority::LOWEST`] (5)
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(from = "u8", into = "u8")]
+pub struct Priority(u8);
+
+impl Priority {
+    pub const HIGHEST: Priority = Priority(1);
+    pub const DEFAULT: Priority = Priority(3);
+    pub const LOWEST: Priority = Priority(5);
+
+    /// Number of priority levels
+    pub const LEVELS: usize = 5;
+
+    /// Priority of `key`, clamped to the levels
+    pub fn new(key: u8) -> Self {
+        Self(key.clamp(Self::HIGHEST.0, Self::LOWEST.0))
+    }
+
+    /// Level of the priority, 1 being handed out first
+    pub fn key(self) -> u8 {
+        self.0
+    }
+
+    /// All priorities, highest first
+    pub fn levels() -> impl Iterator<Item = Priority> {
+        (Self::HIGHEST.0..=Self::LOWEST.0).map(Priority)
+    }
+
+    fn index(self) -> usize {
+        usize::from(self.0 - Self::HIGHEST.0)
+    }
+
+    /// Level a task of this priority counts as after waiting `waited`, lower is handed out first
+    ///
+    /// Each full `aging` interval waited takes one level off; a zero `aging` turns aging off.
+    pub(crate) fn aged(self, waited: Duration, aging: Duration) -> i64 {
+        let steps = if aging.is_zero() { 0 } else { waited.as_nanos() / aging.as_nanos() };
+        i64::from(self.0) - i64::try_from(steps).unwrap_or(i64::MAX)
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl From<u8> for Priority {
+    fn from(key: u8) -> Self {
+        Self::new(key)
+    }
+}
+
+impl From<Priority> for u8 {
+    fn from(priority: Priority) -> Self {
+        priority.0
+    }
+}
+
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Request to run (or resume) a workflow execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowTask {
+    pub execution: WorkflowExecution,
+    pub workflow_type: String,
+    pub task_queue: String,
+    pub input: serde_json::Value,
+    /// Priority of the run, inherited by its activities
+    #[serde(default)]
+    pub priority: Priority,
+    /// Trace context of the span that enqueued the task
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub trace_context: TraceContext,
+}
+
+/// Request to run one attempt of an activity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityTask {
+    pub activity_id: ActivityId,
+    pub activity_type: String,
+    pub workflow_execution: WorkflowExecution,
+    pub input: serde_json::Value,
+    pub attempt: u32,
+    pub heartbeat_timeout: Option<Duration>,
+    #[serde(default)]
+    pub priority: Priority,
+    /// Trace context of the span that enqueued the task
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub trace_context: TraceContext,
+}
+
+/// Request to deliver a signal to a workflow execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignalTask {
+    pub execution: WorkflowExecution,
+    pub signal_name: String,
+    pub input: serde_json::Value,
+    /// Trace context of the span that enqueued the task
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub trace_context: TraceContext,
+}
+
+/// A unit of work handed to a worker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Task {
+    Workflow(WorkflowTask),
+    Activity(ActivityTask),
+    Signal(SignalTask),
+}
+
+impl Task {
+    /// Lane this task belongs to
+    pub fn kind(&self) -> TaskKind {
+        match self {
+            Task::Workflow(_) => TaskKind::Workflow,
+            Task::Activity(_) => TaskKind::Activity,
+            Task::Signal(_) => TaskKind::Signal,
+        }
+    }
+
+    /// Workflow type, activity type or signal name of the task
+    pub fn type_name(&self) -> &str {
+        match self {
+            Task::Workflow(t) => &t.workflow_type,
+            Task::Activity(t) => &t.activity_type,
+            Task::Signal(t) => &t.signal_name,
+        }
+    }
+
+    /// Workflow the task belongs to
+    pub fn workflow_id(&self) -> &WorkflowId {
+        match self {
+            Task::Workflow(t) => &t.execution.workflow_id,
+            Task::Activity(t) => &t.workflow_execution.workflow_id,
+            Task::Signal(t) => &t.execution.workflow_id,
+        }
+    }
+
+    /// Dequeue priority of the task; signals all have the default one
+    pub fn priority(&self) -> Priority {
+        match self {
+            Task::Workflow(t) => t.priority,
+            Task::Activity(t) => t.priority,
+            Task::Signal(_) => Priority::DEFAULT,
+        }
+    }
+}
+
+/// Task queue abstraction
+#[async_trait]
+pub trait TaskQueue: Send + Sync {
+    /// Enqueue a task on the named queue
+    async fn push(&self, queue: &str, task: Task) -> Result<(), StorageError>;
+
+    /// Wait up to `timeout` for a task of the given kind, returning `None` if none arrived
+    async fn poll(&self, queue: &str, kind: TaskKind, timeout: Duration) -> Result<Option<Task>, StorageError>;
+
+    /// Like [`poll`](Self::poll), also returning how long the task waited in the queue if the queue tracks it
+    ///
+    /// Workers report the wait as their schedule-to-start latency, see [`tuning`](super::tuning).
+    async fn poll_with_latency(
+        &self,
+        queue: &str,
+        kind: TaskKind,
+        timeout: Duration,
+    ) -> Result<Option<(Task, Option<Duration>)>, StorageError> {
+        Ok(self.poll(queue, kind, timeout).await?.map(|task| (task, None)))
+    }
+
+    /// Number of tasks waiting on a lane
+    async fn len(&self, queue: &str, kind: TaskKind) -> Result<usize, StorageError>;
+
+    /// Called once a polled task has been handled
+    ///
+    /// Queues with at-least-once delivery hand out tasks that are not completed again, for example
+    /// after the worker handling them crashed. Tasks are completed whatever their outcome.
+    async fn complete(&self, _queue: &str, _task: &Task) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    /// Check that the backend is reachable, for readiness probes
+    ///
+    /// The default reports in-process queues as always reachable.
+    async fn ping(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+/// Record how long a task of `priority` waited before a worker picked it up
+pub(crate) fn record_priority_wait(queue: &str, kind: TaskKind, priority: Priority, waited: Duration) {
+    metrics::histogram!(
+        TASK_PRIORITY_WAIT,
+        "task_queue" => queue.to_string(),
+        "kind" => kind.as_str(),
+        "priority" => priority.to_string()
+    )
+    .record(waited.as_secs_f64());
+}
+
+#[derive(Default)]
+struct Lane {
+    /// Tasks of each priority with the time they were enqueued, oldest first
+    tasks: Mutex<[VecDeque<(Instant, Task)>; Priority::LEVELS]>,
+    notify: Notify,
+}
+
+impl Lane {
+    fn len(&self) -> usize {
+        self.tasks.lock().iter().map(VecDeque::len).sum()
+    }
+
+    /// Take the task of the most urgent aged priority, the oldest one on ties
+    fn pop(&self, queue: &str, kind: TaskKind, aging: Duration) -> Option<(Task, Option<Duration>)> {
+        let mut tasks = self.tasks.lock();
+        let now = Instant::now();
+        let (_, level) = Priority::levels()
+            .filter_map(|priority| {
+                let (enqueued, _) = tasks[priority.index()].front()?;
+                Some(((priority.aged(now - *enqueued, aging), *enqueued), priority.index()))
+            })
+            .min()?;
+        let (enqueued, task) = tasks[level].pop_front()?;
+        let waited = now - enqueued;
+        let depth: usize = tasks.iter().map(VecDeque::len).sum();
+        metrics::gauge!(TASK_QUEUE_DEPTH, "task_queue" => queue.to_string(), "kind" => kind.as_str()).set(depth as f64);
+        metrics::histogram!(
+            SCHEDULE_TO_START,
+            "task_queue" => queue.to_string(),
+            "kind" => kind.as_str(),
+            "type" => task.type_name().to_string()
+        )
+        .record(waited.as_secs_f64());
+        record_priority_wait(queue, kind, task.priority(), waited);
+        Some((task, Some(waited)))
+    }
+}
+
+/// In-process task queue
+pub struct InMemoryTaskQueue {
+    lanes: Mutex<HashMap<(String, TaskKind), Arc<Lane>>>,
+    aging: Duration,
+}
+
+impl Default for InMemoryTaskQueue {
+    fn default() -> Self {
+        Self {
+            lanes: Mutex::default(),
+            aging: DEFAULT_AGING,
+        }
+    }
+}
+
+impl InMemoryTaskQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count tasks as one priority level more urgent per `aging` they wait, [`DEFAULT_AGING`] by default
+    ///
+    /// `Duration::ZERO` hands out tasks strictly by priority.
+    pub fn with_aging(mut self, aging: Duration) -> Self {
+        self.aging = aging;
+        self
+    }
+
+    fn lane(&self, queue: &str, kind: TaskKind) -> Arc<Lane> {
+        self.lanes
+            .lock()
+            .entry((queue.to_string(), kind))
+            .or_default()
+            .clone()
+    }
+}
+
+#[async_trait]
+impl TaskQueue for InMemoryTaskQueue {
+    async fn push(&self, queue: &str, task: Task) -> Result<(), StorageError> {
+        let kind = task.kind();
+        let lane = self.lane(queue, kind);
+        let depth = {
+            let mut tasks = lane.tasks.lock();
+            tasks[task.priority().index()].push_back((Instant::now(), task));
+            tasks.iter().map(VecDeque::len).sum::<usize>()
+        };
+        metrics::gauge!(TASK_QUEUE_DEPTH, "task_queue" => queue.to_string(), "kind" => kind.as_str()).set(depth as f64);
+        lane.notify.notify_one();
+        Ok(())
+    }
+
+    async fn poll(&self, queue: &str, kind: TaskKind, timeout: Duration) -> Result<Option<Task>, StorageError> {
+        Ok(self.poll_with_latency(queue, kind, timeout).await?.map(|(task, _)| task))
+    }
+
+    async fn poll_with_latency(
+        &self,
+        queue: &str,
+        kind: TaskKind,
+        timeout: Duration,
+    ) -> Result<Option<(Task, Option<Duration>)>, StorageError> {
+        let lane = self.lane(queue, kind);
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if let Some(task) = lane.pop(queue, kind, self.aging) {
+                return Ok(Some(task));
+            }
+            if tokio::time::timeout_at(deadline, lane.notify.notified()).await.is_err() {
+                return Ok(lane.pop(queue, kind, self.aging));
+            }
+        }
+    }
+
+    async fn len(&self, queue: &str, kind: TaskKind) -> Result<usize, StorageError> {
+        Ok(self.lane(queue, kind).len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::WorkflowId;
+
+    fn workflow_task(id: &str) -> Task {
+        prioritized_task(id, Priority::DEFAULT)
+    }
+
+    fn prioritized_task(id: &str, priority: Priority) -> Task {
+        Task::Workflow(WorkflowTask {
+            execution: WorkflowExecution::new(WorkflowId::new(id)),
+            workflow_type: "test".to_string(),
+            task_queue: "q".to_string(),
+            input: serde_json::json!(null),
+            priority,
+            trace_context: Default::default(),
+        })
+    }
+
+    async fn next_id(queue: &InMemoryTaskQueue) -> String {
+        match queue.poll("q", TaskKind::Workflow, Duration::ZERO).await.unwrap() {
+            Some(Task::Workflow(task)) => task.execution.workflow_id.to_string(),
+            other => panic!("expected a workflow task, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lanes_are_independent() {
+        let queue = InMemoryTaskQueue::new();
+        queue.push("q", workflow_task("wf-1")).await.unwrap();
+
+        let none = queue.poll("q", TaskKind::Activity, Duration::from_millis(10)).await.unwrap();
+        assert!(none.is_none());
+        let task = queue.poll("q", TaskKind::Workflow, Duration::from_millis(10)).await.unwrap();
+        assert!(matches!(task, Some(Task::Workflow(t)) if t.execution.workflow_id.as_str() == "wf-1"));
+    }
+
+    #[tokio::test]
+    async fn test_poll_wakes_on_push() {
+        let queue = Arc::new(InMemoryTaskQueue::new());
+        let poller = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.poll("q", TaskKind::Workflow, Duration::from_secs(5)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        queue.push("q", workflow_task("wf-2")).await.unwrap();
+        assert!(poller.await.unwrap().unwrap().is_some());
+        assert_eq!(queue.len("q", TaskKind::Workflow).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_higher_priorities_go_first_until_others_age() {
+        let queue = InMemoryTaskQueue::new().with_aging(Duration::from_millis(100));
+        queue.push("q", prioritized_task("backfill", Priority::LOWEST)).await.unwrap();
+        queue.push("q", prioritized_task("order-1", Priority::DEFAULT)).await.unwrap();
+        queue.push("q", prioritized_task("premium", Priority::HIGHEST)).await.unwrap();
+        queue.push("q", prioritized_task("order-2", Priority::DEFAULT)).await.unwrap();
+        assert_eq!(next_id(&queue).await, "premium");
+        assert_eq!(next_id(&queue).await, "order-1");
+
+        // Two aging intervals take the backfill from level 5 to 3, ahead of the younger order
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        queue.push("q", prioritized_task("order-3", Priority::DEFAULT)).await.unwrap();
+        assert_eq!(next_id(&queue).await, "order-2");
+        assert_eq!(next_id(&queue).await, "backfill");
+        assert_eq!(next_id(&queue).await, "order-3");
+        assert_eq!(queue.len("q", TaskKind::Workflow).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_priorities_are_clamped() {
+        assert_eq!(Priority::new(0), Priority::HIGHEST);
+        assert_eq!(Priority::new(9), Priority::LOWEST);
+        assert_eq!(serde_json::from_str::<Priority>("7").unwrap(), Priority::LOWEST);
+        assert_eq!(Priority::DEFAULT.aged(Duration::from_secs(25), DEFAULT_AGING), 1);
+        assert_eq!(Priority::DEFAULT.aged(Duration::from_secs(25), Duration::ZERO), 3);
+    }
+
+    #[test]
+    fn test_queue_reports_depth_and_schedule_to_start() {
+        let recorder = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            futures::executor::block_on(async {
+                let queue = InMemoryTaskQueue::new();
+                queue.push("q", workflow_task("wf-1")).await.unwrap();
+                queue.push("q", workflow_task("wf-2")).await.unwrap();
+                queue.poll("q", TaskKind::Workflow, Duration::ZERO).await.unwrap().unwrap();
+            })
+        });
+        let rendered = handle.render();
+        assert!(rendered.contains(r#"temporal_task_queue_depth{task_queue="q",kind="workflow"} 1"#), "{rendered}");
+        assert!(
+            rendered.contains(r#"temporal_task_schedule_to_start_seconds_count{task_queue="q",kind="workflow",type="test"} 1"#),
+            "{rendered}"
+        );
+    }
+}
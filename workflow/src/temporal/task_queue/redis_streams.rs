@@ -0,0 +1,493 @@
+//! Redis Streams task queue
+//!
+//! Every lane of a queue is a stream per priority, `{prefix}:{queue}:{kind}` for the default
+//! priority and `{prefix}:{queue}:{kind}:p{priority}` for the others, read through one consumer
+//! group shared by all workers. A poll takes up to one entry of each stream and hands out the one
+//! of the most urgent aged priority, keeping the others for the next polls of the same worker. Entries stay pending until the worker that received them completes the
+//! task, and the worker refreshes their idle time with `XCLAIM ... JUSTID` every third of the
+//! claim timeout meanwhile; entries idle for longer than the claim timeout, because their worker
+//! crashed, are claimed by the next poll of any worker. Delivery is therefore at least once: a
+//! task whose worker dies before completing it runs again elsewhere.
+//!
+//! Needs Redis 6.2 or later for `XAUTOCLAIM`; [`StreamStats::lag`] needs Redis 7.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use redis::AsyncCommands;
+use redis::aio::{ConnectionManager, MultiplexedConnection};
+use redis::streams::{
+    StreamAutoClaimOptions, StreamAutoClaimReply, StreamId, StreamInfoGroupsReply, StreamReadOptions, StreamReadReply,
+};
+
+use super::{record_priority_wait, Priority, Task, TaskKind, TaskQueue, DEFAULT_AGING};
+use crate::temporal::dead_letter::task_key;
+use crate::temporal::error::StorageError;
+use crate::temporal::metrics::SCHEDULE_TO_START;
+
+const DEFAULT_PREFIX: &str = "workflow:tasks";
+const DEFAULT_GROUP: &str = "workers";
+const DEFAULT_CLAIM_IDLE: Duration = Duration::from_secs(60);
+
+/// Idle time refreshes of held entries per claim timeout, so one failed refresh does not lose them
+const REFRESHES_PER_CLAIM_IDLE: u32 = 3;
+
+/// Field of a stream entry holding the JSON-encoded task
+const TASK_FIELD: &str = "task";
+
+/// Backlog of a lane, as reported by `XLEN` and `XINFO GROUPS`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// Entries in the stream, delivered or not
+    pub length: usize,
+    /// Entries delivered to a worker and not completed yet
+    pub pending: usize,
+    /// Entries not delivered yet, `None` before Redis 7
+    pub lag: Option<usize>,
+    /// Workers that have read from the lane
+    pub consumers: usize,
+}
+
+/// Task queue on Redis Streams with consumer groups
+pub struct RedisStreamsTaskQueue {
+    client: redis::Client,
+    commands: ConnectionManager,
+    /// Connections for blocking reads, which would hold up the commands behind them on a shared connection
+    readers: Mutex<Vec<MultiplexedConnection>>,
+    prefix: String,
+    group: String,
+    consumer: String,
+    claim_idle: Duration,
+    /// Streams whose consumer group is known to exist
+    groups: Mutex<HashSet<String>>,
+    /// Entry IDs of the polled tasks that are not completed yet, by stream and task
+    in_flight: Arc<Mutex<HashMap<(String, String), VecDeque<String>>>>,
+    aging: Duration,
+    /// Entries delivered to this worker and not handed out yet, at most one per stream
+    heads: Arc<Mutex<HashMap<String, StreamId>>>,
+    /// Refreshes the idle time of the entries in flight and held, started by the first poll
+    refresher: OnceLock<tokio::task::JoinHandle<()>>,
+}
+
+fn connection_error(e: redis::RedisError) -> StorageError {
+    StorageError::ConnectionError(e.to_string())
+}
+
+fn query_error(e: redis::RedisError) -> StorageError {
+    StorageError::QueryError(e.to_string())
+}
+
+/// How long ago an entry was added; its ID starts with the milliseconds at which it was
+fn waited(id: &str) -> Option<Duration> {
+    let added = id.split('-').next()?.parse::<i64>().ok()?;
+    Some(Duration::from_millis((chrono::Utc::now().timestamp_millis() - added).max(0) as u64))
+}
+
+impl RedisStreamsTaskQueue {
+    /// Connect to the Redis server at `url`, e.g. `redis://127.0.0.1/`
+    pub async fn connect(url: &str) -> Result<Self, StorageError> {
+        let client = redis::Client::open(url).map_err(connection_error)?;
+        let commands = ConnectionManager::new(client.clone()).await.map_err(connection_error)?;
+        Ok(Self {
+            client,
+            commands,
+            readers: Mutex::new(Vec::new()),
+            prefix: DEFAULT_PREFIX.to_string(),
+            group: DEFAULT_GROUP.to_string(),
+            consumer: format!("worker-{}", uuid::Uuid::new_v4()),
+            claim_idle: DEFAULT_CLAIM_IDLE,
+            groups: Mutex::new(HashSet::new()),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            aging: DEFAULT_AGING,
+            heads: Arc::new(Mutex::new(HashMap::new())),
+            refresher: OnceLock::new(),
+        })
+    }
+
+    /// Prefix of the stream keys, `workflow:tasks` by default
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Consumer group shared by the workers, `workers` by default
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = group.into();
+        self
+    }
+
+    /// Name of this process in the consumer group, random by default
+    pub fn with_consumer(mut self, consumer: impl Into<String>) -> Self {
+        self.consumer = consumer.into();
+        self
+    }
+
+    /// How long after its worker stopped refreshing it a pending task is claimed by another worker,
+    /// one minute by default
+    pub fn with_claim_idle(mut self, claim_idle: Duration) -> Self {
+        self.claim_idle = claim_idle;
+        self
+    }
+
+    /// Count tasks as one priority level more urgent per `aging` they wait, 10 seconds by default
+    ///
+    /// `Duration::ZERO` hands out tasks strictly by priority.
+    pub fn with_aging(mut self, aging: Duration) -> Self {
+        self.aging = aging;
+        self
+    }
+
+    fn stream(&self, queue: &str, kind: TaskKind, priority: Priority) -> String {
+        match priority {
+            Priority::DEFAULT => format!("{}:{}:{}", self.prefix, queue, kind.as_str()),
+            priority => format!("{}:{}:{}:p{}", self.prefix, queue, kind.as_str(), priority),
+        }
+    }
+
+    /// Streams of a lane with their priorities, highest first
+    async fn streams(&self, queue: &str, kind: TaskKind) -> Result<Vec<(Priority, String)>, StorageError> {
+        let mut streams = Vec::with_capacity(Priority::LEVELS);
+        for priority in Priority::levels() {
+            let stream = self.stream(queue, kind, priority);
+            self.ensure_group(&stream).await?;
+            streams.push((priority, stream));
+        }
+        Ok(streams)
+    }
+
+    /// Backlog of a lane, summed over its priorities
+    pub async fn stats(&self, queue: &str, kind: TaskKind) -> Result<StreamStats, StorageError> {
+        let mut stats = StreamStats {
+            lag: Some(0),
+            ..StreamStats::default()
+        };
+        let mut conn = self.commands.clone();
+        for (_, stream) in self.streams(queue, kind).await? {
+            let length: usize = conn.xlen(&stream).await.map_err(query_error)?;
+            let groups: StreamInfoGroupsReply = conn.xinfo_groups(&stream).await.map_err(query_error)?;
+            let group = groups.groups.into_iter().find(|group| group.name == self.group);
+            stats.length += length;
+            stats.pending += group.as_ref().map_or(0, |group| group.pending);
+            stats.lag = stats.lag.zip(group.as_ref().and_then(|group| group.lag)).map(|(total, lag)| total + lag);
+            stats.consumers = stats.consumers.max(group.map_or(0, |group| group.consumers));
+        }
+        Ok(stats)
+    }
+
+    /// Keep the entries of a read as heads of their streams
+    fn hold(&self, read: Option<StreamReadReply>) {
+        let mut heads = self.heads.lock();
+        for key in read.into_iter().flat_map(|reply| reply.keys) {
+            if let Some(entry) = key.ids.into_iter().next() {
+                heads.insert(key.key, entry);
+            }
+        }
+    }
+
+    /// Take the head of the most urgent aged priority, the oldest one on ties
+    fn take_head(&self, streams: &[(Priority, String)]) -> Option<(String, StreamId)> {
+        let mut heads = self.heads.lock();
+        let (_, stream) = streams
+            .iter()
+            .filter_map(|(priority, stream)| {
+                let waited = waited(&heads.get(stream)?.id).unwrap_or_default();
+                Some(((priority.aged(waited, self.aging), std::cmp::Reverse(waited)), stream))
+            })
+            .min()?;
+        heads.remove_entry(stream)
+    }
+
+    async fn ensure_group(&self, stream: &str) -> Result<(), StorageError> {
+        if self.groups.lock().contains(stream) {
+            return Ok(());
+        }
+        let mut conn = self.commands.clone();
+        // Read from the start of the stream, so tasks pushed before the first poll are delivered
+        match conn.xgroup_create_mkstream::<_, _, _, ()>(stream, &self.group, "0").await {
+            Ok(()) => {}
+            Err(e) if e.code() == Some("BUSYGROUP") => {}
+            Err(e) => return Err(query_error(e)),
+        }
+        self.groups.lock().insert(stream.to_string());
+        Ok(())
+    }
+
+    /// Decode a delivered entry and remember its ID until the task completes
+    ///
+    /// Entries that do not decode are dropped, or they would be claimed over and over.
+    async fn received(&self, stream: &str, entry: StreamId) -> Result<Option<(Task, Option<Duration>)>, StorageError> {
+        let decoded = entry
+            .get::<String>(TASK_FIELD)
+            .ok_or_else(|| format!("no {} field", TASK_FIELD))
+            .and_then(|json| serde_json::from_str::<Task>(&json).map_err(|e| e.to_string()));
+        let task = match decoded {
+            Ok(task) => task,
+            Err(e) => {
+                tracing::warn!(stream, id = %entry.id, error = %e, "dropping malformed task entry");
+                self.acknowledge(stream, &entry.id).await?;
+                return Ok(None);
+            }
+        };
+        let waited = waited(&entry.id);
+        if let Some(waited) = waited {
+            metrics::histogram!(
+                SCHEDULE_TO_START,
+                "task_queue" => stream.to_string(),
+                "kind" => task.kind().as_str(),
+                "type" => task.type_name().to_string()
+            )
+            .record(waited.as_secs_f64());
+            record_priority_wait(stream, task.kind(), task.priority(), waited);
+        }
+        self.in_flight
+            .lock()
+            .entry((stream.to_string(), task_key(&task)))
+            .or_default()
+            .push_back(entry.id);
+        Ok(Some((task, waited)))
+    }
+
+    async fn acknowledge(&self, stream: &str, id: &str) -> Result<(), StorageError> {
+        let mut conn = self.commands.clone();
+        redis::pipe()
+            .xack(stream, &self.group, &[id])
+            .ignore()
+            .xdel(stream, &[id])
+            .ignore()
+            .query_async::<()>(&mut conn)
+            .await
+            .map_err(query_error)
+    }
+
+    /// Keep resetting the idle time of the entries this worker holds, so that no other worker claims
+    /// a task that is still running however long it takes
+    fn spawn_refresher(&self) -> tokio::task::JoinHandle<()> {
+        let (mut conn, group, consumer) = (self.commands.clone(), self.group.clone(), self.consumer.clone());
+        let (in_flight, heads) = (self.in_flight.clone(), self.heads.clone());
+        let every = self.claim_idle / REFRESHES_PER_CLAIM_IDLE;
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(every).await;
+                let mut held: HashMap<String, Vec<String>> = HashMap::new();
+                for ((stream, _), ids) in in_flight.lock().iter() {
+                    held.entry(stream.clone()).or_default().extend(ids.iter().cloned());
+                }
+                for (stream, entry) in heads.lock().iter() {
+                    held.entry(stream.clone()).or_default().push(entry.id.clone());
+                }
+                if held.is_empty() {
+                    continue;
+                }
+                let mut pipe = redis::pipe();
+                for (stream, ids) in &held {
+                    // JUSTID leaves the delivery count alone; entries completed meanwhile are no longer pending
+                    // and are skipped
+                    pipe.cmd("XCLAIM").arg(stream).arg(&group).arg(&consumer).arg(0).arg(ids).arg("JUSTID").ignore();
+                }
+                if let Err(e) = pipe.query_async::<()>(&mut conn).await {
+                    tracing::warn!(error = %e, "failed to refresh the idle time of in-flight task entries");
+                }
+            }
+        })
+    }
+
+    async fn reader(&self) -> Result<MultiplexedConnection, StorageError> {
+        let pooled = self.readers.lock().pop();
+        match pooled {
+            Some(reader) => Ok(reader),
+            None => self.client.get_multiplexed_async_connection().await.map_err(connection_error),
+        }
+    }
+}
+
+/// Entries not completed stop being refreshed and are claimed by other workers after the claim timeout
+impl Drop for RedisStreamsTaskQueue {
+    fn drop(&mut self) {
+        if let Some(refresher) = self.refresher.get() {
+            refresher.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl TaskQueue for RedisStreamsTaskQueue {
+    async fn push(&self, queue: &str, task: Task) -> Result<(), StorageError> {
+        let stream = self.stream(queue, task.kind(), task.priority());
+        let json = serde_json::to_string(&task).map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        let mut conn = self.commands.clone();
+        conn.xadd::<_, _, _, _, ()>(&stream, "*", &[(TASK_FIELD, json)])
+            .await
+            .map_err(query_error)
+    }
+
+    async fn poll(&self, queue: &str, kind: TaskKind, timeout: Duration) -> Result<Option<Task>, StorageError> {
+        Ok(self.poll_with_latency(queue, kind, timeout).await?.map(|(task, _)| task))
+    }
+
+    async fn poll_with_latency(
+        &self,
+        queue: &str,
+        kind: TaskKind,
+        timeout: Duration,
+    ) -> Result<Option<(Task, Option<Duration>)>, StorageError> {
+        self.refresher.get_or_init(|| self.spawn_refresher());
+        let streams = self.streams(queue, kind).await?;
+        let headless = |streams: &[(Priority, String)]| -> Vec<String> {
+            let heads = self.heads.lock();
+            streams.iter().map(|(_, stream)| stream).filter(|stream| !heads.contains_key(*stream)).cloned().collect()
+        };
+
+        // Tasks left pending by crashed workers come first
+        let mut conn = self.commands.clone();
+        let missing = headless(&streams);
+        if !missing.is_empty() {
+            let mut pipe = redis::pipe();
+            for stream in &missing {
+                pipe.xautoclaim_options(
+                    stream,
+                    &self.group,
+                    &self.consumer,
+                    self.claim_idle.as_millis() as u64,
+                    "0-0",
+                    StreamAutoClaimOptions::default().count(1),
+                );
+            }
+            let claimed: Vec<StreamAutoClaimReply> = pipe.query_async(&mut conn).await.map_err(query_error)?;
+            let mut heads = self.heads.lock();
+            for (stream, reply) in missing.into_iter().zip(claimed) {
+                if let Some(entry) = reply.claimed.into_iter().next() {
+                    heads.insert(stream, entry);
+                }
+            }
+        }
+
+        let options = StreamReadOptions::default().group(&self.group, &self.consumer).count(1);
+        let missing = headless(&streams);
+        if !missing.is_empty() {
+            let ids = vec![">"; missing.len()];
+            let read: Option<StreamReadReply> =
+                conn.xread_options(&missing[..], &ids[..], &options).await.map_err(query_error)?;
+            self.hold(read);
+        }
+        // BLOCK 0 would wait forever
+        let missing = headless(&streams);
+        if missing.len() == streams.len() && !timeout.is_zero() {
+            let ids = vec![">"; missing.len()];
+            let options = options.block(timeout.as_millis().max(1) as usize);
+            let mut reader = self.reader().await?;
+            let read: Option<StreamReadReply> =
+                reader.xread_options(&missing[..], &ids[..], &options).await.map_err(query_error)?;
+            self.readers.lock().push(reader);
+            self.hold(read);
+        }
+
+        while let Some((stream, entry)) = self.take_head(&streams) {
+            if let Some(task) = self.received(&stream, entry).await? {
+                return Ok(Some(task));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Tasks not delivered yet
+    async fn len(&self, queue: &str, kind: TaskKind) -> Result<usize, StorageError> {
+        // Completed entries are deleted, so everything in the stream that is not pending waits
+        let stats = self.stats(queue, kind).await?;
+        Ok(stats.length.saturating_sub(stats.pending))
+    }
+
+    async fn complete(&self, queue: &str, task: &Task) -> Result<(), StorageError> {
+        let stream = self.stream(queue, task.kind(), task.priority());
+        let id = {
+            let mut in_flight = self.in_flight.lock();
+            let key = (stream.clone(), task_key(task));
+            let id = in_flight.get_mut(&key).and_then(VecDeque::pop_front);
+            if in_flight.get(&key).is_some_and(VecDeque::is_empty) {
+                in_flight.remove(&key);
+            }
+            id
+        };
+        match id {
+            Some(id) => self.acknowledge(&stream, &id).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        let mut conn = self.commands.clone();
+        redis::cmd("PING").query_async::<()>(&mut conn).await.map_err(connection_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::task_queue::SignalTask;
+    use crate::temporal::{WorkflowExecution, WorkflowId};
+
+    fn signal(id: &str) -> Task {
+        Task::Signal(SignalTask {
+            execution: WorkflowExecution::new(WorkflowId::new(id)),
+            signal_name: "approve".to_string(),
+            input: serde_json::json!(true),
+            trace_context: Default::default(),
+        })
+    }
+
+    /// Runs against the server in `REDIS_URL`, skipped without one
+    async fn queue(prefix: &str) -> Option<RedisStreamsTaskQueue> {
+        let url = std::env::var("REDIS_URL").ok()?;
+        Some(RedisStreamsTaskQueue::connect(&url).await.unwrap().with_prefix(prefix))
+    }
+
+    #[tokio::test]
+    async fn test_crashed_workers_tasks_are_claimed() {
+        let prefix = format!("test:{}", uuid::Uuid::new_v4());
+        let Some(crashing) = queue(&prefix).await else { return };
+        let survivor = queue(&prefix).await.unwrap().with_claim_idle(Duration::from_millis(50));
+
+        crashing.push("q", signal("wf-1")).await.unwrap();
+        crashing.push("q", signal("wf-2")).await.unwrap();
+        assert_eq!(crashing.len("q", TaskKind::Signal).await.unwrap(), 2);
+
+        // Polled and never completed
+        let lost = crashing.poll("q", TaskKind::Signal, Duration::ZERO).await.unwrap().unwrap();
+        let stats = crashing.stats("q", TaskKind::Signal).await.unwrap();
+        assert_eq!((stats.length, stats.pending), (2, 1));
+
+        let next = survivor.poll("q", TaskKind::Signal, Duration::from_millis(10)).await.unwrap().unwrap();
+        assert_ne!(task_key(&next), task_key(&lost));
+        survivor.complete("q", &next).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let claimed = survivor.poll("q", TaskKind::Signal, Duration::from_millis(10)).await.unwrap().unwrap();
+        assert_eq!(task_key(&claimed), task_key(&lost));
+        survivor.complete("q", &claimed).await.unwrap();
+
+        let stats = survivor.stats("q", TaskKind::Signal).await.unwrap();
+        assert_eq!((stats.length, stats.pending, stats.consumers), (0, 0, 2));
+        assert!(survivor.poll("q", TaskKind::Signal, Duration::from_millis(10)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_running_tasks_are_not_claimed() {
+        let prefix = format!("test:{}", uuid::Uuid::new_v4());
+        let Some(running) = queue(&prefix).await else { return };
+        let running = running.with_claim_idle(Duration::from_millis(150));
+        let idle = queue(&prefix).await.unwrap().with_claim_idle(Duration::from_millis(150));
+
+        running.push("q", signal("wf-1")).await.unwrap();
+        let polled = running.poll("q", TaskKind::Signal, Duration::ZERO).await.unwrap().unwrap();
+        // The task runs for several claim timeouts
+        for _ in 0..5 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(idle.poll("q", TaskKind::Signal, Duration::ZERO).await.unwrap().is_none());
+        }
+        running.complete("q", &polled).await.unwrap();
+        let stats = idle.stats("q", TaskKind::Signal).await.unwrap();
+        assert_eq!((stats.length, stats.pending), (0, 0));
+    }
+}
@@ -0,0 +1,183 @@
+//! Tracing spans for workflow execution and trace-context propagation through task queues
+//!
+//! The client opens `client.start_workflow` and `client.signal_workflow` spans; the worker opens a
+//! `workflow.task` span per workflow task it handles, a `workflow.run` span around the workflow
+//! function, an `activity.attempt` span per activity attempt and a `signal.delivery` span per
+//! signal. All carry `workflow_id` and `run_id`, activity attempts also `activity_id`.
+//!
+//! With the `otel` feature, the context of the span that enqueues a task travels inside it as W3C
+//! trace context, and the span handling the task on the worker continues that trace. A start
+//! request, its workflow run and every activity the run schedules thus end up in one trace, even
+//! when client and worker are separate processes. [`otlp_tracer_provider`] sets up the export.
+
+use std::collections::HashMap;
+
+use tracing::Span;
+
+use super::task_queue::{ActivityTask, SignalTask, WorkflowTask};
+
+/// W3C trace context (`traceparent`, `tracestate`) carried by a task
+pub type TraceContext = HashMap<String, String>;
+
+/// Trace context of `span`, to be carried by the task it enqueues
+///
+/// Empty without the `otel` feature or when no OpenTelemetry layer is installed.
+#[cfg(feature = "otel")]
+pub(crate) fn context_of(span: &Span) -> TraceContext {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let mut carrier = TraceContext::new();
+    let context = span.context();
+    opentelemetry::global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut carrier));
+    carrier
+}
+
+#[cfg(not(feature = "otel"))]
+pub(crate) fn context_of(_span: &Span) -> TraceContext {
+    TraceContext::new()
+}
+
+/// Make `span` continue the trace a task was enqueued in
+#[cfg(feature = "otel")]
+fn follow(span: Span, carrier: &TraceContext) -> Span {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    if !carrier.is_empty() {
+        let parent = opentelemetry::global::get_text_map_propagator(|propagator| propagator.extract(carrier));
+        if let Err(e) = span.set_parent(parent) {
+            tracing::debug!(error = %e, "could not continue propagated trace");
+        }
+    }
+    span
+}
+
+#[cfg(not(feature = "otel"))]
+fn follow(span: Span, _carrier: &TraceContext) -> Span {
+    span
+}
+
+pub(crate) fn start_workflow_span(workflow_type: &str, execution: &super::WorkflowExecution) -> Span {
+    tracing::info_span!(
+        "client.start_workflow",
+        workflow_type,
+        workflow_id = %execution.workflow_id,
+        run_id = %execution.run_id,
+    )
+}
+
+pub(crate) fn signal_workflow_span(signal_name: &str, execution: &super::WorkflowExecution) -> Span {
+    tracing::info_span!(
+        "client.signal_workflow",
+        signal_name,
+        workflow_id = %execution.workflow_id,
+        run_id = %execution.run_id,
+    )
+}
+
+pub(crate) fn workflow_task_span(task: &WorkflowTask) -> Span {
+    follow(
+        tracing::info_span!(
+            "workflow.task",
+            workflow_type = %task.workflow_type,
+            workflow_id = %task.execution.workflow_id,
+            run_id = %task.execution.run_id,
+            task_queue = %task.task_queue,
+        ),
+        &task.trace_context,
+    )
+}
+
+pub(crate) fn workflow_run_span(task: &WorkflowTask) -> Span {
+    tracing::info_span!(
+        "workflow.run",
+        workflow_type = %task.workflow_type,
+        workflow_id = %task.execution.workflow_id,
+        run_id = %task.execution.run_id,
+    )
+}
+
+pub(crate) fn activity_attempt_span(task: &ActivityTask) -> Span {
+    follow(
+        tracing::info_span!(
+            "activity.attempt",
+            activity_type = %task.activity_type,
+            activity_id = %task.activity_id,
+            attempt = task.attempt,
+            workflow_id = %task.workflow_execution.workflow_id,
+            run_id = %task.workflow_execution.run_id,
+        ),
+        &task.trace_context,
+    )
+}
+
+pub(crate) fn signal_delivery_span(task: &SignalTask) -> Span {
+    follow(
+        tracing::info_span!(
+            "signal.delivery",
+            signal_name = %task.signal_name,
+            workflow_id = %task.execution.workflow_id,
+            run_id = %task.execution.run_id,
+        ),
+        &task.trace_context,
+    )
+}
+
+/// Tracer provider exporting spans over OTLP/gRPC and W3C trace-context propagation
+///
+/// The exporter honours the standard `OTEL_EXPORTER_OTLP_*` variables, e.g.
+/// `OTEL_EXPORTER_OTLP_ENDPOINT`. Install it with `tracing_opentelemetry::layer().with_tracer(..)`
+/// and shut the provider down on exit to flush pending spans.
+#[cfg(feature = "otel")]
+pub fn otlp_tracer_provider(
+    service_name: &str,
+) -> Result<opentelemetry_sdk::trace::SdkTracerProvider, opentelemetry_otlp::ExporterBuildError> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder().with_tonic().build()?;
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name(service_name.to_string())
+                .build(),
+        )
+        .build();
+    opentelemetry::global::set_text_map_propagator(opentelemetry_sdk::propagation::TraceContextPropagator::new());
+    Ok(provider)
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+    use super::*;
+    use crate::temporal::{ActivityId, WorkflowExecution, WorkflowId};
+    use opentelemetry::trace::{TraceContextExt, TracerProvider};
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_activity_attempt_continues_enqueuing_trace() {
+        opentelemetry::global::set_text_map_propagator(opentelemetry_sdk::propagation::TraceContextPropagator::new());
+        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder().build();
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let execution = WorkflowExecution::new(WorkflowId::new("traced"));
+            let run = tracing::info_span!("workflow.run");
+            let carrier = context_of(&run);
+            assert!(carrier.contains_key("traceparent"));
+
+            let task = ActivityTask {
+                activity_id: ActivityId::new("a-1"),
+                activity_type: "charge".to_string(),
+                workflow_execution: execution,
+                input: serde_json::Value::Null,
+                attempt: 1,
+                heartbeat_timeout: None,
+                priority: Default::default(),
+                trace_context: carrier,
+            };
+            let attempt = activity_attempt_span(&task);
+            let trace_id = |span: &Span| span.context().span().span_context().trace_id();
+            assert_eq!(trace_id(&attempt), trace_id(&run));
+        });
+    }
+}
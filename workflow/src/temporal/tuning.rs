@@ -0,0 +1,387 @@
+//! Worker saturation signals and slot tuning
+//!
+//! A worker runs at most as many workflow and activity tasks at once as it has slots of each kind.
+//! [`WorkerLoad`] reports how many slots are in use and how long tasks waited in the queue before
+//! a slot picked them up (schedule-to-start latency), and lets the slot counts change while the
+//! worker runs. External autoscalers read these signals through
+//! [`WorkflowWorker::load`](super::WorkflowWorker::load) or `GET /api/v1/worker/load`; a
+//! [`WorkerTuner`] given to [`WorkflowWorker::with_tuner`](super::WorkflowWorker::with_tuner)
+//! adjusts the slots from within the worker.
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use std::time::Duration;
+//! # use workflow::temporal::tuning::UtilizationTuner;
+//! # use workflow::temporal::{WorkerConfig, WorkflowWorker};
+//! let tuner = UtilizationTuner::new(4, 64)
+//!     .target_utilization(0.75)
+//!     .max_schedule_to_start(Duration::from_millis(500));
+//! let worker = WorkflowWorker::new(WorkerConfig::default()).with_tuner(Arc::new(tuner));
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::Notify;
+use utoipa::ToSchema;
+
+use super::task_queue::TaskKind;
+
+/// Default time between tuner adjustments
+pub const DEFAULT_TUNING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Weight of the latest sample in the schedule-to-start average
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// Saturation of the slots of one task kind
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct SlotSignals {
+    pub max_slots: usize,
+    pub used_slots: usize,
+    /// Used slots over max slots, above 1 while slots drain after shrinking
+    pub utilization: f64,
+    /// Moving average of the time polled tasks waited in the queue, in seconds, with empty polls
+    /// counting as no wait; unknown before the first poll
+    pub schedule_to_start_secs: Option<f64>,
+}
+
+impl SlotSignals {
+    pub fn schedule_to_start(&self) -> Option<Duration> {
+        self.schedule_to_start_secs.map(Duration::from_secs_f64)
+    }
+}
+
+/// Saturation of a worker
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct WorkerSignals {
+    pub task_queue: String,
+    pub workflow: SlotSignals,
+    pub activity: SlotSignals,
+}
+
+/// Picks slot counts from saturation signals
+pub trait WorkerTuner: Send + Sync {
+    /// Slots the worker should have for tasks of `kind`; returning `signals.max_slots` keeps them
+    fn tune(&self, kind: TaskKind, signals: &SlotSignals) -> usize;
+
+    /// Time between adjustments
+    fn interval(&self) -> Duration {
+        DEFAULT_TUNING_INTERVAL
+    }
+}
+
+/// Grows slots while they are saturated or tasks wait too long, and shrinks them while mostly idle
+///
+/// Slots grow by a quarter (at least one) when utilization reaches the target or the
+/// schedule-to-start latency exceeds its bound, and shrink by a quarter when utilization falls
+/// below half the target, staying within `min..=max`.
+#[derive(Debug, Clone)]
+pub struct UtilizationTuner {
+    min_slots: usize,
+    max_slots: usize,
+    target_utilization: f64,
+    max_schedule_to_start: Option<Duration>,
+    interval: Duration,
+}
+
+impl UtilizationTuner {
+    pub fn new(min_slots: usize, max_slots: usize) -> Self {
+        let min_slots = min_slots.max(1);
+        Self {
+            min_slots,
+            max_slots: max_slots.max(min_slots),
+            target_utilization: 0.8,
+            max_schedule_to_start: None,
+            interval: DEFAULT_TUNING_INTERVAL,
+        }
+    }
+
+    /// Utilization, between 0 and 1, above which slots grow; 0.8 by default
+    pub fn target_utilization(mut self, target: f64) -> Self {
+        self.target_utilization = target.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Grow slots whenever tasks wait longer than `latency` to start
+    pub fn max_schedule_to_start(mut self, latency: Duration) -> Self {
+        self.max_schedule_to_start = Some(latency);
+        self
+    }
+
+    pub fn every(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+impl WorkerTuner for UtilizationTuner {
+    fn tune(&self, _kind: TaskKind, signals: &SlotSignals) -> usize {
+        let current = signals.max_slots;
+        let step = (current / 4).max(1);
+        let waiting = self
+            .max_schedule_to_start
+            .zip(signals.schedule_to_start())
+            .is_some_and(|(bound, latency)| latency > bound);
+        let target = if waiting || signals.utilization >= self.target_utilization {
+            current + step
+        } else if signals.utilization < self.target_utilization / 2.0 {
+            current.saturating_sub(step)
+        } else {
+            current
+        };
+        target.clamp(self.min_slots, self.max_slots)
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+#[derive(Debug)]
+struct SlotState {
+    used: usize,
+    max: usize,
+    schedule_to_start: Option<f64>,
+}
+
+/// Resizable task slots of one kind
+pub(crate) struct Slots {
+    kind: TaskKind,
+    task_queue: String,
+    state: Mutex<SlotState>,
+    released: Notify,
+}
+
+/// Slot held by a running task, released when dropped
+pub(crate) struct SlotPermit {
+    slots: Arc<Slots>,
+}
+
+impl Slots {
+    pub(crate) fn new(kind: TaskKind, task_queue: &str, max: usize) -> Arc<Self> {
+        let slots = Arc::new(Self {
+            kind,
+            task_queue: task_queue.to_string(),
+            state: Mutex::new(SlotState {
+                used: 0,
+                max: max.max(1),
+                schedule_to_start: None,
+            }),
+            released: Notify::new(),
+        });
+        slots.report(&slots.state.lock());
+        slots
+    }
+
+    fn report(&self, state: &SlotState) {
+        for (slots, count) in [("used", state.used), ("max", state.max)] {
+            metrics::gauge!(
+                super::metrics::WORKER_TASK_SLOTS,
+                "task_queue" => self.task_queue.clone(),
+                "kind" => self.kind.as_str(),
+                "slots" => slots
+            )
+            .set(count as f64);
+        }
+    }
+
+    /// Wait for a free slot
+    pub(crate) async fn acquire(self: &Arc<Self>) -> SlotPermit {
+        loop {
+            // Register for wake-ups before checking, so a release in between is not missed
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            {
+                let mut state = self.state.lock();
+                if state.used < state.max {
+                    state.used += 1;
+                    self.report(&state);
+                    return SlotPermit { slots: self.clone() };
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Change the number of slots; tasks beyond a lowered count finish before new ones start
+    pub(crate) fn resize(&self, max: usize) {
+        {
+            let mut state = self.state.lock();
+            state.max = max.max(1);
+            self.report(&state);
+        }
+        self.released.notify_waiters();
+    }
+
+    /// Fold the schedule-to-start latency of a polled task into the average
+    pub(crate) fn record_schedule_to_start(&self, latency: Duration) {
+        let mut state = self.state.lock();
+        let sample = latency.as_secs_f64();
+        state.schedule_to_start = Some(match state.schedule_to_start {
+            Some(average) => average + LATENCY_SMOOTHING * (sample - average),
+            None => sample,
+        });
+    }
+
+    pub(crate) fn signals(&self) -> SlotSignals {
+        let state = self.state.lock();
+        SlotSignals {
+            max_slots: state.max,
+            used_slots: state.used,
+            utilization: state.used as f64 / state.max as f64,
+            schedule_to_start_secs: state.schedule_to_start,
+        }
+    }
+}
+
+impl Drop for SlotPermit {
+    fn drop(&mut self) {
+        {
+            let mut state = self.slots.state.lock();
+            state.used -= 1;
+            self.slots.report(&state);
+        }
+        self.slots.released.notify_waiters();
+    }
+}
+
+/// Task slots of a worker, readable and resizable while it runs
+pub struct WorkerLoad {
+    task_queue: String,
+    pub(crate) workflow: Arc<Slots>,
+    pub(crate) activity: Arc<Slots>,
+    /// Signal deliveries share the workflow task limit
+    pub(crate) signal: Arc<Slots>,
+}
+
+impl WorkerLoad {
+    pub(crate) fn new(task_queue: &str, max_workflow_tasks: usize, max_activity_tasks: usize) -> Self {
+        Self {
+            task_queue: task_queue.to_string(),
+            workflow: Slots::new(TaskKind::Workflow, task_queue, max_workflow_tasks),
+            activity: Slots::new(TaskKind::Activity, task_queue, max_activity_tasks),
+            signal: Slots::new(TaskKind::Signal, task_queue, max_workflow_tasks),
+        }
+    }
+
+    pub fn signals(&self) -> WorkerSignals {
+        WorkerSignals {
+            task_queue: self.task_queue.clone(),
+            workflow: self.workflow.signals(),
+            activity: self.activity.signals(),
+        }
+    }
+
+    pub fn set_max_concurrent_workflow_tasks(&self, max: usize) {
+        self.workflow.resize(max);
+        self.signal.resize(max);
+    }
+
+    pub fn set_max_concurrent_activity_tasks(&self, max: usize) {
+        self.activity.resize(max);
+    }
+
+    /// Apply one round of `tuner` adjustments
+    pub(crate) fn tune(&self, tuner: &dyn WorkerTuner) {
+        for kind in [TaskKind::Workflow, TaskKind::Activity] {
+            let signals = match kind {
+                TaskKind::Activity => self.activity.signals(),
+                _ => self.workflow.signals(),
+            };
+            let max = tuner.tune(kind, &signals).max(1);
+            if max == signals.max_slots {
+                continue;
+            }
+            tracing::info!(
+                task_queue = %self.task_queue,
+                ?kind,
+                from = signals.max_slots,
+                to = max,
+                utilization = signals.utilization,
+                "worker slots tuned"
+            );
+            match kind {
+                TaskKind::Activity => self.set_max_concurrent_activity_tasks(max),
+                _ => self.set_max_concurrent_workflow_tasks(max),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::{WorkerConfig, WorkflowWorker};
+
+    fn signals(max_slots: usize, used_slots: usize, schedule_to_start_secs: Option<f64>) -> SlotSignals {
+        SlotSignals {
+            max_slots,
+            used_slots,
+            utilization: used_slots as f64 / max_slots as f64,
+            schedule_to_start_secs,
+        }
+    }
+
+    #[test]
+    fn test_utilization_tuner_grows_and_shrinks_within_bounds() {
+        let tuner = UtilizationTuner::new(2, 10).max_schedule_to_start(Duration::from_millis(200));
+        assert_eq!(tuner.tune(TaskKind::Activity, &signals(8, 8, None)), 10);
+        assert_eq!(tuner.tune(TaskKind::Activity, &signals(10, 10, None)), 10);
+        assert_eq!(tuner.tune(TaskKind::Activity, &signals(4, 1, Some(0.5))), 5);
+        assert_eq!(tuner.tune(TaskKind::Activity, &signals(4, 2, Some(0.1))), 4);
+        assert_eq!(tuner.tune(TaskKind::Activity, &signals(8, 1, Some(0.0))), 6);
+        assert_eq!(tuner.tune(TaskKind::Activity, &signals(2, 0, None)), 2);
+    }
+
+    #[tokio::test]
+    async fn test_slots_resize_while_held() {
+        let load = WorkerLoad::new("default", 1, 1);
+        let held = load.activity.acquire().await;
+        let waiting = tokio::spawn({
+            let slots = load.activity.clone();
+            async move { slots.acquire().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        load.set_max_concurrent_activity_tasks(2);
+        let second = waiting.await.unwrap();
+        let activity = load.signals().activity;
+        assert_eq!((activity.used_slots, activity.max_slots, activity.utilization), (2, 2, 1.0));
+
+        load.set_max_concurrent_activity_tasks(1);
+        drop(held);
+        assert_eq!(load.signals().activity.used_slots, 1);
+        drop(second);
+        load.activity.record_schedule_to_start(Duration::from_secs(1));
+        load.activity.record_schedule_to_start(Duration::ZERO);
+        assert_eq!(load.signals().activity.schedule_to_start_secs, Some(0.8));
+        assert_eq!(load.signals().workflow.schedule_to_start(), None);
+    }
+
+    #[tokio::test]
+    async fn test_worker_tuner_shrinks_idle_slots() {
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(20),
+                max_concurrent_workflow_tasks: 8,
+                max_concurrent_activity_tasks: 8,
+                ..WorkerConfig::default()
+            })
+            .with_tuner(Arc::new(UtilizationTuner::new(2, 8).every(Duration::from_millis(10)))),
+        );
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let signals = worker.load().signals();
+        assert_eq!((signals.workflow.max_slots, signals.activity.max_slots), (2, 2));
+        assert_eq!(signals.activity.schedule_to_start(), Some(Duration::ZERO));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
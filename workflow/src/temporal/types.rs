@@ -63,11 +63,6 @@ impl RunId {
     pub fn parse(s: &str) -> Result<Self, uuid::Error> {
         Ok(RunId(Uuid::parse_str(s)?))
     }
-    
-    /// Convert to string
-    pub fn to_string(&self) -> String {
-        self.0.to_string()
-    }
 }
 
 impl fmt::Display for RunId {
@@ -0,0 +1,262 @@
+//! Update definitions and handling
+//!
+//! An update is a request that changes a running workflow and answers the caller, unlike a signal,
+//! which is fire-and-forget. The workflow registers a handler with
+//! [`WorkflowContext::on_update`](super::WorkflowContext::on_update), together with a validator
+//! that rejects invalid updates before anything is recorded; callers send updates with
+//! [`WorkflowClient::update_workflow`](super::WorkflowClient::update_workflow) and wait for the
+//! handler's result. Accepted updates and their results are recorded in the history.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Serialize, de::DeserializeOwned};
+
+use super::WorkflowId;
+use super::error::UpdateError;
+
+/// Update trait - defines the update interface
+pub trait Update: Send + 'static {
+    /// Update name
+    fn name() -> &'static str;
+
+    /// Arguments type
+    type Args: Serialize + DeserializeOwned + Send;
+
+    /// Result type
+    type Result: Serialize + DeserializeOwned + Send;
+}
+
+/// Check of decoded update arguments, returning why they are rejected
+pub(crate) type UpdateValidator = Arc<dyn Fn(&serde_json::Value) -> Result<(), String> + Send + Sync>;
+
+/// Callback applying one update to the workflow state and returning its result
+pub(crate) type UpdateHandler = Arc<dyn Fn(serde_json::Value) -> Result<serde_json::Value, UpdateError> + Send + Sync>;
+
+/// Update handlers registered by one execution
+#[derive(Default)]
+pub(crate) struct UpdateHandlers {
+    handlers: Mutex<HashMap<String, (UpdateValidator, UpdateHandler)>>,
+    /// Inputs of replayed updates whose handler the code has not registered yet, by update name
+    deferred: Mutex<HashMap<String, Vec<serde_json::Value>>>,
+    /// Held while an update is applied, so updates are recorded and handled one at a time
+    pub(crate) applying: tokio::sync::Mutex<()>,
+    accepted: AtomicU64,
+}
+
+impl UpdateHandlers {
+    /// Handlers of an execution whose history already accepted `accepted` updates, whose IDs are not issued again
+    pub(crate) fn after(accepted: u64) -> Self {
+        Self {
+            accepted: AtomicU64::new(accepted),
+            ..Self::default()
+        }
+    }
+
+    /// Handle updates of a name with `handler` after `validator` accepted them, replacing any earlier handler
+    pub(crate) fn set_handler(&self, update_name: &str, validator: UpdateValidator, handler: UpdateHandler) {
+        self.handlers.lock().insert(update_name.to_string(), (validator, handler));
+    }
+
+    pub(crate) fn handler(&self, update_name: &str) -> Result<(UpdateValidator, UpdateHandler), UpdateError> {
+        self.handlers
+            .lock()
+            .get(update_name)
+            .cloned()
+            .ok_or_else(|| UpdateError::UpdateNotRegistered(update_name.to_string()))
+    }
+
+    /// Keep the input of a replayed update until its handler is registered
+    pub(crate) fn defer(&self, update_name: &str, input: serde_json::Value) {
+        self.deferred.lock().entry(update_name.to_string()).or_default().push(input);
+    }
+
+    /// Inputs of the replayed updates waiting for the handler of `update_name`, in the order they were accepted
+    pub(crate) fn take_deferred(&self, update_name: &str) -> Vec<serde_json::Value> {
+        self.deferred.lock().remove(update_name).unwrap_or_default()
+    }
+
+    /// ID of the next accepted update
+    pub(crate) fn next_id(&self) -> String {
+        format!("update-{}", self.accepted.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+}
+
+/// Applies updates to the executions a worker is running
+#[async_trait]
+pub(crate) trait UpdateDispatcher: Send + Sync {
+    /// [`UpdateError::WorkflowNotRunning`] if the execution is not running here
+    async fn update(
+        &self,
+        workflow_id: &WorkflowId,
+        update_name: &str,
+        args: serde_json::Value,
+    ) -> Result<serde_json::Value, UpdateError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use serde::Deserialize;
+
+    use crate::temporal::event::{EventHistory, EventType};
+    use crate::temporal::storage::InMemoryStorage;
+    use crate::temporal::task_queue::InMemoryTaskQueue;
+    use crate::temporal::worker::PendingActivities;
+    use crate::temporal::workflow::ExecutionRuntime;
+    use crate::temporal::{
+        StartWorkflowOptions, WorkerConfig, Workflow, WorkflowContext, WorkflowError, WorkflowExecution, WorkflowInfo,
+        WorkflowWorker,
+    };
+
+    struct AddItem;
+
+    #[derive(Serialize, Deserialize)]
+    struct Item {
+        sku: String,
+        quantity: u32,
+    }
+
+    impl Update for AddItem {
+        fn name() -> &'static str {
+            "add_item"
+        }
+
+        type Args = Item;
+        type Result = u32;
+    }
+
+    /// Collects items until it holds 5
+    struct Cart;
+
+    impl Workflow for Cart {
+        type Input = ();
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "cart"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<u32, WorkflowError> {
+            let items = Arc::new(Mutex::new(0));
+            let held = items.clone();
+            ctx.on_update::<AddItem>(
+                |item| match item.quantity {
+                    0 => Err("quantity must be positive".to_string()),
+                    _ => Ok(()),
+                },
+                move |item| {
+                    let mut items = held.lock();
+                    *items += item.quantity;
+                    *items
+                },
+            )?;
+            ctx.await_condition(|| *items.lock() >= 5, None).await?;
+            Ok(*items.lock())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_updates_are_validated_applied_and_answered() {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(20),
+            ..WorkerConfig::default()
+        }));
+        worker.register_workflow::<Cart>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let client = worker.client();
+        let handle = client.start_workflow::<Cart>((), StartWorkflowOptions::default()).await.unwrap();
+        let workflow_id = handle.execution().workflow_id.clone();
+        let item = |quantity| Item {
+            sku: "apple".to_string(),
+            quantity,
+        };
+        // The handler is registered once the worker picked the run up
+        let added = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match client.update_workflow::<AddItem>(&workflow_id, item(2)).await {
+                    Err(UpdateError::WorkflowNotRunning | UpdateError::UpdateNotRegistered(_)) => {
+                        tokio::time::sleep(Duration::from_millis(10)).await
+                    }
+                    added => return added,
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(added.unwrap(), 2);
+        assert!(matches!(
+            client.update_workflow::<AddItem>(&workflow_id, item(0)).await,
+            Err(UpdateError::Rejected(reason)) if reason.contains("positive")
+        ));
+        assert_eq!(client.update_workflow::<AddItem>(&workflow_id, item(3)).await.unwrap(), 5);
+        assert_eq!(handle.result().await.unwrap(), 5);
+
+        let updates: Vec<_> = handle
+            .history()
+            .await
+            .unwrap()
+            .events()
+            .iter()
+            .filter_map(|e| match &e.event_type {
+                EventType::WorkflowExecutionUpdateAccepted { update_id, .. } => Some(format!("accepted {}", update_id)),
+                EventType::WorkflowExecutionUpdateCompleted { update_id, result } => {
+                    Some(format!("completed {} {}", update_id, result))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            updates,
+            vec!["accepted update-1", "completed update-1 2", "accepted update-2", "completed update-2 5"]
+        );
+        assert!(matches!(
+            client.update_workflow::<AddItem>(&workflow_id, item(1)).await,
+            Err(UpdateError::WorkflowNotRunning)
+        ));
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_ids_continue_after_the_history() {
+        let mut history = EventHistory::new();
+        history.append(EventType::WorkflowExecutionUpdateAccepted {
+            update_id: "update-1".to_string(),
+            update_name: AddItem::name().to_string(),
+            input: serde_json::json!({"sku": "apple", "quantity": 1}),
+        });
+        history.append(EventType::WorkflowExecutionUpdateCompleted {
+            update_id: "update-1".to_string(),
+            result: serde_json::json!(1),
+        });
+        let runtime = Arc::new(ExecutionRuntime::new(
+            WorkflowInfo {
+                workflow_type: Cart::name().to_string(),
+                workflow_execution: WorkflowExecution::new(WorkflowId::new("cart-1")),
+                task_queue: "default".to_string(),
+            },
+            history,
+            Arc::new(InMemoryStorage::new()),
+            Arc::new(InMemoryTaskQueue::new()),
+            Arc::new(PendingActivities::default()),
+        ));
+        WorkflowContext::attached(runtime.clone())
+            .on_update::<AddItem>(|_| Ok(()), |item| item.quantity)
+            .unwrap();
+
+        runtime.update(AddItem::name(), serde_json::json!({"sku": "pear", "quantity": 2})).await.unwrap();
+        let history = runtime.history.lock().await;
+        assert!(matches!(
+            &history.events().last().unwrap().event_type,
+            EventType::WorkflowExecutionUpdateCompleted { update_id, .. } if update_id == "update-2"
+        ));
+    }
+}
@@ -1,60 +1,2952 @@
 //! Worker for processing workflow and activity tasks
 
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use utoipa::ToSchema;
+
+use super::activity::HeartbeatTracker;
+use super::activity_batch::{BatchActivity, BatchDispatcher};
+use super::admission::AdmissionControl;
+use super::batch::{self, BatchWorkflow, BATCH_ACTIVITY_TYPE, BATCH_WORKFLOW_TYPE};
+use super::circuit_breaker::CircuitBreaker;
+use super::tuning::{Slots, WorkerLoad, WorkerTuner};
+use super::clock::VirtualClock;
+use super::client::{StartWorkflowOptions, WorkflowClient};
+use super::converter::{self, DataConverter};
+use super::dead_letter::{task_key, DeadLetterQueue, DiscardHook};
+use super::dynamic_activity::DynamicActivityRegistry;
+use super::error::{QueryError, StorageError, UpdateError};
+use super::interceptor::{ClientInterceptor, WorkerInterceptor};
+#[cfg(feature = "persistence")]
+use super::memo::ActivityMemo;
+use super::namespace::Namespace;
+use super::outbox::{OutboxMessage, StagedMessages};
+use super::query::QueryDispatcher;
+use super::rate_limit::RateLimiter;
+use super::replay::Replay;
+use super::resource_pool::{ResourcePermit, ResourcePools};
+use super::retention::RetentionPolicies;
+use super::schedule::{DueSchedule, FireAction, Schedules};
+use super::session::SessionHost;
+use super::sticky::{HistoryCache, StickyCacheStats};
+use super::event::{EventHistory, EventType, HistoryLimits};
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics::{TaskProfile, TaskProfiler};
+use super::metrics::{
+    ACTIVITY_ATTEMPTS, ACTIVITY_DURATION, ACTIVITY_RATE_LIMITED, STICKY_CACHE_REQUESTS, STICKY_CACHE_SIZE, WORKFLOWS_CLOSED, WORKFLOW_DURATION,
+    WORKFLOW_TASKS,
+};
+use super::storage::{InMemoryStorage, WorkflowStorage};
+use super::task_queue::{ActivityTask, InMemoryTaskQueue, SignalTask, Task, TaskKind, TaskQueue, WorkflowTask};
+use super::telemetry;
+use super::update::UpdateDispatcher;
+use super::workflow::ExecutionRuntime;
+use super::{
+    Activity, ActivityContext, ActivityError, ActivityId, ActivityInfo, RunId, Workflow, WorkflowContext, WorkflowError,
+    WorkflowExecution, WorkflowId, WorkflowInfo,
+};
+use crate::dsl::{DynamicWorkflow, WorkflowSpec};
+#[cfg(feature = "patterns")]
+use crate::patterns::{EngineEvent, EventBus};
+#[cfg(feature = "persistence")]
+use crate::persistence::PersistenceAdapter;
+
+/// How often the worker checks its schedules
+const SCHEDULER_TICK: Duration = Duration::from_millis(100);
+
+/// Delay before an attempt that queued too long for a resource pool permit is put back on its queue
+const RESOURCE_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How long a worker holds the slot of a signal it put back for the worker running its execution,
+/// so that signals do not circle between workers faster than executions start
+const SIGNAL_REDELIVERY_DELAY: Duration = Duration::from_millis(100);
+
+type ActivityResult = Result<serde_json::Value, ActivityError>;
+type ActivityWaiter = (oneshot::Sender<ActivityResult>, CancellationToken);
+pub(crate) type WorkflowFn =
+    Arc<dyn Fn(WorkflowContext, serde_json::Value) -> BoxFuture<'static, Result<serde_json::Value, WorkflowError>> + Send + Sync>;
+type ActivityFn = Arc<dyn Fn(ActivityContext, serde_json::Value) -> BoxFuture<'static, ActivityResult> + Send + Sync>;
+
+/// Run a typed workflow on JSON input and output
+fn workflow_fn<W: Workflow>() -> WorkflowFn {
+    Arc::new(|ctx, input| {
+        async move {
+            let input: W::Input = serde_json::from_value(input).map_err(|e| WorkflowError::InvalidInput(e.to_string()))?;
+            let output = W::execute(ctx, input).await?;
+            Ok(serde_json::to_value(output)?)
+        }
+        .boxed()
+    })
+}
+
+/// Activity attempts awaited by workflow contexts, keyed by run and activity ID
+///
+/// Each attempt carries a cancellation token shared with the activity's context, so that giving
+/// up on an attempt is observable through [`ActivityContext::is_cancelled`].
+#[derive(Default)]
+pub(crate) struct PendingActivities {
+    waiting: Mutex<HashMap<(RunId, ActivityId), ActivityWaiter>>,
+    /// Outbox messages of completed attempts, until their waiter records the completion
+    staged: Mutex<HashMap<(RunId, ActivityId), Vec<OutboxMessage>>>,
+}
+
+impl PendingActivities {
+    pub(crate) fn register(&self, run_id: RunId, activity_id: ActivityId) -> oneshot::Receiver<ActivityResult> {
+        let (sender, receiver) = oneshot::channel();
+        self.waiting
+            .lock()
+            .insert((run_id, activity_id), (sender, CancellationToken::new()));
+        receiver
+    }
+
+    /// Stop waiting for an attempt and cancel it
+    ///
+    /// Messages of a completed attempt stay staged for its waiter, which stops waiting as it records the completion.
+    pub(crate) fn cancel(&self, run_id: RunId, activity_id: &ActivityId) {
+        if let Some((_, cancellation)) = self.waiting.lock().remove(&(run_id, activity_id.clone())) {
+            cancellation.cancel();
+        }
+    }
+
+    /// Cancel every attempt awaited by a run
+    pub(crate) fn cancel_run(&self, run_id: RunId) {
+        self.staged.lock().retain(|(run, _), _| *run != run_id);
+        self.waiting.lock().retain(|(run, _), (_, cancellation)| {
+            if *run == run_id {
+                cancellation.cancel();
+            }
+            *run != run_id
+        });
+    }
+
+    /// Cancellation token of an awaited attempt; `None` if nobody is waiting for it
+    pub(crate) fn cancellation(&self, run_id: RunId, activity_id: &ActivityId) -> Option<CancellationToken> {
+        self.waiting
+            .lock()
+            .get(&(run_id, activity_id.clone()))
+            .map(|(_, cancellation)| cancellation.clone())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.waiting.lock().is_empty()
+    }
+
+    /// Deliver an attempt's result; returns false if nobody is waiting any more
+    pub(crate) fn complete(&self, run_id: RunId, activity_id: &ActivityId, result: ActivityResult) -> bool {
+        self.complete_with_outbox(run_id, activity_id, result, Vec::new())
+    }
+
+    /// Deliver an attempt's result with the outbox messages it staged, which the waiter takes with
+    /// [`take_staged`](Self::take_staged) when recording a success
+    pub(crate) fn complete_with_outbox(
+        &self,
+        run_id: RunId,
+        activity_id: &ActivityId,
+        result: ActivityResult,
+        outbox: Vec<OutboxMessage>,
+    ) -> bool {
+        let key = (run_id, activity_id.clone());
+        let Some((sender, _)) = self.waiting.lock().remove(&key) else {
+            return false;
+        };
+        if result.is_ok() && !outbox.is_empty() {
+            self.staged.lock().insert(key.clone(), outbox);
+        }
+        let delivered = sender.send(result).is_ok();
+        if !delivered {
+            self.staged.lock().remove(&key);
+        }
+        delivered
+    }
+
+    /// Outbox messages staged by the successful attempt of an activity
+    pub(crate) fn take_staged(&self, run_id: RunId, activity_id: &ActivityId) -> Vec<OutboxMessage> {
+        self.staged.lock().remove(&(run_id, activity_id.clone())).unwrap_or_default()
+    }
+}
+
+/// Executions this worker is running
+#[derive(Default)]
+struct Executions {
+    running: HashMap<WorkflowId, Arc<ExecutionRuntime>>,
+}
+
+#[async_trait]
+impl QueryDispatcher for Mutex<Executions> {
+    async fn query(&self, workflow_id: &WorkflowId, query_name: &str) -> Result<serde_json::Value, QueryError> {
+        let runtime = self.lock().running.get(workflow_id).cloned().ok_or(QueryError::WorkflowNotRunning)?;
+        runtime.queries.answer(query_name)
+    }
+}
+
+#[async_trait]
+impl UpdateDispatcher for Mutex<Executions> {
+    async fn update(
+        &self,
+        workflow_id: &WorkflowId,
+        update_name: &str,
+        args: serde_json::Value,
+    ) -> Result<serde_json::Value, UpdateError> {
+        let runtime = self.lock().running.get(workflow_id).cloned().ok_or(UpdateError::WorkflowNotRunning)?;
+        runtime.update(update_name, args).await
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    workflows: RwLock<HashMap<String, WorkflowFn>>,
+    activities: RwLock<HashMap<String, ActivityFn>>,
+    /// Step outlines of the workflows, see [`Workflow::outline`]
+    outlines: RwLock<HashMap<String, WorkflowSpec>>,
+    /// Pools the activities declare, see [`Activity::resources`]
+    resources: RwLock<HashMap<String, &'static [&'static str]>>,
+}
+
+/// State shared between the worker and the tasks it spawns
+#[derive(Clone)]
+struct Shared {
+    registry: Arc<Registry>,
+    task_queue: Arc<dyn TaskQueue>,
+    storage: Arc<dyn WorkflowStorage>,
+    pending: Arc<PendingActivities>,
+    executions: Arc<Mutex<Executions>>,
+    schedules: Arc<Schedules>,
+    /// Runtimes of recently active runs, kept for their in-memory histories
+    histories: Arc<HistoryCache<Arc<ExecutionRuntime>>>,
+    dead_letters: Arc<DeadLetterQueue>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    admission: Option<Arc<AdmissionControl>>,
+    rate_limiter: Option<Arc<dyn RateLimiter>>,
+    resource_pools: Option<Arc<ResourcePools>>,
+    /// Activity sessions hosted by this worker, see [`WorkerConfig::max_concurrent_sessions`]
+    sessions: Option<Arc<SessionHost>>,
+    dynamic_activities: Option<Arc<DynamicActivityRegistry>>,
+    /// Store of memoized activity results, see [`memo`](super::memo)
+    #[cfg(feature = "persistence")]
+    memo: Option<Arc<ActivityMemo>>,
+    #[cfg(feature = "patterns")]
+    event_bus: Option<Arc<EventBus>>,
+    interceptors: Arc<[Arc<dyn WorkerInterceptor>]>,
+    client_interceptors: Arc<[Arc<dyn ClientInterceptor>]>,
+    converter: Arc<dyn DataConverter>,
+    /// Namespace labelling the metrics, see [`Namespaces`](super::namespace::Namespaces)
+    namespace: Namespace,
+    clock: Option<Arc<VirtualClock>>,
+    /// Queue this worker polls, where dead letters are pushed back on retry
+    queue_name: String,
+    max_task_failures: u32,
+    history_limits: HistoryLimits,
+    /// Executions whose next task is profiled, see [`WorkflowWorker::profile_task`]
+    #[cfg(feature = "diagnostics")]
+    task_profiler: Arc<TaskProfiler>,
+}
+
 /// Workflow worker
+///
+/// Polls its task queue for workflow and activity tasks and runs them with the registered
+/// implementations, bounded by the concurrency limits in [`WorkerConfig`].
 pub struct WorkflowWorker {
-    // Worker implementation will be added later
+    config: WorkerConfig,
+    shared: Shared,
+    /// Grace period of a requested shutdown
+    shutdown: Arc<watch::Sender<Option<Duration>>>,
+    retention: Option<RetentionPolicies>,
+    load: Arc<WorkerLoad>,
+    tuner: Option<Arc<dyn WorkerTuner>>,
+    /// Whether [`run`](Self::run) is polling
+    running: Arc<AtomicBool>,
+    /// Whether new workflow tasks are held back
+    paused: Arc<watch::Sender<bool>>,
 }
 
 impl WorkflowWorker {
-    /// Create a new workflow worker
-    pub fn new() -> Self {
-        Self {}
+    /// Create a new workflow worker backed by an in-memory task queue and storage
+    pub fn new(config: WorkerConfig) -> Self {
+        Self {
+            shared: Shared {
+                histories: Arc::new(HistoryCache::new(config.sticky_cache_size)),
+                registry: Arc::new(Registry::default()),
+                task_queue: Arc::new(InMemoryTaskQueue::new()),
+                storage: Arc::new(InMemoryStorage::new()),
+                pending: Arc::new(PendingActivities::default()),
+                executions: Arc::new(Mutex::new(Executions::default())),
+                schedules: Arc::new(Schedules::new()),
+                dead_letters: Arc::new(DeadLetterQueue::new()),
+                circuit_breaker: None,
+                admission: None,
+                rate_limiter: None,
+                resource_pools: None,
+                sessions: (config.max_concurrent_sessions > 0).then(|| {
+                    Arc::new(SessionHost::new(
+                        format!("{}@{}", config.task_queue, config.identity),
+                        config.max_concurrent_sessions,
+                    ))
+                }),
+                dynamic_activities: None,
+                #[cfg(feature = "persistence")]
+                memo: None,
+                #[cfg(feature = "patterns")]
+                event_bus: None,
+                interceptors: Arc::new([]),
+                client_interceptors: Arc::new([]),
+                converter: converter::default_converter(),
+                namespace: Namespace::default(),
+                clock: None,
+                queue_name: config.task_queue.clone(),
+                max_task_failures: config.max_task_failures,
+                history_limits: config.history_limits,
+                #[cfg(feature = "diagnostics")]
+                task_profiler: Arc::new(TaskProfiler::new()),
+            },
+            shutdown: Arc::new(watch::channel(None).0),
+            retention: None,
+            load: Arc::new(WorkerLoad::new(
+                &config.task_queue,
+                config.max_concurrent_workflow_tasks,
+                config.max_concurrent_activity_tasks,
+            )),
+            tuner: None,
+            running: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(watch::channel(false).0),
+            config,
+        }
+    }
+
+    /// Use the given task queue
+    pub fn with_task_queue(mut self, task_queue: Arc<dyn TaskQueue>) -> Self {
+        self.shared.task_queue = task_queue;
+        self
+    }
+
+    /// Use the given storage
+    pub fn with_storage(mut self, storage: Arc<dyn WorkflowStorage>) -> Self {
+        self.shared.storage = storage;
+        self
+    }
+
+    /// Keep the results of [memoized](super::ActivityOptions::memoize) activity calls in `store`
+    ///
+    /// Workers sharing the store reuse each other's results.
+    #[cfg(feature = "persistence")]
+    pub fn with_activity_memo(mut self, store: Arc<dyn PersistenceAdapter>) -> Self {
+        self.shared.memo = Some(Arc::new(ActivityMemo::new(store)));
+        self
+    }
+
+    /// Delete or archive closed executions of this worker's storage as `policies` say
+    pub fn with_retention(mut self, policies: RetentionPolicies) -> Self {
+        self.retention = Some(policies);
+        self
+    }
+
+    /// Let `tuner` adjust the workflow and activity task slots while the worker runs
+    ///
+    /// The slots start at the limits of the [`WorkerConfig`].
+    pub fn with_tuner(mut self, tuner: Arc<dyn WorkerTuner>) -> Self {
+        self.tuner = Some(tuner);
+        self
+    }
+
+    /// Task slot usage and schedule-to-start latency, with setters for the slot counts, for autoscalers
+    pub fn load(&self) -> &Arc<WorkerLoad> {
+        &self.load
+    }
+
+    /// Label the metrics of this worker and its clients with `namespace`
+    ///
+    /// Isolating the namespace's executions and tasks needs scoped storage and task queues too;
+    /// [`Namespaces::worker`](super::namespace::Namespaces::worker) sets up all three.
+    pub fn with_namespace(mut self, namespace: Namespace) -> Self {
+        self.shared.namespace = namespace;
+        self
+    }
+
+    /// Add an interceptor around the starts and signals of the clients this worker hands out,
+    /// including the starts of child workflows
+    pub fn with_client_interceptor(mut self, interceptor: Arc<dyn ClientInterceptor>) -> Self {
+        let mut interceptors = self.shared.client_interceptors.to_vec();
+        interceptors.push(interceptor);
+        self.shared.client_interceptors = interceptors.into();
+        self
+    }
+
+    /// Use the given schedule registry
+    pub fn with_schedules(mut self, schedules: Arc<Schedules>) -> Self {
+        self.shared.schedules = schedules;
+        self
+    }
+
+    /// Use the given dead-letter queue
+    pub fn with_dead_letters(mut self, dead_letters: Arc<DeadLetterQueue>) -> Self {
+        self.shared.dead_letters = dead_letters;
+        self
+    }
+
+    /// Guard activity attempts with the given circuit breaker
+    pub fn with_circuit_breaker(mut self, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        self.shared.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Start activity attempts only as fast as `limiter` allows, putting the others back on their queue
+    pub fn with_rate_limiter(mut self, limiter: Arc<dyn RateLimiter>) -> Self {
+        self.shared.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Take permits of the pools an activity requires before starting its attempts, see
+    /// [`resource_pool`](super::resource_pool)
+    pub fn with_resource_pools(mut self, pools: Arc<ResourcePools>) -> Self {
+        self.shared.resource_pools = Some(pools);
+        self
+    }
+
+    /// Admit the starts of this worker's clients and the activity attempts of its runs through `admission`
+    pub fn with_admission(mut self, admission: Arc<AdmissionControl>) -> Self {
+        self.shared.admission = Some(admission.clone());
+        self.with_client_interceptor(admission)
+    }
+
+    /// Run activities of `registry` for activity types without a typed registration
+    pub fn with_dynamic_activities(mut self, registry: Arc<DynamicActivityRegistry>) -> Self {
+        self.shared.dynamic_activities = Some(registry);
+        self
+    }
+
+    /// Publish lifecycle events of the workflows and activities this worker runs to `event_bus`
+    #[cfg(feature = "patterns")]
+    pub fn with_event_bus(mut self, event_bus: Arc<EventBus>) -> Self {
+        self.shared.event_bus = Some(event_bus);
+        self
+    }
+
+    /// Add an interceptor around workflow tasks, activity attempts and signal deliveries
+    ///
+    /// See [`interceptor`](super::interceptor) for the order interceptors run in.
+    pub fn with_interceptor(mut self, interceptor: Arc<dyn WorkerInterceptor>) -> Self {
+        let mut interceptors = self.shared.interceptors.to_vec();
+        interceptors.push(interceptor);
+        self.shared.interceptors = interceptors.into();
+        self
+    }
+
+    /// Encode workflow, activity and signal payloads with `converter` instead of inline JSON
+    pub fn with_data_converter(mut self, converter: Arc<dyn DataConverter>) -> Self {
+        self.shared.converter = converter;
+        self
+    }
+
+    /// Dynamic activities this worker runs, if any
+    pub fn dynamic_activities(&self) -> Option<&Arc<DynamicActivityRegistry>> {
+        self.shared.dynamic_activities.as_ref()
+    }
+
+    /// Run workflow timers on a virtual clock, see [`TestWorkflowEnvironment`](crate::testing::TestWorkflowEnvironment)
+    pub(crate) fn with_clock(mut self, clock: Arc<VirtualClock>) -> Self {
+        self.shared.clock = Some(clock);
+        self
+    }
+
+    /// Whether no activity attempt is awaited and no task waits on this worker's queue
+    ///
+    /// Workflows of an idle worker wait for timers or signals only.
+    pub(crate) async fn is_idle(&self) -> bool {
+        if !self.shared.pending.is_empty() {
+            return false;
+        }
+        for kind in [TaskKind::Workflow, TaskKind::Activity, TaskKind::Signal] {
+            if !matches!(self.shared.task_queue.len(&self.config.task_queue, kind).await, Ok(0)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Worker configuration
+    pub fn config(&self) -> &WorkerConfig {
+        &self.config
+    }
+
+    /// Client sharing this worker's task queue, storage, schedules and dead-letter queue
+    pub fn client(&self) -> WorkflowClient {
+        self.shared.client()
+    }
+
+    /// Answers queries against the executions this worker is running
+    #[cfg_attr(not(feature = "nats"), allow(dead_code))]
+    pub(crate) fn query_dispatcher(&self) -> Arc<dyn QueryDispatcher> {
+        self.shared.executions.clone()
+    }
+
+    /// Register a workflow implementation
+    pub fn register_workflow<W: Workflow>(&self) {
+        self.shared.registry.workflows.write().insert(W::name().to_string(), workflow_fn::<W>());
+        match W::outline() {
+            Some(outline) => self.shared.registry.outlines.write().insert(W::name().to_string(), outline),
+            None => self.shared.registry.outlines.write().remove(W::name()),
+        };
+    }
+
+    /// Register a workflow interpreted from a declarative spec, under the spec's name
+    pub fn register_dynamic_workflow(&self, workflow: DynamicWorkflow) {
+        let name = workflow.name().to_string();
+        self.shared.registry.outlines.write().insert(name.clone(), workflow.spec().clone());
+        let workflow = Arc::new(workflow);
+        let run: WorkflowFn = Arc::new(move |ctx, input| {
+            let workflow = workflow.clone();
+            async move { workflow.execute(ctx, input).await }.boxed()
+        });
+        self.shared.registry.workflows.write().insert(name, run);
+    }
+
+    /// Register a workflow run by a closure over JSON values, such as a state machine workflow
+    #[cfg_attr(not(feature = "patterns"), allow(dead_code))]
+    pub(crate) fn register_workflow_fn(&self, workflow_type: &str, run: WorkflowFn) {
+        self.shared.registry.outlines.write().remove(workflow_type);
+        self.shared.registry.workflows.write().insert(workflow_type.to_string(), run);
+    }
+
+    /// Register an activity implementation
+    pub fn register_activity<A: Activity>(&self) {
+        let run: ActivityFn = Arc::new(|ctx, input| {
+            async move {
+                let input: A::Input = serde_json::from_value(input)
+                    .map_err(|e| ActivityError::InvalidInput(e.to_string()))?;
+                let output = A::execute(ctx, input).await?;
+                serde_json::to_value(output).map_err(|e| ActivityError::ExecutionFailed(e.to_string()))
+            }
+            .boxed()
+        });
+        self.shared.registry.activities.write().insert(A::name().to_string(), run);
+        self.shared.registry.resources.write().insert(A::name().to_string(), A::resources());
+    }
+
+    /// Register a batch activity, running the calls arriving for it together, see [`activity_batch`](super::activity_batch)
+    pub fn register_batch_activity<A: BatchActivity>(&self) {
+        let dispatcher = Arc::new(BatchDispatcher::new::<A>());
+        let run: ActivityFn = Arc::new(move |_ctx, input| dispatcher.clone().submit(input).boxed());
+        self.shared.registry.activities.write().insert(A::name().to_string(), run);
+    }
+
+    /// Names of the registered workflow types
+    pub fn registered_workflows(&self) -> Vec<String> {
+        self.shared.registry.workflows.read().keys().cloned().collect()
+    }
+
+    /// Step outline of a registered workflow type: the spec of a declarative workflow, or what a typed workflow
+    /// returns from [`Workflow::outline`]
+    pub fn workflow_outline(&self, workflow_type: &str) -> Option<WorkflowSpec> {
+        self.shared.registry.outlines.read().get(workflow_type).cloned()
+    }
+
+    /// Names of the registered activity types
+    ///
+    /// Includes the activities of the dynamic registry, see [`with_dynamic_activities`](Self::with_dynamic_activities).
+    pub fn registered_activities(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.shared.registry.activities.read().keys().cloned().collect();
+        if let Some(registry) = &self.shared.dynamic_activities {
+            for name in registry.names() {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Task queue of the activity sessions this worker hosts, `None` if it hosts none
+    pub fn session_task_queue(&self) -> Option<&str> {
+        self.shared.sessions.as_deref().map(SessionHost::task_queue)
+    }
+
+    /// Number of activity sessions open on this worker
+    pub fn open_sessions(&self) -> usize {
+        self.shared.sessions.as_ref().map_or(0, |sessions| sessions.len())
+    }
+
+    /// Number of runs whose history is held in the sticky history cache
+    pub fn cached_executions(&self) -> usize {
+        self.shared.histories.len()
+    }
+
+    /// Live view of this worker's registrations, settings and load, for probes and admin endpoints
+    pub fn status(&self) -> WorkerStatus {
+        WorkerStatus {
+            config: self.config.clone(),
+            registry: self.shared.registry.clone(),
+            dynamic_activities: self.shared.dynamic_activities.clone(),
+            task_queue: self.shared.task_queue.clone(),
+            executions: self.shared.executions.clone(),
+            histories: self.shared.histories.clone(),
+            load: self.load.clone(),
+            shutdown: self.shutdown.clone(),
+            running: self.running.clone(),
+        }
+    }
+
+    /// Handle that stops [`run`](Self::run) from another task
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle(self.shutdown.clone())
+    }
+
+    /// Handle that pauses workflow dispatch from another task
+    pub fn pause_handle(&self) -> PauseHandle {
+        PauseHandle(self.paused.clone())
+    }
+
+    /// Stop or resume taking new workflow tasks off the queue
+    ///
+    /// Workflows already running carry on, and activity and signal tasks are still dispatched, so
+    /// that in-flight work can finish. Tasks queued meanwhile wait for dispatch to resume.
+    pub fn pause_dispatch(&self, paused: bool) {
+        self.pause_handle().pause(paused);
+    }
+
+    /// Whether workflow dispatch is paused, see [`pause_dispatch`](Self::pause_dispatch)
+    pub fn is_dispatch_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Profile the next task of `workflow_id` on this worker, returning the ID to fetch it by
+    ///
+    /// The task is sampled while it runs, for at most
+    /// [`MAX_PROFILE_SECONDS`](crate::diagnostics::MAX_PROFILE_SECONDS); other tasks keep running. The
+    /// profile is kept among the last [`MAX_TASK_PROFILES`](crate::diagnostics::MAX_TASK_PROFILES).
+    #[cfg(feature = "diagnostics")]
+    pub fn profile_task(&self, workflow_id: &str) -> String {
+        self.shared.task_profiler.request(workflow_id)
+    }
+
+    /// Profile requested with [`profile_task`](Self::profile_task)
+    #[cfg(feature = "diagnostics")]
+    pub fn task_profile(&self, profile_id: &str) -> Option<TaskProfile> {
+        self.shared.task_profiler.get(profile_id)
+    }
+
+    /// Profiler behind [`profile_task`](Self::profile_task), for the `/debug` routes
+    #[cfg(feature = "diagnostics")]
+    pub fn task_profiler(&self) -> &Arc<TaskProfiler> {
+        &self.shared.task_profiler
+    }
+
+    /// Request a graceful shutdown, giving in-flight activity attempts and signal deliveries
+    /// `grace_period` to finish
+    ///
+    /// See [`run`](Self::run) for what happens to the work still in flight.
+    pub fn shutdown(&self, grace_period: Duration) {
+        self.shutdown_handle().shutdown(grace_period);
+    }
+
+    /// Poll and execute tasks, fire the schedules on this worker's task queue and apply its
+    /// retention policies, until shutdown is requested
+    ///
+    /// On shutdown the worker stops polling and waits up to the grace period for in-flight
+    /// activity attempts and signal deliveries, while workflows keep running to take in their
+    /// results. It then stops the workflow tasks, cancels the activity attempts still running,
+    /// and hands all unfinished tasks back to the queue for another worker; the executions stay
+    /// open in storage. Activity sessions hosted here fail once polling stops. The report lists
+    /// the tasks handed back.
+    pub async fn run(&self) -> Result<ShutdownReport, WorkflowError> {
+        tracing::info!(task_queue = %self.config.task_queue, "worker started");
+        self.running.store(true, Ordering::SeqCst);
+        let queue = &self.config.task_queue;
+        let (workflows, mut activities, mut session_activities, mut signals, (), (), ()) = tokio::join!(
+            self.poll_loop(queue, TaskKind::Workflow, self.load.workflow.clone()),
+            self.poll_loop(queue, TaskKind::Activity, self.load.activity.clone()),
+            self.session_poll_loop(),
+            self.poll_loop(queue, TaskKind::Signal, self.load.signal.clone()),
+            self.schedule_loop(),
+            self.janitor_loop(),
+            self.tuner_loop(),
+        );
+        self.running.store(false, Ordering::SeqCst);
+        // Nothing polls the session queue any more
+        if let Some(sessions) = &self.shared.sessions {
+            sessions.shut_down();
+        }
+
+        let grace_period = self.shutdown.borrow().unwrap_or_default();
+        let drained = async {
+            activities.drain().await;
+            session_activities.drain().await;
+            signals.drain().await;
+        };
+        match tokio::time::Instant::now().checked_add(grace_period) {
+            Some(deadline) => {
+                let _ = tokio::time::timeout_at(deadline, drained).await;
+            }
+            None => drained.await,
+        }
+
+        let mut report = ShutdownReport::default();
+        for task in workflows.abort().await {
+            if let Task::Workflow(workflow) = &task {
+                self.shared.stop_run(&workflow.execution);
+            }
+            report.workflows.push(task);
+        }
+        report.activities = activities.abort().await;
+        report.signals = signals.abort().await;
+        for task in report.workflows.iter().chain(&report.activities).chain(&report.signals) {
+            self.hand_back(queue, task).await;
+        }
+        if let Some(sessions) = &self.shared.sessions {
+            for task in session_activities.abort().await {
+                self.hand_back(sessions.task_queue(), &task).await;
+                report.activities.push(task);
+            }
+        }
+
+        if report.is_empty() {
+            tracing::info!(task_queue = %self.config.task_queue, "worker stopped");
+        } else {
+            tracing::warn!(
+                task_queue = %self.config.task_queue,
+                workflows = report.workflows.len(),
+                activities = report.activities.len(),
+                signals = report.signals.len(),
+                "worker stopped, handed unfinished tasks back to the queue"
+            );
+        }
+        Ok(report)
+    }
+
+    /// Push an unfinished task back for another worker, and complete the delivery this worker took
+    async fn hand_back(&self, queue: &str, task: &Task) {
+        if let Err(e) = self.shared.task_queue.push(queue, task.clone()).await {
+            tracing::error!(error = %e, task = task.type_name(), "failed to hand task back");
+            return;
+        }
+        if let Err(e) = self.shared.task_queue.complete(queue, task).await {
+            tracing::warn!(error = %e, task = task.type_name(), "task completion failed");
+        }
+    }
+
+    /// Poll the queue of the hosted activity sessions, sharing the activity slots
+    async fn session_poll_loop(&self) -> InFlight {
+        match &self.shared.sessions {
+            Some(sessions) => self.poll_loop(sessions.task_queue(), TaskKind::Activity, self.load.activity.clone()).await,
+            None => InFlight::default(),
+        }
+    }
+
+    async fn poll_loop(&self, queue: &str, kind: TaskKind, slots: Arc<Slots>) -> InFlight {
+        let mut shutdown = self.shutdown.subscribe();
+        // Only workflow tasks are held back while dispatch is paused
+        let mut paused = (kind == TaskKind::Workflow).then(|| self.paused.subscribe());
+        let mut in_flight = InFlight::default();
+
+        loop {
+            in_flight.reap();
+
+            if let Some(paused) = &mut paused {
+                tokio::select! {
+                    _ = shutdown.wait_for(Option::is_some) => break,
+                    _ = paused.wait_for(|paused| !paused) => {}
+                }
+            }
+            let permit = tokio::select! {
+                _ = shutdown.wait_for(Option::is_some) => break,
+                permit = slots.acquire() => permit,
+            };
+            let pausing = async {
+                match &mut paused {
+                    Some(paused) => {
+                        let _ = paused.wait_for(|paused| *paused).await;
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            let polled = tokio::select! {
+                biased;
+                _ = shutdown.wait_for(Option::is_some) => break,
+                _ = pausing => continue,
+                polled = self.shared.task_queue.poll_with_latency(queue, kind, self.config.poll_timeout) => polled,
+            };
+
+            match polled {
+                // Taken just as dispatch paused
+                Ok(Some((task, _))) if paused.as_ref().is_some_and(|paused| *paused.borrow()) => {
+                    self.shared.reschedule(queue, task, Duration::ZERO);
+                    drop(permit);
+                }
+                Ok(Some((task, waited))) => {
+                    if let Some(waited) = waited {
+                        slots.record_schedule_to_start(waited);
+                    }
+                    if let Some(retry_after) = self.shared.rate_limited(&task).await {
+                        self.shared.reschedule(queue, task, retry_after);
+                        drop(permit);
+                        continue;
+                    }
+                    let shared = self.shared.clone();
+                    let queue = queue.to_string();
+                    in_flight.spawn(task.clone(), async move {
+                        let Ok(resources) = shared.acquire_resources(&task).await else {
+                            shared.reschedule(&queue, task, RESOURCE_RETRY_DELAY);
+                            drop(permit);
+                            return;
+                        };
+                        let handled = task.clone();
+                        shared.handle(&queue, task).await;
+                        drop(resources);
+                        if let Err(e) = shared.task_queue.complete(&queue, &handled).await {
+                            tracing::warn!(error = %e, task = handled.type_name(), "task completion failed");
+                        }
+                        drop(permit);
+                    });
+                }
+                // An empty queue means tasks start without waiting
+                Ok(None) => slots.record_schedule_to_start(Duration::ZERO),
+                Err(e) => {
+                    tracing::warn!(error = %e, ?kind, "task queue poll failed");
+                    tokio::time::sleep(self.config.poll_timeout.min(Duration::from_secs(1))).await;
+                }
+            }
+        }
+        in_flight
+    }
+
+    async fn schedule_loop(&self) {
+        let mut shutdown = self.shutdown.subscribe();
+        loop {
+            for due in self.shared.schedules.take_due(&self.config.task_queue, chrono::Utc::now()) {
+                self.shared.fire_schedule(due).await;
+            }
+            tokio::select! {
+                _ = shutdown.wait_for(Option::is_some) => break,
+                _ = tokio::time::sleep(SCHEDULER_TICK) => {}
+            }
+        }
+    }
+
+    async fn tuner_loop(&self) {
+        let Some(tuner) = &self.tuner else {
+            return;
+        };
+        let mut shutdown = self.shutdown.subscribe();
+        loop {
+            tokio::select! {
+                _ = shutdown.wait_for(Option::is_some) => break,
+                _ = tokio::time::sleep(tuner.interval()) => self.load.tune(&**tuner),
+            }
+        }
+    }
+
+    async fn janitor_loop(&self) {
+        let Some(retention) = &self.retention else {
+            return;
+        };
+        let mut shutdown = self.shutdown.subscribe();
+        loop {
+            match retention.sweep(&*self.shared.storage, chrono::Utc::now()).await {
+                Ok(report) if !report.reclaimed.is_empty() => tracing::info!(
+                    reclaimed = report.reclaimed.len(),
+                    examined = report.examined,
+                    dry_run = report.dry_run,
+                    "retention sweep reclaimed executions"
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, "retention sweep failed"),
+            }
+            tokio::select! {
+                _ = shutdown.wait_for(Option::is_some) => break,
+                _ = tokio::time::sleep(retention.interval()) => {}
+            }
+        }
     }
 }
 
 impl Default for WorkflowWorker {
     fn default() -> Self {
-        Self::new()
+        Self::new(WorkerConfig::default())
     }
 }
 
-/// Worker config
-#[derive(Debug, Clone)]
-pub struct WorkerConfig {
-    /// Task queue
-    pub task_queue: String,
-    
-    /// Maximum concurrent workflow tasks
-    pub max_concurrent_workflow_tasks: usize,
-    
-    /// Maximum concurrent activity tasks
-    pub max_concurrent_activity_tasks: usize,
-}
+impl Shared {
+    fn client(&self) -> WorkflowClient {
+        let client = WorkflowClient::new(self.task_queue.clone(), self.storage.clone())
+            .with_schedules(self.schedules.clone())
+            .with_dead_letters(self.dead_letters.clone())
+            .with_queries(self.executions.clone())
+            .with_updates(self.executions.clone())
+            .with_data_converter(self.converter.clone())
+            .with_namespace(self.namespace.clone());
+        self.client_interceptors
+            .iter()
+            .fold(client, |client, interceptor| client.with_interceptor(interceptor.clone()))
+    }
 
-impl Default for WorkerConfig {
-    fn default() -> Self {
-        Self {
-            task_queue: "default".to_string(),
-            max_concurrent_workflow_tasks: 100,
-            max_concurrent_activity_tasks: 100,
+    #[cfg(feature = "patterns")]
+    fn publish(&self, event: impl FnOnce() -> EngineEvent) {
+        if let Some(event_bus) = &self.event_bus {
+            event_bus.publish(event());
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Count a crash of `task` and move it to the dead-letter queue once it reached the threshold
+    ///
+    /// Returns whether the task was dead-lettered.
+    fn dead_letter_if_poisoned(&self, task: Task, error: &str, on_discard: Option<DiscardHook>) -> bool {
+        if self.max_task_failures == 0 {
+            return false;
+        }
+        if self.dead_letters.record_failure(&task_key(&task)) < self.max_task_failures {
+            return false;
+        }
+        self.dead_letters.add(&self.queue_name, task, error.to_string(), on_discard);
+        true
+    }
 
-    #[test]
-    fn test_worker_creation() {
-        let _worker = WorkflowWorker::new();
+    /// Cancel an execution running on this worker; returns false if it is not running here
+    fn cancel_execution(&self, workflow_id: &WorkflowId) -> bool {
+        match self.executions.lock().running.get(workflow_id) {
+            Some(runtime) => {
+                runtime.cancellation.cancel();
+                true
+            }
+            None => false,
+        }
     }
 
-    #[test]
-    fn test_worker_config_default() {
-        let config = WorkerConfig::default();
-        assert_eq!(config.task_queue, "default");
-        assert_eq!(config.max_concurrent_workflow_tasks, 100);
+    /// Start a run for a due schedule, or apply its overlap policy if the previous run is open
+    async fn fire_schedule(&self, due: DueSchedule) {
+        let previous_open = match self.storage.load_workflow_execution(&due.schedule_id).await {
+            Ok((_, history)) => !history.is_closed(),
+            Err(StorageError::NotFound) => false,
+            Err(e) => {
+                tracing::warn!(schedule = %due.schedule_id, error = %e, "failed to check previous scheduled run");
+                return;
+            }
+        };
+        let action = match (due.fired, previous_open) {
+            (true, _) => due.overlap_policy.on_fire(previous_open),
+            // Only a buffered run is waiting: keep waiting for the previous run to close
+            (false, true) => return,
+            (false, false) => FireAction::Start,
+        };
+
+        match action {
+            FireAction::Start => {
+                let options = StartWorkflowOptions {
+                    workflow_id: Some(due.schedule_id.clone()),
+                    task_queue: due.task_queue.clone(),
+                    ..Default::default()
+                };
+                match self.client().start_workflow_value(&due.workflow_type, due.input, &options).await {
+                    Ok(execution) => tracing::debug!(%execution, "started scheduled run"),
+                    Err(e) => tracing::warn!(schedule = %due.schedule_id, error = %e, "failed to start scheduled run"),
+                }
+                self.schedules.set_buffered(&due.schedule_id, false);
+            }
+            FireAction::Skip => {
+                tracing::debug!(schedule = %due.schedule_id, "skipping fire, previous run still open");
+            }
+            FireAction::Buffer => self.schedules.set_buffered(&due.schedule_id, true),
+            FireAction::CancelAndBuffer => {
+                if !self.cancel_execution(&due.schedule_id) {
+                    tracing::warn!(schedule = %due.schedule_id, "previous run is not running on this worker, cannot cancel it");
+                }
+                self.schedules.set_buffered(&due.schedule_id, true);
+            }
+        }
     }
-}
 
+    /// How long an activity task over its rate limit has to wait, `None` if it may run now
+    async fn rate_limited(&self, task: &Task) -> Option<Duration> {
+        let (Task::Activity(activity), Some(limiter)) = (task, &self.rate_limiter) else {
+            return None;
+        };
+        let retry_after = limiter.acquire(&activity.activity_type).await.err()?;
+        metrics::counter!(ACTIVITY_RATE_LIMITED, "namespace" => self.namespace.to_string(), "activity_type" => activity.activity_type.clone())
+            .increment(1);
+        Some(retry_after)
+    }
+
+    /// Take a permit of each pool an activity task requires, or fail with the pool whose queue timed out
+    async fn acquire_resources(&self, task: &Task) -> Result<Vec<ResourcePermit>, String> {
+        let (Task::Activity(activity), Some(pools)) = (task, &self.resource_pools) else {
+            return Ok(Vec::new());
+        };
+        let declared = self.registry.resources.read().get(&activity.activity_type).copied().unwrap_or_default();
+        let required = pools.required_by(&activity.activity_type, declared);
+        pools.acquire(&required, activity.workflow_execution.run_id).await.inspect_err(|pool| {
+            tracing::debug!(activity_id = %activity.activity_id, %pool, "resource pool exhausted, rescheduling");
+        })
+    }
+
+    /// Put a polled task back on `queue` after `delay`, completing the polled copy once the new one is queued
+    ///
+    /// A copy that cannot be queued stays uncompleted, so queues with at-least-once delivery hand it out again.
+    fn reschedule(&self, queue: &str, task: Task, delay: Duration) {
+        let task_queue = self.task_queue.clone();
+        let queue = queue.to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            if let Err(e) = task_queue.push(&queue, task.clone()).await {
+                tracing::warn!(error = %e, task = task.type_name(), "failed to reschedule task");
+                return;
+            }
+            if let Err(e) = task_queue.complete(&queue, &task).await {
+                tracing::warn!(error = %e, task = task.type_name(), "task completion failed");
+            }
+        });
+    }
+
+    /// Persist and enqueue the run that continues a closed one
+    async fn start_successor(&self, task: WorkflowTask) -> Result<(), WorkflowError> {
+        let mut history = EventHistory::new();
+        history.append(EventType::WorkflowExecutionStarted {
+            workflow_type: task.workflow_type.clone(),
+            task_queue: task.task_queue.clone(),
+            input: task.input.clone(),
+        });
+        self.storage.save_workflow_execution(&task.execution, &history).await?;
+        let queue = task.task_queue.clone();
+        self.task_queue.push(&queue, Task::Workflow(task)).await?;
+        Ok(())
+    }
+
+    async fn handle(&self, queue: &str, task: Task) {
+        // Sampling stops when the guard drops with the task
+        #[cfg(feature = "diagnostics")]
+        let _profile = self.task_profiler.start(task.workflow_id().as_str());
+        match task {
+            Task::Workflow(task) => {
+                let span = telemetry::workflow_task_span(&task);
+                self.run_workflow(task).instrument(span).await
+            }
+            Task::Activity(task) => {
+                let span = telemetry::activity_attempt_span(&task);
+                self.run_activity(task).instrument(span).await
+            }
+            Task::Signal(task) => {
+                let span = telemetry::signal_delivery_span(&task);
+                self.deliver_signal(queue, task).instrument(span).await
+            }
+        }
+    }
+
+    /// Hand a signal to its running execution, or put it back for the worker that runs it
+    async fn deliver_signal(&self, queue: &str, mut task: SignalTask) {
+        let running = self.executions.lock().running.get(&task.execution.workflow_id).cloned();
+        let runtime = match running {
+            Some(runtime) if runtime.info.workflow_execution.run_id == task.execution.run_id => runtime,
+            Some(_) => {
+                tracing::debug!(execution = %task.execution, "dropping signal for a previous run");
+                return;
+            }
+            None => return self.redeliver_signal(queue, task).await,
+        };
+        for interceptor in self.interceptors.iter() {
+            if let Err(e) = interceptor.before_signal(&task.execution, &task.signal_name, &mut task.input).await {
+                tracing::warn!(execution = %task.execution, signal = %task.signal_name, error = %e, "interceptor dropped signal");
+                return;
+            }
+        }
+        if let Err(e) = runtime.signal(task.signal_name, task.input).await {
+            tracing::error!(execution = %task.execution, error = %e, "failed to record signal");
+        }
+    }
+
+    /// Put a signal back on its queue while its execution is open but not running here
+    ///
+    /// The execution runs on another worker, or has not started yet; either way the signal reaches
+    /// the worker running it once that worker polls it.
+    async fn redeliver_signal(&self, queue: &str, task: SignalTask) {
+        match self.storage.load_workflow_execution(&task.execution.workflow_id).await {
+            Ok((execution, history)) if execution.run_id != task.execution.run_id || history.is_closed() => {
+                tracing::debug!(execution = %task.execution, "dropping signal for a closed run");
+                return;
+            }
+            Err(StorageError::NotFound) => {
+                tracing::debug!(execution = %task.execution, "dropping signal for a deleted execution");
+                return;
+            }
+            _ => {}
+        }
+        if let Err(e) = self.task_queue.push(queue, Task::Signal(task)).await {
+            tracing::error!(error = %e, "failed to put signal back for the worker running its execution");
+            return;
+        }
+        tokio::time::sleep(SIGNAL_REDELIVERY_DELAY).await;
+    }
+
+    /// Run a workflow through the interceptors' workflow task hooks
+    async fn intercepted_workflow(
+        &self,
+        run: WorkflowFn,
+        runtime: Arc<ExecutionRuntime>,
+        mut input: serde_json::Value,
+    ) -> Result<serde_json::Value, WorkflowError> {
+        let info = runtime.info.clone();
+        input = converter::decode(&*self.converter, input)?;
+        for interceptor in self.interceptors.iter() {
+            interceptor.before_workflow_task(&info, &mut input).await?;
+        }
+        let mut result = run(WorkflowContext::attached(runtime), input).await;
+        for interceptor in self.interceptors.iter().rev() {
+            interceptor.after_workflow_task(&info, &mut result).await;
+        }
+        match result {
+            Ok(output) => converter::encode(&*self.converter, output),
+            Err(WorkflowError::ContinuedAsNew(input)) => {
+                Err(WorkflowError::ContinuedAsNew(converter::encode(&*self.converter, input)?))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Decoded input of an activity attempt, after the interceptors' activity hooks
+    async fn activity_input(&self, info: &ActivityInfo, input: serde_json::Value) -> ActivityResult {
+        let mut input = converter::decode(&*self.converter, input).map_err(|e| ActivityError::InvalidInput(e.to_string()))?;
+        for interceptor in self.interceptors.iter() {
+            interceptor.before_activity(info, &mut input).await?;
+        }
+        Ok(input)
+    }
+
+    /// Implementation of a workflow type, registrations first, then the system workflows
+    fn workflow(&self, workflow_type: &str) -> Option<WorkflowFn> {
+        if let Some(run) = self.registry.workflows.read().get(workflow_type) {
+            return Some(run.clone());
+        }
+        (workflow_type == BATCH_WORKFLOW_TYPE).then(workflow_fn::<BatchWorkflow>)
+    }
+
+    /// Implementation of an activity type, typed registrations first, then dynamic ones, then the system activities
+    fn activity(&self, activity_type: &str) -> Option<ActivityFn> {
+        if let Some(run) = self.registry.activities.read().get(activity_type) {
+            return Some(run.clone());
+        }
+        if let Some(activity) = self.dynamic_activities.as_ref().and_then(|registry| registry.get(activity_type)) {
+            return Some(Arc::new(move |ctx, input| {
+                let activity = activity.clone();
+                async move { activity.execute(ctx, input).await }.boxed()
+            }));
+        }
+        if activity_type == BATCH_ACTIVITY_TYPE {
+            let client = self.client();
+            return Some(Arc::new(move |ctx, input| batch::apply(client.clone(), ctx, input).boxed()));
+        }
+        None
+    }
+
+    async fn run_activity(&self, task: ActivityTask) {
+        let Some(cancellation) = self.pending.cancellation(task.workflow_execution.run_id, &task.activity_id) else {
+            tracing::debug!(activity_id = %task.activity_id, "skipping activity attempt nobody waits for");
+            return;
+        };
+        let started = Instant::now();
+        let implementation = self.activity(&task.activity_type);
+        let breaker = self.circuit_breaker.as_ref().filter(|_| implementation.is_some());
+        let admitted = breaker.map_or(Ok(()), |breaker| breaker.acquire(&task.activity_type));
+        let outbox = Arc::new(StagedMessages::default());
+        let (result, crashed) = match (implementation, admitted) {
+            (_, Err(open)) => (Err(open), false),
+            (Some(run), Ok(())) => {
+                let heartbeat = Arc::new(HeartbeatTracker::new());
+                let ctx = ActivityContext::new(task.activity_id.clone(), task.workflow_execution.clone())
+                    .with_attempt(task.attempt)
+                    .attached(cancellation.clone(), heartbeat.clone())
+                    .with_outbox(outbox.clone());
+                let info = ActivityInfo {
+                    activity_id: task.activity_id.clone(),
+                    activity_type: task.activity_type.clone(),
+                    workflow_execution: task.workflow_execution.clone(),
+                    attempt: task.attempt,
+                };
+                let input = self.activity_input(&info, task.input.clone()).await;
+                let attempt = async move { run(ctx, input?).await };
+                let attempt = AssertUnwindSafe(attempt).catch_unwind();
+                let caught = match task.heartbeat_timeout {
+                    Some(timeout) => tokio::select! {
+                        caught = attempt => caught,
+                        details = heartbeat.expired(timeout) => {
+                            cancellation.cancel();
+                            Ok(Err(ActivityError::HeartbeatFailed(match details {
+                                Some(details) => format!("no heartbeat within {:?}, last details: {}", timeout, details),
+                                None => format!("no heartbeat within {:?}", timeout),
+                            })))
+                        }
+                    },
+                    None => attempt.await,
+                };
+                let mut outcome = match caught {
+                    Ok(result) => (result, false),
+                    Err(_) => (Err(ActivityError::ExecutionFailed("activity panicked".to_string())), true),
+                };
+                for interceptor in self.interceptors.iter().rev() {
+                    interceptor.after_activity(&info, &mut outcome.0).await;
+                }
+                outcome.0 = outcome.0.and_then(|output| {
+                    converter::encode(&*self.converter, output).map_err(|e| ActivityError::ExecutionFailed(e.to_string()))
+                });
+                if let Some(breaker) = breaker {
+                    breaker.record(&task.activity_type, &outcome.0);
+                }
+                outcome
+            }
+            (None, Ok(())) => (
+                Err(ActivityError::ExecutionFailed(format!(
+                    "activity type not registered: {}",
+                    task.activity_type
+                ))),
+                false,
+            ),
+        };
+
+        let run_id = task.workflow_execution.run_id;
+        let activity_id = task.activity_id.clone();
+        let activity_type = task.activity_type.clone();
+        #[cfg(feature = "patterns")]
+        let (execution, attempt) = (task.workflow_execution.clone(), task.attempt);
+        if crashed {
+            // The waiter stays registered, so a retried dead letter still completes it
+            let pending = self.pending.clone();
+            let waiter_id = activity_id.clone();
+            let on_discard: DiscardHook = Box::new(move |reason| {
+                pending.complete(run_id, &waiter_id, Err(ActivityError::DeadLettered(reason)));
+            });
+            if self.dead_letter_if_poisoned(Task::Activity(task), "activity panicked", Some(on_discard)) {
+                metrics::counter!(ACTIVITY_ATTEMPTS, "namespace" => self.namespace.to_string(), "activity_type" => activity_type, "outcome" => "dead_lettered").increment(1);
+                return;
+            }
+        } else {
+            self.dead_letters.clear_failures(&task_key(&Task::Activity(task)));
+        }
+
+        #[cfg(feature = "patterns")]
+        if let Err(e) = &result {
+            self.publish(|| EngineEvent::ActivityFailed {
+                execution,
+                activity_id: activity_id.clone(),
+                activity_type: activity_type.clone(),
+                attempt,
+                error: e.to_string(),
+            });
+        }
+        let outcome = if result.is_ok() { "completed" } else { "failed" };
+        metrics::counter!(ACTIVITY_ATTEMPTS, "namespace" => self.namespace.to_string(), "activity_type" => activity_type.clone(), "outcome" => outcome).increment(1);
+        metrics::histogram!(ACTIVITY_DURATION, "namespace" => self.namespace.to_string(), "activity_type" => activity_type, "outcome" => outcome)
+            .record(started.elapsed().as_secs_f64());
+        if !self.pending.complete_with_outbox(run_id, &activity_id, result, outbox.take()) {
+            tracing::debug!(%activity_id, "activity result arrived after its waiter gave up");
+        }
+    }
+
+    async fn run_workflow(&self, task: WorkflowTask) {
+        let cached = self.histories.get(&task.execution.run_id);
+        let cache_result = if cached.is_some() { "hit" } else { "miss" };
+        metrics::counter!(STICKY_CACHE_REQUESTS, "namespace" => self.namespace.to_string(), "workflow_type" => task.workflow_type.clone(), "result" => cache_result)
+            .increment(1);
+        // A hit spares the storage read; the run is rebuilt and replayed from the history either way
+        let stored = match cached {
+            Some(runtime) => Ok((task.execution.clone(), runtime.history.lock().await.clone())),
+            None => self.storage.load_workflow_execution(&task.execution.workflow_id).await,
+        };
+        let history = match stored {
+            Ok((execution, history)) if execution.run_id == task.execution.run_id => history,
+            _ => {
+                let mut history = EventHistory::new();
+                history.append(EventType::WorkflowExecutionStarted {
+                    workflow_type: task.workflow_type.clone(),
+                    task_queue: task.task_queue.clone(),
+                    input: task.input.clone(),
+                });
+                history
+            }
+        };
+        if history.is_closed() {
+            tracing::debug!(execution = %task.execution, "skipping task for closed workflow");
+            return;
+        }
+        // Only the started event means no earlier task of the run got anywhere
+        #[cfg(feature = "patterns")]
+        if history.len() == 1 {
+            self.publish(|| EngineEvent::WorkflowStarted {
+                execution: task.execution.clone(),
+                workflow_type: task.workflow_type.clone(),
+                task_queue: task.task_queue.clone(),
+            });
+        }
+
+        let resuming = (history.len() > 1).then(|| Arc::new(Replay::resuming(&history)));
+        let runtime = ExecutionRuntime::new(
+            WorkflowInfo {
+                workflow_type: task.workflow_type.clone(),
+                workflow_execution: task.execution.clone(),
+                task_queue: task.task_queue.clone(),
+            },
+            history,
+            self.storage.clone(),
+            self.task_queue.clone(),
+            self.pending.clone(),
+        )
+        .with_clock(self.clock.clone())
+        .with_converter(self.converter.clone())
+        .with_admission(self.admission.clone())
+        .with_sessions(self.sessions.clone())
+        .with_history_limits(self.history_limits)
+        .with_priority(task.priority);
+        #[cfg(feature = "persistence")]
+        let runtime = runtime.with_memo(self.memo.clone());
+        // A run handed back at shutdown, retried after a crash or taken from the dead-letter queue
+        // picks up where its history ends instead of running its activities again
+        let runtime = match resuming {
+            Some(replay) => runtime.replaying(replay),
+            None => runtime,
+        };
+        let runtime = Arc::new(runtime);
+        self.histories.insert(task.execution.run_id, runtime.clone());
+        metrics::gauge!(STICKY_CACHE_SIZE).set(self.histories.len() as f64);
+        self.executions.lock().running.insert(task.execution.workflow_id.clone(), runtime.clone());
+        runtime.deliver_replayed_inputs();
+
+        let implementation = self.workflow(&task.workflow_type);
+        let result = match implementation {
+            // Cancellation is cooperative: the workflow sees its root scope cancelled and may clean up.
+            // Termination is not: the workflow code is dropped wherever it waits.
+            Some(run) => match tokio::select! {
+                biased;
+                reason = runtime.terminated() => Ok(Err(WorkflowError::Terminated(reason))),
+                caught = AssertUnwindSafe(self.intercepted_workflow(run, runtime.clone(), task.input.clone()))
+                    .catch_unwind()
+                    .instrument(telemetry::workflow_run_span(&task)) => caught,
+            } {
+                Ok(result) => result,
+                Err(_) if self.max_task_failures > 0 => {
+                    // The execution stays open; the task is retried until it is dead-lettered
+                    self.stop_run(&task.execution);
+                    metrics::counter!(WORKFLOW_TASKS, "namespace" => self.namespace.to_string(), "workflow_type" => task.workflow_type.clone(), "outcome" => "crashed")
+                        .increment(1);
+                    let queue = task.task_queue.clone();
+                    if !self.dead_letter_if_poisoned(Task::Workflow(task.clone()), "workflow panicked", None)
+                        && let Err(e) = self.task_queue.push(&queue, Task::Workflow(task)).await
+                    {
+                        tracing::error!(error = %e, "failed to retry crashed workflow task");
+                    }
+                    return;
+                }
+                Err(_) => Err(WorkflowError::Custom("workflow panicked".to_string())),
+            },
+            None => Err(WorkflowError::Custom(format!(
+                "workflow type not registered: {}",
+                task.workflow_type
+            ))),
+        };
+
+        let mut successor = None;
+        let (event, outcome) = match result {
+            Ok(result) => (EventType::WorkflowExecutionCompleted { result }, "completed"),
+            Err(WorkflowError::ContinuedAsNew(input)) => {
+                let next = WorkflowExecution::new(task.execution.workflow_id.clone());
+                let event = EventType::WorkflowExecutionContinuedAsNew {
+                    new_run_id: next.run_id,
+                    input: input.clone(),
+                };
+                successor = Some(WorkflowTask {
+                    execution: next,
+                    workflow_type: task.workflow_type.clone(),
+                    task_queue: task.task_queue.clone(),
+                    input,
+                    priority: task.priority,
+                    trace_context: telemetry::context_of(&tracing::Span::current()),
+                });
+                (event, "continued_as_new")
+            }
+            Err(WorkflowError::Terminated(reason)) => (EventType::WorkflowExecutionTerminated { reason }, "terminated"),
+            Err(e) => (EventType::WorkflowExecutionFailed { failure: e.to_string() }, "failed"),
+        };
+        let workflow_type = task.workflow_type.clone();
+        metrics::counter!(WORKFLOW_TASKS, "namespace" => self.namespace.to_string(), "workflow_type" => workflow_type.clone(), "outcome" => outcome).increment(1);
+        metrics::counter!(WORKFLOWS_CLOSED, "namespace" => self.namespace.to_string(), "workflow_type" => workflow_type.clone(), "outcome" => outcome).increment(1);
+        let started_at = runtime.history.lock().await.events().first().map(|event| event.timestamp);
+        if let Some(duration) = started_at.and_then(|started_at| (chrono::Utc::now() - started_at).to_std().ok()) {
+            metrics::histogram!(WORKFLOW_DURATION, "namespace" => self.namespace.to_string(), "workflow_type" => workflow_type, "outcome" => outcome)
+                .record(duration.as_secs_f64());
+        }
+        self.dead_letters.clear_failures(&task_key(&Task::Workflow(task.clone())));
+        #[cfg(feature = "patterns")]
+        let closed = match &event {
+            EventType::WorkflowExecutionCompleted { result } => Some(EngineEvent::WorkflowCompleted {
+                execution: task.execution.clone(),
+                workflow_type: task.workflow_type.clone(),
+                result: result.clone(),
+            }),
+            EventType::WorkflowExecutionFailed { failure } => Some(EngineEvent::WorkflowFailed {
+                execution: task.execution.clone(),
+                workflow_type: task.workflow_type.clone(),
+                failure: failure.clone(),
+            }),
+            EventType::WorkflowExecutionTerminated { reason } => Some(EngineEvent::WorkflowFailed {
+                execution: task.execution.clone(),
+                workflow_type: task.workflow_type.clone(),
+                failure: format!("terminated: {}", reason),
+            }),
+            _ => None,
+        };
+        match runtime.record(event).await {
+            Err(e) => tracing::error!(execution = %task.execution, error = %e, "failed to record workflow outcome"),
+            Ok(()) => {
+                // A closed run gets no more tasks, so its slot goes to runs that still do
+                self.histories.remove(&task.execution.run_id);
+                metrics::gauge!(STICKY_CACHE_SIZE).set(self.histories.len() as f64);
+                if let Some(next) = successor
+                    && let Err(e) = self.start_successor(next).await
+                {
+                    tracing::error!(execution = %task.execution, error = %e, "failed to start continued-as-new run");
+                }
+            }
+        }
+        #[cfg(feature = "patterns")]
+        if let Some(closed) = closed {
+            self.publish(|| closed);
+        }
+        self.stop_run(&task.execution);
+    }
+
+    /// Forget a run that stopped on this worker, cancelling its activity attempts and freeing its session slots
+    fn stop_run(&self, execution: &WorkflowExecution) {
+        self.executions.lock().running.remove(&execution.workflow_id);
+        self.pending.cancel_run(execution.run_id);
+        if let Some(sessions) = &self.sessions {
+            sessions.close_run(execution.run_id);
+        }
+    }
+}
+
+/// Stops a running worker
+#[derive(Clone)]
+pub struct ShutdownHandle(Arc<watch::Sender<Option<Duration>>>);
+
+impl ShutdownHandle {
+    /// Request a graceful shutdown, see [`WorkflowWorker::shutdown`]
+    pub fn shutdown(&self, grace_period: Duration) {
+        self.0.send_replace(Some(grace_period));
+    }
+}
+
+/// Pauses and resumes the workflow dispatch of a worker
+#[derive(Clone)]
+pub struct PauseHandle(Arc<watch::Sender<bool>>);
+
+impl PauseHandle {
+    /// See [`WorkflowWorker::pause_dispatch`]
+    pub fn pause(&self, paused: bool) {
+        let changed = self.0.send_if_modified(|current| std::mem::replace(current, paused) != paused);
+        if changed {
+            tracing::info!(paused, "workflow dispatch {}", if paused { "paused" } else { "resumed" });
+        }
+    }
+}
+
+/// Live view of a worker, see [`WorkflowWorker::status`]
+#[derive(Clone)]
+pub struct WorkerStatus {
+    config: WorkerConfig,
+    registry: Arc<Registry>,
+    dynamic_activities: Option<Arc<DynamicActivityRegistry>>,
+    task_queue: Arc<dyn TaskQueue>,
+    executions: Arc<Mutex<Executions>>,
+    histories: Arc<HistoryCache<Arc<ExecutionRuntime>>>,
+    load: Arc<WorkerLoad>,
+    shutdown: Arc<watch::Sender<Option<Duration>>>,
+    running: Arc<AtomicBool>,
+}
+
+/// Work a worker is doing right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct InFlightCounts {
+    /// Executions loaded on the worker, waiting or running
+    pub executions: usize,
+    pub workflow_tasks: usize,
+    pub activity_tasks: usize,
+}
+
+/// Tasks waiting on each lane of a worker's task queue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct QueueDepths {
+    pub task_queue: String,
+    pub workflow: usize,
+    pub activity: usize,
+    pub signal: usize,
+}
+
+impl WorkerStatus {
+    /// Number of registered workflow types
+    pub fn registered_workflows(&self) -> usize {
+        self.registry.workflows.read().len()
+    }
+
+    /// Number of registered activity types, including those of the dynamic registry
+    pub fn registered_activities(&self) -> usize {
+        self.activity_types().len()
+    }
+
+    /// Names of the registered workflow types, sorted
+    pub fn workflow_types(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.registry.workflows.read().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Names of the registered activity types, including those of the dynamic registry, sorted
+    pub fn activity_types(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.registry.activities.read().keys().cloned().collect();
+        if let Some(registry) = &self.dynamic_activities {
+            names.extend(registry.names());
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Settings of the worker, with the concurrency limits as currently tuned
+    pub fn config(&self) -> WorkerConfig {
+        let signals = self.load.signals();
+        WorkerConfig {
+            max_concurrent_workflow_tasks: signals.workflow.max_slots,
+            max_concurrent_activity_tasks: signals.activity.max_slots,
+            ..self.config.clone()
+        }
+    }
+
+    pub fn sticky_cache(&self) -> StickyCacheStats {
+        self.histories.stats()
+    }
+
+    pub fn in_flight(&self) -> InFlightCounts {
+        let signals = self.load.signals();
+        InFlightCounts {
+            executions: self.executions.lock().running.len(),
+            workflow_tasks: signals.workflow.used_slots,
+            activity_tasks: signals.activity.used_slots,
+        }
+    }
+
+    /// Tasks waiting on the worker's task queue
+    pub async fn queue_depths(&self) -> Result<QueueDepths, StorageError> {
+        let queue = &self.config.task_queue;
+        Ok(QueueDepths {
+            task_queue: queue.clone(),
+            workflow: self.task_queue.len(queue, TaskKind::Workflow).await?,
+            activity: self.task_queue.len(queue, TaskKind::Activity).await?,
+            signal: self.task_queue.len(queue, TaskKind::Signal).await?,
+        })
+    }
+
+    /// Whether the worker is polling its task queue
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Whether a shutdown was requested; a draining worker takes no new tasks
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.borrow().is_some()
+    }
+}
+
+/// Unfinished tasks a worker handed back to its queue when it stopped
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownReport {
+    /// Workflow tasks stopped mid-run; another worker resumes them from the end of their stored history,
+    /// without running finished activities again
+    pub workflows: Vec<Task>,
+    /// Activity attempts cancelled at the end of the grace period
+    pub activities: Vec<Task>,
+    /// Signal deliveries cancelled at the end of the grace period
+    pub signals: Vec<Task>,
+}
+
+impl ShutdownReport {
+    /// Whether the worker stopped without leaving work behind
+    pub fn is_empty(&self) -> bool {
+        self.workflows.is_empty() && self.activities.is_empty() && self.signals.is_empty()
+    }
+}
+
+/// Tasks a poll loop is running
+#[derive(Default)]
+struct InFlight {
+    running: JoinSet<()>,
+    tasks: HashMap<tokio::task::Id, Task>,
+}
+
+impl InFlight {
+    fn spawn(&mut self, task: Task, handling: impl Future<Output = ()> + Send + 'static) {
+        let id = self.running.spawn(handling).id();
+        self.tasks.insert(id, task);
+    }
+
+    /// Forget the tasks that finished
+    fn reap(&mut self) {
+        while let Some(joined) = self.running.try_join_next_with_id() {
+            self.tasks.remove(&joined.map_or_else(|e| e.id(), |(id, ())| id));
+        }
+    }
+
+    async fn drain(&mut self) {
+        while let Some(joined) = self.running.join_next_with_id().await {
+            self.tasks.remove(&joined.map_or_else(|e| e.id(), |(id, ())| id));
+        }
+    }
+
+    /// Abort the tasks still running, returning them
+    async fn abort(mut self) -> Vec<Task> {
+        self.running.abort_all();
+        let mut aborted = Vec::new();
+        while let Some(joined) = self.running.join_next_with_id().await {
+            match joined {
+                Err(e) if e.is_cancelled() => aborted.extend(self.tasks.remove(&e.id())),
+                joined => {
+                    self.tasks.remove(&joined.map_or_else(|e| e.id(), |(id, ())| id));
+                }
+            }
+        }
+        aborted
+    }
+}
+
+/// Worker config
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    /// Task queue
+    pub task_queue: String,
+    
+    /// Maximum concurrent workflow tasks
+    pub max_concurrent_workflow_tasks: usize,
+    
+    /// Maximum concurrent activity tasks
+    pub max_concurrent_activity_tasks: usize,
+
+    /// How long a single poll waits for a task
+    pub poll_timeout: Duration,
+
+    /// Number of recently active runs whose history is kept in memory, sparing storage reads when
+    /// they get another task; they are still replayed. Zero disables the cache
+    pub sticky_cache_size: usize,
+
+    /// Crashes (panics) of the same task after which it is moved to the dead-letter queue; zero disables it
+    pub max_task_failures: u32,
+
+    /// Free-form tags describing the worker
+    pub tags: HashMap<String, String>,
+
+    /// Name of the worker, unique among the workers of a task queue; names its session task queue
+    pub identity: String,
+
+    /// Activity sessions the worker hosts at once; zero disables sessions, see [`WorkflowContext::create_session`]
+    pub max_concurrent_sessions: usize,
+
+    /// Sizes of a run's history at which the worker warns and at which it terminates the run
+    pub history_limits: HistoryLimits,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            task_queue: "default".to_string(),
+            max_concurrent_workflow_tasks: 100,
+            max_concurrent_activity_tasks: 100,
+            poll_timeout: Duration::from_secs(1),
+            sticky_cache_size: 1000,
+            max_task_failures: 3,
+            tags: HashMap::new(),
+            identity: uuid::Uuid::new_v4().to_string(),
+            max_concurrent_sessions: 0,
+            history_limits: HistoryLimits::default(),
+        }
+    }
+}
+
+impl WorkerConfig {
+    /// Start building a config from the defaults
+    pub fn builder() -> WorkerConfigBuilder {
+        WorkerConfigBuilder { config: Self::default() }
+    }
+}
+
+/// Builder for [`WorkerConfig`]
+#[derive(Debug, Clone)]
+pub struct WorkerConfigBuilder {
+    config: WorkerConfig,
+}
+
+impl WorkerConfigBuilder {
+    pub fn task_queue(mut self, task_queue: impl Into<String>) -> Self {
+        self.config.task_queue = task_queue.into();
+        self
+    }
+
+    pub fn max_concurrent_workflow_tasks(mut self, max: usize) -> Self {
+        self.config.max_concurrent_workflow_tasks = max;
+        self
+    }
+
+    pub fn max_concurrent_activity_tasks(mut self, max: usize) -> Self {
+        self.config.max_concurrent_activity_tasks = max;
+        self
+    }
+
+    pub fn poll_timeout(mut self, timeout: Duration) -> Self {
+        self.config.poll_timeout = timeout;
+        self
+    }
+
+    pub fn sticky_cache_size(mut self, size: usize) -> Self {
+        self.config.sticky_cache_size = size;
+        self
+    }
+
+    pub fn max_task_failures(mut self, max: u32) -> Self {
+        self.config.max_task_failures = max;
+        self
+    }
+
+    pub fn identity(mut self, identity: impl Into<String>) -> Self {
+        self.config.identity = identity.into();
+        self
+    }
+
+    pub fn max_concurrent_sessions(mut self, max: usize) -> Self {
+        self.config.max_concurrent_sessions = max;
+        self
+    }
+
+    pub fn history_limits(mut self, limits: HistoryLimits) -> Self {
+        self.config.history_limits = limits;
+        self
+    }
+
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.tags.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn build(self) -> WorkerConfig {
+        self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worker_creation() {
+        let _worker = WorkflowWorker::new(WorkerConfig::default());
+    }
+
+    #[test]
+    fn test_worker_config_default() {
+        let config = WorkerConfig::default();
+        assert_eq!(config.task_queue, "default");
+        assert_eq!(config.max_concurrent_workflow_tasks, 100);
+    }
+
+    #[test]
+    fn test_worker_config_builder() {
+        let config = WorkerConfig::builder()
+            .task_queue("orders")
+            .max_concurrent_activity_tasks(5)
+            .tag("service", "orders")
+            .build();
+        assert_eq!(config.task_queue, "orders");
+        assert_eq!(config.max_concurrent_activity_tasks, 5);
+        assert_eq!(config.max_concurrent_workflow_tasks, 100);
+        assert_eq!(config.tags["service"], "orders");
+    }
+
+    use crate::temporal::circuit_breaker::{CircuitBreakerConfig, CircuitState};
+    use crate::temporal::{ActivityOptions, RetryPolicy, StartWorkflowOptions, WorkflowHandle};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Double;
+
+    impl Activity for Double {
+        type Input = i64;
+        type Output = i64;
+
+        fn name() -> &'static str {
+            "double"
+        }
+
+        async fn execute(_ctx: ActivityContext, input: i64) -> Result<i64, ActivityError> {
+            Ok(input * 2)
+        }
+    }
+
+    /// Fails with a temporary error until the third attempt
+    struct Flaky;
+
+    impl Activity for Flaky {
+        type Input = i64;
+        type Output = i64;
+
+        fn name() -> &'static str {
+            "flaky"
+        }
+
+        async fn execute(ctx: ActivityContext, input: i64) -> Result<i64, ActivityError> {
+            if ctx.attempt() < 3 {
+                return Err(ActivityError::TemporaryFailure(format!("attempt {}", ctx.attempt())));
+            }
+            Ok(input + 1)
+        }
+    }
+
+    static RUNNING: AtomicUsize = AtomicUsize::new(0);
+    static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+    struct Slow;
+
+    impl Activity for Slow {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "slow"
+        }
+
+        async fn execute(_ctx: ActivityContext, _input: ()) -> Result<(), ActivityError> {
+            let now = RUNNING.fetch_add(1, Ordering::SeqCst) + 1;
+            PEAK.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            RUNNING.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Heartbeats once, then stalls
+    struct Stalls;
+
+    impl Activity for Stalls {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "stalls"
+        }
+
+        async fn execute(ctx: ActivityContext, _input: ()) -> Result<(), ActivityError> {
+            ctx.heartbeat_with_details("step 1").await?;
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        }
+    }
+
+    static OBSERVED_CANCEL: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+    /// Heartbeats until cancelled
+    struct UntilCancelled;
+
+    impl Activity for UntilCancelled {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "until_cancelled"
+        }
+
+        async fn execute(ctx: ActivityContext, _input: ()) -> Result<(), ActivityError> {
+            loop {
+                if let Err(e) = ctx.heartbeat().await {
+                    OBSERVED_CANCEL.store(ctx.is_cancelled(), Ordering::SeqCst);
+                    return Err(e);
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        }
+    }
+
+    /// Runs the activity named by the input with short timeouts and no retries
+    struct Impatient;
+
+    impl Workflow for Impatient {
+        type Input = String;
+        type Output = ();
+
+        fn name() -> &'static str {
+            "impatient"
+        }
+
+        async fn execute(ctx: WorkflowContext, activity: String) -> Result<(), WorkflowError> {
+            let options = ActivityOptions {
+                start_to_close_timeout: Some(Duration::from_millis(100)),
+                heartbeat_timeout: Some(Duration::from_millis(30)),
+                retry_policy: Some(RetryPolicy::no_retry()),
+                ..Default::default()
+            };
+            match activity.as_str() {
+                "stalls" => ctx.execute_activity::<Stalls>((), options).await,
+                _ => ctx.execute_activity::<UntilCancelled>((), options).await,
+            }
+        }
+    }
+
+    /// Waits on an activity until cancelled, then doubles its input in a non-cancellable scope
+    struct CleansUp;
+
+    impl Workflow for CleansUp {
+        type Input = i64;
+        type Output = i64;
+
+        fn name() -> &'static str {
+            "cleans_up"
+        }
+
+        async fn execute(ctx: WorkflowContext, input: i64) -> Result<i64, WorkflowError> {
+            let error = match ctx.execute_activity::<UntilCancelled>((), ActivityOptions::default()).await {
+                Ok(()) => return Ok(input),
+                Err(e) => e,
+            };
+            assert!(ctx.is_cancelled());
+            ctx.non_cancellable_scope(|scope| async move {
+                scope.execute_activity::<Double>(input, ActivityOptions::default()).await
+            })
+            .await?;
+            Err(error)
+        }
+    }
+
+    /// Always panics
+    struct Crashes;
+
+    impl Activity for Crashes {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "crashes"
+        }
+
+        async fn execute(_ctx: ActivityContext, _input: ()) -> Result<(), ActivityError> {
+            panic!("poisoned activity")
+        }
+    }
+
+    static POISONED_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Panics when its input is true, otherwise runs the crashing activity
+    struct Poisoned;
+
+    impl Workflow for Poisoned {
+        type Input = bool;
+        type Output = ();
+
+        fn name() -> &'static str {
+            "poisoned"
+        }
+
+        async fn execute(ctx: WorkflowContext, panics: bool) -> Result<(), WorkflowError> {
+            if panics {
+                POISONED_RUNS.fetch_add(1, Ordering::SeqCst);
+                panic!("poisoned workflow");
+            }
+            ctx.execute_activity::<Crashes>((), fast_retries()).await
+        }
+    }
+
+    fn fast_retries() -> ActivityOptions {
+        ActivityOptions {
+            retry_policy: Some(RetryPolicy {
+                initial_interval: Duration::from_millis(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    struct DoubleThenFlaky;
+
+    impl Workflow for DoubleThenFlaky {
+        type Input = i64;
+        type Output = i64;
+
+        fn name() -> &'static str {
+            "double_then_flaky"
+        }
+
+        async fn execute(ctx: WorkflowContext, input: i64) -> Result<i64, WorkflowError> {
+            let doubled = ctx.execute_activity::<Double>(input, ActivityOptions::default()).await?;
+            ctx.sleep(Duration::from_millis(1)).await;
+            ctx.execute_activity::<Flaky>(doubled, fast_retries()).await
+        }
+    }
+
+    struct SlowWorkflow;
+
+    impl Workflow for SlowWorkflow {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "slow_workflow"
+        }
+
+        async fn execute(ctx: WorkflowContext, _input: ()) -> Result<(), WorkflowError> {
+            ctx.execute_activity::<Slow>((), ActivityOptions::default()).await
+        }
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Approve {
+        by: String,
+    }
+
+    impl crate::temporal::Signal for Approve {
+        fn name() -> &'static str {
+            "approve"
+        }
+    }
+
+    /// Waits for two approvals, giving up if the first takes longer than the input in milliseconds
+    struct Approval;
+
+    impl Workflow for Approval {
+        type Input = u64;
+        type Output = Vec<String>;
+
+        fn name() -> &'static str {
+            "approval"
+        }
+
+        async fn execute(ctx: WorkflowContext, timeout_ms: u64) -> Result<Vec<String>, WorkflowError> {
+            let first = ctx.wait_for_signal::<Approve>(Some(Duration::from_millis(timeout_ms))).await?;
+            let second = ctx.wait_for_signal::<Approve>(None).await?;
+            Ok(vec![first.by, second.by])
+        }
+    }
+
+    struct Approvers;
+
+    impl crate::temporal::Query for Approvers {
+        fn name() -> &'static str {
+            "approvers"
+        }
+
+        type Result = Vec<String>;
+    }
+
+    /// Collects approvals through a signal handler until two arrived, answering who approved so far
+    struct Tally;
+
+    impl Workflow for Tally {
+        type Input = ();
+        type Output = Vec<String>;
+
+        fn name() -> &'static str {
+            "tally"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<Vec<String>, WorkflowError> {
+            let approvers = Arc::new(Mutex::new(Vec::new()));
+            let received = approvers.clone();
+            ctx.on_signal::<Approve>(move |approve| received.lock().push(approve.by))?;
+            let answered = approvers.clone();
+            ctx.on_query::<Approvers>(move || answered.lock().clone())?;
+            let done = approvers.clone();
+            ctx.await_condition(move || done.lock().len() >= 2, None).await?;
+            Ok(approvers.lock().clone())
+        }
+    }
+
+    static TICKS: AtomicUsize = AtomicUsize::new(0);
+    static SLEEPER_STARTS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Tick;
+
+    impl Workflow for Tick {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "tick"
+        }
+
+        async fn execute(_ctx: WorkflowContext, _input: ()) -> Result<(), WorkflowError> {
+            TICKS.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    struct Sleeper;
+
+    impl Workflow for Sleeper {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "sleeper"
+        }
+
+        async fn execute(ctx: WorkflowContext, _input: ()) -> Result<(), WorkflowError> {
+            SLEEPER_STARTS.fetch_add(1, Ordering::SeqCst);
+            ctx.sleep(Duration::from_secs(30)).await;
+            Ok(())
+        }
+    }
+
+    /// Counts down one run at a time, carrying the number of runs so far
+    struct Countdown;
+
+    impl Workflow for Countdown {
+        type Input = (u32, u32);
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "countdown"
+        }
+
+        async fn execute(ctx: WorkflowContext, (remaining, runs): (u32, u32)) -> Result<u32, WorkflowError> {
+            ctx.sleep(Duration::from_millis(1)).await;
+            if remaining == 0 {
+                return Ok(runs + 1);
+            }
+            ctx.continue_as_new((remaining - 1, runs + 1))
+        }
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("condition not reached in time");
+    }
+
+    fn spawn_worker(config: WorkerConfig) -> (Arc<WorkflowWorker>, tokio::task::JoinHandle<Result<ShutdownReport, WorkflowError>>) {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..config
+        }));
+        worker.register_workflow::<DoubleThenFlaky>();
+        worker.register_workflow::<SlowWorkflow>();
+        worker.register_workflow::<Approval>();
+        worker.register_workflow::<Tally>();
+        worker.register_workflow::<Impatient>();
+        worker.register_workflow::<Tick>();
+        worker.register_workflow::<Countdown>();
+        worker.register_workflow::<Sleeper>();
+        worker.register_workflow::<CleansUp>();
+        worker.register_workflow::<Poisoned>();
+        worker.register_activity::<Crashes>();
+        worker.register_activity::<Stalls>();
+        worker.register_activity::<UntilCancelled>();
+        worker.register_activity::<Double>();
+        worker.register_activity::<Flaky>();
+        worker.register_activity::<Slow>();
+        let running = worker.clone();
+        (worker, tokio::spawn(async move { running.run().await }))
+    }
+
+    #[tokio::test]
+    async fn test_worker_runs_workflow_with_retried_activity() {
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let handle: WorkflowHandle<i64> = worker
+            .client()
+            .start_workflow::<DoubleThenFlaky>(20, StartWorkflowOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(handle.result().await.unwrap(), 41);
+
+        let history = handle.history().await.unwrap();
+        let count = |pred: fn(&EventType) -> bool| history.events().iter().filter(|e| pred(&e.event_type)).count();
+        assert_eq!(count(|e| matches!(e, EventType::ActivityTaskScheduled { .. })), 2);
+        assert_eq!(count(|e| matches!(e, EventType::ActivityTaskCompleted { .. })), 2);
+        assert_eq!(count(|e| matches!(e, EventType::ActivityTaskFailed { .. })), 2);
+        assert_eq!(count(|e| matches!(e, EventType::TimerFired { .. })), 1);
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_profile_task_records_the_next_task() {
+        use crate::diagnostics::TaskProfile;
+        use pprof::protos::Message;
+
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let profile_id = worker.profile_task("profiled");
+        let untouched = worker.profile_task("not-started");
+        assert_eq!(worker.profile_task("profiled"), profile_id);
+        let options = StartWorkflowOptions { workflow_id: Some(WorkflowId::new("profiled")), ..Default::default() };
+        let handle: WorkflowHandle<i64> = worker.client().start_workflow::<DoubleThenFlaky>(20, options).await.unwrap();
+        assert_eq!(handle.result().await.unwrap(), 41);
+
+        wait_until(|| !matches!(worker.task_profile(&profile_id), Some(TaskProfile::Pending))).await;
+        let Some(TaskProfile::Ready(body)) = worker.task_profile(&profile_id) else {
+            panic!("task profile failed: {:?}", worker.task_profile(&profile_id));
+        };
+        assert!(pprof::protos::Profile::parse_from_bytes(&body).is_ok());
+        assert!(matches!(worker.task_profile(&untouched), Some(TaskProfile::Pending)));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_fails_attempts_fast() {
+        let breaker = Arc::new(CircuitBreaker::new(
+            CircuitBreakerConfig::default().minimum_calls(1).cool_down(Duration::from_secs(3600)),
+        ));
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                ..Default::default()
+            })
+            .with_circuit_breaker(breaker.clone()),
+        );
+        worker.register_workflow::<DoubleThenFlaky>();
+        worker.register_activity::<Double>();
+        worker.register_activity::<Flaky>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let handle: WorkflowHandle<i64> = worker
+            .client()
+            .start_workflow::<DoubleThenFlaky>(20, StartWorkflowOptions::default())
+            .await
+            .unwrap();
+
+        // Flaky's first failure opens its circuit, so the retries never reach it
+        let error = handle.result().await.unwrap_err();
+        assert!(error.to_string().contains("circuit open for activity flaky"), "{}", error);
+        assert_eq!(breaker.state("flaky"), CircuitState::Open);
+        assert_eq!(breaker.state("double"), CircuitState::Closed);
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unregistered_workflow_fails() {
+        struct Unknown;
+
+        impl Workflow for Unknown {
+            type Input = ();
+            type Output = ();
+
+            fn name() -> &'static str {
+                "unknown"
+            }
+
+            async fn execute(_ctx: WorkflowContext, _input: ()) -> Result<(), WorkflowError> {
+                Ok(())
+            }
+        }
+
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let handle = worker.client().start_workflow::<Unknown>((), StartWorkflowOptions::default()).await.unwrap();
+        let error = handle.result().await.unwrap_err();
+        assert!(error.to_string().contains("workflow type not registered"));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_activity_concurrency_is_bounded() {
+        let (worker, run) = spawn_worker(WorkerConfig {
+            max_concurrent_activity_tasks: 2,
+            ..Default::default()
+        });
+        let client = worker.client();
+        let mut handles = Vec::new();
+        for _ in 0..6 {
+            handles.push(client.start_workflow::<SlowWorkflow>((), StartWorkflowOptions::default()).await.unwrap());
+        }
+        for handle in handles {
+            handle.result().await.unwrap();
+        }
+        assert_eq!(PEAK.load(Ordering::SeqCst), 2);
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_signals_reach_waiting_workflow_in_order() {
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let client = worker.client();
+        let handle = client.start_workflow::<Approval>(5_000, StartWorkflowOptions::default()).await.unwrap();
+        let workflow_id = handle.execution().workflow_id.clone();
+
+        // Sent before the worker necessarily picked up the execution
+        client.signal_workflow(&workflow_id, Approve { by: "alice".to_string() }).await.unwrap();
+        client.signal_workflow(&workflow_id, Approve { by: "bob".to_string() }).await.unwrap();
+        assert_eq!(handle.result().await.unwrap(), vec!["alice", "bob"]);
+
+        let signaled = handle
+            .history()
+            .await
+            .unwrap()
+            .events()
+            .iter()
+            .filter(|e| matches!(e.event_type, EventType::WorkflowExecutionSignaled { .. }))
+            .count();
+        assert_eq!(signaled, 2);
+        let closed = client.signal_workflow(&workflow_id, Approve { by: "carol".to_string() }).await;
+        assert!(matches!(closed, Err(crate::temporal::error::SignalError::WorkflowClosed(_))));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_query_answers_from_running_execution() {
+        use crate::temporal::error::QueryError;
+
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let client = worker.client();
+        let handle = client.start_workflow::<Tally>((), StartWorkflowOptions::default()).await.unwrap();
+        let workflow_id = handle.execution().workflow_id.clone();
+        client.signal_workflow(&workflow_id, Approve { by: "alice".to_string() }).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while client.query_workflow::<Approvers>(&workflow_id).await.ok() != Some(vec!["alice".to_string()]) {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("query not answered in time");
+        let unknown = client.query_workflow_value(&workflow_id, "missing").await;
+        assert!(matches!(unknown, Err(QueryError::QueryNotRegistered(_))));
+
+        client.signal_workflow(&workflow_id, Approve { by: "bob".to_string() }).await.unwrap();
+        assert_eq!(handle.result().await.unwrap(), vec!["alice", "bob"]);
+        let closed = client.query_workflow::<Approvers>(&workflow_id).await;
+        assert!(matches!(closed, Err(QueryError::WorkflowNotRunning)));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_signal_times_out() {
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let handle = worker.client().start_workflow::<Approval>(20, StartWorkflowOptions::default()).await.unwrap();
+        let error = handle.result().await.unwrap_err();
+        assert!(error.to_string().contains("signal approve"));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_missed_heartbeat_fails_attempt() {
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let handle = worker
+            .client()
+            .start_workflow::<Impatient>("stalls".to_string(), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        let error = handle.result().await.unwrap_err().to_string();
+        assert!(error.contains("Heartbeat failed"), "{}", error);
+        assert!(error.contains("step 1"), "{}", error);
+
+        worker.shutdown(Duration::ZERO);
+        tokio::time::timeout(Duration::from_secs(1), run).await.unwrap().unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_start_to_close_timeout_cancels_activity() {
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let handle = worker
+            .client()
+            .start_workflow::<Impatient>("until_cancelled".to_string(), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        let error = handle.result().await.unwrap_err().to_string();
+        assert!(error.contains("Activity timeout"), "{}", error);
+
+        // The worker waits for in-flight activities, which only stop once they see the cancellation
+        worker.shutdown(Duration::ZERO);
+        tokio::time::timeout(Duration::from_secs(1), run).await.unwrap().unwrap().unwrap();
+        assert!(OBSERVED_CANCEL.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_execution_runs_non_cancellable_cleanup() {
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let handle = worker
+            .client()
+            .start_workflow::<CleansUp>(21, StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        let workflow_id = handle.execution().workflow_id.clone();
+        wait_until(|| worker.shared.pending.waiting.lock().keys().any(|(run_id, _)| *run_id == handle.execution().run_id)).await;
+        assert!(worker.shared.cancel_execution(&workflow_id));
+
+        let error = handle.result().await.unwrap_err().to_string();
+        assert!(error.contains("Workflow cancelled"), "{}", error);
+        let history = handle.history().await.unwrap();
+        assert!(history.events().iter().any(|e| matches!(
+            &e.event_type,
+            EventType::ActivityTaskCompleted { result, .. } if *result == serde_json::json!(42)
+        )));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_crashing_activity_is_dead_lettered_and_discarded() {
+        let (worker, run) = spawn_worker(WorkerConfig::builder().max_task_failures(2).build());
+        let client = worker.client();
+        let handle = client.start_workflow::<Poisoned>(false, StartWorkflowOptions::default()).await.unwrap();
+        wait_until(|| !client.dead_letters().is_empty()).await;
+
+        let letter = client.dead_letters().remove(0);
+        assert_eq!(letter.failures, 2);
+        assert!(matches!(letter.task, Task::Activity(ref t) if t.attempt == 2));
+        client.discard_dead_letter(&letter.id, "bad payload").await.unwrap();
+
+        let error = handle.result().await.unwrap_err().to_string();
+        assert!(error.contains("Discarded from dead-letter queue: bad payload"), "{}", error);
+        assert!(client.dead_letters().is_empty());
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_crashing_workflow_task_is_retried_then_dead_lettered() {
+        let (worker, run) = spawn_worker(WorkerConfig::builder().max_task_failures(2).build());
+        let client = worker.client();
+        let handle = client.start_workflow::<Poisoned>(true, StartWorkflowOptions::default()).await.unwrap();
+        wait_until(|| !client.dead_letters().is_empty()).await;
+        assert_eq!(POISONED_RUNS.load(Ordering::SeqCst), 2);
+        assert!(handle.history().await.unwrap().outcome().is_none());
+
+        let letter = client.dead_letters().remove(0);
+        client.retry_dead_letter(&letter.id).await.unwrap();
+        wait_until(|| !client.dead_letters().is_empty()).await;
+        assert_eq!(POISONED_RUNS.load(Ordering::SeqCst), 4);
+
+        let letter = client.dead_letters().remove(0);
+        client.discard_dead_letter(&letter.id, "known bug").await.unwrap();
+        let error = handle.result().await.unwrap_err().to_string();
+        assert!(error.contains("discarded from dead-letter queue: known bug"), "{}", error);
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_schedule_fires_until_paused() {
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let client = worker.client();
+        let schedule_id = WorkflowId::new("every-second");
+        let options = StartWorkflowOptions {
+            workflow_id: Some(schedule_id.clone()),
+            cron_schedule: Some("* * * * * *".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            client.start_workflow::<Tick>((), options.clone()).await,
+            Err(WorkflowError::InvalidInput(_))
+        ));
+        client.schedule_workflow::<Tick>((), options).await.unwrap();
+
+        wait_until(|| TICKS.load(Ordering::SeqCst) >= 2).await;
+        client.pause_schedule(&schedule_id).unwrap();
+        let paused_at = TICKS.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(1_300)).await;
+        assert_eq!(TICKS.load(Ordering::SeqCst), paused_at);
+        assert!(client.describe_schedule(&schedule_id).unwrap().paused);
+        client.delete_schedule(&schedule_id).unwrap();
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_schedule_cancels_previous_run() {
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let client = worker.client();
+        let schedule_id = WorkflowId::new("replace-sleeper");
+        let options = StartWorkflowOptions {
+            workflow_id: Some(schedule_id.clone()),
+            cron_schedule: Some("* * * * * *".to_string()),
+            overlap_policy: crate::temporal::ScheduleOverlapPolicy::CancelPrevious,
+            ..Default::default()
+        };
+        client.schedule_workflow::<Sleeper>((), options).await.unwrap();
+
+        wait_until(|| SLEEPER_STARTS.load(Ordering::SeqCst) >= 2).await;
+        client.delete_schedule(&schedule_id).unwrap();
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_continue_as_new_chains_runs() {
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let handle = worker
+            .client()
+            .start_workflow::<Countdown>((3, 0), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(handle.result().await.unwrap(), 4);
+        let latest = handle.latest_run().await.unwrap();
+        assert_eq!(latest.workflow_id, handle.execution().workflow_id);
+        assert_ne!(latest.run_id, handle.execution().run_id);
+        // Each run starts with a fresh history
+        assert_eq!(handle.history().await.unwrap().len(), 4);
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_history_cache_drops_finished_runs() {
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let client = worker.client();
+        let finished = client.start_workflow::<DoubleThenFlaky>(1, StartWorkflowOptions::default()).await.unwrap();
+        finished.result().await.unwrap();
+        let waiting = client.start_workflow::<Approval>(5_000, StartWorkflowOptions::default()).await.unwrap();
+        let workflow_id = waiting.execution().workflow_id.clone();
+
+        let histories = &worker.shared.histories;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while worker.cached_executions() == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(histories.get(&finished.execution().run_id).is_none());
+        assert!(histories.get(&waiting.execution().run_id).is_some());
+
+        for by in ["alice", "bob"] {
+            client.signal_workflow(&workflow_id, Approve { by: by.to_string() }).await.unwrap();
+        }
+        waiting.result().await.unwrap();
+        // The outcome is stored before the worker lets go of the run
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while worker.cached_executions() > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(histories.get(&waiting.execution().run_id).is_none());
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_paused_dispatch_holds_new_workflows_back() {
+        let (worker, run) = spawn_worker(WorkerConfig::default());
+        let client = worker.client();
+        let running = client.start_workflow::<Approval>(5_000, StartWorkflowOptions::default()).await.unwrap();
+        let workflow_id = running.execution().workflow_id.clone();
+        client.signal_workflow(&workflow_id, Approve { by: "alice".to_string() }).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while worker.cached_executions() == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        worker.pause_dispatch(true);
+        assert!(worker.is_dispatch_paused());
+        let held = client.start_workflow::<DoubleThenFlaky>(1, StartWorkflowOptions::default()).await.unwrap();
+        // The running workflow still takes its signal and finishes
+        client.signal_workflow(&workflow_id, Approve { by: "bob".to_string() }).await.unwrap();
+        assert_eq!(running.result().await.unwrap(), vec!["alice", "bob"]);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let queued = worker.shared.task_queue.len(&worker.config.task_queue, TaskKind::Workflow).await.unwrap();
+        assert_eq!(queued, 1);
+        assert!(held.try_result().await.unwrap().is_none());
+
+        worker.pause_dispatch(false);
+        held.result().await.unwrap();
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    static HANDED_OVER: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+    /// Stalls on its first attempt, then finishes once handed to another worker
+    struct StallsOnce;
+
+    impl Activity for StallsOnce {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "stalls_once"
+        }
+
+        async fn execute(_ctx: ActivityContext, _input: ()) -> Result<(), ActivityError> {
+            if !HANDED_OVER.swap(true, Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+            Ok(())
+        }
+    }
+
+    struct HandedOver;
+
+    impl Workflow for HandedOver {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "handed_over"
+        }
+
+        async fn execute(ctx: WorkflowContext, _input: ()) -> Result<(), WorkflowError> {
+            ctx.execute_activity::<StallsOnce>((), ActivityOptions::default()).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_hands_unfinished_tasks_to_another_worker() {
+        let task_queue: Arc<dyn TaskQueue> = Arc::new(InMemoryTaskQueue::new());
+        let storage: Arc<dyn WorkflowStorage> = Arc::new(InMemoryStorage::new());
+        let worker = || {
+            let worker = Arc::new(
+                WorkflowWorker::new(WorkerConfig {
+                    poll_timeout: Duration::from_millis(50),
+                    ..Default::default()
+                })
+                .with_task_queue(task_queue.clone())
+                .with_storage(storage.clone()),
+            );
+            worker.register_workflow::<HandedOver>();
+            worker.register_activity::<StallsOnce>();
+            let running = worker.clone();
+            (worker, tokio::spawn(async move { running.run().await }))
+        };
+
+        let (first, run) = worker();
+        let handle: WorkflowHandle<()> = first
+            .client()
+            .start_workflow::<HandedOver>((), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        wait_until(|| HANDED_OVER.load(Ordering::SeqCst)).await;
+
+        first.shutdown(Duration::from_millis(50));
+        let report = run.await.unwrap().unwrap();
+        assert_eq!(report.workflows.len(), 1);
+        assert_eq!(report.activities.len(), 1);
+        assert!(report.signals.is_empty());
+
+        let (second, run) = worker();
+        tokio::time::timeout(Duration::from_secs(5), handle.result()).await.unwrap().unwrap();
+        second.shutdown(Duration::ZERO);
+        assert!(run.await.unwrap().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_signals_polled_by_another_worker_reach_the_running_one() {
+        let task_queue: Arc<dyn TaskQueue> = Arc::new(InMemoryTaskQueue::new());
+        let storage: Arc<dyn WorkflowStorage> = Arc::new(InMemoryStorage::new());
+        let worker = || {
+            let worker = Arc::new(
+                WorkflowWorker::new(WorkerConfig {
+                    poll_timeout: Duration::from_millis(50),
+                    ..Default::default()
+                })
+                .with_task_queue(task_queue.clone())
+                .with_storage(storage.clone()),
+            );
+            worker.register_workflow::<Approval>();
+            let running = worker.clone();
+            (worker, tokio::spawn(async move { running.run().await }))
+        };
+        let signal = |execution: &WorkflowExecution, by: &str| {
+            Task::Signal(SignalTask {
+                execution: execution.clone(),
+                signal_name: "approve".to_string(),
+                input: serde_json::json!({ "by": by }),
+                trace_context: Default::default(),
+            })
+        };
+
+        let (owner, owner_run) = worker();
+        let handle = owner.client().start_workflow::<Approval>(5_000, StartWorkflowOptions::default()).await.unwrap();
+        let execution = handle.execution().clone();
+        wait_until(|| owner.shared.executions.lock().running.contains_key(&execution.workflow_id)).await;
+        let (other, other_run) = worker();
+        let queue = other.config.task_queue.clone();
+
+        // The other worker polled the signals, but the owner runs the execution
+        other.shared.handle(&queue, signal(&execution, "alice")).await;
+        other.shared.handle(&queue, signal(&execution, "bob")).await;
+        let approvers = tokio::time::timeout(Duration::from_secs(5), handle.result()).await.unwrap().unwrap();
+        assert_eq!(approvers, vec!["alice", "bob"]);
+
+        // Signals for a closed run are dropped instead of circling
+        other.shared.handle(&queue, signal(&execution, "carol")).await;
+        assert_eq!(task_queue.len(&queue, TaskKind::Signal).await.unwrap(), 0);
+
+        for (worker, run) in [(owner, owner_run), (other, other_run)] {
+            worker.shutdown(Duration::ZERO);
+            run.await.unwrap().unwrap();
+        }
+    }
+
+    static CHARGED: AtomicUsize = AtomicUsize::new(0);
+    static SHIPPED: AtomicUsize = AtomicUsize::new(0);
+
+    struct ChargeOrder;
+
+    impl Activity for ChargeOrder {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "charge_order"
+        }
+
+        async fn execute(_ctx: ActivityContext, _input: ()) -> Result<(), ActivityError> {
+            CHARGED.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    struct ShipOrder;
+
+    impl Activity for ShipOrder {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "ship_order"
+        }
+
+        async fn execute(_ctx: ActivityContext, _input: ()) -> Result<(), ActivityError> {
+            SHIPPED.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Charges, waits a moment and for two approvals, then ships
+    struct ApprovedShipment;
+
+    impl Workflow for ApprovedShipment {
+        type Input = ();
+        type Output = Vec<String>;
+
+        fn name() -> &'static str {
+            "approved_shipment"
+        }
+
+        async fn execute(ctx: WorkflowContext, _input: ()) -> Result<Vec<String>, WorkflowError> {
+            ctx.execute_activity::<ChargeOrder>((), ActivityOptions::default()).await?;
+            ctx.sleep(Duration::from_millis(10)).await;
+            let first = ctx.wait_for_signal::<Approve>(None).await?;
+            let second = ctx.wait_for_signal::<Approve>(None).await?;
+            ctx.execute_activity::<ShipOrder>((), ActivityOptions::default()).await?;
+            Ok(vec![first.by, second.by])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handed_back_runs_resume_without_repeating_work() {
+        let task_queue: Arc<dyn TaskQueue> = Arc::new(InMemoryTaskQueue::new());
+        let storage: Arc<dyn WorkflowStorage> = Arc::new(InMemoryStorage::new());
+        let worker = || {
+            let worker = Arc::new(
+                WorkflowWorker::new(WorkerConfig {
+                    poll_timeout: Duration::from_millis(50),
+                    ..Default::default()
+                })
+                .with_task_queue(task_queue.clone())
+                .with_storage(storage.clone()),
+            );
+            worker.register_workflow::<ApprovedShipment>();
+            worker.register_activity::<ChargeOrder>();
+            worker.register_activity::<ShipOrder>();
+            let running = worker.clone();
+            (worker, tokio::spawn(async move { running.run().await }))
+        };
+        let signaled = |history: &EventHistory| {
+            history
+                .events()
+                .iter()
+                .filter(|e| matches!(e.event_type, EventType::WorkflowExecutionSignaled { .. }))
+                .count()
+        };
+
+        let (first, run) = worker();
+        let client = first.client();
+        let handle = client
+            .start_workflow::<ApprovedShipment>((), StartWorkflowOptions::default())
+            .await
+            .unwrap();
+        let workflow_id = handle.execution().workflow_id.clone();
+        wait_until(|| CHARGED.load(Ordering::SeqCst) == 1).await;
+        client.signal_workflow(&workflow_id, Approve { by: "ops".to_string() }).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while signaled(&handle.history().await.unwrap()) == 0 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+        first.shutdown(Duration::ZERO);
+        assert_eq!(run.await.unwrap().unwrap().workflows.len(), 1);
+
+        // The second worker takes the recorded approval in again and waits for the other one
+        let (second, run) = worker();
+        let client = second.client();
+        client.signal_workflow(&workflow_id, Approve { by: "finance".to_string() }).await.unwrap();
+        let approvers = tokio::time::timeout(Duration::from_secs(5), handle.result()).await.unwrap().unwrap();
+        assert_eq!(approvers, vec!["ops", "finance"]);
+        assert_eq!((CHARGED.load(Ordering::SeqCst), SHIPPED.load(Ordering::SeqCst)), (1, 1));
+
+        let history = handle.history().await.unwrap();
+        let count = |matches: fn(&EventType) -> bool| history.events().iter().filter(|e| matches(&e.event_type)).count();
+        assert_eq!(count(|e| matches!(e, EventType::ActivityTaskScheduled { .. })), 2);
+        assert_eq!(count(|e| matches!(e, EventType::ActivityTaskCompleted { .. })), 2);
+        assert_eq!(count(|e| matches!(e, EventType::TimerFired { .. })), 1);
+        assert_eq!(signaled(&history), 2);
+        second.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_before_run_returns() {
+        let worker = WorkflowWorker::default();
+        worker.shutdown(Duration::ZERO);
+        tokio::time::timeout(Duration::from_secs(1), worker.run()).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_janitor_deletes_expired_runs() {
+        let retention = crate::temporal::retention::RetentionPolicies::new()
+            .default_policy(crate::temporal::retention::RetentionPolicy::new().keep_completed(Duration::ZERO))
+            .every(Duration::from_millis(20));
+        let worker = Arc::new(
+            WorkflowWorker::new(WorkerConfig {
+                poll_timeout: Duration::from_millis(50),
+                ..WorkerConfig::default()
+            })
+            .with_retention(retention),
+        );
+        worker.register_workflow::<Countdown>();
+        worker.register_workflow::<Tally>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+        let client = worker.client();
+        let done = client.start_workflow::<Countdown>((0, 0), StartWorkflowOptions::default()).await.unwrap();
+        let open = client.start_workflow::<Tally>((), StartWorkflowOptions::default()).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while client.load_workflow(&done.execution().workflow_id).await.unwrap().is_some() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("completed run not reclaimed");
+        assert!(client.load_workflow(&open.execution().workflow_id).await.unwrap().is_some());
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+
+    /// Records side effects forever
+    struct Runaway;
+
+    impl Workflow for Runaway {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "runaway"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<(), WorkflowError> {
+            loop {
+                ctx.side_effect(|| 1).await?;
+                tokio::task::yield_now().await;
+            }
+        }
+    }
+
+    /// Records side effects until its history grows too long, then continues as new `runs` times
+    struct Rolling;
+
+    impl Workflow for Rolling {
+        type Input = u32;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "rolling"
+        }
+
+        async fn execute(ctx: WorkflowContext, runs: u32) -> Result<u32, WorkflowError> {
+            while !ctx.continue_as_new_suggested() {
+                ctx.side_effect(|| 1).await?;
+            }
+            match runs {
+                0 => Ok(0),
+                _ => ctx.continue_as_new(runs - 1),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history_limits_suggest_continue_as_new_and_terminate() {
+        let limits = HistoryLimits {
+            soft_events: 5,
+            hard_events: 20,
+            ..HistoryLimits::default()
+        };
+        let worker = Arc::new(WorkflowWorker::new(
+            WorkerConfig::builder().poll_timeout(Duration::from_millis(50)).history_limits(limits).build(),
+        ));
+        worker.register_workflow::<Runaway>();
+        worker.register_workflow::<Rolling>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let rolling = worker.client().start_workflow::<Rolling>(2, StartWorkflowOptions::default()).await.unwrap();
+        assert_eq!(rolling.result().await.unwrap(), 0);
+
+        let runaway = worker.client().start_workflow::<Runaway>((), StartWorkflowOptions::default()).await.unwrap();
+        let failure = runaway.result().await.unwrap_err().to_string();
+        assert!(failure.contains("history has 21 events, over the hard limit of 20"), "{}", failure);
+        let history = runaway.history().await.unwrap();
+        assert!(history.is_terminated());
+        assert_eq!(history.len(), 22);
+
+        let bytes = HistoryLimits {
+            hard_bytes: history.size_bytes() / 2,
+            ..HistoryLimits::unlimited()
+        };
+        assert!(bytes.hard_exceeded(history.len(), history.size_bytes()).is_some_and(|(limit, _)| limit == "bytes"));
+
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+    }
+}
@@ -1,16 +1,54 @@
 //! Workflow definitions and execution context
 
 use std::future::Future;
+use std::ops::Deref;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use futures::FutureExt;
 use serde::{Serialize, de::DeserializeOwned};
-use super::{WorkflowExecution, WorkflowError, ActivityOptions, Activity, ActivityError};
+use tokio_util::sync::CancellationToken;
+use super::{
+    ActivityContext, ActivityError, ActivityId, ActivityOptions, Activity, Signal, WorkflowExecution, WorkflowError,
+    WorkflowInfo,
+};
+use super::activity::RetryPolicy;
+use super::admission::AdmissionControl;
+use super::client::WorkflowClient;
+use super::clock::VirtualClock;
+use super::converter::{self, DataConverter};
+use super::error::{QueryError, UpdateError};
+use super::event::{EventHistory, EventType, HistoryLimits};
+#[cfg(feature = "persistence")]
+use super::memo::ActivityMemo;
+use super::memo::MemoKey;
+use super::outbox::OutboxMessage;
+use super::query::{Query, QueryHandler, QueryHandlers};
+use super::replay::{recorded_failure, Replay};
+use super::saga::Saga;
+use super::search::SearchAttributes;
+use super::session::SessionHost;
+use super::signal::{SignalHandler, SignalMailbox, CANCEL_REQUEST_SIGNAL, TERMINATE_SIGNAL};
+use super::storage::WorkflowStorage;
+use super::task_queue::{ActivityTask, Priority, Task, TaskQueue};
+use super::telemetry;
+use super::update::{Update, UpdateHandler, UpdateHandlers, UpdateValidator};
+use super::worker::PendingActivities;
+use super::RunId;
+use crate::dsl::WorkflowSpec;
+
+/// Version returned by [`WorkflowContext::get_version`] for histories recorded before a change existed
+pub const DEFAULT_VERSION: i32 = -1;
 
 /// Workflow trait - defines the workflow interface
 pub trait Workflow: Send + Sync + 'static {
     /// Input type
-    type Input: DeserializeOwned + Send + 'static;
+    type Input: Serialize + DeserializeOwned + Send + 'static;
     
     /// Output type
-    type Output: Serialize + Send + 'static;
+    type Output: Serialize + DeserializeOwned + Send + 'static;
     
     /// Workflow name
     fn name() -> &'static str;
@@ -20,53 +58,1310 @@ pub trait Workflow: Send + Sync + 'static {
         ctx: WorkflowContext,
         input: Self::Input,
     ) -> impl Future<Output = Result<Self::Output, WorkflowError>> + Send;
+
+    /// Outline of the steps, drawn by diagrams; never executed
+    ///
+    /// The code of `execute` cannot be inspected, so a typed workflow only has a diagram if it describes itself here.
+    fn outline() -> Option<WorkflowSpec> {
+        None
+    }
+}
+
+/// Per-execution state shared by all clones of a worker-attached context
+pub(crate) struct ExecutionRuntime {
+    pub(crate) info: WorkflowInfo,
+    pub(crate) history: tokio::sync::Mutex<EventHistory>,
+    pub(crate) storage: Arc<dyn WorkflowStorage>,
+    pub(crate) task_queue: Arc<dyn TaskQueue>,
+    pub(crate) activities: Arc<PendingActivities>,
+    /// Root cancellation scope of the execution
+    pub(crate) cancellation: CancellationToken,
+    /// Reason the execution was terminated, set along with `terminated`
+    termination: parking_lot::Mutex<Option<String>>,
+    terminated: CancellationToken,
+    signals: SignalMailbox,
+    pub(crate) queries: QueryHandlers,
+    pub(crate) updates: UpdateHandlers,
+    sequence: AtomicU64,
+    /// Set when the execution replays a recorded history instead of running on a worker
+    replay: Option<Arc<Replay>>,
+    /// Clock of the workflow's timers; the Tokio clock if unset
+    clock: Option<Arc<VirtualClock>>,
+    /// Encoding of activity and signal payloads
+    converter: Arc<dyn DataConverter>,
+    /// Limits on the activity attempts in flight
+    admission: Option<Arc<AdmissionControl>>,
+    /// Host of the activity sessions the execution creates; `None` if the worker hosts none
+    pub(crate) sessions: Option<Arc<SessionHost>>,
+    /// Store of memoized activity results; `None` if the worker keeps none
+    #[cfg(feature = "persistence")]
+    memo: Option<Arc<ActivityMemo>>,
+    history_limits: HistoryLimits,
+    /// Priority of the run, the default one of its activities
+    priority: Priority,
+    /// Encoded size of the history, kept up to date as events are recorded
+    history_bytes: AtomicUsize,
+    /// Set once the history went over a soft limit
+    history_warned: AtomicBool,
+}
+
+impl ExecutionRuntime {
+    pub(crate) fn new(
+        info: WorkflowInfo,
+        history: EventHistory,
+        storage: Arc<dyn WorkflowStorage>,
+        task_queue: Arc<dyn TaskQueue>,
+        activities: Arc<PendingActivities>,
+    ) -> Self {
+        let accepted = history
+            .events()
+            .iter()
+            .filter(|event| matches!(event.event_type, EventType::WorkflowExecutionUpdateAccepted { .. }))
+            .count();
+        Self {
+            info,
+            history_bytes: AtomicUsize::new(history.size_bytes()),
+            history: tokio::sync::Mutex::new(history),
+            storage,
+            task_queue,
+            activities,
+            cancellation: CancellationToken::new(),
+            termination: parking_lot::Mutex::new(None),
+            terminated: CancellationToken::new(),
+            signals: SignalMailbox::default(),
+            queries: QueryHandlers::default(),
+            updates: UpdateHandlers::after(accepted as u64),
+            sequence: AtomicU64::new(0),
+            replay: None,
+            clock: None,
+            converter: converter::default_converter(),
+            admission: None,
+            sessions: None,
+            #[cfg(feature = "persistence")]
+            memo: None,
+            history_limits: HistoryLimits::default(),
+            priority: Priority::DEFAULT,
+            history_warned: AtomicBool::new(false),
+        }
+    }
+
+    /// Warn past the soft and terminate past the hard `limits` on the history's size
+    ///
+    /// A history already over them when the execution resumes is checked right away.
+    pub(crate) fn with_history_limits(mut self, limits: HistoryLimits) -> Self {
+        self.history_limits = limits;
+        let events = self.history.get_mut().len();
+        self.check_history_size(events);
+        self
+    }
+
+    /// Encode and decode activity and signal payloads with `converter`
+    pub(crate) fn with_converter(mut self, converter: Arc<dyn DataConverter>) -> Self {
+        self.converter = converter;
+        self
+    }
+
+    /// Admit activity attempts through `admission` before scheduling them
+    pub(crate) fn with_admission(mut self, admission: Option<Arc<AdmissionControl>>) -> Self {
+        self.admission = admission;
+        self
+    }
+
+    /// Open the execution's activity sessions on `sessions`
+    pub(crate) fn with_sessions(mut self, sessions: Option<Arc<SessionHost>>) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
+    /// Keep memoized activity results in `memo`
+    #[cfg(feature = "persistence")]
+    pub(crate) fn with_memo(mut self, memo: Option<Arc<ActivityMemo>>) -> Self {
+        self.memo = memo;
+        self
+    }
+
+    /// Result memoized for `key`, if any
+    #[cfg(feature = "persistence")]
+    async fn memoized(&self, key: &MemoKey) -> Option<serde_json::Value> {
+        self.memo.as_ref()?.get(key).await
+    }
+
+    #[cfg(not(feature = "persistence"))]
+    async fn memoized(&self, _key: &MemoKey) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// Keep `result` in the memo store, encoded like the history that records its reuse
+    #[cfg(feature = "persistence")]
+    async fn memoize(&self, key: &MemoKey, result: &serde_json::Value, ttl: Duration) {
+        let Some(memo) = &self.memo else {
+            return;
+        };
+        match self.encode(result.clone()) {
+            Ok(encoded) => memo.put(key, &encoded, ttl).await,
+            Err(e) => {
+                tracing::warn!(activity_type = %key.activity_type, error = %e, "failed to encode memoized result")
+            }
+        }
+    }
+
+    #[cfg(not(feature = "persistence"))]
+    async fn memoize(&self, _key: &MemoKey, _result: &serde_json::Value, _ttl: Duration) {}
+
+    /// Queue the run's activities with `priority` unless they set their own
+    pub(crate) fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Run the workflow's timers on `clock` instead of the Tokio clock
+    pub(crate) fn with_clock(mut self, clock: Option<Arc<VirtualClock>>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Check recorded events against `replay` instead of appending them
+    ///
+    /// A [resuming](Replay::resuming) replay appends the events its history lacks.
+    pub(crate) fn replaying(mut self, replay: Arc<Replay>) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    /// Whether the execution only checks its history, without reaching anything outside it
+    pub(crate) fn is_replaying(&self) -> bool {
+        self.replay.as_ref().is_some_and(|replay| !replay.is_resuming())
+    }
+
+    /// Whether the code runs over a recorded history, replaying it or resuming from it, so
+    /// activity attempts that already finished return their recorded outcome
+    pub(crate) fn has_recorded_history(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Whether the code runs on from the history of a run taken over by this worker
+    pub(crate) fn is_resuming(&self) -> bool {
+        self.replay.as_ref().is_some_and(|replay| replay.is_resuming())
+    }
+
+    /// Client reaching the workflows of the execution's storage and task queues, encoding like the execution
+    pub(crate) fn client(&self) -> WorkflowClient {
+        WorkflowClient::new(self.task_queue.clone(), self.storage.clone()).with_data_converter(self.converter.clone())
+    }
+
+    /// Encode a payload recorded in the history
+    pub(crate) fn encode(&self, value: serde_json::Value) -> Result<serde_json::Value, WorkflowError> {
+        converter::encode(&*self.converter, value)
+    }
+
+    /// Decode a payload recorded in the history
+    pub(crate) fn decode(&self, value: serde_json::Value) -> Result<serde_json::Value, WorkflowError> {
+        converter::decode(&*self.converter, value)
+    }
+
+    /// Wait until the execution is terminated, returning the reason
+    pub(crate) async fn terminated(&self) -> String {
+        self.terminated.cancelled().await;
+        self.termination.lock().clone().unwrap_or_default()
+    }
+
+    /// Next per-execution sequence number, used for activity and timer IDs
+    pub(crate) fn next_sequence(&self) -> u64 {
+        self.sequence.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Append an event to the history and persist it
+    ///
+    /// While replaying, fails if the event differs from the one recorded at this point.
+    pub(crate) async fn record(&self, event_type: EventType) -> Result<(), WorkflowError> {
+        self.record_with_outbox(event_type, &[]).await
+    }
+
+    /// Record an event, saving it together with [outbox](super::outbox) messages
+    pub(crate) async fn record_with_outbox(
+        &self,
+        event_type: EventType,
+        outbox: &[OutboxMessage],
+    ) -> Result<(), WorkflowError> {
+        if let Some(replay) = &self.replay
+            && replay.produced(&event_type)?
+        {
+            self.deliver_replayed_inputs();
+            return Ok(());
+        }
+        let mut history = self.history.lock().await;
+        history.append(event_type);
+        let appended = history.events().last().map_or(0, |e| e.size_bytes());
+        self.history_bytes.fetch_add(appended, Ordering::SeqCst);
+        self.check_history_size(history.len());
+        if outbox.is_empty() {
+            self.storage
+                .save_workflow_execution(&self.info.workflow_execution, &history)
+                .await?;
+        } else {
+            self.storage
+                .save_with_outbox(&self.info.workflow_execution, &history, outbox)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Whether the history has gone over a soft size limit
+    pub(crate) fn history_over_soft_limit(&self) -> bool {
+        self.history_warned.load(Ordering::SeqCst)
+    }
+
+    /// Warn once the history of `events` events goes over a soft limit, and terminate the execution
+    /// once it goes over a hard one
+    fn check_history_size(&self, events: usize) {
+        let bytes = self.history_bytes.load(Ordering::SeqCst);
+        let execution = &self.info.workflow_execution;
+        let workflow_type = &self.info.workflow_type;
+        if let Some((limit, reason)) = self.history_limits.hard_exceeded(events, bytes) {
+            if self.terminated.is_cancelled() {
+                return;
+            }
+            tracing::error!(execution = %execution, workflow_type = %workflow_type, "{}, terminating", reason);
+            metrics::counter!(
+                super::metrics::HISTORY_LIMIT_EXCEEDED,
+                "workflow_type" => workflow_type.clone(),
+                "limit" => limit,
+                "severity" => "hard"
+            )
+            .increment(1);
+            self.termination.lock().get_or_insert(reason);
+            self.terminated.cancel();
+        }
+        if let Some((limit, reason)) = self.history_limits.soft_exceeded(events, bytes)
+            && !self.history_warned.swap(true, Ordering::SeqCst)
+        {
+            tracing::warn!(execution = %execution, workflow_type = %workflow_type, "{}, continue as new", reason);
+            metrics::counter!(
+                super::metrics::HISTORY_LIMIT_EXCEEDED,
+                "workflow_type" => workflow_type.clone(),
+                "limit" => limit,
+                "severity" => "soft"
+            )
+            .increment(1);
+        }
+    }
+
+    /// Hand the signals, updates and cancellation requests recorded after the events replayed so far to the workflow
+    pub(crate) fn deliver_replayed_inputs(&self) {
+        let Some(replay) = &self.replay else {
+            return;
+        };
+        for input in replay.take_inputs() {
+            match input {
+                EventType::WorkflowExecutionSignaled { signal_name, input } => {
+                    match converter::decode(&*self.converter, input) {
+                        Ok(input) => self.signals.deliver(&signal_name, input),
+                        Err(e) => tracing::error!(signal = %signal_name, error = %e, "failed to decode replayed signal"),
+                    }
+                }
+                EventType::WorkflowExecutionUpdateAccepted { update_name, input, .. } => {
+                    let applied = converter::decode(&*self.converter, input)
+                        .map_err(|e| UpdateError::SerializationError(e.to_string()))
+                        .and_then(|input| match self.updates.handler(&update_name) {
+                            Ok((_, handler)) => handler(input).map(drop),
+                            // Applied once the code registers the handler, as it had when the update arrived
+                            Err(_) => {
+                                self.updates.defer(&update_name, input);
+                                Ok(())
+                            }
+                        });
+                    if let Err(e) = applied {
+                        tracing::error!(update = %update_name, error = %e, "failed to apply replayed update");
+                    }
+                    self.signals.wake();
+                }
+                // The result went to the caller when the update was first applied
+                EventType::WorkflowExecutionUpdateCompleted { .. } => {}
+                _ => self.cancellation.cancel(),
+            }
+        }
+    }
+
+    /// Validate an update, then record it, apply it with its handler and record the result
+    ///
+    /// Rejected updates leave no trace in the history. `args` is the encoded payload, as kept in the history.
+    pub(crate) async fn update(
+        &self,
+        update_name: &str,
+        args: serde_json::Value,
+    ) -> Result<serde_json::Value, UpdateError> {
+        let _applying = self.updates.applying.lock().await;
+        let (validator, handler) = self.updates.handler(update_name)?;
+        let decoded = converter::decode(&*self.converter, args.clone())
+            .map_err(|e| UpdateError::SerializationError(e.to_string()))?;
+        validator(&decoded).map_err(UpdateError::Rejected)?;
+
+        let update_id = self.updates.next_id();
+        self.record(EventType::WorkflowExecutionUpdateAccepted {
+            update_id: update_id.clone(),
+            update_name: update_name.to_string(),
+            input: args,
+        })
+        .await
+        .map_err(|e| UpdateError::Custom(e.to_string()))?;
+        let result = handler(decoded)?;
+        let encoded = converter::encode(&*self.converter, result.clone())
+            .map_err(|e| UpdateError::SerializationError(e.to_string()))?;
+        self.record(EventType::WorkflowExecutionUpdateCompleted {
+            update_id,
+            result: encoded,
+        })
+        .await
+        .map_err(|e| UpdateError::Custom(e.to_string()))?;
+        self.signals.wake();
+        Ok(result)
+    }
+
+    /// Record a received signal and make it available to [`WorkflowContext::wait_for_signal`]
+    ///
+    /// The history keeps the encoded payload. A cancellation request is recorded and cancels the
+    /// root scope instead; a termination is left for the worker to record once it stopped the code.
+    pub(crate) async fn signal(&self, signal_name: String, input: serde_json::Value) -> Result<(), WorkflowError> {
+        if signal_name == TERMINATE_SIGNAL {
+            let reason = converter::decode(&*self.converter, input)?;
+            let reason = reason.as_str().map_or_else(|| reason.to_string(), str::to_string);
+            self.termination.lock().get_or_insert(reason);
+            self.terminated.cancel();
+            return Ok(());
+        }
+        if signal_name == CANCEL_REQUEST_SIGNAL {
+            self.record(EventType::WorkflowExecutionCancelRequested).await?;
+            self.cancellation.cancel();
+            return Ok(());
+        }
+        let decoded = converter::decode(&*self.converter, input.clone())?;
+        self.record(EventType::WorkflowExecutionSignaled {
+            signal_name: signal_name.clone(),
+            input,
+        })
+        .await?;
+        self.signals.deliver(&signal_name, decoded);
+        Ok(())
+    }
+}
+
+/// Cancels an activity attempt when the workflow stops waiting for it
+struct CancelOnDrop<'a> {
+    activities: &'a PendingActivities,
+    run_id: RunId,
+    activity_id: &'a ActivityId,
+}
+
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        self.activities.cancel(self.run_id, self.activity_id);
+    }
 }
 
 /// Workflow context - provides workflow execution environment
 #[derive(Clone)]
 pub struct WorkflowContext {
     execution: WorkflowExecution,
-    // Additional fields will be added as implementation progresses
+    runtime: Option<Arc<ExecutionRuntime>>,
+    /// Cancellation scope that activities, timers and signal waits started from this context belong to
+    scope: CancellationToken,
+}
+
+/// A cancellation scope, passed to the closure of [`WorkflowContext::cancellation_scope`]
+///
+/// Dereferences to a [`WorkflowContext`] whose activities, timers and signal waits belong to the
+/// scope. Cancelling the scope cancels them and every nested scope, but not the enclosing one.
+#[derive(Clone)]
+pub struct CancellationScope {
+    ctx: WorkflowContext,
+}
+
+impl CancellationScope {
+    /// Cancel the scope; waits in it fail with [`WorkflowError::Cancelled`] and timers end early
+    pub fn cancel(&self) {
+        self.ctx.scope.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.ctx.scope.is_cancelled()
+    }
+
+    /// Context bound to this scope, e.g. to move into a future
+    pub fn context(&self) -> WorkflowContext {
+        self.ctx.clone()
+    }
+}
+
+impl Deref for CancellationScope {
+    type Target = WorkflowContext;
+
+    fn deref(&self) -> &WorkflowContext {
+        &self.ctx
+    }
 }
 
 impl WorkflowContext {
-    /// Create a new workflow context
+    /// Create a new workflow context that is not attached to a worker
+    ///
+    /// Such a context can describe an execution but cannot schedule activities.
     pub fn new(execution: WorkflowExecution) -> Self {
-        Self { execution }
+        Self {
+            execution,
+            runtime: None,
+            scope: CancellationToken::new(),
+        }
+    }
+
+    pub(crate) fn attached(runtime: Arc<ExecutionRuntime>) -> Self {
+        Self {
+            execution: runtime.info.workflow_execution.clone(),
+            scope: runtime.cancellation.clone(),
+            runtime: Some(runtime),
+        }
     }
     
     /// Get workflow execution
     pub fn execution(&self) -> &WorkflowExecution {
         &self.execution
     }
+
+    /// Get workflow info (type and task queue), if attached to a worker
+    pub fn workflow_info(&self) -> Option<&WorkflowInfo> {
+        self.runtime.as_ref().map(|r| &r.info)
+    }
+
+    pub(crate) fn runtime(&self) -> Result<&Arc<ExecutionRuntime>, WorkflowError> {
+        self.runtime
+            .as_ref()
+            .ok_or_else(|| WorkflowError::Custom("workflow context is not attached to a worker".to_string()))
+    }
+
+    /// Whether the cancellation scope of this context has been cancelled
+    ///
+    /// The root scope is cancelled when the execution is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.scope.is_cancelled()
+    }
+
+    /// Run `f` in a new scope nested in this context's scope
+    ///
+    /// The scope is cancelled with [`CancellationScope::cancel`] or when an enclosing scope is
+    /// cancelled. Activities in flight are then asked to stop through their
+    /// [`ActivityContext`] and their calls fail with [`WorkflowError::Cancelled`].
+    pub async fn cancellation_scope<F, Fut, T>(&self, f: F) -> T
+    where
+        F: FnOnce(CancellationScope) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        f(CancellationScope {
+            ctx: self.with_scope(self.scope.child_token()),
+        })
+        .await
+    }
+
+    /// Run `f` in a scope that enclosing cancellations do not reach
+    ///
+    /// Meant for cleanup after the workflow or a scope has been cancelled.
+    pub async fn non_cancellable_scope<F, Fut, T>(&self, f: F) -> T
+    where
+        F: FnOnce(CancellationScope) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        f(CancellationScope { ctx: self.non_cancellable() }).await
+    }
+
+    pub(crate) fn non_cancellable(&self) -> Self {
+        self.with_scope(CancellationToken::new())
+    }
+
+    fn with_scope(&self, scope: CancellationToken) -> Self {
+        Self {
+            execution: self.execution.clone(),
+            runtime: self.runtime.clone(),
+            scope,
+        }
+    }
+
+    /// Wait for `duration` on the execution's clock
+    async fn wait(&self, duration: Duration) {
+        match self.runtime.as_ref().and_then(|runtime| runtime.clock.as_ref()) {
+            Some(clock) => clock.sleep(duration).await,
+            None => tokio::time::sleep(duration).await,
+        }
+    }
+
+    /// Await `future` for up to `duration` on the execution's clock; `None` if it took longer
+    pub(super) async fn within<T>(&self, duration: Duration, future: impl Future<Output = T>) -> Option<T> {
+        tokio::select! {
+            biased;
+            output = future => Some(output),
+            _ = self.wait(duration) => None,
+        }
+    }
+
+    /// Await `future` unless the scope is cancelled first; dropping it cancels what it waits for
+    pub(super) async fn unless_cancelled<T>(
+        &self,
+        future: impl Future<Output = Result<T, WorkflowError>>,
+    ) -> Result<T, WorkflowError> {
+        tokio::select! {
+            biased;
+            _ = self.scope.cancelled() => Err(WorkflowError::Cancelled),
+            result = future => result,
+        }
+    }
     
     /// Execute an activity
+    ///
+    /// Schedules the activity on the task queue, waits for a worker to complete it and retries
+    /// failed attempts according to the options' retry policy.
     pub async fn execute_activity<A: Activity>(
         &self,
         input: A::Input,
-        _options: ActivityOptions,
+        options: ActivityOptions,
     ) -> Result<A::Output, WorkflowError> {
-        // Placeholder implementation
-        // In actual implementation, this would:
-        // 1. Schedule activity task
-        // 2. Wait for completion
-        // 3. Return result
-        todo!("Activity execution not yet implemented")
+        let result = self
+            .execute_activity_value(A::name(), serde_json::to_value(input)?, &options)
+            .await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Execute an activity in this process, without a round trip through the task queue
+    ///
+    /// Meant for short operations. Attempts run inline, retried and timed out like
+    /// [`execute_activity`](Self::execute_activity) (the task queue and heartbeat options do not
+    /// apply), and only the final outcome is recorded as a marker event, which later executions
+    /// of the same history return instead of running the activity again. Timeouts run on the
+    /// execution's clock, like its timers.
+    pub async fn execute_local_activity<A: Activity>(
+        &self,
+        input: A::Input,
+        options: ActivityOptions,
+    ) -> Result<A::Output, WorkflowError> {
+        let seq = self.runtime.as_ref().map(|runtime| runtime.next_sequence());
+        if let (Some(runtime), Some(seq)) = (&self.runtime, seq) {
+            let recorded = runtime.history.lock().await.local_activity(seq).cloned();
+            match recorded {
+                Some(Ok(value)) => return Ok(serde_json::from_value(runtime.decode(value)?)?),
+                Some(Err(failure)) => return Err(WorkflowError::ActivityFailed(failure)),
+                None => {}
+            }
+        }
+
+        let activity_id = ActivityId::new(format!("{}-local-{}", A::name(), seq.unwrap_or_default()));
+        let attempts = self.run_local_attempts::<A>(input, activity_id, &options);
+        let result = self
+            .unless_cancelled(async {
+                Ok(match options.schedule_to_close_timeout {
+                    Some(timeout) => self
+                        .within(timeout, attempts)
+                        .await
+                        .unwrap_or_else(|| Err(format!("{}: schedule-to-close timeout", A::name()))),
+                    None => attempts.await,
+                })
+            })
+            .await?;
+
+        if let (Some(runtime), Some(seq)) = (&self.runtime, seq) {
+            let recorded = match &result {
+                Ok(value) => Ok(runtime.encode(value.clone())?),
+                Err(failure) => Err(failure.clone()),
+            };
+            runtime
+                .record(EventType::LocalActivityMarker {
+                    seq,
+                    activity_type: A::name().to_string(),
+                    result: recorded,
+                })
+                .await?;
+        }
+        match result {
+            Ok(value) => Ok(serde_json::from_value(value)?),
+            Err(failure) => Err(WorkflowError::ActivityFailed(failure)),
+        }
+    }
+
+    async fn run_local_attempts<A: Activity>(
+        &self,
+        input: A::Input,
+        activity_id: ActivityId,
+        options: &ActivityOptions,
+    ) -> Result<serde_json::Value, String> {
+        // Attempts after the first need their own copy of the input
+        let input = serde_json::to_value(input).map_err(|e| e.to_string())?;
+        let policy = options.retry_policy.clone().unwrap_or_else(RetryPolicy::no_retry);
+        let mut attempt = 1;
+        loop {
+            let ctx = ActivityContext::new(activity_id.clone(), self.execution.clone()).with_attempt(attempt);
+            let outcome = match serde_json::from_value::<A::Input>(input.clone()) {
+                Ok(input) => {
+                    let run = AssertUnwindSafe(A::execute(ctx, input))
+                        .catch_unwind()
+                        .map(|caught| caught.unwrap_or_else(|_| Err(ActivityError::ExecutionFailed("activity panicked".to_string()))));
+                    match options.start_to_close_timeout {
+                        Some(timeout) => self.within(timeout, run).await.unwrap_or(Err(ActivityError::Timeout)),
+                        None => run.await,
+                    }
+                }
+                Err(e) => Err(ActivityError::InvalidInput(e.to_string())),
+            };
+
+            let outcome = outcome
+                .and_then(|output| serde_json::to_value(output).map_err(|e| ActivityError::ExecutionFailed(e.to_string())));
+            match outcome {
+                Ok(value) => return Ok(value),
+                Err(error) => {
+                    if !policy.should_retry(attempt, &error) {
+                        return Err(format!("{}: {}", A::name(), error));
+                    }
+                    self.wait(policy.backoff(attempt)).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Untyped [`execute_activity`](Self::execute_activity), for callers that only know the activity type name
+    pub(crate) async fn execute_activity_value(
+        &self,
+        activity_type: &str,
+        input: serde_json::Value,
+        options: &ActivityOptions,
+    ) -> Result<serde_json::Value, WorkflowError> {
+        let Some(ttl) = options.memoize else {
+            return self.execute_activity_attempts(activity_type, input, options).await;
+        };
+        // The lookup is recorded, so executions of the same history reuse the same result or run the activity again
+        let runtime = self.runtime()?;
+        let seq = runtime.next_sequence();
+        let key = MemoKey::new(activity_type, &input);
+        let recorded = runtime.history.lock().await.activity_memo(seq).cloned();
+        let looked_up = recorded.is_none();
+        let reused = match recorded {
+            Some(reused) => reused,
+            None => {
+                let reused = runtime.memoized(&key).await;
+                runtime
+                    .record(EventType::ActivityMemoMarker {
+                        seq,
+                        activity_type: activity_type.to_string(),
+                        input_hash: key.input_hash.clone(),
+                        result: reused.clone(),
+                    })
+                    .await?;
+                reused
+            }
+        };
+        // Recorded and memoized results are encoded
+        if let Some(result) = reused {
+            return runtime.decode(result);
+        }
+        let result = self.execute_activity_attempts(activity_type, input, options).await?;
+        // Only the execution that looked the result up stores it, so replays do not extend its TTL
+        if looked_up {
+            runtime.memoize(&key, &result, ttl).await;
+        }
+        Ok(result)
+    }
+
+    async fn execute_activity_attempts(
+        &self,
+        activity_type: &str,
+        input: serde_json::Value,
+        options: &ActivityOptions,
+    ) -> Result<serde_json::Value, WorkflowError> {
+        let attempts = self.run_activity(activity_type, input, options);
+        self.unless_cancelled(async {
+            match options.schedule_to_close_timeout {
+                Some(timeout) => tokio::time::timeout(timeout, attempts)
+                    .await
+                    .map_err(|_| WorkflowError::Timeout(format!("activity {} schedule-to-close", activity_type)))?,
+                None => attempts.await,
+            }
+        })
+        .await
+    }
+
+    /// Close this run and start a new run of the same workflow with `input`
+    ///
+    /// Keeps event histories of long-running workflows bounded. The new run has the same workflow
+    /// ID and task queue and a new run ID. Return the result directly from the workflow:
+    /// `return ctx.continue_as_new(next_input);`. The error must reach the worker unchanged.
+    pub fn continue_as_new<I: Serialize, T>(&self, input: I) -> Result<T, WorkflowError> {
+        Err(WorkflowError::ContinuedAsNew(serde_json::to_value(input)?))
+    }
+
+    /// Whether the history of this run has gone over a soft limit of the worker's
+    /// [`HistoryLimits`](super::event::HistoryLimits) and the workflow should
+    /// [`continue_as_new`](Self::continue_as_new) before the hard limit terminates it
+    pub fn continue_as_new_suggested(&self) -> bool {
+        self.runtime.as_ref().is_some_and(|runtime| runtime.history_over_soft_limit())
+    }
+
+    /// Version of the workflow code to follow at a change point
+    ///
+    /// The first execution records `max_supported` as a version marker; later executions of the
+    /// same history return the recorded version, so code can branch on it and old runs keep
+    /// taking the old path. Fails if the recorded version is outside `min_supported..=max_supported`,
+    /// i.e. the code that handled it has been removed.
+    pub async fn get_version(&self, change_id: &str, min_supported: i32, max_supported: i32) -> Result<i32, WorkflowError> {
+        let runtime = self.runtime()?;
+        let recorded = runtime.history.lock().await.version_marker(change_id);
+        match recorded {
+            Some(version) if (min_supported..=max_supported).contains(&version) => Ok(version),
+            Some(version) => Err(WorkflowError::Custom(format!(
+                "version {} of change {} is outside the supported range {}..={}",
+                version, change_id, min_supported, max_supported
+            ))),
+            None => {
+                runtime
+                    .record(EventType::VersionMarker {
+                        change_id: change_id.to_string(),
+                        version: max_supported,
+                    })
+                    .await?;
+                Ok(max_supported)
+            }
+        }
+    }
+
+    /// Whether this run follows the patched code path of a change
+    ///
+    /// Shorthand for a two-version [`get_version`](Self::get_version): `true` unless the history
+    /// recorded the change as unpatched.
+    pub async fn patched(&self, change_id: &str) -> Result<bool, WorkflowError> {
+        Ok(self.get_version(change_id, DEFAULT_VERSION, 1).await? == 1)
+    }
+
+    /// Run a non-deterministic computation once and record its result
+    ///
+    /// Executions of the same history return the recorded value instead of calling `f` again,
+    /// keyed by the order of calls. Detached contexts just call `f`.
+    pub async fn side_effect<T, F>(&self, f: F) -> Result<T, WorkflowError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> T,
+    {
+        let Some(runtime) = &self.runtime else {
+            return Ok(f());
+        };
+        let seq = runtime.next_sequence();
+        let recorded = runtime.history.lock().await.side_effect(seq).cloned();
+        if let Some(value) = recorded {
+            return Ok(serde_json::from_value(runtime.decode(value)?)?);
+        }
+        let value = f();
+        runtime
+            .record(EventType::SideEffectRecorded {
+                seq,
+                value: runtime.encode(serde_json::to_value(&value)?)?,
+            })
+            .await?;
+        Ok(value)
+    }
+
+    /// Current time, recorded as a side effect; use instead of `Utc::now()`
+    pub async fn now(&self) -> Result<chrono::DateTime<chrono::Utc>, WorkflowError> {
+        self.side_effect(chrono::Utc::now).await
+    }
+
+    /// Random UUID, recorded as a side effect; use instead of `Uuid::new_v4()`
+    pub async fn random_uuid(&self) -> Result<uuid::Uuid, WorkflowError> {
+        self.side_effect(uuid::Uuid::new_v4).await
+    }
+
+    /// Random number in `[0, 1)`, recorded as a side effect
+    pub async fn random(&self) -> Result<f64, WorkflowError> {
+        self.side_effect(rand::random::<f64>).await
+    }
+
+    /// Add or replace search attributes of this execution
+    ///
+    /// Attributes not named in `attributes` keep their values.
+    pub async fn upsert_search_attributes(&self, attributes: SearchAttributes) -> Result<(), WorkflowError> {
+        self.record(EventType::UpsertSearchAttributes { attributes }).await
+    }
+
+    /// Start a saga whose steps register compensations to run if a later step fails
+    ///
+    /// Compensations run outside the context's cancellation scope, so they still run after the
+    /// workflow has been cancelled.
+    pub fn saga(&self) -> Saga {
+        Saga::new(self.clone())
+    }
+
+    /// Record an event if attached to a worker
+    pub(crate) async fn record(&self, event_type: EventType) -> Result<(), WorkflowError> {
+        self.runtime()?.record(event_type).await
+    }
+
+    async fn run_activity(
+        &self,
+        activity_type: &str,
+        input: serde_json::Value,
+        options: &ActivityOptions,
+    ) -> Result<serde_json::Value, WorkflowError> {
+        let runtime = self.runtime()?;
+        let activity_id = options
+            .activity_id
+            .clone()
+            .unwrap_or_else(|| ActivityId::new(format!("{}-{}", activity_type, runtime.next_sequence())));
+        let queue = options.task_queue.as_deref().unwrap_or(&runtime.info.task_queue);
+        let policy = options.retry_policy.clone().unwrap_or_else(RetryPolicy::no_retry);
+        let run_id = self.execution.run_id;
+        let input = converter::encode(&*runtime.converter, input)?;
+
+        runtime
+            .record(EventType::ActivityTaskScheduled {
+                activity_id: activity_id.clone(),
+                activity_type: activity_type.to_string(),
+                input: input.clone(),
+            })
+            .await?;
+
+        let mut attempt = 1;
+        loop {
+            // Attempts that finished before the run was taken over are not run again
+            let recorded = if runtime.has_recorded_history() {
+                runtime.history.lock().await.activity_attempt(&activity_id, attempt)
+            } else {
+                None
+            };
+            let replayed = recorded.is_some();
+            let outcome = match recorded {
+                Some(outcome) => outcome.map_err(|failure| recorded_failure(&failure)),
+                None => {
+                    // Held until the attempt's outcome is in
+                    let _slot = match &runtime.admission {
+                        Some(admission) => {
+                            Some(self.unless_cancelled(admission.admit_activity(&runtime.info.workflow_type)).await?)
+                        }
+                        None => None,
+                    };
+                    let completion = runtime.activities.register(run_id, activity_id.clone());
+                    runtime
+                        .task_queue
+                        .push(
+                            queue,
+                            Task::Activity(ActivityTask {
+                                activity_id: activity_id.clone(),
+                                activity_type: activity_type.to_string(),
+                                workflow_execution: self.execution.clone(),
+                                input: input.clone(),
+                                attempt,
+                                heartbeat_timeout: options.heartbeat_timeout,
+                                priority: options.priority.unwrap_or(runtime.priority),
+                                trace_context: telemetry::context_of(&tracing::Span::current()),
+                            }),
+                        )
+                        .await?;
+
+                    let _cancel = CancelOnDrop {
+                        activities: &runtime.activities,
+                        run_id,
+                        activity_id: &activity_id,
+                    };
+                    match options.start_to_close_timeout {
+                        Some(timeout) => tokio::time::timeout(timeout, completion)
+                            .await
+                            .unwrap_or(Ok(Err(ActivityError::Timeout)))
+                            .unwrap_or(Err(ActivityError::Cancelled)),
+                        None => completion.await.unwrap_or(Err(ActivityError::Cancelled)),
+                    }
+                }
+            };
+
+            match outcome {
+                Ok(result) => {
+                    let outbox = runtime.activities.take_staged(run_id, &activity_id);
+                    runtime
+                        .record_with_outbox(
+                            EventType::ActivityTaskCompleted {
+                                activity_id,
+                                result: result.clone(),
+                            },
+                            &outbox,
+                        )
+                        .await?;
+                    return converter::decode(&*runtime.converter, result);
+                }
+                Err(error) => {
+                    runtime
+                        .record(EventType::ActivityTaskFailed {
+                            activity_id: activity_id.clone(),
+                            failure: format!("attempt {}: {}", attempt, error),
+                        })
+                        .await?;
+                    if !policy.should_retry(attempt, &error) {
+                        return Err(WorkflowError::ActivityFailed(format!("{}: {}", activity_type, error)));
+                    }
+                    // The backoff after an attempt recorded before the takeover has passed
+                    if !(replayed && runtime.is_resuming()) {
+                        self.wait(policy.backoff(attempt)).await;
+                    }
+                    attempt += 1;
+                }
+            }
+        }
     }
     
+    /// Wait for the next signal of type `S`
+    ///
+    /// Signals are consumed in the order they were received. Signals that arrived before the
+    /// call are returned immediately. With a timeout, fails with [`WorkflowError::Timeout`] if
+    /// no signal arrived in time.
+    pub async fn wait_for_signal<S: Signal>(&self, timeout: Option<Duration>) -> Result<S, WorkflowError> {
+        let runtime = self.runtime()?;
+        let received = runtime.signals.receive(S::name());
+        let input = self
+            .unless_cancelled(async {
+                match timeout {
+                    Some(timeout) => self
+                        .within(timeout, received)
+                        .await
+                        .ok_or_else(|| WorkflowError::Timeout(format!("signal {}", S::name()))),
+                    None => Ok(received.await),
+                }
+            })
+            .await?;
+        Ok(serde_json::from_value(input)?)
+    }
+
+    /// Wait for the signal that arrived first among `signal_names`, returning its name and payload
+    pub(crate) async fn wait_for_any_signal(&self, signal_names: &[&str]) -> Result<(String, serde_json::Value), WorkflowError> {
+        let runtime = self.runtime()?;
+        self.unless_cancelled(async { Ok(runtime.signals.receive_any(signal_names).await) }).await
+    }
+
+    /// Handle every signal of type `S` with `handler` instead of queueing it for [`wait_for_signal`](Self::wait_for_signal)
+    ///
+    /// Signals already queued are passed to the handler right away. Handlers typically update
+    /// workflow state that [`await_condition`](Self::await_condition) waits on.
+    pub fn on_signal<S: Signal>(&self, handler: impl Fn(S) + Send + Sync + 'static) -> Result<(), WorkflowError> {
+        let handler: SignalHandler = Arc::new(move |input| match serde_json::from_value(input) {
+            Ok(signal) => handler(signal),
+            Err(e) => tracing::warn!(signal = S::name(), error = %e, "dropping signal with invalid payload"),
+        });
+        self.runtime()?.signals.set_handler(S::name(), handler);
+        Ok(())
+    }
+
+    /// Answer queries `Q` with `handler`, replacing any earlier handler
+    ///
+    /// Queries are answered from the in-memory state of the running execution without recording
+    /// events, so the handler must not change workflow state.
+    pub fn on_query<Q: Query>(&self, handler: impl Fn() -> Q::Result + Send + Sync + 'static) -> Result<(), WorkflowError> {
+        let handler: QueryHandler =
+            Arc::new(move || serde_json::to_value(handler()).map_err(|e| QueryError::SerializationError(e.to_string())));
+        self.runtime()?.queries.set_handler(Q::name(), handler);
+        Ok(())
+    }
+
+    /// Handle updates `U` with `handler` once `validator` accepted them, replacing any earlier handler
+    ///
+    /// Updates the validator rejects fail for the caller with [`UpdateError::Rejected`] and are not
+    /// recorded. The handler runs outside the workflow code, like a signal handler, and its result
+    /// is returned to the caller; it typically changes state that
+    /// [`await_condition`](Self::await_condition) waits on.
+    pub fn on_update<U: Update>(
+        &self,
+        validator: impl Fn(&U::Args) -> Result<(), String> + Send + Sync + 'static,
+        handler: impl Fn(U::Args) -> U::Result + Send + Sync + 'static,
+    ) -> Result<(), WorkflowError> {
+        let validator: UpdateValidator = Arc::new(move |args| {
+            let args = <U::Args as serde::Deserialize>::deserialize(args)
+                .map_err(|e| format!("invalid arguments: {}", e))?;
+            validator(&args)
+        });
+        let handler: UpdateHandler = Arc::new(move |args| {
+            let args = serde_json::from_value(args).map_err(|e| UpdateError::SerializationError(e.to_string()))?;
+            serde_json::to_value(handler(args)).map_err(|e| UpdateError::SerializationError(e.to_string()))
+        });
+        let runtime = self.runtime()?;
+        runtime.updates.set_handler(U::name(), validator, handler.clone());
+        for input in runtime.updates.take_deferred(U::name()) {
+            if let Err(e) = handler(input) {
+                tracing::error!(update = %U::name(), error = %e, "failed to apply replayed update");
+            }
+            runtime.signals.wake();
+        }
+        Ok(())
+    }
+
+    /// Wait until `condition` holds
+    ///
+    /// The condition is checked now and after every signal and update, so it should depend only on
+    /// state changed by [`on_signal`](Self::on_signal) and [`on_update`](Self::on_update) handlers.
+    /// Returns `false` if the timeout passed first. The outcome is recorded as a marker event,
+    /// which later executions of the same history return instead of waiting again.
+    pub async fn await_condition(
+        &self,
+        condition: impl FnMut() -> bool,
+        timeout: Option<Duration>,
+    ) -> Result<bool, WorkflowError> {
+        let runtime = self.runtime()?;
+        let seq = runtime.next_sequence();
+        if let Some(satisfied) = runtime.history.lock().await.condition(seq) {
+            return Ok(satisfied);
+        }
+
+        let satisfied = self
+            .unless_cancelled(async {
+                let wait = runtime.signals.wait_until(condition);
+                Ok(match timeout {
+                    Some(timeout) => self.within(timeout, wait).await.is_some(),
+                    None => {
+                        wait.await;
+                        true
+                    }
+                })
+            })
+            .await?;
+        runtime.record(EventType::ConditionMarker { seq, satisfied }).await?;
+        Ok(satisfied)
+    }
+
     /// Sleep for a duration
-    pub async fn sleep(&self, _duration: std::time::Duration) {
-        // Placeholder implementation
-        // In actual implementation, this would use a durable timer
-        todo!("Sleep not yet implemented")
+    ///
+    /// The timer is recorded in the history when the context is attached to a worker. Returns
+    /// early if the scope is cancelled; check [`is_cancelled`](Self::is_cancelled) afterwards.
+    pub async fn sleep(&self, duration: Duration) {
+        let Some(runtime) = &self.runtime else {
+            tokio::select! {
+                _ = self.wait(duration) => {}
+                _ = self.scope.cancelled() => {}
+            }
+            return;
+        };
+        let timer_id = format!("timer-{}", runtime.next_sequence());
+        // Replays leave timers to the paused clock, so they fire in the order they did
+        let recorded = if runtime.is_resuming() {
+            runtime.history.lock().await.timer(&timer_id)
+        } else {
+            None
+        };
+        let started = runtime
+            .record(EventType::TimerStarted {
+                timer_id: timer_id.clone(),
+                duration_ms: duration.as_millis() as u64,
+            })
+            .await;
+        if let Err(e) = started {
+            tracing::warn!(%timer_id, error = %e, "failed to record timer start");
+        }
+        let event = match recorded {
+            Some((_, Some(true))) => EventType::TimerFired { timer_id: timer_id.clone() },
+            Some((_, Some(false))) => EventType::TimerCancelled { timer_id: timer_id.clone() },
+            // A timer started before the run was taken over waits only for the rest of its duration
+            _ => {
+                let elapsed = recorded.and_then(|(started_at, _)| (chrono::Utc::now() - started_at).to_std().ok());
+                let remaining = duration.saturating_sub(elapsed.unwrap_or_default());
+                tokio::select! {
+                    _ = self.wait(remaining) => EventType::TimerFired { timer_id: timer_id.clone() },
+                    _ = self.scope.cancelled() => EventType::TimerCancelled { timer_id: timer_id.clone() },
+                }
+            }
+        };
+        if let Err(e) = runtime.record(event).await {
+            tracing::warn!(%timer_id, error = %e, "failed to record timer outcome");
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::temporal::storage::InMemoryStorage;
+    use crate::temporal::task_queue::InMemoryTaskQueue;
     use crate::temporal::WorkflowId;
 
+    /// Context attached to a runtime over `history`, without a worker
+    fn attached(history: EventHistory) -> WorkflowContext {
+        let execution = WorkflowExecution::new(WorkflowId::new("test"));
+        let runtime = ExecutionRuntime::new(
+            WorkflowInfo {
+                workflow_type: "test".to_string(),
+                workflow_execution: execution,
+                task_queue: "default".to_string(),
+            },
+            history,
+            Arc::new(InMemoryStorage::new()),
+            Arc::new(InMemoryTaskQueue::new()),
+            Arc::new(PendingActivities::default()),
+        );
+        WorkflowContext::attached(Arc::new(runtime))
+    }
+
+    #[tokio::test]
+    async fn test_get_version_records_and_respects_markers() {
+        let ctx = attached(EventHistory::new());
+        assert_eq!(ctx.get_version("new-step", DEFAULT_VERSION, 2).await.unwrap(), 2);
+        assert_eq!(ctx.get_version("new-step", DEFAULT_VERSION, 3).await.unwrap(), 2);
+
+        let mut history = EventHistory::new();
+        history.append(EventType::VersionMarker {
+            change_id: "new-step".to_string(),
+            version: 1,
+        });
+        let replayed = attached(history);
+        assert_eq!(replayed.get_version("new-step", 1, 2).await.unwrap(), 1);
+        assert!(replayed.get_version("new-step", 2, 2).await.is_err());
+        assert!(replayed.patched("other-change").await.unwrap());
+    }
+
+    static LOCAL_CALLS: AtomicU64 = AtomicU64::new(0);
+
+    /// Fails its first attempt
+    struct Validate;
+
+    impl Activity for Validate {
+        type Input = u32;
+        type Output = bool;
+
+        fn name() -> &'static str {
+            "validate"
+        }
+
+        async fn execute(ctx: ActivityContext, input: u32) -> Result<bool, ActivityError> {
+            LOCAL_CALLS.fetch_add(1, Ordering::SeqCst);
+            if ctx.attempt() == 1 {
+                return Err(ActivityError::TemporaryFailure("cold cache".to_string()));
+            }
+            Ok(input > 0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_activity_retries_inline_and_replays_marker() {
+        let options = ActivityOptions {
+            retry_policy: Some(RetryPolicy {
+                initial_interval: Duration::from_millis(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let ctx = attached(EventHistory::new());
+        assert!(ctx.execute_local_activity::<Validate>(3, options.clone()).await.unwrap());
+        assert_eq!(LOCAL_CALLS.load(Ordering::SeqCst), 2);
+
+        let history = ctx.runtime().unwrap().history.lock().await.clone();
+        assert_eq!(history.len(), 1);
+        let replayed = attached(history);
+        assert!(replayed.execute_local_activity::<Validate>(3, options).await.unwrap());
+        assert_eq!(LOCAL_CALLS.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_side_effects_are_replayed_from_history() {
+        let ctx = attached(EventHistory::new());
+        let now = ctx.now().await.unwrap();
+        let id = ctx.random_uuid().await.unwrap();
+        let roll = ctx.random().await.unwrap();
+        assert!((0.0..1.0).contains(&roll));
+
+        let history = ctx.runtime().unwrap().history.lock().await.clone();
+        assert_eq!(history.len(), 3);
+        let replayed = attached(history);
+        assert_eq!(replayed.now().await.unwrap(), now);
+        assert_eq!(replayed.random_uuid().await.unwrap(), id);
+        assert_eq!(replayed.random().await.unwrap(), roll);
+        assert_eq!(replayed.side_effect(|| 5).await.unwrap(), 5);
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Resume;
+
+    impl Signal for Resume {
+        fn name() -> &'static str {
+            "resume"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelling_scope_cancels_activity_in_flight() {
+        let ctx = attached(EventHistory::new());
+        let result = ctx
+            .cancellation_scope(|scope| async move {
+                let canceller = scope.clone();
+                let (result, ()) = tokio::join!(scope.execute_activity::<Validate>(1, ActivityOptions::default()), async move {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    canceller.cancel();
+                });
+                result
+            })
+            .await;
+        assert!(matches!(result, Err(WorkflowError::Cancelled)));
+
+        let runtime = ctx.runtime().unwrap();
+        let activity_id = ActivityId::new("validate-1");
+        assert!(runtime.activities.cancellation(ctx.execution.run_id, &activity_id).is_none());
+        assert!(!ctx.is_cancelled());
+        assert!(matches!(
+            ctx.wait_for_signal::<Resume>(Some(Duration::from_millis(1))).await,
+            Err(WorkflowError::Timeout(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_non_cancellable_scope_ignores_execution_cancellation() {
+        let ctx = attached(EventHistory::new());
+        ctx.runtime().unwrap().cancellation.cancel();
+        assert!(matches!(ctx.wait_for_signal::<Resume>(None).await, Err(WorkflowError::Cancelled)));
+        let nested = ctx.cancellation_scope(|scope| async move { scope.is_cancelled() }).await;
+        assert!(nested);
+
+        let cleanup = ctx
+            .non_cancellable_scope(|scope| async move {
+                scope.sleep(Duration::from_millis(1)).await;
+                scope.wait_for_signal::<Resume>(Some(Duration::from_millis(1))).await
+            })
+            .await;
+        assert!(matches!(cleanup, Err(WorkflowError::Timeout(_))));
+        let history = ctx.runtime().unwrap().history.lock().await.clone();
+        assert!(history.events().iter().any(|e| matches!(e.event_type, EventType::TimerFired { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_await_condition_wakes_on_handled_signal_and_replays() {
+        let ctx = attached(EventHistory::new());
+        let approved = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = approved.clone();
+        ctx.on_signal::<Resume>(move |Resume| flag.store(true, Ordering::SeqCst)).unwrap();
+
+        let runtime = ctx.runtime().unwrap().clone();
+        let signaller = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            runtime.signal("resume".to_string(), serde_json::json!(null)).await.unwrap();
+        });
+        let check = approved.clone();
+        assert!(ctx.await_condition(move || check.load(Ordering::SeqCst), None).await.unwrap());
+        signaller.await.unwrap();
+        assert!(!ctx.await_condition(|| false, Some(Duration::from_millis(5))).await.unwrap());
+
+        let history = ctx.runtime().unwrap().history.lock().await.clone();
+        let replayed = attached(history);
+        assert!(replayed.await_condition(|| false, None).await.unwrap());
+        assert!(!replayed.await_condition(|| true, None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_upserted_search_attributes_merge() {
+        let ctx = attached(EventHistory::new());
+        ctx.upsert_search_attributes(SearchAttributes::from([
+            ("Stage".to_string(), "reserved".into()),
+            ("Amount".to_string(), 10.into()),
+        ]))
+        .await
+        .unwrap();
+        ctx.upsert_search_attributes(SearchAttributes::from([("Stage".to_string(), "shipped".into())]))
+            .await
+            .unwrap();
+
+        let attributes = ctx.runtime().unwrap().history.lock().await.search_attributes();
+        assert_eq!(attributes["Stage"], "shipped".into());
+        assert_eq!(attributes["Amount"], 10.into());
+    }
+
     #[test]
     fn test_workflow_context_creation() {
         let workflow_id = WorkflowId::new("test");
@@ -74,6 +1369,6 @@ mod tests {
         let ctx = WorkflowContext::new(execution.clone());
         
         assert_eq!(ctx.execution(), &execution);
+        assert!(ctx.workflow_info().is_none());
     }
 }
-
@@ -0,0 +1,320 @@
+//! 时间跳跃测试环境 / Time-skipping test environment
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::temporal::clock::VirtualClock;
+use crate::temporal::error::SignalError;
+use crate::temporal::{
+    Activity, BatchActivity, ShutdownReport, Signal, StartWorkflowOptions, WorkerConfig, Workflow, WorkflowClient,
+    WorkflowError, WorkflowHandle, WorkflowId, WorkflowWorker,
+};
+
+/// 检查工作者是否空闲的真实时间间隔 / Real time between checks whether the worker is idle
+const SETTLE_INTERVAL: Duration = Duration::from_millis(5);
+
+/// 连续多少次检查空闲才视为已稳定 / Consecutive idle checks after which the worker counts as settled
+const SETTLED_AFTER: usize = 2;
+
+/// 在虚拟时钟上运行工作者的测试环境 / Test environment running a worker on a virtual clock
+///
+/// 工作流计时器（`ctx.sleep`、信号与条件超时、重试退避）使用环境的 [`VirtualClock`]，活动照常以真实时间运行。
+/// 等待结果时，只要没有活动在执行、也没有任务排队，时钟就直接跳到最早的计时器，因此 `ctx.sleep(30 天)`
+/// 立即结束，计时器按截止时间顺序触发。测试可用 [`advance`](Self::advance) 让一段虚拟时间流逝后检查中间状态。
+/// Workflow timers (`ctx.sleep`, signal and condition timeouts, retry backoffs) use the environment's
+/// [`VirtualClock`] while activities run in real time as usual. While a result is awaited the clock jumps straight to
+/// the earliest timer whenever no activity is executing and no task is queued, so `ctx.sleep(30 days)` ends at once
+/// and timers fire in the order of their deadlines. Tests let a stretch of virtual time pass with
+/// [`advance`](Self::advance) and inspect the state in between.
+pub struct TestWorkflowEnvironment {
+    worker: Arc<WorkflowWorker>,
+    clock: Arc<VirtualClock>,
+    run: Option<JoinHandle<Result<ShutdownReport, WorkflowError>>>,
+}
+
+impl TestWorkflowEnvironment {
+    /// 使用默认配置的工作者 / With a worker using the default config
+    ///
+    /// # Panics
+    ///
+    /// 不在 Tokio 运行时中时 / Outside a Tokio runtime
+    pub fn new() -> Self {
+        Self::with_config(WorkerConfig::default())
+    }
+
+    /// 启动使用 `config` 的工作者 / Start a worker using `config`
+    ///
+    /// # Panics
+    ///
+    /// 不在 Tokio 运行时中时 / Outside a Tokio runtime
+    pub fn with_config(config: WorkerConfig) -> Self {
+        let clock = Arc::new(VirtualClock::new());
+        let worker = Arc::new(WorkflowWorker::new(config).with_clock(clock.clone()));
+        let running = worker.clone();
+        Self {
+            worker,
+            clock,
+            run: Some(tokio::spawn(async move { running.run().await })),
+        }
+    }
+
+    pub fn register_workflow<W: Workflow>(&self) -> &Self {
+        self.worker.register_workflow::<W>();
+        self
+    }
+
+    pub fn register_activity<A: Activity>(&self) -> &Self {
+        self.worker.register_activity::<A>();
+        self
+    }
+
+    pub fn register_batch_activity<A: BatchActivity>(&self) -> &Self {
+        self.worker.register_batch_activity::<A>();
+        self
+    }
+
+    /// 环境中的工作者，例如用于注册拦截器或熔断器 / The environment's worker
+    pub fn worker(&self) -> &WorkflowWorker {
+        &self.worker
+    }
+
+    pub fn client(&self) -> WorkflowClient {
+        self.worker.client()
+    }
+
+    /// 启动工作流 / Start a workflow
+    pub async fn start_workflow<W: Workflow>(
+        &self,
+        input: W::Input,
+        options: StartWorkflowOptions,
+    ) -> Result<WorkflowHandle<W::Output>, WorkflowError> {
+        self.client().start_workflow::<W>(input, options).await
+    }
+
+    /// 向工作流发送信号 / Signal a workflow
+    pub async fn signal_workflow<S: Signal>(&self, workflow_id: &WorkflowId, signal: S) -> Result<(), SignalError> {
+        self.client().signal_workflow(workflow_id, signal).await
+    }
+
+    /// 环境创建以来流逝的虚拟时间 / Virtual time passed since the environment was created
+    pub fn elapsed(&self) -> Duration {
+        self.clock.elapsed()
+    }
+
+    /// 让 `duration` 的虚拟时间流逝，期间工作流照常运行 / Let `duration` of virtual time pass while workflows run
+    ///
+    /// 截止时间更早的计时器先触发，每个计时器触发后工作流先运行到再次等待；之后的计时器保持挂起。
+    /// Timers due earlier fire first, each letting the workflows run until they wait again; later ones stay pending.
+    pub async fn advance(&self, duration: Duration) {
+        let target = self.clock.elapsed() + duration;
+        self.settle().await;
+        while let Some(deadline) = self.clock.next_deadline().filter(|deadline| *deadline <= target) {
+            self.clock.advance_to(deadline);
+            self.settle().await;
+        }
+        self.clock.advance_to(target);
+        self.settle().await;
+    }
+
+    /// 等待工作流结果，空闲时跳到下一个计时器 / Await a workflow's result, skipping to the next timer whenever idle
+    ///
+    /// 工作流等待一个永远不会到来的信号时不会返回。
+    /// Does not return while the workflow waits for a signal that never comes.
+    pub async fn result<T: serde::de::DeserializeOwned>(&self, handle: &WorkflowHandle<T>) -> Result<T, WorkflowError> {
+        loop {
+            if let Some(result) = handle.try_result().await? {
+                return Ok(result);
+            }
+            self.settle().await;
+            if handle.try_result().await?.is_none()
+                && let Some(deadline) = self.clock.next_deadline()
+            {
+                self.clock.advance_to(deadline);
+            }
+        }
+    }
+
+    /// 等到工作者空闲：没有活动在执行，也没有任务排队 / Wait until the worker is idle: no activity executing and no task queued
+    async fn settle(&self) {
+        let mut idle = 0;
+        while idle < SETTLED_AFTER {
+            tokio::time::sleep(SETTLE_INTERVAL).await;
+            idle = if self.worker.is_idle().await { idle + 1 } else { 0 };
+        }
+    }
+
+    /// 停止工作者并等待其退出 / Stop the worker and wait for it to exit
+    pub async fn shutdown(mut self) -> Result<(), WorkflowError> {
+        self.worker.shutdown(Duration::ZERO);
+        match self.run.take() {
+            Some(run) => run
+                .await
+                .map_err(|e| WorkflowError::Custom(format!("worker task failed: {}", e)))?
+                .map(|_| ()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for TestWorkflowEnvironment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TestWorkflowEnvironment {
+    fn drop(&mut self) {
+        self.worker.shutdown(Duration::ZERO);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::event::EventType;
+    use crate::temporal::{ActivityContext, ActivityError, ActivityOptions, RetryPolicy, WorkflowContext};
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    struct Remind;
+
+    impl Activity for Remind {
+        type Input = u32;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "remind"
+        }
+
+        async fn execute(_ctx: ActivityContext, day: u32) -> Result<u32, ActivityError> {
+            Ok(day)
+        }
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Renewed;
+
+    impl Signal for Renewed {
+        fn name() -> &'static str {
+            "renewed"
+        }
+    }
+
+    /// 试用期：第 7 天提醒，之后等待续订至第 30 天 / Trial: reminds on day 7, then waits for renewal until day 30
+    struct Trial;
+
+    impl Workflow for Trial {
+        type Input = ();
+        type Output = bool;
+
+        fn name() -> &'static str {
+            "trial"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<bool, WorkflowError> {
+            ctx.sleep(7 * DAY).await;
+            ctx.execute_activity::<Remind>(7, ActivityOptions::default()).await?;
+            match ctx.wait_for_signal::<Renewed>(Some(23 * DAY)).await {
+                Ok(Renewed) => Ok(true),
+                Err(WorkflowError::Timeout(_)) => Ok(false),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    /// 真实时间里一小时才完成 / Takes an hour of real time
+    struct Stuck;
+
+    impl Activity for Stuck {
+        type Input = ();
+        type Output = ();
+
+        fn name() -> &'static str {
+            "stuck"
+        }
+
+        async fn execute(_ctx: ActivityContext, _: ()) -> Result<(), ActivityError> {
+            tokio::time::sleep(Duration::from_secs(60 * 60)).await;
+            Ok(())
+        }
+    }
+
+    /// 本地活动限时一天 / Gives a local activity a day
+    struct Deadline;
+
+    impl Workflow for Deadline {
+        type Input = ();
+        type Output = String;
+
+        fn name() -> &'static str {
+            "deadline"
+        }
+
+        async fn execute(ctx: WorkflowContext, _: ()) -> Result<String, WorkflowError> {
+            let options = ActivityOptions {
+                start_to_close_timeout: Some(DAY),
+                retry_policy: Some(RetryPolicy::no_retry()),
+                ..Default::default()
+            };
+            match ctx.execute_local_activity::<Stuck>((), options).await {
+                Err(WorkflowError::ActivityFailed(failure)) => Ok(failure),
+                other => Err(WorkflowError::Custom(format!("expected a timeout, got {:?}", other))),
+            }
+        }
+    }
+
+    fn environment() -> TestWorkflowEnvironment {
+        let env = TestWorkflowEnvironment::new();
+        env.register_workflow::<Trial>().register_activity::<Remind>();
+        env
+    }
+
+    #[tokio::test]
+    async fn test_long_timers_skip_ahead() {
+        let env = environment();
+        let real = std::time::Instant::now();
+        let handle = env.start_workflow::<Trial>((), StartWorkflowOptions::default()).await.unwrap();
+
+        assert!(!env.result(&handle).await.unwrap());
+        assert!(env.elapsed() >= 30 * DAY);
+        assert!(real.elapsed() < Duration::from_secs(5));
+        env.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_intermediate_state_and_signals() {
+        let env = environment();
+        let handle = env.start_workflow::<Trial>((), StartWorkflowOptions::default()).await.unwrap();
+        let reminded = |history: &crate::temporal::event::EventHistory| {
+            history
+                .events()
+                .iter()
+                .any(|event| matches!(event.event_type, EventType::ActivityTaskCompleted { .. }))
+        };
+
+        env.advance(3 * DAY).await;
+        assert!(!reminded(&handle.history().await.unwrap()));
+        env.advance(5 * DAY).await;
+        assert!(reminded(&handle.history().await.unwrap()));
+
+        env.signal_workflow(&handle.execution().workflow_id, Renewed).await.unwrap();
+        assert!(env.result(&handle).await.unwrap());
+        assert!(env.elapsed() < 9 * DAY);
+        env.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_local_activity_timeouts_skip_ahead() {
+        let env = environment();
+        env.register_workflow::<Deadline>();
+        let real = std::time::Instant::now();
+        let handle = env.start_workflow::<Deadline>((), StartWorkflowOptions::default()).await.unwrap();
+
+        assert_eq!(env.result(&handle).await.unwrap(), "stuck: Activity timeout");
+        assert!(env.elapsed() >= DAY);
+        assert!(real.elapsed() < Duration::from_secs(5));
+        env.shutdown().await.unwrap();
+    }
+}
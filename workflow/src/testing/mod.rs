@@ -0,0 +1,345 @@
+//! 工作流测试工具 / Workflow Testing Utilities
+//! 用记录下的事件历史回放工作流代码，在 CI 中发现破坏确定性的改动；在虚拟时钟上运行工作流
+//! Replays workflow code against recorded event histories, so CI catches changes that break determinism, and
+//! runs workflows on a virtual clock
+//!
+//! [`TestWorkflowEnvironment`] 让工作者的计时器使用虚拟时钟，长计时器无需真实等待。
+//! [`TestWorkflowEnvironment`] runs a worker whose timers use a virtual clock, so long timers take no real time.
+//!
+//! [`WorkflowReplayer`] 在暂停的虚拟时钟上运行工作流：计时器与重试退避立即到期，活动以历史中记录的结果完成，
+//! 信号在其之前的事件匹配后送达。工作流记录的每个事件都与历史逐一比对，第一个分歧以 [`ReplayError`] 报告，
+//! 其中带有历史中的事件 ID。
+//! [`WorkflowReplayer`] runs the workflow on a paused virtual clock: timers and retry backoffs elapse at once,
+//! activities complete with the outcome recorded in the history and signals arrive once the events before them
+//! have been matched. Every event the workflow records is compared with the history; the first divergence is
+//! reported as a [`ReplayError`] carrying the event ID in the history.
+//!
+//! ```no_run
+//! # use workflow::temporal::{HistoryExport, HistoryFormat, Workflow, WorkflowContext, WorkflowError};
+//! # struct OrderWorkflow;
+//! # impl Workflow for OrderWorkflow {
+//! #     type Input = ();
+//! #     type Output = ();
+//! #     fn name() -> &'static str { "order" }
+//! #     async fn execute(_ctx: WorkflowContext, _input: ()) -> Result<(), WorkflowError> { Ok(()) }
+//! # }
+//! use workflow::testing::WorkflowReplayer;
+//!
+//! let bytes = std::fs::read("tests/histories/order-7.json").unwrap();
+//! let export = HistoryExport::decode(HistoryFormat::Json, &bytes).unwrap();
+//! WorkflowReplayer::replay_export::<OrderWorkflow>(&export).unwrap();
+//! ```
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::dsl::DynamicWorkflow;
+use crate::temporal::event::{EventHistory, EventType};
+use crate::temporal::replay::{Replay, ReplayTaskQueue};
+use crate::temporal::session::SessionHost;
+use crate::temporal::storage::InMemoryStorage;
+use crate::temporal::worker::PendingActivities;
+use crate::temporal::workflow::ExecutionRuntime;
+use crate::temporal::{
+    HistoryExport, RunId, Workflow, WorkflowContext, WorkflowError, WorkflowExecution, WorkflowId, WorkflowInfo,
+};
+
+mod environment;
+
+pub use crate::temporal::ReplayError;
+pub use environment::TestWorkflowEnvironment;
+
+/// 虚拟时间内无进展即判定卡住 / Virtual time after which a replay that makes no progress is stuck
+///
+/// 时钟暂停时空闲的运行时会直接跳到下一个计时器，因此这不花费真实时间。
+/// With the clock paused an idle runtime jumps straight to the next timer, so this costs no real time.
+const STALLED_AFTER: Duration = Duration::from_secs(10 * 365 * 24 * 60 * 60);
+
+/// 工作流历史回放器 / Replays workflow code against recorded histories
+pub struct WorkflowReplayer;
+
+impl WorkflowReplayer {
+    /// 回放 `history`；未关闭的历史在其事件用尽后即视为成功 /
+    /// Replay `history`; a history that has not closed replays successfully once its events are used up
+    ///
+    /// 在独立线程上运行，可从同步或异步测试中调用 / Runs on its own thread, so it can be called from sync and async tests
+    pub fn replay<W: Workflow>(history: &EventHistory) -> Result<(), ReplayError> {
+        Self::replay_execution::<W>(WorkflowExecution::new(WorkflowId::new("replay")), history.clone())
+    }
+
+    /// 回放导出的运行，工作流看到其原始执行 / Replay an exported run, which the workflow sees under its original execution
+    pub fn replay_export<W: Workflow>(export: &HistoryExport) -> Result<(), ReplayError> {
+        Self::replay_execution::<W>(export.execution.clone(), export.history.clone())
+    }
+
+    /// 以导出的运行回放声明式工作流 / Replay a declarative workflow against an exported run
+    pub fn replay_dynamic(workflow: &DynamicWorkflow, export: &HistoryExport) -> Result<(), ReplayError> {
+        let (info, history, input) = started(workflow.name(), export.execution.clone(), export.history.clone())?;
+        let workflow = workflow.clone();
+        on_own_thread(run(info, history, move |ctx| async move { workflow.execute(ctx, input).await }))
+    }
+
+    fn replay_execution<W: Workflow>(execution: WorkflowExecution, history: EventHistory) -> Result<(), ReplayError> {
+        let (info, history, input) = started(W::name(), execution, history)?;
+        let input: W::Input = serde_json::from_value(input)
+            .map_err(|e| ReplayError::InvalidHistory(format!("start input does not decode: {}", e)))?;
+        on_own_thread(run(info, history, |ctx| async move {
+            let output = W::execute(ctx, input).await?;
+            Ok(serde_json::to_value(output)?)
+        }))
+    }
+}
+
+/// 从历史的启动事件取出运行信息与输入 / The run info and input from the start event of the history
+fn started(
+    expected: &str,
+    execution: WorkflowExecution,
+    history: EventHistory,
+) -> Result<(WorkflowInfo, EventHistory, serde_json::Value), ReplayError> {
+    let Some(EventType::WorkflowExecutionStarted { workflow_type, task_queue, input }) =
+        history.events().first().map(|event| event.event_type.clone())
+    else {
+        return Err(ReplayError::InvalidHistory("history does not begin with a start event".to_string()));
+    };
+    if workflow_type != expected {
+        return Err(ReplayError::InvalidHistory(format!(
+            "history is of workflow type {}, not {}",
+            workflow_type, expected
+        )));
+    }
+    let info = WorkflowInfo {
+        workflow_type,
+        workflow_execution: execution,
+        task_queue,
+    };
+    Ok((info, history, input))
+}
+
+fn on_own_thread(replay: impl Future<Output = Result<(), ReplayError>> + Send + 'static) -> Result<(), ReplayError> {
+    let replaying = std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .expect("failed to build replay runtime");
+        runtime.block_on(replay)
+    });
+    replaying.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+async fn run<F, Fut>(info: WorkflowInfo, history: EventHistory, execute: F) -> Result<(), ReplayError>
+where
+    F: FnOnce(WorkflowContext) -> Fut,
+    Fut: Future<Output = Result<serde_json::Value, WorkflowError>>,
+{
+    let activities = Arc::new(PendingActivities::default());
+    let replay = Arc::new(Replay::new(&history));
+    // Sessions of the replayed code open on a stand-in host; only their IDs are checked
+    let sessions = Arc::new(SessionHost::new(format!("{}@replay", info.task_queue), usize::MAX));
+    let runtime = Arc::new(
+        ExecutionRuntime::new(
+            info,
+            history.clone(),
+            Arc::new(InMemoryStorage::new()),
+            Arc::new(ReplayTaskQueue::new(history, activities.clone())),
+            activities,
+        )
+        .with_sessions(Some(sessions))
+        .replaying(replay.clone()),
+    );
+    runtime.deliver_replayed_inputs();
+
+    let result = tokio::select! {
+        biased;
+        _ = replay.finished() => return replay.outcome(),
+        result = execute(WorkflowContext::attached(runtime)) => result,
+        _ = tokio::time::sleep(STALLED_AFTER) => return replay.outcome(),
+    };
+    let closing = match result {
+        Ok(result) => EventType::WorkflowExecutionCompleted { result },
+        Err(WorkflowError::ContinuedAsNew(input)) => EventType::WorkflowExecutionContinuedAsNew {
+            new_run_id: RunId::generate(),
+            input,
+        },
+        Err(e) => EventType::WorkflowExecutionFailed { failure: e.to_string() },
+    };
+    replay.close(&closing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::{
+        Activity, ActivityContext, ActivityError, ActivityOptions, EventId, RetryPolicy, Signal, StartWorkflowOptions,
+        WorkerConfig, WorkflowWorker,
+    };
+
+    struct Charge;
+
+    impl Activity for Charge {
+        type Input = u32;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "charge"
+        }
+
+        async fn execute(ctx: ActivityContext, cents: u32) -> Result<u32, ActivityError> {
+            if ctx.attempt() < 2 {
+                return Err(ActivityError::TemporaryFailure("gateway busy".to_string()));
+            }
+            Ok(cents)
+        }
+    }
+
+    struct Refund;
+
+    impl Activity for Refund {
+        type Input = u32;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "refund"
+        }
+
+        async fn execute(_ctx: ActivityContext, cents: u32) -> Result<u32, ActivityError> {
+            Ok(cents)
+        }
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Ship;
+
+    impl Signal for Ship {
+        fn name() -> &'static str {
+            "ship"
+        }
+    }
+
+    fn retrying() -> ActivityOptions {
+        ActivityOptions {
+            retry_policy: Some(RetryPolicy {
+                initial_interval: Duration::from_millis(1),
+                ..RetryPolicy::default()
+            }),
+            ..ActivityOptions::default()
+        }
+    }
+
+    /// 收款、等待发货信号并在一天后完成 / Charges, waits for the ship signal and completes a day later
+    struct Order;
+
+    impl Workflow for Order {
+        type Input = u32;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "order"
+        }
+
+        async fn execute(ctx: WorkflowContext, cents: u32) -> Result<u32, WorkflowError> {
+            let discount = ctx.side_effect(|| 5).await?;
+            let charged = ctx.execute_activity::<Charge>(cents - discount, retrying()).await?;
+            ctx.wait_for_signal::<Ship>(None).await?;
+            ctx.sleep(Duration::from_millis(1)).await;
+            Ok(charged)
+        }
+    }
+
+    /// 同一类型的新版本，把收款换成了退款 / A new version of the same type that refunds instead of charging
+    struct ChangedOrder;
+
+    impl Workflow for ChangedOrder {
+        type Input = u32;
+        type Output = u32;
+
+        fn name() -> &'static str {
+            "order"
+        }
+
+        async fn execute(ctx: WorkflowContext, cents: u32) -> Result<u32, WorkflowError> {
+            let discount = ctx.side_effect(|| 5).await?;
+            ctx.execute_activity::<Refund>(cents - discount, retrying()).await
+        }
+    }
+
+    async fn recorded_order() -> EventHistory {
+        let worker = Arc::new(WorkflowWorker::new(WorkerConfig {
+            poll_timeout: Duration::from_millis(50),
+            ..WorkerConfig::default()
+        }));
+        worker.register_workflow::<Order>();
+        worker.register_activity::<Charge>();
+        let running = worker.clone();
+        let run = tokio::spawn(async move { running.run().await });
+
+        let client = worker.client();
+        let handle = client.start_workflow::<Order>(100, StartWorkflowOptions::default()).await.unwrap();
+        client.signal_workflow(&handle.execution().workflow_id, Ship).await.unwrap();
+        assert_eq!(handle.result().await.unwrap(), 95);
+        worker.shutdown(Duration::ZERO);
+        run.await.unwrap().unwrap();
+        handle.history().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_recorded_history_replays() {
+        let history = recorded_order().await;
+        WorkflowReplayer::replay::<Order>(&history).unwrap();
+
+        // 记录到一半的运行 / a run recorded halfway
+        let mut open = EventHistory::new();
+        for event in history.events().iter().take(4) {
+            open.add_event(event.clone());
+        }
+        WorkflowReplayer::replay::<Order>(&open).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_changed_code_is_reported_at_diverging_event() {
+        let history = recorded_order().await;
+        let charge = history
+            .events()
+            .iter()
+            .find(|event| matches!(event.event_type, EventType::ActivityTaskScheduled { .. }))
+            .unwrap()
+            .event_id;
+
+        match WorkflowReplayer::replay::<ChangedOrder>(&history) {
+            Err(ReplayError::Mismatch { event_id, expected, produced }) => {
+                assert_eq!(event_id, charge);
+                assert_eq!(expected, "ActivityTaskScheduled(charge, charge-2)");
+                assert_eq!(produced, "ActivityTaskScheduled(refund, refund-2)");
+            }
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_history_must_match_workflow_type() {
+        let mut history = EventHistory::new();
+        assert!(matches!(WorkflowReplayer::replay::<Order>(&history), Err(ReplayError::InvalidHistory(_))));
+        history.append(EventType::WorkflowExecutionStarted {
+            workflow_type: "invoice".to_string(),
+            task_queue: "default".to_string(),
+            input: serde_json::json!(100),
+        });
+        assert!(matches!(WorkflowReplayer::replay::<Order>(&history), Err(ReplayError::InvalidHistory(_))));
+
+        let mut started = EventHistory::new();
+        started.append(EventType::WorkflowExecutionStarted {
+            workflow_type: "order".to_string(),
+            task_queue: "default".to_string(),
+            input: serde_json::json!(100),
+        });
+        started.append(EventType::WorkflowExecutionCompleted { result: serde_json::json!(95) });
+        assert_eq!(
+            WorkflowReplayer::replay::<Order>(&started),
+            Err(ReplayError::UnexpectedEvent {
+                event_id: EventId(2),
+                produced: "SideEffectRecorded(1)".to_string(),
+            })
+        );
+    }
+}
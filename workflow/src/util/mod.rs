@@ -0,0 +1,8 @@
+//! # 通用工具模块 / Shared Utilities Module
+//!
+//! 本模块收纳各个子系统共用的小型基础设施，例如列表 API 的分页类型。
+//! This module hosts small pieces of infrastructure shared across subsystems, such as pagination for list APIs.
+
+pub mod pagination;
+
+pub use pagination::{Cursor, Page, PageRequest, PageToken, PageTokenCodec, PaginationError};
@@ -0,0 +1,387 @@
+//! # 分页模块 / Pagination Module
+//!
+//! 为列表 API（工作流列表、运行历史、审计日志）提供统一的键集分页。
+//! Unified keyset pagination shared by the list APIs (workflow listing, run history, audit log).
+//!
+//! `PageToken` 是经过 HMAC 签名的 [`Cursor`]（排序键、最后一个 ID、过滤条件哈希）的 base64 编码，
+//! 因此客户端既无法伪造令牌，也无法把为某个过滤条件签发的令牌用于另一个过滤条件。
+//! A `PageToken` is the base64 encoding of an HMAC-signed [`Cursor`] (sort key, last id, filter hash), so clients
+//! can neither forge tokens nor replay a token issued for one filter against a different filter.
+//!
+//! ```rust
+//! use workflow::util::pagination::{filter_hash, PageRequest, PageTokenCodec};
+//!
+//! let codec = PageTokenCodec::new(b"secret".to_vec());
+//! let rows: Vec<(String, String)> = (0..5).map(|i| (format!("2025-01-0{i}"), format!("wf-{i}"))).collect();
+//! let filter = filter_hash(&serde_json::json!({ "status": "failed" }));
+//!
+//! let first = codec.paginate(&rows, |r| (r.0.clone(), r.1.clone()), &filter, &PageRequest::new(2)).unwrap();
+//! assert_eq!(first.items.len(), 2);
+//! let second = codec
+//!     .paginate(&rows, |r| (r.0.clone(), r.1.clone()), &filter, &PageRequest::new(2).with_token(first.next_token.unwrap()))
+//!     .unwrap();
+//! assert_eq!(second.items[0].1, "wf-2");
+//! ```
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::OnceLock;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 默认页大小 / Default page size
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+/// 最大页大小 / Maximum page size
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// 分页结果 / A page of results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_token: Option<PageToken>,
+}
+
+impl<T> Page<T> {
+    /// 空页 / An empty, final page
+    pub fn empty() -> Self {
+        Self { items: Vec::new(), next_token: None }
+    }
+
+    /// 是否还有下一页 / Whether another page follows
+    pub fn has_more(&self) -> bool {
+        self.next_token.is_some()
+    }
+
+    /// 转换元素类型，保留令牌 / Map the items while keeping the token
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page { items: self.items.into_iter().map(f).collect(), next_token: self.next_token }
+    }
+}
+
+/// 不透明分页令牌 / Opaque page token
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PageToken(String);
+
+impl PageToken {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for PageToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<String> for PageToken {
+    fn from(s: String) -> Self {
+        PageToken(s)
+    }
+}
+
+impl From<&str> for PageToken {
+    fn from(s: &str) -> Self {
+        PageToken(s.to_string())
+    }
+}
+
+/// 分页游标 / Pagination cursor carried inside a token
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// 最后一条记录的排序键 / Sort key of the last returned item
+    pub sort_key: String,
+    /// 最后一条记录的 ID（排序键相同时的决胜键）/ Id of the last returned item (tie breaker)
+    pub last_id: String,
+    /// 签发令牌时的过滤条件哈希 / Hash of the filter the token was issued for
+    pub filter_hash: String,
+}
+
+impl Cursor {
+    /// 生成 SQL 键集分页子句 / Build a SQL keyset clause for this cursor
+    ///
+    /// 返回 `(sort_column, id_column) > ($n, $n+1)` 以及按顺序绑定的参数，
+    /// 供 Postgres 等适配器直接拼接到 `WHERE` 条件中（配合 `ORDER BY sort_column, id_column`）。
+    /// Returns `(sort_column, id_column) > ($n, $n+1)` plus the parameters to bind, for SQL adapters to append
+    /// to their `WHERE` clause (together with `ORDER BY sort_column, id_column`).
+    pub fn keyset_clause(&self, sort_column: &str, id_column: &str, first_param: usize) -> (String, Vec<String>) {
+        (
+            format!("({sort_column}, {id_column}) > (${}, ${})", first_param, first_param + 1),
+            vec![self.sort_key.clone(), self.last_id.clone()],
+        )
+    }
+}
+
+/// 分页请求 / Page request
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageRequest {
+    pub page_size: Option<usize>,
+    pub page_token: Option<PageToken>,
+}
+
+impl PageRequest {
+    pub fn new(page_size: usize) -> Self {
+        Self { page_size: Some(page_size), page_token: None }
+    }
+
+    pub fn with_token(mut self, token: PageToken) -> Self {
+        self.page_token = Some(token);
+        self
+    }
+
+    /// 实际使用的页大小（限制在 1..=MAX_PAGE_SIZE）/ Effective page size, clamped to 1..=MAX_PAGE_SIZE
+    pub fn effective_page_size(&self) -> usize {
+        self.page_size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+/// 分页错误 / Pagination errors
+///
+/// 三种错误都是调用方的问题，HTTP 层应映射为 400。
+/// All variants are caller errors; the HTTP layer maps them to 400.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PaginationError {
+    #[error("分页令牌格式无效 / Malformed page token")]
+    MalformedToken,
+
+    #[error("分页令牌签名无效 / Page token signature is invalid")]
+    TamperedToken,
+
+    #[error("分页令牌与当前过滤条件不匹配 / Page token was issued for a different filter")]
+    FilterMismatch,
+}
+
+/// 令牌编解码器 / Page token codec
+///
+/// 多实例部署时应使用相同的密钥，以便任一实例都能校验其他实例签发的令牌。
+/// Multi-instance deployments should share the key so any instance can verify tokens issued by another.
+#[derive(Clone)]
+pub struct PageTokenCodec {
+    key: Vec<u8>,
+}
+
+impl fmt::Debug for PageTokenCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PageTokenCodec").finish_non_exhaustive()
+    }
+}
+
+impl Default for PageTokenCodec {
+    /// 使用进程级随机密钥 / Use a random per-process key
+    fn default() -> Self {
+        static PROCESS_KEY: OnceLock<Vec<u8>> = OnceLock::new();
+        let key = PROCESS_KEY.get_or_init(|| {
+            let mut key = uuid::Uuid::new_v4().as_bytes().to_vec();
+            key.extend_from_slice(uuid::Uuid::new_v4().as_bytes());
+            key
+        });
+        Self::new(key.clone())
+    }
+}
+
+impl PageTokenCodec {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length")
+    }
+
+    /// 签发令牌 / Issue a token for a cursor
+    pub fn encode(&self, cursor: &Cursor) -> PageToken {
+        let payload = serde_json::to_vec(cursor).expect("cursor serializes");
+        let mut mac = self.mac();
+        mac.update(&payload);
+        let signature = mac.finalize().into_bytes();
+        PageToken(format!("{}.{}", URL_SAFE_NO_PAD.encode(&payload), URL_SAFE_NO_PAD.encode(signature)))
+    }
+
+    /// 校验并解析令牌 / Verify and decode a token issued for `expected_filter_hash`
+    pub fn decode(&self, token: &PageToken, expected_filter_hash: &str) -> Result<Cursor, PaginationError> {
+        let (payload, signature) = token.0.split_once('.').ok_or(PaginationError::MalformedToken)?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| PaginationError::MalformedToken)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| PaginationError::MalformedToken)?;
+
+        let mut mac = self.mac();
+        mac.update(&payload);
+        mac.verify_slice(&signature).map_err(|_| PaginationError::TamperedToken)?;
+
+        let cursor: Cursor = serde_json::from_slice(&payload).map_err(|_| PaginationError::MalformedToken)?;
+        if cursor.filter_hash != expected_filter_hash {
+            return Err(PaginationError::FilterMismatch);
+        }
+        Ok(cursor)
+    }
+
+    /// 对按 `(sort_key, id)` 升序排列的切片做键集分页 / Keyset-paginate a slice sorted ascending by `(sort_key, id)`
+    ///
+    /// 因为下一页从"严格大于上一页最后一条"处开始，翻页期间插入的新记录不会造成重复或遗漏。
+    /// 排序键按字符串比较，时间等字段应使用可字典序比较的格式（如 RFC 3339、补零数字）。
+    /// Each page starts strictly after the previous page's last item, so inserts between calls cause neither
+    /// duplicates nor skips. Sort keys compare as strings; use lexicographically ordered encodings
+    /// (RFC 3339, zero padded numbers) for timestamps and counters.
+    pub fn paginate<T: Clone>(
+        &self,
+        sorted: &[T],
+        key: impl Fn(&T) -> (String, String),
+        filter_hash: &str,
+        request: &PageRequest,
+    ) -> Result<Page<T>, PaginationError> {
+        let start = match &request.page_token {
+            Some(token) => {
+                let cursor = self.decode(token, filter_hash)?;
+                let after = (cursor.sort_key, cursor.last_id);
+                sorted.partition_point(|item| key(item) <= after)
+            }
+            None => 0,
+        };
+
+        let page_size = request.effective_page_size();
+        let end = (start + page_size).min(sorted.len());
+        let items = sorted[start..end].to_vec();
+        let next_token = match items.last() {
+            Some(last) if end < sorted.len() => {
+                let (sort_key, last_id) = key(last);
+                Some(self.encode(&Cursor { sort_key, last_id, filter_hash: filter_hash.to_string() }))
+            }
+            _ => None,
+        };
+        Ok(Page { items, next_token })
+    }
+}
+
+/// 计算过滤条件哈希 / Hash a filter for binding into page tokens
+///
+/// 先把过滤条件序列化为键有序的规范 JSON，因此字段顺序不同的等价过滤条件得到相同哈希。
+/// The filter is serialized to canonical (key-sorted) JSON first, so equivalent filters hash identically
+/// regardless of field order.
+pub fn filter_hash<F: Serialize + ?Sized>(filter: &F) -> String {
+    let value = serde_json::to_value(filter).unwrap_or(serde_json::Value::Null);
+    let mut canonical = String::new();
+    write_canonical(&value, &mut canonical);
+    format!("{:x}", Sha256::digest(canonical.as_bytes()))
+}
+
+fn write_canonical(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, k) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::String(k.clone()).to_string());
+                out.push(':');
+                write_canonical(&map[k], out);
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(items) => {
+            out.push('[');
+            for (i, v) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(v, out);
+            }
+            out.push(']');
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(ts: u32, id: &str) -> (String, String) {
+        (format!("{ts:010}"), id.to_string())
+    }
+
+    fn key(r: &(String, String)) -> (String, String) {
+        r.clone()
+    }
+
+    #[test]
+    fn test_keyset_paging_is_stable_under_concurrent_inserts() {
+        let codec = PageTokenCodec::new(b"k".to_vec());
+        let filter = filter_hash(&serde_json::json!({ "workflow_type": "Order" }));
+        let mut rows: Vec<_> = (0..10).map(|i| row(i * 10, &format!("wf-{i}"))).collect();
+        let original: Vec<_> = rows.clone();
+
+        let mut seen = Vec::new();
+        let mut request = PageRequest::new(3);
+        loop {
+            let page = codec.paginate(&rows, key, &filter, &request).unwrap();
+            seen.extend(page.items.iter().cloned());
+            // 翻页期间插入：一条在游标之前，一条在最后 / insert one row before the cursor and one at the end
+            rows.push(row(1, &format!("early-{}", seen.len())));
+            rows.push(row(1_000 + seen.len() as u32, &format!("late-{}", seen.len())));
+            rows.sort();
+            match page.next_token {
+                Some(token) => request = PageRequest::new(3).with_token(token),
+                None => break,
+            }
+        }
+
+        let mut ids: Vec<_> = seen.iter().map(|r| r.1.clone()).collect();
+        let before = ids.len();
+        ids.dedup();
+        assert_eq!(ids.len(), before, "no duplicates across pages");
+        for r in &original {
+            assert!(seen.contains(r), "row {r:?} was skipped");
+        }
+    }
+
+    #[test]
+    fn test_token_filter_mismatch_is_rejected() {
+        let codec = PageTokenCodec::new(b"k".to_vec());
+        let rows: Vec<_> = (0..5).map(|i| row(i, &format!("wf-{i}"))).collect();
+        let failed = filter_hash(&serde_json::json!({ "status": "failed", "type": "Order" }));
+        let reordered = filter_hash(&serde_json::json!({ "type": "Order", "status": "failed" }));
+        assert_eq!(failed, reordered);
+
+        let page = codec.paginate(&rows, key, &failed, &PageRequest::new(2)).unwrap();
+        let token = page.next_token.unwrap();
+        let other = filter_hash(&serde_json::json!({ "status": "completed", "type": "Order" }));
+        let err = codec.paginate(&rows, key, &other, &PageRequest::new(2).with_token(token)).unwrap_err();
+        assert_eq!(err, PaginationError::FilterMismatch);
+    }
+
+    #[test]
+    fn test_tampered_and_foreign_tokens_are_detected() {
+        let codec = PageTokenCodec::new(b"k".to_vec());
+        let filter = filter_hash(&serde_json::json!({}));
+        let token = codec.encode(&Cursor { sort_key: "a".into(), last_id: "wf-1".into(), filter_hash: filter.clone() });
+
+        // 改写游标内容但保留签名 / rewrite the cursor while keeping the signature
+        let (_, signature) = token.as_str().split_once('.').unwrap();
+        let forged_cursor = Cursor { sort_key: "a".into(), last_id: "wf-9".into(), filter_hash: filter.clone() };
+        let forged = PageToken::from(format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged_cursor).unwrap()),
+            signature
+        ));
+        assert_eq!(codec.decode(&forged, &filter).unwrap_err(), PaginationError::TamperedToken);
+
+        let foreign = PageTokenCodec::new(b"other".to_vec());
+        assert_eq!(foreign.decode(&token, &filter).unwrap_err(), PaginationError::TamperedToken);
+        assert_eq!(codec.decode(&PageToken::from("garbage"), &filter).unwrap_err(), PaginationError::MalformedToken);
+        assert_eq!(codec.decode(&token, &filter).unwrap().last_id, "wf-1");
+    }
+
+    #[test]
+    fn test_keyset_clause() {
+        let cursor = Cursor { sort_key: "2025-01-01".into(), last_id: "wf-1".into(), filter_hash: String::new() };
+        let (clause, params) = cursor.keyset_clause("start_time", "workflow_id", 3);
+        assert_eq!(clause, "(start_time, workflow_id) > ($3, $4)");
+        assert_eq!(params, vec!["2025-01-01".to_string(), "wf-1".to_string()]);
+    }
+}
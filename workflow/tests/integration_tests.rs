@@ -9,6 +9,11 @@ use axum::{Router, body::{Body, to_bytes}, http::{Request, StatusCode}};
 use tower::ServiceExt;
 use workflow::http::build_router;
 
+// 堆剖析需要采样分配器 / heap profiles need the sampling allocator
+#[cfg(feature = "diagnostics")]
+#[global_allocator]
+static ALLOCATOR: workflow::diagnostics::heap::SamplingAllocator = workflow::diagnostics::heap::SamplingAllocator::system();
+
 #[tokio::test]
 async fn test_jit_optimized_processor() {
     let mut processor = JITOptimizedProcessor::new(vec![1, 2, 3, 4, 5]);
@@ -143,3 +148,285 @@ async fn test_http_stats() {
     let v: serde_json::Value = serde_json::from_str(&s).unwrap();
     assert_eq!(v.get("version").and_then(|x| x.as_str()).unwrap(), workflow::VERSION);
 }
+
+#[tokio::test]
+async fn test_http_openapi_and_docs() {
+    let app: Router = build_router();
+    let response = app.clone().oneshot(Request::get("/openapi.json").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(v["paths"].get("/health").is_some());
+
+    let response = app.oneshot(Request::get("/docs/").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_http_auth_protects_api_routes() {
+    use workflow::http::auth::Authenticator;
+
+    let app: Router = workflow::http::build_router_with_auth(None, Authenticator::new().api_key("k3y", "ci"));
+    let response = app.clone().oneshot(Request::get("/health").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app.clone().oneshot(Request::get("/api/versions").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()["www-authenticate"], "Bearer");
+
+    let request = Request::get("/api/versions").header("x-api-key", "k3y").body(Body::empty()).unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_http_rbac_checks_roles_per_operation() {
+    use workflow::http::auth::Authenticator;
+    use workflow::middleware::rbac::{RbacPolicy, Role, RoleMapping};
+
+    let auth = Authenticator::new().api_key("view-key", "dashboard").api_key("admin-key", "oncall");
+    let policy = RbacPolicy::new(RoleMapping::new().api_key("dashboard", Role::Viewer).api_key("oncall", Role::Admin));
+    let app: Router = workflow::http::build_router_with_rbac(None, auth, policy);
+    let request = |path: &str, key: &str| Request::get(path).header("x-api-key", key).body(Body::empty()).unwrap();
+
+    let response = app.clone().oneshot(request("/api/v1/admin/read-only", "view-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.clone().oneshot(request("/api/v1/audit", "view-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    // 授权通过；未配置审计日志时路由不存在 / authorized; the route is absent without an audit log
+    let response = app.oneshot(request("/api/v1/audit", "admin-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_http_debug_routes_require_admin() {
+    use workflow::http::auth::Authenticator;
+    use workflow::middleware::rbac::{RbacPolicy, Role, RoleMapping};
+
+    let auth = Authenticator::new().api_key("view-key", "dashboard");
+    let policy = RbacPolicy::new(RoleMapping::new().api_key("dashboard", Role::Viewer));
+    let app: Router = workflow::http::build_router_with_rbac(None, auth, policy);
+    let request = |key: Option<&str>| {
+        let request = Request::get("/debug/pprof/profile?seconds=1");
+        match key {
+            Some(key) => request.header("x-api-key", key),
+            None => request,
+        }
+        .body(Body::empty())
+        .unwrap()
+    };
+
+    let response = app.clone().oneshot(request(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app.oneshot(request(Some("view-key"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_http_rate_limit_returns_429_with_retry_after() {
+    use workflow::middleware::rate_limit::{RateLimiter, TokenBucketConfig};
+
+    let limiter = std::sync::Arc::new(RateLimiter::new(TokenBucketConfig::per_minute(1)));
+    let app: Router = workflow::http::with_rate_limit(build_router(), limiter);
+    let request = |path: &str| Request::get(path).header("x-api-key", "client-a").body(Body::empty()).unwrap();
+
+    let response = app.clone().oneshot(request("/version")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.clone().oneshot(request("/version")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!(retry_after >= 59);
+    // 健康检查不限流 / health checks are not limited
+    let response = app.oneshot(request("/health")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_http_read_only_mode() {
+    use workflow::http::workflows::WorkflowApi;
+    use workflow::temporal::WorkflowWorker;
+
+    let worker = WorkflowWorker::default();
+    let api = WorkflowApi::from_worker(&worker);
+    let read_only = api.read_only().clone();
+    let app = workflow::http::build_router_with_workflows(api);
+    let toggle = |enabled: bool| {
+        Request::post("/admin/read-only")
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::json!({ "enabled": enabled }).to_string()))
+            .unwrap()
+    };
+    let post = |path: &str| {
+        Request::post(path)
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"workflow_type":"order","workflow_ids":["o-1"],"signal_name":"go"}"#))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(toggle(true)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(read_only.is_enabled());
+    // 工作者不再派发新的工作流任务 / the worker stops dispatching new workflow tasks
+    assert!(worker.is_dispatch_paused());
+
+    // 读请求仍然可用 / reads keep working
+    for path in ["/health", "/version", "/stats", "/admin/read-only", "/api/v1/workflows", "/api/v1/workflows/o-1"] {
+        let response = app.clone().oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap();
+        assert_ne!(response.status(), StatusCode::SERVICE_UNAVAILABLE, "{path} should stay readable");
+    }
+    // 就绪探针给出警告 / the readiness probe warns about the mode
+    let response = app.clone().oneshot(Request::get("/readyz").body(Body::empty()).unwrap()).await.unwrap();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["warnings"][0], "read-only mode: writes are rejected");
+    let response = app.clone().oneshot(post("/api/v1/workflows/o-1/query/total")).await.unwrap();
+    assert_ne!(response.status(), StatusCode::SERVICE_UNAVAILABLE, "queries should stay available");
+
+    // 写请求被拒绝 / writes are rejected with 503 + Retry-After
+    for path in [
+        "/api/v1/workflows",
+        "/api/v1/workflows/o-1/signal/go",
+        "/api/v1/workflows/o-1/update/add",
+        "/api/v1/workflows/o-1/cancel",
+        "/api/v1/workflows/o-1/history/import",
+        "/api/v1/batches",
+    ] {
+        let response = app.clone().oneshot(post(path)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE, "{path} should be rejected");
+        assert!(response.headers().contains_key("retry-after"));
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["code"], "READ_ONLY_MODE");
+    }
+
+    let response = app.clone().oneshot(toggle(false)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!read_only.is_enabled());
+    assert!(!worker.is_dispatch_paused());
+    let response = app.clone().oneshot(post("/api/v1/workflows/o-1/cancel")).await.unwrap();
+    assert_ne!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // 开关经持久化适配器保存，重启后恢复 / the switch survives a simulated restart
+    #[cfg(feature = "persistence")]
+    {
+        use std::sync::Arc;
+        use workflow::http::ReadOnlyMode;
+        use workflow::persistence::{InMemoryAdapter, PersistenceAdapter};
+
+        let adapter: Arc<dyn PersistenceAdapter> = Arc::new(InMemoryAdapter::new());
+        assert!(!read_only.restore(adapter.clone()).await.unwrap());
+        let response = app.clone().oneshot(toggle(true)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let restarted = ReadOnlyMode::new();
+        assert!(restarted.restore(adapter).await.unwrap());
+        assert!(restarted.is_enabled());
+    }
+}
+
+/// 剖析名额全进程共享，剖析测试依次运行 / The profile slot is process-wide, so profiling tests take turns
+#[cfg(feature = "diagnostics")]
+static PROFILING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[cfg(feature = "diagnostics")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_http_cpu_profile() {
+    use pprof::protos::Message;
+
+    let _profiling = PROFILING.lock().await;
+    // 合成负载 / synthetic load so the profiler has samples to take
+    let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let load = {
+        let stop = stop.clone();
+        std::thread::spawn(move || {
+            let mut x: u64 = 0;
+            while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+                x = std::hint::black_box(x.wrapping_mul(31).wrapping_add(7));
+            }
+        })
+    };
+
+    // 未认证且未显式开启时不提供 / not served without authentication unless opted in
+    let response = build_router().oneshot(Request::get("/debug/pprof/profile?seconds=1").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let app: Router = workflow::http::build_router_with_debug_endpoints(None);
+    let first = tokio::spawn(app.clone().oneshot(Request::get("/debug/pprof/profile?seconds=1").body(Body::empty()).unwrap()));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // 同时只能有一个剖析 / a second concurrent profile is refused
+    let response = app.oneshot(Request::get("/debug/pprof/profile?seconds=1").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let response = first.await.unwrap().unwrap();
+    stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    load.join().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert!(!body.is_empty());
+    let profile = pprof::protos::Profile::parse_from_bytes(&body).unwrap();
+    assert!(!profile.sample.is_empty());
+}
+
+#[cfg(feature = "diagnostics")]
+#[tokio::test]
+async fn test_http_heap_profile() {
+    use pprof::protos::Message;
+
+    // 每块都超过采样间隔，必被采样 / each block exceeds the sampling interval, so every one is sampled
+    let retained: Vec<Vec<u8>> = (0..8).map(|i| vec![i as u8; 2 * workflow::diagnostics::heap::SAMPLE_INTERVAL]).collect();
+    let app: Router = workflow::http::build_router_with_debug_endpoints(None);
+    let _profiling = PROFILING.lock().await;
+    let response = app.oneshot(Request::get("/debug/pprof/heap").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let profile = pprof::protos::Profile::parse_from_bytes(&body).unwrap();
+    let space = profile.string_table.iter().position(|s| s == "inuse_space").unwrap() as i64;
+    assert_eq!(profile.sample_type[1].type_, space);
+    let sampled: i64 = profile.sample.iter().map(|sample| sample.value[1]).sum();
+    assert!(sampled >= (retained.len() * 2 * workflow::diagnostics::heap::SAMPLE_INTERVAL) as i64);
+    drop(retained);
+}
+
+#[tokio::test]
+async fn test_http_api_versioning() {
+    let app: Router = build_router();
+
+    // 无前缀探活端点保持可用 / unprefixed probes keep working
+    for path in ["/health", "/livez", "/readyz", "/version", "/stats"] {
+        let response = app.clone().oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-api-version"], "v1");
+        assert!(!response.headers().contains_key("deprecation"));
+    }
+
+    // v1 路由 / v1 routes respond
+    let response = app.clone().oneshot(Request::get("/api/v1/admin/read-only").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-api-version"], "v1");
+    assert!(!response.headers().contains_key("deprecation"));
+
+    // 注册表中标记弃用的路由带 Deprecation/Sunset 头 / deprecated routes carry Deprecation + Sunset
+    let response = app.clone().oneshot(Request::get("/admin/read-only").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["deprecation"], "true");
+    assert!(response.headers().contains_key("sunset"));
+    assert!(response.headers()["link"].to_str().unwrap().contains("/api/v1/admin/read-only"));
+
+    // 未知版本返回 406 / unknown versions yield 406
+    let response = app.clone().oneshot(Request::get("/api/v9/workflows").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["supported_versions"], serde_json::json!(["v1"]));
+
+    // 机器可读的版本清单 / machine readable version listing
+    let response = app.oneshot(Request::get("/api/versions").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(v["current"], "v1");
+    let routes = v["routes"].as_array().unwrap();
+    assert!(routes.iter().any(|r| r["path"] == "/admin/read-only" && r["deprecated"]["sunset"].is_string()));
+    assert!(routes.iter().any(|r| r["path"] == "/api/v1/admin/read-only" && r["deprecated"].is_null()));
+}